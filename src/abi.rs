@@ -0,0 +1,275 @@
+//! Snapshots of the exported ABI of shared libraries.
+//!
+//! `export_snapshot()` records the dynamic symbols which other files can bind to,
+//! and `diff()` compares two snapshots, e.g. the one committed with the last release and the new build.
+//! The snapshots are serializable, so they can be stored as JSON next to the sources.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::*;
+
+/// An exported symbol in `AbiSnapshot`
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct AbiSymbol {
+    pub name: String,
+    /// the version, or `None` for the unversioned symbols
+    pub version: Option<String>,
+    /// the type as `readelf` shows(e.g. `FUNC`, `OBJECT`)
+    pub ty: String,
+    /// the binding as `readelf` shows(e.g. `GLOBAL`, `WEAK`)
+    pub bind: String,
+    pub size: Elf64Xword,
+}
+
+impl AbiSymbol {
+    /// `name@version`, or `name` for the unversioned symbols.
+    pub fn versioned_name(&self) -> String {
+        match &self.version {
+            Some(version) => format!("{}@{}", self.name, version),
+            None => self.name.clone(),
+        }
+    }
+
+    fn is_function(&self) -> bool {
+        self.ty == symbol::Type::Func.to_string() || self.ty == symbol::Type::GNUIFunc.to_string()
+    }
+}
+
+/// The exported ABI of a file, created by `export_snapshot()`
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize)]
+pub struct AbiSnapshot {
+    /// `DT_SONAME`
+    pub soname: Option<String>,
+    /// the versions defined in `.gnu.version_d`, except the base version
+    pub versions: Vec<String>,
+    /// sorted by the names and the versions
+    pub symbols: Vec<AbiSymbol>,
+}
+
+/// A change found by `diff()`
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum AbiChange {
+    SonameChanged {
+        old: Option<String>,
+        new: Option<String>,
+    },
+    Added {
+        symbol: AbiSymbol,
+    },
+    Removed {
+        symbol: AbiSymbol,
+    },
+    SizeChanged {
+        symbol: AbiSymbol,
+        old: Elf64Xword,
+    },
+    TypeChanged {
+        symbol: AbiSymbol,
+        old: String,
+    },
+}
+
+impl AbiChange {
+    /// whether the files linked against the old library may break with the new one.
+    ///
+    /// The size of functions isn't a part of the ABI,
+    /// while the size of objects is copied into the executables by copy relocations.
+    pub fn is_breaking(&self) -> bool {
+        match self {
+            Self::SonameChanged { .. } | Self::Added { .. } => false,
+            Self::Removed { .. } | Self::TypeChanged { .. } => true,
+            Self::SizeChanged { symbol, .. } => !symbol.is_function(),
+        }
+    }
+}
+
+impl fmt::Display for AbiChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = |name: &Option<String>| name.clone().unwrap_or_else(|| "(none)".to_string());
+        match self {
+            Self::SonameChanged { old, new } => {
+                write!(f, "soname: {} -> {}", name(old), name(new))
+            }
+            Self::Added { symbol } => write!(f, "{}: added", symbol.versioned_name()),
+            Self::Removed { symbol } => write!(f, "{}: removed", symbol.versioned_name()),
+            Self::SizeChanged { symbol, old } => write!(
+                f,
+                "{}: size {} -> {}",
+                symbol.versioned_name(),
+                old,
+                symbol.size
+            ),
+            Self::TypeChanged { symbol, old } => write!(
+                f,
+                "{}: type {} -> {}",
+                symbol.versioned_name(),
+                old,
+                symbol.ty
+            ),
+        }
+    }
+}
+
+/// record the symbols in `.dynsym` which other files can bind to.
+///
+/// Only the defined global and weak symbols with the default or protected visibility are recorded.
+/// A symbol exported in several versions(e.g. `sym@VER_1` and `sym@@VER_2`) is recorded for each version.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::{abi, builder};
+///
+/// let elf = builder::SharedObjectWriter::new()
+///     .function(builder::ExportedFunction::new("f", vec![0xc3]))
+///     .soname("libfoo.so.1")
+///     .build()
+///     .unwrap();
+///
+/// let snapshot = abi::export_snapshot(&elf);
+/// assert_eq!(Some("libfoo.so.1".to_string()), snapshot.soname);
+/// assert!(snapshot.symbols.iter().any(|sym| sym.name == "f" && sym.ty == "FUNC"));
+/// ```
+pub fn export_snapshot(elf: &file::ELF64) -> AbiSnapshot {
+    let versions = elf
+        .version_definitions()
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|def| !def.is_base())
+        .map(|def| def.name)
+        .collect();
+    let mut symbols: Vec<AbiSymbol> = analysis::dynamic_exports(elf)
+        .into_iter()
+        .map(|(name, version, sym)| AbiSymbol {
+            name,
+            version,
+            ty: sym.get_type().to_string(),
+            bind: sym.get_bind().to_string(),
+            size: sym.st_size,
+        })
+        .collect();
+    symbols.sort();
+    symbols.dedup();
+
+    AbiSnapshot {
+        soname: elf.soname(),
+        versions,
+        symbols,
+    }
+}
+
+/// compare the exported symbols of two snapshots, matching them by the names and the versions.
+///
+/// Moving a symbol to another version is reported as a removal and an addition,
+/// since the files linked against the old version can't bind to the new one.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::{abi, builder};
+///
+/// let old = builder::SharedObjectWriter::new()
+///     .function(builder::ExportedFunction::new("f", vec![0xc3]))
+///     .build()
+///     .unwrap();
+/// let new = builder::SharedObjectWriter::new()
+///     .function(builder::ExportedFunction::new("g", vec![0xc3]))
+///     .build()
+///     .unwrap();
+///
+/// let changes = abi::diff(&abi::export_snapshot(&old), &abi::export_snapshot(&new));
+/// let shown: Vec<String> = changes.iter().map(|c| c.to_string()).collect();
+/// assert_eq!(vec!["f: removed", "g: added"], shown);
+/// assert!(changes.iter().any(|c| c.is_breaking()));
+/// ```
+pub fn diff(old: &AbiSnapshot, new: &AbiSnapshot) -> Vec<AbiChange> {
+    let mut changes = Vec::new();
+    if old.soname != new.soname {
+        changes.push(AbiChange::SonameChanged {
+            old: old.soname.clone(),
+            new: new.soname.clone(),
+        });
+    }
+
+    let key = |sym: &AbiSymbol| (sym.name.clone(), sym.version.clone());
+    let old_symbols: BTreeMap<_, _> = old.symbols.iter().map(|sym| (key(sym), sym)).collect();
+    let new_symbols: BTreeMap<_, _> = new.symbols.iter().map(|sym| (key(sym), sym)).collect();
+
+    // 名前順に並べるため，削除と追加を一つのマップで回す
+    let mut keys: Vec<_> = old_symbols.keys().chain(new_symbols.keys()).collect();
+    keys.sort();
+    keys.dedup();
+    for k in keys {
+        match (old_symbols.get(k), new_symbols.get(k)) {
+            (Some(old), None) => changes.push(AbiChange::Removed {
+                symbol: (*old).clone(),
+            }),
+            (None, Some(new)) => changes.push(AbiChange::Added {
+                symbol: (*new).clone(),
+            }),
+            (Some(old), Some(new)) => {
+                if old.ty != new.ty {
+                    changes.push(AbiChange::TypeChanged {
+                        symbol: (*new).clone(),
+                        old: old.ty.clone(),
+                    });
+                } else if old.size != new.size {
+                    changes.push(AbiChange::SizeChanged {
+                        symbol: (*new).clone(),
+                        old: old.size,
+                    });
+                }
+            }
+            (None, None) => {}
+        }
+    }
+    changes
+}
+
+#[cfg(test)]
+mod abi_tests {
+    use super::*;
+
+    #[test]
+    fn diff_test() {
+        let build = |code: Vec<u8>, version: &str| {
+            let mut elf = builder::SharedObjectWriter::new()
+                .function(builder::ExportedFunction::new("f", code))
+                .function(builder::ExportedFunction::new("g", vec![0xc3]))
+                .build()
+                .unwrap();
+            let script = transform::VersionScript::parse(&format!(
+                "{} {{ global: f; g; local: *; }};",
+                version
+            ))
+            .unwrap();
+            transform::apply_version_script(&mut elf, &script).unwrap();
+            export_snapshot(&elf)
+        };
+        let old = build(vec![0xc3], "LIB_1");
+        assert_eq!(vec!["LIB_1".to_string()], old.versions);
+        assert_eq!(Some("LIB_1".to_string()), old.symbols[0].version);
+        assert!(diff(&old, &old).is_empty());
+
+        // 関数のサイズの変化はABIを壊さない
+        let grown = build(vec![0x90, 0xc3], "LIB_1");
+        let changes = diff(&old, &grown);
+        assert_eq!(1, changes.len());
+        assert_eq!("f@LIB_1: size 1 -> 2", changes[0].to_string());
+        assert!(!changes[0].is_breaking());
+
+        let moved = build(vec![0xc3], "LIB_2");
+        let changes = diff(&old, &moved);
+        assert_eq!(4, changes.len());
+        assert!(changes.iter().any(|c| c.is_breaking()));
+
+        let mut object = old.clone();
+        object.symbols[1].ty = symbol::Type::Object.to_string();
+        let changes = diff(&old, &object);
+        assert_eq!("g@LIB_1: type FUNC -> OBJECT", changes[0].to_string());
+        assert!(changes[0].is_breaking());
+    }
+}
@@ -0,0 +1,342 @@
+//! Static analyses on ELF files.
+
+use std::fmt;
+
+use crate::*;
+
+mod aliases;
+mod branch_protection;
+mod conflicts;
+mod data_layout;
+mod dep_tree;
+mod diff;
+mod dlopen;
+mod function_facts;
+mod got;
+mod ifunc;
+mod load;
+mod needed;
+mod packer;
+mod size;
+mod strings;
+mod x86_isa;
+mod xref;
+
+pub use aliases::*;
+pub use branch_protection::*;
+pub use conflicts::*;
+pub use data_layout::*;
+pub use dep_tree::*;
+pub use diff::*;
+pub use dlopen::*;
+pub use function_facts::*;
+pub use got::*;
+pub use ifunc::*;
+pub use load::*;
+pub use needed::*;
+pub use packer::*;
+pub use size::*;
+pub use strings::*;
+pub use x86_isa::*;
+pub use xref::*;
+
+/// A reason why an `ET_EXEC` can't be loaded at a random address
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PieBlocker {
+    /// an absolute address is embedded at `offset` by the relocation in `section`
+    AbsoluteRelocation {
+        section: String,
+        offset: Elf64Addr,
+        ty: Elf64Xword,
+    },
+    /// a copy relocation, which is generated only for non-PIC code
+    CopyRelocation { symbol: String },
+    /// the file has text relocations(`DT_TEXTREL`/`DF_TEXTREL`)
+    TextRel,
+}
+
+impl fmt::Display for PieBlocker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::AbsoluteRelocation {
+                section,
+                offset,
+                ty,
+            } => write!(
+                f,
+                "absolute relocation (type {}) at {:#x} in `{}`",
+                ty, offset, section
+            ),
+            Self::CopyRelocation { symbol } => write!(f, "copy relocation against `{}`", symbol),
+            Self::TextRel => write!(f, "text relocations"),
+        }
+    }
+}
+
+/// list the reasons which prevent converting the `ET_EXEC` into a position independent executable.
+///
+/// Absolute addresses are found only by relocations,
+/// so code linked without `--emit-relocs` may have absolute addresses which aren't reported.
+/// An empty list means the file can be converted by `ELF64::convert_to_pie()`.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::{analysis, builder};
+///
+/// // the generated code uses only PC-relative addressing
+/// let elf = builder::ExecutableWriter::new()
+///     .function(builder::ExportedFunction::new("_start", vec![0xc3]))
+///     .build()
+///     .unwrap();
+/// assert!(analysis::pie_blockers(&elf).is_empty());
+/// ```
+pub fn pie_blockers(elf: &file::ELF64) -> Vec<PieBlocker> {
+    let mut blockers = Vec::new();
+    if has_textrel(elf) {
+        blockers.push(PieBlocker::TextRel);
+    }
+
+    for sct in elf.sections.iter() {
+        let relas = match &sct.contents {
+            section::Contents64::RelaSymbols(relas)
+                if sct.header.get_type() == section::Type::Rela =>
+            {
+                relas
+            }
+            _ => continue,
+        };
+        // SHF_ALLOCなら動的リンカが処理するリロケーション，そうでなければ--emit-relocsで残されたもの
+        let dynamic = layout::is_alloc(&sct.header);
+
+        for rela in relas.iter() {
+            let absolute = match rela.get_type() {
+                relocation::R_X86_64_32 | relocation::R_X86_64_32S => true,
+                // 64bitの絶対アドレスは動的リロケーションであれば再配置できる
+                relocation::R_X86_64_64 => !dynamic,
+                relocation::R_X86_64_COPY => {
+                    blockers.push(PieBlocker::CopyRelocation {
+                        symbol: symbol_name(elf, &sct.header, rela.get_sym()).unwrap_or_default(),
+                    });
+                    false
+                }
+                _ => false,
+            };
+            if absolute {
+                blockers.push(PieBlocker::AbsoluteRelocation {
+                    section: sct.name.to_string(),
+                    offset: rela.get_offset(),
+                    ty: rela.get_type(),
+                });
+            }
+        }
+    }
+
+    blockers
+}
+
+/// A dynamic relocation which writes into a read-only segment
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TextRelocation {
+    /// the relocation section(`.rela.dyn`, etc.)
+    pub rela_section: String,
+    /// the section which contains the relocated address
+    pub section: Option<String>,
+    pub offset: Elf64Addr,
+    pub ty: Elf64Xword,
+    /// the referenced symbol (`None` for relocations without symbols)
+    pub symbol: Option<String>,
+}
+
+/// text relocations found by `text_relocations()`
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct TextRelReport {
+    /// `.dynamic` has `DT_TEXTREL` or `DF_TEXTREL`
+    pub dt_textrel: bool,
+    pub relocations: Vec<TextRelocation>,
+}
+
+impl TextRelReport {
+    /// whether the file has no text relocations
+    pub fn is_empty(&self) -> bool {
+        !self.dt_textrel && self.relocations.is_empty()
+    }
+}
+
+/// list the dynamic relocations whose targets are in non-writable `PT_LOAD` segments.
+///
+/// Such relocations make the dynamic linker write into the code,
+/// which is refused by hardened kernels(e.g. SELinux's `execmod`).
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::{analysis, builder};
+///
+/// let elf = builder::SharedObjectWriter::new()
+///     .function(builder::ExportedFunction::new("f", vec![0xc3]))
+///     .build()
+///     .unwrap();
+/// assert!(analysis::text_relocations(&elf).is_empty());
+/// ```
+pub fn text_relocations(elf: &file::ELF64) -> TextRelReport {
+    let writable: Elf64Word = segment::Flag::W.into();
+    let read_only = |addr: Elf64Addr| {
+        elf.segments
+            .iter()
+            .map(|sgt| &sgt.header)
+            .filter(|phdr| phdr.get_type() == segment::Type::Load)
+            .find(|phdr| {
+                phdr.p_vaddr <= addr
+                    && phdr
+                        .p_vaddr
+                        .checked_add(phdr.p_memsz)
+                        .is_none_or(|end| addr < end)
+            })
+            .is_some_and(|phdr| phdr.p_flags & writable == 0)
+    };
+
+    let index = elf.address_index();
+    let mut relocations = Vec::new();
+    for sct in elf.sections.iter() {
+        let relas = match &sct.contents {
+            section::Contents64::RelaSymbols(relas) if layout::is_alloc(&sct.header) => relas,
+            _ => continue,
+        };
+        for rela in relas.iter().filter(|rela| read_only(rela.get_offset())) {
+            let offset = rela.get_offset();
+            let owner = index.section_containing_vaddr(offset);
+            let symbol = match rela.get_sym() {
+                0 => None,
+                sym => symbol_name(elf, &sct.header, sym),
+            };
+            relocations.push(TextRelocation {
+                rela_section: sct.name.to_string(),
+                section: owner.map(|s| s.name.to_string()),
+                offset,
+                ty: rela.get_type(),
+                symbol,
+            });
+        }
+    }
+
+    TextRelReport {
+        dt_textrel: has_textrel(elf),
+        relocations,
+    }
+}
+
+/// whether `.dynamic` has `DT_TEXTREL` or `DF_TEXTREL`.
+pub(crate) fn has_textrel(elf: &file::ELF64) -> bool {
+    let entries = match elf
+        .first_section_by(|sct| sct.header.get_type() == section::Type::Dynamic)
+        .map(|sct| &sct.contents)
+    {
+        Some(section::Contents64::Dynamics(entries)) => entries,
+        _ => return false,
+    };
+
+    entries.iter().any(|ent| match ent.get_type() {
+        dynamic::EntryType::TextRel => true,
+        dynamic::EntryType::Flags => ent.d_un & dynamic::Flag::TextRel.to_bytes() != 0,
+        _ => false,
+    })
+}
+
+/// the name of the symbol referenced by a relocation section.
+fn symbol_name(elf: &file::ELF64, rela_shdr: &section::Shdr64, sym: Elf64Xword) -> Option<String> {
+    match &elf.sections.get(rela_shdr.sh_link as usize)?.contents {
+        section::Contents64::Symbols(syms) => {
+            syms.get(sym as usize).map(|s| s.symbol_name.to_string())
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod analysis_tests {
+    use super::*;
+
+    #[test]
+    fn text_relocations_test() {
+        let f = crate::builder::ExportedFunction::new(
+            "f",
+            vec![0x48, 0x8b, 0x05, 0x00, 0x00, 0x00, 0x00, 0xc3],
+        )
+        .relocation(3, "puts", relocation::R_X86_64_GOTPCREL, -4);
+        let mut elf = crate::builder::SharedObjectWriter::new()
+            .function(f)
+            .build()
+            .unwrap();
+        assert!(text_relocations(&elf).is_empty());
+
+        // .textの中を書き換えるリロケーションを追加する
+        let text = elf.first_section_by(|sct| sct.name == ".text").unwrap();
+        let mut rela = relocation::Rela64::default();
+        rela.set_info(1 << 32 | relocation::R_X86_64_64);
+        rela.set_offset(text.header.sh_addr + 3);
+        let rela_dyn = elf.first_shidx_by(|sct| sct.name == ".rela.dyn").unwrap();
+        if let section::Contents64::RelaSymbols(relas) = &mut elf.sections[rela_dyn].contents {
+            relas.push(rela);
+        }
+
+        let report = text_relocations(&elf);
+        assert!(!report.dt_textrel);
+        assert_eq!(1, report.relocations.len());
+        let found = &report.relocations[0];
+        assert_eq!(".rela.dyn", found.rela_section);
+        assert_eq!(Some(".text".to_string()), found.section);
+        assert_eq!(relocation::R_X86_64_64, found.ty);
+        assert_eq!(
+            symbol_name(&elf, &elf.sections[rela_dyn].header, 1),
+            found.symbol
+        );
+
+        // p_memszが溢れるLOADでも落ちない
+        for sgt in elf.segments.iter_mut() {
+            sgt.header.p_memsz = u64::MAX;
+        }
+        assert!(!text_relocations(&elf).relocations.is_empty());
+    }
+
+    #[test]
+    fn pie_blockers_test() {
+        // mov rax, [rip + puts@GOTPCREL]; ret
+        let f = crate::builder::ExportedFunction::new(
+            "f",
+            vec![0x48, 0x8b, 0x05, 0x00, 0x00, 0x00, 0x00, 0xc3],
+        )
+        .relocation(3, "puts", relocation::R_X86_64_GOTPCREL, -4);
+        let mut elf = crate::builder::SharedObjectWriter::new()
+            .function(f)
+            .build()
+            .unwrap();
+        assert!(pie_blockers(&elf).is_empty());
+
+        let dynamic = elf.first_shidx_by(|sct| sct.name == ".dynamic").unwrap();
+        if let section::Contents64::Dynamics(entries) = &mut elf.sections[dynamic].contents {
+            entries.insert(0, dynamic::Dyn64::new(dynamic::EntryType::TextRel, 0));
+        }
+        let mut abs = relocation::Rela64::default();
+        abs.set_info(relocation::R_X86_64_32S);
+        abs.set_offset(0x1000);
+        let mut copy = relocation::Rela64::default();
+        copy.set_info(1 << 32 | relocation::R_X86_64_COPY);
+        let rela = elf.first_shidx_by(|sct| sct.name == ".rela.dyn").unwrap();
+        let copied = symbol_name(&elf, &elf.sections[rela].header, 1).unwrap();
+        elf.sections[rela].contents = section::Contents64::RelaSymbols(vec![abs, copy]);
+
+        assert_eq!(
+            vec![
+                PieBlocker::TextRel,
+                PieBlocker::AbsoluteRelocation {
+                    section: ".rela.dyn".to_string(),
+                    offset: 0x1000,
+                    ty: relocation::R_X86_64_32S,
+                },
+                PieBlocker::CopyRelocation { symbol: copied },
+            ],
+            pie_blockers(&elf)
+        );
+    }
+}
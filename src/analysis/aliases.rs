@@ -0,0 +1,192 @@
+//! Symbols which are aliases of each other.
+
+use std::collections::BTreeMap;
+
+use crate::*;
+
+/// How `symbol_aliases()` chooses the preferred name of an alias group
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum AliasPolicy {
+    /// the strongest binding(global, weak, then local), then the fewest leading underscores,
+    /// then the shortest name
+    #[default]
+    Public,
+    /// the strongest binding, then the order in the symbol table
+    Strong,
+    /// the shortest name
+    Shortest,
+}
+
+/// A name in an alias group
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Alias {
+    pub name: String,
+    pub bind: symbol::Bind,
+    pub ty: symbol::Type,
+    /// the name is in `.dynsym`
+    pub dynamic: bool,
+}
+
+/// Symbols defined at the same place with the same size
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct AliasGroup {
+    pub shndx: Elf64Section,
+    pub value: Elf64Addr,
+    pub size: Elf64Xword,
+    /// the names ordered by the policy, so the first one is preferred
+    pub aliases: Vec<Alias>,
+}
+
+impl AliasGroup {
+    pub fn preferred(&self) -> &str {
+        &self.aliases[0].name
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.aliases.iter().map(|alias| alias.name.as_str())
+    }
+}
+
+/// The result of `symbol_aliases()`
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct SymbolAliases {
+    pub groups: Vec<AliasGroup>,
+}
+
+impl SymbolAliases {
+    /// the group which has the name.
+    pub fn group_of(&self, name: &str) -> Option<&AliasGroup> {
+        self.groups
+            .iter()
+            .find(|group| group.aliases.iter().any(|alias| alias.name == name))
+    }
+
+    /// the preferred name of the symbol, which is the name itself if it has no aliases.
+    pub fn canonical<'a>(&'a self, name: &'a str) -> &'a str {
+        self.group_of(name).map_or(name, |group| group.preferred())
+    }
+}
+
+/// group the defined symbols sharing `st_shndx`, `st_value` and `st_size`, like a strong symbol and its weak aliases.
+///
+/// The symbols in `.symtab` and `.dynsym` are merged by the name.
+/// Only functions, objects, IFUNCs and untyped symbols are grouped, and the groups of a single name are omitted.
+/// The names in each group are ordered by `AliasPolicy::Public`.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::{analysis, file, section, symbol};
+///
+/// let sym = |name: &str, bind| {
+///     let mut sym = symbol::Symbol64 {
+///         symbol_name: name.into(),
+///         st_value: 0x1000,
+///         st_size: 16,
+///         st_shndx: 1,
+///         ..Default::default()
+///     };
+///     sym.set_info(symbol::Type::Func, bind);
+///     sym
+/// };
+/// let mut elf = file::ELF64::default();
+/// elf.add_section(section::Section64::new(
+///     ".symtab".to_string(),
+///     section::ShdrPreparation64::default().ty(section::Type::SymTab),
+///     section::Contents64::Symbols(vec![
+///         symbol::Symbol64::new_null_symbol(),
+///         sym("__libc_read", symbol::Bind::Global),
+///         sym("read", symbol::Bind::Weak),
+///         sym("__read", symbol::Bind::Global),
+///     ]),
+/// ));
+///
+/// let aliases = analysis::symbol_aliases(&elf);
+/// assert_eq!(1, aliases.groups.len());
+/// assert_eq!("__read", aliases.canonical("read"));
+///
+/// let aliases = analysis::symbol_aliases_by(&elf, analysis::AliasPolicy::Shortest);
+/// assert_eq!("read", aliases.canonical("__libc_read"));
+/// ```
+pub fn symbol_aliases(elf: &file::ELF64) -> SymbolAliases {
+    symbol_aliases_by(elf, AliasPolicy::default())
+}
+
+/// `symbol_aliases()` with the policy choosing the preferred names.
+pub fn symbol_aliases_by(elf: &file::ELF64, policy: AliasPolicy) -> SymbolAliases {
+    let mut groups: BTreeMap<(Elf64Section, Elf64Addr, Elf64Xword), Vec<Alias>> = BTreeMap::new();
+    for sct in elf.sections.iter() {
+        let syms = match &sct.contents {
+            section::Contents64::Symbols(syms) => syms,
+            _ => continue,
+        };
+        let dynamic = sct.header.get_type() == section::Type::DynSym;
+        for sym in syms.iter() {
+            let ty = sym.get_type();
+            let groupable = matches!(
+                ty,
+                symbol::Type::NoType
+                    | symbol::Type::Object
+                    | symbol::Type::Func
+                    | symbol::Type::GNUIFunc
+            );
+            if !groupable || sym.st_shndx == 0 || sym.symbol_name.is_empty() {
+                continue;
+            }
+            let aliases = groups
+                .entry((sym.st_shndx, sym.st_value, sym.st_size))
+                .or_default();
+            match aliases
+                .iter_mut()
+                .find(|alias| alias.name == sym.symbol_name)
+            {
+                Some(alias) => alias.dynamic |= dynamic,
+                None => aliases.push(Alias {
+                    name: sym.symbol_name.to_string(),
+                    bind: sym.get_bind(),
+                    ty,
+                    dynamic,
+                }),
+            }
+        }
+    }
+
+    let groups = groups
+        .into_iter()
+        .filter(|(_, aliases)| aliases.len() > 1)
+        .map(|((shndx, value, size), mut aliases)| {
+            // sort_by_keyは安定なので，同順位はシンボルテーブルの順に残る
+            match policy {
+                AliasPolicy::Public => aliases.sort_by_key(|alias| {
+                    (
+                        bind_rank(alias.bind),
+                        leading_underscores(&alias.name),
+                        alias.name.len(),
+                    )
+                }),
+                AliasPolicy::Strong => aliases.sort_by_key(|alias| bind_rank(alias.bind)),
+                AliasPolicy::Shortest => aliases.sort_by_key(|alias| alias.name.len()),
+            }
+            AliasGroup {
+                shndx,
+                value,
+                size,
+                aliases,
+            }
+        })
+        .collect();
+    SymbolAliases { groups }
+}
+
+/// global < weak < the others(e.g. local)
+fn bind_rank(bind: symbol::Bind) -> u8 {
+    match bind {
+        symbol::Bind::Global | symbol::Bind::GNUUnique => 0,
+        symbol::Bind::Weak => 1,
+        _ => 2,
+    }
+}
+
+fn leading_underscores(name: &str) -> usize {
+    name.chars().take_while(|c| *c == '_').count()
+}
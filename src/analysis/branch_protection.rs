@@ -0,0 +1,308 @@
+//! AArch64 BTI/PAC protection of linked files.
+
+use crate::*;
+
+/// `EM_AARCH64`
+pub const EM_AARCH64: Elf64Half = 183;
+/// the PLT entries start with `bti c`
+pub const DT_AARCH64_BTI_PLT: Elf64Sxword = 0x70000001;
+/// the PLT entries authenticate the GOT entries
+pub const DT_AARCH64_PAC_PLT: Elf64Sxword = 0x70000003;
+
+const INSN_BTI_C: u32 = 0xd503245f;
+const INSN_BTI_JC: u32 = 0xd50324df;
+const INSN_AUTIA1716: u32 = 0xd503219f;
+const INSN_AUTIB1716: u32 = 0xd50321df;
+const INSN_BR_X17: u32 = 0xd61f0220;
+const INSN_NOP: u32 = 0xd503201f;
+
+/// A stub in `.plt`
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PltStub {
+    pub addr: Elf64Addr,
+    /// the GOT entry which the stub jumps through
+    pub got_entry: Option<Elf64Addr>,
+    /// the symbol of the `R_AARCH64_JUMP_SLOT` for the GOT entry, `None` for the PLT header
+    pub symbol: Option<String>,
+    /// the stub starts with `bti c`
+    pub bti: bool,
+    /// the stub authenticates the target with `autia1716`/`autib1716`
+    pub pac: bool,
+}
+
+/// The result of `branch_protection()`
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct BranchProtection {
+    /// `GNU_PROPERTY_AARCH64_FEATURE_1_BTI` is set
+    pub bti: bool,
+    /// `GNU_PROPERTY_AARCH64_FEATURE_1_PAC` is set
+    pub pac: bool,
+    /// `DT_AARCH64_BTI_PLT` exists
+    pub bti_plt: bool,
+    /// `DT_AARCH64_PAC_PLT` exists
+    pub pac_plt: bool,
+    pub plt: Vec<PltStub>,
+}
+
+impl BranchProtection {
+    /// whether the code and every PLT stub are valid targets only at landing pads.
+    pub fn is_bti_protected(&self) -> bool {
+        self.bti && self.plt.iter().all(|stub| stub.bti)
+    }
+
+    /// the PLT stubs which an indirect branch can enter at any instruction under BTI.
+    pub fn stubs_without_bti(&self) -> impl Iterator<Item = &PltStub> {
+        self.plt.iter().filter(|stub| !stub.bti)
+    }
+}
+
+/// report whether the AArch64 file and each of its PLT stubs are protected by BTI and PAC.
+///
+/// The file-level protection is read from `.note.gnu.property`.
+/// The PLT is split at each `br x17`, and each stub is matched with its `R_AARCH64_JUMP_SLOT`
+/// by decoding the `adrp x16`/`ldr x17, [x16, #imm]` which loads the GOT entry,
+/// so both the GNU ld and lld layouts are understood.
+/// Returns `None` if the file isn't for AArch64.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::{analysis, builder};
+///
+/// let elf = builder::SharedObjectWriter::new()
+///     .function(builder::ExportedFunction::new("f", vec![0xc3]))
+///     .build()
+///     .unwrap();
+/// // the file is for x86_64
+/// assert!(analysis::branch_protection(&elf).is_none());
+/// ```
+pub fn branch_protection(elf: &file::ELF64) -> Option<BranchProtection> {
+    if elf.ehdr.get_machine() != header::Machine::Any(EM_AARCH64) {
+        return None;
+    }
+    let mut report = BranchProtection::default();
+
+    let features = elf
+        .gnu_properties()
+        .and_then(|props| props.ok())
+        .and_then(|props| props.feature_1_and(section::GNU_PROPERTY_AARCH64_FEATURE_1_AND))
+        .unwrap_or(0);
+    report.bti = features & section::GNU_PROPERTY_AARCH64_FEATURE_1_BTI != 0;
+    report.pac = features & section::GNU_PROPERTY_AARCH64_FEATURE_1_PAC != 0;
+
+    if let Some(dynamic) =
+        elf.first_section_by(|sct| sct.header.get_type() == section::Type::Dynamic)
+    {
+        if let section::Contents64::Dynamics(entries) = &dynamic.contents {
+            report.bti_plt = entries.iter().any(|ent| ent.d_tag == DT_AARCH64_BTI_PLT);
+            report.pac_plt = entries.iter().any(|ent| ent.d_tag == DT_AARCH64_PAC_PLT);
+        }
+    }
+
+    if let Some(plt) = elf.first_section_by(|sct| sct.name == ".plt") {
+        let slots = jump_slots(elf);
+        report.plt = plt_stubs(plt)
+            .into_iter()
+            .map(|mut stub| {
+                stub.symbol = stub
+                    .got_entry
+                    .and_then(|entry| slots.iter().find(|(offset, _)| *offset == entry))
+                    .map(|(_, name)| name.clone());
+                stub
+            })
+            .collect();
+    }
+    Some(report)
+}
+
+/// split `.plt` into the stubs.
+fn plt_stubs(plt: &section::Section64) -> Vec<PltStub> {
+    let bytes = plt.to_le_bytes();
+    let insns: Vec<u32> = bytes
+        .chunks_exact(4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect();
+
+    let mut stubs = Vec::new();
+    let mut start = 0;
+    for (i, insn) in insns.iter().enumerate() {
+        if *insn != INSN_BR_X17 {
+            continue;
+        }
+        // 前のスタブの後ろのnopはパディング
+        while start < i && insns[start] == INSN_NOP {
+            start += 1;
+        }
+        let body = &insns[start..=i];
+        let first = body[0];
+        stubs.push(PltStub {
+            addr: plt.header.sh_addr + start as Elf64Addr * 4,
+            got_entry: got_entry(plt.header.sh_addr + start as Elf64Addr * 4, body),
+            symbol: None,
+            bti: first == INSN_BTI_C || first == INSN_BTI_JC,
+            pac: body
+                .iter()
+                .any(|insn| *insn == INSN_AUTIA1716 || *insn == INSN_AUTIB1716),
+        });
+        start = i + 1;
+    }
+    stubs
+}
+
+/// the address loaded by `adrp x16, page` and `ldr x17, [x16, #imm]`.
+fn got_entry(stub_addr: Elf64Addr, body: &[u32]) -> Option<Elf64Addr> {
+    let (adrp_idx, adrp) = body
+        .iter()
+        .enumerate()
+        .find(|(_, insn)| *insn & 0x9f00_001f == 0x9000_0010)?;
+    let ldr = body
+        .iter()
+        .find(|insn| *insn & 0xffc0_03ff == 0xf940_0211)?;
+
+    // immhi:immloは4KiBページ単位の符号付き21ビット
+    let immlo = ((adrp >> 29) & 0x3) as i64;
+    let immhi = ((adrp >> 5) & 0x7ffff) as i64;
+    let imm = ((immhi << 2 | immlo) << 43) >> 31;
+    let pc = stub_addr + adrp_idx as Elf64Addr * 4;
+    let page = (pc & !0xfff).wrapping_add(imm as Elf64Addr);
+
+    let offset = ((ldr >> 10) & 0xfff) as Elf64Addr * 8;
+    Some(page + offset)
+}
+
+/// the GOT entries and symbol names of the relocations in `.rela.plt`.
+fn jump_slots(elf: &file::ELF64) -> Vec<(Elf64Addr, String)> {
+    let rela_plt = match elf.first_section_by(|sct| sct.name == ".rela.plt") {
+        Some(sct) => sct,
+        None => return Vec::new(),
+    };
+    let (relas, syms) = match (
+        &rela_plt.contents,
+        elf.sections
+            .get(rela_plt.header.sh_link as usize)
+            .map(|s| &s.contents),
+    ) {
+        (section::Contents64::RelaSymbols(relas), Some(section::Contents64::Symbols(syms))) => {
+            (relas, syms)
+        }
+        _ => return Vec::new(),
+    };
+    relas
+        .iter()
+        .filter_map(|rela| {
+            let sym = syms.get(rela.get_sym() as usize)?;
+            Some((rela.get_offset(), sym.symbol_name.to_string()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod branch_protection_tests {
+    use super::*;
+
+    fn insns(insns: &[u32]) -> Vec<u8> {
+        insns
+            .iter()
+            .flat_map(|insn| insn.to_le_bytes().to_vec())
+            .collect()
+    }
+
+    #[test]
+    fn branch_protection_test() {
+        let mut elf = file::ELF64::default();
+        elf.ehdr.set_machine(header::Machine::Any(EM_AARCH64));
+
+        let mut note = vec![4, 0, 0, 0, 0x10, 0, 0, 0, 5, 0, 0, 0];
+        note.extend_from_slice(b"GNU\0");
+        note.extend_from_slice(&[0x00, 0x00, 0x00, 0xc0, 4, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0]);
+        elf.add_section(section::Section64::new(
+            ".note.gnu.property".to_string(),
+            section::ShdrPreparation64::default().ty(section::Type::Note),
+            section::Contents64::Raw(note),
+        ));
+
+        let adrp = 0xb000_0010; // adrp x16, (次のページ)
+        let ldr = |slot: u32| 0xf940_0211 | (slot << 10);
+        let plt = insns(&[
+            // PLT0 (bti c; stp x16, x30, [sp, #-16]!; ...)
+            INSN_BTI_C,
+            0xa9bf_7bf0,
+            adrp,
+            ldr(2),
+            0x9100_4210,
+            INSN_BR_X17,
+            INSN_NOP,
+            INSN_NOP,
+            // bti c + autia1716
+            INSN_BTI_C,
+            adrp,
+            ldr(3),
+            0x9100_6210,
+            INSN_AUTIA1716,
+            INSN_BR_X17,
+            // BTIなし
+            adrp,
+            ldr(4),
+            0x9100_8210,
+            INSN_BR_X17,
+        ]);
+        let mut plt = section::Section64::new(
+            ".plt".to_string(),
+            section::ShdrPreparation64::default().ty(section::Type::ProgBits),
+            section::Contents64::Raw(plt),
+        );
+        plt.header.sh_addr = 0x1000;
+        elf.add_section(plt);
+
+        let syms = ["", "foo", "bar"]
+            .iter()
+            .map(|name| symbol::Symbol64 {
+                symbol_name: (*name).into(),
+                ..Default::default()
+            })
+            .collect();
+        elf.add_section(section::Section64::new(
+            ".dynsym".to_string(),
+            section::ShdrPreparation64::default().ty(section::Type::DynSym),
+            section::Contents64::Symbols(syms),
+        ));
+        // 末尾は.shstrtab
+        let dynsym = elf.sections.len() - 2;
+        let relas = [(0x2018, 1), (0x2020, 2)]
+            .iter()
+            .map(|(offset, sym)| {
+                let mut rela = relocation::Rela64::default();
+                rela.set_offset(*offset);
+                rela.set_info(sym << 32 | 1026);
+                rela
+            })
+            .collect();
+        elf.add_section(section::Section64::new(
+            ".rela.plt".to_string(),
+            section::ShdrPreparation64::default()
+                .ty(section::Type::Rela)
+                .link(dynsym as Elf64Word),
+            section::Contents64::RelaSymbols(relas),
+        ));
+
+        let report = branch_protection(&elf).unwrap();
+        assert!(report.bti && !report.pac);
+        assert!(!report.bti_plt);
+        let stubs: Vec<(Elf64Addr, Option<&str>, bool, bool)> = report
+            .plt
+            .iter()
+            .map(|s| (s.addr, s.symbol.as_deref(), s.bti, s.pac))
+            .collect();
+        assert_eq!(
+            vec![
+                (0x1000, None, true, false),
+                (0x1020, Some("foo"), true, true),
+                (0x1038, Some("bar"), false, false),
+            ],
+            stubs
+        );
+        assert_eq!(Some(0x2010), report.plt[0].got_entry);
+        assert!(!report.is_bti_protected());
+        assert_eq!(1, report.stubs_without_bti().count());
+    }
+}
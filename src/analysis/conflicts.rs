@@ -0,0 +1,259 @@
+//! Symbols defined by several files in a process, which interpose each other.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::PathBuf;
+
+use super::{DepNode, DepStatus, DepTree};
+use crate::*;
+
+/// the symbols which the linker defines in every file
+const LINKER_DEFINED: [&str; 5] = ["_init", "_fini", "_edata", "_end", "__bss_start"];
+
+/// A definition of a conflicting symbol
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SymbolProvider {
+    /// the name of the node in `DepTree`
+    pub library: String,
+    pub path: PathBuf,
+    pub bind: symbol::Bind,
+    pub ty: symbol::Type,
+    pub size: Elf64Xword,
+}
+
+/// A symbol exported by several files
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SymbolConflict {
+    pub name: String,
+    /// the version, or `None` for the unversioned symbols
+    pub version: Option<String>,
+    /// the definitions in the load order. the dynamic linker binds references to the first one.
+    pub providers: Vec<SymbolProvider>,
+}
+
+impl SymbolConflict {
+    /// the definition which the dynamic linker picks.
+    pub fn chosen(&self) -> &SymbolProvider {
+        &self.providers[0]
+    }
+
+    /// whether the definitions differ in size or type, which breaks the interposed callers.
+    pub fn is_incompatible(&self) -> bool {
+        self.providers
+            .iter()
+            .any(|p| p.size != self.chosen().size || p.ty != self.chosen().ty)
+    }
+}
+
+/// list the symbols exported by more than one file in the tree.
+///
+/// The files are ordered breadth-first like the global scope of `ld.so`,
+/// which binds each reference to the first definition regardless of `STB_WEAK`.
+/// A definition in the root file interposes all the libraries.
+/// The versioned symbols conflict only with the same version,
+/// and the symbols defined by the linker(`_init`, `_end`, etc.) are ignored.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::{analysis, builder};
+///
+/// let f = || builder::ExportedFunction::new("log_message", vec![0xc3]);
+/// let app = builder::SharedObjectWriter::new()
+///     .function(builder::ExportedFunction::new("main", vec![0xc3]))
+///     .needed("liba.so")
+///     .needed("libb.so")
+///     .build()
+///     .unwrap();
+/// let liba = builder::SharedObjectWriter::new().function(f()).build().unwrap();
+/// let libb = builder::SharedObjectWriter::new().function(f()).build().unwrap();
+///
+/// let root = std::env::temp_dir().join(format!("elf_utilities_conflicts_doctest_{}", std::process::id()));
+/// std::fs::create_dir_all(root.join("lib")).unwrap();
+/// std::fs::write(root.join("lib/app.so"), app.to_le_bytes()).unwrap();
+/// std::fs::write(root.join("lib/liba.so"), liba.to_le_bytes()).unwrap();
+/// std::fs::write(root.join("lib/libb.so"), libb.to_le_bytes()).unwrap();
+///
+/// let config = analysis::SearchConfig::new().root(&root).default_dirs(&["/lib"]);
+/// let tree = analysis::dependency_tree("/lib/app.so", &config).unwrap();
+/// let conflicts = analysis::symbol_conflicts(&tree);
+///
+/// assert_eq!(1, conflicts.len());
+/// assert_eq!("log_message", conflicts[0].name);
+/// // 幅優先で先に読み込まれたliba.soが選ばれる
+/// assert_eq!("liba.so", conflicts[0].chosen().library);
+/// # std::fs::remove_dir_all(&root).unwrap();
+/// ```
+pub fn symbol_conflicts(tree: &DepTree) -> Vec<SymbolConflict> {
+    let mut definitions: HashMap<(String, Option<String>), Vec<SymbolProvider>> = HashMap::new();
+    let mut order = Vec::new();
+    for node in load_order(tree) {
+        let path = match &node.path {
+            Some(path) => path,
+            None => continue,
+        };
+        let elf = match parser::parse_elf(&path.to_string_lossy()) {
+            Ok(file::ELF::ELF64(elf)) => elf,
+            _ => continue,
+        };
+        for (key, provider) in exported_symbols(&elf, &node.name, path) {
+            let providers = definitions.entry(key.clone()).or_default();
+            if providers.is_empty() {
+                order.push(key);
+            }
+            providers.push(provider);
+        }
+    }
+
+    order
+        .into_iter()
+        .filter_map(|key| {
+            let providers = definitions.remove(&key)?;
+            if providers.len() < 2 {
+                return None;
+            }
+            Some(SymbolConflict {
+                name: key.0,
+                version: key.1,
+                providers,
+            })
+        })
+        .collect()
+}
+
+/// the loaded nodes in the breadth-first order, each file once.
+fn load_order(tree: &DepTree) -> Vec<&DepNode> {
+    let mut loaded = HashMap::new();
+    for node in tree.nodes() {
+        if node.status == DepStatus::Loaded {
+            if let Some(path) = &node.path {
+                loaded.insert(path, node);
+            }
+        }
+    }
+
+    let mut order = Vec::new();
+    let mut visited = HashSet::new();
+    let mut queue = VecDeque::new();
+    queue.push_back(&tree.root);
+    while let Some(node) = queue.pop_front() {
+        let path = match &node.path {
+            Some(path) => path,
+            None => continue,
+        };
+        if !visited.insert(path) {
+            continue;
+        }
+        // AlreadyLoadedやCycleの依存は，同じファイルのLoadedなノードが持つ
+        let node = loaded.get(path).copied().unwrap_or(node);
+        order.push(node);
+        queue.extend(node.children.iter());
+    }
+    order
+}
+
+fn exported_symbols(
+    elf: &file::ELF64,
+    library: &str,
+    path: &std::path::Path,
+) -> Vec<((String, Option<String>), SymbolProvider)> {
+    dynamic_exports(elf)
+        .into_iter()
+        .filter(|(name, _, _)| !LINKER_DEFINED.contains(&name.as_str()))
+        .map(|(name, version, sym)| {
+            (
+                (name, version),
+                SymbolProvider {
+                    library: library.to_string(),
+                    path: path.to_path_buf(),
+                    bind: sym.get_bind(),
+                    ty: sym.get_type(),
+                    size: sym.st_size,
+                },
+            )
+        })
+        .collect()
+}
+
+/// the symbols in `.dynsym` which other files can bind to, with their versions.
+pub(crate) fn dynamic_exports(
+    elf: &file::ELF64,
+) -> Vec<(String, Option<String>, &symbol::Symbol64)> {
+    let syms = match elf.dynsym() {
+        Some(dynsym) => dynsym.symbols(),
+        None => return Vec::new(),
+    };
+    let versions = elf.symbol_versions().unwrap_or_default();
+
+    let mut exported = Vec::new();
+    for (i, sym) in syms.iter().enumerate().skip(1) {
+        let name = sym.symbol_name.to_string();
+        let visible = matches!(
+            sym.get_visibility(),
+            symbol::Visibility::Default | symbol::Visibility::Protected
+        );
+        let global = matches!(sym.get_bind(), symbol::Bind::Global | symbol::Bind::Weak);
+        let data = matches!(
+            sym.get_type(),
+            symbol::Type::NoType
+                | symbol::Type::Object
+                | symbol::Type::Func
+                | symbol::Type::Common
+                | symbol::Type::TLS
+                | symbol::Type::GNUIFunc
+        );
+        if sym.st_shndx == section::SHN_UNDEF || !visible || !global || !data {
+            continue;
+        }
+        let version = versions
+            .get(i)
+            .and_then(|v| v.as_ref())
+            .map(|v| v.name.clone());
+        exported.push((name, version, sym));
+    }
+    exported
+}
+
+#[cfg(test)]
+mod conflicts_tests {
+    use super::*;
+    use crate::analysis::SearchConfig;
+
+    #[test]
+    fn interposition_test() {
+        let root = std::env::temp_dir().join(format!(
+            "elf_utilities_conflicts_interposition_{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(root.join("lib")).unwrap();
+        let write = |name: &str, code: Vec<u8>, needed: &[&str]| {
+            let writer = needed.iter().fold(
+                builder::SharedObjectWriter::new()
+                    .function(builder::ExportedFunction::new("malloc", code))
+                    .soname(name),
+                |w, lib| w.needed(lib),
+            );
+            let elf = writer.build().unwrap();
+            std::fs::write(root.join("lib").join(name), elf.to_le_bytes()).unwrap();
+        };
+        // libcはlibaからも依存されるが，一度だけ数える
+        write("app.so", vec![0xc3], &["liba.so", "libc.so"]);
+        write("liba.so", vec![0x90, 0xc3], &["libc.so"]);
+        write("libc.so", vec![0xc3], &[]);
+
+        let config = SearchConfig::new().root(&root).default_dirs(&["/lib"]);
+        let tree = analysis::dependency_tree("/lib/app.so", &config).unwrap();
+        let conflicts = symbol_conflicts(&tree);
+
+        assert_eq!(1, conflicts.len());
+        let libraries: Vec<&str> = conflicts[0]
+            .providers
+            .iter()
+            .map(|p| p.library.as_str())
+            .collect();
+        assert_eq!(vec!["app.so", "liba.so", "libc.so"], libraries);
+        assert_eq!(None, conflicts[0].version);
+        assert!(conflicts[0].is_incompatible());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,180 @@
+//! Variables in the writable data sections.
+
+use crate::layout::{is_alloc, is_nobits};
+use crate::*;
+
+/// The kind of the storage of a variable
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DataKind {
+    /// initialized data(e.g. `.data`)
+    Data,
+    /// zero-initialized data(e.g. `.bss`)
+    Bss,
+    /// the initial image of thread-local data(`.tdata`)
+    TData,
+    /// zero-initialized thread-local data(`.tbss`)
+    TBss,
+}
+
+impl DataKind {
+    /// the kind of the writable alloc section, `None` for the other sections.
+    pub fn of(shdr: &section::Shdr64) -> Option<Self> {
+        let write: Elf64Xword = section::Flag::Write.into();
+        let tls: Elf64Xword = section::Flag::TLS.into();
+        if !is_alloc(shdr) || shdr.sh_flags & write == 0 {
+            return None;
+        }
+        Some(match (shdr.sh_flags & tls != 0, is_nobits(shdr)) {
+            (false, false) => Self::Data,
+            (false, true) => Self::Bss,
+            (true, false) => Self::TData,
+            (true, true) => Self::TBss,
+        })
+    }
+
+    pub fn is_thread_local(&self) -> bool {
+        matches!(self, Self::TData | Self::TBss)
+    }
+}
+
+/// A variable found by `data_layout()`
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DataObject {
+    pub name: String,
+    pub section: String,
+    pub kind: DataKind,
+    /// the address of the variable(of the initial image for thread-local ones)
+    pub addr: Elf64Addr,
+    pub size: Elf64Xword,
+    pub bind: symbol::Bind,
+    /// the initial bytes, `None` for zero-initialized ones and the sections parsed into tables(e.g. `.dynamic`)
+    pub initial: Option<Vec<u8>>,
+}
+
+/// The result of `data_layout()`
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct DataLayout {
+    /// the objects ordered by kind and address
+    pub objects: Vec<DataObject>,
+}
+
+impl DataLayout {
+    /// the total size of the objects of the kind.
+    pub fn total(&self, kind: DataKind) -> Elf64Xword {
+        self.objects
+            .iter()
+            .filter(|obj| obj.kind == kind)
+            .map(|obj| obj.size)
+            .sum()
+    }
+
+    /// the objects from the largest, for finding what eats the memory.
+    pub fn largest(&self) -> Vec<&DataObject> {
+        let mut objects: Vec<&DataObject> = self.objects.iter().collect();
+        objects.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.name.cmp(&b.name)));
+        objects
+    }
+}
+
+/// list the variables(`STT_OBJECT` and `STT_TLS` symbols) in the writable data sections
+/// such as `.data`, `.bss`, `.tdata` and `.tbss`, with their initial bytes.
+///
+/// The symbols in `.symtab` and `.dynsym` are merged by the name and the address.
+/// In linked files `st_value` of thread-local symbols is the offset in `PT_TLS`,
+/// so `addr` is made the address in the initial image.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::{analysis, file, section, symbol};
+///
+/// let mut elf = file::ELF64::default();
+/// let writable = [section::Flag::Alloc, section::Flag::Write];
+/// elf.add_section(section::Section64::new(
+///     ".data".to_string(),
+///     section::ShdrPreparation64::default()
+///         .ty(section::Type::ProgBits)
+///         .flags(writable.iter()),
+///     section::Contents64::Raw(vec![1, 0, 0, 0]),
+/// ));
+/// let mut counter = symbol::Symbol64 {
+///     symbol_name: "counter".into(),
+///     st_shndx: 1,
+///     st_size: 4,
+///     ..Default::default()
+/// };
+/// counter.set_info(symbol::Type::Object, symbol::Bind::Global);
+/// elf.add_section(section::Section64::new(
+///     ".symtab".to_string(),
+///     section::ShdrPreparation64::default().ty(section::Type::SymTab),
+///     section::Contents64::Symbols(vec![symbol::Symbol64::new_null_symbol(), counter]),
+/// ));
+///
+/// let layout = analysis::data_layout(&elf);
+/// assert_eq!("counter", layout.objects[0].name);
+/// assert_eq!(Some(vec![1, 0, 0, 0]), layout.objects[0].initial);
+/// assert_eq!(4, layout.total(analysis::DataKind::Data));
+/// ```
+pub fn data_layout(elf: &file::ELF64) -> DataLayout {
+    let linked = elf.ehdr.get_type() != header::Type::Rel;
+    let tls_base = elf
+        .segments
+        .iter()
+        .find(|sgt| sgt.header.get_type() == segment::Type::TLS)
+        .map(|sgt| sgt.header.p_vaddr);
+
+    let mut objects: Vec<DataObject> = Vec::new();
+    for sct in elf.sections.iter() {
+        let syms = match &sct.contents {
+            section::Contents64::Symbols(syms) => syms,
+            _ => continue,
+        };
+        for sym in syms.iter() {
+            if !matches!(sym.get_type(), symbol::Type::Object | symbol::Type::TLS) {
+                continue;
+            }
+            let owner = match elf.sections.get(sym.st_shndx as usize) {
+                Some(owner) if sym.st_shndx != 0 => owner,
+                _ => continue,
+            };
+            let kind = match DataKind::of(&owner.header) {
+                Some(kind) => kind,
+                None => continue,
+            };
+
+            // 再配置可能ファイルではセクション内のオフセット
+            let addr = match (linked, kind.is_thread_local(), tls_base) {
+                (true, true, Some(base)) => base + sym.st_value,
+                _ => sym.st_value,
+            };
+            if objects
+                .iter()
+                .any(|obj| obj.name == sym.symbol_name && obj.addr == addr)
+            {
+                continue;
+            }
+            let offset = if linked {
+                addr.wrapping_sub(owner.header.sh_addr)
+            } else {
+                addr
+            };
+            let initial = match &owner.contents {
+                section::Contents64::Raw(bytes) if !is_nobits(&owner.header) => bytes
+                    .get(offset as usize..(offset + sym.st_size) as usize)
+                    .map(|b| b.to_vec()),
+                _ => None,
+            };
+            objects.push(DataObject {
+                name: sym.symbol_name.to_string(),
+                section: owner.name.to_string(),
+                kind,
+                addr,
+                size: sym.st_size,
+                bind: sym.get_bind(),
+                initial,
+            });
+        }
+    }
+    objects.sort_by(|a, b| (a.kind, a.addr, &a.name).cmp(&(b.kind, b.addr, &b.name)));
+    DataLayout { objects }
+}
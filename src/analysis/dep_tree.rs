@@ -0,0 +1,306 @@
+//! Recursive dependency trees of dynamically linked files.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use super::needed::open_library;
+use super::{resolve_needed, SearchConfig};
+use crate::*;
+use thiserror::Error as TError;
+
+#[derive(TError, Debug, Clone, PartialEq, Eq)]
+pub enum DependencyError {
+    #[error("failed to parse `{path}` => `{message}`")]
+    Parse { path: String, message: String },
+    #[error("`{path}` isn't a 64-bit ELF file")]
+    NotElf64 { path: String },
+}
+
+/// How a node of `DepTree` is loaded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DepStatus {
+    Loaded,
+    /// the library isn't found in the search path
+    NotFound,
+    /// the library is one of its ancestors, so its dependencies aren't expanded again
+    Cycle,
+    /// the library is already loaded by another node, which has its dependencies
+    AlreadyLoaded,
+}
+
+/// A file in `DepTree`
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DepNode {
+    /// the name in `DT_NEEDED`, or the file name of the root
+    pub name: String,
+    /// the path under the root directory of `SearchConfig`
+    pub path: Option<PathBuf>,
+    pub status: DepStatus,
+    /// `DT_SONAME`
+    pub soname: Option<String>,
+    /// the versions defined in `.gnu.version_d`, except the base version
+    pub versions: Vec<String>,
+    pub children: Vec<DepNode>,
+}
+
+impl DepNode {
+    fn new(name: String, path: PathBuf, elf: &file::ELF64, status: DepStatus) -> Self {
+        let versions = elf
+            .version_definitions()
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|def| !def.is_base())
+            .map(|def| def.name)
+            .collect();
+        Self {
+            name,
+            path: Some(path),
+            status,
+            soname: elf.soname(),
+            versions,
+            children: Vec::new(),
+        }
+    }
+
+    fn not_found(name: String) -> Self {
+        Self {
+            name,
+            path: None,
+            status: DepStatus::NotFound,
+            soname: None,
+            versions: Vec::new(),
+            children: Vec::new(),
+        }
+    }
+}
+
+/// The dependency tree built by `dependency_tree()`
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DepTree {
+    pub root: DepNode,
+}
+
+impl DepTree {
+    /// every node in the depth-first order, starting from the root.
+    pub fn nodes(&self) -> Vec<&DepNode> {
+        let mut nodes = Vec::new();
+        let mut stack = vec![&self.root];
+        while let Some(node) = stack.pop() {
+            nodes.push(node);
+            stack.extend(node.children.iter().rev());
+        }
+        nodes
+    }
+
+    /// the names of the libraries which aren't found, without duplicates.
+    pub fn missing(&self) -> Vec<&str> {
+        let mut missing: Vec<&str> = Vec::new();
+        for node in self.nodes() {
+            if node.status == DepStatus::NotFound && !missing.contains(&node.name.as_str()) {
+                missing.push(&node.name);
+            }
+        }
+        missing
+    }
+
+    pub fn has_cycle(&self) -> bool {
+        self.nodes()
+            .iter()
+            .any(|node| node.status == DepStatus::Cycle)
+    }
+
+    /// the graph in the DOT language of Graphviz.
+    /// the missing libraries are drawn in red, and the edges closing cycles are dashed.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph dependencies {\n");
+        let mut declared = HashSet::new();
+        let mut edges = HashSet::new();
+        let mut stack = vec![&self.root];
+        while let Some(node) = stack.pop() {
+            if declared.insert(node.name.as_str()) {
+                let style = match node.status {
+                    DepStatus::NotFound => ", color=red, style=dashed",
+                    _ => "",
+                };
+                out.push_str(&format!("    {}[shape=box{}];\n", quote(&node.name), style));
+            }
+            for child in node.children.iter() {
+                if edges.insert((node.name.as_str(), child.name.as_str())) {
+                    let style = match child.status {
+                        DepStatus::Cycle => " [style=dashed]",
+                        _ => "",
+                    };
+                    out.push_str(&format!(
+                        "    {} -> {}{};\n",
+                        quote(&node.name),
+                        quote(&child.name),
+                        style
+                    ));
+                }
+            }
+            stack.extend(node.children.iter().rev());
+        }
+        out.push_str("}\n");
+        out
+    }
+}
+
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// parse the file at `path` under the root directory of `config`,
+/// and resolve its dependencies recursively with `resolve_needed()`.
+///
+/// Each library is expanded once; the other nodes referring it are `AlreadyLoaded`,
+/// and a library depending on its ancestor is a `Cycle`.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::{analysis, builder};
+///
+/// let f = builder::ExportedFunction::new("f", vec![0xc3]);
+/// let app = builder::SharedObjectWriter::new()
+///     .function(f.clone())
+///     .needed("libdep.so")
+///     .needed("libmissing.so")
+///     .runpath("$ORIGIN")
+///     .build()
+///     .unwrap();
+/// let lib = builder::SharedObjectWriter::new()
+///     .function(f)
+///     .soname("libdep.so")
+///     .build()
+///     .unwrap();
+///
+/// let root = std::env::temp_dir().join(format!("elf_utilities_dep_tree_doctest_{}", std::process::id()));
+/// std::fs::create_dir_all(root.join("app")).unwrap();
+/// std::fs::write(root.join("app/app.so"), app.to_le_bytes()).unwrap();
+/// std::fs::write(root.join("app/libdep.so"), lib.to_le_bytes()).unwrap();
+///
+/// let config = analysis::SearchConfig::new().root(&root).default_dirs(&[]);
+/// let tree = analysis::dependency_tree("/app/app.so", &config).unwrap();
+///
+/// assert_eq!(Some("libdep.so".to_string()), tree.root.children[0].soname);
+/// assert_eq!(vec!["libmissing.so"], tree.missing());
+/// assert!(tree.to_dot().contains("\"app.so\" -> \"libdep.so\";"));
+/// # std::fs::remove_dir_all(&root).unwrap();
+/// ```
+pub fn dependency_tree(path: &str, config: &SearchConfig) -> Result<DepTree, DependencyError> {
+    let host = config.resolve(path);
+    let parsed =
+        parser::parse_elf(&host.to_string_lossy()).map_err(|e| DependencyError::Parse {
+            path: path.to_string(),
+            message: e.to_string(),
+        })?;
+    let elf = match parsed {
+        file::ELF::ELF64(elf) => elf,
+        _ => {
+            return Err(DependencyError::NotElf64 {
+                path: path.to_string(),
+            })
+        }
+    };
+
+    let name = Path::new(path)
+        .file_name()
+        .map_or_else(|| path.to_string(), |n| n.to_string_lossy().to_string());
+    let mut loaded = HashSet::new();
+    loaded.insert(host.clone());
+    let mut ancestors = vec![host.clone()];
+    let config = config.for_file(&host);
+    let mut root = DepNode::new(name, host, &elf, DepStatus::Loaded);
+    root.children = expand(&elf, &config, &mut ancestors, &mut loaded);
+    Ok(DepTree { root })
+}
+
+fn expand(
+    elf: &file::ELF64,
+    config: &SearchConfig,
+    ancestors: &mut Vec<PathBuf>,
+    loaded: &mut HashSet<PathBuf>,
+) -> Vec<DepNode> {
+    let mut children = Vec::new();
+    for dep in resolve_needed(elf, config) {
+        let (path, lib) = match dep
+            .path
+            .and_then(|path| Some((path.clone(), open_library(&path, elf.ehdr.e_machine)?)))
+        {
+            Some(found) => found,
+            None => {
+                children.push(DepNode::not_found(dep.name));
+                continue;
+            }
+        };
+        let status = if ancestors.contains(&path) {
+            DepStatus::Cycle
+        } else if !loaded.insert(path.clone()) {
+            DepStatus::AlreadyLoaded
+        } else {
+            DepStatus::Loaded
+        };
+        let mut node = DepNode::new(dep.name, path.clone(), &lib, status);
+        if status == DepStatus::Loaded {
+            let config = config.for_library(elf, &path);
+            ancestors.push(path);
+            node.children = expand(&lib, &config, ancestors, loaded);
+            ancestors.pop();
+        }
+        children.push(node);
+    }
+    children
+}
+
+#[cfg(test)]
+mod dep_tree_tests {
+    use super::*;
+
+    #[test]
+    fn cycle_test() {
+        let root = std::env::temp_dir().join(format!(
+            "elf_utilities_dep_tree_cycle_{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(root.join("lib")).unwrap();
+        let f = builder::ExportedFunction::new("f", vec![0xc3]);
+        let write = |name: &str, needed: &[&str]| {
+            let writer = needed.iter().fold(
+                builder::SharedObjectWriter::new()
+                    .function(f.clone())
+                    .soname(name),
+                |w, lib| w.needed(lib),
+            );
+            let elf = writer.build().unwrap();
+            std::fs::write(root.join("lib").join(name), elf.to_le_bytes()).unwrap();
+        };
+        write("liba.so", &["libb.so", "libc.so"]);
+        write("libb.so", &["liba.so", "libc.so"]);
+        write("libc.so", &[]);
+
+        let config = SearchConfig::new().root(&root).default_dirs(&["/lib"]);
+        let tree = dependency_tree("/lib/liba.so", &config).unwrap();
+        let statuses: Vec<(&str, DepStatus)> = tree
+            .nodes()
+            .iter()
+            .map(|node| (node.name.as_str(), node.status))
+            .collect();
+        assert_eq!(
+            vec![
+                ("liba.so", DepStatus::Loaded),
+                ("libb.so", DepStatus::Loaded),
+                ("liba.so", DepStatus::Cycle),
+                ("libc.so", DepStatus::Loaded),
+                ("libc.so", DepStatus::AlreadyLoaded),
+            ],
+            statuses
+        );
+        assert!(tree.has_cycle());
+        assert!(tree.missing().is_empty());
+        assert!(tree
+            .to_dot()
+            .contains("\"libb.so\" -> \"liba.so\" [style=dashed];"));
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,344 @@
+//! Structural differences between two ELF files.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::*;
+
+/// A difference found by `diff()`
+///
+/// Sections are matched by name, segments by index, and symbols by name in each symbol table.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Difference {
+    Header {
+        field: String,
+        old: u128,
+        new: u128,
+    },
+    SectionAdded {
+        name: String,
+    },
+    SectionRemoved {
+        name: String,
+    },
+    SectionHeader {
+        name: String,
+        field: String,
+        old: u64,
+        new: u64,
+    },
+    /// the contents differ although the header fields are equal
+    SectionContents {
+        name: String,
+    },
+    SegmentAdded {
+        index: usize,
+    },
+    SegmentRemoved {
+        index: usize,
+    },
+    SegmentHeader {
+        index: usize,
+        field: String,
+        old: u64,
+        new: u64,
+    },
+    SymbolAdded {
+        table: String,
+        name: String,
+    },
+    SymbolRemoved {
+        table: String,
+        name: String,
+    },
+    Symbol {
+        table: String,
+        name: String,
+        field: String,
+        old: u64,
+        new: u64,
+    },
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Header { field, old, new } => {
+                write!(f, "header: {} {:#x} -> {:#x}", field, old, new)
+            }
+            Self::SectionAdded { name } => write!(f, "section {}: added", name),
+            Self::SectionRemoved { name } => write!(f, "section {}: removed", name),
+            Self::SectionHeader {
+                name,
+                field,
+                old,
+                new,
+            } => write!(f, "section {}: {} {:#x} -> {:#x}", name, field, old, new),
+            Self::SectionContents { name } => write!(f, "section {}: contents differ", name),
+            Self::SegmentAdded { index } => write!(f, "segment {}: added", index),
+            Self::SegmentRemoved { index } => write!(f, "segment {}: removed", index),
+            Self::SegmentHeader {
+                index,
+                field,
+                old,
+                new,
+            } => write!(f, "segment {}: {} {:#x} -> {:#x}", index, field, old, new),
+            Self::SymbolAdded { table, name } => write!(f, "{} {}: added", table, name),
+            Self::SymbolRemoved { table, name } => write!(f, "{} {}: removed", table, name),
+            Self::Symbol {
+                table,
+                name,
+                field,
+                old,
+                new,
+            } => write!(f, "{} {}: {} {:#x} -> {:#x}", table, name, field, old, new),
+        }
+    }
+}
+
+/// compare the headers, sections, segments and symbols of two files.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::{analysis, file, section};
+///
+/// let old = file::ELF64::default();
+/// let mut new = old.clone();
+/// new.ehdr.e_entry = 0x401000;
+/// new.add_section(section::Section64::new(
+///     ".text".to_string(),
+///     section::ShdrPreparation64::default().ty(section::Type::ProgBits),
+///     section::Contents64::Raw(vec![0xc3]),
+/// ));
+///
+/// let diffs = analysis::diff(&old, &new);
+/// assert!(diffs.contains(&analysis::Difference::SectionAdded {
+///     name: ".text".to_string()
+/// }));
+/// assert_eq!("header: e_entry 0x0 -> 0x401000", diffs[0].to_string());
+/// ```
+pub fn diff(old: &file::ELF64, new: &file::ELF64) -> Vec<Difference> {
+    let mut diffs = Vec::new();
+    diff_header(&old.ehdr, &new.ehdr, &mut diffs);
+    diff_sections(old, new, &mut diffs);
+    diff_segments(old, new, &mut diffs);
+    for table in [".symtab", ".dynsym"].iter() {
+        diff_symbols(table, old, new, &mut diffs);
+    }
+    diffs
+}
+
+fn diff_header(old: &header::Ehdr64, new: &header::Ehdr64, diffs: &mut Vec<Difference>) {
+    let fields = |ehdr: &header::Ehdr64| -> Vec<(&'static str, u128)> {
+        vec![
+            ("e_ident", u128::from(ehdr.e_ident)),
+            ("e_type", ehdr.e_type as u128),
+            ("e_machine", ehdr.e_machine as u128),
+            ("e_version", ehdr.e_version as u128),
+            ("e_entry", ehdr.e_entry as u128),
+            ("e_phoff", ehdr.e_phoff as u128),
+            ("e_shoff", ehdr.e_shoff as u128),
+            ("e_flags", ehdr.e_flags as u128),
+            ("e_ehsize", ehdr.e_ehsize as u128),
+            ("e_phentsize", ehdr.e_phentsize as u128),
+            ("e_phnum", ehdr.e_phnum as u128),
+            ("e_shentsize", ehdr.e_shentsize as u128),
+            ("e_shnum", ehdr.e_shnum as u128),
+            ("e_shstrndx", ehdr.e_shstrndx as u128),
+        ]
+    };
+    for ((field, old), (_, new)) in fields(old).into_iter().zip(fields(new)) {
+        if old != new {
+            diffs.push(Difference::Header {
+                field: field.to_string(),
+                old,
+                new,
+            });
+        }
+    }
+}
+
+fn shdr_fields(shdr: &section::Shdr64) -> [(&'static str, u64); 9] {
+    [
+        ("sh_type", shdr.sh_type as u64),
+        ("sh_flags", shdr.sh_flags),
+        ("sh_addr", shdr.sh_addr),
+        ("sh_offset", shdr.sh_offset),
+        ("sh_size", shdr.sh_size),
+        ("sh_link", shdr.sh_link as u64),
+        ("sh_info", shdr.sh_info as u64),
+        ("sh_addralign", shdr.sh_addralign),
+        ("sh_entsize", shdr.sh_entsize),
+    ]
+}
+
+fn diff_sections(old: &file::ELF64, new: &file::ELF64, diffs: &mut Vec<Difference>) {
+    let old_scts = by_name(
+        old.sections
+            .iter()
+            .skip(1)
+            .map(|sct| (sct.name.as_str(), sct)),
+    );
+    let new_scts = by_name(
+        new.sections
+            .iter()
+            .skip(1)
+            .map(|sct| (sct.name.as_str(), sct)),
+    );
+
+    for (name, old_sct) in old_scts.iter() {
+        let new_sct = match new_scts.get(name) {
+            Some(sct) => sct,
+            None => {
+                diffs.push(Difference::SectionRemoved {
+                    name: name.to_string(),
+                });
+                continue;
+            }
+        };
+
+        let mut header_differs = false;
+        for ((field, old), (_, new)) in shdr_fields(&old_sct.header)
+            .iter()
+            .zip(shdr_fields(&new_sct.header).iter())
+        {
+            if old != new {
+                header_differs = true;
+                diffs.push(Difference::SectionHeader {
+                    name: name.to_string(),
+                    field: field.to_string(),
+                    old: *old,
+                    new: *new,
+                });
+            }
+        }
+        if !header_differs && old_sct.to_le_bytes() != new_sct.to_le_bytes() {
+            diffs.push(Difference::SectionContents {
+                name: name.to_string(),
+            });
+        }
+    }
+    for name in new_scts.keys().filter(|name| !old_scts.contains_key(*name)) {
+        diffs.push(Difference::SectionAdded {
+            name: name.to_string(),
+        });
+    }
+}
+
+fn phdr_fields(phdr: &segment::Phdr64) -> [(&'static str, u64); 8] {
+    [
+        ("p_type", phdr.p_type as u64),
+        ("p_flags", phdr.p_flags as u64),
+        ("p_offset", phdr.p_offset),
+        ("p_vaddr", phdr.p_vaddr),
+        ("p_paddr", phdr.p_paddr),
+        ("p_filesz", phdr.p_filesz),
+        ("p_memsz", phdr.p_memsz),
+        ("p_align", phdr.p_align),
+    ]
+}
+
+fn diff_segments(old: &file::ELF64, new: &file::ELF64, diffs: &mut Vec<Difference>) {
+    for (index, (old_sgt, new_sgt)) in old.segments.iter().zip(new.segments.iter()).enumerate() {
+        for ((field, old), (_, new)) in phdr_fields(&old_sgt.header)
+            .iter()
+            .zip(phdr_fields(&new_sgt.header).iter())
+        {
+            if old != new {
+                diffs.push(Difference::SegmentHeader {
+                    index,
+                    field: field.to_string(),
+                    old: *old,
+                    new: *new,
+                });
+            }
+        }
+    }
+    for index in new.segments.len()..old.segments.len() {
+        diffs.push(Difference::SegmentRemoved { index });
+    }
+    for index in old.segments.len()..new.segments.len() {
+        diffs.push(Difference::SegmentAdded { index });
+    }
+}
+
+fn diff_symbols(table: &str, old: &file::ELF64, new: &file::ELF64, diffs: &mut Vec<Difference>) {
+    let fields = |sym: &symbol::Symbol64| -> [(&'static str, u64); 5] {
+        [
+            ("st_value", sym.st_value),
+            ("st_size", sym.st_size),
+            ("st_info", sym.st_info as u64),
+            ("st_other", sym.st_other as u64),
+            ("st_shndx", sym.st_shndx as u64),
+        ]
+    };
+    let old_syms = symbol_map(old, table);
+    let new_syms = symbol_map(new, table);
+
+    for (name, old_sym) in old_syms.iter() {
+        let new_sym = match new_syms.get(name) {
+            Some(sym) => sym,
+            None => {
+                diffs.push(Difference::SymbolRemoved {
+                    table: table.to_string(),
+                    name: name.to_string(),
+                });
+                continue;
+            }
+        };
+        for ((field, old), (_, new)) in fields(old_sym).iter().zip(fields(new_sym).iter()) {
+            if old != new {
+                diffs.push(Difference::Symbol {
+                    table: table.to_string(),
+                    name: name.to_string(),
+                    field: field.to_string(),
+                    old: *old,
+                    new: *new,
+                });
+            }
+        }
+    }
+    for name in new_syms.keys().filter(|name| !old_syms.contains_key(*name)) {
+        diffs.push(Difference::SymbolAdded {
+            table: table.to_string(),
+            name: name.to_string(),
+        });
+    }
+}
+
+fn symbol_map<'a>(elf: &'a file::ELF64, table: &str) -> BTreeMap<String, &'a symbol::Symbol64> {
+    match elf
+        .first_section_by(|sct| sct.name == table)
+        .map(|sct| &sct.contents)
+    {
+        Some(section::Contents64::Symbols(syms)) => by_name(
+            syms.iter()
+                .filter(|sym| !sym.symbol_name.is_empty())
+                .map(|sym| (sym.symbol_name.as_str(), sym)),
+        ),
+        _ => BTreeMap::new(),
+    }
+}
+
+/// index items by name. the n-th item of the same name is keyed as `name#n`.
+fn by_name<'a, T, I>(items: I) -> BTreeMap<String, T>
+where
+    I: Iterator<Item = (&'a str, T)>,
+{
+    let mut map = BTreeMap::new();
+    let mut seen: BTreeMap<&str, usize> = BTreeMap::new();
+    for (name, item) in items {
+        let n = seen.entry(name).or_insert(0);
+        let key = if *n == 0 {
+            name.to_string()
+        } else {
+            format!("{}#{}", name, n)
+        };
+        *n += 1;
+        map.insert(key, item);
+    }
+    map
+}
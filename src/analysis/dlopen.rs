@@ -0,0 +1,300 @@
+//! Checking whether plugins can be loaded by `dlopen(3)`.
+
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+
+use super::conflicts::dynamic_exports;
+use crate::*;
+
+/// A library already loaded in the process which opens plugins
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+struct HostLibrary {
+    /// the name in `DT_NEEDED` of the plugins
+    name: String,
+    /// the versions in `.gnu.version_d`, except the base version
+    versions: Vec<String>,
+    symbols: BTreeSet<(String, Option<String>)>,
+}
+
+/// The process which opens plugins, checked by `check_dlopenable()`
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::{analysis, builder};
+///
+/// let lib = builder::SharedObjectWriter::new()
+///     .function(builder::ExportedFunction::new("host_log", vec![0xc3]))
+///     .soname("libhost.so")
+///     .build()
+///     .unwrap();
+/// let host = analysis::PluginHost::new()
+///     .library("libhost.so", &lib)
+///     .entry("plugin_init");
+/// assert_eq!(vec!["plugin_init".to_string()], host.get_entries());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct PluginHost {
+    libraries: Vec<HostLibrary>,
+    entries: Vec<String>,
+}
+
+impl PluginHost {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// a library which the host provides to the plugins, named as in their `DT_NEEDED`.
+    pub fn library(mut self, name: &str, elf: &file::ELF64) -> Self {
+        let versions = elf
+            .version_definitions()
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|def| !def.is_base())
+            .map(|def| def.name)
+            .collect();
+        let symbols = dynamic_exports(elf)
+            .into_iter()
+            .map(|(name, version, _)| (name, version))
+            .collect();
+        self.libraries.push(HostLibrary {
+            name: name.to_string(),
+            versions,
+            symbols,
+        });
+        self
+    }
+
+    /// a symbol which the host looks up by `dlsym(3)`.
+    pub fn entry(mut self, symbol: &str) -> Self {
+        self.entries.push(symbol.to_string());
+        self
+    }
+
+    pub fn get_entries(&self) -> &[String] {
+        &self.entries
+    }
+}
+
+/// A reason why `dlopen(3)` or `dlsym(3)` fails on a plugin
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DlopenBlocker {
+    /// `e_type` isn't `ET_DYN`
+    NotSharedObject { ty: header::Type },
+    /// `DF_1_PIE` is set, which glibc refuses to open
+    Executable,
+    /// `DF_1_NOOPEN` is set
+    NoOpen,
+    /// a host library doesn't define a version required in `.gnu.version_r`
+    VersionNotFound { version: String, library: String },
+    /// a host library doesn't export a versioned symbol which the plugin refers
+    SymbolNotFound {
+        symbol: String,
+        version: String,
+        library: String,
+    },
+    /// an entry symbol of `PluginHost` isn't exported
+    EntryNotExported { symbol: String },
+}
+
+impl fmt::Display for DlopenBlocker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotSharedObject { ty } => write!(
+                f,
+                "the file is {}, but only DYN can be opened; link it with `-shared`",
+                ty
+            ),
+            Self::Executable => write!(
+                f,
+                "the file is a position independent executable; link it with `-shared` instead of `-pie`"
+            ),
+            Self::NoOpen => write!(
+                f,
+                "DF_1_NOOPEN is set; remove `-z nodlopen` from the link options"
+            ),
+            Self::VersionNotFound { version, library } => write!(
+                f,
+                "version `{}` is required but not defined by `{}`; build the plugin against an older `{}`",
+                version, library, library
+            ),
+            Self::SymbolNotFound {
+                symbol,
+                version,
+                library,
+            } => write!(
+                f,
+                "undefined symbol `{}@{}`, which `{}` doesn't export",
+                symbol, version, library
+            ),
+            Self::EntryNotExported { symbol } => write!(
+                f,
+                "the entry symbol `{}` isn't exported; check its visibility and `extern \"C\"`",
+                symbol
+            ),
+        }
+    }
+}
+
+/// list the reasons why the host can't open the plugin or look up its entry symbols.
+///
+/// Each versioned reference is checked only against the host libraries of `PluginHost`,
+/// since the other dependencies are loaded with the plugin.
+/// A host library without `.gnu.version_d` satisfies any version, as in `simulate_load()`.
+/// An empty list means the plugin can be opened.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::{analysis, builder};
+///
+/// let plugin = builder::SharedObjectWriter::new()
+///     .function(builder::ExportedFunction::new("plugin_init", vec![0xc3]))
+///     .build()
+///     .unwrap();
+///
+/// let host = analysis::PluginHost::new().entry("plugin_init");
+/// assert!(analysis::check_dlopenable(&plugin, &host).is_empty());
+///
+/// let host = host.entry("plugin_fini");
+/// assert_eq!(
+///     vec![analysis::DlopenBlocker::EntryNotExported { symbol: "plugin_fini".to_string() }],
+///     analysis::check_dlopenable(&plugin, &host)
+/// );
+/// ```
+pub fn check_dlopenable(elf: &file::ELF64, host: &PluginHost) -> Vec<DlopenBlocker> {
+    let mut blockers = Vec::new();
+    let ty = elf.ehdr.get_type();
+    if ty != header::Type::Dyn {
+        blockers.push(DlopenBlocker::NotSharedObject { ty });
+    }
+    let flags_1 = elf
+        .first_section_by(|sct| sct.header.get_type() == section::Type::Dynamic)
+        .and_then(|sct| match &sct.contents {
+            section::Contents64::Dynamics(entries) => entries
+                .iter()
+                .find(|ent| ent.get_type() == dynamic::EntryType::Flags1)
+                .map(|ent| ent.d_un),
+            _ => None,
+        })
+        .unwrap_or(0);
+    if flags_1 & dynamic::Flag::PIE1.to_bytes() != 0 {
+        blockers.push(DlopenBlocker::Executable);
+    }
+    if flags_1 & dynamic::Flag::NoOpen1.to_bytes() != 0 {
+        blockers.push(DlopenBlocker::NoOpen);
+    }
+
+    // バージョン名から，それを要求しているホストのライブラリを引く
+    let mut required = HashMap::new();
+    for need in elf.version_needs().unwrap_or_default() {
+        let lib = match host.libraries.iter().find(|lib| lib.name == need.file) {
+            Some(lib) if !lib.versions.is_empty() => lib,
+            _ => continue,
+        };
+        for version in need.versions.iter() {
+            if lib.versions.contains(&version.name) {
+                required.insert(version.name.clone(), lib);
+            } else if !version.is_weak() {
+                blockers.push(DlopenBlocker::VersionNotFound {
+                    version: version.name.clone(),
+                    library: lib.name.clone(),
+                });
+            }
+        }
+    }
+
+    let versions = elf.symbol_versions().unwrap_or_default();
+    let syms = elf.dynsym().map_or(&[][..], |dynsym| dynsym.symbols());
+    for (i, sym) in syms.iter().enumerate().skip(1) {
+        // 弱い未定義シンボルは見つからなくても0になるだけ
+        if sym.st_shndx != section::SHN_UNDEF || sym.get_bind() == symbol::Bind::Weak {
+            continue;
+        }
+        let version = match versions.get(i).and_then(|v| v.as_ref()) {
+            Some(version) => &version.name,
+            None => continue,
+        };
+        let lib = match required.get(version) {
+            Some(lib) => lib,
+            None => continue,
+        };
+        let name = sym.symbol_name.to_string();
+        if !lib.symbols.contains(&(name.clone(), Some(version.clone()))) {
+            blockers.push(DlopenBlocker::SymbolNotFound {
+                symbol: name,
+                version: version.clone(),
+                library: lib.name.clone(),
+            });
+        }
+    }
+
+    let exports = dynamic_exports(elf);
+    for entry in host.entries.iter() {
+        if !exports.iter().any(|(name, _, _)| name == entry) {
+            blockers.push(DlopenBlocker::EntryNotExported {
+                symbol: entry.clone(),
+            });
+        }
+    }
+    blockers
+}
+
+#[cfg(test)]
+mod dlopen_tests {
+    use super::*;
+
+    #[test]
+    fn check_dlopenable_test() {
+        // sampleはPIEで，libcのGLIBC_2.2.5を要求する
+        let elf = parser::parse_elf64("src/parser/testdata/sample").unwrap();
+        let answer =
+            builder::ExportedFunction::new("answer", vec![0xb8, 0x2a, 0x00, 0x00, 0x00, 0xc3]);
+        let mut libc = builder::SharedObjectWriter::new()
+            .soname("libc.so.6")
+            .function(answer)
+            .build()
+            .unwrap();
+
+        // バージョン定義を持たないlibcは何でも満たす．mainは-rdynamicなしでは.dynsymに無い
+        let host = PluginHost::new().library("libc.so.6", &libc).entry("main");
+        assert_eq!(
+            vec![
+                DlopenBlocker::Executable,
+                DlopenBlocker::EntryNotExported {
+                    symbol: "main".to_string()
+                },
+            ],
+            check_dlopenable(&elf, &host)
+        );
+
+        let script = transform::VersionScript::parse("GLIBC_2.34 { global: answer; };").unwrap();
+        transform::apply_version_script(&mut libc, &script).unwrap();
+        let host = PluginHost::new().library("libc.so.6", &libc);
+        assert_eq!(
+            vec![
+                DlopenBlocker::Executable,
+                DlopenBlocker::VersionNotFound {
+                    version: "GLIBC_2.2.5".to_string(),
+                    library: "libc.so.6".to_string(),
+                },
+            ],
+            check_dlopenable(&elf, &host)
+        );
+
+        let script = transform::VersionScript::parse("GLIBC_2.2.5 { global: answer; };").unwrap();
+        let mut libc = builder::SharedObjectWriter::new()
+            .soname("libc.so.6")
+            .function(builder::ExportedFunction::new("answer", vec![0xc3]))
+            .build()
+            .unwrap();
+        transform::apply_version_script(&mut libc, &script).unwrap();
+        let host = PluginHost::new().library("libc.so.6", &libc);
+        let blockers = check_dlopenable(&elf, &host);
+        assert!(blockers.contains(&DlopenBlocker::SymbolNotFound {
+            symbol: "__libc_start_main".to_string(),
+            version: "GLIBC_2.2.5".to_string(),
+            library: "libc.so.6".to_string(),
+        }));
+    }
+}
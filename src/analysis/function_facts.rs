@@ -0,0 +1,325 @@
+//! Per-function metadata for call graph and CFI tools.
+
+use serde::{Deserialize, Serialize};
+
+use crate::*;
+
+/// A reference from a function found by a relocation
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct OutgoingCall {
+    /// the relocated place
+    pub site: Elf64Addr,
+    pub target: String,
+    /// the relocation type
+    #[serde(rename = "type")]
+    pub ty: Elf64Xword,
+}
+
+/// The facts of a function collected by `function_facts()`
+///
+/// The addresses are the offsets in the section in relocatable files, as `st_value`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize)]
+pub struct FunctionFacts {
+    pub name: String,
+    pub section: String,
+    pub start: Elf64Addr,
+    pub end: Elf64Addr,
+    /// an FDE in `.eh_frame` describes the function
+    pub unwind_info: bool,
+    /// the FDE has an LSDA, e.g. in `.gcc_except_table`
+    pub exception_table: bool,
+    pub landing_pads: Vec<Elf64Addr>,
+    /// the functions and undefined symbols referenced by the relocations in the function.
+    /// Linked files have them only if linked with `--emit-relocs` or with text relocations.
+    pub calls: Vec<OutgoingCall>,
+}
+
+/// the unwind information of a function, keyed by its section and start
+#[derive(Default)]
+struct UnwindFacts {
+    exception_table: bool,
+    landing_pads: Vec<Elf64Addr>,
+}
+
+/// collect the facts of the sized functions in `.symtab`(or `.dynsym` if stripped), ordered by section and address.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::{analysis, parser};
+///
+/// let elf = parser::parse_elf64("tests/fixtures/x86_64.o").unwrap();
+/// let facts = analysis::function_facts(&elf);
+/// assert_eq!("answer", facts[0].name);
+/// assert!(!facts[0].unwind_info);
+/// assert_eq!("external", facts[0].calls[0].target);
+/// // call external
+/// assert_eq!(1, facts[0].calls[0].site);
+/// ```
+pub fn function_facts(elf: &file::ELF64) -> Vec<FunctionFacts> {
+    let syms = match (elf.symtab(), elf.dynsym()) {
+        (Some(symtab), _) => symtab.symbols(),
+        (None, Some(dynsym)) => dynsym.symbols(),
+        (None, None) => &[],
+    };
+
+    let mut functions: Vec<(usize, FunctionFacts)> = Vec::new();
+    for sym in syms.iter() {
+        let shndx = sym.st_shndx as usize;
+        if sym.get_type() != symbol::Type::Func
+            || sym.st_size == 0
+            || sym.st_shndx == section::SHN_UNDEF
+            || sym.st_shndx >= section::SHN_LORESERVE
+        {
+            continue;
+        }
+        // 同じ位置の別名は最初のものだけ
+        if functions
+            .iter()
+            .any(|(idx, f)| *idx == shndx && f.start == sym.st_value)
+        {
+            continue;
+        }
+        functions.push((
+            shndx,
+            FunctionFacts {
+                name: sym.symbol_name.to_string(),
+                section: elf
+                    .sections
+                    .get(shndx)
+                    .map(|sct| sct.name.to_string())
+                    .unwrap_or_default(),
+                start: sym.st_value,
+                end: sym.st_value + sym.st_size,
+                ..Default::default()
+            },
+        ));
+    }
+    functions.sort_by_key(|(shndx, f)| (*shndx, f.start));
+
+    let is_rel = elf.ehdr.get_type() == header::Type::Rel;
+    let unwind = if is_rel {
+        relocatable_unwind_facts(elf)
+    } else {
+        linked_unwind_facts(elf)
+    };
+    for (shndx, f) in functions.iter_mut() {
+        // 再配置可能ファイルではセクションごと
+        let key = if is_rel { *shndx } else { 0 };
+        if let Some((_, facts)) = unwind.iter().find(|(k, _)| *k == (key, f.start)) {
+            f.unwind_info = true;
+            f.exception_table = facts.exception_table;
+            f.landing_pads = facts.landing_pads.clone();
+        }
+    }
+
+    for sct in elf.sections.iter() {
+        let relas = match &sct.contents {
+            section::Contents64::RelaSymbols(relas) => relas,
+            _ => continue,
+        };
+        let rel_syms = match elf.sections.get(sct.header.sh_link as usize) {
+            Some(section::Section64 {
+                contents: section::Contents64::Symbols(syms),
+                ..
+            }) => syms.as_slice(),
+            _ => continue,
+        };
+        let place_shndx = if is_rel && !layout::is_alloc(&sct.header) {
+            Some(sct.header.sh_info as usize)
+        } else {
+            None
+        };
+        for rela in relas.iter() {
+            let target = match rel_syms.get(rela.get_sym() as usize) {
+                Some(target) if is_call_target(target) => target,
+                _ => continue,
+            };
+            let site = rela.get_offset();
+            let function = functions.iter_mut().find(|(shndx, f)| {
+                place_shndx.is_none_or(|place| place == *shndx) && f.start <= site && site < f.end
+            });
+            if let Some((_, f)) = function {
+                f.calls.push(OutgoingCall {
+                    site,
+                    target: target.symbol_name.to_string(),
+                    ty: rela.get_type(),
+                });
+            }
+        }
+    }
+
+    functions
+        .into_iter()
+        .map(|(_, mut f)| {
+            f.calls.sort();
+            f
+        })
+        .collect()
+}
+
+fn is_call_target(sym: &symbol::Symbol64) -> bool {
+    match sym.get_type() {
+        symbol::Type::Func | symbol::Type::GNUIFunc => true,
+        // 再配置可能ファイルの未定義シンボルは型を持たない
+        symbol::Type::NoType => sym.st_shndx == section::SHN_UNDEF && !sym.symbol_name.is_empty(),
+        _ => false,
+    }
+}
+
+/// the FDEs keyed by their addresses.
+fn linked_unwind_facts(elf: &file::ELF64) -> Vec<((usize, Elf64Addr), UnwindFacts)> {
+    let frame = match elf.eh_frame() {
+        Some(Ok(frame)) => frame,
+        _ => return Vec::new(),
+    };
+    frame
+        .fdes
+        .iter()
+        .map(|fde| {
+            let lsda = elf.lsda(fde).and_then(Result::ok);
+            let facts = UnwindFacts {
+                exception_table: fde.lsda.is_some(),
+                landing_pads: lsda.map(landing_pads).unwrap_or_default(),
+            };
+            ((0, fde.pc_begin), facts)
+        })
+        .collect()
+}
+
+/// the FDEs keyed by the sections and the offsets which their relocations refer to.
+fn relocatable_unwind_facts(elf: &file::ELF64) -> Vec<((usize, Elf64Addr), UnwindFacts)> {
+    let eh_idx = match elf.first_shidx_by(|sct| sct.name == unwind::EH_FRAME_SECTION) {
+        Some(idx) => idx,
+        None => return Vec::new(),
+    };
+    let rela = elf.first_section_by(|sct| {
+        sct.header.get_type() == section::Type::Rela && sct.header.sh_info as usize == eh_idx
+    });
+    let (relas, syms) = match rela {
+        Some(rela) => match (
+            &rela.contents,
+            elf.sections.get(rela.header.sh_link as usize),
+        ) {
+            (
+                section::Contents64::RelaSymbols(relas),
+                Some(section::Section64 {
+                    contents: section::Contents64::Symbols(syms),
+                    ..
+                }),
+            ) => (relas, syms),
+            _ => return Vec::new(),
+        },
+        None => return Vec::new(),
+    };
+    let data = elf.ehdr.get_data();
+    let frame = match unwind::EhFrame::parse(&elf.sections[eh_idx].to_le_bytes(), 0, data) {
+        Ok(frame) => frame,
+        Err(_) => return Vec::new(),
+    };
+    // リロケーションが指す(セクション, オフセット)
+    let target_of = |rela: &relocation::Rela64| {
+        let sym = syms.get(rela.get_sym() as usize)?;
+        let base = if sym.get_type() == symbol::Type::Section {
+            0
+        } else {
+            sym.st_value
+        };
+        Some((
+            sym.st_shndx as usize,
+            base.wrapping_add(rela.get_addend() as Elf64Addr),
+        ))
+    };
+
+    let mut starts: Vec<usize> = frame
+        .cies
+        .iter()
+        .map(|cie| cie.offset)
+        .chain(frame.fdes.iter().map(|fde| fde.offset))
+        .collect();
+    starts.sort_unstable();
+
+    let mut facts = Vec::new();
+    for fde in frame.fdes.iter() {
+        let end = starts
+            .iter()
+            .copied()
+            .find(|start| *start > fde.offset)
+            .unwrap_or(usize::MAX);
+        let mut in_fde: Vec<&relocation::Rela64> = relas
+            .iter()
+            .filter(|rela| {
+                let offset = rela.get_offset() as usize;
+                fde.offset + 8 <= offset && offset < end
+            })
+            .collect();
+        in_fde.sort_by_key(|rela| rela.get_offset());
+        // 最初がpc_begin，その後がLSDA
+        let function = match in_fde.first().and_then(|rela| target_of(rela)) {
+            Some(function) => function,
+            None => continue,
+        };
+        let lsda = in_fde.get(1).and_then(|rela| target_of(rela));
+        let landing_pads = lsda
+            .and_then(|(shndx, offset)| {
+                let bytes = elf.sections.get(shndx)?.to_le_bytes();
+                let lsda =
+                    unwind::Lsda::parse(bytes.get(offset as usize..)?, offset, function.1, data)
+                        .ok()?;
+                Some(landing_pads(lsda))
+            })
+            .unwrap_or_default();
+        facts.push((
+            function,
+            UnwindFacts {
+                exception_table: lsda.is_some(),
+                landing_pads,
+            },
+        ));
+    }
+    facts
+}
+
+fn landing_pads(lsda: unwind::Lsda) -> Vec<Elf64Addr> {
+    let mut pads: Vec<Elf64Addr> = lsda
+        .call_sites
+        .iter()
+        .filter_map(|site| site.landing_pad)
+        .collect();
+    pads.sort_unstable();
+    pads.dedup();
+    pads
+}
+
+#[cfg(test)]
+mod function_facts_tests {
+    use super::*;
+
+    #[test]
+    fn exception_facts_test() {
+        for path in ["tests/fixtures/exceptions", "tests/fixtures/exceptions.o"].iter() {
+            let elf = parser::parse_elf64(path).unwrap();
+            let facts = function_facts(&elf);
+            let by_name = |name: &str| facts.iter().find(|f| f.name == name).unwrap();
+
+            let recover = by_name("_Z7recoveri");
+            assert!(recover.unwind_info && recover.exception_table, "{}", path);
+            assert_eq!(1, recover.landing_pads.len(), "{}", path);
+            assert!(
+                recover.start < recover.landing_pads[0] && recover.landing_pads[0] < recover.end
+            );
+            let cleanup = by_name("_Z7cleanupiPi");
+            assert!(cleanup.exception_table, "{}", path);
+            assert_eq!(1, cleanup.landing_pads.len(), "{}", path);
+            let main = by_name("main");
+            assert!(main.unwind_info && !main.exception_table, "{}", path);
+        }
+
+        // 再配置可能ファイルではリロケーションから呼び出し先がわかる
+        let elf = parser::parse_elf64("tests/fixtures/exceptions.o").unwrap();
+        let facts = function_facts(&elf);
+        let main = facts.iter().find(|f| f.name == "main").unwrap();
+        let callees: Vec<&str> = main.calls.iter().map(|c| c.target.as_str()).collect();
+        assert!(callees.contains(&"_Z7recoveri") && callees.contains(&"_Z7cleanupiPi"));
+    }
+}
@@ -0,0 +1,306 @@
+//! GOT entries of linked files.
+
+use crate::layout::is_alloc;
+use crate::*;
+use section::PayloadReader;
+use thiserror::Error as TError;
+
+/// the size of a GOT slot in ELF64
+const GOT_ENTRY_SIZE: usize = 8;
+/// `.got.plt[0..3]` are reserved for `_DYNAMIC` and the lazy binding of the dynamic linker
+const RESERVED_ENTRIES: u64 = 3;
+
+#[derive(TError, Debug, Clone, PartialEq, Eq)]
+pub enum GotError {
+    #[error("no GOT entry is resolved to `{symbol}`")]
+    NotFound { symbol: String },
+    #[error(
+        "`{symbol}` is bound lazily through the PLT, use `transform::redirect_import()` instead"
+    )]
+    PltSlot { symbol: String },
+}
+
+/// The value a GOT entry holds after relocation
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum GotValue {
+    /// no dynamic relocation applies, so the value in the file is used as is
+    Constant(Elf64Xword),
+    /// the address of the symbol plus `addend`
+    Symbol {
+        name: String,
+        addend: Elf64Sxword,
+        /// bound through the PLT(`R_*_JUMP_SLOT`), and holds the address in the PLT until resolved
+        plt: bool,
+    },
+    /// the load base plus `addend`(`R_*_RELATIVE`)
+    Relative { addend: Elf64Sxword },
+    /// the address returned by the IFUNC resolver at the load base plus `resolver`(`R_*_IRELATIVE`)
+    IRelative { resolver: Elf64Addr },
+    /// the other relocations(e.g. TLS)
+    Other {
+        ty: Elf64Xword,
+        symbol: Option<String>,
+        addend: Elf64Sxword,
+    },
+}
+
+/// A slot in `.got` or `.got.plt`
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct GotEntry {
+    pub addr: Elf64Addr,
+    pub section: String,
+    /// the value in the file
+    pub stored: Elf64Xword,
+    pub value: GotValue,
+    /// one of the first three slots from `DT_PLTGOT`, used by the dynamic linker
+    pub reserved: bool,
+}
+
+/// list the slots of `.got` and `.got.plt`, and what each slot holds after relocation.
+///
+/// The slots are matched with the dynamic relocations(the `SHF_ALLOC` `SHT_RELA` sections) by the offset.
+/// Relocation types are known for x86_64 and AArch64; the others are reported as `GotValue::Other`.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::{analysis, builder, relocation};
+///
+/// // mov rax, [rip + puts@GOTPCREL]; ret
+/// let f = builder::ExportedFunction::new("f", vec![0x48, 0x8b, 0x05, 0, 0, 0, 0, 0xc3])
+///     .relocation(3, "puts", relocation::R_X86_64_GOTPCREL, -4);
+/// let elf = builder::SharedObjectWriter::new().function(f).build().unwrap();
+///
+/// let entries = analysis::got_entries(&elf);
+/// assert_eq!(1, entries.len());
+/// assert_eq!(
+///     analysis::GotValue::Symbol {
+///         name: "puts".to_string(),
+///         addend: 0,
+///         plt: false,
+///     },
+///     entries[0].value
+/// );
+/// ```
+pub fn got_entries(elf: &file::ELF64) -> Vec<GotEntry> {
+    let data = elf.ehdr.get_data();
+    let machine = elf.ehdr.get_machine();
+    let pltgot = pltgot(elf);
+    let relocations = dynamic_relocations(elf);
+
+    let mut entries = Vec::new();
+    for sct in elf.sections.iter() {
+        let is_got = sct.name == ".got"
+            || sct.name == ".got.plt"
+            || pltgot.is_some_and(|addr| addr == sct.header.sh_addr);
+        if !is_got || sct.header.get_type() != section::Type::ProgBits {
+            continue;
+        }
+        let bytes = sct.to_le_bytes();
+        let mut r = PayloadReader::new(&bytes, data);
+        while r.remaining() >= GOT_ENTRY_SIZE {
+            let addr = sct.header.sh_addr + r.position() as Elf64Addr;
+            let stored = r.u64().unwrap();
+            let value = match relocations
+                .iter()
+                .find(|(rela, _)| rela.get_offset() == addr)
+            {
+                Some((rela, symbol)) => classify(machine, rela, symbol.clone(), stored),
+                None => GotValue::Constant(stored),
+            };
+            let reserved = pltgot.is_some_and(|base| {
+                base <= addr && addr < base + RESERVED_ENTRIES * GOT_ENTRY_SIZE as Elf64Addr
+            });
+            entries.push(GotEntry {
+                addr,
+                section: sct.name.to_string(),
+                stored,
+                value,
+                reserved,
+            });
+        }
+    }
+    entries.sort_by_key(|ent| ent.addr);
+    entries.dedup_by_key(|ent| ent.addr);
+    entries
+}
+
+/// `DT_PLTGOT`
+fn pltgot(elf: &file::ELF64) -> Option<Elf64Addr> {
+    let dynamic = elf.first_section_by(|sct| sct.header.get_type() == section::Type::Dynamic)?;
+    match &dynamic.contents {
+        section::Contents64::Dynamics(entries) => entries
+            .iter()
+            .find(|ent| ent.get_type() == dynamic::EntryType::PLTGOT)
+            .map(|ent| ent.d_un),
+        _ => None,
+    }
+}
+
+/// the relocations applied by the dynamic linker, and the names of their symbols.
+pub(super) fn dynamic_relocations(elf: &file::ELF64) -> Vec<(relocation::Rela64, Option<String>)> {
+    let mut relocations = Vec::new();
+    for sct in elf.sections.iter().filter(|sct| is_alloc(&sct.header)) {
+        let relas = match &sct.contents {
+            section::Contents64::RelaSymbols(relas) => relas,
+            _ => continue,
+        };
+        let syms = match elf
+            .sections
+            .get(sct.header.sh_link as usize)
+            .map(|s| &s.contents)
+        {
+            Some(section::Contents64::Symbols(syms)) => Some(syms),
+            _ => None,
+        };
+        for rela in relas.iter() {
+            let symbol = syms
+                .and_then(|syms| syms.get(rela.get_sym() as usize))
+                .filter(|_| rela.get_sym() != 0)
+                .map(|sym| sym.symbol_name.to_string());
+            relocations.push((*rela, symbol));
+        }
+    }
+    relocations
+}
+
+/// the kind of the dynamic relocation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Kind {
+    None,
+    Symbol { plt: bool },
+    Relative,
+    IRelative,
+    Other,
+}
+
+pub(super) fn kind(machine: header::Machine, ty: Elf64Xword) -> Kind {
+    match machine {
+        header::Machine::X8664 => match ty {
+            relocation::R_X86_64_NONE => Kind::None,
+            relocation::R_X86_64_64 | relocation::R_X86_64_GLOB_DAT => Kind::Symbol { plt: false },
+            relocation::R_X86_64_JUMP_SLOT => Kind::Symbol { plt: true },
+            relocation::R_X86_64_RELATIVE => Kind::Relative,
+            relocation::R_X86_64_IRELATIVE => Kind::IRelative,
+            _ => Kind::Other,
+        },
+        header::Machine::Any(analysis::EM_AARCH64) => match ty {
+            relocation::R_AARCH64_NONE => Kind::None,
+            relocation::R_AARCH64_ABS64 | relocation::R_AARCH64_GLOB_DAT => {
+                Kind::Symbol { plt: false }
+            }
+            relocation::R_AARCH64_JUMP_SLOT => Kind::Symbol { plt: true },
+            relocation::R_AARCH64_RELATIVE => Kind::Relative,
+            relocation::R_AARCH64_IRELATIVE => Kind::IRelative,
+            _ => Kind::Other,
+        },
+        _ => Kind::Other,
+    }
+}
+
+fn classify(
+    machine: header::Machine,
+    rela: &relocation::Rela64,
+    symbol: Option<String>,
+    stored: Elf64Xword,
+) -> GotValue {
+    let addend = rela.get_addend();
+    match (kind(machine, rela.get_type()), symbol) {
+        (Kind::None, _) => GotValue::Constant(stored),
+        (Kind::Symbol { plt }, Some(name)) => GotValue::Symbol { name, addend, plt },
+        // シンボルなしの絶対アドレスは加数そのもの
+        (Kind::Symbol { plt: false }, None) => GotValue::Constant(addend as Elf64Xword),
+        (Kind::Relative, _) => GotValue::Relative { addend },
+        (Kind::IRelative, _) => GotValue::IRelative {
+            resolver: addend as Elf64Addr,
+        },
+        (_, symbol) => GotValue::Other {
+            ty: rela.get_type(),
+            symbol,
+            addend,
+        },
+    }
+}
+
+impl file::ELF64 {
+    /// make the GOT entries resolved to `symbol` hold `value` without the dynamic linker's lookup.
+    ///
+    /// `value` is an address in the file: each relocation is rewritten to `R_*_RELATIVE` so that
+    /// the load base is added, or to `R_*_NONE` for 0 to keep a null pointer.
+    /// The value is also written to the slot, for static executables and for tools reading the file.
+    /// Slots bound through the PLT are rejected since `.rela.plt` accepts only `R_*_JUMP_SLOT`(and `R_*_IRELATIVE`).
+    /// Returns the number of the updated slots.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use elf_utilities::{analysis, builder, relocation};
+    ///
+    /// let f = builder::ExportedFunction::new("f", vec![0x48, 0x8b, 0x05, 0, 0, 0, 0, 0xc3])
+    ///     .relocation(3, "puts", relocation::R_X86_64_GOTPCREL, -4);
+    /// let mut elf = builder::SharedObjectWriter::new().function(f).build().unwrap();
+    ///
+    /// assert_eq!(Ok(1), elf.set_got_entry("puts", 0x1000));
+    /// let entries = analysis::got_entries(&elf);
+    /// assert_eq!(analysis::GotValue::Relative { addend: 0x1000 }, entries[0].value);
+    /// assert_eq!(0x1000, entries[0].stored);
+    /// ```
+    pub fn set_got_entry(&mut self, symbol: &str, value: Elf64Addr) -> Result<usize, GotError> {
+        let slots: Vec<(Elf64Addr, bool)> = got_entries(self)
+            .into_iter()
+            .filter_map(|ent| match ent.value {
+                GotValue::Symbol { name, plt, .. } if name == symbol => Some((ent.addr, plt)),
+                _ => None,
+            })
+            .collect();
+        if slots.is_empty() {
+            return Err(GotError::NotFound {
+                symbol: symbol.to_string(),
+            });
+        }
+        if slots.iter().any(|(_, plt)| *plt) {
+            return Err(GotError::PltSlot {
+                symbol: symbol.to_string(),
+            });
+        }
+
+        let ty = match (self.ehdr.get_machine(), value) {
+            (header::Machine::X8664, 0) => relocation::R_X86_64_NONE,
+            (header::Machine::X8664, _) => relocation::R_X86_64_RELATIVE,
+            (_, 0) => relocation::R_AARCH64_NONE,
+            // シンボルとして解決されるのはx86_64とAArch64だけ
+            _ => relocation::R_AARCH64_RELATIVE,
+        };
+        let addend = if value == 0 { 0 } else { value as Elf64Sxword };
+        let bytes = match self.ehdr.get_data() {
+            header::Data::MSB2 => value.to_be_bytes(),
+            _ => value.to_le_bytes(),
+        };
+        for sct in self.sections.iter_mut() {
+            let header = sct.header;
+            match &mut sct.contents {
+                section::Contents64::RelaSymbols(relas) if is_alloc(&header) => {
+                    for rela in relas.iter_mut() {
+                        if slots.iter().any(|(addr, _)| *addr == rela.get_offset()) {
+                            rela.set_info(ty);
+                            rela.set_addend(addend);
+                        }
+                    }
+                }
+                section::Contents64::Raw(contents) if is_alloc(&header) => {
+                    for (addr, _) in slots.iter() {
+                        if header.sh_addr <= *addr
+                            && addr + GOT_ENTRY_SIZE as Elf64Addr
+                                <= header.sh_addr + contents.len() as Elf64Addr
+                        {
+                            let start = (addr - header.sh_addr) as usize;
+                            contents[start..start + GOT_ENTRY_SIZE].copy_from_slice(&bytes);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(slots.len())
+    }
+}
@@ -0,0 +1,221 @@
+//! GNU indirect functions(IFUNC) of linked files.
+
+use super::got::{dynamic_relocations, kind, Kind};
+use crate::*;
+
+/// An `R_*_IRELATIVE` relocation, which stores the result of the resolver
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct IRelative {
+    /// the address of the slot which receives the implementation
+    pub offset: Elf64Addr,
+    /// the address of the resolver(relative to the load base)
+    pub resolver: Elf64Addr,
+}
+
+/// An `STT_GNU_IFUNC` symbol
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Ifunc {
+    pub name: String,
+    /// `st_value` of IFUNC symbols is the address of the resolver, not of the implementation
+    pub resolver: Elf64Addr,
+    pub size: Elf64Xword,
+    /// the symbol is in `.dynsym`
+    pub dynamic: bool,
+    /// the `R_*_IRELATIVE`s calling the resolver
+    pub irelatives: Vec<IRelative>,
+}
+
+/// The result of `ifunc_map()`
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct IfuncMap {
+    pub ifuncs: Vec<Ifunc>,
+    /// the `R_*_IRELATIVE`s whose resolver has no IFUNC symbol(e.g. in stripped files)
+    pub unnamed: Vec<IRelative>,
+}
+
+impl IfuncMap {
+    pub fn get(&self, name: &str) -> Option<&Ifunc> {
+        self.ifuncs.iter().find(|ifunc| ifunc.name == name)
+    }
+
+    /// the IFUNC whose resolver is at `addr`.
+    pub fn by_resolver(&self, addr: Elf64Addr) -> Option<&Ifunc> {
+        self.ifuncs.iter().find(|ifunc| ifunc.resolver == addr)
+    }
+
+    /// the IFUNC whose implementation is stored to the slot at `addr`(e.g. a `.got.plt` entry).
+    pub fn by_slot(&self, addr: Elf64Addr) -> Option<&Ifunc> {
+        self.ifuncs
+            .iter()
+            .find(|ifunc| ifunc.irelatives.iter().any(|rel| rel.offset == addr))
+    }
+}
+
+/// list the `STT_GNU_IFUNC` symbols with their resolvers, and the `R_*_IRELATIVE` relocations calling them.
+///
+/// A naive symbolizer takes the address of an IFUNC symbol as the function,
+/// but it's the resolver choosing the implementation at load time.
+/// The symbols in `.symtab` and `.dynsym` are merged by the name and the address.
+/// `R_*_IRELATIVE` is known for x86_64 and AArch64.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::{analysis, file, header, relocation, section, symbol};
+///
+/// let mut elf = file::ELF64::default();
+/// elf.ehdr.set_machine(header::Machine::X8664);
+///
+/// let mut memcpy = symbol::Symbol64 {
+///     symbol_name: "memcpy".into(),
+///     st_value: 0x401000,
+///     st_shndx: 1,
+///     ..Default::default()
+/// };
+/// memcpy.set_info(symbol::Type::GNUIFunc, symbol::Bind::Global);
+/// elf.add_section(section::Section64::new(
+///     ".symtab".to_string(),
+///     section::ShdrPreparation64::default().ty(section::Type::SymTab).info(1),
+///     section::Contents64::Symbols(vec![symbol::Symbol64::new_null_symbol(), memcpy]),
+/// ));
+///
+/// let mut irelative = relocation::Rela64::default();
+/// irelative.set_offset(0x404018);
+/// irelative.set_info(relocation::R_X86_64_IRELATIVE);
+/// irelative.set_addend(0x401000);
+/// elf.add_section(section::Section64::new(
+///     ".rela.plt".to_string(),
+///     section::ShdrPreparation64::default()
+///         .ty(section::Type::Rela)
+///         .flags([section::Flag::Alloc].iter()),
+///     section::Contents64::RelaSymbols(vec![irelative]),
+/// ));
+///
+/// let map = analysis::ifunc_map(&elf);
+/// assert_eq!(0x401000, map.get("memcpy").unwrap().resolver);
+/// assert_eq!("memcpy", map.by_slot(0x404018).unwrap().name);
+/// ```
+pub fn ifunc_map(elf: &file::ELF64) -> IfuncMap {
+    let mut ifuncs: Vec<Ifunc> = Vec::new();
+    for sct in elf.sections.iter() {
+        let syms = match &sct.contents {
+            section::Contents64::Symbols(syms) => syms,
+            _ => continue,
+        };
+        let dynamic = sct.header.get_type() == section::Type::DynSym;
+        for sym in syms.iter() {
+            // 未定義のIFUNCシンボルは他のファイルのもの
+            if sym.get_type() != symbol::Type::GNUIFunc || sym.st_shndx == 0 {
+                continue;
+            }
+            match ifuncs
+                .iter_mut()
+                .find(|ifunc| ifunc.name == sym.symbol_name && ifunc.resolver == sym.st_value)
+            {
+                Some(ifunc) => ifunc.dynamic |= dynamic,
+                None => ifuncs.push(Ifunc {
+                    name: sym.symbol_name.to_string(),
+                    resolver: sym.st_value,
+                    size: sym.st_size,
+                    dynamic,
+                    irelatives: Vec::new(),
+                }),
+            }
+        }
+    }
+
+    let machine = elf.ehdr.get_machine();
+    let mut unnamed = Vec::new();
+    for (rela, _) in dynamic_relocations(elf) {
+        if kind(machine, rela.get_type()) != Kind::IRelative {
+            continue;
+        }
+        let irelative = IRelative {
+            offset: rela.get_offset(),
+            resolver: rela.get_addend() as Elf64Addr,
+        };
+        // 同じリゾルバを持つ別名(例: memcpyと__memcpy)の全てに対応付ける
+        let mut found = false;
+        for ifunc in ifuncs
+            .iter_mut()
+            .filter(|ifunc| ifunc.resolver == irelative.resolver)
+        {
+            ifunc.irelatives.push(irelative);
+            found = true;
+        }
+        if !found {
+            unnamed.push(irelative);
+        }
+    }
+
+    ifuncs.sort_by(|a, b| (a.resolver, &a.name).cmp(&(b.resolver, &b.name)));
+    IfuncMap { ifuncs, unnamed }
+}
+
+#[cfg(test)]
+mod ifunc_tests {
+    use super::*;
+
+    #[test]
+    fn ifunc_map_test() {
+        let mut elf = file::ELF64::default();
+        elf.ehdr.set_machine(header::Machine::X8664);
+
+        let ifunc = |name: &str| {
+            let mut sym = symbol::Symbol64 {
+                symbol_name: name.into(),
+                st_value: 0x1000,
+                st_shndx: 1,
+                ..Default::default()
+            };
+            sym.set_info(symbol::Type::GNUIFunc, symbol::Bind::Global);
+            sym
+        };
+        let null = symbol::Symbol64::new_null_symbol();
+        elf.add_section(section::Section64::new(
+            ".symtab".to_string(),
+            section::ShdrPreparation64::default().ty(section::Type::SymTab),
+            section::Contents64::Symbols(vec![null.clone(), ifunc("memcpy"), ifunc("__memcpy")]),
+        ));
+        elf.add_section(section::Section64::new(
+            ".dynsym".to_string(),
+            section::ShdrPreparation64::default().ty(section::Type::DynSym),
+            section::Contents64::Symbols(vec![null, ifunc("memcpy")]),
+        ));
+        let relas = [(0x3000, 0x1000), (0x3008, 0x2000)]
+            .iter()
+            .map(|(offset, resolver)| {
+                let mut rela = relocation::Rela64::default();
+                rela.set_offset(*offset);
+                rela.set_info(relocation::R_X86_64_IRELATIVE);
+                rela.set_addend(*resolver);
+                rela
+            })
+            .collect();
+        elf.add_section(section::Section64::new(
+            ".rela.plt".to_string(),
+            section::ShdrPreparation64::default()
+                .ty(section::Type::Rela)
+                .flags([section::Flag::Alloc].iter()),
+            section::Contents64::RelaSymbols(relas),
+        ));
+
+        let map = ifunc_map(&elf);
+        // 別名は両方とも同じIRELATIVEを持つ
+        let names: Vec<(&str, bool)> = map
+            .ifuncs
+            .iter()
+            .map(|f| (f.name.as_str(), f.dynamic))
+            .collect();
+        assert_eq!(vec![("__memcpy", false), ("memcpy", true)], names);
+        assert!(map.ifuncs.iter().all(|f| f.irelatives.len() == 1));
+        assert_eq!(Some(0x1000), map.by_slot(0x3000).map(|f| f.resolver));
+        assert_eq!(
+            vec![IRelative {
+                offset: 0x3008,
+                resolver: 0x2000
+            }],
+            map.unnamed
+        );
+    }
+}
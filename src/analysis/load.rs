@@ -0,0 +1,387 @@
+//! Dry-running the checks of the kernel and the dynamic linker.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::*;
+
+/// `PATH_MAX` on Linux, the limit of `PT_INTERP`
+const PATH_MAX: Elf64Xword = 4096;
+
+/// The environment which `simulate_load()` loads a file in
+///
+/// The paths of the interpreter and the libraries are resolved under `root`,
+/// like in `chroot(2)`.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::analysis;
+///
+/// let env = analysis::LoadEnvironment::new()
+///     .root("/srv/rootfs")
+///     .search_path(&["/opt/lib", "/usr/lib"]);
+/// assert_eq!(2, env.get_search_path().len());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct LoadEnvironment {
+    root: PathBuf,
+    search_path: Vec<String>,
+    page_size: Elf64Xword,
+}
+
+impl Default for LoadEnvironment {
+    fn default() -> Self {
+        Self {
+            root: PathBuf::from("/"),
+            search_path: [
+                "/lib/x86_64-linux-gnu",
+                "/usr/lib/x86_64-linux-gnu",
+                "/lib64",
+                "/usr/lib64",
+                "/lib",
+                "/usr/lib",
+            ]
+            .iter()
+            .map(|dir| dir.to_string())
+            .collect(),
+            page_size: 0x1000,
+        }
+    }
+}
+
+impl LoadEnvironment {
+    /// the root directory, and the default search path of glibc on x86_64.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn root<P: AsRef<Path>>(mut self, root: P) -> Self {
+        self.root = root.as_ref().to_path_buf();
+        self
+    }
+
+    /// the directories searched for `DT_NEEDED` libraries, in order.
+    pub fn search_path(mut self, dirs: &[&str]) -> Self {
+        self.search_path = dirs.iter().map(|dir| dir.to_string()).collect();
+        self
+    }
+
+    pub fn page_size(mut self, page_size: Elf64Xword) -> Self {
+        self.page_size = page_size;
+        self
+    }
+
+    pub fn get_search_path(&self) -> &[String] {
+        &self.search_path
+    }
+
+    /// `path` under the root directory.
+    fn resolve(&self, path: &str) -> PathBuf {
+        self.root.join(path.trim_start_matches('/'))
+    }
+}
+
+/// The first check which fails in `simulate_load()`
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LoadFailure {
+    /// `e_type` is neither `ET_EXEC` nor `ET_DYN`
+    NotExecutable { ty: header::Type },
+    /// `e_phentsize` isn't the size of `Elf64_Phdr`
+    InvalidPhentsize { phentsize: Elf64Half },
+    /// `e_phnum` is zero or the table exceeds 64KiB
+    InvalidPhnum { phnum: Elf64Half },
+    /// `PT_INTERP` is empty, too long or not terminated by NUL
+    InvalidInterpreter { filesz: Elf64Xword },
+    /// the file in `PT_INTERP` doesn't exist
+    InterpreterNotFound { path: String },
+    /// `p_offset` and `p_vaddr` of a `PT_LOAD` aren't congruent modulo the page size
+    MisalignedSegment {
+        index: usize,
+        offset: Elf64Off,
+        vaddr: Elf64Addr,
+    },
+    /// `p_filesz` of a `PT_LOAD` exceeds `p_memsz`
+    FileSizeExceedsMemSize {
+        index: usize,
+        filesz: Elf64Xword,
+        memsz: Elf64Xword,
+    },
+    /// a `DT_NEEDED` library isn't found in the search path
+    LibraryNotFound { name: String },
+    /// a library doesn't define a version required in `.gnu.version_r`
+    VersionNotFound { version: String, library: String },
+}
+
+impl LoadFailure {
+    /// the errno which `execve(2)` or `dlopen(3)` fails with,
+    /// or `None` for the errors which the dynamic linker reports without one.
+    pub fn errno(&self) -> Option<&'static str> {
+        match self {
+            Self::NotExecutable { .. }
+            | Self::InvalidPhentsize { .. }
+            | Self::InvalidPhnum { .. }
+            | Self::InvalidInterpreter { .. } => Some("ENOEXEC"),
+            Self::MisalignedSegment { .. } | Self::FileSizeExceedsMemSize { .. } => Some("EINVAL"),
+            Self::InterpreterNotFound { .. } | Self::LibraryNotFound { .. } => Some("ENOENT"),
+            Self::VersionNotFound { .. } => None,
+        }
+    }
+}
+
+impl fmt::Display for LoadFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(errno) = self.errno() {
+            write!(f, "{}: ", errno)?;
+        }
+        match self {
+            Self::NotExecutable { ty } => write!(
+                f,
+                "the file is {}, but the kernel executes only EXEC and DYN",
+                ty
+            ),
+            Self::InvalidPhentsize { phentsize } => write!(
+                f,
+                "e_phentsize is {}, but the kernel requires {}",
+                phentsize,
+                segment::Phdr64::SIZE
+            ),
+            Self::InvalidPhnum { phnum } => write!(
+                f,
+                "e_phnum is {}, but the program headers must be 1 to 64KiB",
+                phnum
+            ),
+            Self::InvalidInterpreter { filesz } => write!(
+                f,
+                "PT_INTERP is {} bytes, which isn't a NUL-terminated path",
+                filesz
+            ),
+            Self::InterpreterNotFound { path } => write!(
+                f,
+                "the interpreter `{}` doesn't exist; the shell reports the executable itself as missing",
+                path
+            ),
+            Self::MisalignedSegment {
+                index,
+                offset,
+                vaddr,
+            } => write!(
+                f,
+                "PT_LOAD [{}] maps offset {:#x} at {:#x}, which differ within a page, so mmap(2) fails",
+                index, offset, vaddr
+            ),
+            Self::FileSizeExceedsMemSize {
+                index,
+                filesz,
+                memsz,
+            } => write!(
+                f,
+                "PT_LOAD [{}] has {:#x} bytes in the file but only {:#x} bytes in memory",
+                index, filesz, memsz
+            ),
+            Self::LibraryNotFound { name } => write!(
+                f,
+                "the dynamic linker can't find `{}` in the search path",
+                name
+            ),
+            Self::VersionNotFound { version, library } => write!(
+                f,
+                "version `{}` is required but not defined by `{}`",
+                version, library
+            ),
+        }
+    }
+}
+
+/// run the main checks of `execve(2)` and the dynamic linker, without executing the file,
+/// and report the first one which fails.
+///
+/// The checks are done in the order of the kernel and `ld.so`:
+///
+/// 1. `e_type`, `e_phentsize` and `e_phnum`
+/// 2. `PT_INTERP` is a NUL-terminated path, and the file exists
+/// 3. each `PT_LOAD` can be mapped with the page size
+/// 4. each `DT_NEEDED` library is found in the search path(only the direct dependencies)
+/// 5. each library defines the versions required in `.gnu.version_r`
+///
+/// Libraries which aren't 64-bit ELF of the same machine are skipped, like `ld.so` does.
+/// A library without `.gnu.version_d` satisfies any version, for which `ld.so` only warns.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::{analysis, parser};
+///
+/// let elf = parser::parse_elf64("src/parser/testdata/sample").unwrap();
+/// let dir = std::env::temp_dir().join(format!("elf_utilities_load_doctest_{}", std::process::id()));
+/// std::fs::create_dir_all(&dir).unwrap();
+/// let env = analysis::LoadEnvironment::new().root(&dir);
+///
+/// // 空のルートにはインタプリタが無い
+/// let failure = analysis::simulate_load(&elf, &env).unwrap_err();
+/// assert_eq!(Some("ENOENT"), failure.errno());
+/// assert!(failure.to_string().contains("/lib64/ld-linux-x86-64.so.2"));
+/// # std::fs::remove_dir_all(&dir).unwrap();
+/// ```
+pub fn simulate_load(elf: &file::ELF64, env: &LoadEnvironment) -> Result<(), LoadFailure> {
+    let ehdr = &elf.ehdr;
+    match ehdr.get_type() {
+        header::Type::Exec | header::Type::Dyn => {}
+        ty => return Err(LoadFailure::NotExecutable { ty }),
+    }
+    if ehdr.e_phentsize as usize != segment::Phdr64::SIZE {
+        return Err(LoadFailure::InvalidPhentsize {
+            phentsize: ehdr.e_phentsize,
+        });
+    }
+    if ehdr.e_phnum == 0 || ehdr.e_phnum as usize * segment::Phdr64::SIZE > 0x10000 {
+        return Err(LoadFailure::InvalidPhnum {
+            phnum: ehdr.e_phnum,
+        });
+    }
+
+    let interp = elf
+        .segments
+        .iter()
+        .find(|sgt| sgt.header.get_type() == segment::Type::Interp);
+    if let Some(interp) = interp {
+        let filesz = interp.header.p_filesz;
+        let terminated = elf
+            .file_bytes(interp.header.p_offset, filesz)
+            .is_some_and(|bytes| bytes.last() == Some(&0));
+        if !(2..=PATH_MAX).contains(&filesz) || !terminated {
+            return Err(LoadFailure::InvalidInterpreter { filesz });
+        }
+        // 終端を確認済みなので必ず読める
+        let path = elf.interpreter().unwrap();
+        if !env.resolve(&path).is_file() {
+            return Err(LoadFailure::InterpreterNotFound { path });
+        }
+    }
+
+    for (index, sgt) in elf.segments.iter().enumerate() {
+        let phdr = &sgt.header;
+        if phdr.get_type() != segment::Type::Load {
+            continue;
+        }
+        if phdr.p_offset % env.page_size != phdr.p_vaddr % env.page_size {
+            return Err(LoadFailure::MisalignedSegment {
+                index,
+                offset: phdr.p_offset,
+                vaddr: phdr.p_vaddr,
+            });
+        }
+        if phdr.p_filesz > phdr.p_memsz {
+            return Err(LoadFailure::FileSizeExceedsMemSize {
+                index,
+                filesz: phdr.p_filesz,
+                memsz: phdr.p_memsz,
+            });
+        }
+    }
+
+    // 静的リンクされたファイルは動的リンカを通らない
+    if interp.is_none() {
+        return Ok(());
+    }
+    let mut libraries = Vec::new();
+    for name in elf.needed() {
+        match find_library(elf, env, &name) {
+            Some(lib) => libraries.push((name, lib)),
+            None => return Err(LoadFailure::LibraryNotFound { name }),
+        }
+    }
+
+    // 壊れた.gnu.version_rはld.soも読めないので検査しない
+    for need in elf.version_needs().unwrap_or_default() {
+        let lib = match libraries.iter().find(|(name, _)| *name == need.file) {
+            Some((_, lib)) => lib,
+            None => continue,
+        };
+        let defs = lib.version_definitions().unwrap_or_default();
+        if defs.is_empty() {
+            continue;
+        }
+        for version in need.versions.iter().filter(|v| !v.is_weak()) {
+            if !defs.iter().any(|def| def.name == version.name) {
+                return Err(LoadFailure::VersionNotFound {
+                    version: version.name.clone(),
+                    library: need.file.clone(),
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// the first loadable library of the name in the search path.
+/// a name with a slash is a path, which isn't searched.
+fn find_library(elf: &file::ELF64, env: &LoadEnvironment, name: &str) -> Option<file::ELF64> {
+    let candidates: Vec<PathBuf> = if name.contains('/') {
+        vec![env.resolve(name)]
+    } else {
+        env.search_path
+            .iter()
+            .map(|dir| env.resolve(dir).join(name))
+            .collect()
+    };
+    candidates
+        .iter()
+        .find_map(|path| super::open_library(path, elf.ehdr.e_machine))
+}
+
+#[cfg(test)]
+mod load_tests {
+    use super::*;
+
+    #[test]
+    fn simulate_load_test() {
+        let elf = parser::parse_elf64("src/parser/testdata/sample").unwrap();
+        let root = std::env::temp_dir().join(format!(
+            "elf_utilities_simulate_load_{}",
+            std::process::id()
+        ));
+        let lib = root.join("lib");
+        std::fs::create_dir_all(root.join("lib64")).unwrap();
+        std::fs::create_dir_all(&lib).unwrap();
+        std::fs::write(root.join("lib64/ld-linux-x86-64.so.2"), b"").unwrap();
+        let env = LoadEnvironment::new().root(&root).search_path(&["/lib"]);
+
+        assert_eq!(
+            Err(LoadFailure::LibraryNotFound {
+                name: "libc.so.6".to_string()
+            }),
+            simulate_load(&elf, &env)
+        );
+
+        // バージョン定義を持たないlibcは何でも満たす
+        let answer =
+            builder::ExportedFunction::new("answer", vec![0xb8, 0x2a, 0x00, 0x00, 0x00, 0xc3]);
+        let mut libc = builder::SharedObjectWriter::new()
+            .soname("libc.so.6")
+            .function(answer)
+            .build()
+            .unwrap();
+        std::fs::write(lib.join("libc.so.6"), libc.to_le_bytes()).unwrap();
+        assert_eq!(Ok(()), simulate_load(&elf, &env));
+
+        let script = transform::VersionScript::parse("GLIBC_2.34 { global: answer; };").unwrap();
+        transform::apply_version_script(&mut libc, &script).unwrap();
+        std::fs::write(lib.join("libc.so.6"), libc.to_le_bytes()).unwrap();
+        let failure = simulate_load(&elf, &env).unwrap_err();
+        assert!(matches!(failure, LoadFailure::VersionNotFound { .. }));
+        assert_eq!(None, failure.errno());
+
+        let mut broken = elf.clone();
+        let load = broken
+            .segments
+            .iter()
+            .position(|sgt| sgt.header.get_type() == segment::Type::Load)
+            .unwrap();
+        broken.segments[load].header.p_vaddr += 0x10;
+        let failure = simulate_load(&broken, &env).unwrap_err();
+        assert_eq!(Some("EINVAL"), failure.errno());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
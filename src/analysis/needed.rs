@@ -0,0 +1,459 @@
+//! Resolving `DT_NEEDED` libraries in the search order of the dynamic linker.
+
+use std::path::{Path, PathBuf};
+
+use crate::file::glob_match;
+use crate::*;
+
+/// Where a library is found
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SearchSource {
+    /// the name contains a slash, so it's used as a path
+    Path,
+    /// `DT_RPATH`, which is used only without `DT_RUNPATH`
+    RPath,
+    LdLibraryPath,
+    /// `DT_RUNPATH`
+    RunPath,
+    /// the directories configured in `ld.so.conf`(cached in `ld.so.cache`)
+    LdSoConf,
+    /// the trusted directories of the dynamic linker
+    Default,
+}
+
+/// The configuration of `resolve_needed()`
+///
+/// The directories are resolved under `root`, like in `chroot(2)`.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::analysis;
+///
+/// let config = analysis::SearchConfig::new()
+///     .origin("/opt/app/bin")
+///     .ld_library_path("/opt/app/lib::/usr/local/lib");
+///
+/// // 空の要素は無視される
+/// assert_eq!(2, config.get_ld_library_path().len());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SearchConfig {
+    root: PathBuf,
+    origin: Option<String>,
+    ld_library_path: Vec<String>,
+    system_dirs: Vec<String>,
+    default_dirs: Vec<String>,
+    /// the expanded `DT_RPATH` of the files which loaded the file, the nearest first
+    loader_rpath: Vec<String>,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        Self {
+            root: PathBuf::from("/"),
+            origin: None,
+            ld_library_path: Vec::new(),
+            system_dirs: Vec::new(),
+            default_dirs: ["/lib64", "/usr/lib64", "/lib", "/usr/lib"]
+                .iter()
+                .map(|dir| dir.to_string())
+                .collect(),
+            loader_rpath: Vec::new(),
+        }
+    }
+}
+
+impl SearchConfig {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn root<P: AsRef<Path>>(mut self, root: P) -> Self {
+        self.root = root.as_ref().to_path_buf();
+        self
+    }
+
+    /// the directory of the file, which `$ORIGIN` expands to.
+    /// without it, the entries with `$ORIGIN` are skipped.
+    pub fn origin(mut self, dir: &str) -> Self {
+        self.origin = Some(dir.to_string());
+        self
+    }
+
+    /// the value of `LD_LIBRARY_PATH`, separated by `:` or `;`.
+    pub fn ld_library_path(mut self, value: &str) -> Self {
+        self.ld_library_path = split_path(value);
+        self
+    }
+
+    /// the directories configured in `ld.so.conf`.
+    pub fn system_dirs(mut self, dirs: &[&str]) -> Self {
+        self.system_dirs = dirs.iter().map(|dir| dir.to_string()).collect();
+        self
+    }
+
+    /// the directories searched at last, `/lib64`, `/usr/lib64`, `/lib` and `/usr/lib` by default.
+    pub fn default_dirs(mut self, dirs: &[&str]) -> Self {
+        self.default_dirs = dirs.iter().map(|dir| dir.to_string()).collect();
+        self
+    }
+
+    /// read the directories from `ld.so.conf` at `path` under the root directory,
+    /// following the `include` directives.
+    pub fn ld_so_conf(mut self, path: &str) -> std::io::Result<Self> {
+        let mut dirs = Vec::new();
+        self.read_ld_so_conf(path, &mut dirs, 0)?;
+        self.system_dirs = dirs;
+        Ok(self)
+    }
+
+    pub fn get_ld_library_path(&self) -> &[String] {
+        &self.ld_library_path
+    }
+
+    pub fn get_system_dirs(&self) -> &[String] {
+        &self.system_dirs
+    }
+
+    fn read_ld_so_conf(
+        &self,
+        path: &str,
+        dirs: &mut Vec<String>,
+        depth: usize,
+    ) -> std::io::Result<()> {
+        // 循環するincludeで止まらないように深さを制限する
+        if depth > 8 {
+            return Ok(());
+        }
+        let text = std::fs::read_to_string(self.resolve(path))?;
+        let base = Path::new(path).parent().unwrap_or_else(|| Path::new("/"));
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            let mut words = line.split_whitespace();
+            match words.next() {
+                Some("include") => {
+                    for pattern in words {
+                        for included in self.expand_include(&base.join(pattern)) {
+                            self.read_ld_so_conf(&included, dirs, depth + 1)?;
+                        }
+                    }
+                }
+                // hwcapの行は古いglibcのためのもの
+                Some("hwcap") | None => {}
+                Some(_) => {
+                    let found = line
+                        .split(|c: char| c.is_whitespace() || c == ':' || c == ',')
+                        .filter(|dir| !dir.is_empty())
+                        .map(|dir| dir.trim_end_matches('/').to_string());
+                    for dir in found {
+                        if !dirs.contains(&dir) {
+                            dirs.push(dir);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// the files matching the glob in the last component of `pattern`, sorted by the name.
+    fn expand_include(&self, pattern: &Path) -> Vec<String> {
+        let (dir, name) = match (pattern.parent(), pattern.file_name()) {
+            (Some(dir), Some(name)) => (dir, name.to_string_lossy().to_string()),
+            _ => return Vec::new(),
+        };
+        let entries = match std::fs::read_dir(self.resolve(&dir.to_string_lossy())) {
+            Ok(entries) => entries,
+            Err(_) => return Vec::new(),
+        };
+        let mut names: Vec<String> = entries
+            .filter_map(|ent| ent.ok())
+            .map(|ent| ent.file_name().to_string_lossy().to_string())
+            .filter(|n| glob_match(&name, n))
+            .collect();
+        names.sort();
+        names
+            .into_iter()
+            .map(|n| dir.join(n).to_string_lossy().to_string())
+            .collect()
+    }
+
+    /// the configuration for the file at `path` under the root directory,
+    /// where `$ORIGIN` is the directory of `path`.
+    pub(super) fn for_file(&self, path: &Path) -> Self {
+        let mut config = self.clone();
+        config.origin = path
+            .parent()
+            .and_then(|dir| dir.strip_prefix(&self.root).ok())
+            .map(|dir| format!("/{}", dir.to_string_lossy()));
+        config
+    }
+
+    /// the configuration for the library at `path` loaded by `loader`, which inherits `DT_RPATH` of it.
+    pub(super) fn for_library(&self, loader: &file::ELF64, path: &Path) -> Self {
+        let mut config = self.for_file(path);
+        config.loader_rpath = rpath_dirs(loader, self);
+        config
+            .loader_rpath
+            .extend(self.loader_rpath.iter().cloned());
+        config
+    }
+
+    /// `path` under the root directory.
+    pub(crate) fn resolve(&self, path: &str) -> PathBuf {
+        self.root.join(path.trim_start_matches('/'))
+    }
+}
+
+/// The resolution of a `DT_NEEDED` entry
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ResolvedDep {
+    /// the name in `DT_NEEDED`
+    pub name: String,
+    /// the path of the library under the root directory, or `None` if it isn't found
+    pub path: Option<PathBuf>,
+    /// where `path` is found
+    pub source: Option<SearchSource>,
+    /// every path tried, in order
+    pub searched: Vec<PathBuf>,
+}
+
+impl ResolvedDep {
+    pub fn is_found(&self) -> bool {
+        self.path.is_some()
+    }
+}
+
+/// resolve each `DT_NEEDED` of the file to the library which the dynamic linker would load.
+///
+/// The directories are searched in the order of glibc's `ld.so`:
+///
+/// 1. `DT_RPATH` of the file and the files which loaded it, if the file has no `DT_RUNPATH`
+/// 2. `LD_LIBRARY_PATH`
+/// 3. `DT_RUNPATH` of the file
+/// 4. the directories of `ld.so.conf`
+/// 5. the default directories
+///
+/// `$ORIGIN`, `$LIB` and `$PLATFORM` are expanded in the paths.
+/// A file which isn't a 64-bit ELF of the same machine is skipped, like `ld.so` does.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::{analysis, builder};
+///
+/// let f = builder::ExportedFunction::new("f", vec![0xc3]);
+/// let lib = builder::SharedObjectWriter::new()
+///     .function(f.clone())
+///     .soname("libdep.so")
+///     .build()
+///     .unwrap();
+/// let app = builder::SharedObjectWriter::new()
+///     .function(f)
+///     .needed("libdep.so")
+///     .runpath("$ORIGIN/../lib")
+///     .build()
+///     .unwrap();
+///
+/// let root = std::env::temp_dir().join(format!("elf_utilities_needed_doctest_{}", std::process::id()));
+/// std::fs::create_dir_all(root.join("app/bin")).unwrap();
+/// std::fs::create_dir_all(root.join("app/lib")).unwrap();
+/// std::fs::write(root.join("app/lib/libdep.so"), lib.to_le_bytes()).unwrap();
+///
+/// let config = analysis::SearchConfig::new().root(&root).origin("/app/bin");
+/// let deps = analysis::resolve_needed(&app, &config);
+/// assert_eq!(Some(root.join("app/bin/../lib/libdep.so")), deps[0].path);
+/// assert_eq!(Some(analysis::SearchSource::RunPath), deps[0].source);
+/// # std::fs::remove_dir_all(&root).unwrap();
+/// ```
+pub fn resolve_needed(elf: &file::ELF64, config: &SearchConfig) -> Vec<ResolvedDep> {
+    let runpath = elf.dynamic_strings(dynamic::EntryType::RunPath);
+    let rpath = if runpath.is_empty() {
+        let mut dirs = rpath_dirs(elf, config);
+        dirs.extend(config.loader_rpath.iter().cloned());
+        dirs
+    } else {
+        Vec::new()
+    };
+    let order = [
+        (SearchSource::RPath, rpath),
+        (
+            SearchSource::LdLibraryPath,
+            expand_paths(elf, config, &config.ld_library_path),
+        ),
+        (SearchSource::RunPath, expand_paths(elf, config, &runpath)),
+        (SearchSource::LdSoConf, config.system_dirs.clone()),
+        (SearchSource::Default, config.default_dirs.clone()),
+    ];
+
+    let mut deps = Vec::new();
+    for name in elf.needed() {
+        let mut dep = ResolvedDep {
+            name: name.clone(),
+            path: None,
+            source: None,
+            searched: Vec::new(),
+        };
+        let candidates: Vec<(SearchSource, PathBuf)> = if name.contains('/') {
+            expand_tokens(elf, config, &name)
+                .map(|path| (SearchSource::Path, config.resolve(&path)))
+                .into_iter()
+                .collect()
+        } else {
+            let name = name.as_str();
+            order
+                .iter()
+                .flat_map(|(source, dirs)| {
+                    dirs.iter()
+                        .map(move |dir| (*source, config.resolve(dir).join(name)))
+                })
+                .collect()
+        };
+        for (source, path) in candidates {
+            // 同じパスは一度しか試さない
+            if dep.searched.contains(&path) {
+                continue;
+            }
+            dep.searched.push(path.clone());
+            if open_library(&path, elf.ehdr.e_machine).is_some() {
+                dep.path = Some(path);
+                dep.source = Some(source);
+                break;
+            }
+        }
+        deps.push(dep);
+    }
+    deps
+}
+
+/// parse the file at `path` if the dynamic linker can load it for a file of the machine.
+pub(super) fn open_library(path: &Path, machine: Elf64Half) -> Option<file::ELF64> {
+    if !path.is_file() {
+        return None;
+    }
+    match parser::parse_elf(path.to_str()?).ok()? {
+        file::ELF::ELF64(lib) if lib.ehdr.e_machine == machine => Some(lib),
+        _ => None,
+    }
+}
+
+/// the expanded `DT_RPATH` of the file, which is ignored with `DT_RUNPATH`.
+fn rpath_dirs(elf: &file::ELF64, config: &SearchConfig) -> Vec<String> {
+    if !elf.dynamic_strings(dynamic::EntryType::RunPath).is_empty() {
+        return Vec::new();
+    }
+    expand_paths(elf, config, &elf.dynamic_strings(dynamic::EntryType::RPath))
+}
+
+fn expand_paths(elf: &file::ELF64, config: &SearchConfig, paths: &[String]) -> Vec<String> {
+    paths
+        .iter()
+        .flat_map(|path| split_path(path))
+        .filter_map(|dir| expand_tokens(elf, config, &dir))
+        .collect()
+}
+
+fn split_path(value: &str) -> Vec<String> {
+    value
+        .split([':', ';'])
+        .filter(|dir| !dir.is_empty())
+        .map(|dir| dir.to_string())
+        .collect()
+}
+
+/// expand `$ORIGIN`, `$LIB` and `$PLATFORM`(also in braces),
+/// or `None` if a token can't be expanded.
+fn expand_tokens(elf: &file::ELF64, config: &SearchConfig, path: &str) -> Option<String> {
+    // EM_X86_64とEM_AARCH64
+    let platform = match elf.ehdr.e_machine {
+        62 => Some("x86_64"),
+        183 => Some("aarch64"),
+        _ => None,
+    };
+    let mut expanded = path.to_string();
+    for (token, value) in [
+        ("ORIGIN", config.origin.as_deref()),
+        ("LIB", Some("lib64")),
+        ("PLATFORM", platform),
+    ]
+    .iter()
+    {
+        let braced = format!("${{{}}}", token);
+        let plain = format!("${}", token);
+        if !expanded.contains(&braced) && !expanded.contains(&plain) {
+            continue;
+        }
+        let value = (*value)?;
+        expanded = expanded.replace(&braced, value).replace(&plain, value);
+    }
+    Some(expanded)
+}
+
+#[cfg(test)]
+mod needed_tests {
+    use super::*;
+
+    #[test]
+    fn search_order_test() {
+        let root =
+            std::env::temp_dir().join(format!("elf_utilities_search_order_{}", std::process::id()));
+        let f = builder::ExportedFunction::new("f", vec![0xc3]);
+        let lib = builder::SharedObjectWriter::new()
+            .function(f.clone())
+            .build()
+            .unwrap();
+        for dir in ["env", "runpath", "conf", "usr/lib64"].iter() {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+            std::fs::write(root.join(dir).join("libdep.so"), lib.to_le_bytes()).unwrap();
+        }
+        std::fs::create_dir_all(root.join("etc/ld.so.conf.d")).unwrap();
+        std::fs::write(root.join("etc/ld.so.conf"), "include ld.so.conf.d/*.conf\n").unwrap();
+        std::fs::write(root.join("etc/ld.so.conf.d/a.conf"), "# comment\n/conf\n").unwrap();
+
+        let mut elf = builder::SharedObjectWriter::new()
+            .function(f)
+            .needed("libdep.so")
+            .runpath("/runpath")
+            .build()
+            .unwrap();
+        let config = SearchConfig::new()
+            .root(&root)
+            .ld_so_conf("/etc/ld.so.conf")
+            .unwrap();
+        assert_eq!(&["/conf".to_string()], config.get_system_dirs());
+        let source =
+            |elf: &file::ELF64, config: &SearchConfig| resolve_needed(elf, config)[0].source;
+
+        // DT_RUNPATHはLD_LIBRARY_PATHより後
+        assert_eq!(Some(SearchSource::RunPath), source(&elf, &config));
+        let env = config.clone().ld_library_path("/env");
+        assert_eq!(Some(SearchSource::LdLibraryPath), source(&elf, &env));
+
+        // DT_RPATHはLD_LIBRARY_PATHより前
+        let dynamic = elf
+            .first_mut_section_by(|sct| sct.header.get_type() == section::Type::Dynamic)
+            .unwrap();
+        if let section::Contents64::Dynamics(entries) = &mut dynamic.contents {
+            for ent in entries.iter_mut() {
+                if ent.get_type() == dynamic::EntryType::RunPath {
+                    ent.set_type(dynamic::EntryType::RPath);
+                }
+            }
+        }
+        assert_eq!(Some(SearchSource::RPath), source(&elf, &env));
+
+        std::fs::remove_dir_all(root.join("runpath")).unwrap();
+        assert_eq!(Some(SearchSource::LdLibraryPath), source(&elf, &env));
+        std::fs::remove_dir_all(root.join("env")).unwrap();
+        assert_eq!(Some(SearchSource::LdSoConf), source(&elf, &env));
+        std::fs::remove_dir_all(root.join("conf")).unwrap();
+        assert_eq!(Some(SearchSource::Default), source(&elf, &env));
+        std::fs::remove_dir_all(root.join("usr")).unwrap();
+        let dep = &resolve_needed(&elf, &env)[0];
+        assert!(!dep.is_found());
+        assert_eq!(root.join("runpath/libdep.so"), dep.searched[0]);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
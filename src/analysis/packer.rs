@@ -0,0 +1,394 @@
+//! Recognition of packed executables and pluggable unpackers.
+
+use std::fmt;
+
+use thiserror::Error as TError;
+
+use crate::*;
+
+/// `l_magic` of UPX's `l_info` and `p_magic` of the pack header
+pub const UPX_MAGIC: &[u8; 4] = b"UPX!";
+
+/// the banner UPX embeds into the decompressor
+const UPX_BANNER: &[u8] = b"$Id: UPX ";
+
+/// a loadable segment whose memory size is this many times larger than the file size
+/// is regarded as a decompression area
+const EXPANSION_RATIO: u64 = 4;
+
+/// A known packer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Packer {
+    Upx,
+    /// the file looks packed, but the packer isn't identified
+    Unknown,
+}
+
+impl fmt::Display for Packer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Upx => write!(f, "UPX"),
+            Self::Unknown => write!(f, "unknown packer"),
+        }
+    }
+}
+
+/// A trait of packed files found by `detect_packer()`
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PackerIndicator {
+    /// `UPX!` just after the program header table(`l_info`)
+    UpxLoaderInfo { offset: usize },
+    /// `UPX!` in the bytes after all segments and sections
+    UpxOverlay { offset: usize },
+    /// a section named like `UPX0`
+    UpxSection { name: String },
+    /// `$Id: UPX <version>` in the file
+    UpxBanner { offset: usize, version: String },
+    /// the file has no section header table
+    NoSectionHeaders,
+    /// a `PT_LOAD` segment which is both writable and executable
+    WritableExecutableSegment { index: usize },
+    /// a `PT_LOAD` segment whose memory size is much larger than the file size
+    ExpandingSegment { index: usize },
+}
+
+/// The result of `detect_packer()`
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PackerReport {
+    pub packer: Packer,
+    /// the version of the packer if it's embedded
+    pub version: Option<String>,
+    pub indicators: Vec<PackerIndicator>,
+}
+
+/// detect whether `elf` is packed.
+///
+/// `bytes` must be the contents of the file `elf` is parsed from,
+/// since packers hide their metadata outside of sections.
+/// A file is regarded as packed by UPX if `UPX!` is found after the program header table,
+/// in the overlay or as a section name.
+/// Otherwise, a file with two or more structural traits
+/// (no section headers, writable and executable segments and expanding segments)
+/// is reported as packed by an unknown packer.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::{analysis, builder};
+///
+/// let main = builder::ExportedFunction::new("main", vec![0xc3]);
+/// let elf = builder::ExecutableWriter::new()
+///     .function(main)
+///     .start_stub(builder::start_stub("main"))
+///     .build()
+///     .unwrap();
+/// assert_eq!(None, analysis::detect_packer(&elf, &elf.to_le_bytes()));
+/// ```
+pub fn detect_packer(elf: &file::ELF64, bytes: &[u8]) -> Option<PackerReport> {
+    let mut upx = Vec::new();
+    let mut structural = Vec::new();
+
+    // 壊れたヘッダでも溢れないように飽和させる
+    let pht_end =
+        (elf.ehdr.e_phoff as usize).saturating_add(elf.segments.len() * segment::Phdr64::SIZE);
+    // l_info: l_checksum(4), l_magic(4), ...
+    let l_magic = pht_end
+        .checked_add(8)
+        .and_then(|end| bytes.get(end - 4..end));
+    if !elf.segments.is_empty() && l_magic == Some(&UPX_MAGIC[..]) {
+        upx.push(PackerIndicator::UpxLoaderInfo {
+            offset: pht_end + 4,
+        });
+    }
+
+    let overlay = overlay_start(elf).min(bytes.len());
+    if let Some(pos) = find(&bytes[overlay..], UPX_MAGIC) {
+        upx.push(PackerIndicator::UpxOverlay {
+            offset: overlay + pos,
+        });
+    }
+
+    for sct in elf.sections.iter() {
+        if sct.name.starts_with("UPX") || sct.name.starts_with(".upx") {
+            upx.push(PackerIndicator::UpxSection {
+                name: sct.name.to_string(),
+            });
+        }
+    }
+
+    let mut version = None;
+    if let Some(pos) = find(bytes, UPX_BANNER) {
+        let v: String = bytes[pos + UPX_BANNER.len()..]
+            .iter()
+            .take_while(|b| b.is_ascii_alphanumeric() || **b == b'.')
+            .map(|b| *b as char)
+            .collect();
+        if !v.is_empty() {
+            version = Some(v.clone());
+            upx.push(PackerIndicator::UpxBanner {
+                offset: pos,
+                version: v,
+            });
+        }
+    }
+
+    // 以下は特定のパッカーに依らない特徴
+    if elf.ehdr.e_shnum == 0 && elf.ehdr.get_type() != header::Type::Rel {
+        structural.push(PackerIndicator::NoSectionHeaders);
+    }
+    let wx: Elf64Word = Elf64Word::from(segment::Flag::W) | Elf64Word::from(segment::Flag::X);
+    for (i, sgt) in elf.segments.iter().enumerate() {
+        let phdr = &sgt.header;
+        if phdr.get_type() != segment::Type::Load {
+            continue;
+        }
+        if phdr.p_flags & wx == wx {
+            structural.push(PackerIndicator::WritableExecutableSegment { index: i });
+        }
+        if phdr.p_memsz > phdr.p_filesz.max(1).saturating_mul(EXPANSION_RATIO) {
+            structural.push(PackerIndicator::ExpandingSegment { index: i });
+        }
+    }
+
+    // バナーだけでは判定しない(文字列として含んでいるだけのことがある)
+    let is_upx = upx
+        .iter()
+        .any(|i| !matches!(i, PackerIndicator::UpxBanner { .. }));
+    let packer = if is_upx {
+        Packer::Upx
+    } else if structural.len() >= 2 {
+        Packer::Unknown
+    } else {
+        return None;
+    };
+    if !is_upx {
+        version = None;
+        upx.clear();
+    }
+
+    upx.append(&mut structural);
+    Some(PackerReport {
+        packer,
+        version,
+        indicators: upx,
+    })
+}
+
+/// the end of the bytes described by the headers.
+fn overlay_start(elf: &file::ELF64) -> usize {
+    let mut end =
+        (elf.ehdr.e_phoff as usize).saturating_add(elf.segments.len() * segment::Phdr64::SIZE);
+    end = end.max(
+        (elf.ehdr.e_shoff as usize).saturating_add(elf.sections.len() * section::Shdr64::SIZE),
+    );
+    for sgt in elf.segments.iter() {
+        end = end.max(sgt.header.p_offset.saturating_add(sgt.header.p_filesz) as usize);
+    }
+    for sct in elf
+        .sections
+        .iter()
+        .filter(|s| !layout::is_nobits(&s.header))
+    {
+        end = end.max(sct.header.sh_offset.saturating_add(sct.header.sh_size) as usize);
+    }
+    end
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+#[derive(TError, Debug)]
+pub enum UnpackError {
+    #[error("the file isn't packed")]
+    NotPacked,
+    #[error("no unpacker supports {packer}")]
+    NoUnpacker { packer: Packer },
+    #[error("`{unpacker}` failed to unpack => `{k}`")]
+    Failed {
+        unpacker: String,
+        k: Box<dyn std::error::Error + Send + Sync>,
+    },
+}
+
+/// A strategy to unpack files, registered to `Unpackers`
+pub trait Unpacker {
+    /// the name used in error messages
+    fn name(&self) -> &str;
+
+    /// whether the strategy can unpack the file described by `report`
+    fn supports(&self, report: &PackerReport) -> bool;
+
+    /// unpack the file and returns the original ELF
+    fn unpack(
+        &self,
+        elf: &file::ELF64,
+        bytes: &[u8],
+        report: &PackerReport,
+    ) -> Result<file::ELF64, Box<dyn std::error::Error + Send + Sync>>;
+}
+
+/// A set of unpacking strategies, tried in the registered order
+#[derive(Default)]
+pub struct Unpackers {
+    unpackers: Vec<Box<dyn Unpacker + Send + Sync>>,
+}
+
+impl Unpackers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register<U: Unpacker + Send + Sync + 'static>(mut self, unpacker: U) -> Self {
+        self.unpackers.push(Box::new(unpacker));
+        self
+    }
+
+    /// detect the packer and unpack `elf` with the first strategy which supports it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use elf_utilities::{analysis, file};
+    ///
+    /// struct Passthrough;
+    ///
+    /// impl analysis::Unpacker for Passthrough {
+    ///     fn name(&self) -> &str {
+    ///         "passthrough"
+    ///     }
+    ///     fn supports(&self, report: &analysis::PackerReport) -> bool {
+    ///         report.packer == analysis::Packer::Upx
+    ///     }
+    ///     fn unpack(
+    ///         &self,
+    ///         elf: &file::ELF64,
+    ///         _bytes: &[u8],
+    ///         _report: &analysis::PackerReport,
+    ///     ) -> Result<file::ELF64, Box<dyn std::error::Error + Send + Sync>> {
+    ///         Ok(elf.clone())
+    ///     }
+    /// }
+    ///
+    /// let unpackers = analysis::Unpackers::new().register(Passthrough);
+    /// let elf = file::ELF64::default();
+    /// assert!(matches!(
+    ///     unpackers.unpack(&elf, &elf.to_le_bytes()),
+    ///     Err(analysis::UnpackError::NotPacked)
+    /// ));
+    /// ```
+    pub fn unpack(&self, elf: &file::ELF64, bytes: &[u8]) -> Result<file::ELF64, UnpackError> {
+        let report = detect_packer(elf, bytes).ok_or(UnpackError::NotPacked)?;
+        let unpacker =
+            self.unpackers
+                .iter()
+                .find(|u| u.supports(&report))
+                .ok_or(UnpackError::NoUnpacker {
+                    packer: report.packer,
+                })?;
+
+        unpacker
+            .unpack(elf, bytes, &report)
+            .map_err(|k| UnpackError::Failed {
+                unpacker: unpacker.name().to_string(),
+                k,
+            })
+    }
+}
+
+#[cfg(test)]
+mod packer_tests {
+    use super::*;
+
+    /// an executable rewritten like UPX's output
+    fn upx_like() -> (file::ELF64, Vec<u8>) {
+        let main = builder::ExportedFunction::new("main", vec![0xc3]);
+        let elf = builder::ExecutableWriter::new()
+            .function(main)
+            .start_stub(builder::start_stub("main"))
+            .build()
+            .unwrap();
+        let mut bytes = elf.to_le_bytes();
+
+        // セクションヘッダテーブルを消す
+        let mut ehdr = elf.ehdr;
+        ehdr.e_shoff = 0;
+        ehdr.e_shnum = 0;
+        ehdr.e_shstrndx = 0;
+        bytes[..header::Ehdr64::SIZE as usize].copy_from_slice(&ehdr.to_le_bytes());
+
+        let pht_end = ehdr.e_phoff as usize + elf.segments.len() * segment::Phdr64::SIZE;
+        bytes[pht_end + 4..pht_end + 8].copy_from_slice(UPX_MAGIC);
+        bytes.extend_from_slice(b"$Id: UPX 4.02 Copyright (C) 1996-2024 the UPX Team. $\0");
+        bytes.extend_from_slice(UPX_MAGIC);
+
+        let packed = parser::parse_elf_buf("upx", &bytes).unwrap().into_64bit();
+        (packed, bytes)
+    }
+
+    #[test]
+    fn detect_upx_test() {
+        let (elf, bytes) = upx_like();
+        let report = detect_packer(&elf, &bytes).unwrap();
+        assert_eq!(Packer::Upx, report.packer);
+        assert_eq!(Some("4.02".to_string()), report.version);
+        assert!(report
+            .indicators
+            .iter()
+            .any(|i| matches!(i, PackerIndicator::UpxLoaderInfo { .. })));
+        assert!(report
+            .indicators
+            .iter()
+            .any(|i| matches!(i, PackerIndicator::UpxOverlay { .. })));
+        assert!(report
+            .indicators
+            .contains(&PackerIndicator::NoSectionHeaders));
+    }
+
+    #[test]
+    fn corrupt_headers_test() {
+        let (mut elf, bytes) = upx_like();
+        elf.ehdr.e_phoff = u64::MAX;
+        elf.segments[0].header.p_offset = u64::MAX;
+        elf.segments[0].header.p_filesz = u64::MAX;
+        elf.segments[0].header.p_memsz = u64::MAX;
+        // 溢れずに，UPXの情報はファイルの外にあるものとして扱う
+        assert!(detect_packer(&elf, &bytes).is_none());
+    }
+
+    struct Failing;
+
+    impl Unpacker for Failing {
+        fn name(&self) -> &str {
+            "failing"
+        }
+        fn supports(&self, report: &PackerReport) -> bool {
+            report.packer == Packer::Upx
+        }
+        fn unpack(
+            &self,
+            _elf: &file::ELF64,
+            _bytes: &[u8],
+            _report: &PackerReport,
+        ) -> Result<file::ELF64, Box<dyn std::error::Error + Send + Sync>> {
+            Err("corrupted".into())
+        }
+    }
+
+    #[test]
+    fn unpackers_test() {
+        let (elf, bytes) = upx_like();
+        assert!(matches!(
+            Unpackers::new().unpack(&elf, &bytes),
+            Err(UnpackError::NoUnpacker {
+                packer: Packer::Upx
+            })
+        ));
+
+        let err = Unpackers::new()
+            .register(Failing)
+            .unpack(&elf, &bytes)
+            .unwrap_err();
+        assert_eq!("`failing` failed to unpack => `corrupted`", err.to_string());
+    }
+}
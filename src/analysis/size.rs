@@ -0,0 +1,197 @@
+//! Accounting the sizes of files by the kinds of their sections.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::layout::{is_alloc, is_nobits};
+use crate::section::SectionKind;
+use crate::*;
+
+/// the prefixes of the `.text` subsections which GCC and Clang place the functions in by their profiles
+const TEXT_PREFIXES: [&str; 5] = [
+    ".text.hot",
+    ".text.unlikely",
+    ".text.startup",
+    ".text.exit",
+    ".text.split",
+];
+
+/// The total size of the sections of a kind
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize,
+)]
+pub struct KindSize {
+    pub sections: usize,
+    /// the bytes in the file, which is zero for `SHT_NOBITS`
+    pub file_size: Elf64Xword,
+    /// the bytes in memory, which is zero for the sections without `SHF_ALLOC`
+    pub memory_size: Elf64Xword,
+}
+
+impl KindSize {
+    fn add(&mut self, shdr: &section::Shdr64) {
+        self.sections += 1;
+        if !is_nobits(shdr) {
+            self.file_size += shdr.sh_size;
+        }
+        if is_alloc(shdr) {
+            self.memory_size += shdr.sh_size;
+        }
+    }
+}
+
+/// The sizes computed by `size_by_kind()`
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize)]
+pub struct SizeReport {
+    pub by_kind: BTreeMap<SectionKind, KindSize>,
+    /// the `Text` sections by the subsections(`.text.hot`, `.text.unlikely`, etc.),
+    /// and `.text` for the others
+    pub text_by_prefix: BTreeMap<String, KindSize>,
+    /// the ELF header and the program and section header tables
+    pub headers: Elf64Xword,
+}
+
+impl SizeReport {
+    /// the total of the kind, or zero if the file has no such section.
+    pub fn get(&self, kind: SectionKind) -> KindSize {
+        self.by_kind.get(&kind).copied().unwrap_or_default()
+    }
+
+    /// the total of all the sections.
+    pub fn total(&self) -> KindSize {
+        self.by_kind
+            .values()
+            .fold(KindSize::default(), |acc, size| KindSize {
+                sections: acc.sections + size.sections,
+                file_size: acc.file_size + size.file_size,
+                memory_size: acc.memory_size + size.memory_size,
+            })
+    }
+}
+
+impl fmt::Display for SizeReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:<16}{:>12}{:>12}", "kind", "file", "memory")?;
+        for (kind, size) in self.by_kind.iter() {
+            writeln!(
+                f,
+                "{:<16}{:>12}{:>12}",
+                kind.to_string(),
+                size.file_size,
+                size.memory_size
+            )?;
+            if *kind != SectionKind::Text || self.text_by_prefix.len() < 2 {
+                continue;
+            }
+            for (prefix, size) in self.text_by_prefix.iter() {
+                writeln!(
+                    f,
+                    "  {:<14}{:>12}{:>12}",
+                    prefix, size.file_size, size.memory_size
+                )?;
+            }
+        }
+        writeln!(f, "{:<16}{:>12}", "headers", self.headers)?;
+        let total = self.total();
+        write!(
+            f,
+            "{:<16}{:>12}{:>12}",
+            "total",
+            total.file_size + self.headers,
+            total.memory_size
+        )
+    }
+}
+
+/// sum up the sizes of the sections by `section::canonical_kind()`.
+///
+/// The subsections of `.text` are found by their names,
+/// so linked files keep them only if linked with `-z keep-text-section-prefix`.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::{analysis, parser, section};
+///
+/// let elf = parser::parse_elf64("src/parser/testdata/sample").unwrap();
+/// let report = analysis::size_by_kind(&elf);
+///
+/// let text = report.get(section::SectionKind::Text);
+/// assert!(text.file_size > 0);
+/// assert_eq!(text.file_size, text.memory_size);
+/// // .bssはファイル上の大きさを持たない
+/// assert_eq!(0, report.get(section::SectionKind::Bss).file_size);
+/// assert!(report.to_string().contains("total"));
+/// ```
+pub fn size_by_kind(elf: &file::ELF64) -> SizeReport {
+    let mut report = SizeReport {
+        headers: header::Ehdr64::SIZE as Elf64Xword
+            + (elf.segments.len() * segment::Phdr64::SIZE) as Elf64Xword,
+        ..Default::default()
+    };
+    if elf.ehdr.e_shoff != 0 {
+        report.headers += (elf.sections.len() * section::Shdr64::SIZE) as Elf64Xword;
+    }
+
+    for sct in elf.sections.iter() {
+        let kind = sct.kind();
+        if kind == SectionKind::Null {
+            continue;
+        }
+        report.by_kind.entry(kind).or_default().add(&sct.header);
+        if kind != SectionKind::Text {
+            continue;
+        }
+        let prefix = TEXT_PREFIXES
+            .iter()
+            .find(|prefix| {
+                sct.name
+                    .strip_prefix(**prefix)
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+            })
+            .copied()
+            .unwrap_or(".text");
+        report
+            .text_by_prefix
+            .entry(prefix.to_string())
+            .or_default()
+            .add(&sct.header);
+    }
+    report
+}
+
+#[cfg(test)]
+mod size_tests {
+    use super::*;
+
+    #[test]
+    fn text_prefix_test() {
+        let mut elf = file::ELF64::default();
+        elf.ehdr.set_elf_type(header::Type::Rel);
+        for (name, size) in [(".text", 4), (".text.hot.f", 2), (".text.unlikely", 8)].iter() {
+            elf.add_section(section::Section64::new(
+                name.to_string(),
+                section::ShdrPreparation64::default()
+                    .ty(section::Type::ProgBits)
+                    .flags([section::Flag::Alloc, section::Flag::ExecInstr].iter()),
+                section::Contents64::Raw(vec![0xc3; *size]),
+            ));
+        }
+
+        let report = size_by_kind(&elf);
+        assert_eq!(14, report.get(SectionKind::Text).file_size);
+        assert_eq!(
+            vec![(".text", 4), (".text.hot", 2), (".text.unlikely", 8)],
+            report
+                .text_by_prefix
+                .iter()
+                .map(|(prefix, size)| (prefix.as_str(), size.file_size))
+                .collect::<Vec<_>>()
+        );
+        // .text.hotterは.text.hotではない
+        assert_eq!(".text", section::canonical_name(".text.hotter"));
+        assert!(report.to_string().contains("  .text.hot"));
+    }
+}
@@ -0,0 +1,184 @@
+//! Printable strings in ELF files.
+
+use crate::layout::{is_alloc, is_nobits};
+use crate::*;
+
+/// the sections where the strings of programs usually are
+pub const DATA_SECTIONS: [&str; 3] = [".rodata", ".data", ".comment"];
+
+/// A string found by `strings()`
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FoundString {
+    pub section: String,
+    /// the file offset
+    pub offset: Elf64Off,
+    /// the virtual address, `None` if the section isn't loaded
+    pub vaddr: Option<Elf64Addr>,
+    pub value: String,
+}
+
+/// find the runs of at least `min_len` printable characters in the sections, like `strings -a`.
+///
+/// The runs are decoded as UTF-8, so non-ASCII text is a single string rather than broken into pieces.
+/// Each string is tagged with the section containing it, so names in `.dynstr` are distinguished from messages in `.rodata`.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::{analysis, file, section};
+///
+/// let mut elf = file::ELF64::default();
+/// elf.add_section(section::Section64::new(
+///     ".rodata".to_string(),
+///     section::ShdrPreparation64::default()
+///         .ty(section::Type::ProgBits)
+///         .flags([section::Flag::Alloc].iter()),
+///     section::Contents64::Raw(b"\x01\x02hello\0ok\0\xe3\x81\x93\xe3\x82\x93\xe3\x81\xab\xe3\x81\xa1\xe3\x81\xaf\0".to_vec()),
+/// ));
+///
+/// let found: Vec<String> = analysis::strings(&elf, 4)
+///     .into_iter()
+///     .filter(|s| s.section == ".rodata")
+///     .map(|s| s.value)
+///     .collect();
+/// assert_eq!(vec!["hello", "こんにちは"], found);
+/// ```
+pub fn strings(elf: &file::ELF64, min_len: usize) -> Vec<FoundString> {
+    scan(elf, min_len, |_| true)
+}
+
+/// `strings()` in the sections named one of `sections` or starting with it and `.`,
+/// e.g. `.rodata` matches `.rodata.str1.1`. `DATA_SECTIONS` is the usual choice.
+pub fn strings_in(elf: &file::ELF64, min_len: usize, sections: &[&str]) -> Vec<FoundString> {
+    scan(elf, min_len, |name| {
+        sections.iter().any(|prefix| {
+            name == *prefix
+                || name
+                    .strip_prefix(prefix)
+                    .is_some_and(|rest| rest.starts_with('.'))
+        })
+    })
+}
+
+fn scan<F>(elf: &file::ELF64, min_len: usize, filter: F) -> Vec<FoundString>
+where
+    F: Fn(&str) -> bool,
+{
+    let mut found = Vec::new();
+    for sct in elf.sections.iter() {
+        if sct.header.get_type() == section::Type::Null
+            || is_nobits(&sct.header)
+            || !filter(&sct.name)
+        {
+            continue;
+        }
+        let bytes = sct.to_le_bytes();
+        for (start, value) in printable_runs(&bytes, min_len.max(1)) {
+            found.push(FoundString {
+                section: sct.name.to_string(),
+                offset: sct.header.sh_offset + start as Elf64Off,
+                vaddr: if is_alloc(&sct.header) {
+                    Some(sct.header.sh_addr + start as Elf64Addr)
+                } else {
+                    None
+                },
+                value,
+            });
+        }
+    }
+    found
+}
+
+/// the runs of printable characters with their byte offsets.
+fn printable_runs(bytes: &[u8], min_len: usize) -> Vec<(usize, String)> {
+    let mut runs = Vec::new();
+    let mut run = String::new();
+    let mut chars = 0;
+    let mut start = 0;
+    let mut pos = 0;
+    while pos < bytes.len() {
+        match decode_printable(&bytes[pos..]) {
+            Some((c, len)) => {
+                if run.is_empty() {
+                    start = pos;
+                }
+                run.push(c);
+                chars += 1;
+                pos += len;
+            }
+            None => {
+                if chars >= min_len {
+                    runs.push((start, std::mem::take(&mut run)));
+                }
+                run.clear();
+                chars = 0;
+                pos += 1;
+            }
+        }
+    }
+    if chars >= min_len {
+        runs.push((start, run));
+    }
+    runs
+}
+
+/// decode a printable character at the head of `bytes`.
+fn decode_printable(bytes: &[u8]) -> Option<(char, usize)> {
+    let len = match bytes[0] {
+        0x00..=0x7f => 1,
+        0xc2..=0xdf => 2,
+        0xe0..=0xef => 3,
+        0xf0..=0xf4 => 4,
+        _ => return None,
+    };
+    let c = std::str::from_utf8(bytes.get(..len)?)
+        .ok()?
+        .chars()
+        .next()?;
+    // タブ以外の制御文字は区切り
+    if c.is_control() && c != '\t' {
+        return None;
+    }
+    Some((c, len))
+}
+
+#[cfg(test)]
+mod strings_tests {
+    use super::*;
+
+    #[test]
+    fn strings_in_test() {
+        let mut elf = file::ELF64::default();
+        for (name, contents) in [
+            (".rodata.str1.1", &b"usage: %s\0"[..]),
+            (".rodata_extra", b"not matched\0"),
+            (".text", b"\x48\x8d\x05code\0"),
+            (".comment", b"GCC: (GNU) 12.2.0\0"),
+        ] {
+            elf.add_section(section::Section64::new(
+                name.to_string(),
+                section::ShdrPreparation64::default().ty(section::Type::ProgBits),
+                section::Contents64::Raw(contents.to_vec()),
+            ));
+        }
+
+        let found: Vec<(String, String)> = strings_in(&elf, 4, &DATA_SECTIONS)
+            .into_iter()
+            .map(|s| (s.section, s.value))
+            .collect();
+        assert_eq!(
+            vec![
+                (".rodata.str1.1".to_string(), "usage: %s".to_string()),
+                (".comment".to_string(), "GCC: (GNU) 12.2.0".to_string()),
+            ],
+            found
+        );
+        // 命令のバイト列は区切りになる
+        let text: Vec<String> = strings(&elf, 4)
+            .into_iter()
+            .filter(|s| s.section == ".text")
+            .map(|s| s.value)
+            .collect();
+        assert_eq!(vec!["code"], text);
+    }
+}
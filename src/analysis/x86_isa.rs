@@ -0,0 +1,162 @@
+//! x86-64 microarchitecture levels required by linked files.
+
+use std::fmt;
+
+use crate::*;
+use section::{
+    GNU_PROPERTY_X86_ISA_1_BASELINE, GNU_PROPERTY_X86_ISA_1_V2, GNU_PROPERTY_X86_ISA_1_V3,
+    GNU_PROPERTY_X86_ISA_1_V4,
+};
+
+/// The x86-64 microarchitecture levels defined by the psABI
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum X86IsaLevel {
+    /// x86-64(SSE2)
+    Baseline,
+    /// x86-64-v2(SSE4.2, POPCNT)
+    V2,
+    /// x86-64-v3(AVX2, BMI2, FMA)
+    V3,
+    /// x86-64-v4(AVX-512)
+    V4,
+}
+
+impl X86IsaLevel {
+    /// the highest level in the `GNU_PROPERTY_X86_ISA_1_*` bits.
+    pub fn from_bits(bits: Elf64Word) -> Option<Self> {
+        if bits & GNU_PROPERTY_X86_ISA_1_V4 != 0 {
+            Some(Self::V4)
+        } else if bits & GNU_PROPERTY_X86_ISA_1_V3 != 0 {
+            Some(Self::V3)
+        } else if bits & GNU_PROPERTY_X86_ISA_1_V2 != 0 {
+            Some(Self::V2)
+        } else if bits & GNU_PROPERTY_X86_ISA_1_BASELINE != 0 {
+            Some(Self::Baseline)
+        } else {
+            None
+        }
+    }
+
+    pub fn to_bit(self) -> Elf64Word {
+        match self {
+            Self::Baseline => GNU_PROPERTY_X86_ISA_1_BASELINE,
+            Self::V2 => GNU_PROPERTY_X86_ISA_1_V2,
+            Self::V3 => GNU_PROPERTY_X86_ISA_1_V3,
+            Self::V4 => GNU_PROPERTY_X86_ISA_1_V4,
+        }
+    }
+}
+
+impl fmt::Display for X86IsaLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Baseline => write!(f, "x86-64"),
+            Self::V2 => write!(f, "x86-64-v2"),
+            Self::V3 => write!(f, "x86-64-v3"),
+            Self::V4 => write!(f, "x86-64-v4"),
+        }
+    }
+}
+
+/// The result of `x86_isa_level()`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct X86IsaReport {
+    /// `GNU_PROPERTY_X86_ISA_1_NEEDED`
+    pub needed: Option<Elf64Word>,
+    /// `GNU_PROPERTY_X86_ISA_1_USED`
+    pub used: Option<Elf64Word>,
+}
+
+impl X86IsaReport {
+    /// the level which the CPU must support to run the file.
+    /// `None` if the file doesn't record it, which means the baseline in practice.
+    pub fn needed_level(&self) -> Option<X86IsaLevel> {
+        self.needed.and_then(X86IsaLevel::from_bits)
+    }
+
+    /// the highest level of the instructions in the file.
+    pub fn used_level(&self) -> Option<X86IsaLevel> {
+        self.used.and_then(X86IsaLevel::from_bits)
+    }
+
+    /// whether the file can run only on CPUs above `level`.
+    pub fn requires_above(&self, level: X86IsaLevel) -> bool {
+        self.needed_level().is_some_and(|needed| needed > level)
+    }
+
+    /// AVX2 is a part of x86-64-v3.
+    pub fn requires_avx2(&self) -> bool {
+        self.requires_above(X86IsaLevel::V2)
+    }
+
+    /// AVX-512 is a part of x86-64-v4.
+    pub fn requires_avx512(&self) -> bool {
+        self.requires_above(X86IsaLevel::V3)
+    }
+}
+
+/// report the x86-64 ISA levels recorded in `.note.gnu.property`.
+///
+/// GCC records them with `-mneeded`(or glibc's `--enable-x86-isa-level`),
+/// and the dynamic loader refuses files which need a higher level than the CPU supports.
+/// Returns `None` if the file isn't for x86-64.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::{analysis, builder};
+///
+/// let elf = builder::SharedObjectWriter::new()
+///     .function(builder::ExportedFunction::new("f", vec![0xc3]))
+///     .build()
+///     .unwrap();
+/// let report = analysis::x86_isa_level(&elf).unwrap();
+/// // no properties are recorded
+/// assert_eq!(None, report.needed_level());
+/// assert!(!report.requires_avx2());
+/// ```
+pub fn x86_isa_level(elf: &file::ELF64) -> Option<X86IsaReport> {
+    if elf.ehdr.get_machine() != header::Machine::X8664 {
+        return None;
+    }
+    let props = match elf.gnu_properties() {
+        Some(Ok(props)) => props,
+        _ => return Some(X86IsaReport::default()),
+    };
+    Some(X86IsaReport {
+        needed: props.x86_isa_needed(),
+        used: props.x86_isa_used(),
+    })
+}
+
+#[cfg(test)]
+mod x86_isa_tests {
+    use super::*;
+
+    #[test]
+    fn x86_isa_level_test() {
+        let mut elf = file::ELF64::default();
+        elf.ehdr.set_machine(header::Machine::X8664);
+
+        // gcc -c -march=x86-64-v3 -mneeded の出力
+        let mut note = vec![4, 0, 0, 0, 0x10, 0, 0, 0, 5, 0, 0, 0];
+        note.extend_from_slice(b"GNU\0");
+        note.extend_from_slice(&[0x02, 0x80, 0x00, 0xc0, 4, 0, 0, 0, 7, 0, 0, 0, 0, 0, 0, 0]);
+        elf.add_section(section::Section64::new(
+            ".note.gnu.property".to_string(),
+            section::ShdrPreparation64::default().ty(section::Type::Note),
+            section::Contents64::Raw(note),
+        ));
+
+        let report = x86_isa_level(&elf).unwrap();
+        assert_eq!(Some(X86IsaLevel::V3), report.needed_level());
+        assert_eq!(None, report.used_level());
+        assert!(report.requires_avx2());
+        assert!(!report.requires_avx512());
+        assert_eq!("x86-64-v3", report.needed_level().unwrap().to_string());
+
+        elf.ehdr
+            .set_machine(header::Machine::Any(analysis::EM_AARCH64));
+        assert_eq!(None, x86_isa_level(&elf));
+    }
+}
@@ -0,0 +1,274 @@
+//! Cross references derived from relocations.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::*;
+
+/// A node of the cross reference graph
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Xref {
+    Symbol(String),
+    /// a section referenced via its section symbol, or a part of a section not covered by symbols
+    Section(String),
+}
+
+/// A cross reference graph built by `xrefs()`
+///
+/// `edges` maps each referencing symbol/section to the symbols/sections it references.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct XrefGraph {
+    pub edges: BTreeMap<Xref, BTreeSet<Xref>>,
+}
+
+impl XrefGraph {
+    /// the nodes referenced by `from`.
+    pub fn references(&self, from: &Xref) -> impl Iterator<Item = &Xref> {
+        self.edges.get(from).into_iter().flatten()
+    }
+
+    /// the nodes which reference `to`.
+    pub fn referenced_by<'a>(&'a self, to: &'a Xref) -> impl Iterator<Item = &'a Xref> {
+        self.edges
+            .iter()
+            .filter(move |(_, targets)| targets.contains(to))
+            .map(|(from, _)| from)
+    }
+
+    fn add(&mut self, from: Xref, to: Xref) {
+        self.edges.entry(from).or_default().insert(to);
+    }
+}
+
+/// build the cross reference graph from static(`ET_REL`) and dynamic relocations.
+///
+/// The source of an edge is the symbol which covers the relocated place,
+/// or the section if no symbol covers it.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::{analysis, builder, relocation};
+///
+/// // mov rax, [rip + puts@GOTPCREL]; ret
+/// let f = builder::ExportedFunction::new("f", vec![0x48, 0x8b, 0x05, 0, 0, 0, 0, 0xc3])
+///     .relocation(3, "puts", relocation::R_X86_64_GOTPCREL, -4);
+/// let elf = builder::SharedObjectWriter::new().function(f).build().unwrap();
+///
+/// // the GOT slot of puts is filled by the dynamic linker
+/// let graph = analysis::xrefs(&elf);
+/// let got = analysis::Xref::Section(".got".to_string());
+/// let puts = analysis::Xref::Symbol("puts".to_string());
+/// assert_eq!(vec![&puts], graph.references(&got).collect::<Vec<_>>());
+/// ```
+pub fn xrefs(elf: &file::ELF64) -> XrefGraph {
+    let mut graph = XrefGraph::default();
+    let is_rel = elf.ehdr.get_type() == header::Type::Rel;
+
+    for sct in elf.sections.iter() {
+        let relas = match &sct.contents {
+            section::Contents64::RelaSymbols(relas) => relas,
+            _ => continue,
+        };
+        let syms = match elf.sections.get(sct.header.sh_link as usize) {
+            Some(section::Section64 {
+                contents: section::Contents64::Symbols(syms),
+                ..
+            }) => syms.as_slice(),
+            _ => &[],
+        };
+        // 再配置可能ファイルのr_offsetはsh_infoのセクションからの相対位置
+        let place_shndx = if is_rel && !layout::is_alloc(&sct.header) {
+            Some(sct.header.sh_info as usize)
+        } else {
+            None
+        };
+
+        for rela in relas.iter() {
+            let from = match place_shndx {
+                Some(shndx) => covering_in_section(elf, syms, shndx, rela.get_offset()),
+                None => covering_addr(elf, rela.get_offset()),
+            };
+            let to = match rela.get_sym() {
+                // R_X86_64_RELATIVE等はaddendがアドレスを表す
+                0 if place_shndx.is_none() => covering_addr(elf, rela.get_addend() as Elf64Addr),
+                0 => None,
+                sym => syms.get(sym as usize).and_then(|s| symbol_node(elf, s)),
+            };
+            if let (Some(from), Some(to)) = (from, to) {
+                graph.add(from, to);
+            }
+        }
+    }
+
+    graph
+}
+
+fn symbol_node(elf: &file::ELF64, sym: &symbol::Symbol64) -> Option<Xref> {
+    if sym.get_type() == symbol::Type::Section {
+        return elf
+            .sections
+            .get(sym.st_shndx as usize)
+            .map(|sct| Xref::Section(sct.name.to_string()));
+    }
+    if sym.symbol_name.is_empty() {
+        return None;
+    }
+    Some(Xref::Symbol(sym.symbol_name.to_string()))
+}
+
+/// the node which covers `offset` in the section `shndx`.
+fn covering_in_section(
+    elf: &file::ELF64,
+    syms: &[symbol::Symbol64],
+    shndx: usize,
+    offset: Elf64Addr,
+) -> Option<Xref> {
+    let sym = syms.iter().find(|s| {
+        s.st_shndx as usize == shndx && is_code_or_data(s) && covers(s.st_value, s.st_size, offset)
+    });
+    match sym {
+        Some(s) => Some(Xref::Symbol(s.symbol_name.to_string())),
+        None => elf
+            .sections
+            .get(shndx)
+            .map(|sct| Xref::Section(sct.name.to_string())),
+    }
+}
+
+/// the node which covers the virtual address.
+fn covering_addr(elf: &file::ELF64, addr: Elf64Addr) -> Option<Xref> {
+    let sym = elf
+        .sections
+        .iter()
+        .filter(|sct| {
+            let ty = sct.header.get_type();
+            ty == section::Type::SymTab || ty == section::Type::DynSym
+        })
+        .filter_map(|sct| match &sct.contents {
+            section::Contents64::Symbols(syms) => Some(syms),
+            _ => None,
+        })
+        .flatten()
+        .find(|s| {
+            s.st_shndx != section::SHN_UNDEF
+                && is_code_or_data(s)
+                && covers(s.st_value, s.st_size, addr)
+        });
+    if let Some(s) = sym {
+        return Some(Xref::Symbol(s.symbol_name.to_string()));
+    }
+
+    elf.sections
+        .iter()
+        .find(|sct| {
+            layout::is_alloc(&sct.header) && covers(sct.header.sh_addr, sct.header.sh_size, addr)
+        })
+        .map(|sct| Xref::Section(sct.name.to_string()))
+}
+
+/// `start..start + size` contains `addr`. Ranges overflowing the address space are broken and cover nothing.
+fn covers(start: Elf64Addr, size: Elf64Xword, addr: Elf64Addr) -> bool {
+    start <= addr && start.checked_add(size).is_some_and(|end| addr < end)
+}
+
+fn is_code_or_data(sym: &symbol::Symbol64) -> bool {
+    let ty = sym.get_type();
+    ty == symbol::Type::Func || ty == symbol::Type::Object
+}
+
+#[cfg(test)]
+mod xref_tests {
+    use super::*;
+
+    #[test]
+    fn static_xrefs_test() {
+        // 再配置可能ファイル: mainが.text内のhelperと.dataを参照する
+        let mut elf = file::ELF64::default();
+        elf.ehdr.set_elf_type(header::Type::Rel);
+        elf.add_section(section::Section64::new(
+            ".text".to_string(),
+            section::ShdrPreparation64::default()
+                .ty(section::Type::ProgBits)
+                .flags([section::Flag::Alloc, section::Flag::ExecInstr].iter()),
+            section::Contents64::Raw(vec![0x90; 0x20]),
+        ));
+        elf.add_section(section::Section64::new(
+            ".data".to_string(),
+            section::ShdrPreparation64::default()
+                .ty(section::Type::ProgBits)
+                .flags([section::Flag::Alloc, section::Flag::Write].iter()),
+            section::Contents64::Raw(vec![0; 8]),
+        ));
+        let mut syms = vec![symbol::Symbol64::new_null_symbol()];
+        for (name, ty, shndx, value) in [
+            ("", symbol::Type::Section, 2, 0),
+            ("main", symbol::Type::Func, 1, 0),
+            ("helper", symbol::Type::Func, 1, 0x10),
+        ]
+        .iter()
+        {
+            let mut sym = symbol::Symbol64 {
+                symbol_name: (*name).into(),
+                st_shndx: *shndx,
+                st_value: *value,
+                st_size: 0x10,
+                ..Default::default()
+            };
+            sym.set_info(*ty, symbol::Bind::Global);
+            syms.push(sym);
+        }
+        elf.add_section(section::Section64::new(
+            ".symtab".to_string(),
+            section::ShdrPreparation64::default().ty(section::Type::SymTab),
+            section::Contents64::Symbols(syms),
+        ));
+        let relas = [(1, 3), (5, 1)]
+            .iter()
+            .map(|(offset, sym)| {
+                let mut rela = relocation::Rela64::default();
+                rela.set_offset(*offset);
+                rela.set_info(sym << 32 | relocation::R_X86_64_PC32);
+                rela
+            })
+            .collect();
+        elf.add_section(section::Section64::new(
+            ".rela.text".to_string(),
+            section::ShdrPreparation64::default()
+                .ty(section::Type::Rela)
+                .link(3)
+                .info(1),
+            section::Contents64::RelaSymbols(relas),
+        ));
+
+        let graph = xrefs(&elf);
+        let main = Xref::Symbol("main".to_string());
+        let refs: Vec<&Xref> = graph.references(&main).collect();
+        assert_eq!(
+            vec![
+                &Xref::Symbol("helper".to_string()),
+                &Xref::Section(".data".to_string())
+            ],
+            refs
+        );
+        assert_eq!(
+            vec![&main],
+            graph
+                .referenced_by(&Xref::Symbol("helper".to_string()))
+                .collect::<Vec<_>>()
+        );
+
+        let bytes = bincode::serialize(&graph).unwrap();
+        assert_eq!(graph, bincode::deserialize(&bytes).unwrap());
+
+        // 大きさが溢れるシンボルは何も覆わない
+        if let section::Contents64::Symbols(syms) = &mut elf.sections[3].contents {
+            syms[2].st_value = 1;
+            syms[2].st_size = u64::MAX;
+        }
+        let graph = xrefs(&elf);
+        let text = Xref::Section(".text".to_string());
+        assert_eq!(2, graph.references(&text).count());
+    }
+}
@@ -0,0 +1,829 @@
+//! `proptest` strategies for ELF structures(requires the `arbitrary` feature).
+//!
+//! Headers and table entries implement `Arbitrary` with any field values,
+//! since they are (de)serialized field by field.
+//! `ELF64`/`ELF32` implement `Arbitrary` with *valid* files:
+//! every section is placed at its `sh_offset`, tables have the right `sh_entsize`/`sh_link`,
+//! and symbol names are in the string table.
+//! So `parse(to_le_bytes(x)) == x` is expected to hold for them.
+//!
+//! # Examples
+//!
+//! ```
+//! use elf_utilities::{file, parser};
+//! use proptest::prelude::*;
+//! use proptest::test_runner::TestRunner;
+//!
+//! let path = std::env::temp_dir().join("elf_utilities_arbitrary_doctest");
+//! TestRunner::default()
+//!     .run(&any::<file::ELF64>(), |elf| {
+//!         std::fs::write(&path, elf.to_le_bytes()).unwrap();
+//!         let parsed = parser::parse_elf64(path.to_str().unwrap()).unwrap();
+//!         prop_assert_eq!(elf, parsed);
+//!         Ok(())
+//!     })
+//!     .unwrap();
+//! std::fs::remove_file(&path).unwrap();
+//! ```
+
+use proptest::collection::vec;
+use proptest::prelude::*;
+use proptest::sample::select;
+
+use crate::*;
+use section::StringTable;
+
+impl Arbitrary for header::Ident {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        any::<[u8; 16]>().prop_map(header::Ident::from).boxed()
+    }
+}
+
+impl Arbitrary for header::Ehdr64 {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        (
+            (
+                any::<header::Ident>(),
+                any::<u16>(),
+                any::<u16>(),
+                any::<u32>(),
+            ),
+            (any::<u64>(), any::<u64>(), any::<u64>(), any::<u32>()),
+            any::<[u16; 6]>(),
+        )
+            .prop_map(
+                |((ident, ty, machine, version), (entry, phoff, shoff, flags), halves)| {
+                    header::Ehdr64 {
+                        e_ident: ident,
+                        e_type: ty,
+                        e_machine: machine,
+                        e_version: version,
+                        e_entry: entry,
+                        e_phoff: phoff,
+                        e_shoff: shoff,
+                        e_flags: flags,
+                        e_ehsize: halves[0],
+                        e_phentsize: halves[1],
+                        e_phnum: halves[2],
+                        e_shentsize: halves[3],
+                        e_shnum: halves[4],
+                        e_shstrndx: halves[5],
+                    }
+                },
+            )
+            .boxed()
+    }
+}
+
+impl Arbitrary for header::Ehdr32 {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        (
+            (
+                any::<header::Ident>(),
+                any::<u16>(),
+                any::<u16>(),
+                any::<u32>(),
+            ),
+            any::<[u32; 4]>(),
+            any::<[u16; 6]>(),
+        )
+            .prop_map(
+                |((ident, ty, machine, version), words, halves)| header::Ehdr32 {
+                    e_ident: ident,
+                    e_type: ty,
+                    e_machine: machine,
+                    e_version: version,
+                    e_entry: words[0],
+                    e_phoff: words[1],
+                    e_shoff: words[2],
+                    e_flags: words[3],
+                    e_ehsize: halves[0],
+                    e_phentsize: halves[1],
+                    e_phnum: halves[2],
+                    e_shentsize: halves[3],
+                    e_shnum: halves[4],
+                    e_shstrndx: halves[5],
+                },
+            )
+            .boxed()
+    }
+}
+
+impl Arbitrary for section::Shdr64 {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        (any::<[u32; 4]>(), any::<[u64; 6]>())
+            .prop_map(|(words, xwords)| section::Shdr64 {
+                sh_name: words[0],
+                sh_type: words[1],
+                sh_flags: xwords[0],
+                sh_addr: xwords[1],
+                sh_offset: xwords[2],
+                sh_size: xwords[3],
+                sh_link: words[2],
+                sh_info: words[3],
+                sh_addralign: xwords[4],
+                sh_entsize: xwords[5],
+            })
+            .boxed()
+    }
+}
+
+impl Arbitrary for section::Shdr32 {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        any::<[u32; 10]>()
+            .prop_map(|words| section::Shdr32 {
+                sh_name: words[0],
+                sh_type: words[1],
+                sh_flags: words[2],
+                sh_addr: words[3],
+                sh_offset: words[4],
+                sh_size: words[5],
+                sh_link: words[6],
+                sh_info: words[7],
+                sh_addralign: words[8],
+                sh_entsize: words[9],
+            })
+            .boxed()
+    }
+}
+
+impl Arbitrary for segment::Phdr64 {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        (any::<[u32; 2]>(), any::<[u64; 6]>())
+            .prop_map(|(words, xwords)| segment::Phdr64 {
+                p_type: words[0],
+                p_flags: words[1],
+                p_offset: xwords[0],
+                p_vaddr: xwords[1],
+                p_paddr: xwords[2],
+                p_filesz: xwords[3],
+                p_memsz: xwords[4],
+                p_align: xwords[5],
+            })
+            .boxed()
+    }
+}
+
+impl Arbitrary for segment::Phdr32 {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        any::<[u32; 8]>()
+            .prop_map(|words| segment::Phdr32 {
+                p_type: words[0],
+                p_offset: words[1],
+                p_vaddr: words[2],
+                p_paddr: words[3],
+                p_filesz: words[4],
+                p_memsz: words[5],
+                p_flags: words[6],
+                p_align: words[7],
+            })
+            .boxed()
+    }
+}
+
+impl Arbitrary for symbol::Symbol64 {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    /// `symbol_name` is left empty, because it isn't a part of the entry.
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        (
+            any::<u32>(),
+            any::<[u8; 2]>(),
+            any::<u16>(),
+            any::<[u64; 2]>(),
+        )
+            .prop_map(|(name, info, shndx, xwords)| symbol::Symbol64 {
+                st_name: name,
+                st_info: info[0],
+                st_other: info[1],
+                st_shndx: shndx,
+                st_value: xwords[0],
+                st_size: xwords[1],
+                symbol_name: Default::default(),
+            })
+            .boxed()
+    }
+}
+
+impl Arbitrary for symbol::Symbol32 {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    /// `symbol_name` is left empty, because it isn't a part of the entry.
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        (any::<[u32; 3]>(), any::<[u8; 2]>(), any::<u16>())
+            .prop_map(|(words, info, shndx)| symbol::Symbol32 {
+                st_name: words[0],
+                st_value: words[1],
+                st_size: words[2],
+                st_info: info[0],
+                st_other: info[1],
+                st_shndx: shndx,
+                symbol_name: String::new(),
+            })
+            .boxed()
+    }
+}
+
+impl Arbitrary for relocation::Rela64 {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        (any::<u64>(), any::<u64>(), any::<i64>())
+            .prop_map(|(offset, info, addend)| {
+                let mut rela = relocation::Rela64::default();
+                rela.set_offset(offset);
+                rela.set_info(info);
+                rela.set_addend(addend);
+                rela
+            })
+            .boxed()
+    }
+}
+
+impl Arbitrary for relocation::Rela32 {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        (any::<u32>(), any::<u32>(), any::<i32>())
+            .prop_map(|(offset, info, addend)| {
+                let mut rela = relocation::Rela32::default();
+                rela.set_offset(offset);
+                rela.set_info(info);
+                rela.set_addend(addend);
+                rela
+            })
+            .boxed()
+    }
+}
+
+impl Arbitrary for dynamic::Dyn64 {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        (any::<i64>(), any::<u64>())
+            .prop_map(|(tag, value)| dynamic::Dyn64 {
+                d_tag: tag,
+                d_un: value,
+            })
+            .boxed()
+    }
+}
+
+impl Arbitrary for dynamic::Dyn32 {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        (any::<i32>(), any::<u32>())
+            .prop_map(|(tag, value)| dynamic::Dyn32 {
+                d_tag: tag,
+                d_un: value,
+            })
+            .boxed()
+    }
+}
+
+/// the class-independent shape of a generated file.
+/// it's converted to `ELF64`/`ELF32` by `into_elf64()`/`into_elf32()`.
+#[derive(Debug, Clone)]
+struct FilePlan {
+    ty: header::Type,
+    machine: u16,
+    entry: u64,
+    flags: u32,
+    sections: Vec<SectionPlan>,
+    /// (name, st_info, st_other, st_shndx seed, st_value, st_size)
+    symbols: Vec<(String, u8, u8, u16, u64, u64)>,
+    /// (target seed, r_offset, symbol seed, type, r_addend)
+    relocations: Vec<(usize, u64, usize, u8, i64)>,
+    dynamics: Option<Vec<(i64, u64)>>,
+    segment_count: usize,
+}
+
+#[derive(Debug, Clone)]
+struct SectionPlan {
+    name: String,
+    flags: u64,
+    addr: u64,
+    align: u64,
+    /// `Err(size)` is a `SHT_NOBITS` section
+    contents: Result<Vec<u8>, u64>,
+}
+
+fn section_plan() -> impl Strategy<Value = SectionPlan> {
+    (
+        "\\.[a-z]{1,8}",
+        0u64..8,
+        any::<u32>(),
+        select(vec![1u64, 2, 4, 8, 16]),
+        prop_oneof![
+            3 => vec(any::<u8>(), 0..64).prop_map(Ok),
+            1 => (0u64..0x10000).prop_map(Err),
+        ],
+    )
+        .prop_map(|(name, flags, addr, align, contents)| SectionPlan {
+            name,
+            // Write/Alloc/ExecInstr
+            flags,
+            addr: addr as u64,
+            align,
+            contents,
+        })
+}
+
+fn file_plan() -> impl Strategy<Value = FilePlan> {
+    (
+        (
+            select(vec![
+                header::Type::Rel,
+                header::Type::Exec,
+                header::Type::Dyn,
+            ]),
+            any::<u16>(),
+            any::<u32>(),
+            any::<u32>(),
+        ),
+        vec(section_plan(), 0..6),
+        proptest::collection::btree_set("[a-z_][a-z0-9_]{0,11}", 0..8),
+        vec(
+            (
+                any::<u8>(),
+                any::<u8>(),
+                any::<u16>(),
+                any::<u32>(),
+                any::<u32>(),
+            ),
+            8,
+        ),
+        vec(
+            (
+                any::<usize>(),
+                any::<u32>(),
+                any::<usize>(),
+                any::<u8>(),
+                any::<i32>(),
+            ),
+            0..8,
+        ),
+        proptest::option::of(vec((any::<i32>(), any::<u32>()), 0..6)),
+        0usize..4,
+    )
+        .prop_map(
+            |(
+                (ty, machine, entry, flags),
+                sections,
+                names,
+                attrs,
+                relocations,
+                dynamics,
+                segment_count,
+            )| {
+                // ローカルシンボルはグローバルシンボルより前に置く
+                let mut symbols: Vec<_> = names
+                    .into_iter()
+                    .zip(attrs)
+                    .map(|(name, (info, other, shndx, value, size))| {
+                        (name, info, other, shndx, value as u64, size as u64)
+                    })
+                    .collect();
+                symbols.sort_by_key(|sym| sym.1 >> 4 != 0);
+                FilePlan {
+                    ty,
+                    machine,
+                    entry: entry as u64,
+                    flags,
+                    sections,
+                    symbols,
+                    relocations: relocations
+                        .into_iter()
+                        .map(|(target, offset, sym, ty, addend)| {
+                            (target, offset as u64, sym, ty, addend as i64)
+                        })
+                        .collect(),
+                    dynamics: dynamics.map(|entries| {
+                        entries
+                            .into_iter()
+                            .map(|(tag, value)| (tag as i64, value as u64))
+                            .chain(std::iter::once((0, 0)))
+                            .collect()
+                    }),
+                    segment_count,
+                }
+            },
+        )
+}
+
+/// the section indices of the tables placed after the generated sections
+struct TableIndices {
+    symtab: usize,
+    strtab: usize,
+    rela: Option<(usize, usize)>,
+    shstrtab: usize,
+}
+
+impl FilePlan {
+    fn indices(&self) -> TableIndices {
+        let symtab = self.sections.len() + 1;
+        let strtab = symtab + 1;
+        let mut next = strtab + 1;
+        let rela = if self.sections.is_empty() || self.relocations.is_empty() {
+            None
+        } else {
+            next += 1;
+            Some((next - 1, 1 + self.relocations[0].0 % self.sections.len()))
+        };
+        if self.dynamics.is_some() {
+            next += 1;
+        }
+        TableIndices {
+            symtab,
+            strtab,
+            rela,
+            shstrtab: next,
+        }
+    }
+
+    fn first_global(&self) -> usize {
+        1 + self
+            .symbols
+            .iter()
+            .take_while(|sym| sym.1 >> 4 == 0)
+            .count()
+    }
+
+    fn shndx(&self, seed: u16) -> u16 {
+        // 既存のセクションか，予約済みのインデックス
+        match seed % 4 {
+            0 => section::SHN_UNDEF,
+            1 => section::SHN_ABS,
+            _ => (seed as usize % (self.sections.len() + 1)) as u16,
+        }
+    }
+
+    fn into_elf64(self) -> file::ELF64 {
+        let idx = self.indices();
+        let mut strtab = StringTable::new();
+        let mut elf = file::ELF64 {
+            ehdr: header::Ehdr64::default(),
+            sections: vec![section::Section64::new_null_section()],
+            segments: Vec::new(),
+            address_cache: Default::default(),
+        };
+
+        for sct in self.sections.iter() {
+            let mut shdr = section::Shdr64 {
+                sh_flags: sct.flags,
+                sh_addr: sct.addr,
+                sh_addralign: sct.align,
+                ..Default::default()
+            };
+            let contents = match &sct.contents {
+                Ok(bytes) => {
+                    shdr.sh_type = section::Type::ProgBits.into();
+                    bytes.clone()
+                }
+                Err(size) => {
+                    shdr.sh_type = section::Type::NoBits.into();
+                    shdr.sh_size = *size;
+                    Vec::new()
+                }
+            };
+            elf.sections.push(section::Section64 {
+                name: sct.name.as_str().into(),
+                header: shdr,
+                contents: section::Contents64::Raw(contents),
+            });
+        }
+
+        let mut symbols = vec![symbol::Symbol64::new_null_symbol()];
+        for (name, info, other, shndx, value, size) in self.symbols.iter() {
+            symbols.push(symbol::Symbol64 {
+                st_name: strtab.add(name) as Elf64Word,
+                st_info: *info,
+                st_other: *other,
+                st_shndx: self.shndx(*shndx),
+                st_value: *value,
+                st_size: *size,
+                symbol_name: name.into(),
+            });
+        }
+        let nsyms = symbols.len();
+        elf.sections.push(table64(
+            ".symtab",
+            section::Type::SymTab,
+            symbol::Symbol64::SIZE as u64,
+            (idx.strtab, self.first_global()),
+            section::Contents64::Symbols(symbols),
+        ));
+        elf.sections.push(table64(
+            ".strtab",
+            section::Type::StrTab,
+            0,
+            (0, 0),
+            strtab.to_contents64(),
+        ));
+
+        if let Some((_, target)) = idx.rela {
+            let relas = self
+                .relocations
+                .iter()
+                .map(|(_, offset, sym, ty, addend)| {
+                    let mut rela = relocation::Rela64::default();
+                    rela.set_offset(*offset);
+                    rela.set_info(((sym % nsyms) as u64) << 32 | *ty as u64);
+                    rela.set_addend(*addend);
+                    rela
+                })
+                .collect();
+            elf.sections.push(table64(
+                &format!(".rela{}", self.sections[target - 1].name),
+                section::Type::Rela,
+                relocation::Rela64::SIZE,
+                (idx.symtab, target),
+                section::Contents64::RelaSymbols(relas),
+            ));
+        }
+        if let Some(entries) = &self.dynamics {
+            let entries = entries
+                .iter()
+                .map(|(tag, value)| dynamic::Dyn64 {
+                    d_tag: *tag,
+                    d_un: *value,
+                })
+                .collect();
+            elf.sections.push(table64(
+                ".dynamic",
+                section::Type::Dynamic,
+                dynamic::Dyn64::SIZE as u64,
+                (idx.strtab, 0),
+                section::Contents64::Dynamics(entries),
+            ));
+        }
+        elf.sections.push(table64(
+            ".shstrtab",
+            section::Type::StrTab,
+            0,
+            (0, 0),
+            StringTable::new().to_contents64(),
+        ));
+
+        for _ in 0..self.segment_count {
+            elf.segments.push(segment::Segment64 {
+                header: segment::Phdr64::default(),
+            });
+        }
+
+        let ehdr = &mut elf.ehdr;
+        ehdr.set_class(header::Class::Bit64);
+        ehdr.set_data(header::Data::LSB2);
+        ehdr.set_file_version(header::Version::Current);
+        ehdr.set_object_version(header::Version::Current);
+        ehdr.set_osabi(header::OSABI::SysV);
+        ehdr.set_elf_type(self.ty);
+        ehdr.e_machine = self.machine;
+        ehdr.e_entry = self.entry;
+        ehdr.e_flags = self.flags;
+        ehdr.e_shstrndx = idx.shstrtab as Elf64Half;
+        elf.condition();
+        elf
+    }
+
+    fn into_elf32(self) -> file::ELF32 {
+        let idx = self.indices();
+        let mut strtab = StringTable::new();
+        let mut elf = file::ELF32::default();
+        elf.sections.push(section::Section32 {
+            contents: section::Contents32::Raw(Vec::new()),
+            ..Default::default()
+        });
+
+        for sct in self.sections.iter() {
+            let mut shdr = section::Shdr32 {
+                sh_flags: sct.flags as Elf32Word,
+                sh_addr: sct.addr as Elf32Addr,
+                sh_addralign: sct.align as Elf32Word,
+                ..Default::default()
+            };
+            let contents = match &sct.contents {
+                Ok(bytes) => {
+                    shdr.sh_type = section::Type::ProgBits.into();
+                    bytes.clone()
+                }
+                Err(size) => {
+                    shdr.sh_type = section::Type::NoBits.into();
+                    shdr.sh_size = *size as Elf32Word;
+                    Vec::new()
+                }
+            };
+            elf.sections.push(section::Section32 {
+                name: sct.name.clone(),
+                header: shdr,
+                contents: section::Contents32::Raw(contents),
+            });
+        }
+
+        let mut symbols = vec![symbol::Symbol32::default()];
+        for (name, info, other, shndx, value, size) in self.symbols.iter() {
+            symbols.push(symbol::Symbol32 {
+                st_name: strtab.add(name) as Elf32Word,
+                st_info: *info,
+                st_other: *other,
+                st_shndx: self.shndx(*shndx),
+                st_value: *value as Elf32Addr,
+                st_size: *size as Elf32Word,
+                symbol_name: name.clone(),
+            });
+        }
+        let nsyms = symbols.len();
+        elf.sections.push(table32(
+            ".symtab",
+            section::Type::SymTab,
+            symbol::Symbol32::SIZE as u32,
+            (idx.strtab, self.first_global()),
+            section::Contents32::Symbols(symbols),
+        ));
+        elf.sections.push(table32(
+            ".strtab",
+            section::Type::StrTab,
+            0,
+            (0, 0),
+            strtab.to_contents32(),
+        ));
+
+        if let Some((_, target)) = idx.rela {
+            let relas = self
+                .relocations
+                .iter()
+                .map(|(_, offset, sym, ty, addend)| {
+                    let mut rela = relocation::Rela32::default();
+                    rela.set_offset(*offset as Elf32Addr);
+                    rela.set_info(((sym % nsyms) as Elf32Word) << 8 | *ty as Elf32Word);
+                    rela.set_addend(*addend as Elf32Sword);
+                    rela
+                })
+                .collect();
+            elf.sections.push(table32(
+                &format!(".rela{}", self.sections[target - 1].name),
+                section::Type::Rela,
+                relocation::Rela32::SIZE as u32,
+                (idx.symtab, target),
+                section::Contents32::RelaSymbols(relas),
+            ));
+        }
+        if let Some(entries) = &self.dynamics {
+            let entries = entries
+                .iter()
+                .map(|(tag, value)| dynamic::Dyn32 {
+                    d_tag: *tag as Elf32Sword,
+                    d_un: *value as Elf32Word,
+                })
+                .collect();
+            elf.sections.push(table32(
+                ".dynamic",
+                section::Type::Dynamic,
+                dynamic::Dyn32::SIZE as u32,
+                (idx.strtab, 0),
+                section::Contents32::Dynamics(entries),
+            ));
+        }
+
+        let mut shstrtab = StringTable::new();
+        for sct in elf.sections.iter_mut().skip(1) {
+            sct.header.sh_name = shstrtab.add(&sct.name) as Elf32Word;
+        }
+        let sh_name = shstrtab.add(".shstrtab") as Elf32Word;
+        let mut sct = table32(
+            ".shstrtab",
+            section::Type::StrTab,
+            0,
+            (0, 0),
+            shstrtab.to_contents32(),
+        );
+        sct.header.sh_name = sh_name;
+        elf.sections.push(sct);
+
+        for _ in 0..self.segment_count {
+            elf.segments.push(segment::Segment32 {
+                header: segment::Phdr32::default(),
+            });
+        }
+
+        // ELF32::to_le_bytes()はPHT，セクション，SHTを詰めて書き出す
+        let mut offset =
+            header::Ehdr32::SIZE as u32 + segment::Phdr32::SIZE as u32 * self.segment_count as u32;
+        for sct in elf.sections.iter_mut().skip(1) {
+            sct.header.sh_offset = offset;
+            if sct.header.get_type() != section::Type::NoBits {
+                sct.header.sh_size = sct.contents.size() as u32;
+                offset += sct.header.sh_size;
+            }
+        }
+
+        let ehdr = &mut elf.ehdr;
+        ehdr.set_class(header::Class::Bit32);
+        ehdr.set_data(header::Data::LSB2);
+        ehdr.set_file_version(header::Version::Current);
+        ehdr.set_object_version(header::Version::Current);
+        ehdr.set_osabi(header::OSABI::SysV);
+        ehdr.set_elf_type(self.ty);
+        ehdr.e_machine = self.machine;
+        ehdr.e_version = 1;
+        ehdr.e_entry = self.entry as Elf32Addr;
+        ehdr.e_flags = self.flags;
+        ehdr.e_ehsize = header::Ehdr32::SIZE as Elf32Half;
+        ehdr.e_phentsize = segment::Phdr32::SIZE as Elf32Half;
+        ehdr.e_shentsize = section::Shdr32::SIZE as Elf32Half;
+        ehdr.e_phoff = header::Ehdr32::SIZE as Elf32Off;
+        ehdr.e_phnum = self.segment_count as Elf32Half;
+        ehdr.e_shoff = offset;
+        ehdr.e_shnum = elf.sections.len() as Elf32Half;
+        ehdr.e_shstrndx = idx.shstrtab as Elf32Half;
+        elf
+    }
+}
+
+fn table64(
+    name: &str,
+    ty: section::Type,
+    entsize: u64,
+    (link, info): (usize, usize),
+    contents: section::Contents64,
+) -> section::Section64 {
+    let mut sct = section::Section64::new(
+        name.to_string(),
+        section::ShdrPreparation64::default().ty(ty),
+        contents,
+    );
+    sct.header.sh_addralign = if entsize == 0 { 1 } else { 8 };
+    sct.header.sh_entsize = entsize;
+    sct.header.sh_link = link as Elf64Word;
+    sct.header.sh_info = info as Elf64Word;
+    sct
+}
+
+fn table32(
+    name: &str,
+    ty: section::Type,
+    entsize: u32,
+    (link, info): (usize, usize),
+    contents: section::Contents32,
+) -> section::Section32 {
+    section::Section32 {
+        name: name.to_string(),
+        header: section::Shdr32 {
+            sh_type: ty.into(),
+            sh_addralign: if entsize == 0 { 1 } else { 4 },
+            sh_entsize: entsize,
+            sh_link: link as Elf32Word,
+            sh_info: info as Elf32Word,
+            ..Default::default()
+        },
+        contents,
+    }
+}
+
+impl Arbitrary for file::ELF64 {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    /// a valid file laid out by `condition()`
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        file_plan().prop_map(FilePlan::into_elf64).boxed()
+    }
+}
+
+impl Arbitrary for file::ELF32 {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Self>;
+
+    /// a valid file whose sections are packed in the order of `ELF32::to_le_bytes()`
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        file_plan().prop_map(FilePlan::into_elf32).boxed()
+    }
+}
@@ -0,0 +1,466 @@
+//! `elfutil` - a command line tool built on elf-utilities.
+//!
+//! ```text
+//! elfutil header <file> [--json]
+//! elfutil sections <file> [--json]
+//! elfutil segments <file> [--json]
+//! elfutil symbols <file> [--json]
+//! elfutil dynamic <file> [--json]
+//! elfutil functions <file> [--json]
+//! elfutil set-rpath <file> <path> [-o <output>]
+//! elfutil strip <file> [-o <output>]
+//! elfutil diff <old> <new> [--json]
+//! ```
+
+use std::error::Error;
+use std::process;
+
+use elf_utilities::{analysis, dynamic, file, parser, section, segment};
+use serde::Serialize;
+
+const USAGE: &str = "usage:
+    elfutil header <file> [--json]
+    elfutil sections <file> [--json]
+    elfutil segments <file> [--json]
+    elfutil symbols <file> [--json]
+    elfutil dynamic <file> [--json]
+    elfutil functions <file> [--json]
+    elfutil set-rpath <file> <path> [-o <output>]
+    elfutil strip <file> [-o <output>]
+    elfutil diff <old> <new> [--json]";
+
+/// parsed command line options
+struct Args {
+    command: String,
+    operands: Vec<String>,
+    json: bool,
+    output: Option<String>,
+}
+
+impl Args {
+    fn parse(mut args: impl Iterator<Item = String>) -> Option<Self> {
+        let mut command = None;
+        let mut operands = Vec::new();
+        let mut json = false;
+        let mut output = None;
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--json" => json = true,
+                "-o" | "--output" => output = Some(args.next()?),
+                _ if command.is_none() => command = Some(arg),
+                _ => operands.push(arg),
+            }
+        }
+
+        Some(Self {
+            command: command?,
+            operands,
+            json,
+            output,
+        })
+    }
+}
+
+fn main() {
+    let args = match Args::parse(std::env::args().skip(1)) {
+        Some(args) => args,
+        None => usage(),
+    };
+
+    match run(&args) {
+        Ok(code) => process::exit(code),
+        Err(e) => {
+            eprintln!("elfutil: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
+
+/// run the command and return the exit status.
+fn run(args: &Args) -> Result<i32, Box<dyn Error>> {
+    let operand = |n: usize| match args.operands.get(n) {
+        Some(op) => op.as_str(),
+        None => usage(),
+    };
+
+    match args.command.as_str() {
+        "header" => print(&header(&parser::parse_elf64(operand(0))?), args.json),
+        "sections" => print(&sections(&parser::parse_elf64(operand(0))?), args.json),
+        "segments" => print(&segments(&parser::parse_elf64(operand(0))?), args.json),
+        "symbols" => print(&symbols(&parser::parse_elf64(operand(0))?), args.json),
+        "dynamic" => print(&dynamics(&parser::parse_elf64(operand(0))?), args.json),
+        "functions" => print(
+            &analysis::function_facts(&parser::parse_elf64(operand(0))?),
+            args.json,
+        ),
+        "set-rpath" => {
+            let mut elf = parser::parse_elf64(operand(0))?;
+            elf.set_rpath(operand(1))?;
+            write_elf(&elf, operand(0), args.output.as_deref())?;
+            Ok(0)
+        }
+        "strip" => {
+            let mut elf = parser::parse_elf64(operand(0))?;
+            for name in elf.strip() {
+                eprintln!("removed {}", name);
+            }
+            write_elf(&elf, operand(0), args.output.as_deref())?;
+            Ok(0)
+        }
+        "diff" => {
+            let old = parser::parse_elf64(operand(0))?;
+            let new = parser::parse_elf64(operand(1))?;
+            let diffs = analysis::diff(&old, &new);
+            if args.json {
+                println!("{}", serde_json::to_string_pretty(&diffs)?);
+            } else {
+                for d in diffs.iter() {
+                    println!("{}", d);
+                }
+            }
+            // diff(1)と同様に，差分があれば1を返す
+            Ok(if diffs.is_empty() { 0 } else { 1 })
+        }
+        _ => usage(),
+    }
+}
+
+/// rows which can be printed as a text table or JSON
+trait Report: Serialize {
+    fn print_text(&self);
+}
+
+fn print<R: Report>(report: &R, json: bool) -> Result<i32, Box<dyn Error>> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(report)?);
+    } else {
+        report.print_text();
+    }
+    Ok(0)
+}
+
+/// write the file to `output`, or overwrite `input` keeping its permissions.
+fn write_elf(elf: &file::ELF64, input: &str, output: Option<&str>) -> Result<(), Box<dyn Error>> {
+    let path = output.unwrap_or(input);
+    let permissions = std::fs::metadata(input)?.permissions();
+    std::fs::write(path, elf.to_le_bytes())?;
+    std::fs::set_permissions(path, permissions)?;
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct Header {
+    class: String,
+    data: String,
+    osabi: String,
+    #[serde(rename = "type")]
+    ty: String,
+    machine: String,
+    entry: u64,
+    phoff: u64,
+    shoff: u64,
+    flags: u32,
+    phnum: u16,
+    shnum: u16,
+    shstrndx: u16,
+}
+
+impl Report for Header {
+    fn print_text(&self) {
+        println!("Class:      {}", self.class);
+        println!("Data:       {}", self.data);
+        println!("OS/ABI:     {}", self.osabi);
+        println!("Type:       {}", self.ty);
+        println!("Machine:    {}", self.machine);
+        println!("Entry:      {:#x}", self.entry);
+        println!("PHT offset: {:#x} ({} entries)", self.phoff, self.phnum);
+        println!("SHT offset: {:#x} ({} entries)", self.shoff, self.shnum);
+        println!("Flags:      {:#x}", self.flags);
+        println!("shstrndx:   {}", self.shstrndx);
+    }
+}
+
+fn header(elf: &file::ELF64) -> Header {
+    let ehdr = &elf.ehdr;
+    Header {
+        class: format!("{:?}", ehdr.get_class()),
+        data: format!("{:?}", ehdr.get_data()),
+        osabi: format!("{:?}", ehdr.get_osabi()),
+        ty: format!("{:?}", ehdr.get_type()),
+        machine: format!("{:?}", ehdr.get_machine()),
+        entry: ehdr.e_entry,
+        phoff: ehdr.e_phoff,
+        shoff: ehdr.e_shoff,
+        flags: ehdr.e_flags,
+        phnum: ehdr.e_phnum,
+        shnum: ehdr.e_shnum,
+        shstrndx: ehdr.e_shstrndx,
+    }
+}
+
+#[derive(Serialize)]
+struct Section {
+    index: usize,
+    name: String,
+    #[serde(rename = "type")]
+    ty: String,
+    flags: Vec<String>,
+    addr: u64,
+    offset: u64,
+    size: u64,
+    link: u32,
+    info: u32,
+    align: u64,
+    entsize: u64,
+}
+
+impl Report for Vec<Section> {
+    fn print_text(&self) {
+        println!(
+            "{:>4} {:<24} {:<16} {:>16} {:>8} {:>8} flags",
+            "idx", "name", "type", "addr", "offset", "size"
+        );
+        for sct in self.iter() {
+            println!(
+                "{:>4} {:<24} {:<16} {:>16x} {:>8x} {:>8x} {}",
+                sct.index,
+                sct.name,
+                sct.ty,
+                sct.addr,
+                sct.offset,
+                sct.size,
+                sct.flags.join("|")
+            );
+        }
+    }
+}
+
+fn sections(elf: &file::ELF64) -> Vec<Section> {
+    elf.sections
+        .iter()
+        .enumerate()
+        .map(|(index, sct)| Section {
+            index,
+            name: sct.name.to_string(),
+            ty: format!("{:?}", sct.header.get_type()),
+            flags: sorted_debug(sct.header.get_flags()),
+            addr: sct.header.sh_addr,
+            offset: sct.header.sh_offset,
+            size: sct.header.sh_size,
+            link: sct.header.sh_link,
+            info: sct.header.sh_info,
+            align: sct.header.sh_addralign,
+            entsize: sct.header.sh_entsize,
+        })
+        .collect()
+}
+
+#[derive(Serialize)]
+struct Segment {
+    index: usize,
+    #[serde(rename = "type")]
+    ty: String,
+    flags: Vec<String>,
+    offset: u64,
+    vaddr: u64,
+    paddr: u64,
+    filesz: u64,
+    memsz: u64,
+    align: u64,
+}
+
+impl Report for Vec<Segment> {
+    fn print_text(&self) {
+        println!(
+            "{:>4} {:<16} {:>8} {:>16} {:>8} {:>8} flags",
+            "idx", "type", "offset", "vaddr", "filesz", "memsz"
+        );
+        for sgt in self.iter() {
+            println!(
+                "{:>4} {:<16} {:>8x} {:>16x} {:>8x} {:>8x} {}",
+                sgt.index,
+                sgt.ty,
+                sgt.offset,
+                sgt.vaddr,
+                sgt.filesz,
+                sgt.memsz,
+                sgt.flags.join("|")
+            );
+        }
+    }
+}
+
+fn segments(elf: &file::ELF64) -> Vec<Segment> {
+    elf.segments
+        .iter()
+        .enumerate()
+        .map(|(index, sgt)| {
+            let phdr: &segment::Phdr64 = &sgt.header;
+            Segment {
+                index,
+                ty: format!("{:?}", phdr.get_type()),
+                flags: sorted_debug(phdr.get_flags()),
+                offset: phdr.p_offset,
+                vaddr: phdr.p_vaddr,
+                paddr: phdr.p_paddr,
+                filesz: phdr.p_filesz,
+                memsz: phdr.p_memsz,
+                align: phdr.p_align,
+            }
+        })
+        .collect()
+}
+
+#[derive(Serialize)]
+struct Symbol {
+    table: String,
+    index: usize,
+    name: String,
+    value: u64,
+    size: u64,
+    #[serde(rename = "type")]
+    ty: String,
+    bind: String,
+    visibility: String,
+    shndx: u16,
+}
+
+impl Report for Vec<Symbol> {
+    fn print_text(&self) {
+        println!(
+            "{:<8} {:>5} {:>16} {:>6} {:<8} {:<8} {:>5} name",
+            "table", "idx", "value", "size", "type", "bind", "shndx"
+        );
+        for sym in self.iter() {
+            println!(
+                "{:<8} {:>5} {:>16x} {:>6} {:<8} {:<8} {:>5} {}",
+                sym.table, sym.index, sym.value, sym.size, sym.ty, sym.bind, sym.shndx, sym.name
+            );
+        }
+    }
+}
+
+fn symbols(elf: &file::ELF64) -> Vec<Symbol> {
+    let mut rows = Vec::new();
+    for sct in elf.sections.iter() {
+        let ty = sct.header.get_type();
+        if ty != section::Type::SymTab && ty != section::Type::DynSym {
+            continue;
+        }
+        if let section::Contents64::Symbols(syms) = &sct.contents {
+            rows.extend(syms.iter().enumerate().map(|(index, sym)| Symbol {
+                table: sct.name.to_string(),
+                index,
+                name: sym.symbol_name.to_string(),
+                value: sym.st_value,
+                size: sym.st_size,
+                ty: format!("{:?}", sym.get_type()),
+                bind: format!("{:?}", sym.get_bind()),
+                visibility: format!("{:?}", sym.get_visibility()),
+                shndx: sym.st_shndx,
+            }));
+        }
+    }
+    rows
+}
+
+#[derive(Serialize)]
+struct Dynamic {
+    tag: String,
+    value: u64,
+    /// the string for the entries which point into `.dynstr`(`DT_NEEDED`, etc.)
+    string: Option<String>,
+}
+
+impl Report for Vec<Dynamic> {
+    fn print_text(&self) {
+        for ent in self.iter() {
+            match &ent.string {
+                Some(s) => println!("{:<20} {}", ent.tag, s),
+                None => println!("{:<20} {:#x}", ent.tag, ent.value),
+            }
+        }
+    }
+}
+
+fn dynamics(elf: &file::ELF64) -> Vec<Dynamic> {
+    let dynamic = match elf.first_section_by(|sct| sct.header.get_type() == section::Type::Dynamic)
+    {
+        Some(sct) => sct,
+        None => return Vec::new(),
+    };
+    let dynstr = elf
+        .sections
+        .get(dynamic.header.sh_link as usize)
+        .map(|sct| sct.to_le_bytes())
+        .unwrap_or_default();
+    let string_at = |offset: u64| {
+        let bytes = dynstr.get(offset as usize..)?;
+        let end = bytes.iter().position(|b| *b == 0)?;
+        Some(String::from_utf8_lossy(&bytes[..end]).to_string())
+    };
+
+    let entries = match &dynamic.contents {
+        section::Contents64::Dynamics(entries) => entries,
+        _ => return Vec::new(),
+    };
+    entries
+        .iter()
+        .map(|ent| {
+            let ty = ent.get_type();
+            let has_string = matches!(
+                ty,
+                dynamic::EntryType::Needed
+                    | dynamic::EntryType::SOName
+                    | dynamic::EntryType::RPath
+                    | dynamic::EntryType::RunPath
+            );
+            Dynamic {
+                tag: ty.to_string(),
+                value: ent.d_un,
+                string: if has_string {
+                    string_at(ent.d_un)
+                } else {
+                    None
+                },
+            }
+        })
+        .collect()
+}
+
+impl Report for Vec<analysis::FunctionFacts> {
+    fn print_text(&self) {
+        println!(
+            "{:>16} {:>16} {:<6} {:>5} {:>5} name",
+            "start", "end", "unwind", "pads", "calls"
+        );
+        for f in self.iter() {
+            let unwind = match (f.unwind_info, f.exception_table) {
+                (true, true) => "lsda",
+                (true, false) => "yes",
+                _ => "no",
+            };
+            println!(
+                "{:>16x} {:>16x} {:<6} {:>5} {:>5} {}",
+                f.start,
+                f.end,
+                unwind,
+                f.landing_pads.len(),
+                f.calls.len(),
+                f.name
+            );
+        }
+    }
+}
+
+fn sorted_debug<T: std::fmt::Debug>(items: impl IntoIterator<Item = T>) -> Vec<String> {
+    let mut names: Vec<String> = items.into_iter().map(|i| format!("{:?}", i)).collect();
+    names.sort();
+    names
+}
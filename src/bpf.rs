@@ -0,0 +1,700 @@
+//! eBPF objects, following the conventions of libbpf.
+//!
+//! The programs are the functions in the sections named after their types(e.g. `xdp`, `kprobe/<function>`),
+//! and the maps are defined in `.maps` with BTF or in `maps` as `struct bpf_map_def`.
+
+use crate::*;
+use btf::{Btf, BtfError, BtfKind, BtfTypeData};
+
+/// `EM_BPF`
+pub const EM_BPF: Elf64Half = 247;
+
+pub const R_BPF_NONE: Elf64Xword = 0;
+/// the 64-bit immediate of `ld_imm64`, which refers to a map or a global variable
+pub const R_BPF_64_64: Elf64Xword = 1;
+/// a 64-bit data, e.g. in `.BTF.ext`
+pub const R_BPF_64_ABS64: Elf64Xword = 2;
+/// a 32-bit data, e.g. in `.BTF`
+pub const R_BPF_64_ABS32: Elf64Xword = 3;
+/// a 32-bit data which the JIT linkers don't resolve, e.g. in `.debug_*`
+pub const R_BPF_64_NODYLD32: Elf64Xword = 4;
+/// the 32-bit immediate of a call to a subprogram
+pub const R_BPF_64_32: Elf64Xword = 10;
+
+/// the section with the license, which decides the helpers a program may call
+pub const LICENSE_SECTION: &str = "license";
+/// the section with the kernel version, which old kernels check for kprobes
+pub const VERSION_SECTION: &str = "version";
+/// the section of the maps defined with BTF
+pub const MAPS_SECTION: &str = ".maps";
+/// the section of the maps defined as `struct bpf_map_def`
+pub const LEGACY_MAPS_SECTION: &str = "maps";
+
+/// the name of a BPF relocation type, as readelf does.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::bpf;
+///
+/// assert_eq!(Some("R_BPF_64_64"), bpf::relocation_type_name(bpf::R_BPF_64_64));
+/// assert_eq!(None, bpf::relocation_type_name(5));
+/// ```
+pub fn relocation_type_name(ty: Elf64Xword) -> Option<&'static str> {
+    match ty {
+        R_BPF_NONE => Some("R_BPF_NONE"),
+        R_BPF_64_64 => Some("R_BPF_64_64"),
+        R_BPF_64_ABS64 => Some("R_BPF_64_ABS64"),
+        R_BPF_64_ABS32 => Some("R_BPF_64_ABS32"),
+        R_BPF_64_NODYLD32 => Some("R_BPF_64_NODYLD32"),
+        R_BPF_64_32 => Some("R_BPF_64_32"),
+        _ => None,
+    }
+}
+
+/// The type of a program(`enum bpf_prog_type`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ProgramType {
+    SocketFilter,
+    Kprobe,
+    SchedCls,
+    SchedAct,
+    Tracepoint,
+    Xdp,
+    PerfEvent,
+    CgroupSkb,
+    CgroupSock,
+    LwtIn,
+    LwtOut,
+    LwtXmit,
+    SockOps,
+    SkSkb,
+    CgroupDevice,
+    SkMsg,
+    RawTracepoint,
+    CgroupSockAddr,
+    LwtSeg6Local,
+    LircMode2,
+    SkReuseport,
+    FlowDissector,
+    CgroupSysctl,
+    RawTracepointWritable,
+    CgroupSockopt,
+    Tracing,
+    StructOps,
+    Ext,
+    Lsm,
+    SkLookup,
+    Syscall,
+    Netfilter,
+}
+
+impl ProgramType {
+    /// the value passed to `BPF_PROG_LOAD`.
+    pub fn to_u32(self) -> u32 {
+        match self {
+            Self::SocketFilter => 1,
+            Self::Kprobe => 2,
+            Self::SchedCls => 3,
+            Self::SchedAct => 4,
+            Self::Tracepoint => 5,
+            Self::Xdp => 6,
+            Self::PerfEvent => 7,
+            Self::CgroupSkb => 8,
+            Self::CgroupSock => 9,
+            Self::LwtIn => 10,
+            Self::LwtOut => 11,
+            Self::LwtXmit => 12,
+            Self::SockOps => 13,
+            Self::SkSkb => 14,
+            Self::CgroupDevice => 15,
+            Self::SkMsg => 16,
+            Self::RawTracepoint => 17,
+            Self::CgroupSockAddr => 18,
+            Self::LwtSeg6Local => 19,
+            Self::LircMode2 => 20,
+            Self::SkReuseport => 21,
+            Self::FlowDissector => 22,
+            Self::CgroupSysctl => 23,
+            Self::RawTracepointWritable => 24,
+            Self::CgroupSockopt => 25,
+            Self::Tracing => 26,
+            Self::StructOps => 27,
+            Self::Ext => 28,
+            Self::Lsm => 29,
+            Self::SkLookup => 30,
+            Self::Syscall => 31,
+            Self::Netfilter => 32,
+        }
+    }
+}
+
+/// the section names libbpf recognizes, which may be followed by `/<attach target>`.
+const PROGRAM_SECTIONS: &[(&str, ProgramType)] = &[
+    ("socket", ProgramType::SocketFilter),
+    ("sk_reuseport", ProgramType::SkReuseport),
+    ("sk_reuseport/migrate", ProgramType::SkReuseport),
+    ("kprobe", ProgramType::Kprobe),
+    ("uprobe", ProgramType::Kprobe),
+    ("uprobe.s", ProgramType::Kprobe),
+    ("kretprobe", ProgramType::Kprobe),
+    ("uretprobe", ProgramType::Kprobe),
+    ("uretprobe.s", ProgramType::Kprobe),
+    ("kprobe.multi", ProgramType::Kprobe),
+    ("kretprobe.multi", ProgramType::Kprobe),
+    ("ksyscall", ProgramType::Kprobe),
+    ("kretsyscall", ProgramType::Kprobe),
+    ("usdt", ProgramType::Kprobe),
+    ("tc", ProgramType::SchedCls),
+    ("classifier", ProgramType::SchedCls),
+    ("action", ProgramType::SchedAct),
+    ("tracepoint", ProgramType::Tracepoint),
+    ("tp", ProgramType::Tracepoint),
+    ("raw_tracepoint", ProgramType::RawTracepoint),
+    ("raw_tp", ProgramType::RawTracepoint),
+    ("raw_tracepoint.w", ProgramType::RawTracepointWritable),
+    ("raw_tp.w", ProgramType::RawTracepointWritable),
+    ("tp_btf", ProgramType::Tracing),
+    ("fentry", ProgramType::Tracing),
+    ("fmod_ret", ProgramType::Tracing),
+    ("fexit", ProgramType::Tracing),
+    ("fentry.s", ProgramType::Tracing),
+    ("fmod_ret.s", ProgramType::Tracing),
+    ("fexit.s", ProgramType::Tracing),
+    ("iter", ProgramType::Tracing),
+    ("iter.s", ProgramType::Tracing),
+    ("freplace", ProgramType::Ext),
+    ("lsm", ProgramType::Lsm),
+    ("lsm.s", ProgramType::Lsm),
+    ("lsm_cgroup", ProgramType::Lsm),
+    ("syscall", ProgramType::Syscall),
+    ("xdp", ProgramType::Xdp),
+    ("xdp.frags", ProgramType::Xdp),
+    ("xdp/devmap", ProgramType::Xdp),
+    ("xdp/cpumap", ProgramType::Xdp),
+    ("perf_event", ProgramType::PerfEvent),
+    ("lwt_in", ProgramType::LwtIn),
+    ("lwt_out", ProgramType::LwtOut),
+    ("lwt_xmit", ProgramType::LwtXmit),
+    ("lwt_seg6local", ProgramType::LwtSeg6Local),
+    ("sockops", ProgramType::SockOps),
+    ("sk_skb", ProgramType::SkSkb),
+    ("sk_skb/stream_parser", ProgramType::SkSkb),
+    ("sk_skb/stream_verdict", ProgramType::SkSkb),
+    ("sk_msg", ProgramType::SkMsg),
+    ("lirc_mode2", ProgramType::LircMode2),
+    ("flow_dissector", ProgramType::FlowDissector),
+    ("cgroup_skb/ingress", ProgramType::CgroupSkb),
+    ("cgroup_skb/egress", ProgramType::CgroupSkb),
+    ("cgroup/skb", ProgramType::CgroupSkb),
+    ("cgroup/sock", ProgramType::CgroupSock),
+    ("cgroup/sock_create", ProgramType::CgroupSock),
+    ("cgroup/sock_release", ProgramType::CgroupSock),
+    ("cgroup/post_bind4", ProgramType::CgroupSock),
+    ("cgroup/post_bind6", ProgramType::CgroupSock),
+    ("cgroup/bind4", ProgramType::CgroupSockAddr),
+    ("cgroup/bind6", ProgramType::CgroupSockAddr),
+    ("cgroup/connect4", ProgramType::CgroupSockAddr),
+    ("cgroup/connect6", ProgramType::CgroupSockAddr),
+    ("cgroup/sendmsg4", ProgramType::CgroupSockAddr),
+    ("cgroup/sendmsg6", ProgramType::CgroupSockAddr),
+    ("cgroup/recvmsg4", ProgramType::CgroupSockAddr),
+    ("cgroup/recvmsg6", ProgramType::CgroupSockAddr),
+    ("cgroup/getpeername4", ProgramType::CgroupSockAddr),
+    ("cgroup/getpeername6", ProgramType::CgroupSockAddr),
+    ("cgroup/getsockname4", ProgramType::CgroupSockAddr),
+    ("cgroup/getsockname6", ProgramType::CgroupSockAddr),
+    ("cgroup/sysctl", ProgramType::CgroupSysctl),
+    ("cgroup/getsockopt", ProgramType::CgroupSockopt),
+    ("cgroup/setsockopt", ProgramType::CgroupSockopt),
+    ("cgroup/dev", ProgramType::CgroupDevice),
+    ("struct_ops", ProgramType::StructOps),
+    ("struct_ops.s", ProgramType::StructOps),
+    ("sk_lookup", ProgramType::SkLookup),
+    ("netfilter", ProgramType::Netfilter),
+];
+
+/// the type of the programs in the section and the attach target following the prefix,
+/// e.g. `do_sys_open` of `kprobe/do_sys_open`.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::bpf;
+///
+/// assert_eq!(
+///     Some((bpf::ProgramType::Kprobe, Some("do_sys_open"))),
+///     bpf::program_section("kprobe/do_sys_open")
+/// );
+/// assert_eq!(Some((bpf::ProgramType::Xdp, None)), bpf::program_section("xdp.frags"));
+/// assert_eq!(
+///     Some((bpf::ProgramType::CgroupSockAddr, None)),
+///     bpf::program_section("cgroup/connect4")
+/// );
+/// assert_eq!(None, bpf::program_section(".text"));
+/// ```
+pub fn program_section(name: &str) -> Option<(ProgramType, Option<&str>)> {
+    // 最も長く一致した接頭辞を選ぶ(e.g. "xdp/devmap" と "xdp")
+    PROGRAM_SECTIONS
+        .iter()
+        .filter_map(|(prefix, ty)| {
+            if name == *prefix {
+                Some((prefix.len(), *ty, None))
+            } else {
+                let target = name.strip_prefix(prefix)?.strip_prefix('/')?;
+                Some((prefix.len(), *ty, Some(target)))
+            }
+        })
+        .max_by_key(|(len, _, _)| *len)
+        .map(|(_, ty, target)| (ty, target))
+}
+
+/// the name of a map type(`enum bpf_map_type`) without `BPF_MAP_TYPE_`.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::bpf;
+///
+/// assert_eq!(Some("ARRAY"), bpf::map_type_name(2));
+/// assert_eq!(None, bpf::map_type_name(0x1000));
+/// ```
+pub fn map_type_name(map_type: u32) -> Option<&'static str> {
+    const NAMES: [&str; 34] = [
+        "UNSPEC",
+        "HASH",
+        "ARRAY",
+        "PROG_ARRAY",
+        "PERF_EVENT_ARRAY",
+        "PERCPU_HASH",
+        "PERCPU_ARRAY",
+        "STACK_TRACE",
+        "CGROUP_ARRAY",
+        "LRU_HASH",
+        "LRU_PERCPU_HASH",
+        "LPM_TRIE",
+        "ARRAY_OF_MAPS",
+        "HASH_OF_MAPS",
+        "DEVMAP",
+        "SOCKMAP",
+        "CPUMAP",
+        "XSKMAP",
+        "SOCKHASH",
+        "CGROUP_STORAGE",
+        "REUSEPORT_SOCKARRAY",
+        "PERCPU_CGROUP_STORAGE",
+        "QUEUE",
+        "STACK",
+        "SK_STORAGE",
+        "DEVMAP_HASH",
+        "STRUCT_OPS",
+        "RINGBUF",
+        "INODE_STORAGE",
+        "TASK_STORAGE",
+        "BLOOM_FILTER",
+        "USER_RINGBUF",
+        "CGRP_STORAGE",
+        "ARENA",
+    ];
+    NAMES.get(map_type as usize).copied()
+}
+
+/// A program, which is a global function in a program section
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Program {
+    pub name: String,
+    pub section: String,
+    pub shidx: usize,
+    pub ty: ProgramType,
+    pub attach_target: Option<String>,
+    /// the offset in the section
+    pub offset: Elf64Addr,
+    pub size: Elf64Xword,
+}
+
+impl Program {
+    /// the number of the instructions(`ld_imm64` counts as two).
+    pub fn instructions(&self) -> u64 {
+        self.size / 8
+    }
+}
+
+/// A map definition
+///
+/// The fields which the definition omits are 0, as libbpf does.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Map {
+    pub name: String,
+    /// `.maps` or `maps`
+    pub section: String,
+    /// `enum bpf_map_type`
+    pub map_type: u32,
+    pub key_size: u32,
+    pub value_size: u32,
+    pub max_entries: u32,
+    pub map_flags: u32,
+    /// the BTF type of the key, only for the maps in `.maps`
+    pub key_type: Option<u32>,
+    /// the BTF type of the value, only for the maps in `.maps`
+    pub value_type: Option<u32>,
+}
+
+/// A relocation in a program section
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Relocation {
+    /// the program section
+    pub section: String,
+    /// the offset in the section
+    pub offset: Elf64Addr,
+    /// `R_BPF_*`
+    pub ty: Elf64Xword,
+    pub symbol: String,
+    /// the section which the symbol is defined in, e.g. `.maps` for the references to maps
+    pub symbol_section: Option<String>,
+}
+
+impl Relocation {
+    /// the index of the instruction.
+    pub fn instruction(&self) -> u64 {
+        self.offset / 8
+    }
+
+    /// whether the instruction loads the address of a map.
+    pub fn is_map_reference(&self) -> bool {
+        self.ty == R_BPF_64_64
+            && matches!(
+                self.symbol_section.as_deref(),
+                Some(MAPS_SECTION) | Some(LEGACY_MAPS_SECTION)
+            )
+    }
+}
+
+impl file::ELF64 {
+    pub fn is_bpf(&self) -> bool {
+        self.ehdr.e_machine == EM_BPF
+    }
+
+    /// the programs, ordered by section and offset.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use elf_utilities::{bpf, parser};
+    ///
+    /// let elf = parser::parse_elf64("tests/fixtures/bpf.o").unwrap();
+    /// let programs = elf.bpf_programs();
+    /// assert_eq!("trace_open", programs[1].name);
+    /// assert_eq!(bpf::ProgramType::Kprobe, programs[1].ty);
+    /// assert_eq!(Some("do_sys_open".to_string()), programs[1].attach_target);
+    /// assert_eq!(Some("GPL".to_string()), elf.bpf_license());
+    /// ```
+    pub fn bpf_programs(&self) -> Vec<Program> {
+        let mut programs = Vec::new();
+        for sym in self.bpf_symbols() {
+            if sym.get_type() != symbol::Type::Func || sym.get_bind() == symbol::Bind::Local {
+                continue;
+            }
+            let shidx = sym.st_shndx as usize;
+            let sct = match self.sections.get(shidx) {
+                Some(sct) if sym.st_shndx != section::SHN_UNDEF => sct,
+                _ => continue,
+            };
+            let (ty, attach_target) = match program_section(&sct.name) {
+                Some(classified) => classified,
+                None => continue,
+            };
+            programs.push(Program {
+                name: sym.symbol_name.to_string(),
+                section: sct.name.to_string(),
+                shidx,
+                ty,
+                attach_target: attach_target.map(|s| s.to_string()),
+                offset: sym.st_value,
+                size: sym.st_size,
+            });
+        }
+        programs.sort_by_key(|prog| (prog.shidx, prog.offset));
+        programs
+    }
+
+    /// the string in the `license` section.
+    pub fn bpf_license(&self) -> Option<String> {
+        let shidx = self.first_shidx_by(|sct| sct.name == LICENSE_SECTION)?;
+        let mut r = self.section_reader(shidx)?;
+        r.cstr().ok()
+    }
+
+    /// the kernel version in the `version` section(`LINUX_VERSION_CODE`).
+    pub fn bpf_kernel_version(&self) -> Option<u32> {
+        let shidx = self.first_shidx_by(|sct| sct.name == VERSION_SECTION)?;
+        let mut r = self.section_reader(shidx)?;
+        r.u32().ok()
+    }
+
+    /// the maps defined in `.maps` and `maps`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use elf_utilities::{bpf, parser};
+    ///
+    /// let elf = parser::parse_elf64("tests/fixtures/bpf.o").unwrap();
+    /// let maps = elf.bpf_maps().unwrap();
+    /// assert_eq!("counts", maps[0].name);
+    /// assert_eq!(Some("ARRAY"), bpf::map_type_name(maps[0].map_type));
+    /// assert_eq!((4, 8, 16), (maps[0].key_size, maps[0].value_size, maps[0].max_entries));
+    /// ```
+    pub fn bpf_maps(&self) -> Result<Vec<Map>, BtfError> {
+        let mut maps = Vec::new();
+        if let Some(btf) = self.btf() {
+            maps.extend(btf_maps(&btf?));
+        }
+
+        let shidx = match self.first_shidx_by(|sct| sct.name == LEGACY_MAPS_SECTION) {
+            Some(shidx) => shidx,
+            None => return Ok(maps),
+        };
+        let mut defs: Vec<&symbol::Symbol64> = self
+            .bpf_symbols()
+            .filter(|sym| sym.st_shndx as usize == shidx && !sym.symbol_name.is_empty())
+            .filter(|sym| sym.get_type() != symbol::Type::Section)
+            .collect();
+        if defs.is_empty() {
+            return Ok(maps);
+        }
+        defs.sort_by_key(|sym| sym.st_value);
+        // libbpfと同様に，定義の大きさはセクションを等分したもの
+        let def_size = self.sections[shidx].header.sh_size / defs.len() as u64;
+        for sym in defs {
+            let mut r = match self.section_reader(shidx) {
+                Some(r) => r,
+                None => break,
+            };
+            r.seek(sym.st_value as usize)?;
+            let mut fields = [0; 5];
+            for field in fields.iter_mut().take((def_size / 4) as usize) {
+                *field = r.u32()?;
+            }
+            maps.push(Map {
+                name: sym.symbol_name.to_string(),
+                section: LEGACY_MAPS_SECTION.to_string(),
+                map_type: fields[0],
+                key_size: fields[1],
+                value_size: fields[2],
+                max_entries: fields[3],
+                map_flags: fields[4],
+                key_type: None,
+                value_type: None,
+            });
+        }
+        Ok(maps)
+    }
+
+    /// the relocations in the program sections.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use elf_utilities::{bpf, parser};
+    ///
+    /// let elf = parser::parse_elf64("tests/fixtures/bpf.o").unwrap();
+    /// let relocations = elf.bpf_relocations();
+    /// assert_eq!("counts", relocations[0].symbol);
+    /// assert_eq!(4, relocations[0].instruction());
+    /// assert!(relocations[0].is_map_reference());
+    /// ```
+    pub fn bpf_relocations(&self) -> Vec<Relocation> {
+        let mut relocations = Vec::new();
+        for (shidx, sct) in self.sections.iter().enumerate() {
+            if sct.header.get_type() != section::Type::Rel {
+                continue;
+            }
+            let target = match self.sections.get(sct.header.sh_info as usize) {
+                Some(target) if program_section(&target.name).is_some() => target,
+                _ => continue,
+            };
+            let syms = match self.sections.get(sct.header.sh_link as usize) {
+                Some(section::Section64 {
+                    contents: section::Contents64::Symbols(syms),
+                    ..
+                }) => syms,
+                _ => continue,
+            };
+            let mut r = match self.section_reader(shidx) {
+                Some(r) => r,
+                None => continue,
+            };
+            // r_offset, r_info
+            while r.remaining() >= 16 {
+                let (offset, info) = match (r.u64(), r.u64()) {
+                    (Ok(offset), Ok(info)) => (offset, info),
+                    _ => break,
+                };
+                let sym = syms.get((info >> 32) as usize);
+                relocations.push(Relocation {
+                    section: target.name.to_string(),
+                    offset,
+                    ty: info & 0xffffffff,
+                    symbol: sym
+                        .map(|sym| sym.symbol_name.to_string())
+                        .unwrap_or_default(),
+                    symbol_section: sym
+                        .filter(|sym| sym.st_shndx != section::SHN_UNDEF)
+                        .and_then(|sym| self.sections.get(sym.st_shndx as usize))
+                        .map(|sct| sct.name.to_string()),
+                });
+            }
+        }
+        relocations
+    }
+
+    fn bpf_symbols(&self) -> impl Iterator<Item = &symbol::Symbol64> {
+        let symtab = self.first_section_by(|sct| sct.header.get_type() == section::Type::SymTab);
+        let syms: &[symbol::Symbol64] = match symtab {
+            Some(section::Section64 {
+                contents: section::Contents64::Symbols(syms),
+                ..
+            }) => syms,
+            _ => &[],
+        };
+        syms.iter()
+    }
+}
+
+/// the maps in `DATASEC .maps`, whose members are encoded as the types by `__uint()` and `__type()`.
+fn btf_maps(btf: &Btf) -> Vec<Map> {
+    let vars = btf
+        .types
+        .iter()
+        .filter(|ty| ty.kind == BtfKind::DataSec && ty.name == MAPS_SECTION)
+        .flat_map(|ty| match &ty.data {
+            BtfTypeData::DataSec(vars) => vars.clone(),
+            _ => Vec::new(),
+        });
+
+    let mut maps = Vec::new();
+    for var in vars {
+        let var = match btf.type_by_id(var.ty) {
+            Some(var) if var.kind == BtfKind::Var => var,
+            _ => continue,
+        };
+        let def = match btf.type_by_id(btf.skip_modifiers(var.size_or_type)) {
+            Some(def) if def.kind == BtfKind::Struct => def,
+            _ => continue,
+        };
+        let members = match &def.data {
+            BtfTypeData::Members(members) => members,
+            _ => continue,
+        };
+
+        let mut map = Map {
+            name: var.name.clone(),
+            section: MAPS_SECTION.to_string(),
+            map_type: 0,
+            key_size: 0,
+            value_size: 0,
+            max_entries: 0,
+            map_flags: 0,
+            key_type: None,
+            value_type: None,
+        };
+        for member in members {
+            // どのメンバもポインタで，__uint(name, N)は int (*)[N]，__type(name, T)は T *
+            let pointee = match btf.type_by_id(member.ty) {
+                Some(ptr) if ptr.kind == BtfKind::Ptr => ptr.size_or_type,
+                _ => continue,
+            };
+            let nelems = match btf.type_by_id(pointee).map(|ty| &ty.data) {
+                Some(BtfTypeData::Array { nelems, .. }) => Some(*nelems),
+                _ => None,
+            };
+            match (member.name.as_str(), nelems) {
+                ("type", Some(n)) => map.map_type = n,
+                ("max_entries", Some(n)) => map.max_entries = n,
+                ("map_flags", Some(n)) => map.map_flags = n,
+                ("key_size", Some(n)) => map.key_size = n,
+                ("value_size", Some(n)) => map.value_size = n,
+                ("key", _) => {
+                    map.key_type = Some(pointee);
+                    map.key_size = btf.type_size(pointee).unwrap_or_default() as u32;
+                }
+                ("value", _) => {
+                    map.value_type = Some(pointee);
+                    map.value_size = btf.type_size(pointee).unwrap_or_default() as u32;
+                }
+                _ => {}
+            }
+        }
+        maps.push(map);
+    }
+    maps
+}
+
+#[cfg(test)]
+mod bpf_tests {
+    use super::*;
+
+    #[test]
+    fn bpf_object_test() {
+        let elf = parser::parse_elf64("tests/fixtures/bpf.o").unwrap();
+        assert!(elf.is_bpf());
+
+        let programs = elf.bpf_programs();
+        let programs: Vec<(&str, &str, ProgramType, u64)> = programs
+            .iter()
+            .map(|prog| {
+                (
+                    prog.name.as_str(),
+                    prog.section.as_str(),
+                    prog.ty,
+                    prog.instructions(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                ("prog", "xdp", ProgramType::Xdp, 2),
+                ("trace_open", "kprobe/do_sys_open", ProgramType::Kprobe, 18),
+            ],
+            programs
+        );
+
+        let maps = elf.bpf_maps().unwrap();
+        assert_eq!(2, maps.len());
+        let btf = elf.btf().unwrap().unwrap();
+        assert_eq!(
+            Some("__u64"),
+            maps[0]
+                .value_type
+                .and_then(|id| btf.type_by_id(id))
+                .map(|ty| ty.name.as_str())
+        );
+        assert_eq!(
+            Map {
+                name: "legacy".to_string(),
+                section: LEGACY_MAPS_SECTION.to_string(),
+                map_type: 1,
+                key_size: 4,
+                value_size: 8,
+                max_entries: 128,
+                map_flags: 0,
+                key_type: None,
+                value_type: None,
+            },
+            maps[1]
+        );
+
+        let relocations = elf.bpf_relocations();
+        assert_eq!(2, relocations.len());
+        assert_eq!(
+            ("legacy", 13, Some("maps")),
+            (
+                relocations[1].symbol.as_str(),
+                relocations[1].instruction(),
+                relocations[1].symbol_section.as_deref()
+            )
+        );
+        assert!(relocations.iter().all(|rel| rel.is_map_reference()));
+        assert_eq!(None, elf.bpf_kernel_version());
+    }
+}
@@ -0,0 +1,812 @@
+//! BPF Type Format(`.BTF` and `.BTF.ext`) in BPF objects.
+//!
+//! `.BTF` describes the types of the programs and maps, and `.BTF.ext` maps the instructions to
+//! the functions, the source lines and the CO-RE relocations.
+//! The string offsets in `.BTF.ext` refer to the string section of `.BTF`.
+
+use std::any::Any;
+use std::fmt;
+
+use crate::*;
+use section::{PayloadReader, ReadError};
+use thiserror::Error as TError;
+
+/// the section of the type information
+pub const BTF_SECTION: &str = ".BTF";
+/// the section of the function, line and CO-RE relocation information
+pub const BTF_EXT_SECTION: &str = ".BTF.ext";
+/// `BTF_MAGIC`, which also tells the byte order
+pub const BTF_MAGIC: u16 = 0xeb9f;
+/// the only version defined
+pub const BTF_VERSION: u8 = 1;
+
+#[derive(TError, Debug, Clone, PartialEq, Eq)]
+pub enum BtfError {
+    #[error("magic {magic:#x} is not BTF_MAGIC")]
+    BadMagic { magic: u16 },
+    #[error("type [{id}] has unknown kind {kind}")]
+    UnknownKind { id: u32, kind: u32 },
+    #[error("{0}")]
+    Read(#[from] ReadError),
+}
+
+/// The header of `.BTF`(`struct btf_header`)
+///
+/// The offsets are relative to the end of the header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct BtfHeader {
+    pub magic: u16,
+    pub version: u8,
+    pub flags: u8,
+    pub hdr_len: u32,
+    pub type_off: u32,
+    pub type_len: u32,
+    pub str_off: u32,
+    pub str_len: u32,
+}
+
+/// The kind of a type record(`BTF_KIND_*`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum BtfKind {
+    Int,
+    Ptr,
+    Array,
+    Struct,
+    Union,
+    Enum,
+    Fwd,
+    Typedef,
+    Volatile,
+    Const,
+    Restrict,
+    Func,
+    FuncProto,
+    Var,
+    DataSec,
+    Float,
+    DeclTag,
+    TypeTag,
+    Enum64,
+}
+
+impl BtfKind {
+    pub fn from_u32(kind: u32) -> Option<Self> {
+        Some(match kind {
+            1 => Self::Int,
+            2 => Self::Ptr,
+            3 => Self::Array,
+            4 => Self::Struct,
+            5 => Self::Union,
+            6 => Self::Enum,
+            7 => Self::Fwd,
+            8 => Self::Typedef,
+            9 => Self::Volatile,
+            10 => Self::Const,
+            11 => Self::Restrict,
+            12 => Self::Func,
+            13 => Self::FuncProto,
+            14 => Self::Var,
+            15 => Self::DataSec,
+            16 => Self::Float,
+            17 => Self::DeclTag,
+            18 => Self::TypeTag,
+            19 => Self::Enum64,
+            _ => return None,
+        })
+    }
+
+    /// whether the third word of the record is the size rather than a type id.
+    pub fn has_size(&self) -> bool {
+        matches!(
+            self,
+            Self::Int
+                | Self::Struct
+                | Self::Union
+                | Self::Enum
+                | Self::DataSec
+                | Self::Float
+                | Self::Enum64
+        )
+    }
+}
+
+/// Display as bpftool does.
+impl fmt::Display for BtfKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Int => "INT",
+            Self::Ptr => "PTR",
+            Self::Array => "ARRAY",
+            Self::Struct => "STRUCT",
+            Self::Union => "UNION",
+            Self::Enum => "ENUM",
+            Self::Fwd => "FWD",
+            Self::Typedef => "TYPEDEF",
+            Self::Volatile => "VOLATILE",
+            Self::Const => "CONST",
+            Self::Restrict => "RESTRICT",
+            Self::Func => "FUNC",
+            Self::FuncProto => "FUNC_PROTO",
+            Self::Var => "VAR",
+            Self::DataSec => "DATASEC",
+            Self::Float => "FLOAT",
+            Self::DeclTag => "DECL_TAG",
+            Self::TypeTag => "TYPE_TAG",
+            Self::Enum64 => "ENUM64",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A member of a struct or a union(`struct btf_member`)
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BtfMember {
+    pub name: String,
+    pub ty: u32,
+    pub bit_offset: u32,
+    /// 0 unless the member is a bitfield
+    pub bitfield_size: u32,
+}
+
+/// An enumerator(`struct btf_enum` and `struct btf_enum64`)
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BtfEnumValue {
+    pub name: String,
+    pub value: i64,
+}
+
+/// A parameter of a function prototype(`struct btf_param`)
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BtfParam {
+    /// empty in the prototypes of function pointers
+    pub name: String,
+    pub ty: u32,
+}
+
+/// A variable in a data section(`struct btf_var_secinfo`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BtfVarSecInfo {
+    pub ty: u32,
+    pub offset: u32,
+    pub size: u32,
+}
+
+/// The data following a type record, which depends on the kind
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum BtfTypeData {
+    None,
+    Int {
+        /// `BTF_INT_SIGNED`, `BTF_INT_CHAR` and `BTF_INT_BOOL`
+        encoding: u8,
+        offset: u8,
+        bits: u8,
+    },
+    Array {
+        elem_type: u32,
+        index_type: u32,
+        nelems: u32,
+    },
+    Members(Vec<BtfMember>),
+    Enum(Vec<BtfEnumValue>),
+    Params(Vec<BtfParam>),
+    /// `BTF_VAR_STATIC`, `BTF_VAR_GLOBAL_ALLOCATED` or `BTF_VAR_GLOBAL_EXTERN`
+    Var {
+        linkage: u32,
+    },
+    DataSec(Vec<BtfVarSecInfo>),
+    DeclTag {
+        /// -1 for the type itself, otherwise the member or parameter index
+        component_idx: i32,
+    },
+}
+
+/// A type record(`struct btf_type`)
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BtfType {
+    pub name: String,
+    pub kind: BtfKind,
+    pub kind_flag: bool,
+    /// `vlen`, which is the linkage for `BTF_KIND_FUNC`
+    pub vlen: u16,
+    /// the size or the referred type id, see `BtfKind::has_size()`
+    pub size_or_type: u32,
+    pub data: BtfTypeData,
+}
+
+impl BtfType {
+    pub fn size(&self) -> Option<u32> {
+        if self.kind.has_size() {
+            Some(self.size_or_type)
+        } else {
+            None
+        }
+    }
+
+    /// the type which this refers to, e.g. the pointee or the type of a variable.
+    pub fn referred_type(&self) -> Option<u32> {
+        match self.data {
+            BtfTypeData::Array { elem_type, .. } => Some(elem_type),
+            _ if self.kind.has_size() || self.kind == BtfKind::Fwd => None,
+            _ => Some(self.size_or_type),
+        }
+    }
+}
+
+/// The contents of `.BTF`
+///
+/// The type id 0 is `void`, so the type id of `types[i]` is `i + 1`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Btf {
+    pub header: BtfHeader,
+    pub types: Vec<BtfType>,
+    /// the string section
+    pub strings: Vec<u8>,
+}
+
+impl Btf {
+    /// parse `.BTF` in the byte order which the magic tells.
+    pub fn parse(bytes: &[u8]) -> Result<Self, BtfError> {
+        let mut r = PayloadReader::new(bytes, magic_data(bytes)?);
+        let header = BtfHeader {
+            magic: r.u16()?,
+            version: check_version(&mut r)?,
+            flags: r.u8()?,
+            hdr_len: r.u32()?,
+            type_off: r.u32()?,
+            type_len: r.u32()?,
+            str_off: r.u32()?,
+            str_len: r.u32()?,
+        };
+        r.seek((header.hdr_len + header.str_off) as usize)?;
+        let strings = r.bytes(header.str_len as usize)?;
+
+        let type_start = (header.hdr_len + header.type_off) as usize;
+        let type_end = type_start + header.type_len as usize;
+        r.seek(type_start)?;
+        let mut btf = Self {
+            header,
+            types: Vec::new(),
+            strings,
+        };
+        while r.position() < type_end {
+            let id = btf.types.len() as u32 + 1;
+            let ty = btf.parse_type(&mut r, id)?;
+            btf.types.push(ty);
+        }
+        Ok(btf)
+    }
+
+    /// the type of the id, `None` for `void` and unknown ids.
+    pub fn type_by_id(&self, id: u32) -> Option<&BtfType> {
+        self.types.get((id as usize).checked_sub(1)?)
+    }
+
+    /// the ids of the types with the name.
+    pub fn ids_by_name(&self, name: &str) -> Vec<u32> {
+        self.types
+            .iter()
+            .enumerate()
+            .filter(|(_, ty)| ty.name == name)
+            .map(|(i, _)| i as u32 + 1)
+            .collect()
+    }
+
+    /// the string at the offset in the string section.
+    pub fn string_at(&self, offset: u32) -> Option<&str> {
+        let bytes = self.strings.get(offset as usize..)?;
+        let len = bytes.iter().position(|b| *b == 0)?;
+        std::str::from_utf8(&bytes[..len]).ok()
+    }
+
+    /// follow typedefs and the qualifiers(`const`, `volatile`, `restrict` and type tags).
+    pub fn skip_modifiers(&self, mut id: u32) -> u32 {
+        // 循環した型でも止まるように型の数で打ち切る
+        for _ in 0..=self.types.len() {
+            match self.type_by_id(id) {
+                Some(ty)
+                    if matches!(
+                        ty.kind,
+                        BtfKind::Typedef
+                            | BtfKind::Volatile
+                            | BtfKind::Const
+                            | BtfKind::Restrict
+                            | BtfKind::TypeTag
+                    ) =>
+                {
+                    id = ty.size_or_type
+                }
+                _ => break,
+            }
+        }
+        id
+    }
+
+    /// the size of the type in bytes, like `sizeof`.
+    pub fn type_size(&self, id: u32) -> Option<u64> {
+        let ty = self.type_by_id(self.skip_modifiers(id))?;
+        match (&ty.kind, &ty.data) {
+            (BtfKind::Ptr, _) => Some(8),
+            (
+                BtfKind::Array,
+                BtfTypeData::Array {
+                    elem_type, nelems, ..
+                },
+            ) => Some(self.type_size(*elem_type)? * *nelems as u64),
+            (kind, _) if kind.has_size() => Some(ty.size_or_type as u64),
+            _ => None,
+        }
+    }
+
+    fn parse_type(&self, r: &mut PayloadReader<'_>, id: u32) -> Result<BtfType, BtfError> {
+        let name = self.name_at(r.u32()?);
+        let info = r.u32()?;
+        let size_or_type = r.u32()?;
+        let raw_kind = (info >> 24) & 0x1f;
+        let kind =
+            BtfKind::from_u32(raw_kind).ok_or(BtfError::UnknownKind { id, kind: raw_kind })?;
+        let kind_flag = info >> 31 != 0;
+        let vlen = (info & 0xffff) as u16;
+
+        let data = match kind {
+            BtfKind::Int => {
+                let encoding = r.u32()?;
+                BtfTypeData::Int {
+                    encoding: (encoding >> 24) as u8 & 0x0f,
+                    offset: (encoding >> 16) as u8,
+                    bits: encoding as u8,
+                }
+            }
+            BtfKind::Array => BtfTypeData::Array {
+                elem_type: r.u32()?,
+                index_type: r.u32()?,
+                nelems: r.u32()?,
+            },
+            BtfKind::Struct | BtfKind::Union => {
+                let mut members = Vec::with_capacity(vlen as usize);
+                for _ in 0..vlen {
+                    let name = self.name_at(r.u32()?);
+                    let ty = r.u32()?;
+                    let offset = r.u32()?;
+                    // kind_flagが立っていれば上位8bitがビットフィールドの幅
+                    let (bit_offset, bitfield_size) = if kind_flag {
+                        (offset & 0xffffff, offset >> 24)
+                    } else {
+                        (offset, 0)
+                    };
+                    members.push(BtfMember {
+                        name,
+                        ty,
+                        bit_offset,
+                        bitfield_size,
+                    });
+                }
+                BtfTypeData::Members(members)
+            }
+            BtfKind::Enum | BtfKind::Enum64 => {
+                let mut values = Vec::with_capacity(vlen as usize);
+                for _ in 0..vlen {
+                    let name = self.name_at(r.u32()?);
+                    let lo = r.u32()?;
+                    let value = if kind == BtfKind::Enum64 {
+                        ((r.u32()? as u64) << 32 | lo as u64) as i64
+                    } else if kind_flag {
+                        lo as i32 as i64
+                    } else {
+                        // kind_flagが立っていなければ符号なし
+                        lo as i64
+                    };
+                    values.push(BtfEnumValue { name, value });
+                }
+                BtfTypeData::Enum(values)
+            }
+            BtfKind::FuncProto => {
+                let mut params = Vec::with_capacity(vlen as usize);
+                for _ in 0..vlen {
+                    params.push(BtfParam {
+                        name: self.name_at(r.u32()?),
+                        ty: r.u32()?,
+                    });
+                }
+                BtfTypeData::Params(params)
+            }
+            BtfKind::Var => BtfTypeData::Var { linkage: r.u32()? },
+            BtfKind::DataSec => {
+                let mut vars = Vec::with_capacity(vlen as usize);
+                for _ in 0..vlen {
+                    vars.push(BtfVarSecInfo {
+                        ty: r.u32()?,
+                        offset: r.u32()?,
+                        size: r.u32()?,
+                    });
+                }
+                BtfTypeData::DataSec(vars)
+            }
+            BtfKind::DeclTag => BtfTypeData::DeclTag {
+                component_idx: r.u32()? as i32,
+            },
+            _ => BtfTypeData::None,
+        };
+
+        Ok(BtfType {
+            name,
+            kind,
+            kind_flag,
+            vlen,
+            size_or_type,
+            data,
+        })
+    }
+
+    fn name_at(&self, offset: u32) -> String {
+        self.string_at(offset).unwrap_or_default().to_string()
+    }
+}
+
+impl fmt::Display for Btf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, ty) in self.types.iter().enumerate() {
+            writeln!(f, "[{}] {} '{}'", i + 1, ty.kind, ty.name)?;
+        }
+        Ok(())
+    }
+}
+
+/// The header of `.BTF.ext`(`struct btf_ext_header`)
+///
+/// The offsets are relative to the end of the header.
+/// `core_relo_off` and `core_relo_len` are 0 in the headers without them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct BtfExtHeader {
+    pub magic: u16,
+    pub version: u8,
+    pub flags: u8,
+    pub hdr_len: u32,
+    pub func_info_off: u32,
+    pub func_info_len: u32,
+    pub line_info_off: u32,
+    pub line_info_len: u32,
+    pub core_relo_off: u32,
+    pub core_relo_len: u32,
+}
+
+/// `struct bpf_func_info`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FuncInfo {
+    /// the offset of the first instruction in bytes(in instructions after loading)
+    pub insn_off: u32,
+    /// the `BTF_KIND_FUNC` type
+    pub type_id: u32,
+}
+
+/// `struct bpf_line_info`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LineInfo {
+    pub insn_off: u32,
+    pub file_name_off: u32,
+    /// the offset of the source line
+    pub line_off: u32,
+    pub line_col: u32,
+}
+
+impl LineInfo {
+    pub fn line(&self) -> u32 {
+        self.line_col >> 10
+    }
+    pub fn column(&self) -> u32 {
+        self.line_col & 0x3ff
+    }
+}
+
+/// `struct bpf_core_relo`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CoreRelo {
+    pub insn_off: u32,
+    pub type_id: u32,
+    /// the access string such as `0:1:2`
+    pub access_str_off: u32,
+    /// `enum bpf_core_relo_kind`
+    pub kind: u32,
+}
+
+/// The records of a program section(`struct btf_ext_info_sec`)
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BtfExtSection<T> {
+    pub sec_name_off: u32,
+    pub records: Vec<T>,
+}
+
+/// The contents of `.BTF.ext`
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct BtfExt {
+    pub header: BtfExtHeader,
+    pub func_info: Vec<BtfExtSection<FuncInfo>>,
+    pub line_info: Vec<BtfExtSection<LineInfo>>,
+    pub core_relo: Vec<BtfExtSection<CoreRelo>>,
+}
+
+impl BtfExt {
+    /// parse `.BTF.ext` in the byte order which the magic tells.
+    pub fn parse(bytes: &[u8]) -> Result<Self, BtfError> {
+        let mut r = PayloadReader::new(bytes, magic_data(bytes)?);
+        let mut header = BtfExtHeader {
+            magic: r.u16()?,
+            version: check_version(&mut r)?,
+            flags: r.u8()?,
+            hdr_len: r.u32()?,
+            func_info_off: r.u32()?,
+            func_info_len: r.u32()?,
+            line_info_off: r.u32()?,
+            line_info_len: r.u32()?,
+            ..Default::default()
+        };
+        // CO-RE再配置はヘッダが32バイト以上のときだけ存在する
+        if header.hdr_len >= 32 {
+            header.core_relo_off = r.u32()?;
+            header.core_relo_len = r.u32()?;
+        }
+
+        let hdr_len = header.hdr_len;
+        Ok(Self {
+            header,
+            func_info: parse_ext_info(
+                &mut r,
+                hdr_len + header.func_info_off,
+                header.func_info_len,
+                |r| {
+                    Ok(FuncInfo {
+                        insn_off: r.u32()?,
+                        type_id: r.u32()?,
+                    })
+                },
+            )?,
+            line_info: parse_ext_info(
+                &mut r,
+                hdr_len + header.line_info_off,
+                header.line_info_len,
+                |r| {
+                    Ok(LineInfo {
+                        insn_off: r.u32()?,
+                        file_name_off: r.u32()?,
+                        line_off: r.u32()?,
+                        line_col: r.u32()?,
+                    })
+                },
+            )?,
+            core_relo: parse_ext_info(
+                &mut r,
+                hdr_len + header.core_relo_off,
+                header.core_relo_len,
+                |r| {
+                    Ok(CoreRelo {
+                        insn_off: r.u32()?,
+                        type_id: r.u32()?,
+                        access_str_off: r.u32()?,
+                        kind: r.u32()?,
+                    })
+                },
+            )?,
+        })
+    }
+}
+
+/// parse the `btf_ext_info_sec` list after `rec_size`.
+/// The records may be longer than this crate knows, so the rest of each record is skipped.
+fn parse_ext_info<T>(
+    r: &mut PayloadReader<'_>,
+    start: u32,
+    len: u32,
+    read: fn(&mut PayloadReader<'_>) -> Result<T, ReadError>,
+) -> Result<Vec<BtfExtSection<T>>, ReadError> {
+    let mut sections = Vec::new();
+    if len == 0 {
+        return Ok(sections);
+    }
+    r.seek(start as usize)?;
+    let end = (start + len) as usize;
+    let rec_size = r.u32()? as usize;
+    while r.position() < end {
+        let sec_name_off = r.u32()?;
+        let num_info = r.u32()?;
+        let mut records = Vec::with_capacity(num_info as usize);
+        for _ in 0..num_info {
+            let record_start = r.position();
+            records.push(read(r)?);
+            r.seek(record_start + rec_size)?;
+        }
+        sections.push(BtfExtSection {
+            sec_name_off,
+            records,
+        });
+    }
+    Ok(sections)
+}
+
+/// the byte order which the magic is written in.
+fn magic_data(bytes: &[u8]) -> Result<header::Data, BtfError> {
+    let magic = PayloadReader::new(bytes, header::Data::LSB2).u16()?;
+    if magic == BTF_MAGIC {
+        Ok(header::Data::LSB2)
+    } else if magic == BTF_MAGIC.swap_bytes() {
+        Ok(header::Data::MSB2)
+    } else {
+        Err(BtfError::BadMagic { magic })
+    }
+}
+
+fn check_version(r: &mut PayloadReader<'_>) -> Result<u8, ReadError> {
+    let offset = r.position();
+    let version = r.u8()?;
+    if version != BTF_VERSION {
+        return Err(ReadError::UnsupportedVersion { offset, version });
+    }
+    Ok(version)
+}
+
+impl section::CustomContents for Btf {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// Decodes `.BTF` into `Contents64::Custom` holding `Btf`.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::{btf, parser, section};
+///
+/// let codecs = section::Codecs::new().register(btf::BtfCodec);
+/// let elf = parser::parse_elf64_with("tests/fixtures/bpf.o", &codecs).unwrap();
+/// let sct = elf.first_section_by(|sct| sct.name == btf::BTF_SECTION).unwrap();
+/// match &sct.contents {
+///     section::Contents64::Custom(custom) => {
+///         let btf = custom.downcast_ref::<btf::Btf>().unwrap();
+///         assert_eq!(vec![17], btf.ids_by_name("prog"));
+///     }
+///     _ => panic!("not decoded"),
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct BtfCodec;
+
+impl section::SectionCodec for BtfCodec {
+    fn accepts(&self, shdr: &section::Shdr64, name: &str) -> bool {
+        shdr.get_type() == section::Type::ProgBits && name == BTF_SECTION
+    }
+
+    fn decode(&self, _shdr: &section::Shdr64, bytes: &[u8]) -> section::Contents64 {
+        match Btf::parse(bytes) {
+            Ok(btf) => section::Contents64::Custom(section::CustomData::new(btf, bytes.to_vec())),
+            Err(_) => section::Contents64::Raw(bytes.to_vec()),
+        }
+    }
+
+    fn encode(&self, contents: &section::Contents64) -> Vec<u8> {
+        contents.to_le_bytes()
+    }
+}
+
+impl file::ELF64 {
+    /// parse `.BTF` if exists.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use elf_utilities::{btf, parser};
+    ///
+    /// let elf = parser::parse_elf64("tests/fixtures/bpf.o").unwrap();
+    /// let btf = elf.btf().unwrap().unwrap();
+    /// let counts = btf.ids_by_name("counts")[0];
+    /// let map = btf.type_by_id(counts).unwrap();
+    /// assert_eq!(btf::BtfKind::Var, map.kind);
+    /// assert_eq!(Some(32), btf.type_size(map.size_or_type));
+    ///
+    /// let ext = elf.btf_ext().unwrap().unwrap();
+    /// let func = &ext.func_info[0];
+    /// assert_eq!(Some("xdp"), btf.string_at(func.sec_name_off));
+    /// assert_eq!(Some("prog"), btf.type_by_id(func.records[0].type_id).map(|ty| ty.name.as_str()));
+    /// ```
+    pub fn btf(&self) -> Option<Result<Btf, BtfError>> {
+        let sct = self.first_section_by(|sct| sct.name == BTF_SECTION)?;
+        Some(Btf::parse(&sct.to_le_bytes()))
+    }
+
+    /// parse `.BTF.ext` if exists.
+    pub fn btf_ext(&self) -> Option<Result<BtfExt, BtfError>> {
+        let sct = self.first_section_by(|sct| sct.name == BTF_EXT_SECTION)?;
+        Some(BtfExt::parse(&sct.to_le_bytes()))
+    }
+}
+
+#[cfg(test)]
+mod btf_tests {
+    use super::*;
+
+    #[test]
+    fn parse_btf_test() {
+        let elf = parser::parse_elf64("tests/fixtures/bpf.o").unwrap();
+        let btf = elf.btf().unwrap().unwrap();
+        assert_eq!(27, btf.types.len());
+
+        let int = btf.type_by_id(2).unwrap();
+        assert_eq!(
+            ("int", BtfKind::Int, Some(4)),
+            (int.name.as_str(), int.kind, int.size())
+        );
+        assert_eq!(
+            BtfTypeData::Int {
+                encoding: 1,
+                offset: 0,
+                bits: 32
+            },
+            int.data
+        );
+
+        // __uint(max_entries, 16)は int (*)[16] として表現される
+        let map = btf.type_by_id(13).unwrap();
+        let members = match &map.data {
+            BtfTypeData::Members(members) => members,
+            _ => panic!("{:?}", map),
+        };
+        let names: Vec<&str> = members.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(vec!["type", "max_entries", "key", "value"], names);
+        let max_entries = btf
+            .type_by_id(members[1].ty)
+            .unwrap()
+            .referred_type()
+            .unwrap();
+        assert!(matches!(
+            btf.type_by_id(max_entries).unwrap().data,
+            BtfTypeData::Array { nelems: 16, .. }
+        ));
+        let key = btf
+            .type_by_id(members[2].ty)
+            .unwrap()
+            .referred_type()
+            .unwrap();
+        assert_eq!("__u32", btf.type_by_id(key).unwrap().name);
+        assert_eq!(9, btf.skip_modifiers(key));
+        assert_eq!(Some(4), btf.type_size(key));
+
+        let maps = btf.type_by_id(btf.ids_by_name(".maps")[0]).unwrap();
+        assert_eq!(
+            BtfTypeData::DataSec(vec![BtfVarSecInfo {
+                ty: 14,
+                offset: 0,
+                size: 32
+            }]),
+            maps.data
+        );
+
+        let ext = elf.btf_ext().unwrap().unwrap();
+        assert_eq!(32, ext.header.hdr_len);
+        let lines = &ext.line_info[0].records;
+        assert_eq!(Some("xdp"), btf.string_at(ext.line_info[0].sec_name_off));
+        assert_eq!((12, 5), (lines[0].line(), lines[0].column()));
+        assert!(ext.core_relo.is_empty());
+    }
+
+    #[test]
+    fn btf_error_test() {
+        assert_eq!(
+            Err(BtfError::BadMagic { magic: 0x1234 }),
+            Btf::parse(&[0x34, 0x12, 1, 0])
+        );
+
+        // ビッグエンディアンのヘッダだけのBTF
+        let mut bytes = vec![0xeb, 0x9f, 1, 0, 0, 0, 0, 24];
+        bytes.extend_from_slice(&[0; 16]);
+        let btf = Btf::parse(&bytes).unwrap();
+        assert_eq!((BTF_MAGIC, 24), (btf.header.magic, btf.header.hdr_len));
+        assert!(btf.types.is_empty());
+
+        bytes[2] = 2;
+        assert_eq!(
+            Err(BtfError::Read(ReadError::UnsupportedVersion {
+                offset: 2,
+                version: 2
+            })),
+            Btf::parse(&bytes)
+        );
+    }
+}
@@ -0,0 +1,16 @@
+//! High-level writers which generate a complete ELF file.
+
+mod error;
+mod executable;
+mod function;
+mod interposer;
+mod object32;
+mod section_list;
+mod shared_object;
+
+pub use error::*;
+pub use executable::*;
+pub use function::*;
+pub use interposer::*;
+pub use object32::*;
+pub use shared_object::*;
@@ -0,0 +1,21 @@
+use crate::layout::LayoutError;
+use crate::segment::RelroError;
+use thiserror::Error as TError;
+
+#[derive(TError, Debug)]
+pub enum WriterError {
+    #[error("symbol `{name}` is defined more than once")]
+    DuplicateSymbol { name: String },
+    #[error("symbol `{name}` is not defined")]
+    UndefinedSymbol { name: String },
+    #[error("symbol `{name}` can't be interposed")]
+    NotInterposable { name: String },
+    #[error("relocation type `{ty}` is not supported")]
+    UnsupportedRelocation { ty: u64 },
+    #[error("relocation against `{name}` is out of range")]
+    RelocationOutOfRange { name: String },
+    #[error("invalid GNU_RELRO segment => `{0}`")]
+    InvalidRelro(#[from] RelroError),
+    #[error("can't lay out the file => `{0}`")]
+    Layout(#[from] LayoutError),
+}
@@ -0,0 +1,277 @@
+use std::collections::HashMap;
+
+use super::section_list::{new_segment, SectionList};
+use super::*;
+use crate::*;
+use section::StringTable;
+
+/// Default virtual address of the ELF header in executables
+pub const DEFAULT_EXEC_BASE_ADDR: Elf64Addr = 0x400000;
+
+/// A writer which generates a freestanding static executable(`ET_EXEC`) for x86_64.
+///
+/// The generated file has no `PT_INTERP` and no `.dynamic`, so it doesn't depend on any libraries.
+/// Only `R_X86_64_PC32` and `R_X86_64_PLT32` against the functions in the file are supported.
+/// The stack is marked as non-executable by `PT_GNU_STACK`.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::{builder, header};
+///
+/// // mov eax, 42; ret
+/// let main = builder::ExportedFunction::new("main", vec![0xb8, 0x2a, 0x00, 0x00, 0x00, 0xc3]);
+///
+/// let elf = builder::ExecutableWriter::new()
+///     .function(main)
+///     .start_stub(builder::start_stub("main"))
+///     .build()
+///     .unwrap();
+///
+/// assert_eq!(header::Type::Exec, elf.ehdr.get_type());
+/// let text = elf.first_section_by(|sct| sct.name == ".text").unwrap();
+/// // the stub is placed at the start of .text
+/// assert_eq!(text.header.sh_addr, elf.ehdr.e_entry);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ExecutableWriter {
+    entry: String,
+    functions: Vec<ExportedFunction>,
+    start_stub: Option<ExportedFunction>,
+    layout: layout::Layout,
+    tiny: bool,
+}
+
+/// create a minimal `_start` for Linux, which calls `main` and exits with its return value.
+///
+/// ```text
+/// xor ebp, ebp
+/// call main
+/// mov edi, eax
+/// mov eax, 60 ; SYS_exit
+/// syscall
+/// ```
+pub fn start_stub(main: &str) -> ExportedFunction {
+    ExportedFunction::new(
+        "_start",
+        vec![
+            0x31, 0xed, 0xe8, 0x00, 0x00, 0x00, 0x00, 0x89, 0xc7, 0xb8, 0x3c, 0x00, 0x00, 0x00,
+            0x0f, 0x05,
+        ],
+    )
+    .relocation(3, main, relocation::R_X86_64_PLT32, -4)
+}
+
+impl Default for ExecutableWriter {
+    fn default() -> Self {
+        Self {
+            entry: "_start".to_string(),
+            functions: Vec::new(),
+            start_stub: None,
+            layout: layout::Layout::new().base_addr(DEFAULT_EXEC_BASE_ADDR),
+            tiny: false,
+        }
+    }
+}
+
+impl ExecutableWriter {
+    /// alignment of each function in `.text`
+    const FUNCTION_ALIGN: Elf64Xword = 16;
+
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// set the symbol used as the entry point(`_start` by default).
+    pub fn entry(mut self, name: &str) -> Self {
+        self.entry = name.to_string();
+        self
+    }
+    pub fn function(mut self, f: ExportedFunction) -> Self {
+        self.functions.push(f);
+        self
+    }
+    /// inject a startup routine.
+    /// it's placed at the start of `.text` and becomes the entry point.
+    pub fn start_stub(mut self, stub: ExportedFunction) -> Self {
+        self.entry = stub.name.clone();
+        self.start_stub = Some(stub);
+        self
+    }
+    pub fn layout(mut self, layout: layout::Layout) -> Self {
+        self.layout = layout;
+        self
+    }
+    /// generate a minimal-size executable (expert mode).
+    ///
+    /// The program header table overlaps the last 8 bytes of the ELF header,
+    /// and the section header table(`e_shoff == 0`), symbols and `PT_GNU_STACK` are omitted.
+    /// All code is loaded by a single `R|X` segment, and the functions are not aligned.
+    /// The returned ELF has `e_shoff`, `e_shnum` and `e_shstrndx` of 0, and `sections` only holds
+    /// the contents of `.text` to be written.
+    /// In the written file, `e_shnum` shares the bytes with `p_flags`;
+    /// the kernel ignores it since `e_shoff` is 0, but tools which require section headers may reject the file.
+    pub fn tiny(mut self, tiny: bool) -> Self {
+        self.tiny = tiny;
+        self
+    }
+
+    pub fn build(self) -> Result<file::ELF64, WriterError> {
+        let functions: Vec<ExportedFunction> = self
+            .start_stub
+            .iter()
+            .chain(self.functions.iter())
+            .cloned()
+            .collect();
+        let defined = defined_names(&functions)?;
+        if !defined.contains(&self.entry.as_str()) {
+            return Err(WriterError::UndefinedSymbol {
+                name: self.entry.clone(),
+            });
+        }
+        for rel in functions.iter().flat_map(|f| f.relocations.iter()) {
+            match rel.ty {
+                relocation::R_X86_64_PC32 | relocation::R_X86_64_PLT32 => {
+                    if !defined.contains(&rel.symbol.as_str()) {
+                        return Err(WriterError::UndefinedSymbol {
+                            name: rel.symbol.clone(),
+                        });
+                    }
+                }
+                ty => return Err(WriterError::UnsupportedRelocation { ty }),
+            }
+        }
+
+        if self.tiny {
+            return self.build_tiny(&functions);
+        }
+
+        let (text, func_offsets) = assemble_text(&functions, Self::FUNCTION_ALIGN);
+
+        let mut strtab = StringTable::new();
+        let mut symbols = vec![symbol::Symbol64::new_null_symbol()];
+        for f in functions.iter() {
+            let mut sym = symbol::Symbol64 {
+                st_name: strtab.add(&f.name) as Elf64Word,
+                st_size: f.code.len() as Elf64Xword,
+                symbol_name: f.name.as_str().into(),
+                ..Default::default()
+            };
+            sym.set_info(symbol::Type::Func, symbol::Bind::Global);
+            symbols.push(sym);
+        }
+
+        let mut sections = SectionList::default();
+        let text_idx = sections.push(
+            ".text",
+            section::Type::ProgBits,
+            &[section::Flag::Alloc, section::Flag::ExecInstr],
+            Self::FUNCTION_ALIGN,
+            0,
+            section::Contents64::Raw(text),
+        );
+        let symtab_idx = sections.push(
+            ".symtab",
+            section::Type::SymTab,
+            &[],
+            8,
+            symbol::Symbol64::SIZE as Elf64Xword,
+            section::Contents64::Symbols(symbols),
+        );
+        let strtab_idx = sections.push(
+            ".strtab",
+            section::Type::StrTab,
+            &[],
+            1,
+            0,
+            strtab.to_contents64(),
+        );
+        // ローカルシンボルはnullシンボルのみ
+        sections.link(symtab_idx, strtab_idx, 1);
+
+        let mut elf = sections.into_elf(header::Type::Exec);
+        elf.segments.push(new_segment(
+            segment::Type::GNUStack,
+            &[segment::Flag::R, segment::Flag::W],
+        ));
+        self.layout.apply(&mut elf)?;
+
+        let text_addr = elf.sections[text_idx].header.sh_addr;
+        elf.ehdr.e_entry = text_addr + func_offsets[self.entry.as_str()];
+
+        if let section::Contents64::Symbols(syms) = &mut elf.sections[symtab_idx].contents {
+            for sym in syms.iter_mut().skip(1) {
+                sym.st_shndx = text_idx as Elf64Section;
+                sym.st_value = text_addr + func_offsets[sym.symbol_name.as_str()];
+            }
+        }
+        if let section::Contents64::Raw(text) = &mut elf.sections[text_idx].contents {
+            relocate_text(&functions, text, text_addr, &func_offsets)?;
+        }
+
+        Ok(elf)
+    }
+
+    fn build_tiny(&self, functions: &[ExportedFunction]) -> Result<file::ELF64, WriterError> {
+        let (mut text, func_offsets) = assemble_text(functions, 1);
+        let text_offset = TINY_PHOFF + segment::Phdr64::SIZE as Elf64Off;
+        let base_addr = self.layout.base_addr;
+        let text_addr = base_addr + text_offset;
+        relocate_text(functions, &mut text, text_addr, &func_offsets)?;
+
+        let mut load = new_segment(segment::Type::Load, &[segment::Flag::R, segment::Flag::X]);
+        load.header.p_vaddr = base_addr;
+        load.header.p_paddr = base_addr;
+        load.header.p_filesz = text_offset + text.len() as Elf64Xword;
+        load.header.p_memsz = load.header.p_filesz;
+        load.header.p_align = self.layout.page_size;
+
+        let mut text_sct = section::Section64::new(
+            ".text".to_string(),
+            section::ShdrPreparation64::default()
+                .ty(section::Type::ProgBits)
+                .flags([section::Flag::Alloc, section::Flag::ExecInstr].iter()),
+            section::Contents64::Raw(text),
+        );
+        text_sct.header.sh_offset = text_offset;
+        text_sct.header.sh_addr = text_addr;
+        text_sct.header.sh_size = text_sct.contents.size() as Elf64Xword;
+
+        let mut elf = SectionList::default().into_elf(header::Type::Exec);
+        elf.sections = vec![section::Section64::new_null_section(), text_sct];
+
+        // SHTは出力しないので，モデル上もe_shnum等は0にしておく
+        // ELFヘッダの末尾8バイトはto_le_bytes()でp_type/p_flagsに上書きされる
+        let ehdr = &mut elf.ehdr;
+        ehdr.e_entry = text_addr + func_offsets[self.entry.as_str()];
+        ehdr.e_phoff = TINY_PHOFF;
+        ehdr.e_phnum = 1;
+        ehdr.e_shoff = 0;
+        ehdr.e_shentsize = 0;
+        ehdr.e_shnum = 0;
+        ehdr.e_shstrndx = section::SHN_UNDEF;
+        elf.segments = vec![load];
+
+        Ok(elf)
+    }
+}
+
+/// offset of the program header table in tiny executables
+const TINY_PHOFF: Elf64Off = header::Ehdr64::SIZE as Elf64Off - 8;
+
+fn relocate_text(
+    functions: &[ExportedFunction],
+    text: &mut [u8],
+    text_addr: Elf64Addr,
+    func_offsets: &HashMap<&str, Elf64Addr>,
+) -> Result<(), WriterError> {
+    for f in functions.iter() {
+        let func_offset = func_offsets[f.name.as_str()];
+        let code = &mut text[func_offset as usize..func_offset as usize + f.code.len()];
+        for rel in f.relocations.iter() {
+            let target = text_addr + func_offsets[rel.symbol.as_str()];
+            rel.apply(code, text_addr + func_offset + rel.offset, target)?;
+        }
+    }
+    Ok(())
+}
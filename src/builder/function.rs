@@ -0,0 +1,116 @@
+use std::collections::HashMap;
+
+use super::WriterError;
+use crate::*;
+
+/// A relocation applied to the code of a function.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CodeRelocation {
+    /// offset from the start of the function
+    pub offset: Elf64Addr,
+    /// the referenced symbol
+    pub symbol: String,
+    /// `R_X86_64_PC32`, `R_X86_64_PLT32` or `R_X86_64_GOTPCREL`
+    pub ty: Elf64Xword,
+    pub addend: Elf64Sxword,
+}
+
+/// A function exported from the generated file.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ExportedFunction {
+    pub name: String,
+    pub code: Vec<u8>,
+    pub relocations: Vec<CodeRelocation>,
+}
+
+impl CodeRelocation {
+    /// resolve the relocation and write the value into `code`.
+    /// `place` is the address of the relocated field and `target` is the address of the referenced symbol
+    /// (or its GOT slot for `R_X86_64_GOTPCREL`).
+    pub(crate) fn apply(
+        &self,
+        code: &mut [u8],
+        place: Elf64Addr,
+        target: Elf64Addr,
+    ) -> Result<(), WriterError> {
+        let value = target as i128 + self.addend as i128 - place as i128;
+        let out_of_range = || WriterError::RelocationOutOfRange {
+            name: self.symbol.clone(),
+        };
+        if value < i32::MIN as i128 || value > i32::MAX as i128 {
+            return Err(out_of_range());
+        }
+
+        let start = self.offset as usize;
+        let field = code.get_mut(start..start + 4).ok_or_else(out_of_range)?;
+        field.copy_from_slice(&(value as i32).to_le_bytes());
+        Ok(())
+    }
+}
+
+impl ExportedFunction {
+    pub fn new(name: &str, code: Vec<u8>) -> Self {
+        Self {
+            name: name.to_string(),
+            code,
+            relocations: Vec::new(),
+        }
+    }
+
+    /// add a relocation at `offset` from the start of the function.
+    pub fn relocation(
+        mut self,
+        offset: Elf64Addr,
+        symbol: &str,
+        ty: Elf64Xword,
+        addend: Elf64Sxword,
+    ) -> Self {
+        self.relocations.push(CodeRelocation {
+            offset,
+            symbol: symbol.to_string(),
+            ty,
+            addend,
+        });
+        self
+    }
+}
+
+/// concatenate the code of functions and return the offset of each function.
+/// the gaps between functions are filled with int3.
+pub(crate) fn assemble_text(
+    functions: &[ExportedFunction],
+    align: Elf64Xword,
+) -> (Vec<u8>, HashMap<&str, Elf64Addr>) {
+    assemble_text_with(functions, align, 0xcc)
+}
+
+/// `assemble_text()` with the byte filling the gaps between functions.
+pub(crate) fn assemble_text_with(
+    functions: &[ExportedFunction],
+    align: Elf64Xword,
+    fill: u8,
+) -> (Vec<u8>, HashMap<&str, Elf64Addr>) {
+    let mut text = Vec::new();
+    let mut offsets = HashMap::new();
+    for f in functions.iter() {
+        let padding = layout::align_up(text.len() as u64, align);
+        text.resize(padding as usize, fill);
+        offsets.insert(f.name.as_str(), text.len() as Elf64Addr);
+        text.extend_from_slice(&f.code);
+    }
+    (text, offsets)
+}
+
+/// check there are no duplicate functions and return the defined names.
+pub(crate) fn defined_names(functions: &[ExportedFunction]) -> Result<Vec<&str>, WriterError> {
+    let mut defined: Vec<&str> = Vec::new();
+    for f in functions.iter() {
+        if defined.contains(&f.name.as_str()) {
+            return Err(WriterError::DuplicateSymbol {
+                name: f.name.clone(),
+            });
+        }
+        defined.push(&f.name);
+    }
+    Ok(defined)
+}
@@ -0,0 +1,115 @@
+use super::*;
+use crate::*;
+
+/// the library which provides `dlsym()` on every glibc(it's a stub of libc.so.6 since glibc 2.34)
+pub const INTERPOSER_NEEDED: &str = "libdl.so.2";
+
+/// `(void *)-1`
+const RTLD_NEXT: [u8; 4] = [0xff, 0xff, 0xff, 0xff];
+
+/// create a function which forwards the call to the next definition of `name`,
+/// found by `dlsym(RTLD_NEXT, name)` or `dlvsym(RTLD_NEXT, name, version)`.
+///
+/// The argument registers(including `rax` and `xmm0`-`xmm7`) are saved around the lookup,
+/// and the stack is left untouched, so any function can be forwarded.
+/// The stub looks up the symbol on every call, and traps with `ud2` if it's not found.
+///
+/// ```text
+/// push rdi; push rsi; push rdx; push rcx; push r8; push r9; push rax
+/// sub rsp, 128
+/// movdqu [rsp + 16 * n], xmm<n> ; n = 0..8
+/// mov rdi, -1 ; RTLD_NEXT
+/// lea rsi, [rip + name]
+/// lea rdx, [rip + version] ; dlvsym only
+/// call [rip + dlsym@GOTPCREL]
+/// mov r11, rax
+/// movdqu xmm<n>, [rsp + 16 * n]
+/// add rsp, 128
+/// pop rax; pop r9; pop r8; pop rcx; pop rdx; pop rsi; pop rdi
+/// test r11, r11
+/// jz trap
+/// jmp r11
+/// trap: ud2
+/// name: .asciz "<name>"
+/// version: .asciz "<version>"
+/// ```
+pub fn interposer_stub(name: &str, version: Option<&str>) -> ExportedFunction {
+    let mut code = vec![0x57, 0x56, 0x52, 0x51, 0x41, 0x50, 0x41, 0x51, 0x50];
+    code.extend_from_slice(&[0x48, 0x81, 0xec, 0x80, 0x00, 0x00, 0x00]);
+    for n in 0..8 {
+        code.extend_from_slice(&[0xf3, 0x0f, 0x7f, 0x44 | n << 3, 0x24, n * 16]);
+    }
+    code.extend_from_slice(&[0x48, 0xc7, 0xc7]);
+    code.extend_from_slice(&RTLD_NEXT);
+
+    // 文字列はコードの後ろに置くので，ripからの距離は後で埋める
+    let name_disp = code.len() + 3;
+    code.extend_from_slice(&[0x48, 0x8d, 0x35, 0, 0, 0, 0]);
+    let version_disp = version.map(|_| {
+        code.extend_from_slice(&[0x48, 0x8d, 0x15, 0, 0, 0, 0]);
+        code.len() - 4
+    });
+    let call = code.len() + 2;
+    code.extend_from_slice(&[0xff, 0x15, 0, 0, 0, 0]);
+
+    code.extend_from_slice(&[0x49, 0x89, 0xc3]);
+    for n in 0..8 {
+        code.extend_from_slice(&[0xf3, 0x0f, 0x6f, 0x44 | n << 3, 0x24, n * 16]);
+    }
+    code.extend_from_slice(&[0x48, 0x81, 0xc4, 0x80, 0x00, 0x00, 0x00]);
+    code.extend_from_slice(&[0x58, 0x41, 0x59, 0x41, 0x58, 0x59, 0x5a, 0x5e, 0x5f]);
+    code.extend_from_slice(&[0x4d, 0x85, 0xdb, 0x74, 0x03, 0x41, 0xff, 0xe3, 0x0f, 0x0b]);
+
+    let mut strings = vec![(name_disp, name)];
+    strings.extend(version_disp.zip(version));
+    for (disp, s) in strings {
+        let rel = (code.len() - (disp + 4)) as i32;
+        code[disp..disp + 4].copy_from_slice(&rel.to_le_bytes());
+        code.extend_from_slice(s.as_bytes());
+        code.push(0);
+    }
+
+    let resolver = if version.is_some() { "dlvsym" } else { "dlsym" };
+    ExportedFunction::new(name, code).relocation(
+        call as Elf64Addr,
+        resolver,
+        relocation::R_X86_64_GOTPCREL,
+        -4,
+    )
+}
+
+/// generate a shared object for `LD_PRELOAD` which interposes the symbols.
+///
+/// Each `(name, version)` becomes an `interposer_stub()` which forwards to the next definition,
+/// so the file is a starting point for patching the forwarding code (e.g. for tracing).
+/// The symbols are defined without versions, which the dynamic linker accepts for versioned references too.
+/// `dlsym`/`dlvsym` can't be interposed since the stubs depend on them.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::{builder, section};
+///
+/// let elf = builder::generate_interposer(&[("malloc", None), ("memcpy", Some("GLIBC_2.14"))])
+///     .unwrap();
+/// let dynsym = elf.first_section_by(|sct| sct.name == ".dynsym").unwrap();
+/// if let section::Contents64::Symbols(syms) = &dynsym.contents {
+///     let names: Vec<&str> = syms.iter().map(|sym| sym.symbol_name.as_str()).collect();
+///     assert!(names.contains(&"malloc"));
+///     assert!(names.contains(&"dlvsym"));
+/// }
+///
+/// assert!(builder::generate_interposer(&[("dlsym", None)]).is_err());
+/// ```
+pub fn generate_interposer(symbols: &[(&str, Option<&str>)]) -> Result<file::ELF64, WriterError> {
+    let mut writer = SharedObjectWriter::new().needed(INTERPOSER_NEEDED);
+    for (name, version) in symbols.iter() {
+        if *name == "dlsym" || *name == "dlvsym" {
+            return Err(WriterError::NotInterposable {
+                name: name.to_string(),
+            });
+        }
+        writer = writer.function(interposer_stub(name, *version));
+    }
+    writer.build()
+}
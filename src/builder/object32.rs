@@ -0,0 +1,345 @@
+use std::convert::TryFrom;
+
+use super::*;
+use crate::*;
+use section::StringTable;
+
+/// A 32bit target of `ObjectWriter32`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Arch32 {
+    /// i386, with `SHT_REL`
+    I386,
+    /// ARM EABI version 5, with `SHT_REL`
+    Arm,
+    /// RV32, with `SHT_RELA`
+    RiscV32,
+}
+
+impl Arch32 {
+    pub fn machine(&self) -> header::Machine {
+        match self {
+            Self::I386 => header::Machine::Intel386,
+            Self::Arm => header::Machine::Arm,
+            Self::RiscV32 => header::Machine::Any(243),
+        }
+    }
+
+    /// `e_flags` of the generated files.
+    /// RISC-V objects use the soft-float ABI.
+    pub fn flags(&self) -> Elf32Word {
+        match self {
+            // EF_ARM_EABI_VER5
+            Self::Arm => 0x0500_0000,
+            _ => 0,
+        }
+    }
+
+    /// whether the relocations have explicit addends(`SHT_RELA`).
+    pub fn uses_rela(&self) -> bool {
+        *self == Self::RiscV32
+    }
+
+    /// alignment of each function in `.text`
+    fn function_align(&self) -> Elf64Xword {
+        match self {
+            Self::I386 => 16,
+            _ => 4,
+        }
+    }
+
+    /// the byte filling the gaps between functions
+    fn fill(&self) -> u8 {
+        match self {
+            // int3
+            Self::I386 => 0xcc,
+            _ => 0x00,
+        }
+    }
+}
+
+/// A writer which generates a relocatable object(`ET_REL`) for 32bit targets.
+///
+/// The functions are placed in `.text` and the data in `.data`, both exported as global symbols.
+/// Relocations are not resolved but emitted to `.rel.text`(i386, ARM) or `.rela.text`(RISC-V),
+/// and the symbols not defined in the file become undefined symbols.
+/// For `SHT_REL` targets the addend is added to the 32bit field at the relocation offset,
+/// so use 0 for the fields which aren't plain words like the immediate of `R_ARM_CALL`.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::{builder, header, parser, relocation, section};
+///
+/// // call answer; ret
+/// let main = builder::ExportedFunction::new("main", vec![0xe8, 0, 0, 0, 0, 0xc3])
+///     .relocation(1, "answer", relocation::R_386_PC32, -4);
+///
+/// let elf = builder::ObjectWriter32::new(builder::Arch32::I386)
+///     .function(main)
+///     .data("counter", vec![0; 4])
+///     .build()
+///     .unwrap();
+///
+/// let path = std::env::temp_dir().join(format!("elf_utilities_object32_{}.o", std::process::id()));
+/// std::fs::write(&path, elf.to_le_bytes()).unwrap();
+/// let parsed = parser::parse_elf32(path.to_str().unwrap()).unwrap();
+/// std::fs::remove_file(&path).unwrap();
+///
+/// assert_eq!(header::Type::Rel, parsed.ehdr.get_type());
+/// assert_eq!(elf.sections, parsed.sections);
+///
+/// // the addend is stored in the code
+/// let text = parsed.sections.iter().find(|sct| sct.name == ".text").unwrap();
+/// assert_eq!(vec![0xe8, 0xfc, 0xff, 0xff, 0xff, 0xc3], text.to_le_bytes());
+/// let rel = parsed.sections.iter().find(|sct| sct.name == ".rel.text").unwrap();
+/// assert_eq!(section::Type::Rel, rel.header.get_type());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ObjectWriter32 {
+    arch: Arch32,
+    functions: Vec<ExportedFunction>,
+    data: Vec<(String, Vec<u8>)>,
+}
+
+impl ObjectWriter32 {
+    /// alignment of `.data`
+    const DATA_ALIGN: Elf64Xword = 4;
+
+    pub fn new(arch: Arch32) -> Self {
+        Self {
+            arch,
+            functions: Vec::new(),
+            data: Vec::new(),
+        }
+    }
+
+    pub fn function(mut self, f: ExportedFunction) -> Self {
+        self.functions.push(f);
+        self
+    }
+    /// add a global object in `.data`.
+    pub fn data(mut self, name: &str, bytes: Vec<u8>) -> Self {
+        self.data.push((name.to_string(), bytes));
+        self
+    }
+
+    pub fn build(self) -> Result<file::ELF32, WriterError> {
+        let mut defined = defined_names(&self.functions)?;
+        for (name, _) in self.data.iter() {
+            if defined.contains(&name.as_str()) {
+                return Err(WriterError::DuplicateSymbol { name: name.clone() });
+            }
+            defined.push(name);
+        }
+
+        let (mut text, func_offsets) = assemble_text_with(
+            &self.functions,
+            self.arch.function_align(),
+            self.arch.fill(),
+        );
+        let mut data = Vec::new();
+        let mut data_offsets = Vec::new();
+        for (_, bytes) in self.data.iter() {
+            data.resize(
+                layout::align_up(data.len() as u64, Self::DATA_ALIGN) as usize,
+                0,
+            );
+            data_offsets.push(data.len());
+            data.extend_from_slice(bytes);
+        }
+
+        // セクションの並びは固定
+        const TEXT: usize = 1;
+        const DATA: usize = 2;
+        const SYMTAB: usize = 3;
+        const STRTAB: usize = 4;
+
+        let mut strtab = StringTable::new();
+        let mut symbols = vec![symbol::Symbol32::new_null_symbol()];
+        let mut add_symbol = |name: &str, ty, shndx: usize, value: usize, size: usize| {
+            let mut sym = symbol::Symbol32 {
+                st_name: strtab.add(name) as Elf32Word,
+                st_value: value as Elf32Addr,
+                st_size: size as Elf32Word,
+                st_shndx: shndx as Elf32Section,
+                symbol_name: name.to_string(),
+                ..Default::default()
+            };
+            sym.set_info(ty, symbol::Bind::Global);
+            symbols.push(sym);
+        };
+        for f in self.functions.iter() {
+            let offset = func_offsets[f.name.as_str()];
+            add_symbol(
+                &f.name,
+                symbol::Type::Func,
+                TEXT,
+                offset as usize,
+                f.code.len(),
+            );
+        }
+        for ((name, bytes), offset) in self.data.iter().zip(data_offsets) {
+            add_symbol(name, symbol::Type::Object, DATA, offset, bytes.len());
+        }
+        let mut undefined: Vec<&str> = Vec::new();
+        for rel in self.functions.iter().flat_map(|f| f.relocations.iter()) {
+            let name = rel.symbol.as_str();
+            if !defined.contains(&name) && !undefined.contains(&name) {
+                undefined.push(name);
+                add_symbol(name, symbol::Type::NoType, 0, 0, 0);
+            }
+        }
+
+        let mut rel_bytes = Vec::new();
+        let mut relas = Vec::new();
+        for f in self.functions.iter() {
+            let func_offset = func_offsets[f.name.as_str()];
+            for rel in f.relocations.iter() {
+                let out_of_range = || WriterError::RelocationOutOfRange {
+                    name: rel.symbol.clone(),
+                };
+                if rel.ty > 0xff {
+                    return Err(WriterError::UnsupportedRelocation { ty: rel.ty });
+                }
+                let offset = u32::try_from(func_offset + rel.offset).map_err(|_| out_of_range())?;
+                let addend = i32::try_from(rel.addend).map_err(|_| out_of_range())?;
+                let sym_idx = symbols
+                    .iter()
+                    .position(|sym| sym.symbol_name == rel.symbol)
+                    .unwrap();
+                let info = (sym_idx as Elf32Word) << 8 | rel.ty as Elf32Word;
+
+                if self.arch.uses_rela() {
+                    let mut rela = relocation::Rela32::default();
+                    rela.set_offset(offset);
+                    rela.set_info(info);
+                    rela.set_addend(addend);
+                    relas.push(rela);
+                } else {
+                    // SHT_RELは加数を再配置先に持つ
+                    let start = offset as usize;
+                    let field = text.get_mut(start..start + 4).ok_or_else(out_of_range)?;
+                    let implicit = i32::from_le_bytes([field[0], field[1], field[2], field[3]]);
+                    field.copy_from_slice(&implicit.wrapping_add(addend).to_le_bytes());
+                    rel_bytes.extend_from_slice(&offset.to_le_bytes());
+                    rel_bytes.extend_from_slice(&info.to_le_bytes());
+                }
+            }
+        }
+
+        let (rel_name, rel_ty, rel_entsize, rel_contents) = if self.arch.uses_rela() {
+            (
+                ".rela.text",
+                section::Type::Rela,
+                relocation::Rela32::SIZE as Elf32Word,
+                section::Contents32::RelaSymbols(relas),
+            )
+        } else {
+            (
+                ".rel.text",
+                section::Type::Rel,
+                8,
+                section::Contents32::Raw(rel_bytes),
+            )
+        };
+
+        let mut symtab = new_section(
+            ".symtab",
+            section::Type::SymTab,
+            &[],
+            4,
+            symbol::Symbol32::SIZE as Elf32Word,
+            section::Contents32::Symbols(symbols),
+        );
+        // ローカルシンボルはnullシンボルのみ
+        symtab.header.sh_link = STRTAB as Elf32Word;
+        symtab.header.sh_info = 1;
+        let mut rel = new_section(
+            rel_name,
+            rel_ty,
+            &[section::Flag::InfoLink],
+            4,
+            rel_entsize,
+            rel_contents,
+        );
+        rel.header.sh_link = SYMTAB as Elf32Word;
+        rel.header.sh_info = TEXT as Elf32Word;
+
+        let sections = vec![
+            section::Section32::new_null_section(),
+            new_section(
+                ".text",
+                section::Type::ProgBits,
+                &[section::Flag::Alloc, section::Flag::ExecInstr],
+                self.arch.function_align() as Elf32Word,
+                0,
+                section::Contents32::Raw(text),
+            ),
+            new_section(
+                ".data",
+                section::Type::ProgBits,
+                &[section::Flag::Alloc, section::Flag::Write],
+                Self::DATA_ALIGN as Elf32Word,
+                0,
+                section::Contents32::Raw(data),
+            ),
+            symtab,
+            new_section(
+                ".strtab",
+                section::Type::StrTab,
+                &[],
+                1,
+                0,
+                strtab.to_contents32(),
+            ),
+            rel,
+            new_section(
+                ".shstrtab",
+                section::Type::StrTab,
+                &[],
+                1,
+                0,
+                StringTable::new().to_contents32(),
+            ),
+        ];
+
+        let mut ehdr = header::Ehdr32::default();
+        ehdr.set_class(header::Class::Bit32);
+        ehdr.set_data(header::Data::LSB2);
+        ehdr.set_file_version(header::Version::Current);
+        ehdr.set_object_version(header::Version::Current);
+        ehdr.set_osabi(header::OSABI::SysV);
+        ehdr.set_elf_type(header::Type::Rel);
+        ehdr.set_machine(self.arch.machine());
+        ehdr.e_flags = self.arch.flags();
+        ehdr.e_shstrndx = (sections.len() - 1) as Elf32Half;
+
+        let mut elf = file::ELF32 {
+            ehdr,
+            sections,
+            segments: Vec::new(),
+        };
+        elf.condition();
+        Ok(elf)
+    }
+}
+
+fn new_section(
+    name: &str,
+    ty: section::Type,
+    flags: &[section::Flag],
+    align: Elf32Word,
+    entsize: Elf32Word,
+    contents: section::Contents32,
+) -> section::Section32 {
+    let mut sct = section::Section32::new(
+        name.to_string(),
+        section::ShdrPreparation32::default()
+            .ty(ty)
+            .flags(flags.iter()),
+        contents,
+    );
+    sct.header.sh_addralign = align;
+    sct.header.sh_entsize = entsize;
+    sct
+}
@@ -0,0 +1,103 @@
+use super::WriterError;
+use crate::*;
+use section::StringTable;
+
+/// sections under construction.
+/// the null section is placed at first and `.shstrtab` is appended by `into_elf()`.
+pub(crate) struct SectionList {
+    sections: Vec<section::Section64>,
+}
+
+impl Default for SectionList {
+    fn default() -> Self {
+        Self {
+            sections: vec![section::Section64::new_null_section()],
+        }
+    }
+}
+
+impl SectionList {
+    pub(crate) fn push(
+        &mut self,
+        name: &str,
+        ty: section::Type,
+        flags: &[section::Flag],
+        align: Elf64Xword,
+        entsize: Elf64Xword,
+        contents: section::Contents64,
+    ) -> usize {
+        let mut sct = section::Section64::new(
+            name.to_string(),
+            section::ShdrPreparation64::default()
+                .ty(ty)
+                .flags(flags.iter()),
+            contents,
+        );
+        sct.header.sh_addralign = align;
+        sct.header.sh_entsize = entsize;
+        self.sections.push(sct);
+        self.sections.len() - 1
+    }
+
+    pub(crate) fn link(&mut self, idx: usize, link: usize, info: Elf64Word) {
+        self.sections[idx].header.sh_link = link as Elf64Word;
+        self.sections[idx].header.sh_info = info;
+    }
+
+    /// append `.shstrtab` and create an x86_64 ELF file.
+    pub(crate) fn into_elf(mut self, ty: header::Type) -> file::ELF64 {
+        let shstrndx = self.push(
+            ".shstrtab",
+            section::Type::StrTab,
+            &[],
+            1,
+            0,
+            StringTable::new().to_contents64(),
+        );
+
+        let mut ehdr = header::Ehdr64::default();
+        ehdr.set_class(header::Class::Bit64);
+        ehdr.set_data(header::Data::LSB2);
+        ehdr.set_file_version(header::Version::Current);
+        ehdr.set_object_version(header::Version::Current);
+        ehdr.set_osabi(header::OSABI::SysV);
+        ehdr.set_elf_type(ty);
+        ehdr.set_machine(header::Machine::X8664);
+        ehdr.e_shstrndx = shstrndx as Elf64Half;
+
+        let mut elf = file::ELF64 {
+            ehdr,
+            sections: self.sections,
+            segments: Vec::new(),
+            address_cache: Default::default(),
+        };
+        elf.rebuild_shstrtab();
+        elf
+    }
+}
+
+pub(crate) fn new_segment(ty: segment::Type, flags: &[segment::Flag]) -> segment::Segment64 {
+    let mut phdr = segment::Phdr64::default();
+    phdr.set_type(ty);
+    phdr.set_flags(flags.iter());
+    phdr.p_align = if phdr.get_type() == segment::Type::GNUStack {
+        16
+    } else {
+        8
+    };
+    segment::Segment64 { header: phdr }
+}
+
+/// fill the `PT_GNU_RELRO` placeholder with the computed region and validate it.
+pub(crate) fn apply_relro(elf: &mut file::ELF64) -> Result<(), WriterError> {
+    let relro = segment::compute_relro(elf);
+    segment::validate_relro(elf, &relro)?;
+    if let Some(sgt) = elf
+        .segments
+        .iter_mut()
+        .find(|sgt| sgt.header.get_type() == segment::Type::GNURelRO)
+    {
+        sgt.header = relro;
+    }
+    Ok(())
+}
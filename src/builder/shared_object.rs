@@ -0,0 +1,333 @@
+use super::section_list::{apply_relro, new_segment, SectionList};
+use super::*;
+use crate::*;
+use section::{GnuHash64, StringTable};
+
+/// A writer which generates a shared object(`ET_DYN`) for x86_64.
+///
+/// Relocations against other exported functions(`R_X86_64_PC32`/`R_X86_64_PLT32`) are resolved statically.
+/// `R_X86_64_GOTPCREL` creates a `.got` slot which is filled by the dynamic linker,
+/// so it can refer symbols in the `DT_NEEDED` libraries.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::{builder, header, relocation};
+///
+/// let answer = builder::ExportedFunction::new("answer", vec![0xb8, 0x2a, 0x00, 0x00, 0x00, 0xc3]);
+/// // call answer; ret
+/// let call_answer = builder::ExportedFunction::new("call_answer", vec![0xe8, 0, 0, 0, 0, 0xc3])
+///     .relocation(1, "answer", relocation::R_X86_64_PLT32, -4);
+///
+/// let elf = builder::SharedObjectWriter::new()
+///     .soname("libanswer.so")
+///     .function(answer)
+///     .function(call_answer)
+///     .build()
+///     .unwrap();
+///
+/// assert_eq!(header::Type::Dyn, elf.ehdr.get_type());
+/// assert!(elf.first_section_by(|sct| sct.name == ".gnu.hash").is_some());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SharedObjectWriter {
+    soname: Option<String>,
+    runpath: Option<String>,
+    needed: Vec<String>,
+    functions: Vec<ExportedFunction>,
+    layout: layout::Layout,
+}
+
+impl Default for SharedObjectWriter {
+    fn default() -> Self {
+        Self {
+            soname: None,
+            runpath: None,
+            needed: Vec::new(),
+            functions: Vec::new(),
+            layout: layout::Layout::new(),
+        }
+    }
+}
+
+impl SharedObjectWriter {
+    /// alignment of each function in `.text`
+    const FUNCTION_ALIGN: Elf64Xword = 16;
+
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// DT_SONAME
+    pub fn soname(mut self, soname: &str) -> Self {
+        self.soname = Some(soname.to_string());
+        self
+    }
+    /// DT_RUNPATH
+    pub fn runpath(mut self, path: &str) -> Self {
+        self.runpath = Some(path.to_string());
+        self
+    }
+    /// DT_NEEDED
+    pub fn needed(mut self, lib: &str) -> Self {
+        self.needed.push(lib.to_string());
+        self
+    }
+    pub fn function(mut self, f: ExportedFunction) -> Self {
+        self.functions.push(f);
+        self
+    }
+    pub fn layout(mut self, layout: layout::Layout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    pub fn build(self) -> Result<file::ELF64, WriterError> {
+        let (externs, got_symbols) = self.check_symbols()?;
+
+        let (text, func_offsets) = assemble_text(&self.functions, Self::FUNCTION_ALIGN);
+
+        // .gnu.hashのため，定義済みシンボルはバケット順に並べる
+        let nbuckets = GnuHash64::nbuckets_for(self.functions.len());
+        let mut exported: Vec<&ExportedFunction> = self.functions.iter().collect();
+        exported.sort_by_key(|f| GnuHash64::bucket_of(&f.name, nbuckets));
+        let symoffset = 1 + externs.len() as Elf64Word;
+
+        let mut dynstr = StringTable::new();
+        let mut dynsym = vec![symbol::Symbol64::new_null_symbol()];
+        for name in externs.iter() {
+            let mut sym = symbol::Symbol64 {
+                st_name: dynstr.add(name) as Elf64Word,
+                symbol_name: name.into(),
+                ..Default::default()
+            };
+            sym.set_info(symbol::Type::NoType, symbol::Bind::Global);
+            dynsym.push(sym);
+        }
+        for f in exported.iter() {
+            let mut sym = symbol::Symbol64 {
+                st_name: dynstr.add(&f.name) as Elf64Word,
+                st_size: f.code.len() as Elf64Xword,
+                symbol_name: f.name.as_str().into(),
+                ..Default::default()
+            };
+            sym.set_info(symbol::Type::Func, symbol::Bind::Global);
+            dynsym.push(sym);
+        }
+        let dynsym_idx_of = |name: &str| {
+            dynsym
+                .iter()
+                .position(|sym| sym.symbol_name == name)
+                .unwrap() as Elf64Xword
+        };
+
+        let relas: Vec<relocation::Rela64> = got_symbols
+            .iter()
+            .map(|name| {
+                let mut rela = relocation::Rela64::default();
+                rela.set_info(dynsym_idx_of(name) << 32 | relocation::R_X86_64_GLOB_DAT);
+                rela
+            })
+            .collect();
+
+        let mut dynamic = dynamic::DynamicBuilder::new(&mut dynstr);
+        for lib in self.needed.iter() {
+            dynamic = dynamic.needed(lib);
+        }
+        if let Some(soname) = &self.soname {
+            dynamic = dynamic.soname(soname);
+        }
+        if let Some(runpath) = &self.runpath {
+            dynamic = dynamic.runpath(runpath);
+        }
+        // アドレスはレイアウト後に埋める
+        dynamic = dynamic.gnu_hash(0).symtab(0).strtab(0);
+        // full RELROにするため，遅延束縛を無効にする
+        dynamic = dynamic
+            .flags([dynamic::Flag::BindNow].iter())
+            .flags_1([dynamic::Flag::Now1].iter());
+        if !relas.is_empty() {
+            dynamic = dynamic.rela(0, (relas.len() * relocation::Rela64::SIZE as usize) as u64);
+        }
+        let dynamics = dynamic.build();
+
+        let hashed_names: Vec<&str> = exported.iter().map(|f| f.name.as_str()).collect();
+        let gnu_hash = GnuHash64::build(symoffset, &hashed_names);
+
+        let mut sections = SectionList::default();
+        let readonly = [section::Flag::Alloc];
+        let gnu_hash_idx = sections.push(
+            ".gnu.hash",
+            section::Type::Any(0x6ffffff6),
+            &readonly,
+            8,
+            0,
+            section::Contents64::Raw(gnu_hash.to_le_bytes()),
+        );
+        let dynsym_idx = sections.push(
+            ".dynsym",
+            section::Type::DynSym,
+            &readonly,
+            8,
+            symbol::Symbol64::SIZE as Elf64Xword,
+            section::Contents64::Symbols(dynsym),
+        );
+        let dynstr_idx = sections.push(
+            ".dynstr",
+            section::Type::StrTab,
+            &readonly,
+            1,
+            0,
+            dynstr.to_contents64(),
+        );
+        let rela_idx = if relas.is_empty() {
+            None
+        } else {
+            Some(sections.push(
+                ".rela.dyn",
+                section::Type::Rela,
+                &readonly,
+                8,
+                relocation::Rela64::SIZE,
+                section::Contents64::RelaSymbols(relas),
+            ))
+        };
+        let text_idx = sections.push(
+            ".text",
+            section::Type::ProgBits,
+            &[section::Flag::Alloc, section::Flag::ExecInstr],
+            Self::FUNCTION_ALIGN,
+            0,
+            section::Contents64::Raw(text),
+        );
+        let dynamic_idx = sections.push(
+            ".dynamic",
+            section::Type::Dynamic,
+            &[section::Flag::Alloc, section::Flag::Write],
+            8,
+            dynamic::Dyn64::SIZE as Elf64Xword,
+            section::Contents64::Dynamics(dynamics),
+        );
+        let got_idx = if got_symbols.is_empty() {
+            None
+        } else {
+            Some(sections.push(
+                ".got",
+                section::Type::ProgBits,
+                &[section::Flag::Alloc, section::Flag::Write],
+                8,
+                8,
+                section::Contents64::Raw(vec![0; got_symbols.len() * 8]),
+            ))
+        };
+
+        sections.link(gnu_hash_idx, dynsym_idx, 0);
+        // ローカルシンボルはnullシンボルのみなので，最初の非ローカルシンボルは1番目
+        sections.link(dynsym_idx, dynstr_idx, 1);
+        if let Some(rela_idx) = rela_idx {
+            sections.link(rela_idx, dynsym_idx, 0);
+        }
+        sections.link(dynamic_idx, dynstr_idx, 0);
+
+        let mut elf = sections.into_elf(header::Type::Dyn);
+        elf.segments.push(new_segment(
+            segment::Type::Dynamic,
+            &[segment::Flag::R, segment::Flag::W],
+        ));
+        elf.segments.push(new_segment(
+            segment::Type::GNUStack,
+            &[segment::Flag::R, segment::Flag::W],
+        ));
+        elf.segments
+            .push(new_segment(segment::Type::GNURelRO, &[segment::Flag::R]));
+        self.layout.apply(&mut elf)?;
+
+        let dynamic_sgt = elf
+            .segments
+            .iter()
+            .position(|sgt| sgt.header.get_type() == segment::Type::Dynamic)
+            .unwrap();
+        layout::fit_segment(&mut elf, dynamic_sgt, &[dynamic_idx]);
+        apply_relro(&mut elf)?;
+
+        // レイアウトが決まったので，アドレスを埋める
+        let addr_of = |idx: usize| elf.sections[idx].header.sh_addr;
+        let text_addr = addr_of(text_idx);
+        let got_addr = got_idx.map_or(0, addr_of);
+        let table_addrs = [
+            (dynamic::EntryType::GNUHash, addr_of(gnu_hash_idx)),
+            (dynamic::EntryType::SymTab, addr_of(dynsym_idx)),
+            (dynamic::EntryType::StrTab, addr_of(dynstr_idx)),
+            (dynamic::EntryType::Rela, rela_idx.map_or(0, addr_of)),
+        ];
+
+        if let section::Contents64::Dynamics(entries) = &mut elf.sections[dynamic_idx].contents {
+            for ent in entries.iter_mut() {
+                if let Some((_, addr)) = table_addrs.iter().find(|(ty, _)| *ty == ent.get_type()) {
+                    ent.d_un = *addr;
+                }
+            }
+        }
+        if let section::Contents64::Symbols(syms) = &mut elf.sections[dynsym_idx].contents {
+            for sym in syms.iter_mut().skip(symoffset as usize) {
+                sym.st_shndx = text_idx as Elf64Section;
+                sym.st_value = text_addr + func_offsets[sym.symbol_name.as_str()];
+            }
+        }
+        if let Some(rela_idx) = rela_idx {
+            if let section::Contents64::RelaSymbols(relas) = &mut elf.sections[rela_idx].contents {
+                for (slot, rela) in relas.iter_mut().enumerate() {
+                    rela.set_offset(got_addr + slot as Elf64Addr * 8);
+                }
+            }
+        }
+
+        if let section::Contents64::Raw(text) = &mut elf.sections[text_idx].contents {
+            for f in self.functions.iter() {
+                let func_offset = func_offsets[f.name.as_str()];
+                let code = &mut text[func_offset as usize..func_offset as usize + f.code.len()];
+                for rel in f.relocations.iter() {
+                    let target = if rel.ty == relocation::R_X86_64_GOTPCREL {
+                        let slot = got_symbols.iter().position(|s| *s == rel.symbol).unwrap();
+                        got_addr + slot as Elf64Addr * 8
+                    } else {
+                        text_addr + func_offsets[rel.symbol.as_str()]
+                    };
+                    rel.apply(code, text_addr + func_offset + rel.offset, target)?;
+                }
+            }
+        }
+
+        Ok(elf)
+    }
+
+    /// check the symbols and collect the undefined symbols and the GOT entries
+    fn check_symbols(&self) -> Result<(Vec<String>, Vec<String>), WriterError> {
+        let defined = defined_names(&self.functions)?;
+
+        let mut externs = Vec::new();
+        let mut got_symbols = Vec::new();
+        for rel in self.functions.iter().flat_map(|f| f.relocations.iter()) {
+            match rel.ty {
+                relocation::R_X86_64_PC32 | relocation::R_X86_64_PLT32 => {
+                    if !defined.contains(&rel.symbol.as_str()) {
+                        return Err(WriterError::UndefinedSymbol {
+                            name: rel.symbol.clone(),
+                        });
+                    }
+                }
+                relocation::R_X86_64_GOTPCREL => {
+                    if !got_symbols.contains(&rel.symbol) {
+                        got_symbols.push(rel.symbol.clone());
+                    }
+                    if !defined.contains(&rel.symbol.as_str()) && !externs.contains(&rel.symbol) {
+                        externs.push(rel.symbol.clone());
+                    }
+                }
+                ty => return Err(WriterError::UnsupportedRelocation { ty }),
+            }
+        }
+
+        Ok((externs, got_symbols))
+    }
+}
@@ -0,0 +1,286 @@
+//! Crafting deliberately malformed files, for fuzzing parsers and writing challenges.
+//!
+//! `ElfTemplate` serializes a well-formed `file::ELF64` and then overwrites the raw bytes,
+//! so the overrides bypass every check of this crate:
+//! a field can hold any value, tables can overlap the ELF header, and counts can exceed the file.
+
+use crate::*;
+
+/// A field of the ELF header
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum EhdrField {
+    /// `e_ident[EI_CLASS]`
+    Class,
+    /// `e_ident[EI_DATA]`
+    Data,
+    /// `e_ident[EI_VERSION]`
+    IdentVersion,
+    /// `e_ident[EI_OSABI]`
+    OsAbi,
+    Type,
+    Machine,
+    Version,
+    Entry,
+    Phoff,
+    Shoff,
+    Flags,
+    Ehsize,
+    Phentsize,
+    Phnum,
+    Shentsize,
+    Shnum,
+    Shstrndx,
+}
+
+impl EhdrField {
+    /// `(offset, size)` of the field in the file.
+    pub fn location(&self) -> (usize, usize) {
+        match self {
+            Self::Class => (0x04, 1),
+            Self::Data => (0x05, 1),
+            Self::IdentVersion => (0x06, 1),
+            Self::OsAbi => (0x07, 1),
+            Self::Type => (0x10, 2),
+            Self::Machine => (0x12, 2),
+            Self::Version => (0x14, 4),
+            Self::Entry => (0x18, 8),
+            Self::Phoff => (0x20, 8),
+            Self::Shoff => (0x28, 8),
+            Self::Flags => (0x30, 4),
+            Self::Ehsize => (0x34, 2),
+            Self::Phentsize => (0x36, 2),
+            Self::Phnum => (0x38, 2),
+            Self::Shentsize => (0x3a, 2),
+            Self::Shnum => (0x3c, 2),
+            Self::Shstrndx => (0x3e, 2),
+        }
+    }
+}
+
+/// A field of a program header
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PhdrField {
+    Type,
+    Flags,
+    Offset,
+    Vaddr,
+    Paddr,
+    Filesz,
+    Memsz,
+    Align,
+}
+
+impl PhdrField {
+    /// `(offset, size)` of the field in the program header.
+    pub fn location(&self) -> (usize, usize) {
+        match self {
+            Self::Type => (0x00, 4),
+            Self::Flags => (0x04, 4),
+            Self::Offset => (0x08, 8),
+            Self::Vaddr => (0x10, 8),
+            Self::Paddr => (0x18, 8),
+            Self::Filesz => (0x20, 8),
+            Self::Memsz => (0x28, 8),
+            Self::Align => (0x30, 8),
+        }
+    }
+}
+
+/// A field of a section header
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ShdrField {
+    Name,
+    Type,
+    Flags,
+    Addr,
+    Offset,
+    Size,
+    Link,
+    Info,
+    Addralign,
+    Entsize,
+}
+
+impl ShdrField {
+    /// `(offset, size)` of the field in the section header.
+    pub fn location(&self) -> (usize, usize) {
+        match self {
+            Self::Name => (0x00, 4),
+            Self::Type => (0x04, 4),
+            Self::Flags => (0x08, 8),
+            Self::Addr => (0x10, 8),
+            Self::Offset => (0x18, 8),
+            Self::Size => (0x20, 8),
+            Self::Link => (0x28, 4),
+            Self::Info => (0x2c, 4),
+            Self::Addralign => (0x30, 8),
+            Self::Entsize => (0x38, 8),
+        }
+    }
+}
+
+/// a modification applied to the serialized file, in order
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Edit {
+    /// write the bytes at the offset, extending the file if needed
+    Write {
+        offset: usize,
+        bytes: Vec<u8>,
+    },
+    Append(Vec<u8>),
+    Truncate(usize),
+}
+
+/// A template of a crafted file
+///
+/// The base file is serialized by `to_le_bytes()`,
+/// and the overrides are applied to the bytes in the order they are added.
+/// The program headers and section headers are located by `e_phoff`/`e_shoff` of the base file,
+/// so overriding the offsets in the ELF header doesn't move the tables.
+/// Values are truncated to the size of the field.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::craft;
+///
+/// let bytes = craft::ElfTemplate::minimal()
+///     // プログラムヘッダテーブルをELFヘッダに重ねる
+///     .ehdr(craft::EhdrField::Phoff, 0)
+///     .ehdr(craft::EhdrField::Shnum, 0xffff)
+///     .shdr(1, craft::ShdrField::Offset, u64::MAX - 0xf)
+///     .build();
+///
+/// assert_eq!([0xff, 0xff], bytes[0x3c..0x3e]);
+/// assert_eq!([0; 8], bytes[0x20..0x28]);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ElfTemplate {
+    base: file::ELF64,
+    edits: Vec<Edit>,
+}
+
+impl ElfTemplate {
+    pub fn new(base: file::ELF64) -> Self {
+        Self {
+            base,
+            edits: Vec::new(),
+        }
+    }
+
+    /// a template of a static x86_64 executable, which exits with 0.
+    pub fn minimal() -> Self {
+        // xor eax, eax; ret
+        let main = builder::ExportedFunction::new("main", vec![0x31, 0xc0, 0xc3]);
+        let elf = builder::ExecutableWriter::new()
+            .function(main)
+            .start_stub(builder::start_stub("main"))
+            .build()
+            .unwrap();
+        Self::new(elf)
+    }
+
+    /// the well-formed file which the overrides are applied to.
+    pub fn base(&self) -> &file::ELF64 {
+        &self.base
+    }
+
+    /// override a field of the ELF header.
+    pub fn ehdr(self, field: EhdrField, value: u64) -> Self {
+        let (offset, size) = field.location();
+        self.field(offset, size, value)
+    }
+
+    /// override a field of the `index`th program header, which needn't exist in the base file.
+    pub fn phdr(self, index: usize, field: PhdrField, value: u64) -> Self {
+        let (offset, size) = field.location();
+        let table = self.base.ehdr.e_phoff as usize;
+        self.field(table + index * segment::Phdr64::SIZE + offset, size, value)
+    }
+
+    /// override a field of the `index`th section header, which needn't exist in the base file.
+    pub fn shdr(self, index: usize, field: ShdrField, value: u64) -> Self {
+        let (offset, size) = field.location();
+        let table = self.base.ehdr.e_shoff as usize;
+        self.field(table + index * section::Shdr64::SIZE + offset, size, value)
+    }
+
+    /// override `size` bytes at `offset` with `value` in little endian.
+    pub fn field(self, offset: usize, size: usize, value: u64) -> Self {
+        let bytes = value.to_le_bytes()[..size.min(8)].to_vec();
+        self.write(offset, bytes)
+    }
+
+    /// write the bytes at the file offset, extending the file with zeros if needed.
+    pub fn write(mut self, offset: usize, bytes: Vec<u8>) -> Self {
+        self.edits.push(Edit::Write { offset, bytes });
+        self
+    }
+
+    /// append the bytes to the file, e.g. a fake table referred by an overridden offset.
+    pub fn append(mut self, bytes: Vec<u8>) -> Self {
+        self.edits.push(Edit::Append(bytes));
+        self
+    }
+
+    /// cut the file to `len` bytes, so that the tables and contents run off the end.
+    pub fn truncate(mut self, len: usize) -> Self {
+        self.edits.push(Edit::Truncate(len));
+        self
+    }
+
+    pub fn build(&self) -> Vec<u8> {
+        let mut bytes = self.base.to_le_bytes();
+        for edit in self.edits.iter() {
+            match edit {
+                Edit::Write { offset, bytes: b } => {
+                    let end = offset + b.len();
+                    if bytes.len() < end {
+                        bytes.resize(end, 0);
+                    }
+                    bytes[*offset..end].copy_from_slice(b);
+                }
+                Edit::Append(b) => bytes.extend_from_slice(b),
+                Edit::Truncate(len) => bytes.truncate(*len),
+            }
+        }
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod craft_tests {
+    use super::*;
+    use std::convert::TryInto;
+
+    #[test]
+    fn build_test() {
+        let template = ElfTemplate::minimal();
+        let base = template.base().to_le_bytes();
+        assert_eq!(base, template.build());
+
+        let shoff = template.base().ehdr.e_shoff as usize;
+        let bytes = template
+            .clone()
+            .shdr(1, ShdrField::Size, 0x1_0000_0000)
+            .phdr(0, PhdrField::Type, 0xdead_beef)
+            .append(vec![0xaa; 4])
+            .build();
+        assert_eq!(base.len() + 4, bytes.len());
+        let size = shoff + section::Shdr64::SIZE + 0x20;
+        assert_eq!(
+            0x1_0000_0000,
+            u64::from_le_bytes(bytes[size..size + 8].try_into().unwrap())
+        );
+        assert_eq!([0xef, 0xbe, 0xad, 0xde], bytes[0x40..0x44]);
+
+        // 書き込みは末尾を越えてもよい
+        let bytes = template
+            .clone()
+            .truncate(0x10)
+            .field(0x20, 2, 0x1234)
+            .build();
+        assert_eq!(0x22, bytes.len());
+        assert_eq!([0x34, 0x12], bytes[0x20..]);
+    }
+}
@@ -6,3 +6,5 @@ mod entry_type;
 pub use entry_type::*;
 mod flags;
 pub use flags::*;
+mod builder;
+pub use builder::*;
@@ -0,0 +1,228 @@
+use crate::*;
+use section::StringTable;
+
+/// A `.dynamic` section builder
+///
+/// Strings referenced by entries(`DT_NEEDED`, `DT_SONAME`, ...) are added to the given `.dynstr` table,
+/// so the offsets stay in sync with it.
+/// `DT_NULL` is appended automatically.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::{dynamic, section};
+///
+/// let mut dynstr = section::StringTable::new();
+/// let entries = dynamic::DynamicBuilder::new(&mut dynstr)
+///     .needed("libc.so.6")
+///     .soname("libfoo.so")
+///     .strtab(0x1000)
+///     .build();
+///
+/// assert_eq!(dynamic::EntryType::Needed, entries[0].get_type());
+/// assert_eq!(1, entries[0].d_un);
+/// assert_eq!(dynamic::EntryType::SOName, entries[1].get_type());
+/// assert_eq!(11, entries[1].d_un);
+/// // DT_STRSZ is derived from the table.
+/// assert_eq!(dynamic::EntryType::StrSz, entries[3].get_type());
+/// assert_eq!(dynstr.size() as u64, entries[3].d_un);
+/// assert_eq!(dynamic::EntryType::Null, entries[4].get_type());
+/// ```
+#[derive(Debug)]
+pub struct DynamicBuilder<'a> {
+    dynstr: &'a mut StringTable,
+    entries: Vec<dynamic::Dyn64>,
+}
+
+impl<'a> DynamicBuilder<'a> {
+    pub fn new(dynstr: &'a mut StringTable) -> Self {
+        Self {
+            dynstr,
+            entries: Vec::new(),
+        }
+    }
+
+    /// add an arbitrary entry.
+    pub fn entry(mut self, ty: dynamic::EntryType, value: Elf64Xword) -> Self {
+        self.entries.push(dynamic::Dyn64::new(ty, value));
+        self
+    }
+
+    fn string_entry(self, ty: dynamic::EntryType, s: &str) -> Self {
+        let offset = self.dynstr.add(s);
+        self.entry(ty, offset as Elf64Xword)
+    }
+
+    /// DT_NEEDED
+    pub fn needed(self, lib: &str) -> Self {
+        self.string_entry(dynamic::EntryType::Needed, lib)
+    }
+    /// DT_SONAME
+    pub fn soname(self, name: &str) -> Self {
+        self.string_entry(dynamic::EntryType::SOName, name)
+    }
+    /// DT_RPATH
+    pub fn rpath(self, path: &str) -> Self {
+        self.string_entry(dynamic::EntryType::RPath, path)
+    }
+    /// DT_RUNPATH
+    pub fn runpath(self, path: &str) -> Self {
+        self.string_entry(dynamic::EntryType::RunPath, path)
+    }
+
+    /// DT_HASH
+    pub fn hash(self, addr: Elf64Addr) -> Self {
+        self.entry(dynamic::EntryType::Hash, addr)
+    }
+    /// DT_GNU_HASH
+    pub fn gnu_hash(self, addr: Elf64Addr) -> Self {
+        self.entry(dynamic::EntryType::GNUHash, addr)
+    }
+    /// DT_SYMTAB (DT_SYMENT is added automatically)
+    pub fn symtab(self, addr: Elf64Addr) -> Self {
+        self.entry(dynamic::EntryType::SymTab, addr)
+    }
+    /// DT_STRTAB (DT_STRSZ is added automatically)
+    pub fn strtab(self, addr: Elf64Addr) -> Self {
+        self.entry(dynamic::EntryType::StrTab, addr)
+    }
+    /// DT_RELA and DT_RELASZ (DT_RELAENT is added automatically)
+    pub fn rela(self, addr: Elf64Addr, size: Elf64Xword) -> Self {
+        self.entry(dynamic::EntryType::Rela, addr)
+            .entry(dynamic::EntryType::RelaSz, size)
+    }
+    /// DT_JMPREL, DT_PLTRELSZ and DT_PLTREL
+    pub fn jmprel(self, addr: Elf64Addr, size: Elf64Xword) -> Self {
+        self.entry(dynamic::EntryType::JmpRel, addr)
+            .entry(dynamic::EntryType::PLTRelSz, size)
+            .entry(
+                dynamic::EntryType::PLTRel,
+                dynamic::EntryType::Rela.to_bytes() as Elf64Xword,
+            )
+    }
+    /// DT_PLTGOT
+    pub fn pltgot(self, addr: Elf64Addr) -> Self {
+        self.entry(dynamic::EntryType::PLTGOT, addr)
+    }
+    /// DT_INIT_ARRAY and DT_INIT_ARRAYSZ
+    pub fn init_array(self, addr: Elf64Addr, size: Elf64Xword) -> Self {
+        self.entry(dynamic::EntryType::InitArray, addr)
+            .entry(dynamic::EntryType::InitArraySz, size)
+    }
+    /// DT_FINI_ARRAY and DT_FINI_ARRAYSZ
+    pub fn fini_array(self, addr: Elf64Addr, size: Elf64Xword) -> Self {
+        self.entry(dynamic::EntryType::FiniArray, addr)
+            .entry(dynamic::EntryType::FiniArraySz, size)
+    }
+    /// DT_FLAGS
+    pub fn flags<'b, I>(self, flags: I) -> Self
+    where
+        I: Iterator<Item = &'b dynamic::Flag>,
+    {
+        let value = flags.fold(0, |acc, f| acc | f.to_bytes());
+        self.entry(dynamic::EntryType::Flags, value)
+    }
+    /// DT_FLAGS_1
+    pub fn flags_1<'b, I>(self, flags: I) -> Self
+    where
+        I: Iterator<Item = &'b dynamic::Flag>,
+    {
+        let value = flags.fold(0, |acc, f| acc | f.to_bytes());
+        self.entry(dynamic::EntryType::Flags1, value)
+    }
+
+    fn contains(&self, ty: dynamic::EntryType) -> bool {
+        self.entries.iter().any(|ent| ent.get_type() == ty)
+    }
+
+    /// build the dynamic array terminated by DT_NULL.
+    pub fn build(mut self) -> Vec<dynamic::Dyn64> {
+        if self.contains(dynamic::EntryType::StrTab) && !self.contains(dynamic::EntryType::StrSz) {
+            let size = self.dynstr.size() as Elf64Xword;
+            self = self.entry(dynamic::EntryType::StrSz, size);
+        }
+        if self.contains(dynamic::EntryType::SymTab) && !self.contains(dynamic::EntryType::SymEnt) {
+            self = self.entry(
+                dynamic::EntryType::SymEnt,
+                symbol::Symbol64::SIZE as Elf64Xword,
+            );
+        }
+        if self.contains(dynamic::EntryType::Rela) && !self.contains(dynamic::EntryType::RelaEnt) {
+            self = self.entry(dynamic::EntryType::RelaEnt, relocation::Rela64::SIZE);
+        }
+
+        self.entry(dynamic::EntryType::Null, 0).entries
+    }
+
+    /// build a `.dynamic` section.
+    /// `sh_link` is set to `dynstr_shidx`.
+    pub fn build_section(self, dynstr_shidx: Elf64Word) -> section::Section64 {
+        let mut sct = section::Section64::new(
+            ".dynamic".to_string(),
+            section::ShdrPreparation64::default()
+                .ty(section::Type::Dynamic)
+                .flags([section::Flag::Write, section::Flag::Alloc].iter())
+                .link(dynstr_shidx),
+            section::Contents64::Dynamics(self.build()),
+        );
+        sct.header.sh_addralign = 8;
+        sct.header.sh_entsize = dynamic::Dyn64::SIZE as Elf64Xword;
+        sct
+    }
+
+    /// add a `.dynamic` section and the PT_DYNAMIC segment covering it to `elf`.
+    /// `sh_link` refers the `.dynstr` section if it exists.
+    /// Returns the index of the section.
+    pub fn register(self, elf: &mut file::ELF64) -> usize {
+        let dynstr_shidx = elf.first_shidx_by(|sct| sct.name == ".dynstr").unwrap_or(0);
+
+        // 先にセグメントを追加しないと，セクションのオフセットがずれてしまう
+        elf.add_segment(segment::Segment64::default());
+        elf.add_section(self.build_section(dynstr_shidx as Elf64Word));
+
+        let shidx = elf.sections.len() - 2;
+        let shdr = elf.sections[shidx].header;
+
+        let phdr = &mut elf.segments.last_mut().unwrap().header;
+        phdr.set_type(segment::Type::Dynamic);
+        phdr.set_flags([segment::Flag::R, segment::Flag::W].iter());
+        phdr.p_offset = shdr.sh_offset;
+        phdr.p_vaddr = shdr.sh_addr;
+        phdr.p_paddr = shdr.sh_addr;
+        phdr.p_filesz = shdr.sh_size;
+        phdr.p_memsz = shdr.sh_size;
+        phdr.p_align = shdr.sh_addralign;
+
+        shidx
+    }
+}
+
+#[cfg(test)]
+mod builder_tests {
+    use super::*;
+
+    #[test]
+    fn register_test() {
+        let mut elf = file::ELF64::default();
+        let mut dynstr = StringTable::new();
+        let shidx = DynamicBuilder::new(&mut dynstr)
+            .needed("libc.so.6")
+            .flags_1([dynamic::Flag::Now1, dynamic::Flag::PIE1].iter())
+            .register(&mut elf);
+
+        assert_eq!(1, shidx);
+        let sct = &elf.sections[shidx];
+        assert_eq!(section::Type::Dynamic, sct.header.get_type());
+        assert_eq!(3 * dynamic::Dyn64::SIZE as u64, sct.header.sh_size);
+        assert!(matches!(
+            &sct.contents,
+            section::Contents64::Dynamics(x) if x[1].d_un == 0x8000001
+        ));
+
+        assert_eq!(1, elf.segments.len());
+        let phdr = elf.segments[0].header;
+        assert_eq!(segment::Type::Dynamic, phdr.get_type());
+        assert_eq!(sct.header.sh_offset, phdr.p_offset);
+        assert_eq!(sct.header.sh_size, phdr.p_filesz);
+    }
+}
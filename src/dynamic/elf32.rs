@@ -11,10 +11,21 @@ pub struct Dyn32 {
 }
 
 impl Dyn32 {
-    pub const SIZE: usize = 0x10;
+    pub const SIZE: usize = 0x8;
+
+    pub fn new(ty: dynamic::EntryType, value: Elf32Word) -> Self {
+        Self {
+            d_tag: ty.to_bytes() as Elf32Sword,
+            d_un: value,
+        }
+    }
+
     pub fn get_type(&self) -> dynamic::EntryType {
         dynamic::EntryType::from(self.d_tag as i64)
     }
+    pub fn set_type(&mut self, ty: dynamic::EntryType) {
+        self.d_tag = ty.to_bytes() as Elf32Sword;
+    }
 
     pub fn to_le_bytes(&self) -> Vec<u8> {
         bincode::serialize(self).unwrap()
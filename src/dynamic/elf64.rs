@@ -12,9 +12,20 @@ pub struct Dyn64 {
 
 impl Dyn64 {
     pub const SIZE: usize = 0x10;
+
+    pub fn new(ty: dynamic::EntryType, value: Elf64Xword) -> Self {
+        Self {
+            d_tag: ty.to_bytes(),
+            d_un: value,
+        }
+    }
+
     pub fn get_type(&self) -> dynamic::EntryType {
         dynamic::EntryType::from(self.d_tag)
     }
+    pub fn set_type(&mut self, ty: dynamic::EntryType) {
+        self.d_tag = ty.to_bytes();
+    }
 
     pub fn to_le_bytes(&self) -> Vec<u8> {
         bincode::serialize(self).unwrap()
@@ -1,4 +1,6 @@
-#[derive(Debug, Clone, Hash, PartialOrd, Ord, PartialEq, Eq)]
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, Hash, PartialOrd, Ord, PartialEq, Eq)]
 pub enum EntryType {
     /// Marks end of dynamic section
     Null,
@@ -88,6 +90,10 @@ pub enum EntryType {
     GNUHash,
     /// State Flags, See `Flags::*1`.
     Flags1,
+    /// Address of version definition table
+    VerDef,
+    /// Number of version definitions
+    VerDefNum,
     /// The versioning entry types.
     VerSym,
     RelCount,
@@ -96,6 +102,62 @@ pub enum EntryType {
     Any(i64),
 }
 
+impl EntryType {
+    pub fn to_bytes(&self) -> i64 {
+        match self {
+            Self::Null => 0,
+            Self::Needed => 1,
+            Self::PLTRelSz => 2,
+            Self::PLTGOT => 3,
+            Self::Hash => 4,
+            Self::StrTab => 5,
+            Self::SymTab => 6,
+            Self::Rela => 7,
+            Self::RelaSz => 8,
+            Self::RelaEnt => 9,
+            Self::StrSz => 10,
+            Self::SymEnt => 11,
+            Self::Init => 12,
+            Self::Fini => 13,
+            Self::SOName => 14,
+            Self::RPath => 15,
+            Self::Symbolic => 16,
+            Self::Rel => 17,
+            Self::RelSz => 18,
+            Self::RelEnt => 19,
+            Self::PLTRel => 20,
+            Self::Debug => 21,
+            Self::TextRel => 22,
+            Self::JmpRel => 23,
+            Self::BindNow => 24,
+            Self::InitArray => 25,
+            Self::FiniArray => 26,
+            Self::InitArraySz => 27,
+            Self::FiniArraySz => 28,
+            Self::RunPath => 29,
+            Self::Flags => 30,
+            Self::Encoding | Self::PreInitArray => 32,
+            Self::PreInitArraySz => 33,
+            Self::SymTabShNdx => 34,
+            Self::Num => 35,
+            Self::LoOS => 0x6000000d,
+            Self::HiOS => 0x6ffff000,
+            Self::LoProc => 0x70000000,
+            Self::HiProc => 0x7fffffff,
+            Self::GNUHash => 0x6ffffef5,
+            Self::VerSym => 0x6ffffff0,
+            Self::RelaCount => 0x6ffffff9,
+            Self::RelCount => 0x6ffffffa,
+            Self::Flags1 => 0x6ffffffb,
+            Self::VerDef => 0x6ffffffc,
+            Self::VerDefNum => 0x6ffffffd,
+            Self::VerNeed => 0x6ffffffe,
+            Self::VerNeedNum => 0x6fffffff,
+            Self::Any(v) => *v,
+        }
+    }
+}
+
 impl From<i64> for EntryType {
     fn from(v: i64) -> Self {
         match v {
@@ -143,9 +205,102 @@ impl From<i64> for EntryType {
             0x6ffffff9 => EntryType::RelaCount,
             0x6ffffffa => EntryType::RelCount,
             0x6ffffffb => EntryType::Flags1,
+            0x6ffffffc => EntryType::VerDef,
+            0x6ffffffd => EntryType::VerDefNum,
             0x6ffffffe => EntryType::VerNeed,
             0x6fffffff => EntryType::VerNeedNum,
             _ => EntryType::Any(v),
         }
     }
 }
+
+/// Display as readelf does.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::dynamic;
+///
+/// assert_eq!("NEEDED", dynamic::EntryType::Needed.to_string());
+/// assert_eq!("GNU_HASH", dynamic::EntryType::GNUHash.to_string());
+/// ```
+impl fmt::Display for EntryType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let v = self.to_bytes();
+        let name = match v {
+            0 => "NULL",
+            1 => "NEEDED",
+            2 => "PLTRELSZ",
+            3 => "PLTGOT",
+            4 => "HASH",
+            5 => "STRTAB",
+            6 => "SYMTAB",
+            7 => "RELA",
+            8 => "RELASZ",
+            9 => "RELAENT",
+            10 => "STRSZ",
+            11 => "SYMENT",
+            12 => "INIT",
+            13 => "FINI",
+            14 => "SONAME",
+            15 => "RPATH",
+            16 => "SYMBOLIC",
+            17 => "REL",
+            18 => "RELSZ",
+            19 => "RELENT",
+            20 => "PLTREL",
+            21 => "DEBUG",
+            22 => "TEXTREL",
+            23 => "JMPREL",
+            24 => "BIND_NOW",
+            25 => "INIT_ARRAY",
+            26 => "FINI_ARRAY",
+            27 => "INIT_ARRAYSZ",
+            28 => "FINI_ARRAYSZ",
+            29 => "RUNPATH",
+            30 => "FLAGS",
+            32 => "PREINIT_ARRAY",
+            33 => "PREINIT_ARRAYSZ",
+            34 => "SYMTAB_SHNDX",
+            35 => "RELRSZ",
+            36 => "RELR",
+            37 => "RELRENT",
+            0x6ffffdf5 => "GNU_PRELINKED",
+            0x6ffffdf6 => "GNU_CONFLICTSZ",
+            0x6ffffdf7 => "GNU_LIBLISTSZ",
+            0x6ffffdf8 => "CHECKSUM",
+            0x6ffffdf9 => "PLTPADSZ",
+            0x6ffffdfa => "MOVEENT",
+            0x6ffffdfb => "MOVESZ",
+            0x6ffffdfc => "FEATURE",
+            0x6ffffdfd => "POSFLAG_1",
+            0x6ffffdfe => "SYMINSZ",
+            0x6ffffdff => "SYMINENT",
+            0x6ffffef5 => "GNU_HASH",
+            0x6ffffef6 => "TLSDESC_PLT",
+            0x6ffffef7 => "TLSDESC_GOT",
+            0x6ffffef8 => "GNU_CONFLICT",
+            0x6ffffef9 => "GNU_LIBLIST",
+            0x6ffffefa => "CONFIG",
+            0x6ffffefb => "DEPAUDIT",
+            0x6ffffefc => "AUDIT",
+            0x6ffffefd => "PLTPAD",
+            0x6ffffefe => "MOVETAB",
+            0x6ffffeff => "SYMINFO",
+            0x6ffffff0 => "VERSYM",
+            0x6ffffff9 => "RELACOUNT",
+            0x6ffffffa => "RELCOUNT",
+            0x6ffffffb => "FLAGS_1",
+            0x6ffffffc => "VERDEF",
+            0x6ffffffd => "VERDEFNUM",
+            0x6ffffffe => "VERNEED",
+            0x6fffffff => "VERNEEDNUM",
+            0x7ffffffd => "AUXILIARY",
+            0x7fffffff => "FILTER",
+            0x6000000d..=0x6ffff000 => return write!(f, "Operating System specific: {:x}", v),
+            0x70000000..=0x7fffffff => return write!(f, "Processor Specific: {:x}", v),
+            _ => return write!(f, "<unknown>: {:x}", v),
+        };
+        write!(f, "{}", name)
+    }
+}
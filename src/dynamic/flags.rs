@@ -1,4 +1,4 @@
-#[derive(Debug, Clone, Hash, PartialOrd, Ord, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Hash, PartialOrd, Ord, PartialEq, Eq)]
 pub enum Flag {
     /// Object may use this
     Origin,
@@ -115,4 +115,47 @@ impl Flag {
             _ => Flag::Any(value),
         }
     }
+
+    /// For both DT_FLAGS and DT_FLAGS_1
+    pub fn to_bytes(&self) -> u64 {
+        match self {
+            Flag::Origin => 0x1,
+            Flag::Symbolic => 0x2,
+            Flag::TextRel => 0x4,
+            Flag::BindNow => 0x8,
+            Flag::StaticTLS => 0x10,
+            Flag::Now1 => 0x1,
+            Flag::Global1 => 0x2,
+            Flag::Group1 => 0x4,
+            Flag::NoDelete1 => 0x8,
+            Flag::LoadFilter1 => 0x10,
+            Flag::InitFirst1 => 0x20,
+            Flag::NoOpen1 => 0x40,
+            Flag::Origin1 => 0x80,
+            Flag::Direct1 => 0x100,
+            Flag::Trans1 => 0x200,
+            Flag::Interpose1 => 0x400,
+            Flag::NoDefLib1 => 0x800,
+            Flag::NoDump1 => 0x1000,
+            Flag::ConfAlt1 => 0x2000,
+            Flag::EndFiltee1 => 0x4000,
+            Flag::DispRelDNE1 => 0x8000,
+            Flag::DispRelPND1 => 0x10000,
+            Flag::NoDirect1 => 0x20000,
+            Flag::IGNMulDef1 => 0x40000,
+            Flag::NokSyms1 => 0x80000,
+            Flag::NoHdr1 => 0x100000,
+            Flag::Edited1 => 0x200000,
+            Flag::NoReloc1 => 0x400000,
+            Flag::SymInterpose1 => 0x800000,
+            Flag::GlobalAudit1 => 0x1000000,
+            Flag::Singleton1 => 0x2000000,
+            Flag::Stub1 => 0x4000000,
+            Flag::PIE1 => 0x8000000,
+            Flag::KMod1 => 0x10000000,
+            Flag::WeakFilter1 => 0x20000000,
+            Flag::NoCommon1 => 0x40000000,
+            Flag::Any(v) => *v,
+        }
+    }
 }
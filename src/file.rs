@@ -1,8 +1,42 @@
 #[allow(unused_imports)]
+pub use address::*;
+pub use bare_metal::*;
 pub use base::*;
+pub use edit::*;
 pub use elf32::*;
 pub use elf64::*;
+pub use entry::*;
+pub use file_type::*;
+pub use gc::*;
+pub use hardening::*;
+pub use memory::*;
+pub use prelink::*;
+pub use rpath::*;
+pub use search::*;
+pub use stats::*;
+#[cfg(feature = "json")]
+pub use structured::*;
+pub use transaction::*;
+pub use visibility::*;
 
+mod address;
+mod bare_metal;
 mod base;
+mod dependencies;
+mod edit;
 mod elf32;
 mod elf64;
+mod entry;
+mod file_type;
+mod gc;
+mod hardening;
+mod memory;
+mod prelink;
+mod rpath;
+mod search;
+mod stats;
+mod strip;
+#[cfg(feature = "json")]
+mod structured;
+mod transaction;
+mod visibility;
@@ -0,0 +1,413 @@
+//! Address range queries over the sections.
+
+use crate::layout::{is_alloc, is_nobits};
+use crate::*;
+use std::hash::{Hash, Hasher};
+use std::ops::Range;
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// An interval tree of the `SHF_ALLOC` sections, keyed by their virtual address ranges.
+///
+/// Empty sections and `.tbss`(`SHT_NOBITS` with `SHF_TLS`) are not indexed,
+/// since they occupy no address of their own.
+/// `ELF64::address_index()` builds the tree on the first call and caches it in the file,
+/// so the later queries(including `ELF64::section_containing_vaddr()`) don't scan the sections.
+/// The borrow keeps the file from changing under the index.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::builder;
+///
+/// let elf = builder::SharedObjectWriter::new()
+///     .function(builder::ExportedFunction::new("f", vec![0x90, 0xc3]))
+///     .build()
+///     .unwrap();
+/// let text = elf.first_section_by(|sct| sct.name == ".text").unwrap();
+///
+/// let index = elf.address_index();
+/// let found = index.section_containing_vaddr(text.header.sh_addr + 1).unwrap();
+/// assert_eq!(".text", found.name);
+/// assert!(index.section_containing_vaddr(0xdead_beef_0000).is_none());
+/// ```
+#[derive(Debug, Clone)]
+pub struct AddressIndex<'a> {
+    elf: &'a file::ELF64,
+    tree: Arc<IntervalTree>,
+}
+
+impl<'a> AddressIndex<'a> {
+    pub fn new(elf: &'a file::ELF64) -> Self {
+        elf.address_index()
+    }
+
+    /// the index of the first section containing `addr`.
+    pub fn shidx_containing_vaddr(&self, addr: Elf64Addr) -> Option<usize> {
+        let tree = &self.tree;
+        let mut found = Vec::new();
+        tree.query(
+            0,
+            tree.intervals.len(),
+            addr,
+            addr.saturating_add(1),
+            &mut found,
+        );
+        found.into_iter().min()
+    }
+
+    /// the section containing `addr`.
+    /// if sections overlap, the one with the smallest index is returned, like a linear scan.
+    pub fn section_containing_vaddr(&self, addr: Elf64Addr) -> Option<&'a section::Section64> {
+        let elf = self.elf;
+        self.shidx_containing_vaddr(addr)
+            .map(|idx| &elf.sections[idx])
+    }
+
+    /// the sections which overlap `range`, in address order.
+    pub fn sections_overlapping(&self, range: Range<Elf64Addr>) -> Vec<&'a section::Section64> {
+        let (elf, tree) = (self.elf, &self.tree);
+        let mut found = Vec::new();
+        if range.start < range.end {
+            tree.query(0, tree.intervals.len(), range.start, range.end, &mut found);
+        }
+        found.into_iter().map(|idx| &elf.sections[idx]).collect()
+    }
+}
+
+/// the interval tree which `AddressIndex` queries
+#[derive(Debug, Clone)]
+pub(crate) struct IntervalTree {
+    /// `(start, end, shidx)`, sorted by `start`
+    intervals: Vec<(Elf64Addr, Elf64Addr, usize)>,
+    /// the maximum `end` in the subtree rooted at each position
+    max_end: Vec<Elf64Addr>,
+    /// the address and the length of `sections` when it's built
+    sections: (usize, usize),
+}
+
+impl IntervalTree {
+    fn new(elf: &file::ELF64) -> Self {
+        let mut intervals = intervals(elf);
+        intervals.sort_unstable();
+
+        let mut tree = Self {
+            max_end: vec![0; intervals.len()],
+            intervals,
+            sections: sections_key(elf),
+        };
+        tree.build_max_end(0, tree.intervals.len());
+        tree
+    }
+
+    /// `intervals[lo..hi]`を部分木とし，中央の要素を根とする
+    fn build_max_end(&mut self, lo: usize, hi: usize) -> Elf64Addr {
+        if lo >= hi {
+            return 0;
+        }
+        let mid = lo + (hi - lo) / 2;
+        let left = self.build_max_end(lo, mid);
+        let right = self.build_max_end(mid + 1, hi);
+        self.max_end[mid] = self.intervals[mid].1.max(left).max(right);
+        self.max_end[mid]
+    }
+
+    /// collect the sections overlapping `[start, end)` in the subtree, in address order.
+    fn query(
+        &self,
+        lo: usize,
+        hi: usize,
+        start: Elf64Addr,
+        end: Elf64Addr,
+        found: &mut Vec<usize>,
+    ) {
+        if lo >= hi {
+            return;
+        }
+        let mid = lo + (hi - lo) / 2;
+        // 部分木のどの区間もstartより前で終わっている
+        if self.max_end[mid] <= start {
+            return;
+        }
+        self.query(lo, mid, start, end, found);
+        let (s, e, idx) = self.intervals[mid];
+        // 右の部分木はすべてs以降から始まる
+        if end <= s {
+            return;
+        }
+        if start < e {
+            found.push(idx);
+        }
+        self.query(mid + 1, hi, start, end, found);
+    }
+}
+
+/// The lazily built `IntervalTree` of `ELF64`.
+///
+/// It's only a cache, so comparisons and hashing ignore it and `clone()` doesn't copy it.
+#[derive(Default)]
+pub(crate) struct AddressCache(Mutex<Option<Arc<IntervalTree>>>);
+
+impl std::fmt::Debug for AddressCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("AddressCache")
+    }
+}
+
+impl Clone for AddressCache {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+impl PartialEq for AddressCache {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for AddressCache {}
+
+impl PartialOrd for AddressCache {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for AddressCache {
+    fn cmp(&self, _: &Self) -> std::cmp::Ordering {
+        std::cmp::Ordering::Equal
+    }
+}
+
+impl Hash for AddressCache {
+    fn hash<H: Hasher>(&self, _: &mut H) {}
+}
+
+fn sections_key(elf: &file::ELF64) -> (usize, usize) {
+    (elf.sections.as_ptr() as usize, elf.sections.len())
+}
+
+/// `(start, end, shidx)` of the sections `AddressIndex` indexes, in the section order.
+fn intervals(elf: &file::ELF64) -> Vec<(Elf64Addr, Elf64Addr, usize)> {
+    let tls: Elf64Xword = section::Flag::TLS.into();
+    elf.sections
+        .iter()
+        .enumerate()
+        .filter(|(_, sct)| is_alloc(&sct.header) && sct.header.sh_size != 0)
+        .filter(|(_, sct)| !(is_nobits(&sct.header) && sct.header.sh_flags & tls != 0))
+        .map(|(i, sct)| {
+            let start = sct.header.sh_addr;
+            (start, start.saturating_add(sct.header.sh_size), i)
+        })
+        .collect()
+}
+
+impl file::ELF64 {
+    /// the `AddressIndex` of the sections, which is built on the first call and cached.
+    ///
+    /// Adding or removing sections is noticed, but the cache doesn't see the headers edited in place.
+    /// Call `invalidate_address_index()` after moving or resizing sections directly;
+    /// the methods of this crate do it themselves.
+    pub fn address_index(&self) -> AddressIndex<'_> {
+        let mut cached = self
+            .address_cache
+            .0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        // キャッシュ後にセクションが追加・削除されていたら作り直す
+        let tree = match cached.as_ref() {
+            Some(tree) if tree.sections == sections_key(self) => Arc::clone(tree),
+            _ => {
+                let tree = Arc::new(IntervalTree::new(self));
+                *cached = Some(Arc::clone(&tree));
+                tree
+            }
+        };
+        AddressIndex { elf: self, tree }
+    }
+
+    /// drop the cached `AddressIndex`, so that the next query builds it again.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use elf_utilities::builder;
+    ///
+    /// let mut elf = builder::SharedObjectWriter::new()
+    ///     .function(builder::ExportedFunction::new("f", vec![0xc3]))
+    ///     .build()
+    ///     .unwrap();
+    /// let text = elf.first_shidx_by(|sct| sct.name == ".text").unwrap();
+    /// let addr = elf.sections[text].header.sh_addr;
+    /// assert_eq!(".text", elf.section_containing_vaddr(addr).unwrap().name);
+    ///
+    /// // ヘッダを直接書き換えたら，キャッシュを捨てる
+    /// elf.sections[text].header.sh_addr += 0x10_0000;
+    /// elf.invalidate_address_index();
+    /// assert!(elf.section_containing_vaddr(addr).is_none());
+    /// ```
+    pub fn invalidate_address_index(&mut self) {
+        self.address_cache = AddressCache::default();
+    }
+
+    /// the `SHF_ALLOC` section containing the virtual address.
+    ///
+    /// This queries the cached `address_index()`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use elf_utilities::builder;
+    ///
+    /// let elf = builder::SharedObjectWriter::new()
+    ///     .function(builder::ExportedFunction::new("f", vec![0xc3]))
+    ///     .build()
+    ///     .unwrap();
+    /// let dynsym = elf.first_section_by(|sct| sct.name == ".dynsym").unwrap();
+    ///
+    /// let found = elf.section_containing_vaddr(dynsym.header.sh_addr).unwrap();
+    /// assert_eq!(".dynsym", found.name);
+    /// ```
+    pub fn section_containing_vaddr(&self, addr: Elf64Addr) -> Option<&section::Section64> {
+        self.address_index().section_containing_vaddr(addr)
+    }
+
+    /// the `SHF_ALLOC` sections overlapping the virtual address range, in address order.
+    ///
+    /// This queries the cached `address_index()`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use elf_utilities::builder;
+    ///
+    /// let elf = builder::SharedObjectWriter::new()
+    ///     .function(builder::ExportedFunction::new("f", vec![0xc3]))
+    ///     .build()
+    ///     .unwrap();
+    /// let all = elf.sections_overlapping(0..u64::MAX);
+    /// assert!(all.iter().any(|sct| sct.name == ".text"));
+    /// assert!(all.iter().all(|sct| sct.name != ".shstrtab"));
+    /// assert!(elf.sections_overlapping(0..0).is_empty());
+    /// ```
+    pub fn sections_overlapping(&self, range: Range<Elf64Addr>) -> Vec<&section::Section64> {
+        self.address_index().sections_overlapping(range)
+    }
+
+    /// the load memory address(LMA) of the `SHF_ALLOC` section, as `objdump -h` shows.
+    ///
+    /// The LMA is computed from the `PT_LOAD` segment containing the section in the file,
+    /// so `SHT_NOBITS` sections and the sections out of the segments have `None`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `shidx` is out of range.
+    pub fn section_lma(&self, shidx: usize) -> Option<Elf64Addr> {
+        let shdr = &self.sections[shidx].header;
+        if !is_alloc(shdr) || is_nobits(shdr) {
+            return None;
+        }
+        self.segments
+            .iter()
+            .map(|sgt| &sgt.header)
+            .find(|phdr| {
+                phdr.get_type() == segment::Type::Load
+                    && phdr.p_offset <= shdr.sh_offset
+                    && shdr.sh_offset + shdr.sh_size <= phdr.p_offset + phdr.p_filesz
+            })
+            .map(|phdr| phdr.p_paddr + (shdr.sh_offset - phdr.p_offset))
+    }
+}
+
+#[cfg(test)]
+mod address_tests {
+    use super::*;
+
+    fn elf_with(ranges: &[(Elf64Addr, Elf64Xword)]) -> file::ELF64 {
+        let mut elf = file::ELF64::default();
+        for (i, (addr, size)) in ranges.iter().enumerate() {
+            let mut sct = section::Section64::new(
+                format!(".s{}", i),
+                section::ShdrPreparation64::default()
+                    .ty(section::Type::ProgBits)
+                    .flags([section::Flag::Alloc].iter()),
+                section::Contents64::Raw(vec![0; *size as usize]),
+            );
+            sct.header.sh_addr = *addr;
+            elf.add_section(sct);
+        }
+        elf
+    }
+
+    #[test]
+    fn address_index_test() {
+        let ranges: Vec<(Elf64Addr, Elf64Xword)> = (0..40u64)
+            .map(|i| ((i * 37) % 101 * 0x10, (i * 13) % 7 * 0x18))
+            .collect();
+        let elf = elf_with(&ranges);
+        let index = elf.address_index();
+
+        for addr in (0..0x700).step_by(4) {
+            let expected = elf.sections.iter().position(|sct| {
+                is_alloc(&sct.header)
+                    && sct.header.sh_addr <= addr
+                    && addr < sct.header.sh_addr + sct.header.sh_size
+            });
+            assert_eq!(expected, index.shidx_containing_vaddr(addr), "{:#x}", addr);
+            assert_eq!(
+                expected.map(|idx| &elf.sections[idx]),
+                elf.section_containing_vaddr(addr)
+            );
+
+            let range = addr..addr + 0x30;
+            assert_eq!(
+                index.sections_overlapping(range.clone()),
+                elf.sections_overlapping(range.clone())
+            );
+            let mut expected: Vec<&str> = elf
+                .sections
+                .iter()
+                .filter(|sct| {
+                    is_alloc(&sct.header)
+                        && sct.header.sh_size != 0
+                        && sct.header.sh_addr < range.end
+                        && range.start < sct.header.sh_addr + sct.header.sh_size
+                })
+                .map(|sct| sct.name.as_str())
+                .collect();
+            let mut actual: Vec<&str> = index
+                .sections_overlapping(range)
+                .iter()
+                .map(|sct| sct.name.as_str())
+                .collect();
+            expected.sort_unstable();
+            actual.sort_unstable();
+            assert_eq!(expected, actual);
+        }
+    }
+
+    #[test]
+    fn address_cache_test() {
+        let mut elf = elf_with(&[(0x1000, 0x10)]);
+        assert_eq!(".s0", elf.section_containing_vaddr(0x1000).unwrap().name);
+
+        // セクションの追加はキャッシュを作り直す
+        let mut sct = elf.sections[1].clone();
+        sct.name = ".s1".into();
+        sct.header.sh_addr = 0x2000;
+        elf.sections.push(sct);
+        assert_eq!(".s1", elf.section_containing_vaddr(0x2000).unwrap().name);
+
+        // ヘッダの書き換えは invalidate_address_index() まで見えない
+        elf.sections[1].header.sh_addr = 0x3000;
+        assert!(elf.section_containing_vaddr(0x3000).is_none());
+        elf.invalidate_address_index();
+        assert_eq!(".s0", elf.section_containing_vaddr(0x3000).unwrap().name);
+        assert!(elf.section_containing_vaddr(0x1000).is_none());
+
+        // 複製やキャッシュの有無は比較に影響しない
+        let copied = elf.clone();
+        assert_eq!(elf, copied);
+        assert_eq!(".s0", copied.section_containing_vaddr(0x3000).unwrap().name);
+    }
+}
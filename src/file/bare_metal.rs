@@ -0,0 +1,280 @@
+//! Checks for images which run without an operating system(e.g. firmware).
+
+use std::fmt;
+
+use crate::*;
+
+/// A `PT_LOAD` segment seen from a bare-metal loader
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LoadRegion {
+    /// the index of the segment
+    pub index: usize,
+    /// where the file image is stored(LMA)
+    pub paddr: Elf64Addr,
+    /// where the program accesses it(VMA)
+    pub vaddr: Elf64Addr,
+    pub file_size: Elf64Xword,
+    pub memory_size: Elf64Xword,
+    pub flags: Elf64Word,
+}
+
+impl LoadRegion {
+    /// whether the startup code has to copy the image from LMA to VMA, like `.data` stored in flash.
+    pub fn is_copied(&self) -> bool {
+        self.file_size != 0 && self.paddr != self.vaddr
+    }
+
+    pub fn is_executable(&self) -> bool {
+        self.flags & Elf64Word::from(segment::Flag::X) != 0
+    }
+}
+
+/// A problem found by `ELF64::check_bare_metal()`
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum BareMetalIssue {
+    /// the file isn't `ET_EXEC`
+    NotExecutable { ty: header::Type },
+    /// the file has no `PT_LOAD` segment
+    NoLoadSegment,
+    /// `e_entry` isn't in an executable `PT_LOAD` segment
+    EntryNotExecutable { entry: Elf64Addr },
+    /// the file requests a dynamic linker
+    Interpreter,
+    /// the file has `PT_DYNAMIC`, which nothing processes without a dynamic linker
+    Dynamic,
+    /// the file images of two segments overlap at their physical addresses
+    PhysicalOverlap { first: usize, second: usize },
+    /// the memory images of two segments overlap at their virtual addresses
+    VirtualOverlap { first: usize, second: usize },
+    /// `p_paddr` is 0 although `p_vaddr` isn't, which usually means the linker didn't set it
+    UnsetPhysicalAddress { index: usize },
+}
+
+impl fmt::Display for BareMetalIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotExecutable { ty } => write!(f, "the file is {:?}, not ET_EXEC", ty),
+            Self::NoLoadSegment => write!(f, "the file has no PT_LOAD segment"),
+            Self::EntryNotExecutable { entry } => write!(
+                f,
+                "the entry point {:#x} isn't in an executable PT_LOAD segment",
+                entry
+            ),
+            Self::Interpreter => write!(f, "the file has PT_INTERP"),
+            Self::Dynamic => write!(f, "the file has PT_DYNAMIC"),
+            Self::PhysicalOverlap { first, second } => write!(
+                f,
+                "segments {} and {} overlap at their physical addresses",
+                first, second
+            ),
+            Self::VirtualOverlap { first, second } => write!(
+                f,
+                "segments {} and {} overlap at their virtual addresses",
+                first, second
+            ),
+            Self::UnsetPhysicalAddress { index } => {
+                write!(f, "segment {} has p_paddr 0 but p_vaddr isn't", index)
+            }
+        }
+    }
+}
+
+/// The result of `ELF64::check_bare_metal()`
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct BareMetalReport {
+    /// the `PT_LOAD` segments ordered by the physical address
+    pub regions: Vec<LoadRegion>,
+    pub issues: Vec<BareMetalIssue>,
+}
+
+impl BareMetalReport {
+    pub fn is_ok(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+impl file::ELF64 {
+    /// check that the file can be loaded by a bare-metal loader or a flash programmer.
+    ///
+    /// The loader copies the file images of the `PT_LOAD` segments to their physical addresses(`p_paddr`)
+    /// and jumps to `e_entry`, so the file must be `ET_EXEC` without `PT_INTERP` or `PT_DYNAMIC`,
+    /// the entry point must be in an executable segment,
+    /// and the segments must not overlap in either address space.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use elf_utilities::builder;
+    ///
+    /// let main = builder::ExportedFunction::new("main", vec![0xc3]);
+    /// let elf = builder::ExecutableWriter::new()
+    ///     .function(main)
+    ///     .start_stub(builder::start_stub("main"))
+    ///     .build()
+    ///     .unwrap();
+    ///
+    /// let report = elf.check_bare_metal();
+    /// assert!(report.is_ok(), "{:?}", report.issues);
+    /// assert!(report.regions.iter().all(|region| !region.is_copied()));
+    /// ```
+    pub fn check_bare_metal(&self) -> BareMetalReport {
+        let mut report = BareMetalReport::default();
+
+        let ty = self.ehdr.get_type();
+        if ty != header::Type::Exec {
+            report.issues.push(BareMetalIssue::NotExecutable { ty });
+        }
+        for sgt in self.segments.iter() {
+            match sgt.header.get_type() {
+                segment::Type::Interp => report.issues.push(BareMetalIssue::Interpreter),
+                segment::Type::Dynamic => report.issues.push(BareMetalIssue::Dynamic),
+                _ => {}
+            }
+        }
+
+        for (index, sgt) in self.segments.iter().enumerate() {
+            let phdr = &sgt.header;
+            if phdr.get_type() != segment::Type::Load {
+                continue;
+            }
+            report.regions.push(LoadRegion {
+                index,
+                paddr: phdr.p_paddr,
+                vaddr: phdr.p_vaddr,
+                file_size: phdr.p_filesz,
+                memory_size: phdr.p_memsz,
+                flags: phdr.p_flags,
+            });
+        }
+        report
+            .regions
+            .sort_by_key(|region| (region.paddr, region.index));
+        if report.regions.is_empty() {
+            report.issues.push(BareMetalIssue::NoLoadSegment);
+            return report;
+        }
+
+        let entry = self.ehdr.e_entry;
+        let entry_mapped = report.regions.iter().any(|region| {
+            region.is_executable()
+                && region.vaddr <= entry
+                && entry < region.vaddr + region.memory_size
+        });
+        if !entry_mapped {
+            report
+                .issues
+                .push(BareMetalIssue::EntryNotExecutable { entry });
+        }
+
+        for region in report.regions.iter() {
+            if region.paddr == 0 && region.vaddr != 0 {
+                report.issues.push(BareMetalIssue::UnsetPhysicalAddress {
+                    index: region.index,
+                });
+            }
+        }
+
+        // 物理アドレス上ではファイルイメージだけが書き込まれる
+        let physical_ranges: Vec<(Elf64Addr, Elf64Addr, usize)> = report
+            .regions
+            .iter()
+            .filter(|region| region.file_size != 0)
+            .map(|region| (region.paddr, region.paddr + region.file_size, region.index))
+            .collect();
+        for (first, second) in overlaps(physical_ranges) {
+            report
+                .issues
+                .push(BareMetalIssue::PhysicalOverlap { first, second });
+        }
+        let virtual_ranges: Vec<(Elf64Addr, Elf64Addr, usize)> = report
+            .regions
+            .iter()
+            .filter(|region| region.memory_size != 0)
+            .map(|region| {
+                (
+                    region.vaddr,
+                    region.vaddr + region.memory_size,
+                    region.index,
+                )
+            })
+            .collect();
+        for (first, second) in overlaps(virtual_ranges) {
+            report
+                .issues
+                .push(BareMetalIssue::VirtualOverlap { first, second });
+        }
+        report
+    }
+}
+
+/// the pairs of the overlapping ranges(start, end, index), the smaller index first.
+fn overlaps(mut ranges: Vec<(Elf64Addr, Elf64Addr, usize)>) -> Vec<(usize, usize)> {
+    ranges.sort_unstable();
+    let mut pairs = Vec::new();
+    for (i, a) in ranges.iter().enumerate() {
+        for b in ranges[i + 1..].iter().take_while(|b| b.0 < a.1) {
+            pairs.push((a.2.min(b.2), a.2.max(b.2)));
+        }
+    }
+    pairs
+}
+
+#[cfg(test)]
+mod bare_metal_tests {
+    use super::*;
+
+    fn load(
+        paddr: Elf64Addr,
+        vaddr: Elf64Addr,
+        size: Elf64Xword,
+        flags: Elf64Word,
+    ) -> segment::Segment64 {
+        segment::Segment64 {
+            header: segment::Phdr64 {
+                p_type: segment::Type::Load.to_bytes(),
+                p_paddr: paddr,
+                p_vaddr: vaddr,
+                p_filesz: size,
+                p_memsz: size,
+                p_flags: flags,
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn check_bare_metal_test() {
+        let rx = Elf64Word::from(segment::Flag::R) | Elf64Word::from(segment::Flag::X);
+        let rw = Elf64Word::from(segment::Flag::R) | Elf64Word::from(segment::Flag::W);
+        let mut elf = file::ELF64::default();
+        elf.ehdr.set_elf_type(header::Type::Exec);
+        elf.ehdr.e_entry = 0x0800_0100;
+        // フラッシュに置かれ，RAMにコピーされる.data
+        elf.segments = vec![
+            load(0x0800_0000, 0x0800_0000, 0x1000, rx),
+            load(0x0800_1000, 0x2000_0000, 0x100, rw),
+        ];
+        let report = elf.check_bare_metal();
+        assert!(report.is_ok(), "{:?}", report.issues);
+        assert!(!report.regions[0].is_copied());
+        assert!(report.regions[1].is_copied());
+
+        elf.segments.push(load(0x0800_0f00, 0x2000_0080, 0x100, rw));
+        elf.ehdr.e_entry = 0x2000_0000;
+        let report = elf.check_bare_metal();
+        assert_eq!(
+            vec![
+                BareMetalIssue::EntryNotExecutable { entry: 0x2000_0000 },
+                BareMetalIssue::PhysicalOverlap {
+                    first: 0,
+                    second: 2
+                },
+                BareMetalIssue::VirtualOverlap {
+                    first: 1,
+                    second: 2
+                },
+            ],
+            report.issues
+        );
+    }
+}
@@ -5,6 +5,7 @@ use std::os::unix::fs::OpenOptionsExt;
 #[cfg(target_family = "windows")]
 use std::os::windows::fs::OpenOptionsExt;
 
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ELF {
     ELF32(ELF32),
     ELF64(ELF64),
@@ -15,13 +16,13 @@ pub struct ELFDumper {
 }
 
 impl ELF {
-    pub(crate) fn as_64bit(self) -> ELF64 {
+    pub(crate) fn into_64bit(self) -> ELF64 {
         match self {
             ELF::ELF64(e) => e,
             _ => unreachable!(),
         }
     }
-    pub(crate) fn as_32bit(self) -> ELF32 {
+    pub(crate) fn into_32bit(self) -> ELF32 {
         match self {
             ELF::ELF32(e) => e,
             _ => unreachable!(),
@@ -56,7 +57,7 @@ impl ELFDumper {
             .open(output_filename)?;
 
         #[cfg(target_family = "windows")]
-            let file = std::fs::OpenOptions::new()
+        let file = std::fs::OpenOptions::new()
             .create(true)
             .read(true)
             .write(true)
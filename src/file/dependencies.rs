@@ -0,0 +1,80 @@
+//! Reading what a dynamically linked file depends on.
+
+use crate::*;
+
+impl file::ELF64 {
+    /// the path in `PT_INTERP`, without the terminating NUL.
+    pub fn interpreter(&self) -> Option<String> {
+        let interp = self
+            .segments
+            .iter()
+            .find(|sgt| sgt.header.get_type() == segment::Type::Interp)?;
+        let bytes = self.file_bytes(interp.header.p_offset, interp.header.p_filesz)?;
+        let path = bytes.split(|b| *b == 0).next()?;
+        Some(String::from_utf8_lossy(path).to_string())
+    }
+
+    /// the libraries in `DT_NEEDED`, in the order the dynamic linker loads them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use elf_utilities::parser;
+    ///
+    /// let elf = parser::parse_elf64("src/parser/testdata/sample").unwrap();
+    ///
+    /// assert_eq!(vec!["libc.so.6".to_string()], elf.needed());
+    /// assert_eq!(Some("/lib64/ld-linux-x86-64.so.2".to_string()), elf.interpreter());
+    /// assert_eq!(None, elf.soname());
+    /// ```
+    pub fn needed(&self) -> Vec<String> {
+        self.dynamic_strings(dynamic::EntryType::Needed)
+    }
+
+    /// `DT_SONAME`
+    pub fn soname(&self) -> Option<String> {
+        self.dynamic_strings(dynamic::EntryType::SOName)
+            .into_iter()
+            .next()
+    }
+
+    /// the strings in `.dynstr` referred by the dynamic entries of the type.
+    pub(crate) fn dynamic_strings(&self, ty: dynamic::EntryType) -> Vec<String> {
+        let dynamic =
+            match self.first_section_by(|sct| sct.header.get_type() == section::Type::Dynamic) {
+                Some(sct) => sct,
+                None => return Vec::new(),
+            };
+        let entries = match &dynamic.contents {
+            section::Contents64::Dynamics(entries) => entries,
+            _ => return Vec::new(),
+        };
+        let dynstr = self
+            .sections
+            .get(dynamic.header.sh_link as usize)
+            .map(|sct| sct.to_le_bytes())
+            .unwrap_or_default();
+        // 接尾辞を共有する文字列もあるので，エントリではなくバイト列から読む
+        entries
+            .iter()
+            .filter(|ent| ent.get_type() == ty)
+            .filter_map(|ent| dynstr.get(ent.d_un as usize..))
+            .filter_map(|s| s.split(|b| *b == 0).next())
+            .map(|s| String::from_utf8_lossy(s).to_string())
+            .collect()
+    }
+
+    /// the bytes at the file offset, taken from the section which contains them.
+    pub(crate) fn file_bytes(&self, offset: Elf64Off, size: Elf64Xword) -> Option<Vec<u8>> {
+        let sct = self.sections.iter().find(|sct| {
+            let hdr = &sct.header;
+            !layout::is_nobits(hdr)
+                && hdr.sh_offset <= offset
+                && offset + size <= hdr.sh_offset + hdr.sh_size
+        })?;
+        let start = (offset - sct.header.sh_offset) as usize;
+        sct.to_le_bytes()
+            .get(start..start + size as usize)
+            .map(|b| b.to_vec())
+    }
+}
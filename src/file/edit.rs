@@ -0,0 +1,244 @@
+//! Recording modifications of ELF files for auditing.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::*;
+
+/// A modification recorded by `EditSession`
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Change {
+    /// the API which made the change(e.g. `transform::redirect_import`)
+    pub api: String,
+    /// the modified field with the old and new values
+    pub difference: analysis::Difference,
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.api, self.difference)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Edit {
+    api: String,
+    before: file::ELF64,
+    changes: Vec<Change>,
+}
+
+/// A mutable view of ELF64 which records every modification.
+///
+/// Each edit is made through `apply()` (or the setters) with the name of the API,
+/// and the differences found by `analysis::diff()` are recorded as `Change`s.
+/// The file before each edit is kept, so edits can be undone in reverse order.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::{file, transform};
+///
+/// let mut session = file::EditSession::new(file::ELF64::default());
+/// session.set_entry(0x401000);
+/// let result = session.try_apply("transform::redirect_import", |elf| {
+///     transform::redirect_import(elf, "malloc", "my_malloc")
+/// });
+/// assert!(result.is_err());
+///
+/// assert_eq!(
+///     "set_entry: header: e_entry 0x0 -> 0x401000",
+///     session.change_log()
+/// );
+/// session.undo();
+/// assert_eq!(0, session.elf().ehdr.e_entry);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct EditSession {
+    elf: file::ELF64,
+    edits: Vec<Edit>,
+}
+
+impl EditSession {
+    pub fn new(elf: file::ELF64) -> Self {
+        Self {
+            elf,
+            edits: Vec::new(),
+        }
+    }
+
+    /// the current file
+    pub fn elf(&self) -> &file::ELF64 {
+        &self.elf
+    }
+
+    /// the file before any edits
+    pub fn original(&self) -> &file::ELF64 {
+        self.edits.first().map_or(&self.elf, |edit| &edit.before)
+    }
+
+    /// modify the file by `f`, recording the changes as made by `api`.
+    ///
+    /// Edits leaving the file equal aren't recorded.
+    /// An edit changing only what `analysis::diff()` doesn't compare has no `Change`s, but can be undone.
+    pub fn apply<F, T>(&mut self, api: &str, f: F) -> T
+    where
+        F: FnOnce(&mut file::ELF64) -> T,
+    {
+        let before = self.elf.clone();
+        let result = f(&mut self.elf);
+        // fがセクションを動かしたかもしれない
+        self.elf.invalidate_address_index();
+        self.record(api, before);
+        result
+    }
+
+    /// like `apply()`, but the file is restored if `f` fails.
+    pub fn try_apply<F, T, E>(&mut self, api: &str, f: F) -> Result<T, E>
+    where
+        F: FnOnce(&mut file::ELF64) -> Result<T, E>,
+    {
+        let before = self.elf.clone();
+        match f(&mut self.elf) {
+            Ok(v) => {
+                self.elf.invalidate_address_index();
+                self.record(api, before);
+                Ok(v)
+            }
+            Err(e) => {
+                self.elf = before;
+                Err(e)
+            }
+        }
+    }
+
+    /// set `e_entry`.
+    pub fn set_entry(&mut self, entry: Elf64Addr) {
+        self.apply("set_entry", |elf| elf.ehdr.e_entry = entry);
+    }
+
+    /// set `sh_flags` of the first section named `name`.
+    /// Returns `false` if the section isn't found.
+    pub fn set_section_flags(&mut self, name: &str, flags: Elf64Xword) -> bool {
+        self.apply("set_section_flags", |elf| {
+            elf.first_mut_section_by(|sct| sct.name == name)
+                .map(|sct| sct.header.sh_flags = flags)
+                .is_some()
+        })
+    }
+
+    /// set `p_flags` of the `index`-th segment.
+    /// Returns `false` if the segment isn't found.
+    pub fn set_segment_flags(&mut self, index: usize, flags: Elf64Word) -> bool {
+        self.apply("set_segment_flags", |elf| {
+            elf.segments
+                .get_mut(index)
+                .map(|sgt| sgt.header.p_flags = flags)
+                .is_some()
+        })
+    }
+
+    /// all changes in the order they were made
+    pub fn changes(&self) -> Vec<Change> {
+        self.edits
+            .iter()
+            .flat_map(|edit| edit.changes.iter().cloned())
+            .collect()
+    }
+
+    /// the changes as lines of text.
+    pub fn change_log(&self) -> String {
+        self.changes()
+            .iter()
+            .map(|c| c.to_string())
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    /// revert the last edit and returns its API name.
+    pub fn undo(&mut self) -> Option<String> {
+        let edit = self.edits.pop()?;
+        self.elf = edit.before;
+        Some(edit.api)
+    }
+
+    /// revert all edits.
+    pub fn revert(&mut self) {
+        if !self.edits.is_empty() {
+            self.elf = self.edits.swap_remove(0).before;
+            self.edits.clear();
+        }
+    }
+
+    /// finish the session and returns the current file.
+    pub fn into_inner(self) -> file::ELF64 {
+        self.elf
+    }
+
+    fn record(&mut self, api: &str, before: file::ELF64) {
+        // diff()が比べないフィールド(sh_name等)だけの変更も取り消せるように記録する
+        if before == self.elf {
+            return;
+        }
+        let changes: Vec<Change> = analysis::diff(&before, &self.elf)
+            .into_iter()
+            .map(|difference| Change {
+                api: api.to_string(),
+                difference,
+            })
+            .collect();
+        self.edits.push(Edit {
+            api: api.to_string(),
+            before,
+            changes,
+        });
+    }
+}
+
+#[cfg(test)]
+mod edit_tests {
+    use super::*;
+
+    #[test]
+    fn edit_session_test() {
+        let main = builder::ExportedFunction::new("main", vec![0xc3]);
+        let elf = builder::ExecutableWriter::new()
+            .function(main)
+            .start_stub(builder::start_stub("main"))
+            .build()
+            .unwrap();
+        let mut session = EditSession::new(elf.clone());
+
+        assert!(session.set_section_flags(".text", section::Flag::Alloc.into()));
+        assert!(!session.set_section_flags(".nothing", 0));
+        session.apply("noop", |_| {});
+        let text = elf.first_section_by(|sct| sct.name == ".text").unwrap();
+        assert_eq!(
+            vec![Change {
+                api: "set_section_flags".to_string(),
+                difference: analysis::Difference::SectionHeader {
+                    name: ".text".to_string(),
+                    field: "sh_flags".to_string(),
+                    old: text.header.sh_flags,
+                    new: section::Flag::Alloc.into(),
+                },
+            }],
+            session.changes()
+        );
+
+        session.set_entry(0);
+        assert_eq!(2, session.changes().len());
+        assert_eq!(Some("set_entry".to_string()), session.undo());
+        assert_eq!(elf.ehdr.e_entry, session.elf().ehdr.e_entry);
+
+        session.revert();
+        assert_eq!(&elf, session.elf());
+        assert!(session.changes().is_empty());
+
+        // diff()に現れない変更も取り消せる
+        session.apply("rename", |elf| elf.sections[1].header.sh_name += 1);
+        assert!(session.changes().is_empty());
+        assert_eq!(Some("rename".to_string()), session.undo());
+        assert_eq!(&elf, session.elf());
+    }
+}
@@ -1,11 +1,11 @@
 use crate::{
-    header,
-    section::{self, Section32},
+    header, layout,
+    section::{self, Section32, StringTable},
     segment,
 };
 
 #[repr(C)]
-#[derive(Default, Clone, Hash, PartialOrd, Ord, PartialEq, Eq)]
+#[derive(Default, Debug, Clone, Hash, PartialOrd, Ord, PartialEq, Eq)]
 pub struct ELF32 {
     pub ehdr: header::Ehdr32,
     pub sections: Vec<section::Section32>,
@@ -24,7 +24,10 @@ impl ELF32 {
         self.fill_elf_info(&mut sct, last_sct_idx, &self.sections[last_sct_idx]);
 
         // セクションの追加 => SHTの開始オフセットが変更される
-        self.ehdr.e_shoff += sct.header.sh_size;
+        // NOBITSはファイル上の領域を持たない
+        if sct.header.get_type() != section::Type::NoBits {
+            self.ehdr.e_shoff += sct.header.sh_size;
+        }
         self.ehdr.e_shnum += 1;
 
         self.sections.push(sct);
@@ -44,36 +47,141 @@ impl ELF32 {
 
         self.segments.push(sgt);
     }
-    pub fn to_le_bytes(&self) -> Vec<u8> {
-        let mut file_binary: Vec<u8> = Vec::new();
+    /// get section index if predicate returns true.
+    pub fn first_shidx_by<P>(&self, predicate: P) -> Option<usize>
+    where
+        P: Fn(&section::Section32) -> bool,
+    {
+        self.sections.iter().position(predicate)
+    }
+
+    /// recompute `sh_size`/`sh_offset` of every section and the table offsets in the ELF header,
+    /// so that `to_le_bytes()` emits a consistent file.
+    ///
+    /// Like `ELF64::condition()`, `.shstrtab` is rebuilt and the virtual addresses and segments are kept as is.
+    /// Use `layout::Layout::apply_elf32()` to lay out executables.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use elf_utilities::{file, header, section};
+    ///
+    /// let mut elf = file::ELF32::default();
+    /// elf.sections.push(section::Section32::new_null_section());
+    /// elf.sections.push(section::Section32::new(
+    ///     ".text".to_string(),
+    ///     section::ShdrPreparation32::default().ty(section::Type::ProgBits),
+    ///     section::Contents32::Raw(vec![0xc3]),
+    /// ));
+    /// elf.sections.push(section::Section32::new(
+    ///     ".shstrtab".to_string(),
+    ///     section::ShdrPreparation32::default().ty(section::Type::StrTab),
+    ///     section::Contents32::Raw(Vec::new()),
+    /// ));
+    /// elf.ehdr.e_shstrndx = 2;
+    /// elf.condition();
+    ///
+    /// assert_eq!(header::Ehdr32::SIZE as u32, elf.sections[1].header.sh_offset);
+    /// assert_eq!(3, elf.ehdr.e_shnum);
+    /// assert_eq!(elf.ehdr.e_shoff as usize + 3 * section::Shdr32::SIZE, elf.to_le_bytes().len());
+    /// ```
+    pub fn condition(&mut self) {
+        self.rebuild_shstrtab();
+
+        let mut file_offset =
+            header::Ehdr32::SIZE as u64 + segment::Phdr32::SIZE as u64 * self.segments.len() as u64;
+
+        for sct in self.sections.iter_mut().skip(1) {
+            let is_nobits = sct.header.get_type() == section::Type::NoBits;
+            if !is_nobits {
+                sct.header.sh_size = sct.contents.size() as u32;
+                // NOBITSはファイル上の領域を持たないので，次のセクションの位置に影響しない
+                file_offset = layout::align_up(file_offset, sct.header.sh_addralign as u64);
+            }
+            sct.header.sh_offset = file_offset as u32;
+            if !is_nobits {
+                file_offset += sct.header.sh_size as u64;
+            }
+        }
+
+        // PHTが無ければe_phoffは0にする
+        self.ehdr.e_phoff = if self.segments.is_empty() {
+            0
+        } else {
+            header::Ehdr32::SIZE as u32
+        };
+        self.ehdr.e_phnum = self.segments.len() as u16;
+        self.ehdr.e_shoff = layout::align_up(file_offset, 4) as u32;
+        self.ehdr.e_shnum = self.sections.len() as u16;
+    }
+
+    /// rebuild the section header string table(`e_shstrndx`) from `Section32::name`,
+    /// and assign `sh_name` of every section.
+    ///
+    /// A table shared with the symbol names, as LLVM emits in objects, is kept as is.
+    pub fn rebuild_shstrtab(&mut self) {
+        let shstrndx = self.ehdr.e_shstrndx as usize;
+        if shstrndx == 0 || shstrndx >= self.sections.len() {
+            return;
+        }
+        // シンボル名の文字列表を兼ねていれば，既存のsh_nameも有効
+        let shared = self.sections.iter().any(|sct| {
+            matches!(
+                sct.header.get_type(),
+                section::Type::SymTab | section::Type::DynSym
+            ) && sct.header.sh_link as usize == shstrndx
+        });
+        if shared {
+            return;
+        }
+
+        let mut shstrtab = StringTable::new();
+        for sct in self.sections.iter_mut().skip(1) {
+            sct.header.sh_name = shstrtab.add(&sct.name) as u32;
+        }
+        self.sections[shstrndx].contents = shstrtab.to_contents32();
+    }
 
-        let mut header_binary = self.ehdr.to_le_bytes();
-        file_binary.append(&mut header_binary);
+    /// Create Vec<u8> from this.
+    /// Each table and section is placed at the offset written in its header.
+    /// The section header table is omitted if `e_shoff` is 0.
+    pub fn to_le_bytes(&self) -> Vec<u8> {
+        let mut file_binary: Vec<u8> = self.ehdr.to_le_bytes();
 
+        let mut pht_binary = Vec::new();
         for seg in self.segments.iter() {
-            let mut phdr_binary = seg.header.to_le_bytes();
-            file_binary.append(&mut phdr_binary);
+            pht_binary.append(&mut seg.header.to_le_bytes());
         }
+        write_at(&mut file_binary, self.ehdr.e_phoff, &pht_binary);
 
         for sct in self.sections.iter() {
-            // セクションタイプによって処理を変える
-            let mut section_binary = sct.to_le_bytes();
-            file_binary.append(&mut section_binary);
+            if sct.header.get_type() == section::Type::NoBits {
+                continue;
+            }
+            write_at(&mut file_binary, sct.header.sh_offset, &sct.to_le_bytes());
         }
 
-        for sct in self.sections.iter() {
-            let mut shdr_binary = sct.header.to_le_bytes();
-            file_binary.append(&mut shdr_binary);
+        if self.ehdr.e_shoff != 0 {
+            let mut sht_binary = Vec::new();
+            for sct in self.sections.iter() {
+                sht_binary.append(&mut sct.header.to_le_bytes());
+            }
+            write_at(&mut file_binary, self.ehdr.e_shoff, &sht_binary);
         }
+
         file_binary
     }
 
     /// sh_nameやsh_offset等の調整
     fn fill_elf_info(&self, new_sct: &mut Section32, prev_sct_idx: usize, prev_sct: &Section32) {
         let prev_name_idx = prev_sct.header.sh_name;
-        let prev_name_len = prev_sct.name.as_bytes().len() as u32;
+        let prev_name_len = prev_sct.name.len() as u32;
         let prev_offset = prev_sct.header.sh_offset;
-        let prev_size = prev_sct.header.sh_size;
+        let prev_size = if prev_sct.header.get_type() == section::Type::NoBits {
+            0
+        } else {
+            prev_sct.header.sh_size
+        };
 
         // <prev_section_name> の後に0x00が入るので，+1
         new_sct.header.sh_name = prev_name_idx + prev_name_len + 1;
@@ -87,6 +195,21 @@ impl ELF32 {
             new_sct.header.sh_offset = prev_offset + prev_size;
         }
 
-        new_sct.header.sh_size = new_sct.contents.size() as u32;
+        // NOBITSのサイズはヘッダに指定されたものを使う
+        if new_sct.header.get_type() != section::Type::NoBits {
+            new_sct.header.sh_size = new_sct.contents.size() as u32;
+        }
+    }
+}
+
+fn write_at(buf: &mut Vec<u8>, offset: u32, bytes: &[u8]) {
+    if bytes.is_empty() {
+        return;
+    }
+
+    let start = offset as usize;
+    if buf.len() < start + bytes.len() {
+        buf.resize(start + bytes.len(), 0x00);
     }
+    buf[start..start + bytes.len()].copy_from_slice(bytes);
 }
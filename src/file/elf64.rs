@@ -2,8 +2,8 @@ use section::Section64;
 use segment::Segment64;
 
 use crate::{
-    header,
-    section::{self, Contents64, StrTabEntry},
+    header, layout,
+    section::{self, Contents64, StrTabEntry, StringTable},
     segment,
 };
 
@@ -15,18 +15,27 @@ pub struct ELF64 {
     pub ehdr: header::Ehdr64,
     pub sections: Vec<section::Section64>,
     pub segments: Vec<segment::Segment64>,
+    pub(crate) address_cache: super::AddressCache,
+}
+
+impl std::fmt::Debug for ELF64 {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ELF64")
+            .field("ehdr", &self.ehdr)
+            .field("sections", &self.sections)
+            .field("segments", &self.segments)
+            .finish()
+    }
 }
 
 impl Default for ELF64 {
     fn default() -> Self {
         Self {
-            ehdr: {
-                let mut hdr = header::Ehdr64::default();
-                hdr.e_shnum = 2;
-                hdr.e_shstrndx = 1;
-                hdr.e_shoff += SHSTRTAB_INITIAL_SIZE as u64;
-
-                hdr
+            ehdr: header::Ehdr64 {
+                e_shnum: 2,
+                e_shstrndx: 1,
+                e_shoff: header::Ehdr64::SIZE as u64 + SHSTRTAB_INITIAL_SIZE as u64,
+                ..Default::default()
             },
             sections: {
                 let mut scts = Vec::with_capacity(50);
@@ -34,7 +43,7 @@ impl Default for ELF64 {
 
                 let shstrtab_contents = Contents64::new_string_table(vec![".shstrtab".to_string()]);
                 scts.push(section::Section64 {
-                    name: ".shstrtab".to_string(),
+                    name: ".shstrtab".into(),
                     header: section::Shdr64 {
                         sh_name: 1,
                         sh_type: section::Type::StrTab.into(),
@@ -52,6 +61,7 @@ impl Default for ELF64 {
                 scts
             },
             segments: Vec::with_capacity(10),
+            address_cache: Default::default(),
         }
     }
 }
@@ -59,6 +69,7 @@ impl Default for ELF64 {
 impl ELF64 {
     /// add a section with creating new entry of section table and etc.
     pub fn add_section(&mut self, mut sct: Section64) {
+        self.invalidate_address_index();
         // 新しいセクションのsh_name等を計算する為に
         // 現在の末尾のセクションを取得する
         // 本当の末尾には.shstrtabが存在するので，その一つ前
@@ -67,7 +78,10 @@ impl ELF64 {
         self.fill_elf_info(&mut sct, last_sct_idx);
 
         // セクションの追加 => SHTの開始オフセットが変更される
-        self.ehdr.e_shoff += sct.header.sh_size;
+        // NOBITSはファイル上の領域を持たない
+        if sct.header.get_type() != section::Type::NoBits {
+            self.ehdr.e_shoff += sct.header.sh_size;
+        }
         self.ehdr.e_shnum += 1;
         self.ehdr.e_shstrndx += 1;
 
@@ -120,34 +134,299 @@ impl ELF64 {
         }
     }
 
-    pub fn to_le_bytes(&self) -> Vec<u8> {
-        let mut file_binary: Vec<u8> = Vec::new();
+    /// move the section at `from` to `to`, shifting the sections between them.
+    ///
+    /// Every section index in the file is rewritten to follow the move:
+    /// `sh_link`, `sh_info` of relocation sections(or `SHF_INFO_LINK`),
+    /// `st_shndx` of symbols, members of section groups and `e_shstrndx`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `from` or `to` is out of range or is the null section.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use elf_utilities::{file, section};
+    ///
+    /// let mut elf = file::ELF64::default();
+    /// elf.add_section(section::Section64::new(
+    ///     ".text".to_string(),
+    ///     section::ShdrPreparation64::default().ty(section::Type::ProgBits),
+    ///     section::Contents64::Raw(vec![0xc3]),
+    /// ));
+    ///
+    /// // move .shstrtab to the front
+    /// elf.move_section(2, 1);
+    /// assert_eq!(".shstrtab", elf.sections[1].name);
+    /// assert_eq!(".text", elf.sections[2].name);
+    /// assert_eq!(1, elf.ehdr.e_shstrndx);
+    /// ```
+    pub fn move_section(&mut self, from: usize, to: usize) {
+        let len = self.sections.len();
+        assert!(
+            0 < from && from < len && 0 < to && to < len,
+            "can't move section {} to {}",
+            from,
+            to
+        );
+        if from == to {
+            return;
+        }
+
+        // 移動による各インデックスの変化
+        let new_index = |idx: usize| -> usize {
+            if idx == from {
+                to
+            } else if from < idx && idx <= to {
+                idx - 1
+            } else if to <= idx && idx < from {
+                idx + 1
+            } else {
+                idx
+            }
+        };
+        self.remap_section_indices(new_index);
+
+        let sct = self.sections.remove(from);
+        self.sections.insert(to, sct);
+    }
+
+    /// rewrite every section index in the file by `new_index`, without moving sections.
+    /// removed sections should be mapped to 0.
+    pub(crate) fn remap_section_indices<F>(&mut self, new_index: F)
+    where
+        F: Fn(usize) -> usize,
+    {
+        let len = self.sections.len();
+        let new_shndx = |shndx: u16| -> u16 {
+            // SHN_UNDEFや予約済みのインデックスはそのまま
+            if shndx == section::SHN_UNDEF || shndx >= section::SHN_LORESERVE {
+                shndx
+            } else {
+                new_index(shndx as usize) as u16
+            }
+        };
+
+        let info_link: u64 = section::Flag::InfoLink.into();
+        for sct in self.sections.iter_mut().skip(1) {
+            let hdr = &mut sct.header;
+            if hdr.sh_link != 0 && (hdr.sh_link as usize) < len {
+                hdr.sh_link = new_index(hdr.sh_link as usize) as u32;
+            }
+            let ty = hdr.get_type();
+            if (ty == section::Type::Rel
+                || ty == section::Type::Rela
+                || hdr.sh_flags & info_link != 0)
+                && hdr.sh_info != 0
+                && (hdr.sh_info as usize) < len
+            {
+                hdr.sh_info = new_index(hdr.sh_info as usize) as u32;
+            }
+
+            match &mut sct.contents {
+                Contents64::Symbols(syms) => {
+                    for sym in syms.iter_mut() {
+                        sym.st_shndx = new_shndx(sym.st_shndx);
+                    }
+                }
+                // GRP_COMDAT等のフラグに続いて，メンバのインデックスが並ぶ
+                // 削除されたメンバ(新しいインデックスが0)は取り除く
+                Contents64::Raw(bytes) if ty == section::Type::Group && bytes.len() >= 4 => {
+                    let mut group = bytes[..4].to_vec();
+                    for member in bytes.chunks_exact(4).skip(1) {
+                        let idx = u32::from_le_bytes([member[0], member[1], member[2], member[3]]);
+                        let idx = new_index(idx as usize) as u32;
+                        if idx != 0 {
+                            group.extend_from_slice(&idx.to_le_bytes());
+                        }
+                    }
+                    *bytes = group;
+                }
+                _ => {}
+            }
+        }
+        self.ehdr.e_shstrndx = new_shndx(self.ehdr.e_shstrndx);
+    }
+
+    /// the largest alignment of `PT_LOAD` segments.
+    /// it's the page size the file was linked for(`-z max-page-size`).
+    pub fn max_page_size(&self) -> Option<u64> {
+        self.segments
+            .iter()
+            .filter(|sgt| sgt.header.get_type() == segment::Type::Load)
+            .map(|sgt| sgt.header.p_align)
+            .max()
+    }
 
-        let mut header_binary = self.ehdr.to_le_bytes();
-        file_binary.append(&mut header_binary);
+    /// recompute `sh_size`/`sh_offset` of every section and the table offsets in the ELF header,
+    /// so that `to_le_bytes()` emits a consistent file.
+    ///
+    /// `.shstrtab` is rebuilt from the section names by `rebuild_shstrtab()`.
+    ///
+    /// `SHT_NOBITS` sections keep their `sh_size` and occupy no space in the file.
+    /// Virtual addresses and segments are kept as is.
+    /// Use `layout::Layout` to lay out executables and shared objects.
+    pub fn condition(&mut self) {
+        // 内容の後ろに置く場合は失敗しない
+        let _ = self.condition_with(layout::ShtPlacement::AfterContents);
+    }
 
+    /// `condition()` placing the section header table by `placement`.
+    ///
+    /// `ShtPlacement::FixedOffset` fails if the table overlaps the headers or the contents.
+    /// With `ShtPlacement::Omit`, `e_shstrndx` is cleared and `to_le_bytes()` emits no section headers.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use elf_utilities::{file, layout, section};
+    ///
+    /// let mut elf = file::ELF64::default();
+    /// elf.add_section(section::Section64::new(
+    ///     ".data".to_string(),
+    ///     section::ShdrPreparation64::default().ty(section::Type::ProgBits),
+    ///     section::Contents64::Raw(vec![0; 0x10]),
+    /// ));
+    ///
+    /// elf.condition_with(layout::ShtPlacement::AfterHeader).unwrap();
+    /// // ヘッダの直後にセクションヘッダテーブルがあり，その後ろに内容が続く
+    /// assert_eq!(0x40, elf.ehdr.e_shoff);
+    /// assert!(elf.sections[1].header.sh_offset >= 0x40 + 3 * 0x40);
+    ///
+    /// assert!(elf.condition_with(layout::ShtPlacement::FixedOffset(0x48)).is_err());
+    ///
+    /// elf.condition_with(layout::ShtPlacement::Omit).unwrap();
+    /// assert_eq!(0, elf.ehdr.e_shnum);
+    /// ```
+    pub fn condition_with(
+        &mut self,
+        placement: layout::ShtPlacement,
+    ) -> Result<(), layout::LayoutError> {
+        self.invalidate_address_index();
+        self.rebuild_shstrtab();
+
+        let pht_end =
+            header::Ehdr64::SIZE as u64 + segment::Phdr64::SIZE as u64 * self.segments.len() as u64;
+        let sht_size = section::Shdr64::SIZE as u64 * self.sections.len() as u64;
+        let mut file_offset = placement.contents_start(pht_end, sht_size, 8);
+
+        for sct in self.sections.iter_mut().skip(1) {
+            let is_nobits = sct.header.get_type() == section::Type::NoBits;
+            if !is_nobits {
+                sct.header.sh_size = sct.contents.size() as u64;
+            }
+
+            // NOBITSはファイル上の領域を持たないので，次のセクションの位置に影響しない
+            if !is_nobits {
+                file_offset = layout::align_up(file_offset, sct.header.sh_addralign);
+            }
+            sct.header.sh_offset = file_offset;
+            if !is_nobits {
+                file_offset += sct.header.sh_size;
+            }
+        }
+
+        // PHTが無ければe_phoffは0にする
+        self.ehdr.e_phoff = if self.segments.is_empty() {
+            0
+        } else {
+            header::Ehdr64::SIZE as u64
+        };
+        self.ehdr.e_phnum = self.segments.len() as u16;
+        placement.finish(self, pht_end, file_offset, sht_size, 8)
+    }
+
+    /// rebuild the section header string table(`e_shstrndx`) from `Section64::name`,
+    /// and assign `sh_name` of every section.
+    ///
+    /// Duplicate names share the same string.
+    /// Nothing is done if the file has no section header string table,
+    /// or the table is shared with the symbol names, as LLVM emits in objects.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use elf_utilities::{file, section};
+    ///
+    /// let mut elf = file::ELF64::default();
+    /// for name in [".text", ".data", ".text"].iter() {
+    ///     elf.add_section(section::Section64::new(
+    ///         name.to_string(),
+    ///         section::ShdrPreparation64::default().ty(section::Type::ProgBits),
+    ///         section::Contents64::Raw(vec![0x90]),
+    ///     ));
+    /// }
+    /// elf.sections.swap(1, 2);
+    /// elf.rebuild_shstrtab();
+    ///
+    /// assert_eq!(1, elf.sections[1].header.sh_name);
+    /// assert_eq!(7, elf.sections[2].header.sh_name);
+    /// assert_eq!(7, elf.sections[3].header.sh_name);
+    /// ```
+    pub fn rebuild_shstrtab(&mut self) {
+        let shstrndx = self.ehdr.e_shstrndx as usize;
+        if shstrndx == 0 || shstrndx >= self.sections.len() {
+            return;
+        }
+        // シンボル名の文字列表を兼ねていれば，既存のsh_nameも有効
+        let shared = self.sections.iter().any(|sct| {
+            matches!(
+                sct.header.get_type(),
+                section::Type::SymTab | section::Type::DynSym
+            ) && sct.header.sh_link as usize == shstrndx
+        });
+        if shared {
+            return;
+        }
+
+        let mut shstrtab = StringTable::new();
+        for sct in self.sections.iter_mut().skip(1) {
+            sct.header.sh_name = shstrtab.add(&sct.name) as u32;
+        }
+        self.sections[shstrndx].contents = shstrtab.to_contents64();
+    }
+
+    /// Create Vec<u8> from this.
+    /// Each table and section is placed at the offset written in its header.
+    /// The section header table is omitted if `e_shoff` is 0.
+    pub fn to_le_bytes(&self) -> Vec<u8> {
+        let mut file_binary: Vec<u8> = self.ehdr.to_le_bytes();
+
+        let mut pht_binary = Vec::new();
         for seg in self.segments.iter() {
-            let mut phdr_binary = seg.header.to_le_bytes();
-            file_binary.append(&mut phdr_binary);
+            pht_binary.append(&mut seg.header.to_le_bytes());
         }
+        write_at(&mut file_binary, self.ehdr.e_phoff, &pht_binary);
 
         for sct in self.sections.iter() {
-            let mut section_binary = sct.to_le_bytes();
-            file_binary.append(&mut section_binary);
+            if sct.header.get_type() == section::Type::NoBits {
+                continue;
+            }
+            write_at(&mut file_binary, sct.header.sh_offset, &sct.to_le_bytes());
         }
 
-        for sct in self.sections.iter() {
-            let mut shdr_binary = sct.header.to_le_bytes();
-            file_binary.append(&mut shdr_binary);
+        // e_shoffが0ならセクションヘッダテーブルは存在しない
+        if self.ehdr.e_shoff != 0 {
+            let mut sht_binary = Vec::new();
+            for sct in self.sections.iter() {
+                sht_binary.append(&mut sct.header.to_le_bytes());
+            }
+            write_at(&mut file_binary, self.ehdr.e_shoff, &sht_binary);
         }
+
         file_binary
     }
 
     /// sh_nameやsh_offset等の調整
     fn fill_elf_info(&mut self, new_sct: &mut Section64, prev_sct_idx: usize) {
-        let shstrtab_len = self.sections[self.ehdr.e_shstrndx as usize].contents.size() as usize;
+        let shstrtab_len = self.sections[self.ehdr.e_shstrndx as usize].contents.size();
         let prev_offset = self.sections[prev_sct_idx].header.sh_offset;
-        let prev_size = self.sections[prev_sct_idx].header.sh_size;
+        let prev_size = if self.sections[prev_sct_idx].header.get_type() == section::Type::NoBits {
+            0
+        } else {
+            self.sections[prev_sct_idx].header.sh_size
+        };
 
         // <prev_section_name> の後に0x00が入るので，+1
         new_sct.header.sh_name = shstrtab_len as u32 + 1;
@@ -156,7 +435,7 @@ impl ELF64 {
             self.sections[self.ehdr.e_shstrndx as usize].contents
         {
             tab.push(StrTabEntry {
-                v: new_sct.name.clone(),
+                v: new_sct.name.to_string(),
                 idx: shstrtab_len + 1,
             });
         }
@@ -172,6 +451,21 @@ impl ELF64 {
             new_sct.header.sh_offset = prev_offset + prev_size;
         }
 
-        new_sct.header.sh_size = new_sct.contents.size() as u64;
+        // NOBITSのサイズはヘッダに指定されたものを使う
+        if new_sct.header.get_type() != section::Type::NoBits {
+            new_sct.header.sh_size = new_sct.contents.size() as u64;
+        }
+    }
+}
+
+fn write_at(buf: &mut Vec<u8>, offset: u64, bytes: &[u8]) {
+    if bytes.is_empty() {
+        return;
+    }
+
+    let start = offset as usize;
+    if buf.len() < start + bytes.len() {
+        buf.resize(start + bytes.len(), 0x00);
     }
+    buf[start..start + bytes.len()].copy_from_slice(bytes);
 }
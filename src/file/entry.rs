@@ -0,0 +1,116 @@
+//! Setting the entry point by symbols.
+
+use crate::*;
+use thiserror::Error as TError;
+
+#[derive(TError, Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum EntryError {
+    #[error("`{name}` is not defined in the file")]
+    Undefined { name: String },
+    #[error("`{name}` is in `{section}`, which is not executable")]
+    NotExecutable { name: String, section: String },
+}
+
+impl file::ELF64 {
+    /// set `e_entry` to the address of the symbol `name`, and return it.
+    ///
+    /// `.symtab` is searched first, then `.dynsym`, so stripped files can use their exported functions.
+    /// The symbol must be defined in a section with `SHF_EXECINSTR`;
+    /// absolute and common symbols are refused too.
+    /// In `ET_REL`, `st_value` is an offset in the section, so `sh_addr` of the section is added.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use elf_utilities::{builder, file};
+    ///
+    /// let mut elf = builder::ExecutableWriter::new()
+    ///     .function(builder::ExportedFunction::new("_start", vec![0xc3]))
+    ///     .function(builder::ExportedFunction::new("_start2", vec![0xc3]))
+    ///     .build()
+    ///     .unwrap();
+    ///
+    /// let entry = elf.set_entry_symbol("_start2").unwrap();
+    /// assert_eq!(entry, elf.ehdr.e_entry);
+    /// assert!(matches!(
+    ///     elf.set_entry_symbol("main"),
+    ///     Err(file::EntryError::Undefined { .. })
+    /// ));
+    /// // 失敗してもe_entryは変わらない
+    /// assert_eq!(entry, elf.ehdr.e_entry);
+    /// ```
+    pub fn set_entry_symbol(&mut self, name: &str) -> Result<Elf64Addr, EntryError> {
+        let sym = self
+            .symtab()
+            .and_then(|symtab| symtab.find_defined(name))
+            .or_else(|| self.dynsym().and_then(|dynsym| dynsym.find_defined(name)))
+            .ok_or_else(|| EntryError::Undefined {
+                name: name.to_string(),
+            })?;
+
+        let not_executable = |section: &str| EntryError::NotExecutable {
+            name: name.to_string(),
+            section: section.to_string(),
+        };
+        let sct = match sym.st_shndx {
+            section::SHN_ABS => return Err(not_executable("ABS")),
+            section::SHN_COMMON => return Err(not_executable("COMMON")),
+            shndx => self
+                .sections
+                .get(shndx as usize)
+                .ok_or_else(|| not_executable(&format!("[{}]", shndx)))?,
+        };
+        if sct.header.sh_flags & Elf64Xword::from(section::Flag::ExecInstr) == 0 {
+            return Err(not_executable(&sct.name));
+        }
+
+        let addr = if self.ehdr.get_type() == header::Type::Rel {
+            sct.header.sh_addr + sym.st_value
+        } else {
+            sym.st_value
+        };
+        self.ehdr.e_entry = addr;
+        Ok(addr)
+    }
+}
+
+#[cfg(test)]
+mod entry_tests {
+    use super::*;
+
+    #[test]
+    fn set_entry_symbol_test() {
+        let main = crate::builder::ExportedFunction::new("main", vec![0x31, 0xc0, 0xc3]);
+        let mut elf = crate::builder::ExecutableWriter::new()
+            .function(main)
+            .start_stub(crate::builder::start_stub("main"))
+            .build()
+            .unwrap();
+        let text = elf.first_section_by(|sct| sct.name == ".text").unwrap();
+        let (start, end) = (
+            text.header.sh_addr,
+            text.header.sh_addr + text.header.sh_size,
+        );
+
+        let entry = elf.set_entry_symbol("main").unwrap();
+        assert!(start < entry && entry < end);
+        assert_eq!(entry, elf.ehdr.e_entry);
+
+        // 実行できないセクションのシンボルは拒否する
+        let symtab = elf.first_shidx_by(|sct| sct.name == ".symtab").unwrap();
+        let strtab = elf.first_shidx_by(|sct| sct.name == ".strtab").unwrap() as u16;
+        if let section::Contents64::Symbols(syms) = &mut elf.sections[symtab].contents {
+            for sym in syms.iter_mut().filter(|sym| sym.symbol_name == "main") {
+                sym.st_shndx = strtab;
+            }
+        }
+        assert_eq!(
+            Err(EntryError::NotExecutable {
+                name: "main".to_string(),
+                section: ".strtab".to_string()
+            }),
+            elf.set_entry_symbol("main")
+        );
+        assert_eq!(entry, elf.ehdr.e_entry);
+    }
+}
@@ -0,0 +1,283 @@
+//! Converting files between `ET_REL`, `ET_EXEC` and `ET_DYN`.
+
+use std::fmt;
+
+use crate::*;
+use thiserror::Error as TError;
+
+#[derive(TError, Debug)]
+pub enum FileTypeError {
+    #[error("`{ty}` is neither REL, EXEC nor DYN")]
+    Unsupported { ty: header::Type },
+    #[error("the file can't be converted to `{to}` => `{}`", blockers[0])]
+    Blocked {
+        to: header::Type,
+        blockers: Vec<FileTypeBlocker>,
+    },
+}
+
+/// A reason why `ELF64::set_file_type()` refuses the conversion
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum FileTypeBlocker {
+    /// linked files need `PT_LOAD` segments, which are made by `layout::Layout`
+    NoLoadSegments,
+    /// the relocations in `section` are for the linker and aren't applied yet
+    UnappliedRelocations { section: String },
+    /// the symbol must be defined by the linker
+    UndefinedSymbol { name: String },
+    /// the file can't be loaded at a random address
+    NotPositionIndependent(analysis::PieBlocker),
+    /// `ET_EXEC` is mapped at its addresses, but the first `PT_LOAD` starts at `vaddr`
+    LowBase { vaddr: Elf64Addr },
+    /// linked files can't be linked again, since the linker has dropped the relocations
+    AlreadyLinked,
+}
+
+impl fmt::Display for FileTypeBlocker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoLoadSegments => write!(f, "no PT_LOAD segments, lay out the file first"),
+            Self::UnappliedRelocations { section } => {
+                write!(
+                    f,
+                    "relocations in `{}` must be applied by a linker",
+                    section
+                )
+            }
+            Self::UndefinedSymbol { name } => write!(f, "`{}` is undefined", name),
+            Self::NotPositionIndependent(blocker) => write!(f, "{}", blocker),
+            Self::LowBase { vaddr } => write!(
+                f,
+                "the first PT_LOAD at {:#x} is below the lowest mappable address",
+                vaddr
+            ),
+            Self::AlreadyLinked => write!(f, "linked files can't be turned into object files"),
+        }
+    }
+}
+
+/// What remains to be done, or was done, after `ELF64::set_file_type()`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum FileTypeFollowUp {
+    /// `DF_1_PIE` in `DT_FLAGS_1` was set or cleared to match the new type
+    PieFlagUpdated { set: bool },
+    /// `e_entry` is 0, but executables start from it
+    EntryUnset,
+    /// the file has `.dynamic` but no `PT_INTERP`, so nothing processes it when executed
+    NoInterpreter,
+}
+
+/// the lowest address `ET_EXEC` can be mapped at(the default `vm.mmap_min_addr` of Linux).
+const MIN_EXEC_BASE: Elf64Addr = 0x10000;
+
+impl file::ELF64 {
+    /// change `e_type`, checking that the file makes sense as the new type.
+    ///
+    /// The conversion is refused with all of the blockers found, leaving the file unchanged:
+    ///
+    /// - `ET_REL` becomes linked only if it has `PT_LOAD` segments, no relocations for the linker
+    ///   and no undefined symbols.
+    /// - `ET_EXEC` becomes `ET_DYN` only if `analysis::pie_blockers()` finds nothing.
+    /// - `ET_DYN` becomes `ET_EXEC` only if it's linked at a mappable address.
+    /// - linked files never become `ET_REL`.
+    ///
+    /// `DF_1_PIE` is updated, and the rest which must be fixed by the caller is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use elf_utilities::{builder, file, header, parser};
+    ///
+    /// let mut elf = builder::ExecutableWriter::new()
+    ///     .function(builder::ExportedFunction::new("_start", vec![0xc3]))
+    ///     .build()
+    ///     .unwrap();
+    /// assert!(elf.set_file_type(header::Type::Dyn).unwrap().is_empty());
+    /// assert_eq!(header::Type::Dyn, elf.ehdr.get_type());
+    ///
+    /// // PIEは0番地からリンクされているので，そのままでは固定アドレスに置けない
+    /// let mut pie = parser::parse_elf64("src/parser/testdata/sample").unwrap();
+    /// match pie.set_file_type(header::Type::Exec) {
+    ///     Err(file::FileTypeError::Blocked { blockers, .. }) => {
+    ///         assert_eq!(vec![file::FileTypeBlocker::LowBase { vaddr: 0 }], blockers)
+    ///     }
+    ///     _ => unreachable!(),
+    /// }
+    /// assert_eq!(header::Type::Dyn, pie.ehdr.get_type());
+    /// ```
+    pub fn set_file_type(
+        &mut self,
+        ty: header::Type,
+    ) -> Result<Vec<FileTypeFollowUp>, FileTypeError> {
+        let from = self.ehdr.get_type();
+        for t in [from, ty].iter() {
+            if !matches!(
+                t,
+                header::Type::Rel | header::Type::Exec | header::Type::Dyn
+            ) {
+                return Err(FileTypeError::Unsupported { ty: *t });
+            }
+        }
+        if from == ty {
+            return Ok(Vec::new());
+        }
+
+        let blockers = self.file_type_blockers(from, ty);
+        if !blockers.is_empty() {
+            return Err(FileTypeError::Blocked { to: ty, blockers });
+        }
+        self.ehdr.set_elf_type(ty);
+
+        let mut follow_ups = Vec::new();
+        // 共有ライブラリはエントリポイントを持たなくてよい
+        let executable = ty == header::Type::Exec || from == header::Type::Exec;
+        if executable && self.ehdr.e_entry == 0 {
+            follow_ups.push(FileTypeFollowUp::EntryUnset);
+        }
+        let has_dynamic = self
+            .segments
+            .iter()
+            .any(|sgt| sgt.header.get_type() == segment::Type::Dynamic);
+        if executable && has_dynamic && self.interpreter().is_none() {
+            follow_ups.push(FileTypeFollowUp::NoInterpreter);
+        }
+
+        let pie = dynamic::Flag::PIE1.to_bytes();
+        if let Some(section::Contents64::Dynamics(entries)) = self
+            .first_mut_section_by(|sct| sct.header.get_type() == section::Type::Dynamic)
+            .map(|sct| &mut sct.contents)
+        {
+            let set = ty == header::Type::Dyn && from == header::Type::Exec;
+            for ent in entries.iter_mut() {
+                if ent.get_type() != dynamic::EntryType::Flags1 || (ent.d_un & pie != 0) == set {
+                    continue;
+                }
+                if set {
+                    ent.d_un |= pie;
+                } else {
+                    ent.d_un &= !pie;
+                }
+                follow_ups.push(FileTypeFollowUp::PieFlagUpdated { set });
+            }
+        }
+        Ok(follow_ups)
+    }
+
+    fn file_type_blockers(&self, from: header::Type, to: header::Type) -> Vec<FileTypeBlocker> {
+        if to == header::Type::Rel {
+            return vec![FileTypeBlocker::AlreadyLinked];
+        }
+
+        let mut blockers = Vec::new();
+        let first_load = self
+            .segments
+            .iter()
+            .filter(|sgt| sgt.header.get_type() == segment::Type::Load)
+            .map(|sgt| sgt.header.p_vaddr)
+            .min();
+        if first_load.is_none() {
+            blockers.push(FileTypeBlocker::NoLoadSegments);
+        }
+
+        if from == header::Type::Rel {
+            for sct in self.sections.iter() {
+                let is_reloc = matches!(
+                    sct.header.get_type(),
+                    section::Type::Rela | section::Type::Rel
+                );
+                // SHF_ALLOCなリロケーションは動的リンカが処理する
+                if is_reloc && !layout::is_alloc(&sct.header) && sct.header.sh_info != 0 {
+                    blockers.push(FileTypeBlocker::UnappliedRelocations {
+                        section: sct.name.to_string(),
+                    });
+                }
+            }
+            for sct in self.sections.iter() {
+                let syms = match &sct.contents {
+                    section::Contents64::Symbols(syms)
+                        if sct.header.get_type() == section::Type::SymTab =>
+                    {
+                        syms
+                    }
+                    _ => continue,
+                };
+                for sym in syms.iter().skip(1) {
+                    // 未定義の弱いシンボルは0に解決される
+                    if sym.st_shndx == 0 && sym.get_bind() != symbol::Bind::Weak {
+                        blockers.push(FileTypeBlocker::UndefinedSymbol {
+                            name: sym.symbol_name.to_string(),
+                        });
+                    }
+                }
+            }
+        }
+
+        if from == header::Type::Exec && to == header::Type::Dyn {
+            blockers.extend(
+                analysis::pie_blockers(self)
+                    .into_iter()
+                    .map(FileTypeBlocker::NotPositionIndependent),
+            );
+        }
+        if to == header::Type::Exec {
+            if let Some(vaddr) = first_load.filter(|vaddr| *vaddr < MIN_EXEC_BASE) {
+                blockers.push(FileTypeBlocker::LowBase { vaddr });
+            }
+        }
+        blockers
+    }
+}
+
+#[cfg(test)]
+mod file_type_tests {
+    use super::*;
+
+    #[test]
+    fn set_file_type_test() {
+        let mut elf = crate::builder::ExecutableWriter::new()
+            .function(crate::builder::ExportedFunction::new("_start", vec![0xc3]))
+            .build()
+            .unwrap();
+        let err = elf.set_file_type(header::Type::Rel);
+        assert!(matches!(
+            err,
+            Err(FileTypeError::Blocked { blockers, .. }) if blockers == vec![FileTypeBlocker::AlreadyLinked]
+        ));
+        assert!(matches!(
+            elf.set_file_type(header::Type::Core),
+            Err(FileTypeError::Unsupported { .. })
+        ));
+
+        // エントリポイントが無ければ呼び出し側で設定する必要がある
+        elf.set_file_type(header::Type::Dyn).unwrap();
+        elf.ehdr.e_entry = 0;
+        assert_eq!(
+            vec![FileTypeFollowUp::EntryUnset],
+            elf.set_file_type(header::Type::Exec).unwrap()
+        );
+
+        let mut obj = file::ELF64::default();
+        obj.ehdr.set_elf_type(header::Type::Rel);
+        let mut undef = symbol::Symbol64::new_null_symbol();
+        undef.symbol_name = "puts".into();
+        undef.set_info(symbol::Type::Func, symbol::Bind::Global);
+        obj.add_section(section::Section64::new(
+            ".symtab".to_string(),
+            section::ShdrPreparation64::default().ty(section::Type::SymTab),
+            section::Contents64::Symbols(vec![symbol::Symbol64::new_null_symbol(), undef]),
+        ));
+        match obj.set_file_type(header::Type::Exec) {
+            Err(FileTypeError::Blocked { blockers, .. }) => assert_eq!(
+                vec![
+                    FileTypeBlocker::NoLoadSegments,
+                    FileTypeBlocker::UndefinedSymbol {
+                        name: "puts".to_string()
+                    },
+                ],
+                blockers
+            ),
+            _ => unreachable!(),
+        }
+        assert_eq!(header::Type::Rel, obj.ehdr.get_type());
+    }
+}
@@ -0,0 +1,540 @@
+//! Garbage collection of unreferenced sections in relocatable files.
+
+use crate::analysis::Xref;
+use crate::*;
+use thiserror::Error as TError;
+
+#[derive(TError, Debug)]
+pub enum GcError {
+    #[error("gc_sections() supports only relocatable files")]
+    NotRelocatable,
+    #[error("root symbol `{name}` is not defined")]
+    UnknownRoot { name: String },
+}
+
+impl file::ELF64 {
+    /// remove the sections which are unreachable from `roots`, like `ld --gc-sections`.
+    ///
+    /// Reachability is computed on `analysis::xrefs()`.
+    /// Non-allocated sections(except relocations of removed sections), notes and
+    /// `.init_array`/`.fini_array`/`.preinit_array` are always kept.
+    /// `.eh_frame` is kept too, but its references don't keep the functions alive.
+    /// Like `ld --gc-sections`, the LSDA of an FDE and the personality of its CIE are kept
+    /// while the function of the FDE is live, and the FDEs of the removed functions are removed.
+    /// Symbols defined in the removed sections are removed too, with the relocations against them,
+    /// and the other relocations, `sh_link`/`sh_info` and section groups are rewritten.
+    /// Finally `condition()` is called, and the names of the removed sections are returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use elf_utilities::{file, header, section, symbol};
+    ///
+    /// let mut elf = file::ELF64::default();
+    /// elf.ehdr.set_elf_type(header::Type::Rel);
+    /// let mut syms = vec![symbol::Symbol64::new_null_symbol()];
+    /// for (i, name) in ["main", "unused"].iter().enumerate() {
+    ///     elf.add_section(section::Section64::new(
+    ///         format!(".text.{}", name),
+    ///         section::ShdrPreparation64::default()
+    ///             .ty(section::Type::ProgBits)
+    ///             .flags([section::Flag::Alloc, section::Flag::ExecInstr].iter()),
+    ///         section::Contents64::Raw(vec![0xc3]),
+    ///     ));
+    ///     let mut sym = symbol::Symbol64 {
+    ///         symbol_name: (*name).into(),
+    ///         st_shndx: i as u16 + 1,
+    ///         st_size: 1,
+    ///         ..Default::default()
+    ///     };
+    ///     sym.set_info(symbol::Type::Func, symbol::Bind::Global);
+    ///     syms.push(sym);
+    /// }
+    /// elf.add_section(section::Section64::new(
+    ///     ".symtab".to_string(),
+    ///     section::ShdrPreparation64::default().ty(section::Type::SymTab).info(1),
+    ///     section::Contents64::Symbols(syms),
+    /// ));
+    ///
+    /// let removed = elf.gc_sections(&["main"]).unwrap();
+    /// assert_eq!(vec![".text.unused".to_string()], removed);
+    /// assert!(elf.first_section_by(|sct| sct.name == ".text.unused").is_none());
+    /// ```
+    pub fn gc_sections(&mut self, roots: &[&str]) -> Result<Vec<String>, GcError> {
+        if self.ehdr.get_type() != header::Type::Rel {
+            return Err(GcError::NotRelocatable);
+        }
+
+        let live = self.live_sections(roots)?;
+        let removed: Vec<String> = self
+            .sections
+            .iter()
+            .zip(live.iter())
+            .filter(|(_, live)| !**live)
+            .map(|(sct, _)| sct.name.to_string())
+            .collect();
+        if removed.is_empty() {
+            return Ok(removed);
+        }
+
+        self.prune_eh_frame(&live);
+        self.remove_dead_symbols(&live);
+
+        // 削除されるセクションへの参照は0(SHN_UNDEF)になる
+        let mut new_indices = Vec::with_capacity(live.len());
+        let mut next = 0;
+        for l in live.iter() {
+            if *l {
+                new_indices.push(next);
+                next += 1;
+            } else {
+                new_indices.push(0);
+            }
+        }
+        self.remap_section_indices(|idx| new_indices.get(idx).copied().unwrap_or(0));
+        let mut live_iter = live.iter();
+        self.sections.retain(|_| *live_iter.next().unwrap());
+        self.condition();
+
+        Ok(removed)
+    }
+
+    fn live_sections(&self, roots: &[&str]) -> Result<Vec<bool>, GcError> {
+        let syms: &[symbol::Symbol64] = match self
+            .first_section_by(|sct| sct.header.get_type() == section::Type::SymTab)
+            .map(|sct| &sct.contents)
+        {
+            Some(section::Contents64::Symbols(syms)) => syms,
+            _ => &[],
+        };
+        let defined_in = |name: &str| {
+            syms.iter()
+                .find(|sym| {
+                    sym.symbol_name == name
+                        && sym.st_shndx != section::SHN_UNDEF
+                        && sym.st_shndx < section::SHN_LORESERVE
+                })
+                .map(|sym| sym.st_shndx as usize)
+        };
+        let section_of = |node: &Xref| match node {
+            Xref::Symbol(name) => defined_in(name),
+            Xref::Section(name) => self.first_shidx_by(|sct| &sct.name == name),
+        };
+
+        let mut live = vec![false; self.sections.len()];
+        let mut queue = vec![0];
+        for name in roots.iter() {
+            let shndx = defined_in(name).ok_or_else(|| GcError::UnknownRoot {
+                name: name.to_string(),
+            })?;
+            queue.push(shndx);
+        }
+        for (idx, sct) in self.sections.iter().enumerate() {
+            if always_kept(&sct.header) {
+                queue.push(idx);
+            }
+        }
+
+        let graph = analysis::xrefs(self);
+        let fdes = EhFrame::load(self).map_or_else(Vec::new, |eh| eh.fde_references());
+        loop {
+            while let Some(idx) = queue.pop() {
+                if idx >= live.len() || live[idx] {
+                    continue;
+                }
+                live[idx] = true;
+                for (from, targets) in graph.edges.iter() {
+                    if section_of(from) == Some(idx) {
+                        queue.extend(targets.iter().filter_map(&section_of));
+                    }
+                }
+            }
+            // 関数が残るFDEのLSDAとパーソナリティは残す
+            for (function, refs) in fdes.iter() {
+                if live[*function] {
+                    queue.extend(refs.iter().filter(|idx| !live[**idx]));
+                }
+            }
+            if queue.is_empty() {
+                break;
+            }
+        }
+        // .eh_frameは残すが，そこからの参照では関数を残さない
+        for (idx, sct) in self.sections.iter().enumerate() {
+            if sct.name == EH_FRAME {
+                live[idx] = true;
+            }
+        }
+
+        // リロケーションセクションは対象が残る場合のみ，グループは一つでもメンバが残る場合のみ残す
+        for (idx, sct) in self.sections.iter().enumerate() {
+            let ty = sct.header.get_type();
+            if ty == section::Type::Rela || ty == section::Type::Rel {
+                live[idx] = live
+                    .get(sct.header.sh_info as usize)
+                    .copied()
+                    .unwrap_or(false);
+            } else if ty == section::Type::Group {
+                if let section::Contents64::Raw(bytes) = &sct.contents {
+                    live[idx] = bytes.chunks_exact(4).skip(1).any(|member| {
+                        let member =
+                            u32::from_le_bytes([member[0], member[1], member[2], member[3]]);
+                        live.get(member as usize).copied().unwrap_or(false)
+                    });
+                }
+            }
+        }
+
+        Ok(live)
+    }
+
+    /// remove the FDEs of dead functions from `.eh_frame`, and fix the CIE pointers and the relocations.
+    fn prune_eh_frame(&mut self, live: &[bool]) {
+        let eh = match EhFrame::load(self) {
+            Some(eh) => eh,
+            None => return,
+        };
+        // FDEはpc_beginの指す関数でのみ判断する
+        let dead_records: Vec<bool> = (0..eh.records.len())
+            .map(|k| {
+                eh.function_of(k)
+                    .is_some_and(|idx| !live.get(idx).copied().unwrap_or(true))
+            })
+            .collect();
+        let records = &eh.records;
+        let bytes = &eh.bytes;
+
+        let mut new_bytes = Vec::with_capacity(bytes.len());
+        let mut new_starts = vec![0; records.len()];
+        for (k, r) in records.iter().enumerate() {
+            new_starts[k] = new_bytes.len();
+            if dead_records[k] {
+                continue;
+            }
+            new_bytes.extend_from_slice(&bytes[r.start..r.end]);
+            // CIEポインタはこのフィールドからCIEまでの距離
+            if let Some(cie) = r
+                .cie
+                .and_then(|cie| records.iter().position(|c| c.start == cie))
+            {
+                let pointer = (new_starts[k] + 4 - new_starts[cie]) as u32;
+                new_bytes[new_starts[k] + 4..new_starts[k] + 8]
+                    .copy_from_slice(&pointer.to_le_bytes());
+            }
+        }
+        new_bytes.extend_from_slice(&bytes[records.last().map_or(0, |r| r.end)..]);
+
+        let new_relas = eh
+            .relas
+            .iter()
+            .filter_map(|rela| match eh.record_of(rela.get_offset()) {
+                Some(k) if dead_records[k] => None,
+                Some(k) => {
+                    let mut rela = *rela;
+                    let offset = rela.get_offset() as usize - records[k].start + new_starts[k];
+                    rela.set_offset(offset as Elf64Addr);
+                    Some(rela)
+                }
+                None => Some(*rela),
+            })
+            .collect();
+        self.sections[eh.eh_idx].contents = section::Contents64::Raw(new_bytes);
+        self.sections[eh.rela_idx].contents = section::Contents64::RelaSymbols(new_relas);
+    }
+
+    /// remove the symbols defined in dead sections and rewrite the relocations against the symbol tables.
+    fn remove_dead_symbols(&mut self, live: &[bool]) {
+        let symtabs: Vec<usize> = self
+            .sections
+            .iter()
+            .enumerate()
+            .filter(|(_, sct)| matches!(sct.contents, section::Contents64::Symbols(_)))
+            .map(|(idx, _)| idx)
+            .collect();
+
+        for symtab in symtabs {
+            let mut table = match symbol::SymbolTable::from_section(&self.sections[symtab]) {
+                Some(table) => table,
+                None => continue,
+            };
+            table.retain(|sym| {
+                let shndx = sym.st_shndx;
+                shndx == section::SHN_UNDEF
+                    || shndx >= section::SHN_LORESERVE
+                    || live.get(shndx as usize).copied().unwrap_or(true)
+            });
+            // 削除されたシンボルへのリロケーション(.eh_frameのFDE等)は死んだコードを指すので捨てる
+            self.update_symbol_table(symtab, table);
+        }
+    }
+}
+
+/// unwind information, which is kept without keeping the functions it describes
+const EH_FRAME: &str = ".eh_frame";
+
+/// `.eh_frame` with its relocations
+struct EhFrame {
+    eh_idx: usize,
+    rela_idx: usize,
+    bytes: Vec<u8>,
+    records: Vec<EhFrameRecord>,
+    syms: Vec<symbol::Symbol64>,
+    relas: Vec<relocation::Rela64>,
+}
+
+impl EhFrame {
+    fn load(elf: &file::ELF64) -> Option<Self> {
+        let eh_idx = elf.first_shidx_by(|sct| sct.name == EH_FRAME)?;
+        let rela_idx = elf.first_shidx_by(|sct| {
+            sct.header.get_type() == section::Type::Rela && sct.header.sh_info as usize == eh_idx
+        })?;
+        let bytes = match &elf.sections[eh_idx].contents {
+            section::Contents64::Raw(bytes) => bytes.clone(),
+            _ => return None,
+        };
+        let records = eh_frame_records(&bytes)?;
+        let link = elf.sections[rela_idx].header.sh_link as usize;
+        let syms = match &elf.sections.get(link)?.contents {
+            section::Contents64::Symbols(syms) => syms.clone(),
+            _ => return None,
+        };
+        let relas = match &elf.sections[rela_idx].contents {
+            section::Contents64::RelaSymbols(relas) => relas.clone(),
+            _ => return None,
+        };
+        Some(Self {
+            eh_idx,
+            rela_idx,
+            bytes,
+            records,
+            syms,
+            relas,
+        })
+    }
+
+    fn record_of(&self, offset: Elf64Addr) -> Option<usize> {
+        self.records
+            .iter()
+            .position(|r| r.start <= offset as usize && (offset as usize) < r.end)
+    }
+
+    /// the section which the relocation refers to, if defined in the file.
+    fn target_of(&self, rela: &relocation::Rela64) -> Option<usize> {
+        let shndx = self.syms.get(rela.get_sym() as usize)?.st_shndx;
+        if shndx == section::SHN_UNDEF || shndx >= section::SHN_LORESERVE {
+            return None;
+        }
+        Some(shndx as usize)
+    }
+
+    /// the function section of the FDE, by the relocation of its pc_begin.
+    fn function_of(&self, k: usize) -> Option<usize> {
+        let r = &self.records[k];
+        r.cie?;
+        // length, CIEポインタの次がpc_begin
+        let pc_begin = (r.start + 8) as Elf64Addr;
+        self.relas
+            .iter()
+            .find(|rela| rela.get_offset() == pc_begin)
+            .and_then(|rela| self.target_of(rela))
+    }
+
+    /// the function section of each FDE with the other sections the FDE and its CIE refer to,
+    /// i.e. the LSDA and the personality.
+    fn fde_references(&self) -> Vec<(usize, Vec<usize>)> {
+        let refs_in = |k: usize| {
+            self.relas
+                .iter()
+                .filter(|rela| self.record_of(rela.get_offset()) == Some(k))
+                .filter_map(|rela| self.target_of(rela))
+                .collect::<Vec<usize>>()
+        };
+        (0..self.records.len())
+            .filter_map(|k| {
+                let function = self.function_of(k)?;
+                let mut refs = refs_in(k);
+                if let Some(cie) = self.records[k]
+                    .cie
+                    .and_then(|cie| self.records.iter().position(|c| c.start == cie))
+                {
+                    refs.extend(refs_in(cie));
+                }
+                refs.retain(|idx| *idx != function);
+                Some((function, refs))
+            })
+            .collect()
+    }
+}
+
+/// a CIE or an FDE in `.eh_frame`
+struct EhFrameRecord {
+    start: usize,
+    end: usize,
+    /// the offset of the CIE if the record is an FDE
+    cie: Option<usize>,
+}
+
+/// split `.eh_frame` into records.
+/// returns `None` if the section contains 64-bit records or is broken.
+fn eh_frame_records(bytes: &[u8]) -> Option<Vec<EhFrameRecord>> {
+    let read_u32 = |off: usize| {
+        bytes
+            .get(off..off + 4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize)
+    };
+
+    let mut records = Vec::new();
+    let mut off = 0;
+    while off + 4 <= bytes.len() {
+        let length = read_u32(off)?;
+        // 長さ0は終端
+        if length == 0 {
+            break;
+        }
+        if length == 0xffffffff {
+            return None;
+        }
+        let end = off + 4 + length;
+        let id = read_u32(off + 4)?;
+        if end > bytes.len() {
+            return None;
+        }
+        let cie = if id == 0 {
+            None
+        } else {
+            Some((off + 4).checked_sub(id)?)
+        };
+        records.push(EhFrameRecord {
+            start: off,
+            end,
+            cie,
+        });
+        off = end;
+    }
+    Some(records)
+}
+
+fn always_kept(shdr: &section::Shdr64) -> bool {
+    match shdr.get_type() {
+        section::Type::Null
+        | section::Type::Note
+        | section::Type::InitArray
+        | section::Type::FiniArray
+        | section::Type::PreInitArray => true,
+        section::Type::Rela | section::Type::Rel | section::Type::Group => false,
+        _ => !layout::is_alloc(shdr),
+    }
+}
+
+#[cfg(test)]
+mod gc_tests {
+    use super::*;
+
+    fn add_text(elf: &mut file::ELF64, name: &str) {
+        elf.add_section(section::Section64::new(
+            name.to_string(),
+            section::ShdrPreparation64::default()
+                .ty(section::Type::ProgBits)
+                .flags([section::Flag::Alloc, section::Flag::ExecInstr].iter()),
+            section::Contents64::Raw(vec![0xe8, 0, 0, 0, 0, 0xc3]),
+        ));
+    }
+
+    fn add_rela(elf: &mut file::ELF64, name: &str, target: u32, sym: u64) {
+        let mut rela = relocation::Rela64::default();
+        rela.set_offset(1);
+        rela.set_info(sym << 32 | relocation::R_X86_64_PLT32);
+        elf.add_section(section::Section64::new(
+            name.to_string(),
+            section::ShdrPreparation64::default()
+                .ty(section::Type::Rela)
+                .flags([section::Flag::InfoLink].iter())
+                .link(5)
+                .info(target),
+            section::Contents64::RelaSymbols(vec![rela]),
+        ));
+    }
+
+    #[test]
+    fn gc_sections_test() {
+        // [1] .text.main -> helper, [2] .text.helper, [3] .text.unused -> local, [4] .text.local
+        let mut elf = file::ELF64::default();
+        elf.ehdr.set_elf_type(header::Type::Rel);
+        for name in [".text.main", ".text.helper", ".text.unused", ".text.local"].iter() {
+            add_text(&mut elf, name);
+        }
+        let mut syms = vec![symbol::Symbol64::new_null_symbol()];
+        for (name, shndx, bind) in [
+            ("local", 4, symbol::Bind::Local),
+            ("main", 1, symbol::Bind::Global),
+            ("helper", 2, symbol::Bind::Global),
+            ("unused", 3, symbol::Bind::Global),
+        ]
+        .iter()
+        {
+            let mut sym = symbol::Symbol64 {
+                symbol_name: (*name).into(),
+                st_shndx: *shndx,
+                st_size: 6,
+                ..Default::default()
+            };
+            sym.set_info(symbol::Type::Func, *bind);
+            syms.push(sym);
+        }
+        elf.add_section(section::Section64::new(
+            ".symtab".to_string(),
+            section::ShdrPreparation64::default()
+                .ty(section::Type::SymTab)
+                .info(2),
+            section::Contents64::Symbols(syms),
+        ));
+        add_rela(&mut elf, ".rela.text.main", 1, 3);
+        add_rela(&mut elf, ".rela.text.unused", 3, 1);
+
+        let removed = elf.gc_sections(&["main"]).unwrap();
+        assert_eq!(
+            vec![
+                ".text.unused".to_string(),
+                ".text.local".to_string(),
+                ".rela.text.unused".to_string()
+            ],
+            removed
+        );
+
+        let names: Vec<&str> = elf.sections.iter().map(|sct| sct.name.as_str()).collect();
+        assert_eq!(
+            vec![
+                "",
+                ".text.main",
+                ".text.helper",
+                ".symtab",
+                ".rela.text.main",
+                ".shstrtab"
+            ],
+            names
+        );
+        assert_eq!(5, elf.ehdr.e_shstrndx);
+
+        let symtab = &elf.sections[3];
+        // ローカルシンボルはnullシンボルのみになる
+        assert_eq!(1, symtab.header.sh_info);
+        let syms = match &symtab.contents {
+            section::Contents64::Symbols(syms) => syms,
+            _ => unreachable!(),
+        };
+        let sym_names: Vec<&str> = syms.iter().map(|sym| sym.symbol_name.as_str()).collect();
+        assert_eq!(vec!["", "main", "helper"], sym_names);
+
+        let rela = &elf.sections[4];
+        assert_eq!(3, rela.header.sh_link);
+        assert_eq!(1, rela.header.sh_info);
+        if let section::Contents64::RelaSymbols(relas) = &rela.contents {
+            assert_eq!("helper", syms[relas[0].get_sym() as usize].symbol_name);
+        }
+
+        assert!(matches!(
+            elf.gc_sections(&["nothing"]),
+            Err(GcError::UnknownRoot { .. })
+        ));
+    }
+}
@@ -0,0 +1,367 @@
+//! Hardening transforms and checks for existing binaries.
+
+use crate::*;
+use thiserror::Error as TError;
+
+#[derive(TError, Debug)]
+pub enum HardeningError {
+    #[error("segment {index} is writable and executable")]
+    WritableAndExecutable { index: usize },
+    #[error("the file has no GNU_RELRO segment")]
+    NoRelroSegment,
+    #[error("no spare DT_NULL entry to add DT_FLAGS")]
+    NoSpaceInDynamic,
+    #[error("invalid GNU_RELRO segment => `{0}`")]
+    InvalidRelro(#[from] segment::RelroError),
+    #[error("the file is not an executable")]
+    NotExecutable,
+    #[error("the file can't be converted to PIE => `{}`", blockers[0])]
+    PieBlocked { blockers: Vec<analysis::PieBlocker> },
+}
+
+/// the section which marks the stack permission of relocatable files
+pub const GNU_STACK_SECTION: &str = ".note.GNU-stack";
+
+/// A policy on segments which are both writable and executable
+#[derive(Default, Debug, Clone, Copy, Hash, PartialOrd, Ord, PartialEq, Eq)]
+pub enum WxPolicy {
+    /// W+X segments are emitted as is
+    #[default]
+    Allow,
+    /// W+X segments are refused (W^X)
+    Deny,
+}
+
+impl file::ELF64 {
+    /// clear `PF_X` of the segment.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `idx` is out of range.
+    pub fn make_segment_nx(&mut self, idx: usize) {
+        let phdr = &mut self.segments[idx].header;
+        phdr.p_flags &= !Elf64Word::from(segment::Flag::X);
+    }
+
+    /// turn the file into full RELRO.
+    ///
+    /// The existing `PT_GNU_RELRO` segment is refitted to the relro sections,
+    /// and lazy binding is disabled by `DF_BIND_NOW`/`DF_1_NOW`.
+    /// If `.dynamic` has neither `DT_FLAGS` nor `DT_FLAGS_1`, a spare `DT_NULL` at the end is replaced with `DT_FLAGS`.
+    /// Program headers can't be added without relayout, so the file must have `PT_GNU_RELRO` already.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use elf_utilities::{builder, dynamic, section, segment};
+    ///
+    /// let mut elf = builder::SharedObjectWriter::new()
+    ///     .function(builder::ExportedFunction::new("f", vec![0xc3]))
+    ///     .build()
+    ///     .unwrap();
+    /// elf.make_relro_full().unwrap();
+    ///
+    /// let dynamic = elf.first_section_by(|sct| sct.name == ".dynamic").unwrap();
+    /// if let section::Contents64::Dynamics(entries) = &dynamic.contents {
+    ///     let flags_1 = entries
+    ///         .iter()
+    ///         .find(|ent| ent.get_type() == dynamic::EntryType::Flags1)
+    ///         .unwrap();
+    ///     assert_ne!(0, flags_1.d_un & dynamic::Flag::Now1.to_bytes());
+    /// }
+    /// ```
+    pub fn make_relro_full(&mut self) -> Result<(), HardeningError> {
+        let relro = segment::compute_relro(self);
+        segment::validate_relro(self, &relro)?;
+        let sgt = self
+            .segments
+            .iter_mut()
+            .find(|sgt| sgt.header.get_type() == segment::Type::GNURelRO)
+            .ok_or(HardeningError::NoRelroSegment)?;
+        sgt.header = relro;
+
+        let entries = match self
+            .first_mut_section_by(|sct| sct.header.get_type() == section::Type::Dynamic)
+            .map(|sct| &mut sct.contents)
+        {
+            Some(section::Contents64::Dynamics(entries)) => entries,
+            // 静的リンクされたファイルには遅延束縛が存在しない
+            _ => return Ok(()),
+        };
+
+        let mut updated = false;
+        for ent in entries.iter_mut() {
+            match ent.get_type() {
+                dynamic::EntryType::Flags => {
+                    ent.d_un |= dynamic::Flag::BindNow.to_bytes();
+                    updated = true;
+                }
+                dynamic::EntryType::Flags1 => {
+                    ent.d_un |= dynamic::Flag::Now1.to_bytes();
+                    updated = true;
+                }
+                _ => {}
+            }
+        }
+        if updated {
+            return Ok(());
+        }
+
+        // 終端のDT_NULLは残す必要がある
+        let spare = entries
+            .iter()
+            .position(|ent| ent.get_type() == dynamic::EntryType::Null)
+            .filter(|idx| idx + 1 < entries.len())
+            .ok_or(HardeningError::NoSpaceInDynamic)?;
+        entries[spare] =
+            dynamic::Dyn64::new(dynamic::EntryType::Flags, dynamic::Flag::BindNow.to_bytes());
+        Ok(())
+    }
+
+    /// whether the stack is executable.
+    ///
+    /// For relocatable files it's decided by `SHF_EXECINSTR` of `.note.GNU-stack`,
+    /// and for the others by `PF_X` of `PT_GNU_STACK`.
+    /// Without them, the linker and the kernel assume an executable stack.
+    pub fn executable_stack(&self) -> bool {
+        if self.ehdr.get_type() == header::Type::Rel {
+            let exec: Elf64Xword = section::Flag::ExecInstr.into();
+            return self
+                .first_section_by(|sct| sct.name == GNU_STACK_SECTION)
+                .is_none_or(|sct| sct.header.sh_flags & exec != 0);
+        }
+
+        let exec: Elf64Word = segment::Flag::X.into();
+        self.segments
+            .iter()
+            .find(|sgt| sgt.header.get_type() == segment::Type::GNUStack)
+            .is_none_or(|sgt| sgt.header.p_flags & exec != 0)
+    }
+
+    /// make the stack executable or not.
+    ///
+    /// `.note.GNU-stack`(for relocatable files) or `PT_GNU_STACK` is created if not found.
+    /// A new program header changes the file layout, so apply `layout::Layout` again after that.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use elf_utilities::{file, header, section};
+    ///
+    /// let mut elf = file::ELF64::default();
+    /// elf.ehdr.set_elf_type(header::Type::Rel);
+    /// assert!(elf.executable_stack());
+    ///
+    /// elf.set_executable_stack(false);
+    /// assert!(!elf.executable_stack());
+    /// let note = elf.first_section_by(|sct| sct.name == ".note.GNU-stack").unwrap();
+    /// assert_eq!(section::Type::ProgBits, note.header.get_type());
+    /// ```
+    pub fn set_executable_stack(&mut self, executable: bool) {
+        if self.ehdr.get_type() == header::Type::Rel {
+            let exec: Elf64Xword = section::Flag::ExecInstr.into();
+            if self
+                .first_section_by(|sct| sct.name == GNU_STACK_SECTION)
+                .is_none()
+            {
+                self.add_section(section::Section64::new(
+                    GNU_STACK_SECTION.to_string(),
+                    section::ShdrPreparation64::default().ty(section::Type::ProgBits),
+                    section::Contents64::Raw(Vec::new()),
+                ));
+            }
+            let sct = self
+                .first_mut_section_by(|sct| sct.name == GNU_STACK_SECTION)
+                .unwrap();
+            if executable {
+                sct.header.sh_flags |= exec;
+            } else {
+                sct.header.sh_flags &= !exec;
+            }
+            return;
+        }
+
+        let idx = match self
+            .segments
+            .iter()
+            .position(|sgt| sgt.header.get_type() == segment::Type::GNUStack)
+        {
+            Some(idx) => idx,
+            None => {
+                let mut phdr = segment::Phdr64::default();
+                phdr.set_type(segment::Type::GNUStack);
+                phdr.set_flags([segment::Flag::R, segment::Flag::W].iter());
+                phdr.p_align = 16;
+                self.add_segment(segment::Segment64 { header: phdr });
+                self.segments.len() - 1
+            }
+        };
+        let exec: Elf64Word = segment::Flag::X.into();
+        if executable {
+            self.segments[idx].header.p_flags |= exec;
+        } else {
+            self.segments[idx].header.p_flags &= !exec;
+        }
+    }
+
+    /// convert the `ET_EXEC` into a position independent executable(`ET_DYN`).
+    ///
+    /// Only the ELF header and `DF_1_PIE` are changed, so the code must not depend on its address.
+    /// This is checked by `analysis::pie_blockers()` before the conversion.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use elf_utilities::{builder, header};
+    ///
+    /// let mut elf = builder::ExecutableWriter::new()
+    ///     .function(builder::ExportedFunction::new("_start", vec![0xc3]))
+    ///     .build()
+    ///     .unwrap();
+    /// elf.convert_to_pie().unwrap();
+    /// assert_eq!(header::Type::Dyn, elf.ehdr.get_type());
+    ///
+    /// // shared objects and PIEs aren't converted again
+    /// assert!(elf.convert_to_pie().is_err());
+    /// ```
+    pub fn convert_to_pie(&mut self) -> Result<(), HardeningError> {
+        if self.ehdr.get_type() != header::Type::Exec {
+            return Err(HardeningError::NotExecutable);
+        }
+        let blockers = analysis::pie_blockers(self);
+        if !blockers.is_empty() {
+            return Err(HardeningError::PieBlocked { blockers });
+        }
+
+        self.ehdr.set_elf_type(header::Type::Dyn);
+        if let Some(section::Contents64::Dynamics(entries)) = self
+            .first_mut_section_by(|sct| sct.header.get_type() == section::Type::Dynamic)
+            .map(|sct| &mut sct.contents)
+        {
+            for ent in entries.iter_mut() {
+                if ent.get_type() == dynamic::EntryType::Flags1 {
+                    ent.d_un |= dynamic::Flag::PIE1.to_bytes();
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// check the segments against the policy.
+    pub fn check_wx(&self, policy: WxPolicy) -> Result<(), HardeningError> {
+        if policy == WxPolicy::Allow {
+            return Ok(());
+        }
+
+        let wx = Elf64Word::from(segment::Flag::W) | Elf64Word::from(segment::Flag::X);
+        match self
+            .segments
+            .iter()
+            .position(|sgt| sgt.header.p_flags & wx == wx)
+        {
+            Some(index) => Err(HardeningError::WritableAndExecutable { index }),
+            None => Ok(()),
+        }
+    }
+
+    /// Create Vec<u8> from this if the segments follow the policy.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use elf_utilities::{builder, file, segment};
+    ///
+    /// let mut elf = builder::ExecutableWriter::new()
+    ///     .function(builder::ExportedFunction::new("_start", vec![0xc3]))
+    ///     .build()
+    ///     .unwrap();
+    /// assert!(elf.to_le_bytes_with_policy(file::WxPolicy::Deny).is_ok());
+    ///
+    /// let stack = elf
+    ///     .segments
+    ///     .iter()
+    ///     .position(|sgt| sgt.header.get_type() == segment::Type::GNUStack)
+    ///     .unwrap();
+    /// let flags = [segment::Flag::R, segment::Flag::W, segment::Flag::X];
+    /// elf.segments[stack].header.set_flags(flags.iter());
+    /// assert!(elf.to_le_bytes_with_policy(file::WxPolicy::Deny).is_err());
+    /// // the policy can be overridden explicitly.
+    /// assert!(elf.to_le_bytes_with_policy(file::WxPolicy::Allow).is_ok());
+    ///
+    /// elf.make_segment_nx(stack);
+    /// assert!(elf.to_le_bytes_with_policy(file::WxPolicy::Deny).is_ok());
+    /// ```
+    pub fn to_le_bytes_with_policy(&self, policy: WxPolicy) -> Result<Vec<u8>, HardeningError> {
+        self.check_wx(policy)?;
+        Ok(self.to_le_bytes())
+    }
+}
+
+#[cfg(test)]
+mod hardening_tests {
+    use super::*;
+
+    #[test]
+    fn executable_stack_test() {
+        let mut elf = crate::builder::ExecutableWriter::new()
+            .function(crate::builder::ExportedFunction::new("_start", vec![0xc3]))
+            .build()
+            .unwrap();
+        assert!(!elf.executable_stack());
+        elf.set_executable_stack(true);
+        assert!(elf.executable_stack());
+
+        // PT_GNU_STACKが無ければ作成する
+        elf.segments
+            .retain(|sgt| sgt.header.get_type() != segment::Type::GNUStack);
+        assert!(elf.executable_stack());
+        let phnum = elf.segments.len();
+        elf.set_executable_stack(false);
+        assert!(!elf.executable_stack());
+        assert_eq!(phnum + 1, elf.segments.len());
+    }
+
+    #[test]
+    fn make_relro_full_test() {
+        let mut elf = crate::builder::SharedObjectWriter::new()
+            .function(crate::builder::ExportedFunction::new("f", vec![0xc3]))
+            .build()
+            .unwrap();
+        let dynamic = elf.first_shidx_by(|sct| sct.name == ".dynamic").unwrap();
+
+        // DT_FLAGS/DT_FLAGS_1を取り除き，予備のDT_NULLを置く
+        if let section::Contents64::Dynamics(entries) = &mut elf.sections[dynamic].contents {
+            entries.retain(|ent| {
+                let ty = ent.get_type();
+                ty != dynamic::EntryType::Flags && ty != dynamic::EntryType::Flags1
+            });
+            entries.push(dynamic::Dyn64::new(dynamic::EntryType::Null, 0));
+        }
+        let relro = elf
+            .segments
+            .iter()
+            .position(|sgt| sgt.header.get_type() == segment::Type::GNURelRO)
+            .unwrap();
+        elf.segments[relro].header.p_memsz = 0;
+
+        elf.make_relro_full().unwrap();
+        assert_eq!(segment::compute_relro(&elf), elf.segments[relro].header);
+        if let section::Contents64::Dynamics(entries) = &elf.sections[dynamic].contents {
+            let flags = entries
+                .iter()
+                .find(|ent| ent.get_type() == dynamic::EntryType::Flags)
+                .unwrap();
+            assert_eq!(dynamic::Flag::BindNow.to_bytes(), flags.d_un);
+            assert_eq!(dynamic::EntryType::Null, entries.last().unwrap().get_type());
+        }
+
+        // 予備のDT_NULLが無ければ追加できない
+        if let section::Contents64::Dynamics(entries) = &mut elf.sections[dynamic].contents {
+            entries.retain(|ent| ent.get_type() != dynamic::EntryType::Flags);
+        }
+        assert!(matches!(
+            elf.make_relro_full(),
+            Err(HardeningError::NoSpaceInDynamic)
+        ));
+    }
+}
@@ -0,0 +1,109 @@
+//! Estimating the memory held by parsed ELF files.
+
+use std::collections::HashSet;
+use std::mem::size_of;
+
+use serde::{Deserialize, Serialize};
+
+use crate::*;
+
+/// The bytes held by an `ELF64`, estimated by `ELF64::approx_memory_usage()`
+///
+/// The vectors are counted by their capacity.
+/// The allocator's overhead and the values decoded by the codecs outside this crate aren't counted.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize,
+)]
+pub struct MemoryUsage {
+    /// the ELF header, the section headers and the program headers
+    pub headers: usize,
+    /// the names of the sections and the symbols. a name shared by interning is counted once
+    pub names: usize,
+    /// `Contents64::Raw`
+    pub raw: usize,
+    /// `Contents64::StrTab`
+    pub string_tables: usize,
+    /// `Contents64::Symbols`, without their names
+    pub symbols: usize,
+    /// `Contents64::RelaSymbols`
+    pub relocations: usize,
+    /// `Contents64::Dynamics`
+    pub dynamics: usize,
+    /// the bytes kept by `Contents64::Custom`
+    pub custom: usize,
+}
+
+impl MemoryUsage {
+    pub fn total(&self) -> usize {
+        self.headers
+            + self.names
+            + self.raw
+            + self.string_tables
+            + self.symbols
+            + self.relocations
+            + self.dynamics
+            + self.custom
+    }
+}
+
+impl file::ELF64 {
+    /// estimate the memory held by the file.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use elf_utilities::{intern, parser};
+    ///
+    /// let mut elf = parser::parse_elf64("src/parser/testdata/sample").unwrap();
+    /// let usage = elf.approx_memory_usage();
+    /// assert!(usage.symbols > 0 && usage.string_tables > 0);
+    ///
+    /// // 同じ名前のコピーは一つにまとめられる
+    /// elf.intern_names(&intern::Interner::new());
+    /// assert!(elf.approx_memory_usage().names <= usage.names);
+    /// ```
+    pub fn approx_memory_usage(&self) -> MemoryUsage {
+        let mut usage = MemoryUsage {
+            headers: size_of::<header::Ehdr64>()
+                + self.sections.capacity() * size_of::<section::Section64>()
+                + self.segments.capacity() * size_of::<segment::Segment64>(),
+            ..Default::default()
+        };
+
+        // 共有された名前は一度だけ数える
+        let mut seen = HashSet::new();
+        let mut count_name = |name: &intern::Name| {
+            if seen.insert(name.as_ptr()) {
+                // Arc<str>は参照カウント2つ分を持つ
+                name.len() + 2 * size_of::<usize>()
+            } else {
+                0
+            }
+        };
+
+        for sct in self.sections.iter() {
+            usage.names += count_name(&sct.name);
+            match &sct.contents {
+                section::Contents64::Raw(bytes) => usage.raw += bytes.capacity(),
+                section::Contents64::StrTab(strs) => {
+                    usage.string_tables += strs.capacity() * size_of::<section::StrTabEntry>()
+                        + strs.iter().map(|s| s.v.capacity()).sum::<usize>();
+                }
+                section::Contents64::Symbols(syms) => {
+                    usage.symbols += syms.capacity() * size_of::<symbol::Symbol64>();
+                    for sym in syms.iter() {
+                        usage.names += count_name(&sym.symbol_name);
+                    }
+                }
+                section::Contents64::RelaSymbols(relas) => {
+                    usage.relocations += relas.capacity() * size_of::<relocation::Rela64>();
+                }
+                section::Contents64::Dynamics(dyns) => {
+                    usage.dynamics += dyns.capacity() * size_of::<dynamic::Dyn64>();
+                }
+                section::Contents64::Custom(custom) => usage.custom += custom.bytes().len(),
+            }
+        }
+        usage
+    }
+}
@@ -0,0 +1,314 @@
+//! Handling binaries prelinked by `prelink`.
+//!
+//! A prelinked file records the libraries it was relocated against,
+//! and the dynamic linker skips the relocation processing when they are unchanged.
+//! So editing the relocations of a prelinked file has no effect unless the prelink information is dropped.
+
+use crate::*;
+
+/// `SHT_GNU_LIBLIST`
+pub const SHT_GNU_LIBLIST: Elf64Word = 0x6ffffff7;
+
+/// the time when the file was prelinked
+pub const DT_GNU_PRELINKED: Elf64Sxword = 0x6ffffdf5;
+pub const DT_GNU_CONFLICTSZ: Elf64Sxword = 0x6ffffdf6;
+pub const DT_GNU_LIBLISTSZ: Elf64Sxword = 0x6ffffdf7;
+/// the checksum of the allocated sections
+pub const DT_CHECKSUM: Elf64Sxword = 0x6ffffdf8;
+/// the address of `.gnu.conflict`
+pub const DT_GNU_CONFLICT: Elf64Sxword = 0x6ffffef8;
+/// the address of `.gnu.liblist`
+pub const DT_GNU_LIBLIST: Elf64Sxword = 0x6ffffef9;
+
+/// the section which keeps the headers before prelinking, for `prelink --undo`
+pub const PRELINK_UNDO_SECTION: &str = ".gnu.prelink_undo";
+/// the relocations which resolve the symbol conflicts between the libraries
+pub const GNU_CONFLICT_SECTION: &str = ".gnu.conflict";
+
+const PRELINK_TAGS: [Elf64Sxword; 6] = [
+    DT_GNU_PRELINKED,
+    DT_GNU_CONFLICTSZ,
+    DT_GNU_LIBLISTSZ,
+    DT_CHECKSUM,
+    DT_GNU_CONFLICT,
+    DT_GNU_LIBLIST,
+];
+
+/// An entry of `.gnu.liblist`(`Elf64_Lib`)
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct LibListEntry {
+    pub name: String,
+    pub time_stamp: Elf64Word,
+    pub checksum: Elf64Word,
+    pub version: Elf64Word,
+    pub flags: Elf64Word,
+}
+
+impl LibListEntry {
+    pub const SIZE: usize = 20;
+}
+
+/// The result of `ELF64::prelink_info()`
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct PrelinkInfo {
+    /// the value of `DT_GNU_PRELINKED`
+    pub prelinked_at: Option<Elf64Xword>,
+    /// the value of `DT_CHECKSUM`
+    pub checksum: Option<Elf64Xword>,
+    /// the libraries in `.gnu.liblist`
+    pub libraries: Vec<LibListEntry>,
+    /// the number of relocations in `.gnu.conflict`
+    pub conflicts: usize,
+    /// `.gnu.prelink_undo` exists
+    pub has_undo: bool,
+}
+
+impl file::ELF64 {
+    /// whether the file has been prelinked.
+    pub fn is_prelinked(&self) -> bool {
+        self.prelink_info().is_some()
+    }
+
+    /// collect the information recorded by `prelink`. `None` if the file isn't prelinked.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use elf_utilities::builder;
+    ///
+    /// let elf = builder::SharedObjectWriter::new()
+    ///     .function(builder::ExportedFunction::new("f", vec![0xc3]))
+    ///     .build()
+    ///     .unwrap();
+    /// assert!(elf.prelink_info().is_none());
+    /// ```
+    pub fn prelink_info(&self) -> Option<PrelinkInfo> {
+        let mut info = PrelinkInfo::default();
+        if let Some(entries) = self.dynamic_entries() {
+            let find = |tag: Elf64Sxword| {
+                entries
+                    .iter()
+                    .find(|ent| ent.d_tag == tag)
+                    .map(|ent| ent.d_un)
+            };
+            info.prelinked_at = find(DT_GNU_PRELINKED);
+            info.checksum = find(DT_CHECKSUM);
+        }
+        if let Some(liblist) = self.first_section_by(|sct| sct.header.sh_type == SHT_GNU_LIBLIST) {
+            info.libraries = self.liblist_entries(liblist);
+        }
+        info.conflicts = self
+            .first_section_by(|sct| sct.name == GNU_CONFLICT_SECTION)
+            .map_or(0, |sct| match &sct.contents {
+                section::Contents64::RelaSymbols(relas) => relas.len(),
+                contents => contents.size() / relocation::Rela64::SIZE as usize,
+            });
+        info.has_undo = self
+            .first_section_by(|sct| sct.name == PRELINK_UNDO_SECTION)
+            .is_some();
+
+        if info == PrelinkInfo::default() {
+            None
+        } else {
+            Some(info)
+        }
+    }
+
+    /// drop the `DT_GNU_*` prelink entries from `.dynamic`,
+    /// so that the dynamic linker processes the relocations as usual.
+    ///
+    /// The entries after them are moved up and `DT_NULL`s fill the end, so `.dynamic` keeps its size.
+    /// Returns whether any entry was removed.
+    pub fn invalidate_prelink(&mut self) -> bool {
+        let dynamic = match self
+            .first_mut_section_by(|sct| sct.header.get_type() == section::Type::Dynamic)
+        {
+            Some(sct) => sct,
+            None => return false,
+        };
+        let entries = match &mut dynamic.contents {
+            section::Contents64::Dynamics(entries) => entries,
+            _ => return false,
+        };
+        let len = entries.len();
+        entries.retain(|ent| !PRELINK_TAGS.contains(&ent.d_tag));
+        let removed = len - entries.len();
+        entries.resize(len, dynamic::Dyn64::new(dynamic::EntryType::Null, 0));
+        removed != 0
+    }
+
+    /// undo the prelinking as far as possible without the original libraries.
+    ///
+    /// The prelink entries are dropped from `.dynamic` by `invalidate_prelink()`,
+    /// and `.gnu.liblist`, its string table(if only it refers to), `.gnu.conflict` and `.gnu.prelink_undo` are removed.
+    /// The allocated sections keep their places in the segments, so the removed ones leave holes filled with zeros.
+    /// The relocated values in the file are kept, which is harmless since RELA relocations don't read them.
+    /// Returns the names of the removed sections.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use elf_utilities::{builder, file, section};
+    ///
+    /// let mut elf = builder::SharedObjectWriter::new()
+    ///     .function(builder::ExportedFunction::new("f", vec![0xc3]))
+    ///     .build()
+    ///     .unwrap();
+    /// elf.sections.push(section::Section64::new(
+    ///     file::PRELINK_UNDO_SECTION.to_string(),
+    ///     section::ShdrPreparation64::default().ty(section::Type::ProgBits),
+    ///     section::Contents64::Raw(vec![0; 64]),
+    /// ));
+    /// elf.ehdr.e_shnum += 1;
+    /// assert!(elf.is_prelinked());
+    ///
+    /// assert_eq!(vec![file::PRELINK_UNDO_SECTION], elf.remove_prelink());
+    /// assert!(!elf.is_prelinked());
+    /// ```
+    pub fn remove_prelink(&mut self) -> Vec<String> {
+        self.invalidate_prelink();
+
+        let mut removed: Vec<bool> = self
+            .sections
+            .iter()
+            .map(|sct| {
+                sct.header.sh_type == SHT_GNU_LIBLIST
+                    || sct.name == GNU_CONFLICT_SECTION
+                    || sct.name == PRELINK_UNDO_SECTION
+            })
+            .collect();
+        // .gnu.libstr は他から参照されなければ削除する
+        for idx in 0..self.sections.len() {
+            if self.sections[idx].header.sh_type != SHT_GNU_LIBLIST {
+                continue;
+            }
+            let strtab = self.sections[idx].header.sh_link as usize;
+            let shared = strtab == self.ehdr.e_shstrndx as usize
+                || self
+                    .sections
+                    .iter()
+                    .enumerate()
+                    .any(|(i, sct)| !removed[i] && sct.header.sh_link as usize == strtab);
+            if strtab != 0 && strtab < removed.len() && !shared {
+                removed[strtab] = true;
+            }
+        }
+        self.remove_sections(&removed)
+    }
+
+    fn dynamic_entries(&self) -> Option<&Vec<dynamic::Dyn64>> {
+        let dynamic =
+            self.first_section_by(|sct| sct.header.get_type() == section::Type::Dynamic)?;
+        match &dynamic.contents {
+            section::Contents64::Dynamics(entries) => Some(entries),
+            _ => None,
+        }
+    }
+
+    /// decode `.gnu.liblist`, whose names are in the string table of `sh_link`.
+    fn liblist_entries(&self, liblist: &section::Section64) -> Vec<LibListEntry> {
+        let strtab = self
+            .sections
+            .get(liblist.header.sh_link as usize)
+            .map(|sct| sct.to_le_bytes())
+            .unwrap_or_default();
+        let bytes = liblist.to_le_bytes();
+        let mut r = section::PayloadReader::new(&bytes, self.ehdr.get_data());
+        let mut entries = Vec::new();
+        while r.remaining() >= LibListEntry::SIZE {
+            // 残りが足りているので失敗しない
+            let mut field = || r.u32().unwrap();
+            let l_name = field() as usize;
+            let name = strtab
+                .get(l_name..)
+                .and_then(|s| s.split(|b| *b == 0).next())
+                .map(|s| String::from_utf8_lossy(s).to_string())
+                .unwrap_or_default();
+            entries.push(LibListEntry {
+                name,
+                time_stamp: field(),
+                checksum: field(),
+                version: field(),
+                flags: field(),
+            });
+        }
+        entries
+    }
+}
+
+#[cfg(test)]
+mod prelink_tests {
+    use super::*;
+
+    #[test]
+    fn prelink_test() {
+        let mut elf = file::ELF64::default();
+        elf.ehdr.set_elf_type(header::Type::Dyn);
+        elf.add_section(section::Section64::new(
+            ".gnu.libstr".to_string(),
+            section::ShdrPreparation64::default().ty(section::Type::StrTab),
+            section::Contents64::Raw(b"\0libc.so.6\0".to_vec()),
+        ));
+        let libstr = elf.sections.len() - 2;
+
+        let mut liblist = Vec::new();
+        for field in [1u32, 0x6000_0000, 0xdead_beef, 0, 0].iter() {
+            liblist.extend_from_slice(&field.to_le_bytes());
+        }
+        elf.add_section(section::Section64::new(
+            ".gnu.liblist".to_string(),
+            section::ShdrPreparation64::default()
+                .ty(section::Type::Any(SHT_GNU_LIBLIST))
+                .link(libstr as Elf64Word),
+            section::Contents64::Raw(liblist),
+        ));
+        elf.add_section(section::Section64::new(
+            GNU_CONFLICT_SECTION.to_string(),
+            section::ShdrPreparation64::default().ty(section::Type::Rela),
+            section::Contents64::RelaSymbols(vec![relocation::Rela64::default(); 2]),
+        ));
+        let entries = vec![
+            dynamic::Dyn64 {
+                d_tag: DT_GNU_PRELINKED,
+                d_un: 0x6000_0000,
+            },
+            dynamic::Dyn64::new(dynamic::EntryType::Needed, 1),
+            dynamic::Dyn64 {
+                d_tag: DT_CHECKSUM,
+                d_un: 0x1234,
+            },
+            dynamic::Dyn64::new(dynamic::EntryType::Null, 0),
+        ];
+        elf.add_section(section::Section64::new(
+            ".dynamic".to_string(),
+            section::ShdrPreparation64::default().ty(section::Type::Dynamic),
+            section::Contents64::Dynamics(entries),
+        ));
+
+        let info = elf.prelink_info().unwrap();
+        assert_eq!(Some(0x6000_0000), info.prelinked_at);
+        assert_eq!(Some(0x1234), info.checksum);
+        assert_eq!(2, info.conflicts);
+        assert!(!info.has_undo);
+        assert_eq!(
+            vec![LibListEntry {
+                name: "libc.so.6".to_string(),
+                time_stamp: 0x6000_0000,
+                checksum: 0xdead_beef,
+                version: 0,
+                flags: 0,
+            }],
+            info.libraries
+        );
+
+        assert_eq!(
+            vec![".gnu.libstr", ".gnu.liblist", GNU_CONFLICT_SECTION],
+            elf.remove_prelink()
+        );
+        assert!(!elf.is_prelinked());
+        let entries = elf.dynamic_entries().unwrap();
+        assert_eq!(4, entries.len());
+        assert_eq!(dynamic::EntryType::Needed, entries[0].get_type());
+        assert_eq!(dynamic::EntryType::Null, entries[1].get_type());
+    }
+}
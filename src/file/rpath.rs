@@ -0,0 +1,103 @@
+//! Reading and editing `DT_RUNPATH`/`DT_RPATH` of existing binaries.
+
+use crate::*;
+use thiserror::Error as TError;
+
+#[derive(TError, Debug)]
+pub enum RpathError {
+    #[error("the file has no dynamic section")]
+    NoDynamic,
+    #[error("the file has no DT_RUNPATH or DT_RPATH entry")]
+    NoRpathEntry,
+    #[error("the dynamic string table is broken")]
+    InvalidDynStr,
+    #[error("the new path is {len} bytes, but only {capacity} bytes are available")]
+    TooLong { len: usize, capacity: usize },
+}
+
+impl file::ELF64 {
+    /// the library search path of the file.
+    /// `DT_RUNPATH` is preferred to `DT_RPATH`, like the dynamic linker.
+    pub fn rpath(&self) -> Option<String> {
+        let (dynstr, offset) = self.rpath_string().ok()?;
+        match &self.sections[dynstr].contents {
+            section::Contents64::StrTab(strs) => strs
+                .iter()
+                .find(|ent| ent.idx == offset)
+                .map(|ent| ent.v.clone()),
+            _ => None,
+        }
+    }
+
+    /// overwrite the string of `DT_RUNPATH`(or `DT_RPATH`) in place.
+    ///
+    /// The file layout is kept, so the new path must not be longer than the old one.
+    /// The rest of the old string is filled with null bytes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use elf_utilities::builder;
+    ///
+    /// let answer = builder::ExportedFunction::new("answer", vec![0xb8, 0x2a, 0x00, 0x00, 0x00, 0xc3]);
+    /// let mut elf = builder::SharedObjectWriter::new()
+    ///     .function(answer)
+    ///     .runpath("/opt/example/lib")
+    ///     .build()
+    ///     .unwrap();
+    /// elf.set_rpath("$ORIGIN").unwrap();
+    /// assert_eq!(Some("$ORIGIN".to_string()), elf.rpath());
+    ///
+    /// assert!(elf.set_rpath("/a/very/long/path/to/libraries").is_err());
+    /// ```
+    pub fn set_rpath(&mut self, path: &str) -> Result<(), RpathError> {
+        let (dynstr, offset) = self.rpath_string()?;
+        let strs = match &mut self.sections[dynstr].contents {
+            section::Contents64::StrTab(strs) => strs,
+            _ => return Err(RpathError::InvalidDynStr),
+        };
+        let pos = strs
+            .iter()
+            .position(|ent| ent.idx == offset)
+            .ok_or(RpathError::InvalidDynStr)?;
+
+        let capacity = strs[pos].v.len();
+        if path.len() > capacity {
+            return Err(RpathError::TooLong {
+                len: path.len(),
+                capacity,
+            });
+        }
+
+        // 空文字列のエントリはnull-byte一つになるので，余った分を埋めてオフセットを保つ
+        strs[pos].v = path.to_string();
+        let padding = (path.len()..capacity).map(|i| section::StrTabEntry {
+            v: String::new(),
+            idx: offset + i + 1,
+        });
+        strs.splice(pos + 1..pos + 1, padding);
+        Ok(())
+    }
+
+    /// the index of `.dynstr` and the offset of the search path in it.
+    fn rpath_string(&self) -> Result<(usize, usize), RpathError> {
+        let dynamic = self
+            .first_section_by(|sct| sct.header.get_type() == section::Type::Dynamic)
+            .ok_or(RpathError::NoDynamic)?;
+        let entries = match &dynamic.contents {
+            section::Contents64::Dynamics(entries) => entries,
+            _ => return Err(RpathError::NoDynamic),
+        };
+
+        let find = |ty: dynamic::EntryType| entries.iter().find(|ent| ent.get_type() == ty);
+        let entry = find(dynamic::EntryType::RunPath)
+            .or_else(|| find(dynamic::EntryType::RPath))
+            .ok_or(RpathError::NoRpathEntry)?;
+
+        let dynstr = dynamic.header.sh_link as usize;
+        if dynstr == 0 || dynstr >= self.sections.len() {
+            return Err(RpathError::InvalidDynStr);
+        }
+        Ok((dynstr, entry.d_un as usize))
+    }
+}
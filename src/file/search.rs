@@ -0,0 +1,265 @@
+//! Searching bytes in ELF files.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::layout::{is_alloc, is_nobits};
+use crate::*;
+use thiserror::Error as TError;
+
+#[derive(TError, Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PatternError {
+    #[error("the pattern is empty")]
+    Empty,
+    #[error("`{token}` is neither a hex byte nor `??`")]
+    InvalidToken { token: String },
+}
+
+/// A byte sequence where each byte may be a wildcard
+///
+/// The text form is hex bytes separated by whitespace, and `??`(or `?`) matches any byte,
+/// e.g. `48 8b ?? c3`.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::file::BytePattern;
+///
+/// let pattern: BytePattern = "48 8b ?? c3".parse().unwrap();
+/// assert!(pattern.matches(&[0x48, 0x8b, 0x05, 0xc3]));
+/// assert!(!pattern.matches(&[0x48, 0x89, 0x05, 0xc3]));
+/// assert_eq!("48 8b ?? c3", pattern.to_string());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BytePattern {
+    bytes: Vec<Option<u8>>,
+}
+
+impl BytePattern {
+    pub fn parse(pattern: &str) -> Result<Self, PatternError> {
+        let bytes = pattern
+            .split_whitespace()
+            .map(|token| match token {
+                "?" | "??" => Ok(None),
+                _ if token.len() == 2 => u8::from_str_radix(token, 16).map(Some).map_err(|_| {
+                    PatternError::InvalidToken {
+                        token: token.to_string(),
+                    }
+                }),
+                _ => Err(PatternError::InvalidToken {
+                    token: token.to_string(),
+                }),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if bytes.is_empty() {
+            return Err(PatternError::Empty);
+        }
+        Ok(Self { bytes })
+    }
+
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// whether `bytes` starts with the pattern.
+    pub fn matches(&self, bytes: &[u8]) -> bool {
+        bytes.len() >= self.bytes.len()
+            && self
+                .bytes
+                .iter()
+                .zip(bytes)
+                .all(|(p, b)| p.is_none_or(|p| p == *b))
+    }
+
+    /// the positions where the pattern matches in `haystack`, overlapping ones included.
+    pub fn find_all(&self, haystack: &[u8]) -> Vec<usize> {
+        if self.bytes.is_empty() || haystack.len() < self.bytes.len() {
+            return Vec::new();
+        }
+        (0..=haystack.len() - self.bytes.len())
+            .filter(|&i| self.matches(&haystack[i..]))
+            .collect()
+    }
+}
+
+impl From<&[u8]> for BytePattern {
+    fn from(bytes: &[u8]) -> Self {
+        Self {
+            bytes: bytes.iter().copied().map(Some).collect(),
+        }
+    }
+}
+
+impl FromStr for BytePattern {
+    type Err = PatternError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl fmt::Display for BytePattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, byte) in self.bytes.iter().enumerate() {
+            if i != 0 {
+                write!(f, " ")?;
+            }
+            match byte {
+                Some(b) => write!(f, "{:02x}", b)?,
+                None => write!(f, "??")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A match found by `ELF64::search_bytes()` or `ELF64::search_vaddr_pattern()`
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SearchHit {
+    pub offset: Elf64Off,
+    /// the virtual address, `None` if the bytes aren't loaded
+    pub vaddr: Option<Elf64Addr>,
+    pub section: Option<String>,
+    /// the index of the `PT_LOAD` segment containing the hit
+    pub segment: Option<usize>,
+    /// the symbol containing the hit, and the offset from its start
+    pub symbol: Option<(String, Elf64Xword)>,
+}
+
+impl file::ELF64 {
+    /// search the bytes in the whole file as written by `to_le_bytes()`.
+    ///
+    /// Each hit is annotated with the section and the `PT_LOAD` segment containing the file offset,
+    /// the virtual address it is loaded at, and the symbol defined there.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use elf_utilities::builder;
+    ///
+    /// let main = builder::ExportedFunction::new("main", vec![0xb8, 0x2a, 0x00, 0x00, 0x00, 0xc3]);
+    /// let elf = builder::ExecutableWriter::new()
+    ///     .function(main)
+    ///     .start_stub(builder::start_stub("main"))
+    ///     .build()
+    ///     .unwrap();
+    ///
+    /// let hits = elf.search_bytes(&[0x2a, 0x00, 0x00, 0x00, 0xc3]);
+    /// assert_eq!(1, hits.len());
+    /// assert_eq!(Some(".text"), hits[0].section.as_deref());
+    /// assert_eq!(Some(("main".to_string(), 1)), hits[0].symbol);
+    /// ```
+    pub fn search_bytes(&self, pattern: &[u8]) -> Vec<SearchHit> {
+        let pattern = BytePattern::from(pattern);
+        let bytes = self.to_le_bytes();
+        pattern
+            .find_all(&bytes)
+            .into_iter()
+            .map(|offset| {
+                let offset = offset as Elf64Off;
+                let shidx = self.sections.iter().position(|sct| {
+                    !is_nobits(&sct.header)
+                        && sct.header.sh_offset <= offset
+                        && offset < sct.header.sh_offset + sct.header.sh_size
+                });
+                let segment = self.segments.iter().position(|sgt| {
+                    let phdr = &sgt.header;
+                    phdr.get_type() == segment::Type::Load
+                        && phdr.p_offset <= offset
+                        && offset < phdr.p_offset + phdr.p_filesz
+                });
+                let vaddr = segment.map(|i| {
+                    let phdr = &self.segments[i].header;
+                    phdr.p_vaddr + (offset - phdr.p_offset)
+                });
+                let symbol = shidx.and_then(|shidx| {
+                    let sct = &self.sections[shidx];
+                    self.symbol_in(shidx, sct.header.sh_addr + (offset - sct.header.sh_offset))
+                });
+                SearchHit {
+                    offset,
+                    vaddr,
+                    section: shidx.map(|i| self.sections[i].name.to_string()),
+                    segment,
+                    symbol,
+                }
+            })
+            .collect()
+    }
+
+    /// search the pattern with `??` wildcards in the contents of the `SHF_ALLOC` sections.
+    ///
+    /// A match spanning two sections isn't found.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use elf_utilities::builder;
+    ///
+    /// let main = builder::ExportedFunction::new("main", vec![0xb8, 0x2a, 0x00, 0x00, 0x00, 0xc3]);
+    /// let elf = builder::ExecutableWriter::new()
+    ///     .function(main)
+    ///     .start_stub(builder::start_stub("main"))
+    ///     .build()
+    ///     .unwrap();
+    /// let text = elf.first_section_by(|sct| sct.name == ".text").unwrap().header;
+    ///
+    /// // mov eax, imm32; ret
+    /// let hits = elf.search_vaddr_pattern("b8 ?? ?? ?? ?? c3").unwrap();
+    /// assert!(hits.iter().any(|hit| hit.symbol == Some(("main".to_string(), 0))));
+    /// assert!(hits.iter().all(|hit| hit.vaddr.unwrap() >= text.sh_addr));
+    /// assert!(elf.search_vaddr_pattern("b8 zz").is_err());
+    /// ```
+    pub fn search_vaddr_pattern(&self, pattern: &str) -> Result<Vec<SearchHit>, PatternError> {
+        let pattern = BytePattern::parse(pattern)?;
+        let mut hits = Vec::new();
+        for (shidx, sct) in self.sections.iter().enumerate() {
+            if !is_alloc(&sct.header) || is_nobits(&sct.header) {
+                continue;
+            }
+            for pos in pattern.find_all(&sct.to_le_bytes()) {
+                let vaddr = sct.header.sh_addr + pos as Elf64Addr;
+                let segment = self.segments.iter().position(|sgt| {
+                    let phdr = &sgt.header;
+                    phdr.get_type() == segment::Type::Load
+                        && phdr.p_vaddr <= vaddr
+                        && vaddr < phdr.p_vaddr + phdr.p_memsz
+                });
+                hits.push(SearchHit {
+                    offset: sct.header.sh_offset + pos as Elf64Off,
+                    vaddr: Some(vaddr),
+                    section: Some(sct.name.to_string()),
+                    segment,
+                    symbol: self.symbol_in(shidx, vaddr),
+                });
+            }
+        }
+        hits.sort_by_key(|hit| hit.vaddr);
+        Ok(hits)
+    }
+
+    /// the nearest symbol defined in the section which covers `addr`.
+    /// `addr` is the offset in the section for relocatable files, where `sh_addr` is 0.
+    fn symbol_in(&self, shidx: usize, addr: Elf64Addr) -> Option<(String, Elf64Xword)> {
+        self.sections
+            .iter()
+            .filter_map(|sct| match &sct.contents {
+                section::Contents64::Symbols(syms) => Some(syms),
+                _ => None,
+            })
+            .flatten()
+            .filter(|sym| {
+                sym.st_shndx as usize == shidx
+                    && !sym.symbol_name.is_empty()
+                    && !matches!(sym.get_type(), symbol::Type::Section | symbol::Type::File)
+                    && sym.st_value <= addr
+                    && addr < sym.st_value + sym.st_size.max(1)
+            })
+            .max_by_key(|sym| sym.st_value)
+            .map(|sym| (sym.symbol_name.to_string(), addr - sym.st_value))
+    }
+}
@@ -0,0 +1,164 @@
+//! Summary statistics of ELF files.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::layout::is_nobits;
+use crate::*;
+
+/// The total of the `PT_LOAD` segments with the same permission
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize,
+)]
+pub struct SegmentTotal {
+    pub count: usize,
+    pub file_size: Elf64Xword,
+    pub memory_size: Elf64Xword,
+}
+
+/// The counts and sizes computed by `ELF64::stats()`
+///
+/// The maps are keyed by the names `readelf` shows(e.g. `PROGBITS`, `GLOBAL`, `FUNC`),
+/// except the relocation types which depend on the machine.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize)]
+pub struct ElfStats {
+    pub sections: usize,
+    pub sections_by_type: BTreeMap<String, usize>,
+    /// the symbols in all symbol tables, except the null symbols
+    pub symbols: usize,
+    pub symbols_by_bind: BTreeMap<String, usize>,
+    pub symbols_by_type: BTreeMap<String, usize>,
+    /// the entries of `SHT_RELA` and `SHT_REL` sections
+    pub relocations: usize,
+    pub relocations_by_type: BTreeMap<Elf64Xword, usize>,
+    pub segments: usize,
+    /// the `PT_LOAD` segments by the permission like `R-X`
+    pub load_segments_by_permission: BTreeMap<String, SegmentTotal>,
+    /// the end of the last table or section in the file
+    pub file_size: Elf64Xword,
+    /// the sum of `p_memsz` of the `PT_LOAD` segments
+    pub memory_size: Elf64Xword,
+}
+
+impl file::ELF64 {
+    /// count the sections, symbols, relocations and segments.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use elf_utilities::builder;
+    ///
+    /// let main = builder::ExportedFunction::new("main", vec![0xc3]);
+    /// let elf = builder::ExecutableWriter::new()
+    ///     .function(main)
+    ///     .start_stub(builder::start_stub("main"))
+    ///     .build()
+    ///     .unwrap();
+    ///
+    /// let stats = elf.stats();
+    /// assert_eq!(elf.sections.len(), stats.sections);
+    /// assert_eq!(Some(&1), stats.sections_by_type.get("SYMTAB"));
+    /// assert_eq!(1, stats.load_segments_by_permission["R-X"].count);
+    /// assert_eq!(elf.to_le_bytes().len() as u64, stats.file_size);
+    /// ```
+    pub fn stats(&self) -> ElfStats {
+        let mut stats = ElfStats {
+            sections: self.sections.len(),
+            segments: self.segments.len(),
+            ..Default::default()
+        };
+
+        let data = self.ehdr.get_data();
+        for sct in self.sections.iter() {
+            *stats
+                .sections_by_type
+                .entry(sct.header.get_type().to_string())
+                .or_default() += 1;
+
+            match &sct.contents {
+                section::Contents64::Symbols(syms) => {
+                    for sym in syms.iter().skip(1) {
+                        stats.symbols += 1;
+                        *stats
+                            .symbols_by_bind
+                            .entry(sym.get_bind().to_string())
+                            .or_default() += 1;
+                        *stats
+                            .symbols_by_type
+                            .entry(sym.get_type().to_string())
+                            .or_default() += 1;
+                    }
+                }
+                section::Contents64::RelaSymbols(relas) => {
+                    for rela in relas.iter() {
+                        stats.relocations += 1;
+                        *stats
+                            .relocations_by_type
+                            .entry(rela.get_type())
+                            .or_default() += 1;
+                    }
+                }
+                section::Contents64::Raw(bytes) if sct.header.get_type() == section::Type::Rel => {
+                    // r_offset, r_info
+                    let mut r = section::PayloadReader::new(bytes, data);
+                    while r.remaining() >= 16 {
+                        r.skip(8).unwrap();
+                        let info = r.u64().unwrap();
+                        stats.relocations += 1;
+                        *stats
+                            .relocations_by_type
+                            .entry(info & 0xffffffff)
+                            .or_default() += 1;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let mut file_size = header::Ehdr64::SIZE as Elf64Xword;
+        if !self.segments.is_empty() {
+            file_size = file_size
+                .max(self.ehdr.e_phoff + (self.segments.len() * segment::Phdr64::SIZE) as u64);
+        }
+        if self.ehdr.e_shoff != 0 {
+            file_size = file_size
+                .max(self.ehdr.e_shoff + (self.sections.len() * section::Shdr64::SIZE) as u64);
+        }
+        for sct in self.sections.iter().filter(|s| !is_nobits(&s.header)) {
+            file_size = file_size.max(sct.header.sh_offset + sct.header.sh_size);
+        }
+
+        for sgt in self.segments.iter() {
+            let phdr = &sgt.header;
+            file_size = file_size.max(phdr.p_offset + phdr.p_filesz);
+            if phdr.get_type() != segment::Type::Load {
+                continue;
+            }
+            stats.memory_size += phdr.p_memsz;
+            let permission: String = [
+                (segment::Flag::R, 'R'),
+                (segment::Flag::W, 'W'),
+                (segment::Flag::X, 'X'),
+            ]
+            .iter()
+            .map(|(flag, c)| {
+                if phdr.p_flags & Elf64Word::from(*flag) != 0 {
+                    *c
+                } else {
+                    '-'
+                }
+            })
+            .collect();
+            let total = stats
+                .load_segments_by_permission
+                .entry(permission)
+                .or_default();
+            total.count += 1;
+            total.file_size += phdr.p_filesz;
+            total.memory_size += phdr.p_memsz;
+        }
+        stats.file_size = file_size;
+        stats
+    }
+}
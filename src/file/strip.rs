@@ -0,0 +1,169 @@
+//! Removing symbols and debug information from existing binaries.
+
+use crate::*;
+
+impl file::ELF64 {
+    /// remove the symbol table and the debug sections, like `strip`.
+    ///
+    /// `.symtab`, its string table, `.debug_*`/`.zdebug_*` sections and their relocations are removed.
+    /// Relocatable files keep the symbol table because relocations refer to it.
+    ///
+    /// Allocated sections keep their offsets and addresses,
+    /// and the remaining non-allocated sections are packed after them.
+    /// The names of the removed sections are returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use elf_utilities::builder;
+    ///
+    /// let main = builder::ExportedFunction::new("main", vec![0xb8, 0x2a, 0x00, 0x00, 0x00, 0xc3]);
+    /// let mut elf = builder::ExecutableWriter::new()
+    ///     .function(main)
+    ///     .start_stub(builder::start_stub("main"))
+    ///     .build()
+    ///     .unwrap();
+    /// let entry = elf.ehdr.e_entry;
+    ///
+    /// let removed = elf.strip();
+    /// assert_eq!(vec![".symtab", ".strtab"], removed);
+    /// assert!(elf.first_section_by(|sct| sct.name == ".symtab").is_none());
+    /// assert_eq!(entry, elf.ehdr.e_entry);
+    /// ```
+    pub fn strip(&mut self) -> Vec<String> {
+        let removed = self.strippable_sections();
+        self.remove_sections(&removed)
+    }
+
+    /// remove the sections marked in `removed` and return their names.
+    /// the section indices in the file are rewritten and the non-allocated sections are packed.
+    pub(super) fn remove_sections(&mut self, removed: &[bool]) -> Vec<String> {
+        let names: Vec<String> = self
+            .sections
+            .iter()
+            .zip(removed.iter())
+            .filter(|(_, removed)| **removed)
+            .map(|(sct, _)| sct.name.to_string())
+            .collect();
+        if names.is_empty() {
+            return names;
+        }
+
+        let mut new_indices = Vec::with_capacity(removed.len());
+        let mut next = 0;
+        for r in removed.iter() {
+            if *r {
+                new_indices.push(0);
+            } else {
+                new_indices.push(next);
+                next += 1;
+            }
+        }
+        self.remap_section_indices(|idx| new_indices.get(idx).copied().unwrap_or(0));
+        let mut removed_iter = removed.iter();
+        self.sections.retain(|_| !*removed_iter.next().unwrap());
+
+        if self.ehdr.get_type() == header::Type::Rel {
+            self.condition();
+        } else {
+            self.pack_non_alloc_sections();
+        }
+
+        names
+    }
+
+    fn strippable_sections(&self) -> Vec<bool> {
+        let keep_symtab = self.ehdr.get_type() == header::Type::Rel;
+        let shstrndx = self.ehdr.e_shstrndx as usize;
+
+        let mut removed: Vec<bool> = self
+            .sections
+            .iter()
+            .map(|sct| {
+                if layout::is_alloc(&sct.header) {
+                    return false;
+                }
+                is_debug_section(&sct.name)
+                    || (!keep_symtab && sct.header.get_type() == section::Type::SymTab)
+            })
+            .collect();
+
+        // 削除するシンボルテーブルの文字列テーブルは，他から参照されなければ削除する
+        for idx in 0..self.sections.len() {
+            if !removed[idx] || self.sections[idx].header.get_type() != section::Type::SymTab {
+                continue;
+            }
+            let strtab = self.sections[idx].header.sh_link as usize;
+            let shared = strtab == shstrndx
+                || self.sections.iter().enumerate().any(|(i, sct)| {
+                    !removed[i] && i != strtab && sct.header.sh_link as usize == strtab
+                });
+            if strtab != 0 && strtab < removed.len() && !shared {
+                removed[strtab] = true;
+            }
+        }
+
+        // 削除するシンボルテーブルを参照するLLVMのセクションも削除する
+        for (idx, sct) in self.sections.iter().enumerate() {
+            if matches!(
+                section::LlvmSection::of(sct),
+                Some(section::LlvmSection::Addrsig) | Some(section::LlvmSection::CallGraphProfile)
+            ) && removed
+                .get(sct.header.sh_link as usize)
+                .copied()
+                .unwrap_or(false)
+            {
+                removed[idx] = true;
+            }
+        }
+
+        // 削除するセクションへのリロケーションも削除する
+        for (idx, sct) in self.sections.iter().enumerate() {
+            let ty = sct.header.get_type();
+            if ty == section::Type::Rela || ty == section::Type::Rel {
+                let target = sct.header.sh_info as usize;
+                let symtab = sct.header.sh_link as usize;
+                if removed.get(target).copied().unwrap_or(false)
+                    || removed.get(symtab).copied().unwrap_or(false)
+                {
+                    removed[idx] = true;
+                }
+            }
+        }
+
+        removed
+    }
+
+    /// place the non-allocated sections after the allocated contents,
+    /// without moving the allocated sections.
+    fn pack_non_alloc_sections(&mut self) {
+        let mut file_offset =
+            self.ehdr.e_phoff + segment::Phdr64::SIZE as u64 * self.segments.len() as u64;
+        for sct in self.sections.iter().skip(1) {
+            if layout::is_alloc(&sct.header) && !layout::is_nobits(&sct.header) {
+                file_offset = file_offset.max(sct.header.sh_offset + sct.header.sh_size);
+            }
+        }
+        for sgt in self.segments.iter() {
+            file_offset = file_offset.max(sgt.header.p_offset + sgt.header.p_filesz);
+        }
+
+        self.rebuild_shstrtab();
+        for sct in self.sections.iter_mut().skip(1) {
+            if layout::is_alloc(&sct.header) || layout::is_nobits(&sct.header) {
+                continue;
+            }
+            sct.header.sh_size = sct.contents.size() as u64;
+            file_offset = layout::align_up(file_offset, sct.header.sh_addralign);
+            sct.header.sh_offset = file_offset;
+            file_offset += sct.header.sh_size;
+        }
+
+        self.ehdr.e_shoff = layout::align_up(file_offset, 8);
+        self.ehdr.e_shnum = self.sections.len() as u16;
+    }
+}
+
+fn is_debug_section(name: &str) -> bool {
+    name.starts_with(".debug") || name.starts_with(".zdebug")
+}
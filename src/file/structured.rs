@@ -0,0 +1,183 @@
+//! A machine-readable export of parsed files(requires the `json` feature).
+//!
+//! `ELF64::to_structured()` follows the schema below.
+//! The schema is versioned by `STRUCTURED_SCHEMA_VERSION`;
+//! fields are only added within a version, and renaming or removing one bumps it.
+//!
+//! ```text
+//! {
+//!   "schema": "elf-utilities/structured",
+//!   "version": 1,
+//!   "class": 64,
+//!   "header": {
+//!     "offset": 0, "size": 64,
+//!     "data": 1, "osabi": 0, "abi_version": 0,
+//!     "type": 3, "type_name": "DYN (Shared object file)",
+//!     "machine": 62, "machine_name": "Advanced Micro Devices X86-64",
+//!     "version": 1, "entry": 4160, "phoff": 64, "shoff": 14000, "flags": 0,
+//!     "ehsize": 64, "phentsize": 56, "phnum": 13, "shentsize": 64, "shnum": 31, "shstrndx": 30
+//!   },
+//!   "segments": [
+//!     { "index": 0, "offset": 64, "size": 56,
+//!       "type": 6, "type_name": "PHDR", "flags": 4,
+//!       "p_offset": 64, "vaddr": 64, "paddr": 64, "filesz": 728, "memsz": 728, "align": 8 }
+//!   ],
+//!   "sections": [
+//!     { "index": 1, "name": ".interp", "offset": 14064, "size": 64,
+//!       "type": 1, "type_name": "PROGBITS", "flags": 2, "addr": 792,
+//!       "sh_offset": 792, "sh_size": 28, "link": 0, "info": 0, "addralign": 1, "entsize": 0 }
+//!   ],
+//!   "symbols": [
+//!     { "section": 28, "index": 1, "offset": 12352, "size": 24,
+//!       "name": "crtstuff.c", "value": 0, "st_size": 0,
+//!       "type": 4, "type_name": "FILE", "bind": 0, "bind_name": "LOCAL",
+//!       "visibility": 0, "shndx": 65521 }
+//!   ]
+//! }
+//! ```
+//!
+//! `offset` and `size` of each object are the location of the structure itself in the file,
+//! e.g. the program header, not the segment.
+//! The numeric values are the raw fields, and the `*_name` strings are informative:
+//! they follow the `Display` implementations, which may improve across crate versions.
+//! `sections` and `symbols` are empty if the file has no section header table.
+
+use serde_json::{json, Value};
+
+use crate::*;
+
+/// the value of `"schema"` in `ELF64::to_structured()`
+pub const STRUCTURED_SCHEMA: &str = "elf-utilities/structured";
+/// the value of `"version"` in `ELF64::to_structured()`
+pub const STRUCTURED_SCHEMA_VERSION: u64 = 1;
+
+impl file::ELF64 {
+    /// export the headers and the symbols as JSON, following the schema documented in this module.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use elf_utilities::{file, parser};
+    ///
+    /// let elf = parser::parse_elf64("src/parser/testdata/sample").unwrap();
+    /// let value = elf.to_structured();
+    ///
+    /// assert_eq!(file::STRUCTURED_SCHEMA_VERSION, value["version"].as_u64().unwrap());
+    /// assert_eq!(64, value["header"]["size"]);
+    ///
+    /// // 各構造体のファイルオフセットを持つ
+    /// let text = value["sections"]
+    ///     .as_array()
+    ///     .unwrap()
+    ///     .iter()
+    ///     .find(|sct| sct["name"] == ".text")
+    ///     .unwrap();
+    /// let index = text["index"].as_u64().unwrap();
+    /// assert_eq!(elf.ehdr.e_shoff + index * 64, text["offset"].as_u64().unwrap());
+    /// assert_eq!("PROGBITS", text["type_name"]);
+    /// ```
+    pub fn to_structured(&self) -> Value {
+        let ehdr = &self.ehdr;
+        let ident: [u8; 16] = ehdr.e_ident.into();
+        let header = json!({
+            "offset": 0,
+            "size": header::Ehdr64::SIZE,
+            "data": ident[5],
+            "osabi": ident[7],
+            "abi_version": ehdr.e_ident.abi_version,
+            "type": ehdr.e_type,
+            "type_name": ehdr.get_type().to_string(),
+            "machine": ehdr.e_machine,
+            "machine_name": ehdr.get_machine().to_string(),
+            "version": ehdr.e_version,
+            "entry": ehdr.e_entry,
+            "phoff": ehdr.e_phoff,
+            "shoff": ehdr.e_shoff,
+            "flags": ehdr.e_flags,
+            "ehsize": ehdr.e_ehsize,
+            "phentsize": ehdr.e_phentsize,
+            "phnum": ehdr.e_phnum,
+            "shentsize": ehdr.e_shentsize,
+            "shnum": ehdr.e_shnum,
+            "shstrndx": ehdr.e_shstrndx,
+        });
+
+        let segments: Vec<Value> = self
+            .segments
+            .iter()
+            .enumerate()
+            .map(|(i, sgt)| {
+                let phdr = &sgt.header;
+                json!({
+                    "index": i,
+                    "offset": ehdr.e_phoff + (i * segment::Phdr64::SIZE) as Elf64Off,
+                    "size": segment::Phdr64::SIZE,
+                    "type": phdr.p_type,
+                    "type_name": phdr.get_type().to_string(),
+                    "flags": phdr.p_flags,
+                    "p_offset": phdr.p_offset,
+                    "vaddr": phdr.p_vaddr,
+                    "paddr": phdr.p_paddr,
+                    "filesz": phdr.p_filesz,
+                    "memsz": phdr.p_memsz,
+                    "align": phdr.p_align,
+                })
+            })
+            .collect();
+
+        let mut sections = Vec::new();
+        let mut symbols = Vec::new();
+        // セクションヘッダテーブルが無ければオフセットが定まらない
+        if ehdr.e_shoff != 0 {
+            for (i, sct) in self.sections.iter().enumerate() {
+                let shdr = &sct.header;
+                sections.push(json!({
+                    "index": i,
+                    "name": sct.name.to_string(),
+                    "offset": ehdr.e_shoff + (i * section::Shdr64::SIZE) as Elf64Off,
+                    "size": section::Shdr64::SIZE,
+                    "type": shdr.sh_type,
+                    "type_name": shdr.get_type().to_string(),
+                    "flags": shdr.sh_flags,
+                    "addr": shdr.sh_addr,
+                    "sh_offset": shdr.sh_offset,
+                    "sh_size": shdr.sh_size,
+                    "link": shdr.sh_link,
+                    "info": shdr.sh_info,
+                    "addralign": shdr.sh_addralign,
+                    "entsize": shdr.sh_entsize,
+                }));
+
+                if let section::Contents64::Symbols(syms) = &sct.contents {
+                    for (j, sym) in syms.iter().enumerate() {
+                        symbols.push(json!({
+                            "section": i,
+                            "index": j,
+                            "offset": shdr.sh_offset + (j * symbol::Symbol64::SIZE) as Elf64Off,
+                            "size": symbol::Symbol64::SIZE,
+                            "name": sym.symbol_name.to_string(),
+                            "value": sym.st_value,
+                            "st_size": sym.st_size,
+                            "type": sym.st_info & 0xf,
+                            "type_name": sym.get_type().to_string(),
+                            "bind": sym.st_info >> 4,
+                            "bind_name": sym.get_bind().to_string(),
+                            "visibility": sym.st_other & 0x3,
+                            "shndx": sym.st_shndx,
+                        }));
+                    }
+                }
+            }
+        }
+
+        json!({
+            "schema": STRUCTURED_SCHEMA,
+            "version": STRUCTURED_SCHEMA_VERSION,
+            "class": 64,
+            "header": header,
+            "segments": segments,
+            "sections": sections,
+            "symbols": symbols,
+        })
+    }
+}
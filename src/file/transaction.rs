@@ -0,0 +1,251 @@
+//! Validated, all-or-nothing modifications of ELF files.
+
+use crate::layout::is_nobits;
+use crate::*;
+use thiserror::Error as TError;
+
+#[derive(TError, Debug, Clone, PartialEq, Eq)]
+pub enum ValidationError {
+    #[error("e_shnum is {e_shnum} but the file has {sections} sections")]
+    SectionCount { e_shnum: Elf64Half, sections: usize },
+    #[error("e_phnum is {e_phnum} but the file has {segments} segments")]
+    SegmentCount { e_phnum: Elf64Half, segments: usize },
+    #[error("e_shstrndx {index} is out of range")]
+    InvalidShstrndx { index: Elf64Half },
+    #[error("sh_link {link} of section `{name}` is out of range")]
+    InvalidLink { name: String, link: Elf64Word },
+    #[error("sh_size of section `{name}` is {sh_size:#x} but the contents have {size:#x} bytes")]
+    SizeMismatch {
+        name: String,
+        sh_size: Elf64Xword,
+        size: Elf64Xword,
+    },
+    #[error("section `{first}` overlaps `{second}` in the file")]
+    SectionOverlap { first: String, second: String },
+    #[error("section `{name}` overlaps the section header table")]
+    SectionHeaderOverlap { name: String },
+    #[error("section `{name}` ends beyond the largest file offset")]
+    SectionOverflow { name: String },
+    #[error("the section header table at {e_shoff:#x} ends beyond the largest file offset")]
+    SectionHeaderOverflow { e_shoff: Elf64Off },
+}
+
+#[derive(TError, Debug)]
+pub enum TransactionError<E> {
+    #[error("transaction aborted => `{0}`")]
+    Aborted(E),
+    #[error("transaction produced an invalid file => `{0}`")]
+    Invalid(#[from] ValidationError),
+}
+
+impl file::ELF64 {
+    /// check that `to_le_bytes()` emits a consistent file.
+    ///
+    /// The header fields counting the tables, `e_shstrndx`, `sh_link`
+    /// and `sh_size` are checked against the sections and segments,
+    /// and the sections must not overlap each other or the section header table in the file.
+    /// Empty and `SHT_NOBITS` sections occupy no space.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use elf_utilities::file;
+    ///
+    /// let mut elf = file::ELF64::default();
+    /// assert!(elf.validate().is_ok());
+    ///
+    /// elf.ehdr.e_shstrndx = 5;
+    /// assert!(elf.validate().is_err());
+    /// ```
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        let has_sht = self.ehdr.e_shoff != 0;
+        if has_sht && self.ehdr.e_shnum as usize != self.sections.len() {
+            return Err(ValidationError::SectionCount {
+                e_shnum: self.ehdr.e_shnum,
+                sections: self.sections.len(),
+            });
+        }
+        if self.ehdr.e_phnum as usize != self.segments.len() {
+            return Err(ValidationError::SegmentCount {
+                e_phnum: self.ehdr.e_phnum,
+                segments: self.segments.len(),
+            });
+        }
+        if has_sht && self.ehdr.e_shstrndx as usize >= self.sections.len() {
+            return Err(ValidationError::InvalidShstrndx {
+                index: self.ehdr.e_shstrndx,
+            });
+        }
+
+        for sct in self.sections.iter() {
+            if sct.header.sh_link as usize >= self.sections.len() {
+                return Err(ValidationError::InvalidLink {
+                    name: sct.name.to_string(),
+                    link: sct.header.sh_link,
+                });
+            }
+            let size = sct.contents.size() as Elf64Xword;
+            if !is_nobits(&sct.header) && sct.header.sh_size != size {
+                return Err(ValidationError::SizeMismatch {
+                    name: sct.name.to_string(),
+                    sh_size: sct.header.sh_size,
+                    size,
+                });
+            }
+        }
+
+        // ファイル上の領域をオフセット順に並べて隣同士を比べる
+        let mut ranges: Vec<(Elf64Off, Elf64Off, &str)> = self
+            .sections
+            .iter()
+            .filter(|sct| !is_nobits(&sct.header) && sct.header.sh_size != 0)
+            .map(|sct| {
+                let start = sct.header.sh_offset;
+                let end = start.checked_add(sct.header.sh_size).ok_or_else(|| {
+                    ValidationError::SectionOverflow {
+                        name: sct.name.to_string(),
+                    }
+                })?;
+                Ok((start, end, sct.name.as_str()))
+            })
+            .collect::<Result<_, _>>()?;
+        ranges.sort_unstable();
+        for pair in ranges.windows(2) {
+            if pair[1].0 < pair[0].1 {
+                return Err(ValidationError::SectionOverlap {
+                    first: pair[0].2.to_string(),
+                    second: pair[1].2.to_string(),
+                });
+            }
+        }
+        if has_sht {
+            let start = self.ehdr.e_shoff;
+            let end = start
+                .checked_add((self.sections.len() * section::Shdr64::SIZE) as Elf64Off)
+                .ok_or(ValidationError::SectionHeaderOverflow { e_shoff: start })?;
+            if let Some((_, _, name)) = ranges.iter().find(|(s, e, _)| *s < end && start < *e) {
+                return Err(ValidationError::SectionHeaderOverlap {
+                    name: name.to_string(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// modify a copy of the file by `f`, and replace the file with it if `validate()` passes.
+    ///
+    /// If `f` fails or the result is invalid, the file is left untouched,
+    /// so an error in the middle of the edits never leaves a half-modified file.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use elf_utilities::{builder, file, transform};
+    ///
+    /// let main = builder::ExportedFunction::new("main", vec![0xc3]);
+    /// let mut elf = builder::ExecutableWriter::new()
+    ///     .function(main)
+    ///     .start_stub(builder::start_stub("main"))
+    ///     .build()
+    ///     .unwrap();
+    /// let original = elf.clone();
+    ///
+    /// let result = elf.transaction(|tx| {
+    ///     tx.set_executable_stack(true);
+    ///     // fails since the file has no dynamic symbols
+    ///     transform::redirect_import(tx, "malloc", "my_malloc")
+    /// });
+    /// assert!(matches!(result, Err(file::TransactionError::Aborted(_))));
+    /// assert_eq!(original, elf);
+    ///
+    /// let result = elf.transaction(|tx| {
+    ///     tx.ehdr.e_phnum += 1;
+    ///     Ok::<(), ()>(())
+    /// });
+    /// assert!(matches!(result, Err(file::TransactionError::Invalid(_))));
+    /// assert_eq!(original, elf);
+    /// ```
+    pub fn transaction<F, T, E>(&mut self, f: F) -> Result<T, TransactionError<E>>
+    where
+        F: FnOnce(&mut file::ELF64) -> Result<T, E>,
+    {
+        let mut staged = self.clone();
+        let v = f(&mut staged).map_err(TransactionError::Aborted)?;
+        staged.validate()?;
+        *self = staged;
+        Ok(v)
+    }
+}
+
+#[cfg(test)]
+mod transaction_tests {
+    use super::*;
+
+    #[test]
+    fn transaction_test() {
+        let f = || builder::ExportedFunction::new("f", vec![0xc3]);
+        let so = builder::SharedObjectWriter::new()
+            .function(f())
+            .build()
+            .unwrap();
+        assert_eq!(Ok(()), so.validate());
+        let exe = |tiny| {
+            builder::ExecutableWriter::new()
+                .function(f())
+                .entry("f")
+                .tiny(tiny)
+                .build()
+                .unwrap()
+        };
+        assert_eq!(Ok(()), exe(true).validate());
+
+        let mut elf = exe(false);
+        assert_eq!(Ok(()), elf.validate());
+        let removed = elf.transaction(|tx| Ok::<_, ()>(tx.strip())).unwrap();
+        assert_eq!(vec![".symtab", ".strtab"], removed);
+        assert!(elf.first_section_by(|sct| sct.name == ".symtab").is_none());
+
+        // .textを.shstrtabに重ねる
+        let shstrtab = &elf.sections[elf.ehdr.e_shstrndx as usize];
+        let offset = shstrtab.header.sh_offset;
+        let err = elf
+            .transaction(|tx| {
+                tx.first_mut_section_by(|sct| sct.name == ".text")
+                    .unwrap()
+                    .header
+                    .sh_offset = offset;
+                Ok::<_, ()>(())
+            })
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            TransactionError::Invalid(ValidationError::SectionOverlap { .. })
+        ));
+        assert_ne!(
+            offset,
+            elf.first_section_by(|sct| sct.name == ".text")
+                .unwrap()
+                .header
+                .sh_offset
+        );
+
+        // オフセットが溢れるものは壊れている
+        let mut broken = elf.clone();
+        broken.sections[1].header.sh_offset = u64::MAX;
+        assert_eq!(
+            Err(ValidationError::SectionOverflow {
+                name: broken.sections[1].name.to_string()
+            }),
+            broken.validate()
+        );
+        let mut broken = elf.clone();
+        broken.ehdr.e_shoff = u64::MAX - 0x10;
+        assert_eq!(
+            Err(ValidationError::SectionHeaderOverflow {
+                e_shoff: u64::MAX - 0x10
+            }),
+            broken.validate()
+        );
+    }
+}
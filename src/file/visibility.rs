@@ -0,0 +1,275 @@
+//! Trimming the exported symbols of linked files.
+
+use crate::*;
+
+const SHT_GNU_HASH: Elf64Word = 0x6ffffff6;
+const SHT_GNU_VERSYM: Elf64Word = 0x6fffffff;
+
+/// The symbols changed by `ELF64::hide_symbols()`
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct HiddenSymbols {
+    /// the symbols which became `STV_HIDDEN`
+    pub hidden: Vec<String>,
+    /// the symbols removed from `.dynsym`
+    pub removed: Vec<String>,
+}
+
+impl file::ELF64 {
+    /// make the dynamic symbols matching `pattern` hidden, like a version script's `local:` after linking.
+    ///
+    /// `pattern` is a glob where `*` matches any string and `?` matches any character.
+    /// Only defined symbols with `STV_DEFAULT` or `STV_PROTECTED` are changed,
+    /// and the same symbols in `.symtab` are hidden too.
+    ///
+    /// If `remove` is true, the hidden symbols are also removed from `.dynsym`
+    /// so that the dynamic linker can't find them at all.
+    /// The relocations, `.gnu.version`, `.gnu.hash` and `.hash` are rewritten in place,
+    /// since they only shrink.
+    /// Symbols referenced by dynamic relocations are kept, because the relocations still need them.
+    /// Removing symbols also drops the prelink information(see `ELF64::invalidate_prelink()`),
+    /// because the prelinked values were resolved with the old symbol table.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use elf_utilities::{builder, section};
+    ///
+    /// let mut elf = builder::SharedObjectWriter::new()
+    ///     .function(builder::ExportedFunction::new("api", vec![0xc3]))
+    ///     .function(builder::ExportedFunction::new("internal_f", vec![0xc3]))
+    ///     .build()
+    ///     .unwrap();
+    ///
+    /// let changed = elf.hide_symbols("internal_*", true);
+    /// assert_eq!(vec!["internal_f"], changed.hidden);
+    /// assert_eq!(vec!["internal_f"], changed.removed);
+    ///
+    /// let dynsym = elf.first_section_by(|sct| sct.name == ".dynsym").unwrap();
+    /// if let section::Contents64::Symbols(syms) = &dynsym.contents {
+    ///     assert!(syms.iter().all(|sym| sym.symbol_name != "internal_f"));
+    /// }
+    /// ```
+    pub fn hide_symbols(&mut self, pattern: &str, remove: bool) -> HiddenSymbols {
+        self.hide_symbols_by(|name| glob_match(pattern, name), remove)
+    }
+
+    /// `hide_symbols()` with a predicate on the names instead of a glob.
+    pub(crate) fn hide_symbols_by<F>(&mut self, hide: F, remove: bool) -> HiddenSymbols
+    where
+        F: Fn(&str) -> bool,
+    {
+        let mut changed = HiddenSymbols::default();
+        let dynsym = match self.first_shidx_by(|sct| sct.header.get_type() == section::Type::DynSym)
+        {
+            Some(idx) => idx,
+            None => return changed,
+        };
+
+        let mut matched = Vec::new();
+        if let section::Contents64::Symbols(syms) = &mut self.sections[dynsym].contents {
+            for (i, sym) in syms.iter_mut().enumerate().skip(1) {
+                if !is_exported(sym) || !hide(&sym.symbol_name) {
+                    continue;
+                }
+                sym.set_visibility(symbol::Visibility::Hidden);
+                changed.hidden.push(sym.symbol_name.to_string());
+                matched.push(i);
+            }
+        }
+        for sct in self.sections.iter_mut() {
+            if sct.header.get_type() != section::Type::SymTab {
+                continue;
+            }
+            if let section::Contents64::Symbols(syms) = &mut sct.contents {
+                for sym in syms.iter_mut() {
+                    if is_exported(sym)
+                        && changed.hidden.iter().any(|name| *name == sym.symbol_name)
+                    {
+                        sym.set_visibility(symbol::Visibility::Hidden);
+                    }
+                }
+            }
+        }
+
+        if remove {
+            let referenced = self.dynamic_relocation_symbols(dynsym);
+            matched.retain(|i| !referenced.contains(&(*i as Elf64Xword)));
+            changed.removed = self.remove_dynamic_symbols(dynsym, &matched);
+            if !changed.removed.is_empty() {
+                self.invalidate_prelink();
+            }
+        }
+        changed
+    }
+
+    /// the symbol indices referenced by the relocations against `.dynsym`.
+    fn dynamic_relocation_symbols(&self, dynsym: usize) -> Vec<Elf64Xword> {
+        self.sections
+            .iter()
+            .filter(|sct| sct.header.sh_link as usize == dynsym)
+            .filter_map(|sct| match &sct.contents {
+                section::Contents64::RelaSymbols(relas) => Some(relas),
+                _ => None,
+            })
+            .flat_map(|relas| relas.iter().map(|rela| rela.get_sym()))
+            .collect()
+    }
+
+    /// remove the symbols from `.dynsym` and rewrite the sections indexing it.
+    fn remove_dynamic_symbols(&mut self, dynsym: usize, removed: &[usize]) -> Vec<String> {
+        if removed.is_empty() {
+            return Vec::new();
+        }
+        self.invalidate_address_index();
+        let mut table = match symbol::SymbolTable::from_section(&self.sections[dynsym]) {
+            Some(table) => table,
+            None => return Vec::new(),
+        };
+        let current: Vec<usize> = removed
+            .iter()
+            .filter_map(|idx| table.new_index(*idx))
+            .collect();
+        let names = table
+            .remove(&current)
+            .into_iter()
+            .map(|sym| sym.symbol_name.into())
+            .collect();
+        let kept_names: Vec<String> = table
+            .symbols()
+            .iter()
+            .map(|sym| sym.symbol_name.to_string())
+            .collect();
+        let kept_names: Vec<&str> = kept_names.iter().map(|n| n.as_str()).collect();
+        let new_index = table.index_map();
+        self.update_symbol_table(dynsym, table);
+
+        for sct in self.sections.iter_mut() {
+            if sct.header.sh_link as usize != dynsym {
+                continue;
+            }
+            let ty = sct.header.get_type();
+            match &mut sct.contents {
+                section::Contents64::Raw(bytes) if ty == section::Type::Any(SHT_GNU_VERSYM) => {
+                    // 各シンボルのバージョンを新しい位置に移す
+                    let mut versym = vec![0; kept_names.len() * 2];
+                    for (old, new) in new_index.iter().enumerate() {
+                        if let (Some(new), Some(ver)) = (new, bytes.get(old * 2..old * 2 + 2)) {
+                            versym[new * 2..new * 2 + 2].copy_from_slice(ver);
+                        }
+                    }
+                    *bytes = versym;
+                }
+                section::Contents64::Raw(bytes) if ty == section::Type::Any(SHT_GNU_HASH) => {
+                    if let Some(mut table) = section::GnuHash64::parse(bytes) {
+                        let symoffset = table.symoffset as usize;
+                        table.symoffset -=
+                            removed.iter().filter(|i| **i < symoffset).count() as Elf64Word;
+                        let hashed = kept_names.get(table.symoffset as usize..).unwrap_or(&[]);
+                        *bytes = table.rehash(hashed).to_le_bytes();
+                    }
+                }
+                section::Contents64::Raw(bytes) if ty == section::Type::Hash => {
+                    *bytes = rehash_sysv(bytes, &kept_names);
+                }
+                _ => continue,
+            }
+            sct.header.sh_size = sct.contents.size() as Elf64Xword;
+        }
+
+        names
+    }
+}
+
+/// whether the symbol is defined and visible from the other modules.
+fn is_exported(sym: &symbol::Symbol64) -> bool {
+    sym.st_shndx != section::SHN_UNDEF
+        && matches!(
+            sym.get_visibility(),
+            symbol::Visibility::Default | symbol::Visibility::Protected
+        )
+        && matches!(sym.get_bind(), symbol::Bind::Global | symbol::Bind::Weak)
+}
+
+/// match `name` against a glob which supports `*` and `?`.
+pub(crate) fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // 最後の`*`の位置と，それがマッチし始めた位置
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((sp, sn)) => {
+                    p = sp + 1;
+                    n = sn + 1;
+                    star = Some((sp, sn + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// the hash function of `.hash`
+pub(crate) fn sysv_hash(name: &str) -> u32 {
+    name.bytes().fold(0u32, |h, c| {
+        let h = (h << 4).wrapping_add(c as u32);
+        let g = h & 0xf0000000;
+        (h ^ g >> 24) & !g
+    })
+}
+
+/// rebuild `.hash` for `names` with the same number of buckets.
+fn rehash_sysv(bytes: &[u8], names: &[&str]) -> Vec<u8> {
+    let nbucket = match bytes.get(..4) {
+        Some(b) => u32::from_le_bytes([b[0], b[1], b[2], b[3]]).max(1),
+        None => 1,
+    };
+    let mut buckets = vec![0u32; nbucket as usize];
+    let mut chains = vec![0u32; names.len()];
+    // 0番目のシンボルはハッシュしない
+    for (i, name) in names.iter().enumerate().skip(1).rev() {
+        let bucket = (sysv_hash(name) % nbucket) as usize;
+        chains[i] = buckets[bucket];
+        buckets[bucket] = i as u32;
+    }
+
+    let mut out = Vec::with_capacity((2 + buckets.len() + chains.len()) * 4);
+    out.extend_from_slice(&nbucket.to_le_bytes());
+    out.extend_from_slice(&(names.len() as u32).to_le_bytes());
+    for v in buckets.iter().chain(chains.iter()) {
+        out.extend_from_slice(&v.to_le_bytes());
+    }
+    out
+}
+
+#[cfg(test)]
+mod visibility_tests {
+    use super::*;
+
+    #[test]
+    fn glob_match_test() {
+        assert!(glob_match("*", ""));
+        assert!(glob_match("internal_*", "internal_f"));
+        assert!(glob_match("*_impl", "foo_bar_impl"));
+        assert!(glob_match("f?o*b", "fooxxb"));
+        assert!(!glob_match("f?o", "fo"));
+        assert!(!glob_match("internal_*", "api"));
+    }
+
+    #[test]
+    fn sysv_hash_test() {
+        assert_eq!(0, sysv_hash(""));
+        assert_eq!(0x077905a6, sysv_hash("printf"));
+    }
+}
@@ -0,0 +1,36 @@
+//! `Debug` helper which renders flag fields with symbolic names.
+
+use std::fmt;
+
+/// renders `bits` as `Write | Alloc`.
+/// unknown bits are appended in hex, and an empty set is rendered as `0`.
+pub(crate) struct FlagsDebug<F: 'static> {
+    pub bits: u64,
+    pub all: &'static [F],
+    pub to_bits: fn(F) -> u64,
+}
+
+impl<F: fmt::Debug + Copy> fmt::Debug for FlagsDebug<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut rest = self.bits;
+        let mut first = true;
+        for flag in self.all.iter() {
+            let bit = (self.to_bits)(*flag);
+            if rest & bit == 0 {
+                continue;
+            }
+            rest &= !bit;
+            if !first {
+                write!(f, " | ")?;
+            }
+            write!(f, "{:?}", flag)?;
+            first = false;
+        }
+
+        match (first, rest) {
+            (true, _) => write!(f, "{:#x}", rest),
+            (false, 0) => Ok(()),
+            (false, _) => write!(f, " | {:#x}", rest),
+        }
+    }
+}
@@ -1,9 +1,12 @@
+//! ELF header utilities.
+
 mod base;
 mod class;
 mod data;
 mod elf32;
 mod elf64;
 mod elf_type;
+mod ident;
 mod machine;
 mod osabi;
 mod version;
@@ -15,6 +18,7 @@ pub use data::*;
 pub use elf32::*;
 pub use elf64::*;
 pub use elf_type::*;
+pub use ident::*;
 pub use machine::*;
 pub use osabi::*;
 pub use version::*;
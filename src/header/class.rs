@@ -1,4 +1,6 @@
-#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Class {
     // invalid class
     None,
@@ -36,3 +38,15 @@ impl From<u8> for Class {
         }
     }
 }
+
+/// Display as readelf does.
+impl fmt::Display for Class {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.to_identifier() {
+            0 => write!(f, "none"),
+            1 => write!(f, "ELF32"),
+            2 => write!(f, "ELF64"),
+            v => write!(f, "<unknown: {:x}>", v),
+        }
+    }
+}
@@ -1,3 +1,6 @@
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Data {
     // invalid data encoding
     None,
@@ -35,3 +38,15 @@ impl From<u8> for Data {
         }
     }
 }
+
+/// Display as readelf does.
+impl fmt::Display for Data {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.to_identifier() {
+            0 => write!(f, "none"),
+            1 => write!(f, "2's complement, little endian"),
+            2 => write!(f, "2's complement, big endian"),
+            v => write!(f, "<unknown: {:x}>", v),
+        }
+    }
+}
@@ -1,11 +1,11 @@
-use crate::header::{class, data, elf_type, machine, osabi, version};
+use crate::header::{class, data, elf_type, machine, osabi, version, Ident};
 use crate::*;
 use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Copy, Hash, PartialOrd, Ord, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Hash, PartialOrd, Ord, PartialEq, Eq, Serialize, Deserialize)]
 #[repr(C)]
 pub struct Ehdr32 {
-    pub e_ident: [u8; 16],
+    pub e_ident: Ident,
     pub e_type: Elf32Half,
     pub e_machine: Elf32Half,
     pub e_version: Elf32Word,
@@ -24,21 +24,18 @@ pub struct Ehdr32 {
 impl Default for Ehdr32 {
     fn default() -> Self {
         Self {
-            e_ident: [
-                0x7f, 0x45, 0x4c, 0x46, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-                0x00, 0x00,
-            ],
+            e_ident: Ident::default(),
             e_type: 0,
             e_machine: 0,
             e_version: 0,
             e_entry: 0,
-            e_phoff: 0,
-            e_shoff: 0,
+            e_phoff: Self::SIZE as Elf32Off,
+            e_shoff: Self::SIZE as Elf32Off,
             e_flags: 0,
-            e_ehsize: 0,
-            e_phentsize: 0,
+            e_ehsize: Self::SIZE,
+            e_phentsize: segment::Phdr32::SIZE as Elf32Half,
             e_phnum: 0,
-            e_shentsize: 0,
+            e_shentsize: section::Shdr32::SIZE as Elf32Half,
             e_shnum: 0,
             e_shstrndx: 0,
         }
@@ -49,13 +46,13 @@ impl Ehdr32 {
     pub const SIZE: Elf32Half = 52;
 
     pub fn get_class(&self) -> class::Class {
-        class::Class::from(self.e_ident[class::Class::INDEX])
+        self.e_ident.class
     }
     pub fn get_data(&self) -> data::Data {
-        data::Data::from(self.e_ident[data::Data::INDEX])
+        self.e_ident.data
     }
     pub fn get_file_version(&self) -> version::Version {
-        version::Version::from(self.e_ident[version::Version::INDEX])
+        self.e_ident.version
     }
     pub fn get_object_version(&self) -> version::Version {
         version::Version::from(self.e_version)
@@ -67,22 +64,22 @@ impl Ehdr32 {
         machine::Machine::from(self.e_machine)
     }
     pub fn get_osabi(&self) -> osabi::OSABI {
-        osabi::OSABI::from(self.e_ident[osabi::OSABI::INDEX])
+        self.e_ident.osabi
     }
     pub fn set_class(&mut self, c: class::Class) {
-        self.e_ident[class::Class::INDEX] = c.to_identifier();
+        self.e_ident.class = c;
     }
     pub fn set_data(&mut self, d: data::Data) {
-        self.e_ident[data::Data::INDEX] = d.to_identifier();
+        self.e_ident.data = d;
     }
     pub fn set_file_version(&mut self, v: version::Version) {
-        self.e_ident[version::Version::INDEX] = v.to_identifier();
+        self.e_ident.version = v;
     }
     pub fn set_object_version(&mut self, v: version::Version) {
         self.e_version = v.to_object_version();
     }
     pub fn set_osabi(&mut self, o: osabi::OSABI) {
-        self.e_ident[osabi::OSABI::INDEX] = o.to_identifier();
+        self.e_ident.osabi = o;
     }
     pub fn set_elf_type(&mut self, e_type: elf_type::Type) {
         self.e_type = e_type.to_bytes();
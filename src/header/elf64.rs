@@ -1,11 +1,11 @@
-use crate::header::{class, data, elf_type, machine, osabi, version};
+use crate::header::{class, data, elf_type, machine, osabi, version, Ident};
 use crate::*;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Copy, Hash, PartialOrd, Ord, PartialEq, Eq, Serialize, Deserialize)]
 #[repr(C)]
 pub struct Ehdr64 {
-    pub e_ident: [u8; 16],
+    pub e_ident: Ident,
     pub e_type: Elf64Half,
     pub e_machine: Elf64Half,
     pub e_version: Elf64Word,
@@ -24,10 +24,7 @@ pub struct Ehdr64 {
 impl Default for Ehdr64 {
     fn default() -> Self {
         Self {
-            e_ident: [
-                0x7f, 0x45, 0x4c, 0x46, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-                0x00, 0x00,
-            ],
+            e_ident: Ident::default(),
             e_type: 0,
             e_machine: 0,
             e_version: 0,
@@ -50,13 +47,13 @@ impl Ehdr64 {
     pub const SIZE: Elf64Half = 0x40;
 
     pub fn get_class(&self) -> class::Class {
-        class::Class::from(self.e_ident[class::Class::INDEX])
+        self.e_ident.class
     }
     pub fn get_data(&self) -> data::Data {
-        data::Data::from(self.e_ident[data::Data::INDEX])
+        self.e_ident.data
     }
     pub fn get_file_version(&self) -> version::Version {
-        version::Version::from(self.e_ident[version::Version::INDEX])
+        self.e_ident.version
     }
     pub fn get_object_version(&self) -> version::Version {
         version::Version::from(self.e_version)
@@ -68,22 +65,22 @@ impl Ehdr64 {
         machine::Machine::from(self.e_machine)
     }
     pub fn get_osabi(&self) -> osabi::OSABI {
-        osabi::OSABI::from(self.e_ident[osabi::OSABI::INDEX])
+        self.e_ident.osabi
     }
     pub fn set_class(&mut self, c: class::Class) {
-        self.e_ident[class::Class::INDEX] = c.to_identifier();
+        self.e_ident.class = c;
     }
     pub fn set_data(&mut self, d: data::Data) {
-        self.e_ident[data::Data::INDEX] = d.to_identifier();
+        self.e_ident.data = d;
     }
     pub fn set_file_version(&mut self, v: version::Version) {
-        self.e_ident[version::Version::INDEX] = v.to_identifier();
+        self.e_ident.version = v;
     }
     pub fn set_object_version(&mut self, v: version::Version) {
         self.e_version = v.to_object_version();
     }
     pub fn set_osabi(&mut self, o: osabi::OSABI) {
-        self.e_ident[osabi::OSABI::INDEX] = o.to_identifier();
+        self.e_ident.osabi = o;
     }
     pub fn set_elf_type(&mut self, e_type: elf_type::Type) {
         self.e_type = e_type.to_bytes();
@@ -1,4 +1,6 @@
-#[derive(Debug, Eq, Ord, PartialEq, PartialOrd)]
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, Hash, PartialOrd, Ord, PartialEq, Eq)]
 pub enum Type {
     /// No file type
     None,
@@ -59,3 +61,29 @@ impl From<u16> for Type {
         }
     }
 }
+
+/// Display as readelf does.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::header;
+///
+/// assert_eq!("DYN (Shared object file)", header::Type::Dyn.to_string());
+/// assert_eq!("UNIX - System V", header::OSABI::SysV.to_string());
+/// assert_eq!("Advanced Micro Devices X86-64", header::Machine::X8664.to_string());
+/// ```
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.to_bytes() {
+            0 => write!(f, "NONE (None)"),
+            1 => write!(f, "REL (Relocatable file)"),
+            2 => write!(f, "EXEC (Executable file)"),
+            3 => write!(f, "DYN (Shared object file)"),
+            4 => write!(f, "CORE (Core file)"),
+            v @ 0xfe00..=0xfeff => write!(f, "OS Specific: ({:x})", v),
+            v @ 0xff00..=0xffff => write!(f, "Processor Specific: ({:x})", v),
+            v => write!(f, "<unknown>: {:x}", v),
+        }
+    }
+}
@@ -0,0 +1,105 @@
+//! Type definitions for the ELF identification(`e_ident`).
+
+use crate::header::{Class, Data, Version, OSABI};
+use serde::{Deserialize, Serialize};
+
+/// `\x7fELF`
+pub const ELF_MAGIC: [u8; 4] = [0x7f, 0x45, 0x4c, 0x46];
+
+/// ELF identification placed at the start of the ELF header.
+///
+/// It's (de)serialized as 16 raw bytes, so the layout of `Ehdr32`/`Ehdr64` is unchanged.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::header::{Class, Data, Ident, Version};
+///
+/// let ident = Ident::from([
+///     0x7f, 0x45, 0x4c, 0x46, 0x02, 0x01, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+/// ]);
+/// assert_eq!(Class::Bit64, ident.class);
+/// assert_eq!(Data::LSB2, ident.data);
+/// assert_eq!(Version::Current, ident.version);
+///
+/// // u128 shims keep the first byte in the most significant bits.
+/// assert_eq!(0x7f454c46_02010100_00000000_00000000, u128::from(ident));
+/// assert_eq!(ident, Ident::from(0x7f454c46_02010100_00000000_00000000u128));
+/// ```
+#[derive(Debug, Clone, Copy, Hash, PartialOrd, Ord, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "[u8; 16]", into = "[u8; 16]")]
+pub struct Ident {
+    pub magic: [u8; 4],
+    pub class: Class,
+    pub data: Data,
+    pub version: Version,
+    pub osabi: OSABI,
+    pub abi_version: u8,
+    pub pad: [u8; 7],
+}
+
+impl Ident {
+    /// the size of e_ident
+    pub const SIZE: usize = 16;
+    pub const ABI_VERSION_INDEX: usize = 8;
+    pub const PAD_INDEX: usize = 9;
+
+    pub fn to_bytes(&self) -> [u8; 16] {
+        (*self).into()
+    }
+
+    /// whether the magic number is `\x7fELF`
+    pub fn is_elf(&self) -> bool {
+        self.magic == ELF_MAGIC
+    }
+}
+
+impl Default for Ident {
+    fn default() -> Self {
+        let mut bytes = [0; Self::SIZE];
+        bytes[..4].copy_from_slice(&ELF_MAGIC);
+        Self::from(bytes)
+    }
+}
+
+impl From<[u8; 16]> for Ident {
+    fn from(bytes: [u8; 16]) -> Self {
+        let mut pad = [0; 7];
+        pad.copy_from_slice(&bytes[Self::PAD_INDEX..]);
+        Self {
+            magic: [bytes[0], bytes[1], bytes[2], bytes[3]],
+            class: Class::from(bytes[Class::INDEX]),
+            data: Data::from(bytes[Data::INDEX]),
+            version: Version::from(bytes[Version::INDEX]),
+            osabi: OSABI::from(bytes[OSABI::INDEX]),
+            abi_version: bytes[Self::ABI_VERSION_INDEX],
+            pad,
+        }
+    }
+}
+
+impl From<Ident> for [u8; 16] {
+    fn from(ident: Ident) -> Self {
+        let mut bytes = [0; Ident::SIZE];
+        bytes[..4].copy_from_slice(&ident.magic);
+        bytes[Class::INDEX] = ident.class.to_identifier();
+        bytes[Data::INDEX] = ident.data.to_identifier();
+        bytes[Version::INDEX] = ident.version.to_identifier();
+        bytes[OSABI::INDEX] = ident.osabi.to_identifier();
+        bytes[Ident::ABI_VERSION_INDEX] = ident.abi_version;
+        bytes[Ident::PAD_INDEX..].copy_from_slice(&ident.pad);
+        bytes
+    }
+}
+
+impl From<u128> for Ident {
+    fn from(v: u128) -> Self {
+        Self::from(v.to_be_bytes())
+    }
+}
+
+impl From<Ident> for u128 {
+    fn from(ident: Ident) -> Self {
+        u128::from_be_bytes(ident.into())
+    }
+}
@@ -1,6 +1,7 @@
 use crate::*;
+use std::fmt;
 
-#[derive(Debug, Eq, Ord, PartialEq, PartialOrd)]
+#[derive(Debug, Clone, Copy, Hash, PartialOrd, Ord, PartialEq, Eq)]
 pub enum Machine {
     // No machine
     None,
@@ -185,18 +186,288 @@ pub enum Machine {
 impl Machine {
     pub fn to_bytes(&self) -> Elf64Half {
         match self {
+            Self::None => 0,
+            Self::M32 => 1,
+            Self::SPARC => 2,
+            Self::Intel386 => 3,
+            Self::M68K => 4,
+            Self::M88K => 5,
+            Self::IntelMCU => 6,
+            Self::Intel80860 => 7,
+            Self::MIPS => 8,
+            Self::S370 => 9,
+            Self::MIPSRS3LE => 10,
+            Self::Parisc => 15,
+            Self::VPP500 => 17,
+            Self::SPARC32Plus => 18,
+            Self::Intel80960 => 19,
+            Self::PowerPC => 20,
+            Self::PowerPC65 => 21,
+            Self::S390 => 22,
+            Self::SPU => 23,
+            Self::V800 => 36,
+            Self::FR20 => 37,
+            Self::RH32 => 38,
+            Self::RCE => 39,
+            Self::Arm => 40,
+            Self::FakeAlpha => 41,
+            Self::EMSH => 42,
+            Self::EMSPARCV9 => 43,
+            Self::Tricore => 44,
+            Self::ARC => 45,
+            Self::H8300 => 46,
+            Self::H8300H => 47,
+            Self::H8S => 48,
+            Self::H8500 => 49,
+            Self::MIPSX => 51,
+            Self::Coldfire => 52,
+            Self::M68HC12 => 53,
+            Self::MMA => 54,
+            Self::PCP => 55,
+            Self::NCPU => 56,
+            Self::NDR1 => 57,
+            Self::StarCore => 58,
+            Self::ME16 => 59,
+            Self::ST100 => 60,
+            Self::TinyJ => 61,
             Self::X8664 => 62,
+            Self::PSDP => 63,
+            Self::PDP10 => 64,
+            Self::PDP11 => 65,
+            Self::FX66 => 66,
+            Self::ST9Plus => 67,
+            Self::ST7 => 68,
+            Self::MC68HC16 => 69,
+            Self::MC68HC11 => 70,
+            Self::MC68HC08 => 71,
+            Self::MC68HC05 => 72,
+            Self::SVx => 73,
+            Self::ST19 => 74,
+            Self::VAX => 75,
+            Self::CRIS => 76,
+            Self::Javelin => 77,
+            Self::Firepath => 78,
+            Self::ZSP => 79,
+            Self::MMIX => 80,
+            Self::HUANY => 81,
+            Self::Prism => 82,
+            Self::AVR => 83,
+            Self::FR30 => 84,
+            Self::D10V => 85,
+            Self::D30V => 86,
+            Self::V850 => 87,
+            Self::M32R => 88,
+            Self::MN10300 => 89,
+            Self::MN10200 => 90,
+            Self::PicoJava => 91,
+            Self::OR1K => 92,
+            Self::ARCompact => 93,
+            Self::Xtensa => 94,
+            Self::SCoreOld => 95,
+            Self::VideoCore => 95,
+            Self::TMMGPP => 96,
+            Self::NS32K => 97,
+            Self::TPC => 98,
+            Self::PicoJavaOld => 99,
+            Self::SNP1K => 99,
+            Self::ST200 => 100,
             Self::Any(c) => *c,
-            _ => panic!("not implemented -> {:?}", self),
         }
     }
+
+    /// the name used by readelf.
+    fn readelf_name(v: Elf64Half) -> Option<&'static str> {
+        Some(match v {
+            0 => "None",
+            1 => "WE32100",
+            2 => "Sparc",
+            3 => "Intel 80386",
+            4 => "MC68000",
+            5 => "MC88000",
+            6 => "Intel MCU",
+            7 => "Intel 80860",
+            8 => "MIPS R3000",
+            9 => "IBM System/370",
+            10 => "MIPS R4000 big-endian",
+            15 => "HPPA",
+            17 => "Fujitsu VPP500",
+            18 => "Sparc v8+",
+            19 => "Intel 80960",
+            20 => "PowerPC",
+            21 => "PowerPC64",
+            22 => "IBM S/390",
+            23 => "SPU",
+            36 => "Renesas V850 (using RH850 ABI)",
+            37 => "Fujitsu FR20",
+            38 => "TRW RH32",
+            39 => "MCORE",
+            40 => "ARM",
+            41 => "Digital Alpha (old)",
+            42 => "Renesas / SuperH SH",
+            43 => "Sparc v9",
+            44 => "Siemens Tricore",
+            45 => "ARC",
+            46 => "Renesas H8/300",
+            47 => "Renesas H8/300H",
+            48 => "Renesas H8S",
+            49 => "Renesas H8/500",
+            50 => "Intel IA-64",
+            51 => "Stanford MIPS-X",
+            52 => "Motorola Coldfire",
+            53 => "Motorola MC68HC12 Microcontroller",
+            54 => "Fujitsu Multimedia Accelerator",
+            55 => "Siemens PCP",
+            56 => "Sony nCPU embedded RISC processor",
+            57 => "Denso NDR1 microprocesspr",
+            58 => "Motorola Star*Core processor",
+            59 => "Toyota ME16 processor",
+            60 => "STMicroelectronics ST100 processor",
+            61 => "Advanced Logic Corp. TinyJ embedded processor",
+            62 => "Advanced Micro Devices X86-64",
+            63 => "Sony DSP processor",
+            64 => "Digital Equipment Corp. PDP-10",
+            65 => "Digital Equipment Corp. PDP-11",
+            66 => "Siemens FX66 microcontroller",
+            67 => "STMicroelectronics ST9+ 8/16 bit microcontroller",
+            68 => "STMicroelectronics ST7 8-bit microcontroller",
+            69 => "Motorola MC68HC16 Microcontroller",
+            70 => "Motorola MC68HC11 Microcontroller",
+            71 => "Motorola MC68HC08 Microcontroller",
+            72 => "Motorola MC68HC05 Microcontroller",
+            73 => "Silicon Graphics SVx",
+            74 => "STMicroelectronics ST19 8-bit microcontroller",
+            75 => "Digital VAX",
+            76 => "Axis Communications 32-bit embedded processor",
+            77 => "Infineon Technologies 32-bit embedded cpu",
+            78 => "Element 14 64-bit DSP processor",
+            79 => "LSI Logic's 16-bit DSP processor",
+            80 => "Donald Knuth's educational 64-bit processor",
+            81 => "Harvard Universitys's machine-independent object format",
+            82 => "Vitesse Prism",
+            83 => "Atmel AVR 8-bit microcontroller",
+            84 => "Fujitsu FR30",
+            85 => "d10v",
+            86 => "d30v",
+            87 => "Renesas V850",
+            88 => "Renesas M32R (formerly Mitsubishi M32r)",
+            89 => "mn10300",
+            90 => "mn10200",
+            91 => "picoJava",
+            92 => "OpenRISC 1000",
+            93 => "ARCompact",
+            94 => "Tensilica Xtensa Processor",
+            95 => "Alphamosaic VideoCore processor",
+            96 => "Thompson Multimedia General Purpose Processor",
+            97 => "National Semiconductor 32000 series",
+            98 => "Tenor Network TPC processor",
+            99 => "Trebia SNP 1000 processor",
+            100 => "STMicroelectronics ST200 microcontroller",
+            183 => "AArch64",
+            243 => "RISC-V",
+            247 => "Linux BPF",
+            258 => "LoongArch",
+            _ => return None,
+        })
+    }
 }
 
 impl From<Elf64Half> for Machine {
     fn from(bytes: Elf64Half) -> Self {
         match bytes {
+            0 => Self::None,
+            1 => Self::M32,
+            2 => Self::SPARC,
+            3 => Self::Intel386,
+            4 => Self::M68K,
+            5 => Self::M88K,
+            6 => Self::IntelMCU,
+            7 => Self::Intel80860,
+            8 => Self::MIPS,
+            9 => Self::S370,
+            10 => Self::MIPSRS3LE,
+            15 => Self::Parisc,
+            17 => Self::VPP500,
+            18 => Self::SPARC32Plus,
+            19 => Self::Intel80960,
+            20 => Self::PowerPC,
+            21 => Self::PowerPC65,
+            22 => Self::S390,
+            23 => Self::SPU,
+            36 => Self::V800,
+            37 => Self::FR20,
+            38 => Self::RH32,
+            39 => Self::RCE,
+            40 => Self::Arm,
+            41 => Self::FakeAlpha,
+            42 => Self::EMSH,
+            43 => Self::EMSPARCV9,
+            44 => Self::Tricore,
+            45 => Self::ARC,
+            46 => Self::H8300,
+            47 => Self::H8300H,
+            48 => Self::H8S,
+            49 => Self::H8500,
+            51 => Self::MIPSX,
+            52 => Self::Coldfire,
+            53 => Self::M68HC12,
+            54 => Self::MMA,
+            55 => Self::PCP,
+            56 => Self::NCPU,
+            57 => Self::NDR1,
+            58 => Self::StarCore,
+            59 => Self::ME16,
+            60 => Self::ST100,
+            61 => Self::TinyJ,
             62 => Self::X8664,
+            63 => Self::PSDP,
+            64 => Self::PDP10,
+            65 => Self::PDP11,
+            66 => Self::FX66,
+            67 => Self::ST9Plus,
+            68 => Self::ST7,
+            69 => Self::MC68HC16,
+            70 => Self::MC68HC11,
+            71 => Self::MC68HC08,
+            72 => Self::MC68HC05,
+            73 => Self::SVx,
+            74 => Self::ST19,
+            75 => Self::VAX,
+            76 => Self::CRIS,
+            77 => Self::Javelin,
+            78 => Self::Firepath,
+            79 => Self::ZSP,
+            80 => Self::MMIX,
+            81 => Self::HUANY,
+            82 => Self::Prism,
+            83 => Self::AVR,
+            84 => Self::FR30,
+            85 => Self::D10V,
+            86 => Self::D30V,
+            87 => Self::V850,
+            88 => Self::M32R,
+            89 => Self::MN10300,
+            90 => Self::MN10200,
+            91 => Self::PicoJava,
+            92 => Self::OR1K,
+            93 => Self::ARCompact,
+            94 => Self::Xtensa,
+            95 => Self::VideoCore,
+            96 => Self::TMMGPP,
+            97 => Self::NS32K,
+            98 => Self::TPC,
+            99 => Self::SNP1K,
+            100 => Self::ST200,
             _ => Self::Any(bytes),
         }
     }
 }
+
+impl fmt::Display for Machine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let v = self.to_bytes();
+        match Self::readelf_name(v) {
+            Some(name) => write!(f, "{}", name),
+            None => write!(f, "<unknown>: {:#x}", v),
+        }
+    }
+}
@@ -1,3 +1,6 @@
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum OSABI {
     // UNIX System V ABI
     None,
@@ -80,3 +83,27 @@ impl From<u8> for OSABI {
         }
     }
 }
+
+/// Display as readelf does.
+impl fmt::Display for OSABI {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self.to_identifier() {
+            0 => "UNIX - System V",
+            1 => "UNIX - HP-UX",
+            2 => "UNIX - NetBSD",
+            3 => "UNIX - GNU",
+            6 => "UNIX - Solaris",
+            7 => "UNIX - AIX",
+            8 => "UNIX - IRIX",
+            9 => "UNIX - FreeBSD",
+            10 => "UNIX - TRU64",
+            11 => "Novell - Modesto",
+            12 => "UNIX - OpenBSD",
+            64 => "ARM EABI",
+            97 => "ARM",
+            255 => "Standalone App",
+            v => return write!(f, "<unknown: {:x}>", v),
+        };
+        write!(f, "{}", name)
+    }
+}
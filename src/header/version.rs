@@ -1,3 +1,6 @@
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Version {
     // value must be 1
     Current,
@@ -14,14 +17,14 @@ impl Version {
         match self {
             Self::Current => 1,
             Self::Any(c) => *c,
-            _ => unreachable!(),
+            Self::Any32(c) => *c as u8,
         }
     }
     pub fn to_object_version(&self) -> u32 {
         match self {
             Self::Current => 1,
             Self::Any32(c) => *c,
-            _ => unreachable!(),
+            Self::Any(c) => *c as u32,
         }
     }
 }
@@ -42,3 +45,14 @@ impl From<u32> for Version {
         }
     }
 }
+
+/// Display as readelf does.
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Current => write!(f, "1 (current)"),
+            Self::Any(v) => write!(f, "{} <unknown>", v),
+            Self::Any32(v) => write!(f, "{:#x} <unknown>", v),
+        }
+    }
+}
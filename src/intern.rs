@@ -0,0 +1,251 @@
+//! Interning of section and symbol names.
+//!
+//! Files produced by the same toolchain share most of their names(`.text`, `main`, `__libc_start_main`, ...).
+//! The names are reference-counted, so the files interned by the same `Interner` hold a single copy of each.
+//!
+//! # Examples
+//!
+//! ```
+//! use elf_utilities::{intern, parser};
+//!
+//! let interner = intern::Interner::new();
+//! let mut a = parser::parse_elf64("src/parser/testdata/sample").unwrap();
+//! let mut b = parser::parse_elf64("src/parser/testdata/sample").unwrap();
+//! a.intern_names(&interner);
+//! b.intern_names(&interner);
+//!
+//! let text_a = a.first_section_by(|sct| sct.name == ".text").unwrap();
+//! let text_b = b.first_section_by(|sct| sct.name == ".text").unwrap();
+//! assert!(intern::Name::ptr_eq(&text_a.name, &text_b.name));
+//! ```
+
+use std::borrow::Borrow;
+use std::collections::HashSet;
+use std::fmt;
+use std::ops::Deref;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::*;
+
+/// A name of a section or a symbol
+///
+/// It behaves as `str`, and is cheap to clone.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Name(Arc<str>);
+
+impl Name {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// whether the names share the same storage, i.e. they are interned by the same `Interner`.
+    pub fn ptr_eq(a: &Self, b: &Self) -> bool {
+        Arc::ptr_eq(&a.0, &b.0)
+    }
+}
+
+impl Default for Name {
+    fn default() -> Self {
+        Self::from("")
+    }
+}
+
+impl Deref for Name {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<str> for Name {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Borrow<str> for Name {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Name {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&*self.0, f)
+    }
+}
+
+impl fmt::Display for Name {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&*self.0, f)
+    }
+}
+
+impl From<&str> for Name {
+    fn from(s: &str) -> Self {
+        Self(Arc::from(s))
+    }
+}
+
+impl From<String> for Name {
+    fn from(s: String) -> Self {
+        Self(Arc::from(s))
+    }
+}
+
+impl From<&String> for Name {
+    fn from(s: &String) -> Self {
+        Self::from(s.as_str())
+    }
+}
+
+impl From<Name> for String {
+    fn from(name: Name) -> Self {
+        name.0.to_string()
+    }
+}
+
+impl PartialEq<str> for Name {
+    fn eq(&self, other: &str) -> bool {
+        *self.0 == *other
+    }
+}
+
+impl PartialEq<&str> for Name {
+    fn eq(&self, other: &&str) -> bool {
+        *self.0 == **other
+    }
+}
+
+impl PartialEq<String> for Name {
+    fn eq(&self, other: &String) -> bool {
+        *self.0 == **other
+    }
+}
+
+impl PartialEq<Name> for str {
+    fn eq(&self, other: &Name) -> bool {
+        *self == *other.0
+    }
+}
+
+impl PartialEq<Name> for &str {
+    fn eq(&self, other: &Name) -> bool {
+        **self == *other.0
+    }
+}
+
+impl PartialEq<Name> for String {
+    fn eq(&self, other: &Name) -> bool {
+        **self == *other.0
+    }
+}
+
+impl Serialize for Name {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Name {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Self::from)
+    }
+}
+
+/// A set of names shared by the files interned with it
+///
+/// Clones share the set, so an `Interner` can be passed to the threads of a scan.
+/// The names stay alive while the set holds them; `clear()` releases those unused by the files.
+#[derive(Debug, Clone, Default)]
+pub struct Interner {
+    names: Arc<Mutex<HashSet<Name>>>,
+}
+
+impl Interner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// the shared copy of `s`.
+    pub fn intern(&self, s: &str) -> Name {
+        let mut names = self.names.lock().unwrap();
+        if let Some(name) = names.get(s) {
+            return name.clone();
+        }
+        let name = Name::from(s);
+        names.insert(name.clone());
+        name
+    }
+
+    /// the number of the distinct names.
+    pub fn len(&self) -> usize {
+        self.names.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// forget the names. the files keep theirs.
+    pub fn clear(&self) {
+        self.names.lock().unwrap().clear();
+    }
+}
+
+/// the interner shared by the whole process.
+pub fn global() -> &'static Interner {
+    static GLOBAL: OnceLock<Interner> = OnceLock::new();
+    GLOBAL.get_or_init(Interner::new)
+}
+
+impl file::ELF64 {
+    /// replace the names of the sections and the symbols with the copies in `interner`.
+    pub fn intern_names(&mut self, interner: &Interner) {
+        for sct in self.sections.iter_mut() {
+            sct.name = interner.intern(&sct.name);
+            if let section::Contents64::Symbols(syms) = &mut sct.contents {
+                for sym in syms.iter_mut() {
+                    sym.symbol_name = interner.intern(&sym.symbol_name);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod intern_tests {
+    use super::*;
+
+    #[test]
+    fn interner_test() {
+        let interner = Interner::new();
+        let a = interner.intern("main");
+        let b = interner.clone().intern("main");
+        assert!(Name::ptr_eq(&a, &b));
+        assert_eq!(1, interner.len());
+        assert_eq!("main", a);
+        assert_eq!(a, "main".to_string());
+
+        // 別のInternerとは共有しない
+        let c = Interner::new().intern("main");
+        assert_eq!(a, c);
+        assert!(!Name::ptr_eq(&a, &c));
+
+        let mut elf = parser::parse_elf64("src/parser/testdata/sample").unwrap();
+        elf.intern_names(global());
+        let main = global().intern("main");
+        let symtab = elf
+            .first_section_by(|sct| sct.header.get_type() == section::Type::SymTab)
+            .unwrap();
+        match &symtab.contents {
+            section::Contents64::Symbols(syms) => {
+                assert!(syms.iter().any(|sym| Name::ptr_eq(&sym.symbol_name, &main)))
+            }
+            _ => unreachable!(),
+        }
+    }
+}
@@ -0,0 +1,1047 @@
+//! Layout engine which assigns file offsets and virtual addresses.
+//!
+//! `Layout::apply()` places every section of an `ELF64` after the program header table,
+//! assigns virtual addresses to `SHF_ALLOC` sections and generates `PT_LOAD` segments
+//! which group the sections by their permissions.
+//! The load memory address(`p_paddr`) can differ from the virtual address,
+//! like `AT>` in linker scripts for the data copied from flash to RAM.
+//! `Layout::apply_elf32()` does the same for an `ELF32`.
+
+use crate::*;
+use thiserror::Error as TError;
+
+mod elf32;
+mod linker_script;
+
+pub use linker_script::*;
+
+/// Default page size used to align `PT_LOAD` segments.
+pub const DEFAULT_PAGE_SIZE: Elf64Xword = 0x1000;
+/// page size of aarch64 Android and Apple silicon
+pub const PAGE_SIZE_16K: Elf64Xword = 0x4000;
+/// page size of ppc64 and some aarch64 kernels
+pub const PAGE_SIZE_64K: Elf64Xword = 0x10000;
+
+/// A constraint on the placement of a section
+#[derive(Debug, Clone, Hash, PartialOrd, Ord, PartialEq, Eq)]
+pub enum LayoutConstraint {
+    /// place the section at the file offset
+    FixedOffset(Elf64Off),
+    /// place the section at the virtual address.
+    /// the section starts a new `PT_LOAD` segment, so this also pins the segment.
+    FixedVaddr(Elf64Addr),
+    /// place the section after the given section
+    After(String),
+    /// store the section at the load memory address(LMA) instead of its virtual address.
+    /// the section starts a new `PT_LOAD` segment, whose `p_paddr` is the address.
+    FixedLma(Elf64Addr),
+}
+
+/// Where the section header table is placed in the file
+#[derive(Debug, Clone, Copy, Hash, PartialOrd, Ord, PartialEq, Eq, Default)]
+pub enum ShtPlacement {
+    /// right after the program header table, before the contents
+    AfterHeader,
+    /// after the contents of the sections, like GNU ld
+    #[default]
+    AfterContents,
+    /// at the file offset, which must not overlap the headers and the contents
+    FixedOffset(Elf64Off),
+    /// no section header table(`e_shoff`, `e_shnum` and `e_shstrndx` are 0).
+    /// the loaders don't need it, but most tools can't find the sections without it.
+    Omit,
+}
+
+impl ShtPlacement {
+    /// the file offset where the contents can start.
+    pub(crate) fn contents_start(
+        &self,
+        pht_end: Elf64Off,
+        sht_size: Elf64Xword,
+        word: Elf64Xword,
+    ) -> Elf64Off {
+        match self {
+            Self::AfterHeader => align_up(pht_end, word) + sht_size,
+            _ => pht_end,
+        }
+    }
+
+    /// set `e_shoff`, `e_shnum` and `e_shstrndx` after the contents are placed up to `contents_end`.
+    pub(crate) fn finish(
+        &self,
+        elf: &mut file::ELF64,
+        pht_end: Elf64Off,
+        contents_end: Elf64Off,
+        sht_size: Elf64Xword,
+        word: Elf64Xword,
+    ) -> Result<(), LayoutError> {
+        elf.ehdr.e_shoff = match self {
+            Self::AfterHeader => align_up(pht_end, word),
+            Self::AfterContents => align_up(contents_end, word),
+            Self::FixedOffset(offset) => {
+                let end = offset + sht_size;
+                let overlapped = elf.sections.iter().skip(1).any(|sct| {
+                    let h = &sct.header;
+                    !is_nobits(h)
+                        && h.sh_size != 0
+                        && h.sh_offset < end
+                        && *offset < h.sh_offset + h.sh_size
+                });
+                if offset % word != 0 || *offset < pht_end || overlapped {
+                    return Err(LayoutError::ShtConflict { offset: *offset });
+                }
+                *offset
+            }
+            Self::Omit => {
+                elf.ehdr.e_shoff = 0;
+                elf.ehdr.e_shnum = 0;
+                elf.ehdr.e_shstrndx = 0;
+                return Ok(());
+            }
+        };
+        elf.ehdr.e_shnum = elf.sections.len() as Elf64Half;
+        Ok(())
+    }
+}
+
+#[derive(TError, Debug)]
+pub enum LayoutError {
+    #[error("section `{name}` is not found")]
+    UnknownSection { name: String },
+    #[error("section `{name}` can't be placed at offset {offset:#x}")]
+    OffsetConflict { name: String, offset: Elf64Off },
+    #[error("section `{name}` can't be placed at address {addr:#x}")]
+    VaddrConflict { name: String, addr: Elf64Addr },
+    #[error("section `{name}` can't be loaded at address {addr:#x}")]
+    LmaConflict { name: String, addr: Elf64Addr },
+    #[error("section `{name}` must be placed after `{after}`")]
+    OrderConflict { name: String, after: String },
+    #[error("the section header table can't be placed at offset {offset:#x}")]
+    ShtConflict { offset: Elf64Off },
+    #[error("page size {page_size:#x} is not a power of two")]
+    InvalidPageSize { page_size: Elf64Xword },
+    #[error("{field} of `{name}` ({value:#x}) doesn't fit in a 32bit file")]
+    Overflow32 {
+        name: String,
+        field: &'static str,
+        value: u64,
+    },
+}
+
+/// A layout engine configuration
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::{file, layout, section, segment};
+///
+/// let mut elf = file::ELF64::default();
+/// elf.add_section(section::Section64::new(
+///     ".text".to_string(),
+///     section::ShdrPreparation64::default()
+///         .ty(section::Type::ProgBits)
+///         .flags([section::Flag::Alloc, section::Flag::ExecInstr].iter()),
+///     section::Contents64::Raw(vec![0xc3]),
+/// ));
+///
+/// layout::Layout::new().base_addr(0x400000).apply(&mut elf).unwrap();
+///
+/// // the ELF header and the text are loaded by different segments.
+/// assert_eq!(2, elf.segments.len());
+/// assert_eq!(segment::Type::Load, elf.segments[1].header.get_type());
+/// assert_eq!(0x401000 + elf.sections[1].header.sh_offset, elf.sections[1].header.sh_addr);
+/// ```
+///
+/// Sections can be pinned by constraints.
+///
+/// ```
+/// use elf_utilities::{file, layout, section};
+///
+/// let mut elf = file::ELF64::default();
+/// elf.add_section(section::Section64::new(
+///     ".text".to_string(),
+///     section::ShdrPreparation64::default()
+///         .ty(section::Type::ProgBits)
+///         .flags([section::Flag::Alloc, section::Flag::ExecInstr].iter()),
+///     section::Contents64::Raw(vec![0xc3]),
+/// ));
+///
+/// layout::Layout::new()
+///     .constraint(".text", layout::LayoutConstraint::FixedOffset(0x200))
+///     .constraint(".text", layout::LayoutConstraint::FixedVaddr(0x8000200))
+///     .apply(&mut elf)
+///     .unwrap();
+/// assert_eq!(0x200, elf.sections[1].header.sh_offset);
+/// assert_eq!(0x8000200, elf.sections[1].header.sh_addr);
+///
+/// // the ELF header and the program header table occupy the beginning of the file.
+/// let err = layout::Layout::new()
+///     .constraint(".text", layout::LayoutConstraint::FixedOffset(0x10))
+///     .apply(&mut elf);
+/// assert!(err.is_err());
+/// ```
+///
+/// The data initialized by the startup code can be stored right after the code.
+///
+/// ```
+/// use elf_utilities::{file, layout, section};
+///
+/// let mut elf = file::ELF64::default();
+/// for (name, flags) in [
+///     (".text", [section::Flag::Alloc, section::Flag::ExecInstr]),
+///     (".data", [section::Flag::Alloc, section::Flag::Write]),
+/// ] {
+///     elf.add_section(section::Section64::new(
+///         name.to_string(),
+///         section::ShdrPreparation64::default()
+///             .ty(section::Type::ProgBits)
+///             .flags(flags.iter()),
+///         section::Contents64::Raw(vec![0; 0x10]),
+///     ));
+/// }
+///
+/// layout::Layout::new()
+///     .base_addr(0x0800_0000)
+///     .constraint(".data", layout::LayoutConstraint::FixedVaddr(0x2000_0000))
+///     .constraint(".data", layout::LayoutConstraint::FixedLma(0x0800_2000))
+///     .apply(&mut elf)
+///     .unwrap();
+/// let data = elf.segments.last().unwrap().header;
+/// assert_eq!(0x2000_0000, data.p_vaddr);
+/// assert_eq!(0x0800_2000, data.lma());
+/// assert_eq!(Some(0x0800_2000), elf.section_lma(2));
+/// ```
+#[derive(Debug, Clone, Hash, PartialOrd, Ord, PartialEq, Eq)]
+pub struct Layout {
+    /// alignment of `PT_LOAD` segments
+    pub page_size: Elf64Xword,
+    /// virtual address of the ELF header
+    pub base_addr: Elf64Addr,
+    /// constraints on sections (section name, constraint)
+    pub constraints: Vec<(String, LayoutConstraint)>,
+    /// regenerate `PT_NOTE` segments from the note sections
+    pub note_segments: bool,
+    pub sht_placement: ShtPlacement,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Self {
+            page_size: DEFAULT_PAGE_SIZE,
+            base_addr: 0,
+            constraints: Vec::new(),
+            note_segments: false,
+            sht_placement: ShtPlacement::AfterContents,
+        }
+    }
+}
+
+/// permissions of a `PT_LOAD` segment and the sections it contains
+struct LoadGroup {
+    flags: Elf64Word,
+    sections: Vec<usize>,
+}
+
+/// constraints resolved for a section
+#[derive(Default, Clone, Copy)]
+struct Pin {
+    offset: Option<Elf64Off>,
+    vaddr: Option<Elf64Addr>,
+    lma: Option<Elf64Addr>,
+}
+
+impl Layout {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// create a configuration which keeps the page size, the base address and the LMAs of `elf`.
+    pub fn from_elf(elf: &file::ELF64) -> Self {
+        let base_addr = elf
+            .segments
+            .iter()
+            .filter(|sgt| sgt.header.get_type() == segment::Type::Load)
+            // オフセットより低いアドレスのセグメントは壊れているので無視する
+            .filter_map(|sgt| sgt.header.p_vaddr.checked_sub(sgt.header.p_offset))
+            .min()
+            .unwrap_or(0);
+        // LMAが仮想アドレスと異なるセグメントは，先頭のセクションのLMAとして引き継ぐ
+        let constraints = elf
+            .segments
+            .iter()
+            .map(|sgt| &sgt.header)
+            .filter(|phdr| phdr.get_type() == segment::Type::Load && phdr.p_paddr != phdr.p_vaddr)
+            .filter_map(|phdr| {
+                let shidx = (1..elf.sections.len()).find(|&i| {
+                    let shdr = &elf.sections[i].header;
+                    is_alloc(shdr)
+                        && shdr.sh_addr >= phdr.p_vaddr
+                        && phdr.lma_of(shdr.sh_addr).is_some()
+                })?;
+                let sct = &elf.sections[shidx];
+                Some((
+                    sct.name.to_string(),
+                    LayoutConstraint::FixedLma(phdr.lma_of(sct.header.sh_addr)?),
+                ))
+            })
+            .collect();
+        Self {
+            page_size: elf.max_page_size().unwrap_or(DEFAULT_PAGE_SIZE),
+            base_addr,
+            constraints,
+            note_segments: elf
+                .segments
+                .iter()
+                .any(|sgt| sgt.header.get_type() == segment::Type::Note),
+            sht_placement: sht_placement_of(elf),
+        }
+    }
+
+    /// set the target page size(`PT_LOAD` alignment).
+    /// the segments must be aligned to the largest page size of the target systems.
+    pub fn page_size(mut self, page_size: Elf64Xword) -> Self {
+        self.page_size = page_size;
+        self
+    }
+    pub fn base_addr(mut self, base_addr: Elf64Addr) -> Self {
+        self.base_addr = base_addr;
+        self
+    }
+    /// add a constraint on the section named `section`.
+    pub fn constraint(mut self, section: &str, c: LayoutConstraint) -> Self {
+        self.constraints.push((section.to_string(), c));
+        self
+    }
+    /// regenerate `PT_NOTE` segments which cover the `SHF_ALLOC` note sections.
+    /// existing `PT_NOTE` segments are replaced, and adjacent notes share a segment.
+    pub fn note_segments(mut self, enabled: bool) -> Self {
+        self.note_segments = enabled;
+        self
+    }
+
+    /// place the section header table, after the contents by default.
+    pub fn sht_placement(mut self, placement: ShtPlacement) -> Self {
+        self.sht_placement = placement;
+        self
+    }
+
+    /// assign `sh_offset`/`sh_addr` of every section and regenerate `PT_LOAD` segments.
+    ///
+    /// Segments other than `PT_LOAD` are kept, but only `PT_PHDR` is fitted automatically.
+    /// `PT_NOTE` segments are regenerated if `note_segments` is enabled.
+    /// Use `fit_segment()` to fit the others to the sections they cover.
+    /// Sections are never reordered, so a constraint which can't be satisfied in the current order is reported as an error.
+    pub fn apply(&self, elf: &mut file::ELF64) -> Result<(), LayoutError> {
+        self.place(elf, &ClassSizes::BIT64)
+    }
+
+    /// the body of `apply()`, with the header sizes of the class.
+    fn place(&self, elf: &mut file::ELF64, sizes: &ClassSizes) -> Result<(), LayoutError> {
+        let ehdr_size = sizes.ehdr;
+        if !self.page_size.is_power_of_two() {
+            return Err(LayoutError::InvalidPageSize {
+                page_size: self.page_size,
+            });
+        }
+        let pins = self.resolve_constraints(elf)?;
+        elf.invalidate_address_index();
+        update_section_sizes(elf);
+
+        let groups = load_groups(elf, &pins);
+        let mut others: Vec<segment::Segment64> = elf
+            .segments
+            .iter()
+            .filter(|sgt| sgt.header.get_type() != segment::Type::Load)
+            .filter(|sgt| !(self.note_segments && sgt.header.get_type() == segment::Type::Note))
+            .copied()
+            .collect();
+        // 固定されたセクションは直前のノートと隣接するとは限らない
+        let note_groups = if self.note_segments {
+            segment::note_groups(elf, |_, next| {
+                pins[next].offset.is_none() && pins[next].vaddr.is_none()
+            })
+        } else {
+            Vec::new()
+        };
+        let phnum = others.len() + groups.len() + note_groups.len();
+        let pht_end = ehdr_size + sizes.phdr * phnum as Elf64Off;
+
+        let sht_size = sizes.shdr * elf.sections.len() as Elf64Xword;
+        let mut file_offset = self
+            .sht_placement
+            .contents_start(pht_end, sht_size, sizes.word);
+        let mut delta = self.base_addr;
+        let mut mem_end = self.base_addr + pht_end;
+        let mut group_idx = 0;
+        let mut loads = vec![self.new_load(&groups[0], 0, self.base_addr)];
+        // LMAが固定されたセグメント (loadsの添字, セクション名)
+        let mut lma_loads: Vec<(usize, String)> = Vec::new();
+
+        for (shidx, sct) in elf.sections.iter_mut().enumerate().skip(1) {
+            let align = sct.header.sh_addralign.max(1);
+            let pin = pins[shidx];
+            // NOBITSはファイル上の領域を持たないので，オフセットを揃える必要はない
+            if !is_nobits(&sct.header) {
+                file_offset = align_up(file_offset, align);
+            }
+            if let Some(offset) = pin.offset {
+                if offset < file_offset || offset % align != 0 {
+                    return Err(LayoutError::OffsetConflict {
+                        name: sct.name.to_string(),
+                        offset,
+                    });
+                }
+                file_offset = offset;
+            }
+
+            if !is_alloc(&sct.header) {
+                sct.header.sh_offset = file_offset;
+                if !is_nobits(&sct.header) {
+                    file_offset += sct.header.sh_size;
+                }
+                continue;
+            }
+
+            // 新しいLOADセグメントの先頭ならば，ページを跨いでアドレスを割り当てる
+            if group_idx + 1 < groups.len() && groups[group_idx + 1].sections[0] == shidx {
+                group_idx += 1;
+                let vaddr = match pin.vaddr {
+                    Some(vaddr) => {
+                        let fits = vaddr.checked_add(sct.header.sh_size).is_some();
+                        if vaddr < mem_end || vaddr % align != 0 || !fits {
+                            return Err(LayoutError::VaddrConflict {
+                                name: sct.name.to_string(),
+                                addr: vaddr,
+                            });
+                        }
+                        // オフセットとアドレスはページサイズを法として合同でなければならない
+                        if pin.offset.is_some() {
+                            if vaddr % self.page_size != file_offset % self.page_size {
+                                return Err(LayoutError::VaddrConflict {
+                                    name: sct.name.to_string(),
+                                    addr: vaddr,
+                                });
+                            }
+                        } else {
+                            let gap = (vaddr % self.page_size + self.page_size
+                                - file_offset % self.page_size)
+                                % self.page_size;
+                            file_offset += gap;
+                        }
+                        vaddr
+                    }
+                    None => checked_align_up(mem_end, self.page_size)
+                        .and_then(|vaddr| vaddr.checked_add(file_offset % self.page_size))
+                        .ok_or_else(|| LayoutError::VaddrConflict {
+                            name: sct.name.to_string(),
+                            addr: mem_end,
+                        })?,
+                };
+                delta = vaddr - file_offset;
+                let mut load = self.new_load(&groups[group_idx], file_offset, vaddr);
+                if let Some(lma) = pin.lma {
+                    if lma % align != 0 {
+                        return Err(LayoutError::LmaConflict {
+                            name: sct.name.to_string(),
+                            addr: lma,
+                        });
+                    }
+                    load.header.p_paddr = lma;
+                    lma_loads.push((loads.len(), sct.name.to_string()));
+                }
+                loads.push(load);
+            }
+
+            let load = loads.last_mut().unwrap();
+            sct.header.sh_offset = file_offset;
+            if is_nobits(&sct.header) {
+                sct.header.sh_addr = align_up(mem_end.max(file_offset + delta), align);
+                if !is_tls(&sct.header) {
+                    mem_end = sct.header.sh_addr + sct.header.sh_size;
+                }
+            } else {
+                sct.header.sh_addr = file_offset + delta;
+                file_offset += sct.header.sh_size;
+                mem_end = sct.header.sh_addr + sct.header.sh_size;
+                load.header.p_filesz = file_offset - load.header.p_offset;
+            }
+            load.header.p_memsz = mem_end - load.header.p_vaddr;
+        }
+
+        // ヘッダのみを含むLOADセグメントはPHTまでを覆う
+        if loads[0].header.p_filesz < pht_end {
+            loads[0].header.p_filesz = pht_end;
+            loads[0].header.p_memsz = loads[0].header.p_memsz.max(pht_end);
+        }
+
+        // LMA上ではファイルイメージが重なってはならない
+        let physical = |phdr: &segment::Phdr64| phdr.p_paddr..phdr.p_paddr + phdr.p_filesz;
+        for (idx, name) in lma_loads.iter() {
+            let pinned = physical(&loads[*idx].header);
+            let overlapped = loads.iter().enumerate().any(|(i, load)| {
+                let other = physical(&load.header);
+                i != *idx
+                    && !pinned.is_empty()
+                    && !other.is_empty()
+                    && pinned.start < other.end
+                    && other.start < pinned.end
+            });
+            if overlapped {
+                return Err(LayoutError::LmaConflict {
+                    name: name.clone(),
+                    addr: pinned.start,
+                });
+            }
+        }
+
+        for sgt in others.iter_mut() {
+            if sgt.header.get_type() == segment::Type::Phdr {
+                sgt.header.p_offset = ehdr_size;
+                sgt.header.p_vaddr = self.base_addr + ehdr_size;
+                sgt.header.p_paddr = sgt.header.p_vaddr;
+                sgt.header.p_filesz = sizes.phdr * phnum as Elf64Xword;
+                sgt.header.p_memsz = sgt.header.p_filesz;
+                sgt.header.p_align = sizes.word;
+            }
+        }
+
+        // PT_PHDR/PT_INTERPはPT_LOADより前に置く必要がある
+        let leading = others
+            .iter()
+            .take_while(|sgt| {
+                let ty = sgt.header.get_type();
+                ty == segment::Type::Phdr || ty == segment::Type::Interp
+            })
+            .count();
+        let rest = others.split_off(leading);
+        others.extend(loads);
+        others.extend(note_groups.iter().map(|shidxs| segment::Segment64 {
+            header: segment::note_phdr(elf, shidxs),
+        }));
+        others.extend(rest);
+        elf.segments = others;
+
+        elf.ehdr.e_phoff = ehdr_size;
+        elf.ehdr.e_phnum = phnum as Elf64Half;
+        self.sht_placement
+            .finish(elf, pht_end, file_offset, sht_size, sizes.word)
+    }
+
+    /// look up the sections of constraints and check their order.
+    fn resolve_constraints(&self, elf: &file::ELF64) -> Result<Vec<Pin>, LayoutError> {
+        let shidx_of = |name: &str| {
+            elf.first_shidx_by(|sct| sct.name == name)
+                .ok_or_else(|| LayoutError::UnknownSection {
+                    name: name.to_string(),
+                })
+        };
+
+        let mut pins = vec![Pin::default(); elf.sections.len()];
+        for (name, c) in self.constraints.iter() {
+            let shidx = shidx_of(name)?;
+            match c {
+                LayoutConstraint::FixedOffset(offset) => pins[shidx].offset = Some(*offset),
+                LayoutConstraint::FixedVaddr(addr) => {
+                    if !is_alloc(&elf.sections[shidx].header) {
+                        return Err(LayoutError::VaddrConflict {
+                            name: name.clone(),
+                            addr: *addr,
+                        });
+                    }
+                    pins[shidx].vaddr = Some(*addr);
+                }
+                LayoutConstraint::FixedLma(addr) => {
+                    if !is_alloc(&elf.sections[shidx].header) {
+                        return Err(LayoutError::LmaConflict {
+                            name: name.clone(),
+                            addr: *addr,
+                        });
+                    }
+                    pins[shidx].lma = Some(*addr);
+                }
+                LayoutConstraint::After(after) => {
+                    if shidx_of(after)? >= shidx {
+                        return Err(LayoutError::OrderConflict {
+                            name: name.clone(),
+                            after: after.clone(),
+                        });
+                    }
+                }
+            }
+        }
+        Ok(pins)
+    }
+
+    fn new_load(
+        &self,
+        group: &LoadGroup,
+        offset: Elf64Off,
+        vaddr: Elf64Addr,
+    ) -> segment::Segment64 {
+        segment::Segment64 {
+            header: segment::Phdr64 {
+                p_type: segment::Type::Load.to_bytes(),
+                p_flags: group.flags,
+                p_offset: offset,
+                p_vaddr: vaddr,
+                p_paddr: vaddr,
+                p_filesz: 0,
+                p_memsz: 0,
+                p_align: self.page_size,
+            },
+        }
+    }
+}
+
+/// the sizes which differ between 32bit and 64bit files
+struct ClassSizes {
+    ehdr: Elf64Off,
+    phdr: Elf64Off,
+    shdr: Elf64Off,
+    /// alignment of the header tables
+    word: Elf64Xword,
+}
+
+impl ClassSizes {
+    const BIT64: Self = Self {
+        ehdr: header::Ehdr64::SIZE as Elf64Off,
+        phdr: segment::Phdr64::SIZE as Elf64Off,
+        shdr: section::Shdr64::SIZE as Elf64Off,
+        word: 8,
+    };
+    const BIT32: Self = Self {
+        ehdr: header::Ehdr32::SIZE as Elf64Off,
+        phdr: segment::Phdr32::SIZE as Elf64Off,
+        shdr: section::Shdr32::SIZE as Elf64Off,
+        word: 4,
+    };
+}
+
+/// the placement of the section header table of `elf` relative to its contents.
+fn sht_placement_of(elf: &file::ELF64) -> ShtPlacement {
+    if elf.ehdr.e_shoff == 0 {
+        return ShtPlacement::Omit;
+    }
+    let first = elf
+        .sections
+        .iter()
+        .skip(1)
+        .filter(|sct| !is_nobits(&sct.header) && sct.header.sh_size != 0)
+        .map(|sct| sct.header.sh_offset)
+        .min();
+    match first {
+        Some(first) if elf.ehdr.e_shoff < first => ShtPlacement::AfterHeader,
+        _ => ShtPlacement::AfterContents,
+    }
+}
+
+/// fit the segment to the range which covers all of given sections.
+/// the difference between `p_paddr` and `p_vaddr` is kept.
+pub fn fit_segment(elf: &mut file::ELF64, sgt_idx: usize, shidxs: &[usize]) {
+    let headers: Vec<section::Shdr64> = shidxs.iter().map(|i| elf.sections[*i].header).collect();
+    let phdr = &mut elf.segments[sgt_idx].header;
+    if headers.is_empty() {
+        return;
+    }
+
+    let start_offset = headers.iter().map(|h| h.sh_offset).min().unwrap();
+    let start_addr = headers.iter().map(|h| h.sh_addr).min().unwrap();
+    let file_end = headers
+        .iter()
+        .filter(|h| !is_nobits(h))
+        .map(|h| h.sh_offset + h.sh_size)
+        .max()
+        .unwrap_or(start_offset);
+    let mem_end = headers.iter().map(|h| h.sh_addr + h.sh_size).max().unwrap();
+
+    // LMAと仮想アドレスの差は保つ
+    let lma_delta = phdr.p_paddr.wrapping_sub(phdr.p_vaddr);
+    phdr.p_offset = start_offset;
+    phdr.p_vaddr = start_addr;
+    phdr.p_paddr = start_addr.wrapping_add(lma_delta);
+    phdr.p_filesz = file_end - start_offset;
+    phdr.p_memsz = mem_end - start_addr;
+    phdr.p_align = headers.iter().map(|h| h.sh_addralign).max().unwrap().max(1);
+}
+
+/// round `v` up to a multiple of `align`, `None` if it overflows.
+pub(crate) fn checked_align_up(v: u64, align: u64) -> Option<u64> {
+    if align <= 1 {
+        return Some(v);
+    }
+    v.div_ceil(align).checked_mul(align)
+}
+
+/// round `v` up to a multiple of `align`.
+/// Use `checked_align_up()` for the values given by users.
+///
+/// # Panics
+///
+/// Panics if the result overflows.
+pub(crate) fn align_up(v: u64, align: u64) -> u64 {
+    checked_align_up(v, align).expect("alignment overflows")
+}
+
+fn update_section_sizes(elf: &mut file::ELF64) {
+    for sct in elf.sections.iter_mut().skip(1) {
+        if !is_nobits(&sct.header) {
+            sct.header.sh_size = sct.contents.size() as Elf64Xword;
+        }
+    }
+}
+
+/// セクションを権限ごとにまとめる
+/// 先頭のグループは常にELFヘッダとPHTを含む読み込み専用のグループ
+/// アドレスかLMAが固定されたセクションは新しいグループを始める
+fn load_groups(elf: &file::ELF64, pins: &[Pin]) -> Vec<LoadGroup> {
+    let readonly = segment::Flag::R.into();
+    let mut groups = vec![LoadGroup {
+        flags: readonly,
+        sections: Vec::new(),
+    }];
+    let mut last_is_nobits = false;
+
+    for (shidx, sct) in elf.sections.iter().enumerate().skip(1) {
+        if !is_alloc(&sct.header) {
+            continue;
+        }
+        let flags = load_flags(&sct.header);
+        let nobits = is_nobits(&sct.header);
+
+        let current = groups.last_mut().unwrap();
+        // NOBITSの後ろにPROGBITSを置くとファイル上の範囲が壊れるので，新しいセグメントにする
+        let pinned = pins[shidx].vaddr.is_some() || pins[shidx].lma.is_some();
+        if current.flags == flags && (nobits || !last_is_nobits) && !pinned {
+            current.sections.push(shidx);
+        } else {
+            groups.push(LoadGroup {
+                flags,
+                sections: vec![shidx],
+            });
+        }
+        last_is_nobits = nobits && !is_tls(&sct.header);
+    }
+
+    groups
+}
+
+fn load_flags(shdr: &section::Shdr64) -> Elf64Word {
+    let mut flags: Elf64Word = segment::Flag::R.into();
+    if shdr.sh_flags & Elf64Xword::from(section::Flag::Write) != 0 {
+        flags |= Elf64Word::from(segment::Flag::W);
+    }
+    if shdr.sh_flags & Elf64Xword::from(section::Flag::ExecInstr) != 0 {
+        flags |= Elf64Word::from(segment::Flag::X);
+    }
+    flags
+}
+
+pub(crate) fn is_alloc(shdr: &section::Shdr64) -> bool {
+    shdr.sh_flags & Elf64Xword::from(section::Flag::Alloc) != 0
+}
+pub(crate) fn is_nobits(shdr: &section::Shdr64) -> bool {
+    shdr.get_type() == section::Type::NoBits
+}
+pub(crate) fn is_tls(shdr: &section::Shdr64) -> bool {
+    shdr.sh_flags & Elf64Xword::from(section::Flag::TLS) != 0
+}
+
+#[cfg(test)]
+mod layout_tests {
+    use super::*;
+
+    fn add_alloc_section(elf: &mut file::ELF64, name: &str, size: usize) {
+        elf.add_section(section::Section64::new(
+            name.to_string(),
+            section::ShdrPreparation64::default()
+                .ty(section::Type::ProgBits)
+                .flags([section::Flag::Alloc].iter()),
+            section::Contents64::Raw(vec![0; size]),
+        ));
+    }
+
+    #[test]
+    fn fixed_vaddr_test() {
+        let mut elf = file::ELF64::default();
+        add_alloc_section(&mut elf, ".rodata", 0x10);
+        add_alloc_section(&mut elf, ".vectors", 0x10);
+
+        Layout::new()
+            .constraint(".vectors", LayoutConstraint::FixedVaddr(0x80000))
+            .constraint(".vectors", LayoutConstraint::After(".rodata".to_string()))
+            .apply(&mut elf)
+            .unwrap();
+
+        // 同じ権限でも，アドレスが固定されたセクションは別のセグメントになる
+        let loads: Vec<&segment::Phdr64> = elf
+            .segments
+            .iter()
+            .map(|sgt| &sgt.header)
+            .filter(|phdr| phdr.get_type() == segment::Type::Load)
+            .collect();
+        assert_eq!(2, loads.len());
+        assert_eq!(0x80000, elf.sections[2].header.sh_addr);
+        assert_eq!(0x80000, loads[1].p_vaddr);
+        assert_eq!(0, loads[1].p_offset % DEFAULT_PAGE_SIZE);
+
+        let err = Layout::new()
+            .constraint(".rodata", LayoutConstraint::After(".vectors".to_string()))
+            .apply(&mut elf);
+        assert!(matches!(err, Err(LayoutError::OrderConflict { .. })));
+
+        // アドレス空間の末尾を越えるセクションは置けない
+        let err = Layout::new()
+            .constraint(
+                ".vectors",
+                LayoutConstraint::FixedVaddr(0xffff_ffff_ffff_fff8),
+            )
+            .apply(&mut elf);
+        assert!(matches!(err, Err(LayoutError::VaddrConflict { .. })));
+    }
+
+    #[test]
+    fn fixed_lma_test() {
+        let mut elf = file::ELF64::default();
+        add_alloc_section(&mut elf, ".text", 0x100);
+        add_alloc_section(&mut elf, ".data", 0x10);
+
+        Layout::new()
+            .base_addr(0x0800_0000)
+            .constraint(".data", LayoutConstraint::FixedVaddr(0x2000_0000))
+            .constraint(".data", LayoutConstraint::FixedLma(0x0800_2000))
+            .apply(&mut elf)
+            .unwrap();
+        assert_eq!(Some(0x0800_2000), elf.section_lma(2));
+        assert_eq!(Some(elf.sections[1].header.sh_addr), elf.section_lma(1));
+
+        // 再配置してもLMAは保たれる
+        let layout = Layout::from_elf(&elf);
+        assert_eq!(
+            vec![(".data".to_string(), LayoutConstraint::FixedLma(0x0800_2000))],
+            layout.constraints
+        );
+        layout.apply(&mut elf).unwrap();
+        assert_eq!(Some(0x0800_2000), elf.section_lma(2));
+
+        // .textのファイルイメージと重なる
+        let err = Layout::new()
+            .base_addr(0x0800_0000)
+            .constraint(".data", LayoutConstraint::FixedLma(0x0800_0100))
+            .apply(&mut elf);
+        assert!(matches!(err, Err(LayoutError::LmaConflict { .. })));
+    }
+
+    fn add_nobits_section(elf: &mut file::ELF64, name: &str, flags: &[section::Flag], size: u64) {
+        let mut sct = section::Section64::new(
+            name.to_string(),
+            section::ShdrPreparation64::default()
+                .ty(section::Type::NoBits)
+                .flags(flags.iter()),
+            section::Contents64::Raw(Vec::new()),
+        );
+        sct.header.sh_size = size;
+        sct.header.sh_addralign = 0x1000;
+        elf.add_section(sct);
+    }
+
+    #[test]
+    fn nobits_test() {
+        let rw = [section::Flag::Alloc, section::Flag::Write];
+        let mut elf = file::ELF64::default();
+        elf.add_section(section::Section64::new(
+            ".data".to_string(),
+            section::ShdrPreparation64::default()
+                .ty(section::Type::ProgBits)
+                .flags(rw.iter()),
+            section::Contents64::Raw(vec![1; 0x10]),
+        ));
+        add_nobits_section(
+            &mut elf,
+            ".tbss",
+            &[
+                section::Flag::Alloc,
+                section::Flag::Write,
+                section::Flag::TLS,
+            ],
+            0x100,
+        );
+        add_nobits_section(&mut elf, ".bss", &rw, 0x100000);
+        add_alloc_section(&mut elf, ".rodata", 0x10);
+        // add_section()もNOBITSのサイズをファイルに含めない
+        assert_eq!(
+            elf.sections[1].header.sh_offset + 0x10,
+            elf.sections[4].header.sh_offset
+        );
+
+        Layout::new().apply(&mut elf).unwrap();
+        let data = elf.sections[1].header;
+        let bss = elf.sections[3].header;
+        let rodata = elf.sections[4].header;
+        let shstrtab = elf.sections[5].header;
+        assert_eq!(0x100000, bss.sh_size);
+        assert_eq!(0, bss.sh_addr % 0x1000);
+        // .tbssはメモリ上の領域も.bssと共有する
+        assert!(elf.sections[2].header.sh_addr <= bss.sh_addr);
+
+        // .bssの後ろのセクションは，ファイル上で.dataの直後から配置される
+        assert!(rodata.sh_offset < data.sh_offset + 0x1000);
+        assert_eq!(rodata.sh_offset + 0x10, shstrtab.sh_offset);
+        assert!(elf.to_le_bytes().len() < 0x10000);
+
+        let load = elf
+            .segments
+            .iter()
+            .map(|sgt| &sgt.header)
+            .find(|phdr| phdr.p_vaddr == data.sh_addr)
+            .unwrap();
+        assert_eq!(0x10, load.p_filesz);
+        assert_eq!(bss.sh_addr + bss.sh_size, load.p_vaddr + load.p_memsz);
+    }
+
+    #[test]
+    fn note_segments_test() {
+        let mut elf = file::ELF64::default();
+        for (name, align) in [
+            (".note.gnu.property", 8),
+            (".note.gnu.build-id", 4),
+            (".note.ABI-tag", 4),
+        ]
+        .iter()
+        {
+            let mut sct = section::Section64::new(
+                name.to_string(),
+                section::ShdrPreparation64::default()
+                    .ty(section::Type::Note)
+                    .flags([section::Flag::Alloc].iter()),
+                section::Contents64::Raw(vec![0; 0x20]),
+            );
+            sct.header.sh_addralign = *align;
+            elf.add_section(sct);
+        }
+
+        let layout = Layout::new().note_segments(true);
+        layout.apply(&mut elf).unwrap();
+        // 再配置しても古いPT_NOTEは残らない
+        Layout::from_elf(&elf).apply(&mut elf).unwrap();
+
+        let notes: Vec<&segment::Phdr64> = elf
+            .segments
+            .iter()
+            .map(|sgt| &sgt.header)
+            .filter(|phdr| phdr.get_type() == segment::Type::Note)
+            .collect();
+        assert_eq!(2, notes.len());
+        assert_eq!(elf.segments.len(), elf.ehdr.e_phnum as usize);
+
+        assert_eq!(8, notes[0].p_align);
+        assert_eq!(elf.sections[1].header.sh_offset, notes[0].p_offset);
+        assert_eq!(0x20, notes[0].p_filesz);
+
+        // .note.gnu.build-idと.note.ABI-tagは一つのセグメントにまとめられる
+        assert_eq!(4, notes[1].p_align);
+        assert_eq!(elf.sections[2].header.sh_addr, notes[1].p_vaddr);
+        assert_eq!(0x40, notes[1].p_filesz);
+        assert_eq!(
+            notes.iter().map(|phdr| **phdr).collect::<Vec<_>>(),
+            segment::compute_note_segments(&elf)
+        );
+    }
+
+    #[test]
+    fn page_size_test() {
+        let elf = crate::builder::SharedObjectWriter::new()
+            .layout(Layout::new().page_size(PAGE_SIZE_16K))
+            .function(crate::builder::ExportedFunction::new("f", vec![0xc3]))
+            .build()
+            .unwrap();
+
+        assert_eq!(Some(PAGE_SIZE_16K), elf.max_page_size());
+        for phdr in elf.segments.iter().map(|sgt| &sgt.header) {
+            match phdr.get_type() {
+                segment::Type::Load => {
+                    assert_eq!(phdr.p_offset % PAGE_SIZE_16K, phdr.p_vaddr % PAGE_SIZE_16K)
+                }
+                segment::Type::GNURelRO => {
+                    assert_eq!(0, (phdr.p_vaddr + phdr.p_memsz) % PAGE_SIZE_16K)
+                }
+                _ => {}
+            }
+        }
+        assert_eq!(PAGE_SIZE_16K, Layout::from_elf(&elf).page_size);
+
+        // p_vaddr < p_offset の壊れたLOADは基底アドレスに影響しない
+        let mut elf = elf;
+        let base = Layout::from_elf(&elf).base_addr;
+        let last = elf.segments.len() - 1;
+        elf.segments[last].header.set_type(segment::Type::Load);
+        elf.segments[last].header.p_vaddr = 0;
+        elf.segments[last].header.p_offset = 0x1000;
+        assert_eq!(base, Layout::from_elf(&elf).base_addr);
+
+        let err = Layout::new().page_size(0x3000).apply(&mut elf);
+        assert!(matches!(err, Err(LayoutError::InvalidPageSize { .. })));
+        // 0で割らずにエラーとなる
+        let err = crate::builder::SharedObjectWriter::new()
+            .layout(Layout::new().page_size(0))
+            .function(crate::builder::ExportedFunction::new("f", vec![0xc3]))
+            .build();
+        assert!(matches!(
+            err,
+            Err(crate::builder::WriterError::Layout(
+                LayoutError::InvalidPageSize { page_size: 0 }
+            ))
+        ));
+    }
+
+    #[test]
+    fn sht_placement_test() {
+        let mut elf = file::ELF64::default();
+        elf.ehdr.set_class(header::Class::Bit64);
+        elf.ehdr.set_data(header::Data::LSB2);
+        add_alloc_section(&mut elf, ".rodata", 0x10);
+        elf.rebuild_shstrtab();
+
+        Layout::new()
+            .sht_placement(ShtPlacement::AfterHeader)
+            .apply(&mut elf)
+            .unwrap();
+        let first = elf.sections[1].header.sh_offset;
+        assert!(elf.ehdr.e_shoff < first);
+        assert!(elf.ehdr.e_shoff + 3 * section::Shdr64::SIZE as Elf64Off <= first);
+        assert_eq!(
+            ShtPlacement::AfterHeader,
+            Layout::from_elf(&elf).sht_placement
+        );
+
+        // 書き出したファイルを読み直しても，セクションが同じ位置にある
+        let parsed =
+            crate::parser::parse_elf64_buf_with("sht", &elf.to_le_bytes(), &Default::default())
+                .unwrap();
+        assert_eq!(".rodata", parsed.sections[1].name.as_ref() as &str);
+        assert_eq!(first, parsed.sections[1].header.sh_offset);
+
+        // プログラムヘッダテーブルと重なる
+        let err = Layout::new()
+            .sht_placement(ShtPlacement::FixedOffset(0x40))
+            .apply(&mut elf);
+        assert!(matches!(
+            err,
+            Err(LayoutError::ShtConflict { offset: 0x40 })
+        ));
+        Layout::new()
+            .sht_placement(ShtPlacement::FixedOffset(0x2000))
+            .apply(&mut elf)
+            .unwrap();
+        assert_eq!(0x2000, elf.ehdr.e_shoff);
+
+        Layout::new()
+            .sht_placement(ShtPlacement::Omit)
+            .apply(&mut elf)
+            .unwrap();
+        assert_eq!(
+            (0, 0, 0),
+            (elf.ehdr.e_shoff, elf.ehdr.e_shnum, elf.ehdr.e_shstrndx)
+        );
+        assert_eq!(ShtPlacement::Omit, Layout::from_elf(&elf).sht_placement);
+    }
+}
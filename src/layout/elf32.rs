@@ -0,0 +1,169 @@
+//! Laying out 32bit files with the same engine as `Layout::apply()`.
+
+use std::convert::TryFrom;
+
+use super::{ClassSizes, Layout, LayoutError};
+use crate::*;
+
+impl Layout {
+    /// assign `sh_offset`/`sh_addr` of every section and regenerate `PT_LOAD` segments of a 32bit file.
+    ///
+    /// The rules are the same as `apply()`.
+    /// The sizes of the headers are the ones of `ELFCLASS32`,
+    /// and a value which doesn't fit in 32 bits is reported as `LayoutError::Overflow32`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use elf_utilities::{file, layout, section, segment};
+    ///
+    /// let mut elf = file::ELF32::default();
+    /// elf.sections.push(section::Section32::new_null_section());
+    /// elf.sections.push(section::Section32::new(
+    ///     ".text".to_string(),
+    ///     section::ShdrPreparation32::default()
+    ///         .ty(section::Type::ProgBits)
+    ///         .flags([section::Flag::Alloc, section::Flag::ExecInstr].iter()),
+    ///     section::Contents32::Raw(vec![0xc3]),
+    /// ));
+    ///
+    /// layout::Layout::new().base_addr(0x8048000).apply_elf32(&mut elf).unwrap();
+    /// assert_eq!(2, elf.segments.len());
+    /// assert_eq!(segment::Type::Load, elf.segments[1].header.get_type());
+    /// assert_eq!(0x8049000 + elf.sections[1].header.sh_offset, elf.sections[1].header.sh_addr);
+    ///
+    /// // 32bitのアドレス空間に収まらない
+    /// let err = layout::Layout::new().base_addr(0xffff_f000).apply_elf32(&mut elf);
+    /// assert!(err.is_err());
+    /// ```
+    pub fn apply_elf32(&self, elf: &mut file::ELF32) -> Result<(), LayoutError> {
+        let mut shadow = widen(elf);
+        self.place(&mut shadow, &ClassSizes::BIT32)?;
+
+        // 32bitに収まることを確かめてから書き戻す
+        let mut headers = Vec::with_capacity(elf.sections.len());
+        for (sct, wide) in elf.sections.iter().zip(shadow.sections.iter()) {
+            let h = &wide.header;
+            let narrow = |field: &'static str, value: u64| {
+                u32::try_from(value).map_err(|_| LayoutError::Overflow32 {
+                    name: sct.name.clone(),
+                    field,
+                    value,
+                })
+            };
+            narrow("the end", h.sh_addr + h.sh_size)?;
+            narrow("the end of file image", h.sh_offset + h.sh_size)?;
+            headers.push((
+                narrow("sh_offset", h.sh_offset)?,
+                narrow("sh_addr", h.sh_addr)?,
+                narrow("sh_size", h.sh_size)?,
+            ));
+        }
+        let mut segments = Vec::with_capacity(shadow.segments.len());
+        for sgt in shadow.segments.iter() {
+            segments.push(narrow_segment(&sgt.header)?);
+        }
+        let e_shoff = u32::try_from(shadow.ehdr.e_shoff).map_err(|_| LayoutError::Overflow32 {
+            name: "the section header table".to_string(),
+            field: "e_shoff",
+            value: shadow.ehdr.e_shoff,
+        })?;
+
+        for (sct, (offset, addr, size)) in elf.sections.iter_mut().zip(headers) {
+            sct.header.sh_offset = offset;
+            sct.header.sh_addr = addr;
+            sct.header.sh_size = size;
+        }
+        elf.segments = segments;
+        elf.ehdr.e_phoff = shadow.ehdr.e_phoff as Elf32Off;
+        elf.ehdr.e_phnum = shadow.ehdr.e_phnum;
+        elf.ehdr.e_shoff = e_shoff;
+        elf.ehdr.e_shnum = shadow.ehdr.e_shnum;
+        elf.ehdr.e_shstrndx = shadow.ehdr.e_shstrndx;
+        Ok(())
+    }
+}
+
+/// create an `ELF64` which has the same headers and the encoded contents.
+/// only the fields used by the layout engine are copied.
+fn widen(elf: &file::ELF32) -> file::ELF64 {
+    let sections = elf
+        .sections
+        .iter()
+        .map(|sct| {
+            let h = &sct.header;
+            let contents = if h.get_type() == section::Type::NoBits {
+                Vec::new()
+            } else {
+                sct.to_le_bytes()
+            };
+            section::Section64 {
+                name: sct.name.as_str().into(),
+                header: section::Shdr64 {
+                    sh_name: h.sh_name,
+                    sh_type: h.sh_type,
+                    sh_flags: h.sh_flags as Elf64Xword,
+                    sh_addr: h.sh_addr as Elf64Addr,
+                    sh_offset: h.sh_offset as Elf64Off,
+                    sh_size: h.sh_size as Elf64Xword,
+                    sh_link: h.sh_link,
+                    sh_info: h.sh_info,
+                    sh_addralign: h.sh_addralign as Elf64Xword,
+                    sh_entsize: h.sh_entsize as Elf64Xword,
+                },
+                contents: section::Contents64::Raw(contents),
+            }
+        })
+        .collect();
+    let segments = elf
+        .segments
+        .iter()
+        .map(|sgt| {
+            let p = &sgt.header;
+            segment::Segment64 {
+                header: segment::Phdr64 {
+                    p_type: p.p_type,
+                    p_flags: p.p_flags,
+                    p_offset: p.p_offset as Elf64Off,
+                    p_vaddr: p.p_vaddr as Elf64Addr,
+                    p_paddr: p.p_paddr as Elf64Addr,
+                    p_filesz: p.p_filesz as Elf64Xword,
+                    p_memsz: p.p_memsz as Elf64Xword,
+                    p_align: p.p_align as Elf64Xword,
+                },
+            }
+        })
+        .collect();
+
+    let mut ehdr = header::Ehdr64::default();
+    ehdr.e_ident = elf.ehdr.e_ident;
+    file::ELF64 {
+        ehdr,
+        sections,
+        segments,
+        address_cache: Default::default(),
+    }
+}
+
+fn narrow_segment(p: &segment::Phdr64) -> Result<segment::Segment32, LayoutError> {
+    let narrow = |field: &'static str, value: u64| {
+        u32::try_from(value).map_err(|_| LayoutError::Overflow32 {
+            name: format!("the {} segment", segment::Type::from(p.p_type)),
+            field,
+            value,
+        })
+    };
+    narrow("the end", p.p_vaddr + p.p_memsz)?;
+    Ok(segment::Segment32 {
+        header: segment::Phdr32 {
+            p_type: p.p_type,
+            p_offset: narrow("p_offset", p.p_offset)?,
+            p_vaddr: narrow("p_vaddr", p.p_vaddr)?,
+            p_paddr: narrow("p_paddr", p.p_paddr)?,
+            p_filesz: narrow("p_filesz", p.p_filesz)?,
+            p_memsz: narrow("p_memsz", p.p_memsz)?,
+            p_flags: p.p_flags,
+            p_align: narrow("p_align", p.p_align)?,
+        },
+    })
+}
@@ -0,0 +1,959 @@
+//! A subset of GNU ld linker scripts which drives the layout engine.
+
+use std::collections::BTreeMap;
+
+use crate::layout::{checked_align_up, is_alloc, is_nobits, Layout, LayoutConstraint};
+use crate::*;
+use thiserror::Error as TError;
+
+#[derive(TError, Debug, Clone, PartialEq, Eq)]
+pub enum LinkerScriptError {
+    #[error("line {line}: {message}")]
+    Syntax { line: usize, message: String },
+    #[error("memory region `{name}` is not defined")]
+    UnknownRegion { name: String },
+    #[error("section `{section}` overflows memory region `{region}`")]
+    RegionOverflow { region: String, section: String },
+    #[error("`{name}` is beyond the end of the address space")]
+    AddressOverflow { name: String },
+    #[error("division by zero")]
+    DivisionByZero,
+    #[error("alignment {align:#x} is not a power of two")]
+    InvalidAlign { align: u64 },
+}
+
+/// A region declared in `MEMORY`
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MemoryRegion {
+    pub name: String,
+    /// the attributes like `rx`, empty if omitted
+    pub attributes: String,
+    pub origin: Elf64Addr,
+    pub length: Elf64Xword,
+}
+
+impl MemoryRegion {
+    /// the address after the region, `None` if it overflows.
+    pub fn end(&self) -> Option<Elf64Addr> {
+        self.origin.checked_add(self.length)
+    }
+
+    fn checked_end(&self) -> Result<Elf64Addr, LinkerScriptError> {
+        self.end()
+            .ok_or_else(|| LinkerScriptError::AddressOverflow {
+                name: self.name.clone(),
+            })
+    }
+}
+
+/// A parsed linker script
+///
+/// The output sections are matched with the sections of an `ELF64` by their names,
+/// since the input section descriptions have no meaning for linked files.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinkerScript {
+    /// the symbol of `ENTRY()`
+    pub entry: Option<String>,
+    pub memory: Vec<MemoryRegion>,
+    statements: Vec<Statement>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Statement {
+    /// `. = expr;`(`add` for `+=`)
+    Dot {
+        expr: Expr,
+        add: bool,
+    },
+    Section(OutputSection),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct OutputSection {
+    name: String,
+    addr: Option<Expr>,
+    /// `AT(lma)`
+    at: Option<Expr>,
+    align: Option<Expr>,
+    /// `> region`
+    region: Option<String>,
+    /// `AT> region`
+    lma_region: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Expr {
+    Num(u64),
+    Dot,
+    Origin(String),
+    Length(String),
+    Align(Box<Expr>),
+    Binary(char, Box<Expr>, Box<Expr>),
+}
+
+/// parse a linker script of the subset below.
+///
+/// - `MEMORY` with `ORIGIN`/`LENGTH`(and their abbreviations)
+/// - `SECTIONS` with the assignments to `.`, output section addresses, `AT()`, `ALIGN()`,
+///   `> region` and `AT> region`
+/// - expressions of numbers(with `K`/`M`), `.`, `ORIGIN()`, `LENGTH()`, `ALIGN()` and `+ - * /`
+///
+/// `ENTRY()` is recorded, and the other commands, symbol assignments and `PROVIDE()` are skipped.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::layout;
+///
+/// let script = layout::from_linker_script(
+///     "MEMORY {
+///          FLASH (rx) : ORIGIN = 0x08000000, LENGTH = 64K
+///          RAM (rwx) : ORIGIN = 0x20000000, LENGTH = 20K
+///      }
+///      SECTIONS {
+///          .text : { *(.text*) } > FLASH
+///          .data : { *(.data*) } > RAM AT> FLASH
+///      }",
+/// )
+/// .unwrap();
+/// assert_eq!(0x10000, script.memory[0].length);
+/// assert_eq!(vec![".text", ".data"], script.section_names());
+/// ```
+pub fn from_linker_script(script: &str) -> Result<LinkerScript, LinkerScriptError> {
+    let tokens = tokenize(script);
+    let mut parser = Parser {
+        tokens: &tokens,
+        pos: 0,
+        memory: Vec::new(),
+    };
+    let mut entry = None;
+    let mut statements = Vec::new();
+    while let Some(token) = parser.peek() {
+        match token {
+            "MEMORY" => {
+                parser.next();
+                parser.memory()?;
+            }
+            "SECTIONS" => {
+                parser.next();
+                statements.extend(parser.sections()?);
+            }
+            "ENTRY" => {
+                parser.next();
+                parser.expect("(")?;
+                entry = Some(parser.word()?);
+                parser.expect(")")?;
+            }
+            _ => parser.skip_statement()?,
+        }
+    }
+    Ok(LinkerScript {
+        entry,
+        memory: parser.memory,
+        statements,
+    })
+}
+
+impl LinkerScript {
+    /// the names of the output sections in the order of the script.
+    pub fn section_names(&self) -> Vec<&str> {
+        self.statements
+            .iter()
+            .filter_map(|stmt| match stmt {
+                Statement::Section(os) => Some(os.name.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn region(&self, name: &str) -> Option<&MemoryRegion> {
+        self.memory.iter().find(|region| region.name == name)
+    }
+
+    /// assign the addresses to the `SHF_ALLOC` sections of `elf` as ld does, and return them as constraints.
+    ///
+    /// Each placed section gets `FixedVaddr`, `FixedLma` if its LMA differs,
+    /// and `After` the previous one so that the order of the script is checked.
+    /// The output sections which `elf` doesn't have are skipped like empty sections.
+    pub fn constraints(
+        &self,
+        elf: &file::ELF64,
+    ) -> Result<Vec<(String, LayoutConstraint)>, LinkerScriptError> {
+        // 各メモリ領域の次の空き
+        let mut cursors: BTreeMap<&str, Elf64Addr> = self
+            .memory
+            .iter()
+            .map(|region| (region.name.as_str(), region.origin))
+            .collect();
+        let mut dot: Elf64Addr = 0;
+        let mut prev: Option<&str> = None;
+        let mut constraints = Vec::new();
+
+        for stmt in self.statements.iter() {
+            let os = match stmt {
+                Statement::Dot { expr, add } => {
+                    let value = self.eval(expr, dot)?;
+                    dot = if *add {
+                        dot.checked_add(value).ok_or_else(|| {
+                            LinkerScriptError::AddressOverflow {
+                                name: ".".to_string(),
+                            }
+                        })?
+                    } else {
+                        value
+                    };
+                    continue;
+                }
+                Statement::Section(os) => os,
+            };
+            let sct = match elf.first_section_by(|sct| sct.name == os.name) {
+                Some(sct) if is_alloc(&sct.header) => sct,
+                _ => continue,
+            };
+            let mut align = sct.header.sh_addralign.max(1);
+            if let Some(expr) = &os.align {
+                align = align.max(check_align(self.eval(expr, dot)?)?);
+            }
+            let size = sct.header.sh_size;
+            let overflow = || LinkerScriptError::AddressOverflow {
+                name: os.name.clone(),
+            };
+
+            let vaddr = match (&os.addr, &os.region) {
+                (Some(expr), _) => self.eval(expr, dot)?,
+                (None, Some(name)) => {
+                    let region = self.region_of(name)?;
+                    // 領域内で.が進められていれば従う
+                    let cursor = cursors[name.as_str()];
+                    let base = if region.origin <= dot && dot < region.checked_end()? {
+                        cursor.max(dot)
+                    } else {
+                        cursor
+                    };
+                    checked_align_up(base, align).ok_or_else(overflow)?
+                }
+                (None, None) => checked_align_up(dot, align).ok_or_else(overflow)?,
+            };
+            let end = vaddr.checked_add(size).ok_or_else(overflow)?;
+            if let Some(name) = &os.region {
+                let region = self.region_of(name)?;
+                if end > region.checked_end()? {
+                    return Err(LinkerScriptError::RegionOverflow {
+                        region: name.clone(),
+                        section: os.name.clone(),
+                    });
+                }
+                cursors.insert(name.as_str(), end);
+            }
+            dot = end;
+
+            let lma = match (&os.at, &os.lma_region) {
+                (Some(expr), _) => Some(self.eval(expr, dot)?),
+                (None, Some(name)) => {
+                    let region = self.region_of(name)?;
+                    let lma =
+                        checked_align_up(cursors[name.as_str()], align).ok_or_else(overflow)?;
+                    // NOBITSはLMA上に領域を持たない
+                    let stored = if is_nobits(&sct.header) { 0 } else { size };
+                    let lma_end = lma.checked_add(stored).ok_or_else(overflow)?;
+                    if lma_end > region.checked_end()? {
+                        return Err(LinkerScriptError::RegionOverflow {
+                            region: name.clone(),
+                            section: os.name.clone(),
+                        });
+                    }
+                    cursors.insert(name.as_str(), lma_end);
+                    Some(lma)
+                }
+                (None, None) => None,
+            };
+
+            constraints.push((os.name.clone(), LayoutConstraint::FixedVaddr(vaddr)));
+            if let Some(lma) = lma.filter(|lma| *lma != vaddr) {
+                constraints.push((os.name.clone(), LayoutConstraint::FixedLma(lma)));
+            }
+            if let Some(prev) = prev {
+                constraints.push((os.name.clone(), LayoutConstraint::After(prev.to_string())));
+            }
+            prev = Some(&os.name);
+        }
+        Ok(constraints)
+    }
+
+    /// a `Layout` with `constraints()`, which `Layout::apply()` lays `elf` out with.
+    ///
+    /// The ELF header is still loaded at `base_addr`, so it must not overlap the regions.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use elf_utilities::{file, layout, section};
+    ///
+    /// let mut elf = file::ELF64::default();
+    /// for (name, flags) in [
+    ///     (".text", [section::Flag::Alloc, section::Flag::ExecInstr]),
+    ///     (".data", [section::Flag::Alloc, section::Flag::Write]),
+    /// ] {
+    ///     elf.add_section(section::Section64::new(
+    ///         name.to_string(),
+    ///         section::ShdrPreparation64::default()
+    ///             .ty(section::Type::ProgBits)
+    ///             .flags(flags.iter()),
+    ///         section::Contents64::Raw(vec![0; 0x10]),
+    ///     ));
+    /// }
+    ///
+    /// let script = layout::from_linker_script(
+    ///     "MEMORY { FLASH : ORIGIN = 0x08000000, LENGTH = 64K  RAM : ORIGIN = 0x20000000, LENGTH = 20K }
+    ///      SECTIONS {
+    ///          .text : { *(.text*) } > FLASH
+    ///          .data : { *(.data*) } > RAM AT> FLASH
+    ///      }",
+    /// )
+    /// .unwrap();
+    /// script.layout(&elf).unwrap().apply(&mut elf).unwrap();
+    /// assert_eq!(0x0800_0000, elf.sections[1].header.sh_addr);
+    /// assert_eq!(0x2000_0000, elf.sections[2].header.sh_addr);
+    /// assert_eq!(Some(0x0800_0010), elf.section_lma(2));
+    /// ```
+    pub fn layout(&self, elf: &file::ELF64) -> Result<Layout, LinkerScriptError> {
+        let mut layout = Layout::new();
+        layout.constraints = self.constraints(elf)?;
+        Ok(layout)
+    }
+
+    fn region_of(&self, name: &str) -> Result<&MemoryRegion, LinkerScriptError> {
+        self.region(name)
+            .ok_or_else(|| LinkerScriptError::UnknownRegion {
+                name: name.to_string(),
+            })
+    }
+
+    fn eval(&self, expr: &Expr, dot: Elf64Addr) -> Result<u64, LinkerScriptError> {
+        eval(expr, dot, &self.memory)
+    }
+}
+
+fn eval(expr: &Expr, dot: Elf64Addr, memory: &[MemoryRegion]) -> Result<u64, LinkerScriptError> {
+    let region = |name: &str| {
+        memory
+            .iter()
+            .find(|region| region.name == name)
+            .ok_or_else(|| LinkerScriptError::UnknownRegion {
+                name: name.to_string(),
+            })
+    };
+    Ok(match expr {
+        Expr::Num(n) => *n,
+        Expr::Dot => dot,
+        Expr::Origin(name) => region(name)?.origin,
+        Expr::Length(name) => region(name)?.length,
+        Expr::Align(align) => {
+            let align = check_align(eval(align, dot, memory)?)?;
+            checked_align_up(dot, align).ok_or_else(|| LinkerScriptError::AddressOverflow {
+                name: "ALIGN()".to_string(),
+            })?
+        }
+        Expr::Binary(op, lhs, rhs) => {
+            let lhs = eval(lhs, dot, memory)?;
+            let rhs = eval(rhs, dot, memory)?;
+            match op {
+                '+' => lhs.wrapping_add(rhs),
+                '-' => lhs.wrapping_sub(rhs),
+                '*' => lhs.wrapping_mul(rhs),
+                _ => lhs
+                    .checked_div(rhs)
+                    .ok_or(LinkerScriptError::DivisionByZero)?,
+            }
+        }
+    })
+}
+
+/// `ALIGN()` accepts only powers of two, as ld does.
+fn check_align(align: u64) -> Result<u64, LinkerScriptError> {
+    if align.is_power_of_two() {
+        Ok(align)
+    } else {
+        Err(LinkerScriptError::InvalidAlign { align })
+    }
+}
+
+/// (token, line)
+fn tokenize(script: &str) -> Vec<(String, usize)> {
+    let chars: Vec<char> = script.chars().collect();
+    let mut tokens = Vec::new();
+    let mut line = 1;
+    let mut i = 0;
+    let is_word = |c: char| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '$');
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '\n' {
+            line += 1;
+            i += 1;
+        } else if c.is_whitespace() {
+            i += 1;
+        } else if c == '/' && chars.get(i + 1) == Some(&'*') {
+            // コメント
+            i += 2;
+            while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                if chars[i] == '\n' {
+                    line += 1;
+                }
+                i += 1;
+            }
+            i += 2;
+        } else if chars[i..].starts_with(&['/', 'D', 'I', 'S', 'C', 'A', 'R', 'D', '/']) {
+            tokens.push(("/DISCARD/".to_string(), line));
+            i += 9;
+        } else if is_word(c) {
+            let start = i;
+            while i < chars.len() && is_word(chars[i]) {
+                i += 1;
+            }
+            tokens.push((chars[start..i].iter().collect(), line));
+        } else if c == '+' && chars.get(i + 1) == Some(&'=') {
+            tokens.push(("+=".to_string(), line));
+            i += 2;
+        } else {
+            tokens.push((c.to_string(), line));
+            i += 1;
+        }
+    }
+    tokens
+}
+
+struct Parser<'a> {
+    tokens: &'a [(String, usize)],
+    pos: usize,
+    memory: Vec<MemoryRegion>,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.pos).map(|(t, _)| t.as_str())
+    }
+    fn peek_at(&self, n: usize) -> Option<&'a str> {
+        self.tokens.get(self.pos + n).map(|(t, _)| t.as_str())
+    }
+    fn next(&mut self) -> Option<&'a str> {
+        let token = self.peek();
+        self.pos += 1;
+        token
+    }
+
+    fn error(&self, message: String) -> LinkerScriptError {
+        let line = self
+            .tokens
+            .get(self.pos)
+            .or_else(|| self.tokens.last())
+            .map_or(1, |(_, line)| *line);
+        LinkerScriptError::Syntax { line, message }
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), LinkerScriptError> {
+        match self.peek() {
+            Some(token) if token == expected => {
+                self.pos += 1;
+                Ok(())
+            }
+            Some(token) => {
+                Err(self.error(format!("expected `{}` but found `{}`", expected, token)))
+            }
+            None => Err(self.error(format!("expected `{}` but the script ended", expected))),
+        }
+    }
+
+    fn word(&mut self) -> Result<String, LinkerScriptError> {
+        match self.peek() {
+            Some(token)
+                if token
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "_.$".contains(c)) =>
+            {
+                self.pos += 1;
+                Ok(token.to_string())
+            }
+            Some(token) => Err(self.error(format!("expected a name but found `{}`", token))),
+            None => Err(self.error("expected a name but the script ended".to_string())),
+        }
+    }
+
+    /// skip the tokens up to the matching closing bracket, the opening one already consumed.
+    fn skip_until_closed(&mut self, open: &str, close: &str) -> Result<(), LinkerScriptError> {
+        let mut depth = 1;
+        while depth != 0 {
+            match self.next() {
+                Some(token) if token == open => depth += 1,
+                Some(token) if token == close => depth -= 1,
+                Some(_) => {}
+                None => return Err(self.error(format!("`{}` is not closed", open))),
+            }
+        }
+        Ok(())
+    }
+
+    /// skip an unsupported command like `OUTPUT_ARCH(arm)` or an assignment like `_estack = ...;`.
+    fn skip_statement(&mut self) -> Result<(), LinkerScriptError> {
+        while let Some(token) = self.next() {
+            match token {
+                ";" => return Ok(()),
+                "(" => {
+                    self.skip_until_closed("(", ")")?;
+                    if self.peek() == Some(";") {
+                        self.pos += 1;
+                    }
+                    return Ok(());
+                }
+                "{" | "}" => return Err(self.error(format!("unexpected `{}`", token))),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn memory(&mut self) -> Result<(), LinkerScriptError> {
+        self.expect("{")?;
+        while self.peek() != Some("}") {
+            let name = self.word()?;
+            let mut attributes = String::new();
+            if self.peek() == Some("(") {
+                self.pos += 1;
+                while let Some(token) = self.next() {
+                    if token == ")" {
+                        break;
+                    }
+                    attributes.push_str(token);
+                }
+            }
+            self.expect(":")?;
+            let mut origin = None;
+            let mut length = None;
+            for _ in 0..2 {
+                let key = self.word()?;
+                self.expect("=")?;
+                let value = eval(&self.expr()?, 0, &self.memory)?;
+                match key.as_str() {
+                    "ORIGIN" | "org" | "o" => origin = Some(value),
+                    "LENGTH" | "len" | "l" => length = Some(value),
+                    _ => return Err(self.error(format!("unknown memory attribute `{}`", key))),
+                }
+                if self.peek() == Some(",") {
+                    self.pos += 1;
+                }
+            }
+            match (origin, length) {
+                (Some(origin), Some(length)) => {
+                    let region = MemoryRegion {
+                        name,
+                        attributes,
+                        origin,
+                        length,
+                    };
+                    region.checked_end()?;
+                    self.memory.push(region);
+                }
+                _ => return Err(self.error(format!("region `{}` needs ORIGIN and LENGTH", name))),
+            }
+        }
+        self.expect("}")
+    }
+
+    fn sections(&mut self) -> Result<Vec<Statement>, LinkerScriptError> {
+        self.expect("{")?;
+        let mut statements = Vec::new();
+        while let Some(token) = self.peek() {
+            match token {
+                "}" => {
+                    self.pos += 1;
+                    return Ok(statements);
+                }
+                ";" => self.pos += 1,
+                "." if matches!(self.peek_at(1), Some("=") | Some("+=")) => {
+                    self.pos += 1;
+                    let add = self.next() == Some("+=");
+                    let expr = self.expr()?;
+                    self.expect(";")?;
+                    statements.push(Statement::Dot { expr, add });
+                }
+                _ if matches!(self.peek_at(1), Some("=") | Some("+=")) => self.skip_statement()?,
+                // PROVIDE(...)等のコマンド
+                _ if self.peek_at(1) == Some("(") && !is_section_type(self.peek_at(2)) => {
+                    self.skip_statement()?
+                }
+                "/DISCARD/" => {
+                    self.output_section()?;
+                }
+                _ => statements.push(Statement::Section(self.output_section()?)),
+            }
+        }
+        Err(self.error("`SECTIONS` is not closed".to_string()))
+    }
+
+    fn output_section(&mut self) -> Result<OutputSection, LinkerScriptError> {
+        let name = self.next().unwrap().to_string();
+        let mut os = OutputSection {
+            name,
+            addr: None,
+            at: None,
+            align: None,
+            region: None,
+            lma_region: None,
+        };
+        // アドレスと(NOLOAD)等の種類
+        while self.peek() != Some(":") {
+            if self.peek() == Some("(") && is_section_type(self.peek_at(1)) {
+                self.pos += 1;
+                self.skip_until_closed("(", ")")?;
+            } else if os.addr.is_none() {
+                os.addr = Some(self.expr()?);
+            } else {
+                return Err(self.error(format!("unexpected `{}`", self.peek().unwrap_or(""))));
+            }
+        }
+        self.expect(":")?;
+        while self.peek() != Some("{") {
+            match self.next() {
+                Some("AT") => {
+                    self.expect("(")?;
+                    os.at = Some(self.expr()?);
+                    self.expect(")")?;
+                }
+                Some("ALIGN") => {
+                    self.expect("(")?;
+                    os.align = Some(self.expr()?);
+                    self.expect(")")?;
+                }
+                Some("SUBALIGN") => {
+                    self.expect("(")?;
+                    self.skip_until_closed("(", ")")?;
+                }
+                Some("ONLY_IF_RO") | Some("ONLY_IF_RW") => {}
+                Some(token) => {
+                    return Err(self.error(format!("unexpected `{}` in `{}`", token, os.name)))
+                }
+                None => return Err(self.error(format!("`{}` has no contents", os.name))),
+            }
+        }
+        self.expect("{")?;
+        self.skip_until_closed("{", "}")?;
+
+        loop {
+            match (self.peek(), self.peek_at(1)) {
+                (Some(">"), _) => {
+                    self.pos += 1;
+                    os.region = Some(self.word()?);
+                }
+                (Some("AT"), Some(">")) => {
+                    self.pos += 2;
+                    os.lma_region = Some(self.word()?);
+                }
+                // プログラムヘッダの指定
+                (Some(":"), Some(_)) => {
+                    self.pos += 1;
+                    self.word()?;
+                }
+                (Some("="), _) => {
+                    self.pos += 1;
+                    self.expr()?;
+                }
+                (Some(","), _) => self.pos += 1,
+                _ => return Ok(os),
+            }
+        }
+    }
+
+    fn expr(&mut self) -> Result<Expr, LinkerScriptError> {
+        let mut lhs = self.term()?;
+        while let Some(op) = self.peek().filter(|t| *t == "+" || *t == "-") {
+            self.pos += 1;
+            let rhs = self.term()?;
+            lhs = Expr::Binary(op.chars().next().unwrap(), Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn term(&mut self) -> Result<Expr, LinkerScriptError> {
+        let mut lhs = self.factor()?;
+        while let Some(op) = self.peek().filter(|t| *t == "*" || *t == "/") {
+            self.pos += 1;
+            let rhs = self.factor()?;
+            lhs = Expr::Binary(op.chars().next().unwrap(), Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn factor(&mut self) -> Result<Expr, LinkerScriptError> {
+        let token = match self.next() {
+            Some(token) => token,
+            None => {
+                return Err(self.error("expected an expression but the script ended".to_string()))
+            }
+        };
+        match token {
+            "(" => {
+                let expr = self.expr()?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            "." => Ok(Expr::Dot),
+            "ORIGIN" | "LENGTH" | "ALIGN" => {
+                self.expect("(")?;
+                let expr = match token {
+                    "ORIGIN" => Expr::Origin(self.word()?),
+                    "LENGTH" => Expr::Length(self.word()?),
+                    _ => Expr::Align(Box::new(self.expr()?)),
+                };
+                self.expect(")")?;
+                Ok(expr)
+            }
+            _ if token.starts_with(|c: char| c.is_ascii_digit()) => {
+                parse_number(token).map(Expr::Num).ok_or_else(|| {
+                    self.pos -= 1;
+                    self.error(format!("invalid number `{}`", token))
+                })
+            }
+            _ => {
+                self.pos -= 1;
+                Err(self.error(format!("unsupported expression `{}`", token)))
+            }
+        }
+    }
+}
+
+/// the output section types like `(NOLOAD)`
+fn is_section_type(token: Option<&str>) -> bool {
+    matches!(
+        token,
+        Some("NOLOAD") | Some("COPY") | Some("INFO") | Some("DSECT") | Some("OVERLAY")
+    )
+}
+
+/// `0x100`, `256`, `64K` or `1M`
+fn parse_number(token: &str) -> Option<u64> {
+    let (digits, scale) = match token.as_bytes()[token.len() - 1] {
+        b'K' | b'k' => (&token[..token.len() - 1], 1024),
+        b'M' | b'm' => (&token[..token.len() - 1], 1024 * 1024),
+        _ => (token, 1),
+    };
+    let value = match digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+    {
+        Some(hex) => u64::from_str_radix(hex, 16).ok()?,
+        None => digits.parse().ok()?,
+    };
+    value.checked_mul(scale)
+}
+
+#[cfg(test)]
+mod linker_script_tests {
+    use super::*;
+
+    const STM32: &str = r#"
+/* STM32F103C8 */
+ENTRY(Reset_Handler)
+_estack = ORIGIN(RAM) + LENGTH(RAM);
+
+MEMORY
+{
+  FLASH (rx)  : ORIGIN = 0x08000000, LENGTH = 64K
+  RAM   (xrw) : ORIGIN = 0x20000000, LENGTH = 20K
+}
+
+SECTIONS
+{
+  .isr_vector :
+  {
+    . = ALIGN(4);
+    KEEP(*(.isr_vector))
+    . = ALIGN(4);
+  } >FLASH
+
+  .text :
+  {
+    *(.text)
+    *(.text*)
+    _etext = .;
+  } >FLASH
+
+  _sidata = LOADADDR(.data);
+
+  .data :
+  {
+    _sdata = .;
+    *(.data*)
+    _edata = .;
+  } >RAM AT> FLASH
+
+  . = ALIGN(8);
+  .bss (NOLOAD) : ALIGN(16)
+  {
+    *(.bss*)
+    *(COMMON)
+  } >RAM
+
+  /DISCARD/ : { libc.a ( * ) }
+}
+"#;
+
+    fn firmware() -> file::ELF64 {
+        let mut elf = file::ELF64::default();
+        for (name, flags, size) in [
+            (".isr_vector", vec![section::Flag::Alloc], 0x40),
+            (
+                ".text",
+                vec![section::Flag::Alloc, section::Flag::ExecInstr],
+                0x102,
+            ),
+            (
+                ".data",
+                vec![section::Flag::Alloc, section::Flag::Write],
+                0x8,
+            ),
+        ] {
+            let mut sct = section::Section64::new(
+                name.to_string(),
+                section::ShdrPreparation64::default()
+                    .ty(section::Type::ProgBits)
+                    .flags(flags.iter()),
+                section::Contents64::Raw(vec![0; size]),
+            );
+            sct.header.sh_addralign = 4;
+            elf.add_section(sct);
+        }
+        let mut bss = section::Section64::new(
+            ".bss".to_string(),
+            section::ShdrPreparation64::default()
+                .ty(section::Type::NoBits)
+                .flags([section::Flag::Alloc, section::Flag::Write].iter()),
+            section::Contents64::Raw(Vec::new()),
+        );
+        bss.header.sh_size = 0x100;
+        elf.add_section(bss);
+        elf
+    }
+
+    #[test]
+    fn constraints_test() {
+        let script = from_linker_script(STM32).unwrap();
+        assert_eq!(Some("Reset_Handler".to_string()), script.entry);
+        assert_eq!("xrw", script.region("RAM").unwrap().attributes);
+        assert_eq!(
+            vec![".isr_vector", ".text", ".data", ".bss"],
+            script.section_names()
+        );
+
+        let mut elf = firmware();
+        let constraints = script.constraints(&elf).unwrap();
+        let fixed = |name: &str| -> Vec<&LayoutConstraint> {
+            constraints
+                .iter()
+                .filter(|(n, c)| n == name && !matches!(c, LayoutConstraint::After(_)))
+                .map(|(_, c)| c)
+                .collect()
+        };
+        assert_eq!(
+            vec![&LayoutConstraint::FixedVaddr(0x0800_0000)],
+            fixed(".isr_vector")
+        );
+        assert_eq!(
+            vec![&LayoutConstraint::FixedVaddr(0x0800_0040)],
+            fixed(".text")
+        );
+        // .dataは.textの直後(4バイト境界)に格納される
+        assert_eq!(
+            vec![
+                &LayoutConstraint::FixedVaddr(0x2000_0000),
+                &LayoutConstraint::FixedLma(0x0800_0144)
+            ],
+            fixed(".data")
+        );
+        assert_eq!(
+            vec![&LayoutConstraint::FixedVaddr(0x2000_0010)],
+            fixed(".bss")
+        );
+
+        script.layout(&elf).unwrap().apply(&mut elf).unwrap();
+        assert_eq!(Some(0x0800_0144), elf.section_lma(3));
+        assert_eq!(0x2000_0010, elf.sections[4].header.sh_addr);
+    }
+
+    #[test]
+    fn error_test() {
+        let err = from_linker_script(
+            "SECTIONS {\n  .text : { *(.text) } > FLASH\n  .data 0x20000000 + : {}\n}",
+        );
+        assert_eq!(
+            Err(LinkerScriptError::Syntax {
+                line: 3,
+                message: "unsupported expression `:`".to_string()
+            }),
+            err
+        );
+
+        let script = from_linker_script(
+            "MEMORY { FLASH : ORIGIN = 0x08000000, LENGTH = 0x100 }
+             SECTIONS { .text : { *(.text*) } > FLASH .data : {} > RAM }",
+        )
+        .unwrap();
+        let mut elf = firmware();
+        assert_eq!(
+            Err(LinkerScriptError::RegionOverflow {
+                region: "FLASH".to_string(),
+                section: ".text".to_string()
+            }),
+            script.constraints(&elf)
+        );
+        elf.sections.retain(|sct| sct.name != ".text");
+        assert_eq!(
+            Err(LinkerScriptError::UnknownRegion {
+                name: "RAM".to_string()
+            }),
+            script.constraints(&elf)
+        );
+    }
+
+    #[test]
+    fn overflow_test() {
+        assert_eq!(
+            Err(LinkerScriptError::AddressOverflow {
+                name: "FLASH".to_string()
+            }),
+            from_linker_script("MEMORY { FLASH : ORIGIN = 0xffffffffffffff00, LENGTH = 0x1000 }")
+        );
+
+        let elf = firmware();
+        let script =
+            from_linker_script("SECTIONS { . = 0xfffffffffffffff0; .text : ALIGN(0x100) {} }")
+                .unwrap();
+        assert_eq!(
+            Err(LinkerScriptError::AddressOverflow {
+                name: ".text".to_string()
+            }),
+            script.constraints(&elf)
+        );
+        let script =
+            from_linker_script("SECTIONS { . = 0xfffffffffffffff0; . += 0x100; }").unwrap();
+        assert!(matches!(
+            script.constraints(&elf),
+            Err(LinkerScriptError::AddressOverflow { .. })
+        ));
+
+        // ゼロ除算と2の冪でないアラインメントは拒否する
+        let script = from_linker_script("SECTIONS { . = 0x100 / 0; }").unwrap();
+        assert_eq!(
+            Err(LinkerScriptError::DivisionByZero),
+            script.constraints(&elf)
+        );
+        let script = from_linker_script("SECTIONS { . = ALIGN(3); .text : ALIGN(6) {} }").unwrap();
+        assert_eq!(
+            Err(LinkerScriptError::InvalidAlign { align: 3 }),
+            script.constraints(&elf)
+        );
+        let script = from_linker_script("SECTIONS { .text : ALIGN(6) {} }").unwrap();
+        assert_eq!(
+            Err(LinkerScriptError::InvalidAlign { align: 6 }),
+            script.constraints(&elf)
+        );
+    }
+}
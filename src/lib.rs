@@ -1,11 +1,33 @@
+pub mod abi;
+pub mod analysis;
+#[cfg(feature = "arbitrary")]
+pub mod arbitrary;
+pub mod bpf;
+pub mod btf;
+pub mod builder;
+pub mod craft;
 pub mod dynamic;
 pub mod file;
+mod flags_debug;
 pub mod header;
+pub mod intern;
+pub mod layout;
+pub mod mips;
+pub mod output;
 pub mod parser;
+#[cfg(feature = "patcher")]
+pub mod patcher;
+pub mod process;
 pub mod relocation;
+pub mod scan;
 pub mod section;
 pub mod segment;
 pub mod symbol;
+pub mod symbolize;
+pub mod transform;
+pub mod unwind;
+pub mod util;
+pub mod validation;
 
 #[allow(unused)]
 /* Type for a 16-bit quantity.  */
@@ -0,0 +1,362 @@
+//! MIPS-specific sections and dynamic tags.
+//!
+//! The values are in the processor-specific ranges, so they're meaningful only when `e_machine` is MIPS.
+//! The payloads are decoded in the data encoding of the file, since MIPS firmware is often big endian.
+
+use crate::*;
+use section::{PayloadReader, ReadError};
+
+/// register usage information(`.reginfo`)
+pub const SHT_MIPS_REGINFO: Elf64Word = 0x70000006;
+/// ABI flags(`.MIPS.abiflags`)
+pub const SHT_MIPS_ABIFLAGS: Elf64Word = 0x7000002a;
+
+/// version of the runtime linker interface
+pub const DT_MIPS_RLD_VERSION: Elf64Sxword = 0x70000001;
+/// flags(`RHF_*`)
+pub const DT_MIPS_FLAGS: Elf64Sxword = 0x70000005;
+/// the base address of the segments
+pub const DT_MIPS_BASE_ADDRESS: Elf64Sxword = 0x70000006;
+/// the number of local GOT entries
+pub const DT_MIPS_LOCAL_GOTNO: Elf64Sxword = 0x7000000a;
+/// the number of entries in `.dynsym`
+pub const DT_MIPS_SYMTABNO: Elf64Sxword = 0x70000011;
+/// the index of the first external dynamic symbol not referenced locally
+pub const DT_MIPS_UNREFEXTNO: Elf64Sxword = 0x70000012;
+/// the index of the first dynamic symbol which has a GOT entry
+pub const DT_MIPS_GOTSYM: Elf64Sxword = 0x70000013;
+/// the number of page table entries in the GOT
+pub const DT_MIPS_HIPAGENO: Elf64Sxword = 0x70000014;
+/// the address of the run time loader map, used for debugging
+pub const DT_MIPS_RLD_MAP: Elf64Sxword = 0x70000016;
+/// the address of `.got.plt`
+pub const DT_MIPS_PLTGOT: Elf64Sxword = 0x70000032;
+/// the offset of the run time loader map from this entry
+pub const DT_MIPS_RLD_MAP_REL: Elf64Sxword = 0x70000035;
+
+/// the name of a MIPS section type, as readelf does.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::mips;
+///
+/// assert_eq!(Some("MIPS_ABIFLAGS"), mips::section_type_name(mips::SHT_MIPS_ABIFLAGS));
+/// assert_eq!(None, mips::section_type_name(1));
+/// ```
+pub fn section_type_name(ty: Elf64Word) -> Option<&'static str> {
+    match ty {
+        SHT_MIPS_REGINFO => Some("MIPS_REGINFO"),
+        SHT_MIPS_ABIFLAGS => Some("MIPS_ABIFLAGS"),
+        _ => None,
+    }
+}
+
+/// the name of a MIPS dynamic tag, as readelf does.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::mips;
+///
+/// assert_eq!(Some("MIPS_GOTSYM"), mips::dynamic_tag_name(mips::DT_MIPS_GOTSYM));
+/// assert_eq!(None, mips::dynamic_tag_name(1));
+/// ```
+pub fn dynamic_tag_name(tag: Elf64Sxword) -> Option<&'static str> {
+    match tag {
+        DT_MIPS_RLD_VERSION => Some("MIPS_RLD_VERSION"),
+        DT_MIPS_FLAGS => Some("MIPS_FLAGS"),
+        DT_MIPS_BASE_ADDRESS => Some("MIPS_BASE_ADDRESS"),
+        DT_MIPS_LOCAL_GOTNO => Some("MIPS_LOCAL_GOTNO"),
+        DT_MIPS_SYMTABNO => Some("MIPS_SYMTABNO"),
+        DT_MIPS_UNREFEXTNO => Some("MIPS_UNREFEXTNO"),
+        DT_MIPS_GOTSYM => Some("MIPS_GOTSYM"),
+        DT_MIPS_HIPAGENO => Some("MIPS_HIPAGENO"),
+        DT_MIPS_RLD_MAP => Some("MIPS_RLD_MAP"),
+        DT_MIPS_PLTGOT => Some("MIPS_PLTGOT"),
+        DT_MIPS_RLD_MAP_REL => Some("MIPS_RLD_MAP_REL"),
+        _ => None,
+    }
+}
+
+/// The contents of `.reginfo`(`Elf32_RegInfo`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct RegInfo {
+    /// general registers used by the object
+    pub ri_gprmask: Elf32Word,
+    /// coprocessor registers used by the object
+    pub ri_cprmask: [Elf32Word; 4],
+    /// the initial value of `$gp`
+    pub ri_gp_value: Elf32Sword,
+}
+
+impl RegInfo {
+    pub const SIZE: usize = 24;
+
+    /// # Examples
+    ///
+    /// ```
+    /// use elf_utilities::{header, mips};
+    ///
+    /// let mut bytes = vec![0xf0, 0, 0, 0];
+    /// bytes.extend_from_slice(&[0; 16]);
+    /// bytes.extend_from_slice(&[0x00, 0x81, 0x7f, 0xf0]);
+    /// let info = mips::RegInfo::parse(&bytes, header::Data::MSB2).unwrap();
+    /// assert_eq!(0xf0000000, info.ri_gprmask);
+    /// assert_eq!(0x00817ff0, info.ri_gp_value);
+    /// ```
+    pub fn parse(bytes: &[u8], data: header::Data) -> Result<Self, ReadError> {
+        let mut r = PayloadReader::new(bytes, data);
+        let ri_gprmask = r.u32()?;
+        let mut ri_cprmask = [0; 4];
+        for mask in ri_cprmask.iter_mut() {
+            *mask = r.u32()?;
+        }
+        Ok(Self {
+            ri_gprmask,
+            ri_cprmask,
+            ri_gp_value: r.u32()? as Elf32Sword,
+        })
+    }
+}
+
+/// The contents of `.MIPS.abiflags`(`Elf_MIPS_ABIFlags_v0`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct AbiFlags {
+    pub version: Elf32Half,
+    /// e.g. 32 for MIPS32
+    pub isa_level: u8,
+    pub isa_rev: u8,
+    /// `AFL_REG_*`
+    pub gpr_size: u8,
+    pub cpr1_size: u8,
+    pub cpr2_size: u8,
+    /// `Val_GNU_MIPS_ABI_FP_*`
+    pub fp_abi: u8,
+    /// `AFL_EXT_*`
+    pub isa_ext: Elf32Word,
+    /// `AFL_ASE_*`
+    pub ases: Elf32Word,
+    /// `AFL_FLAGS1_*`
+    pub flags1: Elf32Word,
+    pub flags2: Elf32Word,
+}
+
+impl AbiFlags {
+    pub const SIZE: usize = 24;
+
+    pub fn parse(bytes: &[u8], data: header::Data) -> Result<Self, ReadError> {
+        let mut r = PayloadReader::new(bytes, data);
+        Ok(Self {
+            version: r.u16()?,
+            isa_level: r.u8()?,
+            isa_rev: r.u8()?,
+            gpr_size: r.u8()?,
+            cpr1_size: r.u8()?,
+            cpr2_size: r.u8()?,
+            fp_abi: r.u8()?,
+            isa_ext: r.u32()?,
+            ases: r.u32()?,
+            flags1: r.u32()?,
+            flags2: r.u32()?,
+        })
+    }
+}
+
+/// The MIPS dynamic tags, which describe the layout of the GOT
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct DynamicInfo {
+    pub rld_version: Option<Elf64Xword>,
+    pub flags: Option<Elf64Xword>,
+    pub base_address: Option<Elf64Addr>,
+    pub local_gotno: Option<Elf64Xword>,
+    pub symtabno: Option<Elf64Xword>,
+    pub gotsym: Option<Elf64Xword>,
+}
+
+impl DynamicInfo {
+    /// collect the MIPS tags from `(d_tag, d_un)` pairs.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use elf_utilities::mips;
+    ///
+    /// let info = mips::DynamicInfo::from_entries(vec![
+    ///     (mips::DT_MIPS_LOCAL_GOTNO, 4),
+    ///     (mips::DT_MIPS_SYMTABNO, 10),
+    ///     (mips::DT_MIPS_GOTSYM, 7),
+    /// ]);
+    /// assert_eq!(Some(3), info.global_gotno());
+    /// assert_eq!(Some(7), info.got_entries());
+    /// ```
+    pub fn from_entries<I>(entries: I) -> Self
+    where
+        I: IntoIterator<Item = (Elf64Sxword, Elf64Xword)>,
+    {
+        let mut info = Self::default();
+        for (tag, value) in entries {
+            let field = match tag {
+                DT_MIPS_RLD_VERSION => &mut info.rld_version,
+                DT_MIPS_FLAGS => &mut info.flags,
+                DT_MIPS_BASE_ADDRESS => &mut info.base_address,
+                DT_MIPS_LOCAL_GOTNO => &mut info.local_gotno,
+                DT_MIPS_SYMTABNO => &mut info.symtabno,
+                DT_MIPS_GOTSYM => &mut info.gotsym,
+                // DT_NULL以降は使われない
+                0 => break,
+                _ => continue,
+            };
+            *field = Some(value);
+        }
+        info
+    }
+
+    /// the number of GOT entries for the dynamic symbols from `gotsym`.
+    pub fn global_gotno(&self) -> Option<Elf64Xword> {
+        self.symtabno?.checked_sub(self.gotsym?)
+    }
+
+    /// the number of all GOT entries.
+    pub fn got_entries(&self) -> Option<Elf64Xword> {
+        self.local_gotno?.checked_add(self.global_gotno()?)
+    }
+}
+
+impl file::ELF64 {
+    /// decode `.reginfo` if exists.
+    pub fn mips_reginfo(&self) -> Option<Result<RegInfo, ReadError>> {
+        let sct = self.first_section_by(|sct| sct.header.sh_type == SHT_MIPS_REGINFO)?;
+        Some(RegInfo::parse(&sct.to_le_bytes(), self.ehdr.get_data()))
+    }
+
+    /// decode `.MIPS.abiflags` if exists.
+    pub fn mips_abiflags(&self) -> Option<Result<AbiFlags, ReadError>> {
+        let sct = self.first_section_by(|sct| sct.header.sh_type == SHT_MIPS_ABIFLAGS)?;
+        Some(AbiFlags::parse(&sct.to_le_bytes(), self.ehdr.get_data()))
+    }
+
+    /// collect the MIPS tags of `.dynamic`.
+    pub fn mips_dynamic(&self) -> Option<DynamicInfo> {
+        let sct = self.first_section_by(|sct| sct.header.get_type() == section::Type::Dynamic)?;
+        match &sct.contents {
+            section::Contents64::Dynamics(entries) => Some(DynamicInfo::from_entries(
+                entries.iter().map(|d| (d.d_tag, d.d_un)),
+            )),
+            _ => None,
+        }
+    }
+}
+
+impl file::ELF32 {
+    /// decode `.reginfo` if exists.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use elf_utilities::{file, mips, section};
+    ///
+    /// let mut elf = file::ELF32::default();
+    /// elf.sections.push(section::Section32::new_null_section());
+    /// let hdr = section::ShdrPreparation32::default().ty(section::Type::Any(mips::SHT_MIPS_REGINFO));
+    /// let mut bytes = vec![0; mips::RegInfo::SIZE];
+    /// bytes[0] = 0xf0;
+    /// elf.add_section(section::Section32::new(".reginfo".to_string(), hdr, section::Contents32::Raw(bytes)));
+    ///
+    /// assert_eq!(Some(0xf0), elf.mips_reginfo().map(|info| info.unwrap().ri_gprmask));
+    /// assert!(elf.mips_abiflags().is_none());
+    /// ```
+    pub fn mips_reginfo(&self) -> Option<Result<RegInfo, ReadError>> {
+        let sct = self
+            .sections
+            .iter()
+            .find(|sct| sct.header.sh_type == SHT_MIPS_REGINFO)?;
+        Some(RegInfo::parse(&sct.to_le_bytes(), self.ehdr.get_data()))
+    }
+
+    /// decode `.MIPS.abiflags` if exists.
+    pub fn mips_abiflags(&self) -> Option<Result<AbiFlags, ReadError>> {
+        let sct = self
+            .sections
+            .iter()
+            .find(|sct| sct.header.sh_type == SHT_MIPS_ABIFLAGS)?;
+        Some(AbiFlags::parse(&sct.to_le_bytes(), self.ehdr.get_data()))
+    }
+
+    /// collect the MIPS tags of `.dynamic`.
+    pub fn mips_dynamic(&self) -> Option<DynamicInfo> {
+        let sct = self
+            .sections
+            .iter()
+            .find(|sct| sct.header.get_type() == section::Type::Dynamic)?;
+        match &sct.contents {
+            section::Contents32::Dynamics(entries) => Some(DynamicInfo::from_entries(
+                entries
+                    .iter()
+                    .map(|d| (d.d_tag as Elf64Sxword, d.d_un as Elf64Xword)),
+            )),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod mips_tests {
+    use super::*;
+
+    #[test]
+    fn abiflags_test() {
+        // MIPS32r2, o32 FPXX相当の値
+        let bytes = [
+            0x00, 0x00, 0x20, 0x02, 0x01, 0x01, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00,
+        ];
+        let flags = AbiFlags::parse(&bytes, header::Data::MSB2).unwrap();
+        assert_eq!((0, 32, 2), (flags.version, flags.isa_level, flags.isa_rev));
+        assert_eq!(
+            (1, 1, 0, 1),
+            (
+                flags.gpr_size,
+                flags.cpr1_size,
+                flags.cpr2_size,
+                flags.fp_abi
+            )
+        );
+        assert_eq!(1, flags.flags1);
+        assert!(AbiFlags::parse(&bytes[..20], header::Data::MSB2).is_err());
+
+        let mut elf = file::ELF64::default();
+        elf.add_section(section::Section64::new(
+            ".MIPS.abiflags".to_string(),
+            section::ShdrPreparation64::default().ty(section::Type::Any(SHT_MIPS_ABIFLAGS)),
+            section::Contents64::Raw(bytes.to_vec()),
+        ));
+        // リトルエンディアンとして読む
+        assert_eq!(
+            Some(0x01000000),
+            elf.mips_abiflags().map(|f| f.unwrap().flags1)
+        );
+    }
+
+    #[test]
+    fn mips_dynamic_test() {
+        let mut elf = file::ELF64::default();
+        let entries = vec![
+            dynamic::Dyn64::new(dynamic::EntryType::Any(DT_MIPS_LOCAL_GOTNO), 2),
+            dynamic::Dyn64::new(dynamic::EntryType::Any(DT_MIPS_GOTSYM), 5),
+            dynamic::Dyn64::new(dynamic::EntryType::Null, 0),
+            dynamic::Dyn64::new(dynamic::EntryType::Any(DT_MIPS_SYMTABNO), 9),
+        ];
+        elf.add_section(section::Section64::new(
+            ".dynamic".to_string(),
+            section::ShdrPreparation64::default().ty(section::Type::Dynamic),
+            section::Contents64::Dynamics(entries),
+        ));
+        let info = elf.mips_dynamic().unwrap();
+        assert_eq!(
+            (Some(2), Some(5), None),
+            (info.local_gotno, info.gotsym, info.symtabno)
+        );
+        assert_eq!(None, info.got_entries());
+    }
+}
@@ -0,0 +1,205 @@
+//! Human-readable dumps for debugging generated files.
+//!
+//! `explain()` annotates every header field with its offset and meaning, for learning the format
+//! and finding what is wrong in malformed files.
+
+use std::fmt;
+use std::ops::Range;
+
+use crate::*;
+
+mod explain;
+
+pub use explain::*;
+
+/// fields of `Ehdr64` (offset, size, name)
+const EHDR64_FIELDS: [(usize, usize, &str); 15] = [
+    (0x00, 16, "e_ident"),
+    (0x10, 2, "e_type"),
+    (0x12, 2, "e_machine"),
+    (0x14, 4, "e_version"),
+    (0x18, 8, "e_entry"),
+    (0x20, 8, "e_phoff"),
+    (0x28, 8, "e_shoff"),
+    (0x30, 4, "e_flags"),
+    (0x34, 2, "e_ehsize"),
+    (0x36, 2, "e_phentsize"),
+    (0x38, 2, "e_phnum"),
+    (0x3a, 2, "e_shentsize"),
+    (0x3c, 2, "e_shnum"),
+    (0x3e, 2, "e_shstrndx"),
+    (0x40, 0, ""),
+];
+
+/// A part of the file which contains a file offset
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Region {
+    /// a field of the ELF header
+    ElfHeader { field: &'static str },
+    /// an entry of the program header table
+    ProgramHeader { index: usize },
+    /// an entry of the section header table
+    SectionHeader { index: usize },
+    /// contents of a section
+    Section {
+        index: usize,
+        name: String,
+        offset: Elf64Off,
+    },
+    /// a segment's file image
+    Segment {
+        index: usize,
+        ty: segment::Type,
+        offset: Elf64Off,
+    },
+}
+
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ElfHeader { field } => write!(f, "ELF header ({})", field),
+            Self::ProgramHeader { index } => write!(f, "program header [{}]", index),
+            Self::SectionHeader { index } => write!(f, "section header [{}]", index),
+            Self::Section {
+                index,
+                name,
+                offset,
+            } => write!(f, "section [{}] `{}` +{:#x}", index, name, offset),
+            Self::Segment { index, ty, offset } => {
+                write!(f, "segment [{}] {} +{:#x}", index, ty, offset)
+            }
+        }
+    }
+}
+
+/// dump the range of the file image in `hexdump -C` style.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::{file, output};
+///
+/// let elf = file::ELF64::default();
+/// let dump = output::hexdump(&elf, 0..4);
+/// assert_eq!("00000000  7f 45 4c 46                                      |.ELF|\n", dump);
+/// ```
+pub fn hexdump(elf: &file::ELF64, range: Range<usize>) -> String {
+    let bytes = elf.to_le_bytes();
+    let end = range.end.min(bytes.len());
+    let start = range.start.min(end);
+
+    let mut dump = String::new();
+    for (line, chunk) in bytes[start..end].chunks(16).enumerate() {
+        let mut hex = String::new();
+        for (i, b) in chunk.iter().enumerate() {
+            if i == 8 {
+                hex.push(' ');
+            }
+            hex.push_str(&format!("{:02x} ", b));
+        }
+        let ascii: String = chunk
+            .iter()
+            .map(|b| {
+                if b.is_ascii_graphic() || *b == b' ' {
+                    *b as char
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        dump.push_str(&format!(
+            "{:08x}  {:<49}|{}|\n",
+            start + line * 16,
+            hex,
+            ascii
+        ));
+    }
+    dump
+}
+
+/// list the regions which contain the file offset.
+/// They are ordered as the ELF header, segments, program headers, sections and section headers.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::{file, output};
+///
+/// let elf = file::ELF64::default();
+/// let regions = output::annotate(&elf, 0x20);
+/// assert_eq!("ELF header (e_phoff)", regions[0].to_string());
+///
+/// // .shstrtab starts at 0x40
+/// let regions = output::annotate(&elf, 0x41);
+/// assert_eq!("section [1] `.shstrtab` +0x1", regions[0].to_string());
+/// ```
+pub fn annotate(elf: &file::ELF64, offset: Elf64Off) -> Vec<Region> {
+    let mut regions = Vec::new();
+    // 壊れたヘッダで溢れる領域はアドレス空間の末尾までとみなす
+    let contains = |start: Elf64Off, size: Elf64Xword| {
+        start <= offset && start.checked_add(size).is_none_or(|end| offset < end)
+    };
+
+    if offset < header::Ehdr64::SIZE as Elf64Off {
+        let field = EHDR64_FIELDS
+            .windows(2)
+            .find(|w| (offset as usize) < w[1].0)
+            .map(|w| w[0].2)
+            .unwrap();
+        regions.push(Region::ElfHeader { field });
+    }
+
+    for (index, sgt) in elf.segments.iter().enumerate() {
+        let phdr = &sgt.header;
+        if contains(phdr.p_offset, phdr.p_filesz) {
+            regions.push(Region::Segment {
+                index,
+                ty: phdr.get_type(),
+                offset: offset - phdr.p_offset,
+            });
+        }
+    }
+
+    let phdr_size = segment::Phdr64::SIZE as Elf64Off;
+    if contains(elf.ehdr.e_phoff, phdr_size * elf.segments.len() as Elf64Off) {
+        regions.push(Region::ProgramHeader {
+            index: ((offset - elf.ehdr.e_phoff) / phdr_size) as usize,
+        });
+    }
+
+    for (index, sct) in elf.sections.iter().enumerate() {
+        let shdr = &sct.header;
+        if shdr.get_type() != section::Type::NoBits && contains(shdr.sh_offset, shdr.sh_size) {
+            regions.push(Region::Section {
+                index,
+                name: sct.name.to_string(),
+                offset: offset - shdr.sh_offset,
+            });
+        }
+    }
+
+    let shdr_size = section::Shdr64::SIZE as Elf64Off;
+    if elf.ehdr.e_shoff != 0
+        && contains(elf.ehdr.e_shoff, shdr_size * elf.sections.len() as Elf64Off)
+    {
+        regions.push(Region::SectionHeader {
+            index: ((offset - elf.ehdr.e_shoff) / shdr_size) as usize,
+        });
+    }
+
+    regions
+}
+
+#[cfg(test)]
+mod output_tests {
+    use super::*;
+
+    #[test]
+    fn annotate_overflow_test() {
+        let mut elf = file::ELF64::default();
+        elf.sections[1].header.sh_offset = u64::MAX - 1;
+        elf.sections[1].header.sh_size = 0x10;
+        let regions = annotate(&elf, u64::MAX);
+        assert_eq!("section [1] `.shstrtab` +0x1", regions[0].to_string());
+    }
+}
@@ -0,0 +1,343 @@
+//! Field-by-field annotated dumps of the headers.
+
+use std::fmt;
+
+use crate::flags_debug::FlagsDebug;
+use crate::*;
+
+/// A header field annotated by `explain_fields()`
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ExplainedField {
+    /// the file offset of the field
+    pub offset: Elf64Off,
+    /// `ELF header`, `program header [1]`, `section header [2] .text`, etc.
+    pub structure: String,
+    pub name: &'static str,
+    /// the bytes of the field in the file
+    pub raw: Vec<u8>,
+    /// the decoded meaning of the value
+    pub meaning: String,
+}
+
+impl fmt::Display for ExplainedField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let raw: Vec<String> = self.raw.iter().map(|b| format!("{:02x}", b)).collect();
+        write!(
+            f,
+            "{:08x}  {:<23}  {:<22} {}",
+            self.offset,
+            raw.join(" "),
+            self.name,
+            self.meaning
+        )
+    }
+}
+
+/// list every field of the ELF header, the program headers and the section headers
+/// with its file offset, raw bytes and decoded meaning.
+///
+/// The raw bytes are taken from `to_le_bytes()`, so they are the ones written for the current headers.
+pub fn explain_fields(elf: &file::ELF64) -> Vec<ExplainedField> {
+    let bytes = elf.to_le_bytes();
+    let mut fields = Vec::new();
+    let mut push = |structure: &str, offset: Elf64Off, size: usize, name, meaning: String| {
+        let start = (offset as usize).min(bytes.len());
+        let end = (start + size).min(bytes.len());
+        fields.push(ExplainedField {
+            offset,
+            structure: structure.to_string(),
+            name,
+            raw: bytes[start..end].to_vec(),
+            meaning,
+        });
+    };
+
+    let ehdr = &elf.ehdr;
+    let ident = &ehdr.e_ident;
+    let st = "ELF header";
+    let magic = if ident.is_elf() {
+        "ELF magic"
+    } else {
+        "not ELF magic"
+    };
+    push(st, 0x00, 4, "e_ident[EI_MAG]", magic.to_string());
+    push(st, 0x04, 1, "e_ident[EI_CLASS]", ident.class.to_string());
+    push(st, 0x05, 1, "e_ident[EI_DATA]", ident.data.to_string());
+    push(
+        st,
+        0x06,
+        1,
+        "e_ident[EI_VERSION]",
+        ident.version.to_string(),
+    );
+    push(st, 0x07, 1, "e_ident[EI_OSABI]", ident.osabi.to_string());
+    push(
+        st,
+        0x08,
+        1,
+        "e_ident[EI_ABIVERSION]",
+        ident.abi_version.to_string(),
+    );
+    push(st, 0x09, 7, "e_ident[EI_PAD]", "padding".to_string());
+    push(st, 0x10, 2, "e_type", ehdr.get_type().to_string());
+    push(st, 0x12, 2, "e_machine", ehdr.get_machine().to_string());
+    push(st, 0x14, 4, "e_version", format!("{}", ehdr.e_version));
+    push(
+        st,
+        0x18,
+        8,
+        "e_entry",
+        describe_vaddr(elf, ehdr.e_entry, "entry point"),
+    );
+    push(
+        st,
+        0x20,
+        8,
+        "e_phoff",
+        format!("program header table at {:#x}", ehdr.e_phoff),
+    );
+    push(
+        st,
+        0x28,
+        8,
+        "e_shoff",
+        if ehdr.e_shoff == 0 {
+            "no section header table".to_string()
+        } else {
+            format!("section header table at {:#x}", ehdr.e_shoff)
+        },
+    );
+    push(st, 0x30, 4, "e_flags", format!("{:#x}", ehdr.e_flags));
+    push(
+        st,
+        0x34,
+        2,
+        "e_ehsize",
+        describe_size(ehdr.e_ehsize, header::Ehdr64::SIZE as usize),
+    );
+    push(
+        st,
+        0x36,
+        2,
+        "e_phentsize",
+        describe_size(ehdr.e_phentsize, segment::Phdr64::SIZE),
+    );
+    push(
+        st,
+        0x38,
+        2,
+        "e_phnum",
+        format!("{} program headers", ehdr.e_phnum),
+    );
+    push(
+        st,
+        0x3a,
+        2,
+        "e_shentsize",
+        describe_size(ehdr.e_shentsize, section::Shdr64::SIZE),
+    );
+    push(
+        st,
+        0x3c,
+        2,
+        "e_shnum",
+        format!("{} section headers", ehdr.e_shnum),
+    );
+    push(
+        st,
+        0x3e,
+        2,
+        "e_shstrndx",
+        format!(
+            "section names in {}",
+            describe_shidx(elf, ehdr.e_shstrndx as usize)
+        ),
+    );
+
+    for (i, sgt) in elf.segments.iter().enumerate() {
+        let phdr = &sgt.header;
+        let st = format!("program header [{}]", i);
+        let base = ehdr.e_phoff + (i * segment::Phdr64::SIZE) as Elf64Off;
+        let flags = FlagsDebug {
+            bits: phdr.p_flags as u64,
+            all: &segment::Flag::ALL,
+            to_bits: |f| Elf64Word::from(f) as u64,
+        };
+        push(&st, base, 4, "p_type", phdr.get_type().to_string());
+        push(&st, base + 0x04, 4, "p_flags", format!("{:?}", flags));
+        push(
+            &st,
+            base + 0x08,
+            8,
+            "p_offset",
+            format!("file image at {:#x}", phdr.p_offset),
+        );
+        push(
+            &st,
+            base + 0x10,
+            8,
+            "p_vaddr",
+            format!("loaded at {:#x}", phdr.p_vaddr),
+        );
+        push(
+            &st,
+            base + 0x18,
+            8,
+            "p_paddr",
+            format!("physical address {:#x}", phdr.p_paddr),
+        );
+        push(
+            &st,
+            base + 0x20,
+            8,
+            "p_filesz",
+            format!("{:#x} bytes in file", phdr.p_filesz),
+        );
+        let bss = if phdr.p_memsz > phdr.p_filesz {
+            format!(", {:#x} bytes zero-filled", phdr.p_memsz - phdr.p_filesz)
+        } else {
+            String::new()
+        };
+        push(
+            &st,
+            base + 0x28,
+            8,
+            "p_memsz",
+            format!("{:#x} bytes in memory{}", phdr.p_memsz, bss),
+        );
+        push(
+            &st,
+            base + 0x30,
+            8,
+            "p_align",
+            format!("aligned to {:#x}", phdr.p_align),
+        );
+    }
+
+    if ehdr.e_shoff == 0 {
+        return fields;
+    }
+    for (i, sct) in elf.sections.iter().enumerate() {
+        let shdr = &sct.header;
+        let st = format!("section header [{}] {}", i, sct.name);
+        let base = ehdr.e_shoff + (i * section::Shdr64::SIZE) as Elf64Off;
+        let flags = FlagsDebug {
+            bits: shdr.sh_flags,
+            all: &section::Flag::ALL,
+            to_bits: |f| Elf64Xword::from(f),
+        };
+        push(&st, base, 4, "sh_name", format!("`{}`", sct.name));
+        push(&st, base + 0x04, 4, "sh_type", shdr.get_type().to_string());
+        push(&st, base + 0x08, 8, "sh_flags", format!("{:?}", flags));
+        push(
+            &st,
+            base + 0x10,
+            8,
+            "sh_addr",
+            if shdr.sh_addr == 0 {
+                "not loaded".to_string()
+            } else {
+                format!("loaded at {:#x}", shdr.sh_addr)
+            },
+        );
+        push(
+            &st,
+            base + 0x18,
+            8,
+            "sh_offset",
+            format!("contents at {:#x}", shdr.sh_offset),
+        );
+        push(
+            &st,
+            base + 0x20,
+            8,
+            "sh_size",
+            format!("{:#x} bytes", shdr.sh_size),
+        );
+        let link = match shdr.sh_link {
+            0 => "no link".to_string(),
+            link => format!("linked to {}", describe_shidx(elf, link as usize)),
+        };
+        push(&st, base + 0x28, 4, "sh_link", link);
+        push(&st, base + 0x2c, 4, "sh_info", format!("{}", shdr.sh_info));
+        push(
+            &st,
+            base + 0x30,
+            8,
+            "sh_addralign",
+            format!("aligned to {:#x}", shdr.sh_addralign),
+        );
+        let entsize = match shdr.sh_entsize {
+            0 => "no fixed-size entries".to_string(),
+            size => format!("{:#x} bytes per entry", size),
+        };
+        push(&st, base + 0x38, 8, "sh_entsize", entsize);
+    }
+
+    fields
+}
+
+/// explain every header field, one per line, grouped by the headers.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::{output, parser};
+///
+/// let elf = parser::parse_elf64("src/parser/testdata/sample").unwrap();
+/// let explained = output::explain(&elf);
+///
+/// assert!(explained.starts_with("ELF header\n"));
+/// assert!(explained.contains("00000000  7f 45 4c 46"));
+/// assert!(explained.contains("section header [1]"));
+///
+/// // 壊れたフィールドは期待される値と共に示される
+/// let mut broken = elf.clone();
+/// broken.ehdr.e_ehsize = 52;
+/// let field = output::explain_fields(&broken)
+///     .into_iter()
+///     .find(|f| f.name == "e_ehsize")
+///     .unwrap();
+/// assert_eq!(0x34, field.offset);
+/// assert_eq!(vec![0x34, 0x00], field.raw);
+/// assert_eq!("52 bytes (expected 64)", field.meaning);
+/// ```
+pub fn explain(elf: &file::ELF64) -> String {
+    let mut out = String::new();
+    let mut structure = "";
+    let fields = explain_fields(elf);
+    for field in fields.iter() {
+        if field.structure != structure {
+            structure = &field.structure;
+            out.push_str(structure);
+            out.push('\n');
+        }
+        out.push_str(&format!("  {}\n", field));
+    }
+    out
+}
+
+fn describe_size(size: Elf64Half, expected: usize) -> String {
+    if size as usize == expected {
+        format!("{} bytes", size)
+    } else {
+        format!("{} bytes (expected {})", size, expected)
+    }
+}
+
+fn describe_shidx(elf: &file::ELF64, shidx: usize) -> String {
+    match elf.sections.get(shidx) {
+        Some(sct) => format!("[{}] `{}`", shidx, sct.name),
+        None => format!("[{}] (out of range)", shidx),
+    }
+}
+
+fn describe_vaddr(elf: &file::ELF64, vaddr: Elf64Addr, what: &str) -> String {
+    if vaddr == 0 {
+        return format!("no {}", what);
+    }
+    match elf.section_containing_vaddr(vaddr) {
+        Some(sct) => format!("{} {:#x} in `{}`", what, vaddr, sct.name),
+        None => format!("{} {:#x} (out of the sections)", what, vaddr),
+    }
+}
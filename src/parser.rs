@@ -1,2 +1,10 @@
+#[cfg(feature = "tokio")]
+mod async_parse;
 mod parse;
+#[cfg(target_os = "linux")]
+mod process;
+#[cfg(feature = "tokio")]
+pub use async_parse::*;
 pub use parse::*;
+#[cfg(target_os = "linux")]
+pub use process::*;
@@ -0,0 +1,104 @@
+//! Parsing with tokio's asynchronous I/O.
+//!
+//! Only the reads are asynchronous.
+//! The bytes are parsed on the calling task once fully read, like `parse_elf64_buf_with_options()`.
+
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use crate::*;
+use thiserror::Error as TError;
+
+use super::ParseOptions;
+
+/// The errors of the async parsers
+///
+/// The parse errors are kept as messages, since the errors of `parse_elf64()` can't be sent between threads.
+#[derive(TError, Debug)]
+pub enum AsyncParseError {
+    #[error("can't read `{file_path}` => `{k}`")]
+    Read {
+        file_path: String,
+        k: std::io::Error,
+    },
+    #[error("can't parse `{file_path}` => `{message}`")]
+    Parse { file_path: String, message: String },
+}
+
+/// read and parse 64bit ELF without blocking the runtime.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::parser;
+///
+/// let rt = tokio::runtime::Builder::new_current_thread().build().unwrap();
+/// let elf = rt.block_on(parser::read_elf64_async("src/parser/testdata/sample")).unwrap();
+/// assert_eq!(parser::parse_elf64("src/parser/testdata/sample").unwrap(), elf);
+///
+/// assert!(rt.block_on(parser::read_elf64_async("Cargo.toml")).is_err());
+///
+/// // マルチスレッドのランタイムでspawnできる
+/// fn assert_send<T: Send>(_: T) {}
+/// assert_send(parser::read_elf64_async("src/parser/testdata/sample"));
+/// ```
+pub async fn read_elf64_async(file_path: &str) -> Result<file::ELF64, AsyncParseError> {
+    read_elf64_async_with_options(file_path, &ParseOptions::default()).await
+}
+
+/// read and parse 64bit ELF with `options` without blocking the runtime.
+pub async fn read_elf64_async_with_options(
+    file_path: &str,
+    options: &ParseOptions,
+) -> Result<file::ELF64, AsyncParseError> {
+    let f = tokio::fs::File::open(file_path)
+        .await
+        .map_err(|k| AsyncParseError::Read {
+            file_path: file_path.to_string(),
+            k,
+        })?;
+    read_elf64_from_async_with_options(file_path, f, options).await
+}
+
+/// read `reader` to the end and parse it as 64bit ELF.
+/// `file_path` is only used in the errors, e.g. the name of an uploaded file.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::parser;
+///
+/// let bytes = std::fs::read("src/parser/testdata/sample").unwrap();
+/// let rt = tokio::runtime::Builder::new_current_thread().build().unwrap();
+/// let elf = rt
+///     .block_on(parser::read_elf64_from_async("upload", bytes.as_slice()))
+///     .unwrap();
+/// assert_eq!(bytes, elf.to_le_bytes());
+/// ```
+pub async fn read_elf64_from_async<R: AsyncRead + Unpin>(
+    file_path: &str,
+    reader: R,
+) -> Result<file::ELF64, AsyncParseError> {
+    read_elf64_from_async_with_options(file_path, reader, &ParseOptions::default()).await
+}
+
+/// read `reader` to the end and parse it as 64bit ELF with `options`.
+pub async fn read_elf64_from_async_with_options<R: AsyncRead + Unpin>(
+    file_path: &str,
+    mut reader: R,
+    options: &ParseOptions,
+) -> Result<file::ELF64, AsyncParseError> {
+    let mut buf = Vec::new();
+    reader
+        .read_to_end(&mut buf)
+        .await
+        .map_err(|k| AsyncParseError::Read {
+            file_path: file_path.to_string(),
+            k,
+        })?;
+    super::parse_elf64_buf_with_options(file_path, &buf, options).map_err(|e| {
+        AsyncParseError::Parse {
+            file_path: file_path.to_string(),
+            message: e.to_string(),
+        }
+    })
+}
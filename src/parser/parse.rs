@@ -1,6 +1,8 @@
 use crate::*;
 use std::fs::File;
 use std::io::Read;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
 use serde::Deserialize;
 use thiserror::Error as TError;
@@ -17,15 +19,315 @@ pub enum ReadELFError {
     CantParseProgramHeader { k: Box<dyn std::error::Error> },
     #[error("can't parse symbol => `{k}`")]
     CantParseSymbol { k: Box<dyn std::error::Error> },
+    #[error("{size:#x} bytes at offset {offset:#x} are out of the file")]
+    Truncated { offset: usize, size: usize },
+    #[error("section {index} links to section {link}, which doesn't exist")]
+    InvalidLink { index: usize, link: usize },
+    #[error("can't read the name of section {index} => `{k}`")]
+    InvalidSectionName { index: usize, k: section::ReadError },
+    #[error("can't read the name of symbol {symbol} in section {index} => `{k}`")]
+    InvalidSymbolName {
+        index: usize,
+        symbol: usize,
+        k: section::ReadError,
+    },
+    #[error("parsing `{file_path}` was cancelled")]
+    Cancelled { file_path: String },
+    #[error("module `{module_name}` is not mapped in process {pid}")]
+    ModuleNotFound { pid: u32, module_name: String },
 }
 
+/// The progress of a parse, reported to `ParseOptions::progress()` after each section
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct ParseProgress {
+    pub sections_parsed: usize,
+    pub sections_total: usize,
+    /// the contents of the parsed sections, in bytes
+    pub bytes_processed: usize,
+    /// the contents of all sections except `SHT_NOBITS`
+    pub bytes_total: usize,
+}
+
+/// A flag to abort a parse from another thread
+///
+/// Clones share the flag.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// the callback of `ParseOptions::progress()`
+pub type ProgressCallback = Box<dyn Fn(ParseProgress) + Send + Sync>;
+
+/// the callback of `ParseOptions::warning()`
+pub type WarningCallback = Box<dyn Fn(ReadELFError) + Send + Sync>;
+
 /// parse 64bit ELF
 pub fn parse_elf64(file_path: &str) -> Result<file::ELF64, Box<dyn std::error::Error>> {
-    Ok(parse_elf(file_path)?.as_64bit())
+    Ok(parse_elf(file_path)?.into_64bit())
+}
+/// The options of `parse_elf64_with_options()`
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::{parser, section};
+///
+/// let options = parser::ParseOptions::new().compact(true);
+/// let compact = parser::parse_elf64_with_options("src/parser/testdata/sample", &options).unwrap();
+/// let plain = parser::parse_elf64("src/parser/testdata/sample").unwrap();
+///
+/// // .strtabはバイト列のまま
+/// let strtab = compact.first_section_by(|sct| sct.name == ".strtab").unwrap();
+/// assert!(matches!(strtab.contents, section::Contents64::Raw(_)));
+/// assert!(compact.approx_memory_usage().total() < plain.approx_memory_usage().total());
+/// assert_eq!(plain.to_le_bytes(), compact.to_le_bytes());
+/// ```
+#[derive(Default)]
+pub struct ParseOptions {
+    codecs: section::Codecs,
+    compact: bool,
+    progress: Option<ProgressCallback>,
+    warning: Option<WarningCallback>,
+    cancel: CancelToken,
+}
+
+impl ParseOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// decode sections with the codecs registered in `codecs` as well as the built-in ones.
+    pub fn codecs(mut self, codecs: section::Codecs) -> Self {
+        self.codecs = codecs;
+        self
+    }
+
+    /// keep the string tables linked only from symbol tables(e.g. `.strtab`) as `Contents64::Raw`.
+    ///
+    /// Their strings are held by `Symbol64::symbol_name` as well, so decoding them into `Contents64::StrTab`
+    /// holds every name twice. The bytes are written as they were read.
+    /// `.shstrtab` and the string tables referred by other sections(e.g. `.dynstr` by `.dynamic`) are decoded as usual,
+    /// since the edits of sections and dynamic entries update them.
+    pub fn compact(mut self, compact: bool) -> Self {
+        self.compact = compact;
+        self
+    }
+
+    /// call `callback` after each section is parsed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// use elf_utilities::parser;
+    ///
+    /// let reports = Arc::new(Mutex::new(Vec::new()));
+    /// let sink = reports.clone();
+    /// let options = parser::ParseOptions::new()
+    ///     .progress(move |progress| sink.lock().unwrap().push(progress));
+    /// let elf = parser::parse_elf64_with_options("src/parser/testdata/sample", &options).unwrap();
+    ///
+    /// let reports = reports.lock().unwrap();
+    /// assert_eq!(elf.sections.len(), reports.len());
+    /// let last = reports.last().unwrap();
+    /// assert_eq!(last.sections_total, last.sections_parsed);
+    /// assert_eq!(last.bytes_total, last.bytes_processed);
+    /// ```
+    pub fn progress<F: Fn(ParseProgress) + Send + Sync + 'static>(mut self, callback: F) -> Self {
+        self.progress = Some(Box::new(callback));
+        self
+    }
+
+    /// call `callback` with the problems which don't stop the parse.
+    ///
+    /// A section or symbol whose name is out of its string table
+    /// (`ReadELFError::InvalidSectionName`/`ReadELFError::InvalidSymbolName`) gets an empty name,
+    /// and its `sh_name`/`st_name` is kept as read.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// use elf_utilities::{parser, symbol};
+    ///
+    /// let path = "src/parser/testdata/sample";
+    /// let mut buf = std::fs::read(path).unwrap();
+    /// let dynsym = parser::parse_elf64(path).unwrap().dynsym().unwrap().section().header.sh_offset;
+    /// // 1番目の動的シンボルのst_nameを壊す
+    /// let offset = dynsym as usize + symbol::Symbol64::SIZE;
+    /// buf[offset..offset + 4].copy_from_slice(&0xff_ffffu32.to_le_bytes());
+    ///
+    /// let warnings = Arc::new(Mutex::new(Vec::new()));
+    /// let sink = warnings.clone();
+    /// let options = parser::ParseOptions::new()
+    ///     .warning(move |warning| sink.lock().unwrap().push(warning.to_string()));
+    /// let elf = parser::parse_elf64_buf_with_options(path, &buf, &options).unwrap();
+    ///
+    /// let sym = &elf.dynsym().unwrap().symbols()[1];
+    /// assert_eq!(0xff_ffff, sym.st_name);
+    /// assert_eq!("", sym.symbol_name);
+    /// assert_eq!(1, warnings.lock().unwrap().len());
+    /// ```
+    pub fn warning<F: Fn(ReadELFError) + Send + Sync + 'static>(mut self, callback: F) -> Self {
+        self.warning = Some(Box::new(callback));
+        self
+    }
+
+    /// abort the parse with `ReadELFError::Cancelled` once `token` is cancelled.
+    /// The token is checked between sections.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use elf_utilities::parser;
+    ///
+    /// let token = parser::CancelToken::new();
+    /// let cancel = token.clone();
+    /// let options = parser::ParseOptions::new()
+    ///     .cancel(token)
+    ///     .progress(move |progress| {
+    ///         if progress.sections_parsed == 3 {
+    ///             cancel.cancel();
+    ///         }
+    ///     });
+    /// let err = parser::parse_elf64_with_options("src/parser/testdata/sample", &options).unwrap_err();
+    /// assert!(err.to_string().contains("cancelled"));
+    /// ```
+    pub fn cancel(mut self, token: CancelToken) -> Self {
+        self.cancel = token;
+        self
+    }
+
+    fn report(&self, progress: ParseProgress) {
+        if let Some(callback) = &self.progress {
+            callback(progress);
+        }
+    }
+
+    fn warn(&self, warning: ReadELFError) {
+        if let Some(callback) = &self.warning {
+            callback(warning);
+        }
+    }
+
+    fn check_cancelled(&self, file_path: &str) -> Result<(), ReadELFError> {
+        if self.cancel.is_cancelled() {
+            return Err(ReadELFError::Cancelled {
+                file_path: file_path.to_string(),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// parse 64bit ELF, decoding sections with the codecs registered in `codecs` as well as the built-in ones
+pub fn parse_elf64_with(
+    file_path: &str,
+    codecs: &section::Codecs,
+) -> Result<file::ELF64, Box<dyn std::error::Error>> {
+    let mut f = File::open(file_path)?;
+    let mut buf = Vec::new();
+    let _ = f.read_to_end(&mut buf);
+
+    parse_elf64_buf_with(file_path, &buf, codecs)
+}
+/// parse 64bit ELF from the bytes already read from `file_path` with `codecs`
+pub fn parse_elf64_buf_with(
+    file_path: &str,
+    buf: &[u8],
+    codecs: &section::Codecs,
+) -> Result<file::ELF64, Box<dyn std::error::Error>> {
+    let mut elf = parse_elf_buf(file_path, buf)?.into_64bit();
+    decode_registered(&mut elf, buf, codecs);
+    Ok(elf)
+}
+
+/// 登録されたコーデックはセクション名で判断することがあるため，名前付けの後に適用する
+fn decode_registered(elf: &mut file::ELF64, buf: &[u8], codecs: &section::Codecs) {
+    for sct in elf.sections.iter_mut() {
+        if sct.header.get_type() == section::Type::NoBits {
+            continue;
+        }
+        if let Some(codec) = codecs.registered(&sct.header, &sct.name) {
+            let start = sct.header.sh_offset as usize;
+            let raw = &buf[start..start + sct.header.sh_size as usize];
+            sct.contents = codec.decode(&sct.header, raw);
+        }
+    }
+}
+/// parse 64bit ELF with `options`
+pub fn parse_elf64_with_options(
+    file_path: &str,
+    options: &ParseOptions,
+) -> Result<file::ELF64, Box<dyn std::error::Error>> {
+    let mut f = File::open(file_path)?;
+    let mut buf = Vec::new();
+    let _ = f.read_to_end(&mut buf);
+
+    parse_elf64_buf_with_options(file_path, &buf, options)
+}
+/// parse 64bit ELF from the bytes already read from `file_path` with `options`
+pub fn parse_elf64_buf_with_options(
+    file_path: &str,
+    buf: &[u8],
+    options: &ParseOptions,
+) -> Result<file::ELF64, Box<dyn std::error::Error>> {
+    let mut elf = parse_elf_buf_with(file_path, buf, options)?.into_64bit();
+    decode_registered(&mut elf, buf, &options.codecs);
+    if options.compact {
+        for shidx in symbol_only_string_tables(&elf) {
+            let shdr = &elf.sections[shidx].header;
+            let start = shdr.sh_offset as usize;
+            let raw = buf[start..start + shdr.sh_size as usize].to_vec();
+            elf.sections[shidx].contents = section::Contents64::Raw(raw);
+        }
+    }
+    Ok(elf)
+}
+
+/// the string tables which only symbol tables link to, except `.shstrtab`.
+fn symbol_only_string_tables(elf: &file::ELF64) -> Vec<usize> {
+    let is_symbol_table = |sct: &section::Section64| {
+        matches!(
+            sct.header.get_type(),
+            section::Type::SymTab | section::Type::DynSym
+        )
+    };
+    (0..elf.sections.len())
+        .filter(|&shidx| {
+            shidx != elf.ehdr.e_shstrndx as usize
+                && matches!(elf.sections[shidx].contents, section::Contents64::StrTab(_))
+                && elf
+                    .sections
+                    .iter()
+                    .any(|sct| is_symbol_table(sct) && sct.header.sh_link as usize == shidx)
+                && elf
+                    .sections
+                    .iter()
+                    .all(|sct| is_symbol_table(sct) || sct.header.sh_link as usize != shidx)
+        })
+        .collect()
 }
 /// parse 32bit ELF
 pub fn parse_elf32(file_path: &str) -> Result<file::ELF32, Box<dyn std::error::Error>> {
-    Ok(parse_elf(file_path)?.as_32bit())
+    Ok(parse_elf(file_path)?.into_32bit())
 }
 
 /// parse ELF and construct `file::ELF`
@@ -34,38 +336,74 @@ pub fn parse_elf(file_path: &str) -> Result<file::ELF, Box<dyn std::error::Error
     let mut buf = Vec::new();
     let _ = f.read_to_end(&mut buf);
 
-    let _ = check_elf_magic(file_path, &buf[..4])?;
+    parse_elf_buf(file_path, &buf)
+}
+
+/// parse ELF from the bytes already read from `file_path`
+pub(crate) fn parse_elf_buf(
+    file_path: &str,
+    buf: &[u8],
+) -> Result<file::ELF, Box<dyn std::error::Error>> {
+    parse_elf_buf_with(file_path, buf, &ParseOptions::default())
+}
+
+/// `options`のコーデック等はここでは使わない
+fn parse_elf_buf_with(
+    file_path: &str,
+    buf: &[u8],
+    options: &ParseOptions,
+) -> Result<file::ELF, Box<dyn std::error::Error>> {
+    if buf.len() < 4 {
+        return Err(Box::new(ReadELFError::NotELF {
+            file_path: file_path.to_string(),
+        }));
+    }
+    check_elf_magic(file_path, &buf[..4])?;
 
     // 32bit/64bitでパース処理を共通化するため，classを取っておく
     let elf_class = header::Class::from(buf[header::Class::INDEX]);
 
-    let elf_header = parse_elf_header(elf_class, &buf)?;
+    let elf_header = parse_elf_header(elf_class, buf)?;
     let phdr_table_exists = elf_header.pht_exists();
 
-    let mut sections = read_sht(elf_class, elf_header.shnum(), elf_header.sht_start(), &buf)?;
+    // e_shoffが0ならSHTは存在せず，e_shnumは意味を持たない
+    let shnum = if elf_header.sht_start() == 0 {
+        0
+    } else {
+        elf_header.shnum()
+    };
+    let mut sections = read_sht(
+        file_path,
+        elf_class,
+        shnum,
+        elf_header.sht_start(),
+        buf,
+        options,
+    )?;
     let mut segments = Vec::new();
 
     if phdr_table_exists {
-        segments = read_pht(elf_class, elf_header.phnum(), elf_header.pht_start(), &buf)?;
+        segments = read_pht(elf_class, elf_header.phnum(), elf_header.pht_start(), buf)?;
     }
 
     // セクション名の設定
     // .shstrtabセクションは大抵SHTの末尾にあるため，read_sht() 後に行う必要がある
-    naming_sections_from_shstrtab(elf_header.shstrndx(), &mut sections);
+    naming_sections_from_shstrtab(elf_header.shstrndx(), &mut sections, buf, options)?;
 
     // シンボル名の設定
     // これもセクション名の設定と同様，SHTパース後に実行する必要があるため切り離している
-    naming_symbols(&mut sections);
+    naming_symbols(&mut sections, buf, options)?;
 
     match elf_class {
         header::Class::Bit64 => Ok(file::ELF::ELF64(file::ELF64 {
             ehdr: elf_header.as_64bit(),
-            sections: sections.iter().map(|sct| sct.as_64bit()).collect(),
+            sections: sections.into_iter().map(|sct| sct.into_64bit()).collect(),
             segments: segments.iter().map(|sgt| sgt.as_64bit()).collect(),
+            address_cache: Default::default(),
         })),
         header::Class::Bit32 => Ok(file::ELF::ELF32(file::ELF32 {
             ehdr: elf_header.as_32bit(),
-            sections: sections.iter().map(|sct| sct.as_32bit()).collect(),
+            sections: sections.into_iter().map(|sct| sct.into_32bit()).collect(),
             segments: segments.iter().map(|sgt| sgt.as_32bit()).collect(),
         })),
         _ => todo!(),
@@ -74,67 +412,90 @@ pub fn parse_elf(file_path: &str) -> Result<file::ELF, Box<dyn std::error::Error
 
 /// セクションヘッダテーブルのパース
 fn read_sht(
+    file_path: &str,
     class: header::Class,
 
     section_number: usize,
     sht_offset: usize,
     buf: &[u8],
+    options: &ParseOptions,
 ) -> Result<Vec<section::Section>, Box<dyn std::error::Error>> {
-    let mut sections = Vec::with_capacity(50);
+    let mut sections = Vec::with_capacity(section_number);
     let shdr_size = match class {
         header::Class::Bit32 => section::Shdr32::SIZE,
         header::Class::Bit64 => section::Shdr64::SIZE,
         _ => todo!(),
     };
 
+    // 進捗の総量を知るため，先にヘッダだけ読む
+    let mut shdrs = Vec::with_capacity(section_number);
     for sct_idx in 0..section_number {
-        let header_start = sht_offset + shdr_size * sct_idx;
+        let raw_shdr = file_range(buf, sht_offset + shdr_size * sct_idx, shdr_size)?;
         let shdr = match class {
-            header::Class::Bit32 => {
-                section::Shdr::Shdr32(bincode::deserialize(&buf[header_start..])?)
-            }
-            header::Class::Bit64 => {
-                section::Shdr::Shdr64(bincode::deserialize(&buf[header_start..])?)
-            }
+            header::Class::Bit32 => section::Shdr::Shdr32(bincode::deserialize(raw_shdr)?),
+            header::Class::Bit64 => section::Shdr::Shdr64(bincode::deserialize(raw_shdr)?),
             _ => todo!(),
         };
+        shdrs.push(shdr);
+    }
+    let contents_size = |sct: &section::Section| {
+        if sct.ty() == section::Type::NoBits {
+            0
+        } else {
+            sct.size()
+        }
+    };
+    let mut progress = ParseProgress {
+        sections_total: section_number,
+        bytes_total: shdrs
+            .iter()
+            .map(|shdr| contents_size(&section::Section::new(shdr.clone())))
+            .sum(),
+        ..Default::default()
+    };
+
+    for shdr in shdrs {
+        options.check_cancelled(file_path)?;
 
         let mut sct = section::Section::new(shdr);
         let section_type = sct.ty();
 
         if section_type != section::Type::NoBits {
-            let section_offset = sct.offset();
-            let section_raw_contents =
-                buf[section_offset..section_offset + sct.size() as usize].to_vec();
-
-            sct.contents = match section_type {
-                section::Type::StrTab => parse_string_table(class, &section_raw_contents),
-                section::Type::SymTab | section::Type::DynSym => {
-                    parse_symbol_table(class, &sct, &section_raw_contents)
+            let section_raw_contents = file_range(buf, sct.offset(), sct.size())?;
+
+            sct.contents = match &sct.header {
+                // デコードするセクションの生バイト列はコピーしない
+                section::Shdr::Shdr64(shdr) => {
+                    section::Contents::Contents64(match section::builtin_codec(shdr, "") {
+                        Some(codec) => codec.decode(shdr, section_raw_contents),
+                        None => section::Contents64::Raw(section_raw_contents.to_vec()),
+                    })
                 }
-                section::Type::Rela => parse_rela_symbol_table(class, &sct, &section_raw_contents),
-                section::Type::Dynamic => {
-                    parse_dynamic_information(class, &sct, &section_raw_contents)
-                }
-                _ => match class {
-                    header::Class::Bit32 => section::Contents::Contents32(
-                        section::Contents32::Raw(section_raw_contents),
-                    ),
-                    header::Class::Bit64 => section::Contents::Contents64(
-                        section::Contents64::Raw(section_raw_contents),
-                    ),
-                    _ => todo!(),
+                section::Shdr::Shdr32(_) => match section_type {
+                    section::Type::StrTab => parse_string_table(section_raw_contents),
+                    section::Type::SymTab | section::Type::DynSym => {
+                        parse_symbol_table(&sct, section_raw_contents)
+                    }
+                    section::Type::Rela => parse_rela_symbol_table(&sct, section_raw_contents),
+                    section::Type::Dynamic => parse_dynamic_information(&sct, section_raw_contents),
+                    _ => section::Contents::Contents32(section::Contents32::Raw(
+                        section_raw_contents.to_vec(),
+                    )),
                 },
             }
         }
 
+        progress.sections_parsed += 1;
+        progress.bytes_processed += contents_size(&sct);
+        options.report(progress);
         sections.push(sct);
     }
 
     Ok(sections)
 }
 
-fn parse_string_table(class: header::Class, section_raw_contents: &Vec<u8>) -> section::Contents {
+/// 64bitのセクションは`section::SectionCodec`でデコードする
+fn parse_string_table(section_raw_contents: &[u8]) -> section::Contents {
     let mut strs: Vec<section::StrTabEntry> = Default::default();
     let mut name_idx = 0;
     loop {
@@ -150,74 +511,53 @@ fn parse_string_table(class: header::Class, section_raw_contents: &Vec<u8>) -> s
         let nul_range_end = section_raw_contents[name_idx..]
             .iter()
             .position(|&c| c == b'\0')
-            .unwrap_or(section_raw_contents.len());
-        let s = std::str::from_utf8(&section_raw_contents[name_idx..name_idx + nul_range_end])
-            .unwrap()
-            .to_string();
+            .unwrap_or(section_raw_contents.len() - name_idx);
+        let s = match std::str::from_utf8(&section_raw_contents[name_idx..name_idx + nul_range_end])
+        {
+            Ok(s) => s.to_string(),
+            // 名前は生のバイト列から読むので，テーブルとしては解釈しない
+            Err(_) => {
+                return section::Contents::Contents32(section::Contents32::Raw(
+                    section_raw_contents.to_vec(),
+                ))
+            }
+        };
 
         let idx = name_idx;
         name_idx += s.len();
         strs.push(section::StrTabEntry { v: s, idx });
     }
 
-    match class {
-        header::Class::Bit32 => section::Contents::Contents32(section::Contents32::StrTab(strs)),
-        header::Class::Bit64 => section::Contents::Contents64(section::Contents64::StrTab(strs)),
-        _ => todo!(),
-    }
+    section::Contents::Contents32(section::Contents32::StrTab(strs))
 }
-fn parse_rela_symbol_table(
-    class: header::Class,
-    sct: &section::Section,
-    raw_symtab: &Vec<u8>,
-) -> section::Contents {
+fn parse_rela_symbol_table(sct: &section::Section, raw_symtab: &[u8]) -> section::Contents {
     let entry_size = sct.entry_size();
     let entry_number = sct.size() / entry_size;
-    match class {
-        header::Class::Bit32 => section::Contents::Contents32(section::Contents32::RelaSymbols(
-            parse_table(entry_size, entry_number, raw_symtab),
-        )),
-        header::Class::Bit64 => section::Contents::Contents64(section::Contents64::RelaSymbols(
-            parse_table(entry_size, entry_number, raw_symtab),
-        )),
-        _ => todo!(),
-    }
+    section::Contents::Contents32(section::Contents32::RelaSymbols(parse_table(
+        entry_size,
+        entry_number,
+        raw_symtab,
+    )))
 }
 
-fn parse_dynamic_information(
-    class: header::Class,
-    sct: &section::Section,
-    raw_symtab: &Vec<u8>,
-) -> section::Contents {
+fn parse_dynamic_information(sct: &section::Section, raw_symtab: &[u8]) -> section::Contents {
     let entry_size = sct.entry_size();
     let entry_number = sct.size() / entry_size;
-    match class {
-        header::Class::Bit32 => section::Contents::Contents32(section::Contents32::Dynamics(
-            parse_table(entry_size, entry_number, raw_symtab),
-        )),
-        header::Class::Bit64 => section::Contents::Contents64(section::Contents64::Dynamics(
-            parse_table(entry_size, entry_number, raw_symtab),
-        )),
-        _ => todo!(),
-    }
+    section::Contents::Contents32(section::Contents32::Dynamics(parse_table(
+        entry_size,
+        entry_number,
+        raw_symtab,
+    )))
 }
 
-fn parse_symbol_table(
-    class: header::Class,
-    sct: &section::Section,
-    raw_symtab: &Vec<u8>,
-) -> section::Contents {
+fn parse_symbol_table(sct: &section::Section, raw_symtab: &[u8]) -> section::Contents {
     let entry_size = sct.entry_size();
     let entry_number = sct.size() / entry_size;
-    match class {
-        header::Class::Bit32 => section::Contents::Contents32(section::Contents32::Symbols(
-            parse_table(entry_size, entry_number, raw_symtab),
-        )),
-        header::Class::Bit64 => section::Contents::Contents64(section::Contents64::Symbols(
-            parse_table(entry_size, entry_number, raw_symtab),
-        )),
-        _ => todo!(),
-    }
+    section::Contents::Contents32(section::Contents32::Symbols(parse_table(
+        entry_size,
+        entry_number,
+        raw_symtab,
+    )))
 }
 
 fn parse_table<'a, T: Deserialize<'a>>(
@@ -250,7 +590,7 @@ fn read_pht(
     };
 
     for seg_idx in 0..phnum {
-        let header_start = pht_start as usize + phdr_size * seg_idx;
+        let header_start = pht_start + phdr_size * seg_idx;
         let phdr = match class {
             header::Class::Bit32 => {
                 segment::Phdr::Phdr32(segment::Phdr32::deserialize(buf, header_start)?)
@@ -268,82 +608,101 @@ fn read_pht(
     Ok(segments)
 }
 
+/// `buf[offset..offset + size]`, or `ReadELFError::Truncated` if the file is shorter.
+fn file_range(buf: &[u8], offset: usize, size: usize) -> Result<&[u8], ReadELFError> {
+    offset
+        .checked_add(size)
+        .and_then(|end| buf.get(offset..end))
+        .ok_or(ReadELFError::Truncated { offset, size })
+}
+
+/// 文字列テーブルの生のバイト列．NOBITSは空として扱う
+fn string_table_bytes<'a>(sct: &section::Section, buf: &'a [u8]) -> Result<&'a [u8], ReadELFError> {
+    if sct.ty() == section::Type::NoBits {
+        return Ok(&[]);
+    }
+    file_range(buf, sct.offset(), sct.size())
+}
+
 /// セクション名を.shstrtabから探して，Section構造体に書き込む
 /// このようにしているのは，SHTのパースがすべて終わってからでないとshstrtabを使用できない為
-fn naming_sections_from_shstrtab(shstrndx: usize, sections: &mut Vec<section::Section>) {
-    let shstrtab = sections[shstrndx].contents.as_strtab();
+/// 読めない名前は空のままにして，警告として報告する
+fn naming_sections_from_shstrtab(
+    shstrndx: usize,
+    sections: &mut [section::Section],
+    buf: &[u8],
+    options: &ParseOptions,
+) -> Result<(), ReadELFError> {
+    // パックされたファイル等はSHTを持たない
+    if sections.len() <= shstrndx {
+        return Ok(());
+    }
+    let shstrtab = string_table_bytes(&sections[shstrndx], buf)?;
 
-    for sct in sections.iter_mut() {
+    for (index, sct) in sections.iter_mut().enumerate() {
         let name_idx = sct.name_idx();
         if name_idx == 0 {
             continue;
         }
-
-        let s = shstrtab
-            .iter()
-            .find(|&s| s.idx <= name_idx && name_idx <= s.idx + s.v.len())
-            .unwrap();
-
-        let (_, name) = s.v.split_at(name_idx - s.idx);
-        sct.name = name.to_string();
+        match section::read_name(shstrtab, name_idx) {
+            Ok(name) => sct.name = name.to_string(),
+            Err(k) => options.warn(ReadELFError::InvalidSectionName { index, k }),
+        }
     }
+    Ok(())
 }
 
 /// シンボル名をsh_linkが指す文字列テーブルから探して割り当てる
 /// このようにしているのは，SHTのパースがすべて終わってからでないとshstrtabを使用できない為
-fn naming_symbols(sections: &mut Vec<section::Section>) {
-    let section_number = sections.len();
-    for sct_idx in 0..section_number {
-        let sct = &sections[sct_idx];
+fn naming_symbols(
+    sections: &mut [section::Section],
+    buf: &[u8],
+    options: &ParseOptions,
+) -> Result<(), ReadELFError> {
+    for index in 0..sections.len() {
+        let sct = &sections[index];
         if sct.ty() != section::Type::SymTab && sct.ty() != section::Type::DynSym {
             continue;
         }
+        let link = sct.link();
+        let strtab = match sections.get(link) {
+            Some(strtab) => string_table_bytes(strtab, buf)?,
+            None => return Err(ReadELFError::InvalidLink { index, link }),
+        };
+        // 読めない名前は空にして，警告として報告する
+        let name_of = |symbol: usize, name_idx: usize| match section::read_name(strtab, name_idx) {
+            Ok(name) => name,
+            Err(k) => {
+                options.warn(ReadELFError::InvalidSymbolName { index, symbol, k });
+                ""
+            }
+        };
 
-        let strtab = sections[sct.link()].contents.as_strtab();
-
-        match &mut sections[sct_idx].contents {
-            section::Contents::Contents32(c) => {
-                if let section::Contents32::Symbols(ref mut symbols) = c {
-                    for sym in symbols.iter_mut() {
-                        let name_idx = sym.st_name as usize;
-                        if name_idx == 0 {
-                            continue;
-                        }
-
-                        let s = strtab
-                            .iter()
-                            .find(|s| s.idx <= name_idx && name_idx <= s.idx + s.v.len())
-                            .unwrap();
-                        let (_, name) = s.v.split_at(name_idx - s.idx);
-
-                        sym.symbol_name = name.to_string();
+        match &mut sections[index].contents {
+            section::Contents::Contents32(section::Contents32::Symbols(symbols)) => {
+                for (i, sym) in symbols.iter_mut().enumerate() {
+                    if sym.st_name != 0 {
+                        sym.symbol_name = name_of(i, sym.st_name as usize).to_string();
                     }
                 }
             }
-
-            section::Contents::Contents64(c) => {
-                if let section::Contents64::Symbols(ref mut symbols) = c {
-                    for sym in symbols.iter_mut() {
-                        let name_idx = sym.st_name as usize;
-                        if name_idx == 0 {
-                            continue;
-                        }
-
-                        let s = strtab
-                            .iter()
-                            .find(|s| s.idx <= name_idx && name_idx <= s.idx + s.v.len())
-                            .unwrap();
-                        let (_, name) = s.v.split_at(name_idx - s.idx);
-
-                        sym.symbol_name = name.to_string();
+            section::Contents::Contents64(section::Contents64::Symbols(symbols)) => {
+                for (i, sym) in symbols.iter_mut().enumerate() {
+                    if sym.st_name != 0 {
+                        sym.symbol_name = name_of(i, sym.st_name as usize).into();
                     }
                 }
             }
+            _ => {}
         }
     }
+    Ok(())
 }
 
-fn check_elf_magic(file_path: &str, buf: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+pub(crate) fn check_elf_magic(
+    file_path: &str,
+    buf: &[u8],
+) -> Result<(), Box<dyn std::error::Error>> {
     assert_eq!(buf.len(), 4);
 
     if buf[0] != 0x7f || buf[1] != 0x45 || buf[2] != 0x4c || buf[3] != 0x46 {
@@ -355,7 +714,7 @@ fn check_elf_magic(file_path: &str, buf: &[u8]) -> Result<(), Box<dyn std::error
     Ok(())
 }
 
-fn parse_elf_header(
+pub(crate) fn parse_elf_header(
     class: header::Class,
     buf: &[u8],
 ) -> Result<header::Ehdr, Box<dyn std::error::Error>> {
@@ -490,6 +849,31 @@ mod parse_tests {
         }
     }
 
+    #[test]
+    fn compact_parse_test() {
+        let options = ParseOptions::new().compact(true);
+        let elf = parse_elf64_with_options("tests/fixtures/exceptions", &options).unwrap();
+        let plain = parse_elf64("tests/fixtures/exceptions").unwrap();
+        assert_eq!(plain.to_le_bytes(), elf.to_le_bytes());
+
+        // .dynstrは.dynamicからも参照されるのでデコードしたまま
+        let contents_of = |name: &str| {
+            &elf.first_section_by(|sct| sct.name == name)
+                .unwrap()
+                .contents
+        };
+        assert!(matches!(contents_of(".strtab"), Contents64::Raw(_)));
+        assert!(matches!(contents_of(".dynstr"), Contents64::StrTab(_)));
+        assert!(matches!(contents_of(".shstrtab"), Contents64::StrTab(_)));
+        assert!(
+            elf.approx_memory_usage().string_tables < plain.approx_memory_usage().string_tables
+        );
+        assert_eq!(
+            plain.approx_memory_usage().symbols,
+            elf.approx_memory_usage().symbols
+        );
+    }
+
     #[test]
     fn read_elf32_test() {
         let f_result = parse_elf("src/parser/testdata/32bit");
@@ -513,4 +897,68 @@ mod parse_tests {
             assert_eq!(0x208, f.sections[4].header.sh_addr);
         }
     }
+
+    #[test]
+    fn corrupt_names_test() {
+        let path = "src/parser/testdata/sample";
+        let buf = std::fs::read(path).unwrap();
+        let elf = parse_elf64(path).unwrap();
+        // 壊れた名前はエラーにせず，警告として報告される
+        let parse_with_warnings = |bytes: &[u8]| {
+            let warnings = Arc::new(std::sync::Mutex::new(Vec::new()));
+            let sink = warnings.clone();
+            let options =
+                ParseOptions::new().warning(move |w| sink.lock().unwrap().push(w.to_string()));
+            let parsed = parse_elf_buf_with(path, bytes, &options)
+                .unwrap()
+                .into_64bit();
+            let warnings = warnings.lock().unwrap().clone();
+            (parsed, warnings)
+        };
+
+        // 途中で切れたファイルはパニックせずエラーになる
+        let err = parse_elf_buf(path, &buf[..buf.len() / 2]).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ReadELFError>(),
+            Some(ReadELFError::Truncated { .. })
+        ));
+
+        // st_nameが.dynstrの外を指す
+        let dynsym = elf.dynsym().unwrap();
+        let offset = dynsym.section().header.sh_offset as usize + symbol::Symbol64::SIZE;
+        let mut corrupt = buf.clone();
+        corrupt[offset..offset + 4].copy_from_slice(&0xff_ffffu32.to_le_bytes());
+        let (parsed, warnings) = parse_with_warnings(&corrupt);
+        let syms = parsed.dynsym().unwrap().symbols();
+        assert_eq!(0xff_ffff, syms[1].st_name);
+        assert_eq!("", syms[1].symbol_name);
+        // 他のシンボルの名前は読める
+        assert_eq!(elf.dynsym().unwrap().symbols()[2], syms[2]);
+        let dynsym_idx = elf.first_shidx_by(|sct| sct.name == ".dynsym").unwrap();
+        let expected = ReadELFError::InvalidSymbolName {
+            index: dynsym_idx,
+            symbol: 1,
+            k: section::ReadError::UnexpectedEnd {
+                offset: 0xff_ffff,
+                needed: 1,
+            },
+        };
+        assert_eq!(vec![expected.to_string()], warnings);
+
+        // .shstrtabの終端のNULが無いと，最後の名前は隣のセクションにはみ出す
+        let shstrtab = &elf.sections[elf.ehdr.e_shstrndx as usize].header;
+        let end = (shstrtab.sh_offset + shstrtab.sh_size) as usize;
+        let mut corrupt = buf;
+        corrupt[end - 1] = b'x';
+        let (parsed, warnings) = parse_with_warnings(&corrupt);
+        assert_eq!(1, warnings.len());
+        let unnamed: Vec<usize> = (0..parsed.sections.len())
+            .filter(|&i| parsed.sections[i].name != elf.sections[i].name)
+            .collect();
+        assert_eq!(1, unnamed.len());
+        let sct = &parsed.sections[unnamed[0]];
+        assert_eq!("", sct.name);
+        assert_eq!(elf.sections[unnamed[0]].header.sh_name, sct.header.sh_name);
+        assert!(warnings[0].starts_with(&format!("can't read the name of section {} ", unnamed[0])));
+    }
 }
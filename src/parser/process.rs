@@ -0,0 +1,57 @@
+//! Parsing the files mapped by running processes, through procfs.
+
+use crate::{file, process};
+
+use super::{parse_elf, ReadELFError};
+
+/// parse the executable of the current process(`/proc/self/exe`).
+///
+/// The file is read even if it has been replaced or deleted since the process started.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::{file, header, parser};
+///
+/// match parser::read_current_exe().unwrap() {
+///     file::ELF::ELF64(elf) => assert_ne!(header::Type::Rel, elf.ehdr.get_type()),
+///     file::ELF::ELF32(elf) => assert_ne!(header::Type::Rel, elf.ehdr.get_type()),
+/// }
+/// ```
+pub fn read_current_exe() -> Result<file::ELF, Box<dyn std::error::Error>> {
+    parse_elf("/proc/self/exe")
+}
+
+/// parse the file mapped as `module_name` in the process `pid`.
+///
+/// `module_name` is compared with the path in `/proc/<pid>/maps` and its file name,
+/// e.g. `libc.so.6` or `/usr/lib/x86_64-linux-gnu/libc.so.6`.
+/// The file is read through `/proc/<pid>/root`, so it's found even in another mount namespace.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::parser;
+///
+/// let exe = std::env::current_exe().unwrap();
+/// let name = exe.file_name().unwrap().to_str().unwrap();
+/// assert!(parser::read_process_module(std::process::id(), name).is_ok());
+///
+/// assert!(parser::read_process_module(std::process::id(), "not_mapped.so").is_err());
+/// ```
+pub fn read_process_module(
+    pid: u32,
+    module_name: &str,
+) -> Result<file::ELF, Box<dyn std::error::Error>> {
+    let maps = std::fs::read_to_string(format!("/proc/{}/maps", pid))?;
+    let path = process::parse_maps(&maps)
+        .into_iter()
+        .filter(|m| m.is_file())
+        .filter_map(|m| m.path)
+        .find(|path| path == module_name || path.rsplit('/').next() == Some(module_name))
+        .ok_or_else(|| ReadELFError::ModuleNotFound {
+            pid,
+            module_name: module_name.to_string(),
+        })?;
+    parse_elf(&format!("/proc/{}/root{}", pid, path))
+}
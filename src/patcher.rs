@@ -0,0 +1,299 @@
+//! Assembling small snippets and patching them into linked files.
+//!
+//! The assembler is pluggable by `Assembler`, so a full assembler(e.g. keystone) can be used.
+//! `X86_64` is a built-in one which knows the instructions binary patchers usually need.
+
+use std::convert::TryFrom;
+
+use crate::layout::is_nobits;
+use crate::*;
+use thiserror::Error as TError;
+
+#[derive(TError, Debug, Clone, PartialEq, Eq)]
+pub enum PatchError {
+    #[error("can't assemble `{snippet}` => `{message}`")]
+    Assemble { snippet: String, message: String },
+    #[error("no section contains {vaddr:#x}")]
+    Unmapped { vaddr: Elf64Addr },
+    #[error("the section at {vaddr:#x} has no contents in the file")]
+    NoContents { vaddr: Elf64Addr },
+    #[error("the patch of {len} bytes at {vaddr:#x} runs over the end of the section")]
+    OutOfSection { vaddr: Elf64Addr, len: usize },
+    #[error("the patch is {len} bytes, but only {max} bytes are available")]
+    TooLong { len: usize, max: usize },
+}
+
+/// A backend which turns assembly into machine code
+pub trait Assembler {
+    /// assemble `source` placed at `vaddr`. the error is a message for `PatchError::Assemble`.
+    fn assemble(&self, vaddr: Elf64Addr, source: &str) -> Result<Vec<u8>, String>;
+
+    /// the instruction which fills the rest of the replaced instructions.
+    fn nop(&self) -> Vec<u8>;
+}
+
+/// A patch written by `patch_bytes()`
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Patch {
+    pub vaddr: Elf64Addr,
+    /// the file offset of the patch
+    pub offset: Elf64Off,
+    pub bytes: Vec<u8>,
+    /// the bytes which were overwritten
+    pub original: Vec<u8>,
+}
+
+/// assemble `source` for `vaddr` and write it there.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::{builder, patcher};
+///
+/// let main = builder::ExportedFunction::new("main", vec![0xb8, 0x2a, 0x00, 0x00, 0x00, 0xc3]);
+/// let mut elf = builder::ExecutableWriter::new()
+///     .function(main)
+///     .start_stub(builder::start_stub("main"))
+///     .build()
+///     .unwrap();
+/// let text = elf.first_section_by(|sct| sct.name == ".text").unwrap().header;
+///
+/// let patch = patcher::assemble_and_patch(&mut elf, &patcher::X86_64, text.sh_addr, "nop; ret").unwrap();
+/// assert_eq!(vec![0x90, 0xc3], patch.bytes);
+/// assert_eq!(text.sh_offset, patch.offset);
+/// ```
+pub fn assemble_and_patch<A: Assembler>(
+    elf: &mut file::ELF64,
+    arch: &A,
+    vaddr: Elf64Addr,
+    source: &str,
+) -> Result<Patch, PatchError> {
+    let bytes = assemble(arch, vaddr, source)?;
+    patch_bytes(elf, vaddr, &bytes)
+}
+
+/// assemble `source` for `vaddr` and replace the `len` bytes of the instructions there.
+///
+/// `len` must cover whole instructions, so that no instruction is left broken;
+/// the rest of the patch is filled with `Assembler::nop()`.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::{builder, patcher};
+///
+/// // mov eax, 42; ret
+/// let main = builder::ExportedFunction::new("main", vec![0xb8, 0x2a, 0x00, 0x00, 0x00, 0xc3]);
+/// let mut elf = builder::ExecutableWriter::new()
+///     .function(main)
+///     .start_stub(builder::start_stub("main"))
+///     .build()
+///     .unwrap();
+/// let text = elf.first_section_by(|sct| sct.name == ".text").unwrap().header.sh_addr;
+///
+/// // replace `mov eax, 42` with `ret`
+/// let patch = patcher::assemble_and_patch_within(&mut elf, &patcher::X86_64, text, "ret", 5).unwrap();
+/// assert_eq!(vec![0xc3, 0x90, 0x90, 0x90, 0x90], patch.bytes);
+/// assert!(patcher::assemble_and_patch_within(&mut elf, &patcher::X86_64, text, "call 0x0", 4).is_err());
+/// ```
+pub fn assemble_and_patch_within<A: Assembler>(
+    elf: &mut file::ELF64,
+    arch: &A,
+    vaddr: Elf64Addr,
+    source: &str,
+    len: usize,
+) -> Result<Patch, PatchError> {
+    let mut bytes = assemble(arch, vaddr, source)?;
+    if bytes.len() > len {
+        return Err(PatchError::TooLong {
+            len: bytes.len(),
+            max: len,
+        });
+    }
+    let nop = arch.nop();
+    while bytes.len() < len {
+        let rest = len - bytes.len();
+        bytes.extend(nop.iter().take(rest));
+    }
+    patch_bytes(elf, vaddr, &bytes)
+}
+
+/// overwrite the contents at `vaddr` with `bytes`. the patch must fit in the section containing `vaddr`.
+pub fn patch_bytes(
+    elf: &mut file::ELF64,
+    vaddr: Elf64Addr,
+    bytes: &[u8],
+) -> Result<Patch, PatchError> {
+    let shidx = elf
+        .address_index()
+        .shidx_containing_vaddr(vaddr)
+        .ok_or(PatchError::Unmapped { vaddr })?;
+    let sct = &mut elf.sections[shidx];
+    if is_nobits(&sct.header) {
+        return Err(PatchError::NoContents { vaddr });
+    }
+    let start = (vaddr - sct.header.sh_addr) as usize;
+    let end = start + bytes.len();
+    if end as Elf64Xword > sct.header.sh_size {
+        return Err(PatchError::OutOfSection {
+            vaddr,
+            len: bytes.len(),
+        });
+    }
+    let contents = match &mut sct.contents {
+        section::Contents64::Raw(contents) => contents,
+        _ => return Err(PatchError::NoContents { vaddr }),
+    };
+    let original = contents[start..end].to_vec();
+    contents[start..end].copy_from_slice(bytes);
+    Ok(Patch {
+        vaddr,
+        offset: sct.header.sh_offset + start as Elf64Off,
+        bytes: bytes.to_vec(),
+        original,
+    })
+}
+
+fn assemble<A: Assembler>(arch: &A, vaddr: Elf64Addr, source: &str) -> Result<Vec<u8>, PatchError> {
+    arch.assemble(vaddr, source)
+        .map_err(|message| PatchError::Assemble {
+            snippet: source.to_string(),
+            message,
+        })
+}
+
+/// A minimal x86-64 assembler for control flow patches
+///
+/// The instructions are separated by `;` or newlines.
+/// `jmp`, `call` and the conditional jumps take an absolute address, and are encoded with rel32
+/// so that the size doesn't depend on the target(`jmp short` uses rel8).
+/// `nop`, `ret`, `int3`, `hlt` and `ud2` are also supported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct X86_64;
+
+/// the condition codes of `Jcc`
+const CONDITIONS: [(&str, u8); 30] = [
+    ("jo", 0x0),
+    ("jno", 0x1),
+    ("jb", 0x2),
+    ("jc", 0x2),
+    ("jnae", 0x2),
+    ("jae", 0x3),
+    ("jnb", 0x3),
+    ("jnc", 0x3),
+    ("je", 0x4),
+    ("jz", 0x4),
+    ("jne", 0x5),
+    ("jnz", 0x5),
+    ("jbe", 0x6),
+    ("jna", 0x6),
+    ("ja", 0x7),
+    ("jnbe", 0x7),
+    ("js", 0x8),
+    ("jns", 0x9),
+    ("jp", 0xa),
+    ("jpe", 0xa),
+    ("jnp", 0xb),
+    ("jpo", 0xb),
+    ("jl", 0xc),
+    ("jnge", 0xc),
+    ("jge", 0xd),
+    ("jnl", 0xd),
+    ("jle", 0xe),
+    ("jng", 0xe),
+    ("jg", 0xf),
+    ("jnle", 0xf),
+];
+
+impl Assembler for X86_64 {
+    fn assemble(&self, vaddr: Elf64Addr, source: &str) -> Result<Vec<u8>, String> {
+        let mut code = Vec::new();
+        for insn in source.split([';', '\n']) {
+            let insn = insn.trim().to_ascii_lowercase();
+            if insn.is_empty() {
+                continue;
+            }
+            let pc = vaddr + code.len() as Elf64Addr;
+            let mut words = insn.split_whitespace();
+            let mnemonic = words.next().unwrap();
+            let operands: Vec<&str> = words.collect();
+            let encoded = match (mnemonic, operands.as_slice()) {
+                ("nop", []) => vec![0x90],
+                ("ret", []) => vec![0xc3],
+                ("int3", []) => vec![0xcc],
+                ("hlt", []) => vec![0xf4],
+                ("ud2", []) => vec![0x0f, 0x0b],
+                ("jmp", ["short", target]) => {
+                    let disp = displacement(pc + 2, target)?;
+                    let disp = i8::try_from(disp)
+                        .map_err(|_| format!("{} is too far for a short jump", target))?;
+                    vec![0xeb, disp as u8]
+                }
+                ("jmp", [target]) => rel32(&[0xe9], pc, target)?,
+                ("call", [target]) => rel32(&[0xe8], pc, target)?,
+                (jcc, [target]) => match CONDITIONS.iter().find(|(name, _)| *name == jcc) {
+                    Some((_, cc)) => rel32(&[0x0f, 0x80 | cc], pc, target)?,
+                    None => return Err(format!("unsupported instruction `{}`", insn)),
+                },
+                _ => return Err(format!("unsupported instruction `{}`", insn)),
+            };
+            code.extend(encoded);
+        }
+        Ok(code)
+    }
+
+    fn nop(&self) -> Vec<u8> {
+        vec![0x90]
+    }
+}
+
+/// encode `opcode` followed by the rel32 to `target`.
+fn rel32(opcode: &[u8], pc: Elf64Addr, target: &str) -> Result<Vec<u8>, String> {
+    let next = pc + opcode.len() as Elf64Addr + 4;
+    let disp = displacement(next, target)?;
+    let disp = i32::try_from(disp).map_err(|_| format!("{} is out of rel32 range", target))?;
+    let mut bytes = opcode.to_vec();
+    bytes.extend_from_slice(&disp.to_le_bytes());
+    Ok(bytes)
+}
+
+/// the displacement from the next instruction to the absolute address.
+fn displacement(next: Elf64Addr, target: &str) -> Result<i64, String> {
+    let target = match target.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => target.parse(),
+    }
+    .map_err(|_| format!("invalid address `{}`", target))?;
+    Ok(target.wrapping_sub(next) as i64)
+}
+
+#[cfg(test)]
+mod patcher_tests {
+    use super::*;
+
+    #[test]
+    fn x86_64_test() {
+        // jmp, call, je は rel32，jmp short は rel8
+        let code = X86_64
+            .assemble(
+                0x401000,
+                "jmp 0x401000\ncall 0x400ff0; je 0x401100; jmp short 0x401020",
+            )
+            .unwrap();
+        assert_eq!(
+            vec![
+                0xe9, 0xfb, 0xff, 0xff, 0xff, // jmp
+                0xe8, 0xe6, 0xff, 0xff, 0xff, // call
+                0x0f, 0x84, 0xf0, 0x00, 0x00, 0x00, // je
+                0xeb, 0x0e, // jmp short
+            ],
+            code
+        );
+        assert_eq!(
+            Ok(vec![0x90, 0xc3, 0x0f, 0x0b]),
+            X86_64.assemble(0, "NOP; ret; ud2")
+        );
+        assert!(X86_64.assemble(0, "mov eax, 1").is_err());
+        assert!(X86_64.assemble(0, "jmp short 0x1000").is_err());
+    }
+}
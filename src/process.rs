@@ -0,0 +1,283 @@
+//! Correlating the memory maps of processes with the mapped ELF files.
+//!
+//! `map_modules()` reads `/proc/<pid>/maps`, groups the mappings by the backing file
+//! and computes the load bias of each module,
+//! i.e. the difference between the runtime addresses and the link-time virtual addresses.
+
+use std::fmt;
+
+use crate::*;
+
+/// A line of `/proc/<pid>/maps`
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Mapping {
+    pub start: Elf64Addr,
+    pub end: Elf64Addr,
+    pub readable: bool,
+    pub writable: bool,
+    pub executable: bool,
+    /// `s` (shared) instead of `p` (private, copy-on-write)
+    pub shared: bool,
+    /// the offset in the backing file
+    pub offset: Elf64Off,
+    /// `major:minor` of the device
+    pub dev: String,
+    pub inode: u64,
+    /// the backing file, or a pseudo name like `[heap]`. `None` for anonymous mappings
+    pub path: Option<String>,
+}
+
+impl Mapping {
+    pub fn contains(&self, addr: Elf64Addr) -> bool {
+        self.start <= addr && addr < self.end
+    }
+
+    /// whether the mapping is backed by a file, not `[heap]`, `[vdso]` or anonymous memory.
+    pub fn is_file(&self) -> bool {
+        self.path.as_deref().is_some_and(|p| p.starts_with('/'))
+    }
+}
+
+impl fmt::Display for Mapping {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let flag = |b: bool, c: char| if b { c } else { '-' };
+        write!(
+            f,
+            "{:08x}-{:08x} {}{}{}{} {:08x} {} {}",
+            self.start,
+            self.end,
+            flag(self.readable, 'r'),
+            flag(self.writable, 'w'),
+            flag(self.executable, 'x'),
+            if self.shared { 's' } else { 'p' },
+            self.offset,
+            self.dev,
+            self.inode
+        )?;
+        if let Some(path) = &self.path {
+            write!(f, " {}", path)?;
+        }
+        Ok(())
+    }
+}
+
+/// parse the contents of `/proc/<pid>/maps`.
+/// malformed lines are skipped.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::process;
+///
+/// let maps = process::parse_maps(
+///     "55d0c3a00000-55d0c3a28000 r-xp 00000000 08:02 173521 /usr/bin/true\n\
+///      7ffd6b5e1000-7ffd6b602000 rw-p 00000000 00:00 0 [stack]\n",
+/// );
+/// assert_eq!(2, maps.len());
+/// assert_eq!(0x55d0c3a00000, maps[0].start);
+/// assert!(maps[0].executable && maps[0].is_file());
+/// assert_eq!(Some("[stack]"), maps[1].path.as_deref());
+/// assert!(!maps[1].is_file());
+/// ```
+pub fn parse_maps(maps: &str) -> Vec<Mapping> {
+    maps.lines().filter_map(parse_maps_line).collect()
+}
+
+/// `00400000-00452000 r-xp 00000000 08:02 173521 /usr/bin/dbus-daemon`
+fn parse_maps_line(line: &str) -> Option<Mapping> {
+    // アドレス，権限，オフセット，デバイス，inodeの後ろがパス (空白を含み得る)
+    let mut fields = Vec::with_capacity(5);
+    let mut rest = line;
+    for _ in 0..5 {
+        rest = rest.trim_start();
+        let end = rest.find(' ').unwrap_or(rest.len());
+        fields.push(&rest[..end]);
+        rest = &rest[end..];
+    }
+    let path = rest.trim_start();
+
+    let (start, end) = fields[0].split_once('-')?;
+    let perms = fields[1].as_bytes();
+    if perms.len() != 4 {
+        return None;
+    }
+    Some(Mapping {
+        start: Elf64Addr::from_str_radix(start, 16).ok()?,
+        end: Elf64Addr::from_str_radix(end, 16).ok()?,
+        readable: perms[0] == b'r',
+        writable: perms[1] == b'w',
+        executable: perms[2] == b'x',
+        shared: perms[3] == b's',
+        offset: Elf64Off::from_str_radix(fields[2], 16).ok()?,
+        dev: fields[3].to_string(),
+        inode: fields[4].parse().ok()?,
+        path: if path.is_empty() {
+            None
+        } else {
+            Some(path.to_string())
+        },
+    })
+}
+
+/// An ELF file mapped in a process
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Module {
+    /// the path in `/proc/<pid>/maps`
+    pub path: String,
+    /// the mappings of the file, in address order
+    pub mappings: Vec<Mapping>,
+    /// runtime address - link-time virtual address (wrapping)
+    pub load_bias: Elf64Addr,
+    pub elf: file::ELF,
+}
+
+impl Module {
+    /// the link-time virtual address of the runtime address.
+    pub fn to_vaddr(&self, addr: Elf64Addr) -> Elf64Addr {
+        addr.wrapping_sub(self.load_bias)
+    }
+
+    /// the runtime address of the link-time virtual address.
+    pub fn to_runtime(&self, vaddr: Elf64Addr) -> Elf64Addr {
+        vaddr.wrapping_add(self.load_bias)
+    }
+
+    pub fn contains(&self, addr: Elf64Addr) -> bool {
+        self.mappings.iter().any(|m| m.contains(addr))
+    }
+}
+
+/// the module containing the runtime address.
+pub fn module_containing(modules: &[Module], addr: Elf64Addr) -> Option<&Module> {
+    modules.iter().find(|m| m.contains(addr))
+}
+
+/// group the mappings of the files by their paths, parse each file and compute its load bias.
+///
+/// The files are read through `/proc/<pid>/root`, so it works for processes in containers.
+/// The files which aren't ELF(e.g. `locale-archive`) and the deleted ones are skipped.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::process;
+///
+/// let modules = process::map_modules(std::process::id()).unwrap();
+/// let exe = std::fs::canonicalize(std::env::current_exe().unwrap()).unwrap();
+/// let main = modules.iter().find(|m| m.path == exe.to_str().unwrap()).unwrap();
+///
+/// // this function is in the executable
+/// let addr = process::map_modules as usize as u64;
+/// assert!(std::ptr::eq(main, process::module_containing(&modules, addr).unwrap()));
+/// ```
+#[cfg(target_os = "linux")]
+pub fn map_modules(pid: u32) -> Result<Vec<Module>, Box<dyn std::error::Error>> {
+    let maps = parse_maps(&std::fs::read_to_string(format!("/proc/{}/maps", pid))?);
+    let root = format!("/proc/{}/root", pid);
+    Ok(group_mappings(maps)
+        .into_iter()
+        .filter_map(|(path, mappings)| {
+            let elf = parser::parse_elf(&format!("{}{}", root, path)).ok()?;
+            Some(new_module(path, mappings, elf))
+        })
+        .collect())
+}
+
+/// group the file-backed mappings by `(path, dev, inode)`, in the order of appearance.
+fn group_mappings(maps: Vec<Mapping>) -> Vec<(String, Vec<Mapping>)> {
+    let mut groups: Vec<(String, Vec<Mapping>)> = Vec::new();
+    for m in maps.into_iter().filter(|m| m.is_file()) {
+        let path = m.path.clone().unwrap();
+        match groups
+            .iter_mut()
+            .find(|(p, ms)| *p == path && ms[0].dev == m.dev && ms[0].inode == m.inode)
+        {
+            Some((_, ms)) => ms.push(m),
+            None => groups.push((path, vec![m])),
+        }
+    }
+    groups
+}
+
+/// create a module, computing the load bias from the lowest mapping,
+/// which maps the first `PT_LOAD` segment.
+pub fn new_module(path: String, mut mappings: Vec<Mapping>, elf: file::ELF) -> Module {
+    mappings.sort();
+    let first_load = load_segments(&elf)
+        .into_iter()
+        .min_by_key(|(_, vaddr)| *vaddr);
+    let load_bias = match (mappings.first(), first_load) {
+        // マッピングの先頭はページ境界に切り下げられている
+        (Some(m), Some((offset, vaddr))) if m.offset <= offset => {
+            let linked = vaddr.wrapping_sub(offset - m.offset);
+            m.start.wrapping_sub(linked)
+        }
+        _ => 0,
+    };
+    Module {
+        path,
+        mappings,
+        load_bias,
+        elf,
+    }
+}
+
+/// `(p_offset, p_vaddr)` of the `PT_LOAD` segments.
+fn load_segments(elf: &file::ELF) -> Vec<(Elf64Off, Elf64Addr)> {
+    match elf {
+        file::ELF::ELF64(elf) => elf
+            .segments
+            .iter()
+            .map(|sgt| &sgt.header)
+            .filter(|phdr| phdr.get_type() == segment::Type::Load)
+            .map(|phdr| (phdr.p_offset, phdr.p_vaddr))
+            .collect(),
+        file::ELF::ELF32(elf) => elf
+            .segments
+            .iter()
+            .map(|sgt| &sgt.header)
+            .filter(|phdr| phdr.get_type() == segment::Type::Load)
+            .map(|phdr| (phdr.p_offset as Elf64Off, phdr.p_vaddr as Elf64Addr))
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod process_tests {
+    use super::*;
+
+    #[test]
+    fn parse_maps_test() {
+        let maps = parse_maps(
+            "00400000-00452000 r-xp 00000000 08:02 173521      /usr/bin/dbus-daemon\n\
+             7f00-7f10 r--p 00001000 08:02 42 /tmp/with space.so\n\
+             00e03000-00e24000 rw-p 00000000 00:00 0          [heap]\n\
+             7f10-7f20 rw-p 00000000 00:00 0\n\
+             broken line\n",
+        );
+        assert_eq!(4, maps.len());
+        assert_eq!(
+            "00400000-00452000 r-xp 00000000 08:02 173521 /usr/bin/dbus-daemon",
+            maps[0].to_string()
+        );
+        assert_eq!(Some("/tmp/with space.so"), maps[1].path.as_deref());
+        assert_eq!(0x1000, maps[1].offset);
+        assert!(!maps[2].is_file());
+        assert_eq!(None, maps[3].path);
+    }
+
+    #[test]
+    fn load_bias_test() {
+        let elf = parser::parse_elf("src/parser/testdata/sample").unwrap();
+        let first = load_segments(&elf)[0];
+        let maps = parse_maps(&format!(
+            "{:x}-{:x} r--p {:08x} 08:02 1 /sample\n",
+            0x5555_5555_4000 + first.1,
+            0x5555_5555_5000 + first.1,
+            first.0
+        ));
+        let module = new_module("/sample".to_string(), maps, elf);
+        assert_eq!(0x5555_5555_4000, module.load_bias);
+        assert_eq!(first.1, module.to_vaddr(0x5555_5555_4000 + first.1));
+    }
+}
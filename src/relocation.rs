@@ -6,6 +6,33 @@ pub use elf32::*;
 
 mod elf32;
 
+pub const R_X86_64_NONE: Elf64Xword = 0;
+pub const R_X86_64_64: Elf64Xword = 1;
 pub const R_X86_64_PC32: Elf64Xword = 2;
 pub const R_X86_64_PLT32: Elf64Xword = 4;
+pub const R_X86_64_COPY: Elf64Xword = 5;
+pub const R_X86_64_GLOB_DAT: Elf64Xword = 6;
+pub const R_X86_64_JUMP_SLOT: Elf64Xword = 7;
+pub const R_X86_64_RELATIVE: Elf64Xword = 8;
+pub const R_X86_64_GOTPCREL: Elf64Xword = 9;
 pub const R_X86_64_32: Elf64Xword = 10;
+pub const R_X86_64_32S: Elf64Xword = 11;
+pub const R_X86_64_IRELATIVE: Elf64Xword = 37;
+
+pub const R_AARCH64_NONE: Elf64Xword = 0;
+pub const R_AARCH64_ABS64: Elf64Xword = 257;
+pub const R_AARCH64_GLOB_DAT: Elf64Xword = 1025;
+pub const R_AARCH64_JUMP_SLOT: Elf64Xword = 1026;
+pub const R_AARCH64_RELATIVE: Elf64Xword = 1027;
+pub const R_AARCH64_IRELATIVE: Elf64Xword = 1032;
+
+pub const R_386_32: Elf64Xword = 1;
+pub const R_386_PC32: Elf64Xword = 2;
+pub const R_386_PLT32: Elf64Xword = 4;
+
+pub const R_ARM_ABS32: Elf64Xword = 2;
+pub const R_ARM_REL32: Elf64Xword = 3;
+pub const R_ARM_CALL: Elf64Xword = 28;
+
+pub const R_RISCV_32: Elf64Xword = 1;
+pub const R_RISCV_CALL_PLT: Elf64Xword = 19;
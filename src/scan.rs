@@ -0,0 +1,348 @@
+//! Bulk scanning of directory trees for ELF files.
+//!
+//! # Examples
+//!
+//! ```
+//! use elf_utilities::scan;
+//!
+//! let opts = scan::ScanOptions::default().depth(scan::ParseDepth::Header);
+//! let found = scan::scan_dir("src/parser/testdata", &opts, |entry| {
+//!     assert!(entry.result.is_ok());
+//! })
+//! .unwrap();
+//!
+//! assert_eq!(2, found);
+//! ```
+
+use crate::{file, header, parser};
+
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Mutex};
+use std::thread;
+
+use thiserror::Error as TError;
+
+/// How much of each ELF file is parsed while scanning.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ParseDepth {
+    /// read only the ELF header
+    Header,
+    /// parse the whole file into `file::ELF`
+    Full,
+}
+
+/// Options for `scan_dir()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ScanOptions {
+    /// how much of each file is parsed
+    pub depth: ParseDepth,
+    /// number of worker threads
+    pub threads: usize,
+    /// descend into symbolic links
+    pub follow_symlinks: bool,
+    /// maximum depth of the scanned entries (the root is 0, and the entries in it are 1)
+    pub max_depth: Option<usize>,
+}
+
+/// A parsed ELF file found while scanning.
+pub enum ScannedELF {
+    Header32(header::Ehdr32),
+    Header64(header::Ehdr64),
+    Full(file::ELF),
+}
+
+#[derive(TError, Debug)]
+pub enum ScanError {
+    #[error("can't read `{file_path}` => `{msg}`")]
+    CantRead { file_path: String, msg: String },
+    #[error("can't parse `{file_path}` => `{msg}`")]
+    CantParse { file_path: String, msg: String },
+}
+
+/// A scan result for an ELF file.
+pub struct ScanEntry {
+    pub path: PathBuf,
+    pub result: Result<ScannedELF, ScanError>,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        Self {
+            depth: ParseDepth::Header,
+            threads: thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(1),
+            follow_symlinks: false,
+            max_depth: None,
+        }
+    }
+}
+
+impl ScanOptions {
+    pub fn depth(mut self, depth: ParseDepth) -> Self {
+        self.depth = depth;
+        self
+    }
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
+    pub fn follow_symlinks(mut self, follow: bool) -> Self {
+        self.follow_symlinks = follow;
+        self
+    }
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+}
+
+/// walk `root` recursively and parse every ELF file with a thread pool.
+///
+/// `callback` is invoked on the calling thread for each ELF file as soon as it is parsed.
+/// Files that don't start with the ELF magic are skipped silently.
+/// Returns the number of ELF files found.
+pub fn scan_dir<P, F>(
+    root: P,
+    opts: &ScanOptions,
+    mut callback: F,
+) -> Result<usize, Box<dyn std::error::Error>>
+where
+    P: AsRef<Path>,
+    F: FnMut(ScanEntry),
+{
+    let root = root.as_ref();
+    // ルートが存在しない場合は走査を開始する前にエラーとする
+    std::fs::metadata(root)?;
+
+    let (path_tx, path_rx) = mpsc::channel::<PathBuf>();
+    let path_rx = Mutex::new(path_rx);
+    let (result_tx, result_rx) = mpsc::channel::<ScanEntry>();
+    let depth = opts.depth;
+    let mut found = 0;
+
+    thread::scope(|s| {
+        for _ in 0..opts.threads.max(1) {
+            let result_tx = result_tx.clone();
+            let path_rx = &path_rx;
+            s.spawn(move || loop {
+                let next = path_rx.lock().unwrap().recv();
+                let path = match next {
+                    Ok(path) => path,
+                    Err(_) => break,
+                };
+
+                if let Some(result) = scan_file(&path, depth) {
+                    if result_tx.send(ScanEntry { path, result }).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(result_tx);
+
+        // path_txはwalk終了時にdropされ，ワーカーが終了する
+        s.spawn(move || walk(root, 0, opts, &path_tx, &mut HashSet::new()));
+
+        for entry in result_rx {
+            found += 1;
+            callback(entry);
+        }
+    });
+
+    Ok(found)
+}
+
+/// identifies a directory, to avoid walking a symlink cycle forever
+#[cfg(unix)]
+type DirId = (u64, u64);
+#[cfg(not(unix))]
+type DirId = PathBuf;
+
+#[cfg(unix)]
+fn dir_id(_path: &Path, metadata: &std::fs::Metadata) -> Option<DirId> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn dir_id(path: &Path, _metadata: &std::fs::Metadata) -> Option<DirId> {
+    std::fs::canonicalize(path).ok()
+}
+
+fn walk(
+    path: &Path,
+    depth: usize,
+    opts: &ScanOptions,
+    tx: &mpsc::Sender<PathBuf>,
+    visited: &mut HashSet<DirId>,
+) {
+    if opts.max_depth.is_some_and(|max| depth > max) {
+        return;
+    }
+    // 呼び出し側が渡したルートは，シンボリックリンクであっても辿る
+    let metadata = if opts.follow_symlinks || depth == 0 {
+        std::fs::metadata(path)
+    } else {
+        std::fs::symlink_metadata(path)
+    };
+    let metadata = match metadata {
+        Ok(m) => m,
+        Err(_) => return,
+    };
+
+    if metadata.is_file() {
+        let _ = tx.send(path.to_path_buf());
+        return;
+    }
+    if !metadata.is_dir() {
+        return;
+    }
+    // シンボリックリンクを辿ると同じディレクトリに戻ってくることがある
+    if opts.follow_symlinks {
+        match dir_id(path, &metadata) {
+            Some(id) if visited.insert(id) => {}
+            _ => return,
+        }
+    }
+
+    if let Ok(entries) = std::fs::read_dir(path) {
+        for entry in entries.flatten() {
+            walk(&entry.path(), depth + 1, opts, tx, visited);
+        }
+    }
+}
+
+/// ELFファイルでなければNoneを返す
+fn scan_file(path: &Path, depth: ParseDepth) -> Option<Result<ScannedELF, ScanError>> {
+    let file_path = path.display().to_string();
+    let cant_read = |e: std::io::Error| ScanError::CantRead {
+        file_path: file_path.clone(),
+        msg: e.to_string(),
+    };
+
+    let mut f = match File::open(path) {
+        Ok(f) => f,
+        Err(e) => return Some(Err(cant_read(e))),
+    };
+    let mut buf = Vec::new();
+    let read_result = match depth {
+        ParseDepth::Header => f.take(header::Ehdr64::SIZE as u64).read_to_end(&mut buf),
+        ParseDepth::Full => f.read_to_end(&mut buf),
+    };
+    if let Err(e) = read_result {
+        return Some(Err(cant_read(e)));
+    }
+
+    if buf.len() < 4 || parser::check_elf_magic(&file_path, &buf[..4]).is_err() {
+        return None;
+    }
+
+    // 壊れたファイルでパーサがpanicしても走査全体は止めない
+    let parsed = std::panic::catch_unwind(|| parse_scanned(&file_path, &buf, depth));
+    Some(match parsed {
+        Ok(Ok(elf)) => Ok(elf),
+        Ok(Err(msg)) => Err(ScanError::CantParse { file_path, msg }),
+        Err(_) => Err(ScanError::CantParse {
+            file_path,
+            msg: "malformed ELF file".to_string(),
+        }),
+    })
+}
+
+fn parse_scanned(file_path: &str, buf: &[u8], depth: ParseDepth) -> Result<ScannedELF, String> {
+    let class = header::Class::from(buf.get(header::Class::INDEX).copied().unwrap_or(0));
+    if class != header::Class::Bit32 && class != header::Class::Bit64 {
+        return Err(format!("unknown ELF class {}", class.to_identifier()));
+    }
+
+    match depth {
+        ParseDepth::Header => match parser::parse_elf_header(class, buf) {
+            Ok(header::Ehdr::Ehdr32(ehdr)) => Ok(ScannedELF::Header32(ehdr)),
+            Ok(header::Ehdr::Ehdr64(ehdr)) => Ok(ScannedELF::Header64(ehdr)),
+            Err(e) => Err(e.to_string()),
+        },
+        ParseDepth::Full => match parser::parse_elf_buf(file_path, buf) {
+            Ok(elf) => Ok(ScannedELF::Full(elf)),
+            Err(e) => Err(e.to_string()),
+        },
+    }
+}
+
+#[cfg(test)]
+mod scan_tests {
+    use super::*;
+
+    #[test]
+    fn scan_dir_full_test() {
+        let opts = ScanOptions::default().depth(ParseDepth::Full).threads(2);
+        let mut entries = Vec::new();
+        let found = scan_dir("src/parser/testdata", &opts, |entry| entries.push(entry)).unwrap();
+        assert_eq!(2, found);
+
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+        assert!(matches!(
+            entries[0].result,
+            Ok(ScannedELF::Full(file::ELF::ELF32(_)))
+        ));
+        assert!(matches!(
+            entries[1].result,
+            Ok(ScannedELF::Full(file::ELF::ELF64(_)))
+        ));
+    }
+
+    #[test]
+    fn scan_dir_skips_non_elf_test() {
+        let opts = ScanOptions::default().max_depth(0);
+        let found = scan_dir("src", &opts, |_| {}).unwrap();
+        assert_eq!(0, found);
+
+        assert!(scan_dir("no/such/dir", &opts, |_| {}).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_cycle_test() {
+        let dir = std::env::temp_dir().join(format!("elf_utilities_scan_{}", std::process::id()));
+        let sub = dir.join("sub");
+        std::fs::create_dir_all(&sub).unwrap();
+        std::fs::copy("src/parser/testdata/sample", sub.join("sample")).unwrap();
+        // sub/loop -> .. で循環する
+        std::os::unix::fs::symlink("..", sub.join("loop")).unwrap();
+
+        let opts = ScanOptions::default().follow_symlinks(true).threads(1);
+        let found = scan_dir(&dir, &opts, |_| {});
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(1, found.unwrap());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn max_depth_test() {
+        let dir = std::env::temp_dir().join(format!("elf_utilities_depth_{}", std::process::id()));
+        let sub = dir.join("sub");
+        std::fs::create_dir_all(&sub).unwrap();
+        std::fs::copy("src/parser/testdata/sample", dir.join("sample")).unwrap();
+        std::fs::copy("src/parser/testdata/sample", sub.join("sample")).unwrap();
+        let link =
+            std::env::temp_dir().join(format!("elf_utilities_depth_{}_link", std::process::id()));
+        std::os::unix::fs::symlink(&dir, &link).unwrap();
+
+        let count =
+            |root: &Path, opts: ScanOptions| scan_dir(root, &opts.threads(1), |_| {}).unwrap();
+        let found = [
+            count(&dir, ScanOptions::default().max_depth(0)),
+            count(&dir, ScanOptions::default().max_depth(1)),
+            count(&dir, ScanOptions::default().max_depth(2)),
+            // ルートのシンボリックリンクは辿るが，中のリンクは辿らない
+            count(&link, ScanOptions::default()),
+        ];
+        std::fs::remove_file(&link).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!([0, 1, 2, 2], found);
+    }
+}
@@ -1,20 +1,52 @@
 //! ELF section and section header utilities.
 
+pub use arm_attributes::*;
 #[allow(unused_imports)]
 pub use base::*;
+pub use build_attributes::*;
+pub use codec::*;
 pub use elf32::*;
 pub use elf64::*;
+pub use gnu_hash::*;
+pub use gnu_property::*;
+pub use gnu_version::*;
+pub use insert::*;
+pub use kind::*;
+pub use llvm::*;
+pub use reader::*;
+pub use registry::*;
+pub use resize::*;
+pub use riscv_attributes::*;
 pub use section_flag::*;
 pub use section_type::*;
+pub use stapsdt::*;
+pub use string_table::*;
 
+mod arm_attributes;
 mod base;
+mod build_attributes;
+mod codec;
 mod elf32;
 mod elf64;
+mod gnu_hash;
+mod gnu_property;
+mod gnu_version;
+mod insert;
+mod kind;
+mod llvm;
+mod reader;
+mod registry;
+mod resize;
+mod riscv_attributes;
 mod section_flag;
 mod section_type;
+mod stapsdt;
+mod string_table;
 
 /// Undefined section
 pub const SHN_UNDEF: u16 = 0;
+/// Start of reserved indices
+pub const SHN_LORESERVE: u16 = 0xff00;
 /// Start of processor-specific
 pub const SHN_LOPROC: u16 = 0xff00;
 /// End of processor-specific
@@ -0,0 +1,170 @@
+//! `.ARM.attributes` section utilities.
+
+use crate::*;
+use section::{build_attributes, BuildAttributeValue, ReadError};
+
+/// `SHT_ARM_ATTRIBUTES`
+pub const SHT_ARM_ATTRIBUTES: Elf64Word = 0x70000003;
+
+/// the vendor of the public attributes
+pub const AEABI_VENDOR: &str = "aeabi";
+
+/// the name of the CPU, e.g. "cortex-a9"
+pub const TAG_CPU_NAME: u64 = 5;
+/// the architecture version(`v7` is 10)
+pub const TAG_CPU_ARCH: u64 = 6;
+/// 'A', 'R', 'M' or 'S'
+pub const TAG_CPU_ARCH_PROFILE: u64 = 7;
+pub const TAG_ARM_ISA_USE: u64 = 8;
+pub const TAG_THUMB_ISA_USE: u64 = 9;
+/// the floating point architecture(`VFPv3` is 3)
+pub const TAG_FP_ARCH: u64 = 10;
+pub const TAG_ADVANCED_SIMD_ARCH: u64 = 12;
+/// the size of `wchar_t`
+pub const TAG_ABI_PCS_WCHAR_T: u64 = 18;
+/// 0 for the base(soft-float) procedure call standard, 1 for VFP registers(hard-float)
+pub const TAG_ABI_VFP_ARGS: u64 = 28;
+pub const TAG_COMPATIBILITY: u64 = 32;
+pub const TAG_ALSO_COMPATIBLE_WITH: u64 = 65;
+pub const TAG_CONFORMANCE: u64 = 67;
+
+/// The build attributes in `.ARM.attributes`
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::{header, section};
+///
+/// let mut attrs = section::ArmAttributes::default();
+/// attrs.set_file_attribute(section::TAG_CPU_NAME, section::BuildAttributeValue::Str("cortex-a9".to_string()));
+/// attrs.set_file_attribute(section::TAG_ABI_VFP_ARGS, section::BuildAttributeValue::Int(1));
+///
+/// let bytes = attrs.to_bytes(header::Data::LSB2);
+/// let parsed = section::ArmAttributes::parse(&bytes, header::Data::LSB2).unwrap();
+/// assert_eq!(Some("cortex-a9"), parsed.cpu_name());
+/// assert_eq!(Some(1), parsed.vfp_args());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct ArmAttributes {
+    pub subsections: Vec<section::BuildAttributesSubsection>,
+}
+
+const AEABI: build_attributes::Vendor = build_attributes::Vendor {
+    name: AEABI_VENDOR,
+    is_string_tag,
+    compat_tag: Some(TAG_COMPATIBILITY),
+};
+
+impl ArmAttributes {
+    pub fn parse(bytes: &[u8], data: header::Data) -> Result<Self, ReadError> {
+        let subsections = build_attributes::parse_subsections(bytes, data, &AEABI)?;
+        Ok(Self { subsections })
+    }
+
+    /// Create the section's contents from this.
+    pub fn to_bytes(&self, data: header::Data) -> Vec<u8> {
+        build_attributes::encode_subsections(&self.subsections, data)
+    }
+
+    pub fn to_le_bytes(&self) -> Vec<u8> {
+        self.to_bytes(header::Data::LSB2)
+    }
+
+    /// create `.ARM.attributes` from this.
+    pub fn to_section32(&self, data: header::Data) -> section::Section32 {
+        let mut hdr =
+            section::ShdrPreparation32::default().ty(section::Type::Any(SHT_ARM_ATTRIBUTES));
+        hdr.sh_addralign = 1;
+        section::Section32::new(
+            ".ARM.attributes".to_string(),
+            hdr,
+            section::Contents32::Raw(self.to_bytes(data)),
+        )
+    }
+
+    /// the file-scope attribute in the "aeabi" subsection.
+    pub fn file_attribute(&self, tag: u64) -> Option<&BuildAttributeValue> {
+        build_attributes::file_attribute(&self.subsections, tag)
+    }
+
+    /// set the file-scope attribute in the "aeabi" subsection, creating it if not found.
+    pub fn set_file_attribute(&mut self, tag: u64, value: BuildAttributeValue) {
+        build_attributes::set_file_attribute(&mut self.subsections, &AEABI, tag, value)
+    }
+
+    /// `Tag_CPU_name`
+    pub fn cpu_name(&self) -> Option<&str> {
+        match self.file_attribute(TAG_CPU_NAME)? {
+            BuildAttributeValue::Str(name) => Some(name),
+            _ => None,
+        }
+    }
+    /// `Tag_CPU_arch`
+    pub fn cpu_arch(&self) -> Option<u64> {
+        self.int_attribute(TAG_CPU_ARCH)
+    }
+    /// `Tag_FP_arch`
+    pub fn fp_arch(&self) -> Option<u64> {
+        self.int_attribute(TAG_FP_ARCH)
+    }
+    /// `Tag_ABI_VFP_args`, which tells soft-float and hard-float ABIs apart.
+    pub fn vfp_args(&self) -> Option<u64> {
+        self.int_attribute(TAG_ABI_VFP_ARGS)
+    }
+
+    fn int_attribute(&self, tag: u64) -> Option<u64> {
+        match self.file_attribute(tag)? {
+            BuildAttributeValue::Int(v) => Some(*v),
+            _ => None,
+        }
+    }
+}
+
+/// 4,5,65,67と32以上の奇数のタグは文字列
+fn is_string_tag(tag: u64) -> bool {
+    matches!(tag, 4 | 5 | TAG_ALSO_COMPATIBLE_WITH | TAG_CONFORMANCE) || (tag > 32 && tag % 2 == 1)
+}
+
+#[cfg(test)]
+mod arm_attributes_tests {
+    use super::*;
+
+    // llvm-mc -triple=armv7a-linux-gnueabihf で
+    // .cpu cortex-a9, .fpu neon, Tag_ABI_VFP_args = 1, Tag_conformance, Tag_compatibility を指定
+    const LE: [u8; 45] = [
+        0x41, 0x2c, 0x00, 0x00, 0x00, 0x61, 0x65, 0x61, 0x62, 0x69, 0x00, 0x01, 0x22, 0x00, 0x00,
+        0x00, 0x43, 0x32, 0x2e, 0x30, 0x39, 0x00, 0x05, 0x63, 0x6f, 0x72, 0x74, 0x65, 0x78, 0x2d,
+        0x61, 0x39, 0x00, 0x0a, 0x03, 0x0c, 0x01, 0x1c, 0x01, 0x20, 0x01, 0x67, 0x6e, 0x75, 0x00,
+    ];
+
+    #[test]
+    fn arm_attributes_test() {
+        let attrs = ArmAttributes::parse(&LE, header::Data::LSB2).unwrap();
+        assert_eq!(Some("cortex-a9"), attrs.cpu_name());
+        assert_eq!(Some(3), attrs.fp_arch());
+        assert_eq!(Some(1), attrs.vfp_args());
+        assert_eq!(None, attrs.cpu_arch());
+        assert_eq!(
+            Some(&BuildAttributeValue::Str("2.09".to_string())),
+            attrs.file_attribute(TAG_CONFORMANCE)
+        );
+        assert_eq!(
+            Some(&BuildAttributeValue::Compat(1, "gnu".to_string())),
+            attrs.file_attribute(TAG_COMPATIBILITY)
+        );
+        assert_eq!(LE.to_vec(), attrs.to_le_bytes());
+
+        // ビッグエンディアンでは長さだけが異なる
+        let mut be = LE;
+        be[1..5].copy_from_slice(&0x2cu32.to_be_bytes());
+        be[12..16].copy_from_slice(&0x22u32.to_be_bytes());
+        assert_eq!(
+            attrs,
+            ArmAttributes::parse(&be, header::Data::MSB2).unwrap()
+        );
+        assert_eq!(be.to_vec(), attrs.to_bytes(header::Data::MSB2));
+
+        assert!(ArmAttributes::parse(&LE[..40], header::Data::LSB2).is_err());
+        assert!(ArmAttributes::parse(b"B", header::Data::LSB2).is_err());
+    }
+}
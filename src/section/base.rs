@@ -40,17 +40,17 @@ impl Section {
         }
     }
 
-    pub fn as_64bit(&self) -> Section64 {
+    pub fn into_64bit(self) -> Section64 {
         Section64 {
-            name: self.name.clone(),
-            contents: self.contents.as_64bit(),
+            name: self.name.into(),
+            contents: self.contents.into_64bit(),
             header: self.header.as_64bit(),
         }
     }
-    pub fn as_32bit(&self) -> Section32 {
+    pub fn into_32bit(self) -> Section32 {
         Section32 {
-            name: self.name.clone(),
-            contents: self.contents.as_32bit(),
+            name: self.name,
+            contents: self.contents.into_32bit(),
             header: self.header.as_32bit(),
         }
     }
@@ -94,18 +94,19 @@ impl Section {
 }
 
 impl Contents {
-    pub fn as_64bit(&self) -> Contents64 {
+    pub fn into_64bit(self) -> Contents64 {
         match self {
-            Contents::Contents64(contents) => contents.clone(),
+            Contents::Contents64(contents) => contents,
             _ => unreachable!(),
         }
     }
-    pub fn as_32bit(&self) -> Contents32 {
+    pub fn into_32bit(self) -> Contents32 {
         match self {
-            Contents::Contents32(contents) => contents.clone(),
+            Contents::Contents32(contents) => contents,
             _ => unreachable!(),
         }
     }
+    #[allow(dead_code)]
     pub fn as_strtab(&self) -> Vec<StrTabEntry> {
         match self {
             Contents::Contents32(contents) => match contents {
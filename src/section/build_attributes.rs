@@ -0,0 +1,274 @@
+//! The build attributes format shared by `.ARM.attributes` and `.riscv.attributes`.
+//!
+//! The section starts with the version 'A' and is followed by vendor subsections.
+//! Each subsection has groups(sub-subsections) which apply attributes to the file, sections or symbols.
+
+use crate::*;
+use section::{PayloadReader, ReadError};
+
+/// the format version of the build attributes
+const FORMAT_VERSION: u8 = b'A';
+
+/// The value of an attribute
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum BuildAttributeValue {
+    Int(u64),
+    Str(String),
+    /// ARM's `Tag_compatibility`, which has a flag and a vendor name
+    Compat(u64, String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BuildAttribute {
+    pub tag: u64,
+    pub value: BuildAttributeValue,
+}
+
+/// The entities which the attributes apply to
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum BuildAttributeScope {
+    File,
+    /// the section indices
+    Section(Vec<u64>),
+    /// the symbol indices
+    Symbol(Vec<u64>),
+}
+
+/// A sub-subsection of a vendor subsection
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BuildAttributeGroup {
+    pub scope: BuildAttributeScope,
+    pub attributes: Vec<BuildAttribute>,
+}
+
+/// The contents of a vendor subsection
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum VendorAttributes {
+    /// the attributes of the public vendor(e.g. "aeabi" and "riscv")
+    Decoded(Vec<BuildAttributeGroup>),
+    /// the attributes of the other vendors, whose format is unknown
+    Raw(Vec<u8>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BuildAttributesSubsection {
+    pub vendor: String,
+    pub contents: VendorAttributes,
+}
+
+/// how the public vendor's attributes are encoded
+pub(crate) struct Vendor {
+    pub name: &'static str,
+    pub is_string_tag: fn(u64) -> bool,
+    /// the tag which has a ULEB128 followed by a string
+    pub compat_tag: Option<u64>,
+}
+
+pub(crate) fn parse_subsections(
+    bytes: &[u8],
+    data: header::Data,
+    vendor: &Vendor,
+) -> Result<Vec<BuildAttributesSubsection>, ReadError> {
+    let mut r = PayloadReader::new(bytes, data);
+    if r.is_empty() {
+        return Ok(Vec::new());
+    }
+    let version = r.u8()?;
+    if version != FORMAT_VERSION {
+        return Err(ReadError::UnsupportedVersion { offset: 0, version });
+    }
+
+    let mut subsections = Vec::new();
+    while !r.is_empty() {
+        let start = r.position();
+        let end = start + r.u32()? as usize;
+        let name = r.cstr()?;
+        check_end(&r, end)?;
+        let contents = if name == vendor.name {
+            let mut groups = Vec::new();
+            while r.position() < end {
+                groups.push(parse_group(&mut r, vendor)?);
+            }
+            check_end(&r, end)?;
+            VendorAttributes::Decoded(groups)
+        } else {
+            VendorAttributes::Raw(r.bytes(end - r.position())?)
+        };
+        subsections.push(BuildAttributesSubsection {
+            vendor: name,
+            contents,
+        });
+    }
+    Ok(subsections)
+}
+
+pub(crate) fn encode_subsections(
+    subsections: &[BuildAttributesSubsection],
+    data: header::Data,
+) -> Vec<u8> {
+    let u32_bytes = |v: u32| match data {
+        header::Data::MSB2 => v.to_be_bytes(),
+        _ => v.to_le_bytes(),
+    };
+    let mut bytes = vec![FORMAT_VERSION];
+    for sub in subsections.iter() {
+        let mut body = sub.vendor.as_bytes().to_vec();
+        body.push(0);
+        match &sub.contents {
+            VendorAttributes::Decoded(groups) => {
+                for group in groups.iter() {
+                    let (tag, indices) = match &group.scope {
+                        BuildAttributeScope::File => (1, &[][..]),
+                        BuildAttributeScope::Section(indices) => (2, &indices[..]),
+                        BuildAttributeScope::Symbol(indices) => (3, &indices[..]),
+                    };
+                    let mut contents = Vec::new();
+                    if tag != 1 {
+                        for idx in indices.iter() {
+                            contents.extend(util::encode_uleb128(*idx));
+                        }
+                        contents.push(0);
+                    }
+                    for attr in group.attributes.iter() {
+                        encode_attribute(&mut contents, attr);
+                    }
+                    // タグ1バイトと長さ4バイトを含む
+                    body.push(tag);
+                    body.extend_from_slice(&u32_bytes(contents.len() as u32 + 5));
+                    body.extend(contents);
+                }
+            }
+            VendorAttributes::Raw(raw) => body.extend_from_slice(raw),
+        }
+        bytes.extend_from_slice(&u32_bytes(body.len() as u32 + 4));
+        bytes.extend(body);
+    }
+    bytes
+}
+
+/// the file-scope attribute in the decoded subsections.
+pub(crate) fn file_attribute(
+    subsections: &[BuildAttributesSubsection],
+    tag: u64,
+) -> Option<&BuildAttributeValue> {
+    subsections
+        .iter()
+        .filter_map(|sub| match &sub.contents {
+            VendorAttributes::Decoded(groups) => Some(groups),
+            _ => None,
+        })
+        .flatten()
+        .filter(|group| group.scope == BuildAttributeScope::File)
+        .flat_map(|group| group.attributes.iter())
+        .find(|attr| attr.tag == tag)
+        .map(|attr| &attr.value)
+}
+
+/// set the file-scope attribute, creating the public vendor's subsection if not found.
+pub(crate) fn set_file_attribute(
+    subsections: &mut Vec<BuildAttributesSubsection>,
+    vendor: &Vendor,
+    tag: u64,
+    value: BuildAttributeValue,
+) {
+    let sub_idx = match subsections
+        .iter()
+        .position(|sub| matches!(sub.contents, VendorAttributes::Decoded(_)))
+    {
+        Some(idx) => idx,
+        None => {
+            // 公開ベンダーのサブセクションは先頭に置く
+            subsections.insert(
+                0,
+                BuildAttributesSubsection {
+                    vendor: vendor.name.to_string(),
+                    contents: VendorAttributes::Decoded(Vec::new()),
+                },
+            );
+            0
+        }
+    };
+    let groups = match &mut subsections[sub_idx].contents {
+        VendorAttributes::Decoded(groups) => groups,
+        VendorAttributes::Raw(_) => unreachable!(),
+    };
+    if !groups.iter().any(|g| g.scope == BuildAttributeScope::File) {
+        groups.insert(
+            0,
+            BuildAttributeGroup {
+                scope: BuildAttributeScope::File,
+                attributes: Vec::new(),
+            },
+        );
+    }
+    let group = groups
+        .iter_mut()
+        .find(|g| g.scope == BuildAttributeScope::File)
+        .unwrap();
+    match group.attributes.iter_mut().find(|attr| attr.tag == tag) {
+        Some(attr) => attr.value = value,
+        None => group.attributes.push(BuildAttribute { tag, value }),
+    }
+}
+
+fn parse_group(r: &mut PayloadReader, vendor: &Vendor) -> Result<BuildAttributeGroup, ReadError> {
+    let start = r.position();
+    let tag = r.uleb128()?;
+    let end = start + r.u32()? as usize;
+    let mut indices = Vec::new();
+    if tag != 1 {
+        loop {
+            match r.uleb128()? {
+                0 => break,
+                idx => indices.push(idx),
+            }
+        }
+    }
+    let scope = match tag {
+        2 => BuildAttributeScope::Section(indices),
+        3 => BuildAttributeScope::Symbol(indices),
+        _ => BuildAttributeScope::File,
+    };
+
+    let mut attributes = Vec::new();
+    while r.position() < end {
+        let tag = r.uleb128()?;
+        let value = if Some(tag) == vendor.compat_tag {
+            BuildAttributeValue::Compat(r.uleb128()?, r.cstr()?)
+        } else if (vendor.is_string_tag)(tag) {
+            BuildAttributeValue::Str(r.cstr()?)
+        } else {
+            BuildAttributeValue::Int(r.uleb128()?)
+        };
+        attributes.push(BuildAttribute { tag, value });
+    }
+    check_end(r, end)?;
+    Ok(BuildAttributeGroup { scope, attributes })
+}
+
+fn encode_attribute(bytes: &mut Vec<u8>, attr: &BuildAttribute) {
+    bytes.extend(util::encode_uleb128(attr.tag));
+    match &attr.value {
+        BuildAttributeValue::Int(v) => bytes.extend(util::encode_uleb128(*v)),
+        BuildAttributeValue::Str(s) => {
+            bytes.extend_from_slice(s.as_bytes());
+            bytes.push(0);
+        }
+        BuildAttributeValue::Compat(flag, vendor) => {
+            bytes.extend(util::encode_uleb128(*flag));
+            bytes.extend_from_slice(vendor.as_bytes());
+            bytes.push(0);
+        }
+    }
+}
+
+/// the length field must cover what was read.
+fn check_end(r: &PayloadReader, end: usize) -> Result<(), ReadError> {
+    if r.position() > end {
+        return Err(ReadError::UnexpectedEnd {
+            offset: end,
+            needed: r.position() - end,
+        });
+    }
+    Ok(())
+}
@@ -0,0 +1,420 @@
+//! Decoding and encoding of section contents.
+//!
+//! The parser decodes the generic tables through the built-in codecs,
+//! and downstream crates plug their codecs into `Codecs`(e.g. for `.BTF` or `.note.stapsdt`).
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+use serde::Deserialize;
+
+use crate::*;
+use section::{Contents64, CustomContents, PayloadReader, Shdr64, StrTabEntry, TypeRegistry};
+
+/// Converts the bytes of sections into `Contents64` and back
+pub trait SectionCodec {
+    /// whether the codec decodes the section.
+    fn accepts(&self, shdr: &Shdr64, name: &str) -> bool;
+
+    /// decode the contents, `Contents64::Raw` if the bytes are malformed.
+    fn decode(&self, shdr: &Shdr64, bytes: &[u8]) -> Contents64;
+
+    /// encode the contents which this codec decoded.
+    fn encode(&self, contents: &Contents64) -> Vec<u8>;
+}
+
+/// The contents decoded by a codec outside this crate, with the bytes it was decoded from
+///
+/// The contents are immutable, so encode new ones into a `CustomData` to edit them.
+/// Comparisons and hashes only look at the bytes.
+#[derive(Debug, Clone)]
+pub struct CustomData {
+    value: Arc<dyn CustomContents>,
+    bytes: Vec<u8>,
+}
+
+impl CustomData {
+    pub fn new<C: CustomContents + 'static>(value: C, bytes: Vec<u8>) -> Self {
+        Self {
+            value: Arc::new(value),
+            bytes,
+        }
+    }
+
+    pub fn value(&self) -> &dyn CustomContents {
+        self.value.as_ref()
+    }
+
+    /// downcast to the codec's type.
+    pub fn downcast_ref<T: 'static>(&self) -> Option<&T> {
+        self.value.as_any().downcast_ref()
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+impl fmt::Display for CustomData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.value.fmt(f)
+    }
+}
+
+impl PartialEq for CustomData {
+    fn eq(&self, other: &Self) -> bool {
+        self.bytes == other.bytes
+    }
+}
+
+impl Eq for CustomData {}
+
+impl PartialOrd for CustomData {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for CustomData {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.bytes.cmp(&other.bytes)
+    }
+}
+
+impl Hash for CustomData {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.bytes.hash(state);
+    }
+}
+
+/// `SHT_STRTAB`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct StrTabCodec;
+
+impl SectionCodec for StrTabCodec {
+    fn accepts(&self, shdr: &Shdr64, _name: &str) -> bool {
+        shdr.get_type() == section::Type::StrTab
+    }
+
+    fn decode(&self, _shdr: &Shdr64, bytes: &[u8]) -> Contents64 {
+        let mut strs = Vec::new();
+        let mut name_idx = 0;
+        while name_idx < bytes.len() {
+            if bytes[name_idx] == 0x00 {
+                name_idx += 1;
+                continue;
+            }
+
+            let len = bytes[name_idx..]
+                .iter()
+                .position(|&c| c == b'\0')
+                .unwrap_or(bytes.len() - name_idx);
+            let v = match std::str::from_utf8(&bytes[name_idx..name_idx + len]) {
+                Ok(v) => v.to_string(),
+                Err(_) => return Contents64::Raw(bytes.to_vec()),
+            };
+            strs.push(StrTabEntry { v, idx: name_idx });
+            name_idx += len;
+        }
+        Contents64::StrTab(strs)
+    }
+
+    fn encode(&self, contents: &Contents64) -> Vec<u8> {
+        contents.to_le_bytes()
+    }
+}
+
+/// `SHT_SYMTAB` and `SHT_DYNSYM`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct SymbolTableCodec;
+
+impl SectionCodec for SymbolTableCodec {
+    fn accepts(&self, shdr: &Shdr64, _name: &str) -> bool {
+        matches!(
+            shdr.get_type(),
+            section::Type::SymTab | section::Type::DynSym
+        )
+    }
+
+    fn decode(&self, shdr: &Shdr64, bytes: &[u8]) -> Contents64 {
+        decode_table(shdr, bytes, Contents64::Symbols)
+    }
+
+    fn encode(&self, contents: &Contents64) -> Vec<u8> {
+        contents.to_le_bytes()
+    }
+}
+
+/// `SHT_RELA`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct RelaCodec;
+
+impl SectionCodec for RelaCodec {
+    fn accepts(&self, shdr: &Shdr64, _name: &str) -> bool {
+        shdr.get_type() == section::Type::Rela
+    }
+
+    fn decode(&self, shdr: &Shdr64, bytes: &[u8]) -> Contents64 {
+        decode_table(shdr, bytes, Contents64::RelaSymbols)
+    }
+
+    fn encode(&self, contents: &Contents64) -> Vec<u8> {
+        contents.to_le_bytes()
+    }
+}
+
+/// `SHT_DYNAMIC`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct DynamicCodec;
+
+impl SectionCodec for DynamicCodec {
+    fn accepts(&self, shdr: &Shdr64, _name: &str) -> bool {
+        shdr.get_type() == section::Type::Dynamic
+    }
+
+    fn decode(&self, shdr: &Shdr64, bytes: &[u8]) -> Contents64 {
+        decode_table(shdr, bytes, Contents64::Dynamics)
+    }
+
+    fn encode(&self, contents: &Contents64) -> Vec<u8> {
+        contents.to_le_bytes()
+    }
+}
+
+/// The types registered in `TypeRegistry` are decoded into `Contents64::Custom`.
+/// The contents are read as little endian like the rest of `ELF64`.
+impl SectionCodec for TypeRegistry {
+    fn accepts(&self, shdr: &Shdr64, _name: &str) -> bool {
+        shdr.get_type() != section::Type::NoBits && self.lookup(shdr.sh_type).is_some()
+    }
+
+    fn decode(&self, shdr: &Shdr64, bytes: &[u8]) -> Contents64 {
+        let registered = match self.lookup(shdr.sh_type) {
+            Some(registered) => registered,
+            None => return Contents64::Raw(bytes.to_vec()),
+        };
+        match (registered.decoder)(shdr, PayloadReader::new(bytes, header::Data::LSB2)) {
+            Ok(value) => Contents64::Custom(CustomData {
+                value: Arc::from(value),
+                bytes: bytes.to_vec(),
+            }),
+            Err(_) => Contents64::Raw(bytes.to_vec()),
+        }
+    }
+
+    fn encode(&self, contents: &Contents64) -> Vec<u8> {
+        contents.to_le_bytes()
+    }
+}
+
+/// The codecs the parser decodes sections with
+///
+/// The registered codecs are tried in the registered order, before the built-in ones.
+///
+/// # Examples
+///
+/// ```
+/// use std::any::Any;
+/// use std::fmt;
+///
+/// use elf_utilities::{parser, section};
+///
+/// /// `.comment`, the NUL-separated compiler versions
+/// #[derive(Debug)]
+/// struct Comment(Vec<String>);
+///
+/// impl fmt::Display for Comment {
+///     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+///         write!(f, "{}", self.0.join(", "))
+///     }
+/// }
+///
+/// impl section::CustomContents for Comment {
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+/// }
+///
+/// struct CommentCodec;
+///
+/// impl section::SectionCodec for CommentCodec {
+///     fn accepts(&self, _shdr: &section::Shdr64, name: &str) -> bool {
+///         name == ".comment"
+///     }
+///     fn decode(&self, _shdr: &section::Shdr64, bytes: &[u8]) -> section::Contents64 {
+///         let versions = bytes
+///             .split(|b| *b == 0)
+///             .filter(|s| !s.is_empty())
+///             .map(|s| String::from_utf8_lossy(s).to_string())
+///             .collect();
+///         section::Contents64::Custom(section::CustomData::new(Comment(versions), bytes.to_vec()))
+///     }
+///     fn encode(&self, contents: &section::Contents64) -> Vec<u8> {
+///         contents.to_le_bytes()
+///     }
+/// }
+///
+/// let codecs = section::Codecs::new().register(CommentCodec);
+/// let elf = parser::parse_elf64_with("src/parser/testdata/sample", &codecs).unwrap();
+/// let comment = elf.first_section_by(|sct| sct.name == ".comment").unwrap();
+/// match &comment.contents {
+///     section::Contents64::Custom(custom) => {
+///         assert_eq!("GCC: (Ubuntu 9.3.0-10ubuntu2) 9.3.0", custom.to_string());
+///     }
+///     _ => panic!("not decoded"),
+/// }
+///
+/// // 元のバイト列に戻る
+/// let plain = parser::parse_elf64("src/parser/testdata/sample").unwrap();
+/// assert_eq!(plain.to_le_bytes(), elf.to_le_bytes());
+/// ```
+#[derive(Default)]
+pub struct Codecs {
+    codecs: Vec<Box<dyn SectionCodec + Send + Sync>>,
+}
+
+impl Codecs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register<C: SectionCodec + Send + Sync + 'static>(mut self, codec: C) -> Self {
+        self.codecs.push(Box::new(codec));
+        self
+    }
+
+    /// the codec which decodes the section.
+    pub fn find(&self, shdr: &Shdr64, name: &str) -> Option<&dyn SectionCodec> {
+        self.registered(shdr, name)
+            .or_else(|| builtin_codec(shdr, name))
+    }
+
+    /// decode the contents of the section, `Contents64::Raw` if no codec accepts it.
+    pub fn decode(&self, shdr: &Shdr64, name: &str, bytes: &[u8]) -> Contents64 {
+        match self.find(shdr, name) {
+            Some(codec) => codec.decode(shdr, bytes),
+            None => Contents64::Raw(bytes.to_vec()),
+        }
+    }
+
+    /// the registered codec which decodes the section, ignoring the built-in ones.
+    pub(crate) fn registered(&self, shdr: &Shdr64, name: &str) -> Option<&dyn SectionCodec> {
+        self.codecs
+            .iter()
+            .find(|codec| codec.accepts(shdr, name))
+            .map(|codec| codec.as_ref() as &dyn SectionCodec)
+    }
+}
+
+/// the built-in codec which decodes the section.
+pub(crate) fn builtin_codec(shdr: &Shdr64, name: &str) -> Option<&'static dyn SectionCodec> {
+    let builtins: [&'static dyn SectionCodec; 4] =
+        [&StrTabCodec, &SymbolTableCodec, &RelaCodec, &DynamicCodec];
+    builtins
+        .iter()
+        .copied()
+        .find(|codec| codec.accepts(shdr, name))
+}
+
+/// decode the table of `sh_entsize` entries.
+fn decode_table<'a, T: Deserialize<'a>>(
+    shdr: &Shdr64,
+    bytes: &'a [u8],
+    wrap: fn(Vec<T>) -> Contents64,
+) -> Contents64 {
+    let entry_size = shdr.sh_entsize as usize;
+    if entry_size == 0 {
+        return Contents64::Raw(bytes.to_vec());
+    }
+    let table: Result<Vec<T>, _> = bytes
+        .chunks_exact(entry_size)
+        .map(bincode::deserialize)
+        .collect();
+    match table {
+        Ok(table) => wrap(table),
+        Err(_) => Contents64::Raw(bytes.to_vec()),
+    }
+}
+
+#[cfg(test)]
+mod codec_tests {
+    use super::*;
+
+    #[test]
+    fn builtin_codec_test() {
+        let strtab = Contents64::new_string_table(vec!["main".to_string(), "_start".to_string()]);
+        let bytes = strtab.to_le_bytes();
+        let shdr = Shdr64 {
+            sh_type: section::Type::StrTab.into(),
+            ..Default::default()
+        };
+        let codecs = Codecs::new();
+        let codec = codecs.find(&shdr, ".strtab").unwrap();
+        assert_eq!(strtab, codec.decode(&shdr, &bytes));
+        assert_eq!(bytes, codec.encode(&strtab));
+
+        // エントリサイズが0のテーブルは読まない
+        let shdr = Shdr64 {
+            sh_type: section::Type::SymTab.into(),
+            ..Default::default()
+        };
+        assert_eq!(
+            Contents64::Raw(vec![0; 24]),
+            codecs.decode(&shdr, ".symtab", &[0; 24])
+        );
+    }
+
+    #[derive(Debug)]
+    struct Words(Vec<u32>);
+
+    impl fmt::Display for Words {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "{:?}", self.0)
+        }
+    }
+
+    impl CustomContents for Words {
+        fn as_any(&self) -> &dyn std::any::Any {
+            self
+        }
+    }
+
+    fn words(_shdr: &Shdr64, mut r: PayloadReader<'_>) -> section::Decoded {
+        let mut words = Vec::new();
+        while !r.is_empty() {
+            words.push(r.u32()?);
+        }
+        Ok(Box::new(Words(words)))
+    }
+
+    #[test]
+    fn registry_codec_test() {
+        let codecs = Codecs::new().register(TypeRegistry::new().register(
+            0x8000_0000..=0x8000_0000,
+            "WORDS",
+            words,
+        ));
+        let shdr = Shdr64 {
+            sh_type: 0x8000_0000,
+            ..Default::default()
+        };
+        let bytes = [1, 0, 0, 0, 2, 0, 0, 0];
+        let contents = codecs.decode(&shdr, ".words", &bytes);
+        match &contents {
+            Contents64::Custom(custom) => {
+                assert_eq!(vec![1, 2], custom.downcast_ref::<Words>().unwrap().0);
+                assert_eq!("[1, 2]", custom.to_string());
+            }
+            _ => panic!("not decoded"),
+        }
+        assert_eq!(bytes.to_vec(), contents.to_le_bytes());
+
+        // デコードに失敗したらRawのまま
+        assert_eq!(
+            Contents64::Raw(vec![1, 0]),
+            codecs.decode(&shdr, ".words", &[1, 0])
+        );
+    }
+}
@@ -2,7 +2,9 @@
 
 use std::collections::HashSet;
 
+use crate::flags_debug::FlagsDebug;
 use crate::*;
+use std::fmt;
 
 use serde::{Deserialize, Serialize};
 
@@ -23,14 +25,14 @@ pub enum Contents32 {
     Dynamics(Vec<dynamic::Dyn32>),
 }
 
-#[derive(Default, Clone, Hash, PartialOrd, Ord, PartialEq, Eq)]
+#[derive(Default, Debug, Clone, Hash, PartialOrd, Ord, PartialEq, Eq)]
 pub struct Section32 {
     pub name: String,
     pub header: Shdr32,
     pub contents: Contents32,
 }
 
-#[derive(Debug, Clone, Copy, Hash, PartialOrd, Ord, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Default, Clone, Copy, Hash, PartialOrd, Ord, PartialEq, Eq, Serialize, Deserialize)]
 #[repr(C)]
 pub struct Shdr32 {
     /// Section name, index in string tbl
@@ -55,6 +57,30 @@ pub struct Shdr32 {
     pub sh_entsize: Elf32Word,
 }
 
+impl fmt::Debug for Shdr32 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Shdr32")
+            .field("sh_name", &self.sh_name)
+            .field("sh_type", &self.get_type())
+            .field(
+                "sh_flags",
+                &FlagsDebug {
+                    bits: self.sh_flags as u64,
+                    all: &section::Flag::ALL,
+                    to_bits: |f| Elf32Word::from(f) as u64,
+                },
+            )
+            .field("sh_addr", &self.sh_addr)
+            .field("sh_offset", &self.sh_offset)
+            .field("sh_size", &self.sh_size)
+            .field("sh_link", &self.sh_link)
+            .field("sh_info", &self.sh_info)
+            .field("sh_addralign", &self.sh_addralign)
+            .field("sh_entsize", &self.sh_entsize)
+            .finish()
+    }
+}
+
 /// A `Shdr32` builder
 ///
 /// # Examples
@@ -70,7 +96,7 @@ pub struct Shdr32 {
 /// assert!(shdr.get_flags().contains(&section::Flag::Alloc));
 /// assert!(shdr.get_flags().contains(&section::Flag::Write));
 /// ```
-#[derive(Clone, Copy, Hash, PartialOrd, Ord, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Hash, PartialOrd, Ord, PartialEq, Eq)]
 #[repr(C)]
 pub struct ShdrPreparation32 {
     /// Type of section
@@ -126,23 +152,6 @@ impl Contents32 {
     }
 }
 
-impl Default for Shdr32 {
-    fn default() -> Self {
-        Self {
-            sh_name: 0,
-            sh_type: 0,
-            sh_flags: 0,
-            sh_addr: 0,
-            sh_offset: 0,
-            sh_size: 0,
-            sh_link: 0,
-            sh_info: 0,
-            sh_addralign: 0,
-            sh_entsize: 0,
-        }
-    }
-}
-
 impl Section32 {
     pub fn new(name: String, hdr: ShdrPreparation32, contents: Contents32) -> Self {
         Self {
@@ -230,7 +239,7 @@ impl Shdr32 {
         I: Iterator<Item = &'a section::Flag>,
     {
         for flag in flags {
-            self.sh_flags = self.sh_flags | Into::<Elf32Word>::into(*flag);
+            self.sh_flags |= Into::<Elf32Word>::into(*flag);
         }
     }
 
@@ -287,18 +296,18 @@ impl Default for ShdrPreparation32 {
         }
     }
 }
-impl Into<Shdr32> for ShdrPreparation32 {
-    fn into(self) -> Shdr32 {
+impl From<ShdrPreparation32> for Shdr32 {
+    fn from(prep: ShdrPreparation32) -> Self {
         Shdr32 {
             sh_name: 0,
-            sh_type: self.sh_type.into(),
-            sh_flags: self.sh_flags,
+            sh_type: prep.sh_type.into(),
+            sh_flags: prep.sh_flags,
             sh_addr: 0,
             sh_offset: 0,
             sh_size: 0,
-            sh_link: self.sh_link,
-            sh_info: self.sh_info,
-            sh_addralign: self.sh_addralign,
+            sh_link: prep.sh_link,
+            sh_info: prep.sh_info,
+            sh_addralign: prep.sh_addralign,
             sh_entsize: 0,
         }
     }
@@ -2,8 +2,10 @@
 
 use std::collections::HashSet;
 
+use crate::flags_debug::FlagsDebug;
 use crate::section;
 use crate::*;
+use std::fmt;
 
 use serde::{Deserialize, Serialize};
 
@@ -21,17 +23,19 @@ pub enum Contents64 {
     Dynamics(Vec<dynamic::Dyn64>),
     /// String Table
     StrTab(Vec<StrTabEntry>),
+    /// contents decoded by a codec outside this crate
+    Custom(section::CustomData),
 }
 
-#[derive(Clone, Hash, PartialOrd, Ord, PartialEq, Eq)]
+#[derive(Debug, Clone, Hash, PartialOrd, Ord, PartialEq, Eq)]
 pub struct Section64 {
-    pub name: String,
+    pub name: intern::Name,
     pub header: Shdr64,
 
     pub contents: Contents64,
 }
 
-#[derive(Debug, Clone, Copy, Hash, PartialOrd, Ord, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Default, Clone, Copy, Hash, PartialOrd, Ord, PartialEq, Eq, Serialize, Deserialize)]
 #[repr(C)]
 pub struct Shdr64 {
     /// Section name, index in string tbl
@@ -56,6 +60,45 @@ pub struct Shdr64 {
     pub sh_entsize: Elf64Xword,
 }
 
+/// `sh_type` and `sh_flags` are rendered with symbolic names.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::section;
+/// let shdr: section::Shdr64 = section::ShdrPreparation64::default()
+///     .ty(section::Type::ProgBits)
+///     .flags([section::Flag::Alloc, section::Flag::ExecInstr].iter())
+///     .into();
+///
+/// let s = format!("{:?}", shdr);
+/// assert!(s.contains("sh_type: ProgBits"));
+/// assert!(s.contains("sh_flags: Alloc | ExecInstr"));
+/// ```
+impl fmt::Debug for Shdr64 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Shdr64")
+            .field("sh_name", &self.sh_name)
+            .field("sh_type", &self.get_type())
+            .field(
+                "sh_flags",
+                &FlagsDebug {
+                    bits: self.sh_flags,
+                    all: &section::Flag::ALL,
+                    to_bits: |f| Elf64Xword::from(f),
+                },
+            )
+            .field("sh_addr", &self.sh_addr)
+            .field("sh_offset", &self.sh_offset)
+            .field("sh_size", &self.sh_size)
+            .field("sh_link", &self.sh_link)
+            .field("sh_info", &self.sh_info)
+            .field("sh_addralign", &self.sh_addralign)
+            .field("sh_entsize", &self.sh_entsize)
+            .finish()
+    }
+}
+
 /// A `Shdr64` builder
 ///
 /// # Examples
@@ -71,7 +114,7 @@ pub struct Shdr64 {
 /// assert!(shdr.get_flags().contains(&section::Flag::Alloc));
 /// assert!(shdr.get_flags().contains(&section::Flag::Write));
 /// ```
-#[derive(Clone, Copy, Hash, PartialOrd, Ord, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Hash, PartialOrd, Ord, PartialEq, Eq)]
 #[repr(C)]
 pub struct ShdrPreparation64 {
     /// Type of section
@@ -86,23 +129,6 @@ pub struct ShdrPreparation64 {
     pub sh_addralign: Elf64Xword,
 }
 
-impl Default for Shdr64 {
-    fn default() -> Self {
-        Self {
-            sh_name: 0,
-            sh_type: 0,
-            sh_flags: 0,
-            sh_addr: 0,
-            sh_offset: 0,
-            sh_size: 0,
-            sh_link: 0,
-            sh_info: 0,
-            sh_addralign: 0,
-            sh_entsize: 0,
-        }
-    }
-}
-
 #[allow(dead_code)]
 impl Shdr64 {
     pub const SIZE: usize = 0x40;
@@ -136,7 +162,7 @@ impl Shdr64 {
         I: Iterator<Item = &'a section::Flag>,
     {
         for flag in flags {
-            self.sh_flags = self.sh_flags | Into::<Elf64Xword>::into(*flag);
+            self.sh_flags |= Into::<Elf64Xword>::into(*flag);
         }
     }
 
@@ -167,51 +193,14 @@ impl Section64 {
     pub fn new(name: String, hdr: ShdrPreparation64, contents: Contents64) -> Self {
         Self {
             contents,
-            name,
+            name: name.into(),
             header: hdr.into(),
         }
     }
 
     /// create binary without header
     pub fn to_le_bytes(&self) -> Vec<u8> {
-        match &self.contents {
-            Contents64::Raw(bytes) => bytes.clone(),
-            Contents64::StrTab(strs) => {
-                // ELFの文字列テーブルは null-byte + (name + null-byte) * n という形状に
-                // それに合うようにバイト列を構築.
-                let mut string_table: Vec<u8> = vec![0x00];
-
-                for st in strs {
-                    for byte in st.v.as_bytes() {
-                        string_table.push(*byte);
-                    }
-                    string_table.push(0x00);
-                }
-
-                string_table
-            }
-            Contents64::Symbols(syms) => {
-                let mut bytes = Vec::new();
-                for sym in syms.iter() {
-                    bytes.append(&mut sym.to_le_bytes());
-                }
-                bytes
-            }
-            Contents64::RelaSymbols(rela_syms) => {
-                let mut bytes = Vec::new();
-                for sym in rela_syms.iter() {
-                    bytes.append(&mut sym.to_le_bytes());
-                }
-                bytes
-            }
-            Contents64::Dynamics(dynamics) => {
-                let mut bytes = Vec::new();
-                for sym in dynamics.iter() {
-                    bytes.append(&mut sym.to_le_bytes());
-                }
-                bytes
-            }
-        }
+        self.contents.to_le_bytes()
     }
 }
 
@@ -254,18 +243,18 @@ impl Default for ShdrPreparation64 {
     }
 }
 
-impl Into<Shdr64> for ShdrPreparation64 {
-    fn into(self) -> Shdr64 {
+impl From<ShdrPreparation64> for Shdr64 {
+    fn from(prep: ShdrPreparation64) -> Self {
         Shdr64 {
             sh_name: 0,
-            sh_type: self.sh_type.into(),
-            sh_flags: self.sh_flags,
+            sh_type: prep.sh_type.into(),
+            sh_flags: prep.sh_flags,
             sh_addr: 0,
             sh_offset: 0,
             sh_size: 0,
-            sh_link: self.sh_link,
-            sh_info: self.sh_info,
-            sh_addralign: self.sh_addralign,
+            sh_link: prep.sh_link,
+            sh_info: prep.sh_info,
+            sh_addralign: prep.sh_addralign,
             sh_entsize: 0,
         }
     }
@@ -285,6 +274,50 @@ impl Contents64 {
                 relocation::Rela64::SIZE as usize * rela_syms.len()
             }
             Contents64::Dynamics(dyn_info) => dynamic::Dyn64::SIZE * dyn_info.len(),
+            Contents64::Custom(custom) => custom.bytes().len(),
+        }
+    }
+
+    /// the bytes of the contents in little endian.
+    pub fn to_le_bytes(&self) -> Vec<u8> {
+        match self {
+            Contents64::Raw(bytes) => bytes.clone(),
+            Contents64::StrTab(strs) => {
+                // ELFの文字列テーブルは null-byte + (name + null-byte) * n という形状に
+                // それに合うようにバイト列を構築.
+                let mut string_table: Vec<u8> = vec![0x00];
+
+                for st in strs {
+                    for byte in st.v.as_bytes() {
+                        string_table.push(*byte);
+                    }
+                    string_table.push(0x00);
+                }
+
+                string_table
+            }
+            Contents64::Symbols(syms) => {
+                let mut bytes = Vec::new();
+                for sym in syms.iter() {
+                    bytes.append(&mut sym.to_le_bytes());
+                }
+                bytes
+            }
+            Contents64::RelaSymbols(rela_syms) => {
+                let mut bytes = Vec::new();
+                for sym in rela_syms.iter() {
+                    bytes.append(&mut sym.to_le_bytes());
+                }
+                bytes
+            }
+            Contents64::Dynamics(dynamics) => {
+                let mut bytes = Vec::new();
+                for sym in dynamics.iter() {
+                    bytes.append(&mut sym.to_le_bytes());
+                }
+                bytes
+            }
+            Contents64::Custom(custom) => custom.bytes().to_vec(),
         }
     }
 
@@ -0,0 +1,197 @@
+//! `.gnu.hash` section utilities.
+
+use crate::*;
+
+/// Bloom filter word size in bits (in ELF64)
+const BLOOM_WORD_BITS: u32 = 64;
+
+/// GNU-style hash function used by `.gnu.hash`.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::section::gnu_hash;
+///
+/// assert_eq!(0x00001505, gnu_hash(""));
+/// assert_eq!(0x156b2bb8, gnu_hash("printf"));
+/// ```
+pub fn gnu_hash(name: &str) -> u32 {
+    name.bytes()
+        .fold(5381u32, |h, c| h.wrapping_mul(33).wrapping_add(c as u32))
+}
+
+/// A `.gnu.hash` table (in ELF64)
+///
+/// Symbols after `symoffset` in the dynamic symbol table must be sorted by `bucket_of()`.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::section::GnuHash64;
+///
+/// let names = ["foo", "bar"];
+/// let nbuckets = GnuHash64::nbuckets_for(names.len());
+/// let mut sorted = names.to_vec();
+/// sorted.sort_by_key(|n| GnuHash64::bucket_of(n, nbuckets));
+///
+/// // the null symbol is not hashed.
+/// let table = GnuHash64::build(1, &sorted);
+/// let idx = table.lookup("foo", &sorted).unwrap();
+/// assert_eq!("foo", sorted[idx - 1]);
+/// assert_eq!(None, table.lookup("baz", &sorted));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct GnuHash64 {
+    pub nbuckets: Elf64Word,
+    /// index of the first hashed symbol in the dynamic symbol table
+    pub symoffset: Elf64Word,
+    pub bloom_shift: Elf64Word,
+    pub bloom: Vec<Elf64Xword>,
+    pub buckets: Vec<Elf64Word>,
+    pub chains: Vec<Elf64Word>,
+}
+
+impl GnuHash64 {
+    /// the number of buckets to use for `count` symbols.
+    pub fn nbuckets_for(count: usize) -> Elf64Word {
+        count.max(1) as Elf64Word
+    }
+
+    pub fn bucket_of(name: &str, nbuckets: Elf64Word) -> Elf64Word {
+        gnu_hash(name) % nbuckets
+    }
+
+    /// build a table from the hashed symbol names.
+    /// `names[i]` is the symbol at `symoffset + i` in the dynamic symbol table.
+    pub fn build(symoffset: Elf64Word, names: &[&str]) -> Self {
+        let shape = Self {
+            nbuckets: Self::nbuckets_for(names.len()),
+            symoffset,
+            bloom_shift: 6,
+            bloom: vec![0; (names.len() / 2).max(1).next_power_of_two()],
+            buckets: Vec::new(),
+            chains: Vec::new(),
+        };
+        shape.rehash(names)
+    }
+
+    /// build a table for `names` with the same `nbuckets` and bloom filter size as this.
+    ///
+    /// The table never grows beyond the original one for fewer symbols,
+    /// so it can be rewritten in place after removing symbols.
+    pub fn rehash(&self, names: &[&str]) -> Self {
+        let nbuckets = self.nbuckets.max(1);
+        let bloom_size = self.bloom.len().max(1);
+        let mut bloom = vec![0; bloom_size];
+        let mut buckets = vec![0; nbuckets as usize];
+        let mut chains = Vec::with_capacity(names.len());
+
+        for (i, name) in names.iter().enumerate() {
+            let h = gnu_hash(name);
+            let word = (h / BLOOM_WORD_BITS) as usize % bloom_size;
+            bloom[word] |= 1 << (h % BLOOM_WORD_BITS);
+            bloom[word] |= 1 << ((h >> self.bloom_shift) % BLOOM_WORD_BITS);
+
+            let bucket = (h % nbuckets) as usize;
+            if buckets[bucket] == 0 {
+                buckets[bucket] = self.symoffset + i as Elf64Word;
+            }
+
+            // チェインの最後のシンボルは最下位ビットを立てる
+            let is_last = names
+                .get(i + 1)
+                .is_none_or(|next| Self::bucket_of(next, nbuckets) as usize != bucket);
+            chains.push(if is_last { h | 1 } else { h & !1 });
+        }
+
+        Self {
+            nbuckets,
+            symoffset: self.symoffset,
+            bloom_shift: self.bloom_shift,
+            bloom,
+            buckets,
+            chains,
+        }
+    }
+
+    /// parse a table from the section's contents.
+    pub fn parse(buf: &[u8]) -> Option<Self> {
+        let mut r = section::PayloadReader::new(buf, header::Data::LSB2);
+        let nbuckets = r.u32().ok()?;
+        let symoffset = r.u32().ok()?;
+        let bloom_size = r.u32().ok()?;
+        let bloom_shift = r.u32().ok()?;
+
+        let bloom = (0..bloom_size)
+            .map(|_| r.u64())
+            .collect::<Result<Vec<_>, _>>()
+            .ok()?;
+        let buckets = (0..nbuckets)
+            .map(|_| r.u32())
+            .collect::<Result<Vec<_>, _>>()
+            .ok()?;
+        let chains = (0..r.remaining() / 4)
+            .map(|_| r.u32())
+            .collect::<Result<Vec<_>, _>>()
+            .ok()?;
+
+        Some(Self {
+            nbuckets,
+            symoffset,
+            bloom_shift,
+            bloom,
+            buckets,
+            chains,
+        })
+    }
+
+    /// look up a symbol index by name.
+    /// `names[i]` is the name of the symbol at `symoffset + i`.
+    pub fn lookup(&self, name: &str, names: &[&str]) -> Option<usize> {
+        if self.nbuckets == 0 || self.bloom.is_empty() {
+            return None;
+        }
+        let h = gnu_hash(name);
+        let word = self.bloom[(h / BLOOM_WORD_BITS) as usize % self.bloom.len()];
+        let mask = 1 << (h % BLOOM_WORD_BITS) | 1 << ((h >> self.bloom_shift) % BLOOM_WORD_BITS);
+        if word & mask != mask {
+            return None;
+        }
+
+        let mut idx = *self.buckets.get((h % self.nbuckets) as usize)? as usize;
+        if idx < self.symoffset as usize {
+            return None;
+        }
+        loop {
+            let chain_idx = idx - self.symoffset as usize;
+            let chain = *self.chains.get(chain_idx)?;
+            if chain | 1 == h | 1 && names.get(chain_idx) == Some(&name) {
+                return Some(idx);
+            }
+            if chain & 1 != 0 {
+                return None;
+            }
+            idx += 1;
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        16 + self.bloom.len() * 8 + (self.buckets.len() + self.chains.len()) * 4
+    }
+
+    /// Create Vec<u8> from this.
+    pub fn to_le_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.size());
+        bytes.extend_from_slice(&self.nbuckets.to_le_bytes());
+        bytes.extend_from_slice(&self.symoffset.to_le_bytes());
+        bytes.extend_from_slice(&(self.bloom.len() as Elf64Word).to_le_bytes());
+        bytes.extend_from_slice(&self.bloom_shift.to_le_bytes());
+        for word in self.bloom.iter() {
+            bytes.extend_from_slice(&word.to_le_bytes());
+        }
+        for v in self.buckets.iter().chain(self.chains.iter()) {
+            bytes.extend_from_slice(&v.to_le_bytes());
+        }
+        bytes
+    }
+}
@@ -0,0 +1,155 @@
+//! `.note.gnu.property` section utilities.
+
+use crate::*;
+use section::{PayloadReader, ReadError};
+
+/// the note type of the program properties
+pub const NT_GNU_PROPERTY_TYPE_0: Elf64Word = 5;
+/// the `AND` of the AArch64 features of all input objects
+pub const GNU_PROPERTY_AARCH64_FEATURE_1_AND: Elf64Word = 0xc0000000;
+/// Branch Target Identification
+pub const GNU_PROPERTY_AARCH64_FEATURE_1_BTI: Elf64Word = 1 << 0;
+/// Pointer Authentication
+pub const GNU_PROPERTY_AARCH64_FEATURE_1_PAC: Elf64Word = 1 << 1;
+/// the `AND` of the x86 features of all input objects
+pub const GNU_PROPERTY_X86_FEATURE_1_AND: Elf64Word = 0xc0000002;
+/// Indirect Branch Tracking
+pub const GNU_PROPERTY_X86_FEATURE_1_IBT: Elf64Word = 1 << 0;
+/// Shadow Stack
+pub const GNU_PROPERTY_X86_FEATURE_1_SHSTK: Elf64Word = 1 << 1;
+/// the ISA levels which the code requires, the `OR` of all input objects
+pub const GNU_PROPERTY_X86_ISA_1_NEEDED: Elf64Word = 0xc0008002;
+/// the ISA levels which the code uses, the `OR` of all input objects(or 0 if any lacks it)
+pub const GNU_PROPERTY_X86_ISA_1_USED: Elf64Word = 0xc0010002;
+/// CMOV, CX8, FPU, FXSR, MMX, OSFXSR, SCE, SSE and SSE2
+pub const GNU_PROPERTY_X86_ISA_1_BASELINE: Elf64Word = 1 << 0;
+/// CMPXCHG16B, LAHF/SAHF, POPCNT, SSE3, SSSE3, SSE4.1 and SSE4.2
+pub const GNU_PROPERTY_X86_ISA_1_V2: Elf64Word = 1 << 1;
+/// AVX, AVX2, BMI1, BMI2, F16C, FMA, LZCNT, MOVBE and XSAVE
+pub const GNU_PROPERTY_X86_ISA_1_V3: Elf64Word = 1 << 2;
+/// AVX512F, AVX512BW, AVX512CD, AVX512DQ and AVX512VL
+pub const GNU_PROPERTY_X86_ISA_1_V4: Elf64Word = 1 << 3;
+
+/// the alignment of the property notes in ELF64
+const PROPERTY_ALIGN: usize = 8;
+
+/// A program property in `NT_GNU_PROPERTY_TYPE_0` notes
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct GnuProperty {
+    pub pr_type: Elf64Word,
+    pub pr_data: Vec<u8>,
+    /// the offset of `pr_data` in the section, for patching it in place
+    pub offset: usize,
+}
+
+/// The program properties in `.note.gnu.property`(in ELF64)
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::{header, section};
+///
+/// let mut note = vec![4, 0, 0, 0, 0x10, 0, 0, 0, 5, 0, 0, 0];
+/// note.extend_from_slice(b"GNU\0");
+/// // GNU_PROPERTY_AARCH64_FEATURE_1_AND: BTI | PAC
+/// note.extend_from_slice(&[0x00, 0x00, 0x00, 0xc0, 4, 0, 0, 0, 3, 0, 0, 0, 0, 0, 0, 0]);
+///
+/// let props = section::GnuProperties::parse(&note, header::Data::LSB2).unwrap();
+/// let features = props.feature_1_and(section::GNU_PROPERTY_AARCH64_FEATURE_1_AND);
+/// assert_eq!(Some(3), features);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct GnuProperties {
+    pub properties: Vec<GnuProperty>,
+    /// the data encoding of `pr_data`
+    pub data: header::Data,
+}
+
+impl Default for GnuProperties {
+    fn default() -> Self {
+        Self {
+            properties: Vec::new(),
+            data: header::Data::LSB2,
+        }
+    }
+}
+
+impl GnuProperties {
+    /// parse the properties in the notes. the notes of the other types are skipped.
+    pub fn parse(bytes: &[u8], data: header::Data) -> Result<Self, ReadError> {
+        let mut r = PayloadReader::new(bytes, data);
+        let mut properties = Vec::new();
+        while !r.is_empty() {
+            let namesz = r.u32()? as usize;
+            let descsz = r.u32()? as usize;
+            let ty = r.u32()?;
+            let name = r.bytes(namesz)?;
+            r.align(4)?;
+            let desc_start = r.position();
+            let desc_end = desc_start + descsz;
+            if ty != NT_GNU_PROPERTY_TYPE_0 || name != b"GNU\0" {
+                r.skip(descsz)?;
+                r.align(PROPERTY_ALIGN)?;
+                continue;
+            }
+
+            while r.position() < desc_end {
+                let pr_type = r.u32()?;
+                let datasz = r.u32()? as usize;
+                let offset = r.position();
+                let pr_data = r.bytes(datasz)?;
+                r.align(PROPERTY_ALIGN)?;
+                properties.push(GnuProperty {
+                    pr_type,
+                    pr_data,
+                    offset,
+                });
+            }
+            if r.position() != desc_end {
+                return Err(ReadError::UnexpectedEnd {
+                    offset: desc_end,
+                    needed: r.position() - desc_end,
+                });
+            }
+        }
+        Ok(Self { properties, data })
+    }
+
+    pub fn get(&self, pr_type: Elf64Word) -> Option<&GnuProperty> {
+        self.properties.iter().find(|p| p.pr_type == pr_type)
+    }
+
+    /// the value of a 4-byte property.
+    pub fn u32(&self, pr_type: Elf64Word) -> Option<Elf64Word> {
+        let property = self.get(pr_type)?;
+        PayloadReader::new(&property.pr_data, self.data).u32().ok()
+    }
+
+    /// the bits of a `*_FEATURE_1_AND` property.
+    pub fn feature_1_and(&self, pr_type: Elf64Word) -> Option<Elf64Word> {
+        self.u32(pr_type)
+    }
+
+    /// the bits of `GNU_PROPERTY_X86_ISA_1_NEEDED`.
+    pub fn x86_isa_needed(&self) -> Option<Elf64Word> {
+        self.u32(GNU_PROPERTY_X86_ISA_1_NEEDED)
+    }
+
+    /// the bits of `GNU_PROPERTY_X86_ISA_1_USED`.
+    pub fn x86_isa_used(&self) -> Option<Elf64Word> {
+        self.u32(GNU_PROPERTY_X86_ISA_1_USED)
+    }
+}
+
+impl file::ELF64 {
+    /// parse `.note.gnu.property` if exists.
+    pub fn gnu_properties(&self) -> Option<Result<GnuProperties, ReadError>> {
+        let sct = self.first_section_by(|sct| {
+            sct.header.get_type() == section::Type::Note && sct.name == ".note.gnu.property"
+        })?;
+        Some(GnuProperties::parse(
+            &sct.to_le_bytes(),
+            self.ehdr.get_data(),
+        ))
+    }
+}
@@ -0,0 +1,276 @@
+//! GNU symbol versioning sections(`.gnu.version_d` and `.gnu.version_r`).
+
+use crate::*;
+use section::{PayloadReader, ReadError};
+
+/// the version definitions
+pub const SHT_GNU_VERDEF: Elf64Word = 0x6ffffffd;
+/// the version requirements
+pub const SHT_GNU_VERNEED: Elf64Word = 0x6ffffffe;
+/// the version of each dynamic symbol
+pub const SHT_GNU_VERSYM: Elf64Word = 0x6fffffff;
+/// the version definition of the file itself
+pub const VER_FLG_BASE: u16 = 0x1;
+/// the version requirement is weak, so a missing version isn't fatal
+pub const VER_FLG_WEAK: u16 = 0x2;
+/// the symbol is local
+pub const VER_NDX_LOCAL: u16 = 0;
+/// the symbol is global and unversioned
+pub const VER_NDX_GLOBAL: u16 = 1;
+/// the symbol isn't the default version(`sym@VER`, not `sym@@VER`)
+pub const VERSYM_HIDDEN: u16 = 0x8000;
+
+/// A version definition in `.gnu.version_d`(`Elf64_Verdef`)
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct VersionDefinition {
+    pub flags: u16,
+    /// the index referred from `.gnu.version`
+    pub index: u16,
+    pub hash: Elf64Word,
+    pub name: String,
+    /// the versions which this version inherits
+    pub parents: Vec<String>,
+}
+
+impl VersionDefinition {
+    pub fn is_base(&self) -> bool {
+        self.flags & VER_FLG_BASE != 0
+    }
+}
+
+/// A version required from a file(`Elf64_Vernaux`)
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct VersionNeedAux {
+    pub hash: Elf64Word,
+    pub flags: u16,
+    /// the index referred from `.gnu.version`
+    pub other: u16,
+    pub name: String,
+}
+
+impl VersionNeedAux {
+    pub fn is_weak(&self) -> bool {
+        self.flags & VER_FLG_WEAK != 0
+    }
+}
+
+/// The versions required from a `DT_NEEDED` file in `.gnu.version_r`(`Elf64_Verneed`)
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct VersionNeed {
+    pub file: String,
+    pub versions: Vec<VersionNeedAux>,
+}
+
+/// The version of a dynamic symbol in `.gnu.version`
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SymbolVersion {
+    pub name: String,
+    /// `VERSYM_HIDDEN` is set
+    pub hidden: bool,
+}
+
+/// parse `.gnu.version_d`. `count` is `sh_info` of the section,
+/// and the names are in `strtab`, the section of `sh_link`.
+pub fn parse_verdef(
+    bytes: &[u8],
+    count: Elf64Word,
+    strtab: &[u8],
+    data: header::Data,
+) -> Result<Vec<VersionDefinition>, ReadError> {
+    let mut r = PayloadReader::new(bytes, data);
+    let mut defs = Vec::new();
+    let mut vd = 0;
+    for _ in 0..count {
+        // Elf64_Verdef: vd_version, vd_flags, vd_ndx, vd_cnt, vd_hash, vd_aux, vd_next
+        r.seek(vd)?;
+        let _version = r.u16()?;
+        let flags = r.u16()?;
+        let index = r.u16()?;
+        let cnt = r.u16()?;
+        let hash = r.u32()?;
+        let aux = r.u32()? as usize;
+        let next = r.u32()? as usize;
+
+        // Elf64_Verdaux: vda_name, vda_next
+        let mut names = Vec::new();
+        let mut vda = vd + aux;
+        for _ in 0..cnt {
+            r.seek(vda)?;
+            let name = r.u32()?;
+            let next = r.u32()? as usize;
+            names.push(string_at(strtab, name, data)?);
+            if next == 0 {
+                break;
+            }
+            vda += next;
+        }
+        let mut names = names.into_iter();
+        defs.push(VersionDefinition {
+            flags,
+            index,
+            hash,
+            name: names.next().unwrap_or_default(),
+            parents: names.collect(),
+        });
+        if next == 0 {
+            break;
+        }
+        vd += next;
+    }
+    Ok(defs)
+}
+
+/// parse `.gnu.version_r`. `count` is `sh_info` of the section,
+/// and the names are in `strtab`, the section of `sh_link`.
+pub fn parse_verneed(
+    bytes: &[u8],
+    count: Elf64Word,
+    strtab: &[u8],
+    data: header::Data,
+) -> Result<Vec<VersionNeed>, ReadError> {
+    let mut r = PayloadReader::new(bytes, data);
+    let mut needs = Vec::new();
+    let mut vn = 0;
+    for _ in 0..count {
+        // Elf64_Verneed: vn_version, vn_cnt, vn_file, vn_aux, vn_next
+        r.seek(vn)?;
+        let _version = r.u16()?;
+        let cnt = r.u16()?;
+        let file = r.u32()?;
+        let aux = r.u32()? as usize;
+        let next = r.u32()? as usize;
+
+        // Elf64_Vernaux: vna_hash, vna_flags, vna_other, vna_name, vna_next
+        let mut versions = Vec::new();
+        let mut vna = vn + aux;
+        for _ in 0..cnt {
+            r.seek(vna)?;
+            let hash = r.u32()?;
+            let flags = r.u16()?;
+            let other = r.u16()?;
+            let name = r.u32()?;
+            let next = r.u32()? as usize;
+            versions.push(VersionNeedAux {
+                hash,
+                flags,
+                other,
+                name: string_at(strtab, name, data)?,
+            });
+            if next == 0 {
+                break;
+            }
+            vna += next;
+        }
+        needs.push(VersionNeed {
+            file: string_at(strtab, file, data)?,
+            versions,
+        });
+        if next == 0 {
+            break;
+        }
+        vn += next;
+    }
+    Ok(needs)
+}
+
+fn string_at(strtab: &[u8], offset: Elf64Word, data: header::Data) -> Result<String, ReadError> {
+    let mut r = PayloadReader::new(strtab, data);
+    r.seek(offset as usize)?;
+    r.cstr()
+}
+
+impl file::ELF64 {
+    /// the version definitions in `.gnu.version_d`, or empty if the file has none.
+    pub fn version_definitions(&self) -> Result<Vec<VersionDefinition>, ReadError> {
+        match self.version_section(SHT_GNU_VERDEF) {
+            Some((bytes, count, strtab)) => {
+                parse_verdef(&bytes, count, &strtab, self.ehdr.get_data())
+            }
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// the version requirements in `.gnu.version_r`, or empty if the file has none.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use elf_utilities::parser;
+    ///
+    /// let elf = parser::parse_elf64("src/parser/testdata/sample").unwrap();
+    /// let needs = elf.version_needs().unwrap();
+    ///
+    /// assert_eq!("libc.so.6", needs[0].file);
+    /// assert!(needs[0].versions.iter().any(|v| v.name == "GLIBC_2.2.5"));
+    /// ```
+    pub fn version_needs(&self) -> Result<Vec<VersionNeed>, ReadError> {
+        match self.version_section(SHT_GNU_VERNEED) {
+            Some((bytes, count, strtab)) => {
+                parse_verneed(&bytes, count, &strtab, self.ehdr.get_data())
+            }
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// the version of each symbol in `.dynsym`, or empty if the file has no `.gnu.version`.
+    /// `None` for the unversioned symbols.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use elf_utilities::{parser, section};
+    ///
+    /// let elf = parser::parse_elf64("src/parser/testdata/sample").unwrap();
+    /// let versions = elf.symbol_versions().unwrap();
+    /// let dynsym = elf.first_section_by(|sct| sct.header.get_type() == section::Type::DynSym).unwrap();
+    /// let start = match &dynsym.contents {
+    ///     section::Contents64::Symbols(syms) => syms.iter().position(|sym| sym.symbol_name == "__libc_start_main").unwrap(),
+    ///     _ => unreachable!(),
+    /// };
+    ///
+    /// assert_eq!("GLIBC_2.2.5", versions[start].as_ref().unwrap().name);
+    /// ```
+    pub fn symbol_versions(&self) -> Result<Vec<Option<SymbolVersion>>, ReadError> {
+        let versym = match self
+            .first_section_by(|sct| sct.header.get_type() == section::Type::Any(SHT_GNU_VERSYM))
+        {
+            Some(sct) => sct.to_le_bytes(),
+            None => return Ok(Vec::new()),
+        };
+        let mut names = std::collections::HashMap::new();
+        for def in self.version_definitions()? {
+            names.insert(def.index, def.name);
+        }
+        for need in self.version_needs()? {
+            for aux in need.versions {
+                names.insert(aux.other, aux.name);
+            }
+        }
+
+        let mut r = PayloadReader::new(&versym, self.ehdr.get_data());
+        let mut versions = Vec::with_capacity(versym.len() / 2);
+        while r.remaining() >= 2 {
+            let v = r.u16()?;
+            let index = v & !VERSYM_HIDDEN;
+            versions.push(match index {
+                VER_NDX_LOCAL | VER_NDX_GLOBAL => None,
+                _ => names.get(&index).map(|name| SymbolVersion {
+                    name: name.clone(),
+                    hidden: v & VERSYM_HIDDEN != 0,
+                }),
+            });
+        }
+        Ok(versions)
+    }
+
+    /// the contents, `sh_info` and the linked string table of the first section of the type.
+    fn version_section(&self, ty: Elf64Word) -> Option<(Vec<u8>, Elf64Word, Vec<u8>)> {
+        let sct = self.first_section_by(|sct| sct.header.get_type() == section::Type::Any(ty))?;
+        let strtab = self
+            .sections
+            .get(sct.header.sh_link as usize)
+            .map(|s| s.to_le_bytes())
+            .unwrap_or_default();
+        Some((sct.to_le_bytes(), sct.header.sh_info, strtab))
+    }
+}
@@ -0,0 +1,251 @@
+//! Inserting bytes into the contents of sections.
+
+use crate::*;
+use thiserror::Error as TError;
+
+#[derive(TError, Debug, Clone, PartialEq, Eq)]
+pub enum InsertError {
+    #[error("offset {offset:#x} is out of the section of {size:#x} bytes")]
+    OutOfRange { offset: usize, size: usize },
+    #[error("the section has no raw contents")]
+    NotRaw,
+    #[error("bytes can be inserted only into relocatable files")]
+    NotRelocatable,
+    #[error("section index {shidx} is out of range")]
+    InvalidSection { shidx: usize },
+}
+
+impl section::Section64 {
+    /// insert the bytes at `offset` of the contents, shifting the rest back.
+    /// `sh_size` is updated, but nothing referring the contents is.
+    /// Use `ELF64::insert_bytes()` to move the relocations and symbols too.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use elf_utilities::section;
+    ///
+    /// let mut text = section::Section64::new(
+    ///     ".text".to_string(),
+    ///     section::ShdrPreparation64::default().ty(section::Type::ProgBits),
+    ///     section::Contents64::Raw(vec![0xc3]),
+    /// );
+    /// text.insert_bytes(0, &[0x90, 0x90]).unwrap();
+    /// assert_eq!(vec![0x90, 0x90, 0xc3], text.to_le_bytes());
+    /// assert_eq!(3, text.header.sh_size);
+    /// assert!(text.insert_bytes(4, &[0x90]).is_err());
+    /// ```
+    pub fn insert_bytes(&mut self, offset: usize, bytes: &[u8]) -> Result<(), InsertError> {
+        let contents = match &mut self.contents {
+            section::Contents64::Raw(contents) => contents,
+            _ => return Err(InsertError::NotRaw),
+        };
+        if offset > contents.len() {
+            return Err(InsertError::OutOfRange {
+                offset,
+                size: contents.len(),
+            });
+        }
+        contents.splice(offset..offset, bytes.iter().copied());
+        self.header.sh_size = contents.len() as Elf64Xword;
+        Ok(())
+    }
+}
+
+impl file::ELF64 {
+    /// insert the bytes at `offset` of the section `shidx`, and move everything referring the contents behind it.
+    ///
+    /// - the relocations applied to the section at or after `offset` get the new `r_offset`
+    /// - the symbols defined in the section at or after `offset` get the new `st_value`,
+    ///   and the ones spanning `offset` grow by the inserted size
+    /// - the relocations against the section symbol whose target(`r_addend`, plus 4 for `R_X86_64_PC32`/`R_X86_64_PLT32`)
+    ///   is at or after `offset` get the new `r_addend`
+    ///
+    /// Thus the inserted bytes are placed before what was at `offset`, and belong to a symbol only if it spans `offset`.
+    /// The file is conditioned by `ELF64::condition()` after inserting.
+    /// Only relocatable files are supported, since linked files have code referring the addresses directly.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use elf_utilities::{file, header, section, symbol};
+    ///
+    /// let mut elf = file::ELF64::default();
+    /// elf.ehdr.set_elf_type(header::Type::Rel);
+    /// elf.add_section(section::Section64::new(
+    ///     ".text".to_string(),
+    ///     section::ShdrPreparation64::default()
+    ///         .ty(section::Type::ProgBits)
+    ///         .flags([section::Flag::Alloc, section::Flag::ExecInstr].iter()),
+    ///     section::Contents64::Raw(vec![0xc3, 0xc3]),
+    /// ));
+    /// let mut g = symbol::Symbol64 {
+    ///     symbol_name: "g".into(),
+    ///     st_shndx: 1,
+    ///     st_value: 1,
+    ///     st_size: 1,
+    ///     ..Default::default()
+    /// };
+    /// g.set_info(symbol::Type::Func, symbol::Bind::Global);
+    /// elf.add_section(section::Section64::new(
+    ///     ".symtab".to_string(),
+    ///     section::ShdrPreparation64::default().ty(section::Type::SymTab).info(1),
+    ///     section::Contents64::Symbols(vec![symbol::Symbol64::new_null_symbol(), g]),
+    /// ));
+    ///
+    /// // endbr64 at the start of g
+    /// elf.insert_bytes(1, 1, &[0xf3, 0x0f, 0x1e, 0xfa]).unwrap();
+    /// if let section::Contents64::Symbols(syms) = &elf.sections[2].contents {
+    ///     assert_eq!(5, syms[1].st_value);
+    /// }
+    /// ```
+    pub fn insert_bytes(
+        &mut self,
+        shidx: usize,
+        offset: usize,
+        bytes: &[u8],
+    ) -> Result<(), InsertError> {
+        if self.ehdr.get_type() != header::Type::Rel {
+            return Err(InsertError::NotRelocatable);
+        }
+        self.sections
+            .get_mut(shidx)
+            .ok_or(InsertError::InvalidSection { shidx })?
+            .insert_bytes(offset, bytes)?;
+        self.invalidate_address_index();
+        let offset = offset as Elf64Addr;
+        let len = bytes.len() as Elf64Addr;
+
+        // 各シンボルテーブルで，このセクションのセクションシンボルを集める
+        let mut section_syms: Vec<(usize, usize)> = Vec::new();
+        for (tab_idx, sct) in self.sections.iter_mut().enumerate() {
+            let syms = match &mut sct.contents {
+                section::Contents64::Symbols(syms) => syms,
+                _ => continue,
+            };
+            for (sym_idx, sym) in syms.iter_mut().enumerate() {
+                if sym.st_shndx as usize != shidx || sym_idx == 0 {
+                    continue;
+                }
+                if sym.get_type() == symbol::Type::Section {
+                    section_syms.push((tab_idx, sym_idx));
+                } else if sym.st_value >= offset {
+                    sym.st_value += len;
+                } else if sym.st_value + sym.st_size > offset {
+                    sym.st_size += len;
+                }
+            }
+        }
+
+        for sct in self.sections.iter_mut() {
+            let applies_here = sct.header.sh_info as usize == shidx;
+            let symtab = sct.header.sh_link as usize;
+            let relas = match &mut sct.contents {
+                section::Contents64::RelaSymbols(relas) => relas,
+                _ => continue,
+            };
+            for rela in relas.iter_mut() {
+                if applies_here && rela.get_offset() >= offset {
+                    rela.set_offset(rela.get_offset() + len);
+                }
+                if !section_syms.contains(&(symtab, rela.get_sym() as usize)) {
+                    continue;
+                }
+                let field = match rela.get_type() {
+                    relocation::R_X86_64_PC32 | relocation::R_X86_64_PLT32 => 4,
+                    _ => 0,
+                };
+                if rela.get_addend() + field >= offset as Elf64Sxword {
+                    rela.set_addend(rela.get_addend() + len as Elf64Sxword);
+                }
+            }
+        }
+
+        self.condition();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod insert_tests {
+    use super::*;
+
+    #[test]
+    fn insert_bytes_test() {
+        let mut elf = file::ELF64::default();
+        elf.ehdr.set_elf_type(header::Type::Rel);
+        elf.add_section(section::Section64::new(
+            ".text".to_string(),
+            section::ShdrPreparation64::default()
+                .ty(section::Type::ProgBits)
+                .flags([section::Flag::Alloc, section::Flag::ExecInstr].iter()),
+            section::Contents64::Raw(vec![0x90; 8]),
+        ));
+        let mut syms = vec![symbol::Symbol64::new_null_symbol()];
+        let mut section_sym = symbol::Symbol64 {
+            st_shndx: 1,
+            ..Default::default()
+        };
+        section_sym.set_info(symbol::Type::Section, symbol::Bind::Local);
+        syms.push(section_sym);
+        for (name, value) in [("f", 0), ("g", 4)].iter() {
+            let mut sym = symbol::Symbol64 {
+                symbol_name: (*name).into(),
+                st_shndx: 1,
+                st_value: *value,
+                st_size: 4,
+                ..Default::default()
+            };
+            sym.set_info(symbol::Type::Func, symbol::Bind::Global);
+            syms.push(sym);
+        }
+        elf.add_section(section::Section64::new(
+            ".symtab".to_string(),
+            section::ShdrPreparation64::default()
+                .ty(section::Type::SymTab)
+                .info(2),
+            section::Contents64::Symbols(syms),
+        ));
+        // .text+4(g) への絶対アドレスと，f への相対アドレス
+        let mut to_g = relocation::Rela64::default();
+        to_g.set_offset(1);
+        to_g.set_info(1 << 32 | relocation::R_X86_64_64);
+        to_g.set_addend(4);
+        let mut to_f = relocation::Rela64::default();
+        to_f.set_offset(5);
+        to_f.set_info(2 << 32 | relocation::R_X86_64_PC32);
+        to_f.set_addend(-4);
+        elf.add_section(section::Section64::new(
+            ".rela.text".to_string(),
+            section::ShdrPreparation64::default()
+                .ty(section::Type::Rela)
+                .link(2)
+                .info(1),
+            section::Contents64::RelaSymbols(vec![to_g, to_f]),
+        ));
+
+        // f と g の間，f の途中
+        elf.insert_bytes(1, 4, &[0xcc; 2]).unwrap();
+        elf.insert_bytes(1, 2, &[0xcc]).unwrap();
+
+        assert_eq!(11, elf.sections[1].header.sh_size);
+        if let section::Contents64::Symbols(syms) = &elf.sections[2].contents {
+            assert_eq!((0, 5), (syms[2].st_value, syms[2].st_size));
+            assert_eq!((7, 4), (syms[3].st_value, syms[3].st_size));
+        }
+        if let section::Contents64::RelaSymbols(relas) = &elf.sections[3].contents {
+            assert_eq!((1, 7), (relas[0].get_offset(), relas[0].get_addend()));
+            assert_eq!((8, -4), (relas[1].get_offset(), relas[1].get_addend()));
+        }
+        assert!(elf.validate().is_ok());
+
+        assert_eq!(
+            Err(InsertError::OutOfRange {
+                offset: 12,
+                size: 11
+            }),
+            elf.insert_bytes(1, 12, &[0])
+        );
+        assert_eq!(Err(InsertError::NotRaw), elf.insert_bytes(2, 0, &[0]));
+    }
+}
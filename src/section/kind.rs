@@ -0,0 +1,222 @@
+//! Semantic kinds of sections, independent of their names.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::*;
+use section::{SHT_GNU_VERDEF, SHT_GNU_VERNEED, SHT_GNU_VERSYM};
+
+/// `SHT_GNU_HASH`
+const SHT_GNU_HASH: Elf64Word = 0x6ffffff6;
+
+/// The input section names which the default linker scripts of GNU ld merge into an output section,
+/// as `(prefix, output section)`. The longer prefixes come first.
+pub const SECTION_ALIASES: [(&str, &str); 24] = [
+    (".text.", ".text"),
+    (".gnu.linkonce.t.", ".text"),
+    (".rodata.", ".rodata"),
+    (".gnu.linkonce.r.", ".rodata"),
+    (".data.rel.ro.", ".data.rel.ro"),
+    (".gnu.linkonce.d.rel.ro.", ".data.rel.ro"),
+    (".data.", ".data"),
+    (".gnu.linkonce.d.", ".data"),
+    (".bss.", ".bss"),
+    (".gnu.linkonce.b.", ".bss"),
+    (".tdata.", ".tdata"),
+    (".gnu.linkonce.td.", ".tdata"),
+    (".tbss.", ".tbss"),
+    (".gnu.linkonce.tb.", ".tbss"),
+    (".init_array.", ".init_array"),
+    (".fini_array.", ".fini_array"),
+    (".ctors.", ".ctors"),
+    (".dtors.", ".dtors"),
+    (".sdata.", ".sdata"),
+    (".sbss.", ".sbss"),
+    (".lrodata.", ".lrodata"),
+    (".ldata.", ".ldata"),
+    (".lbss.", ".lbss"),
+    (".gcc_except_table.", ".gcc_except_table"),
+];
+
+/// The semantic kind of a section, returned by `canonical_kind()`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum SectionKind {
+    Null,
+    /// executable code, including `.plt` and `.init`
+    Text,
+    /// read-only data
+    RoData,
+    /// writable data, including `.data.rel.ro`, `.got` and `.init_array`
+    Data,
+    /// zero-initialized data
+    Bss,
+    /// initialized thread-local data(`.tdata`)
+    Tls,
+    /// zero-initialized thread-local data(`.tbss`)
+    TlsBss,
+    /// the unwind tables(`.eh_frame` and `.eh_frame_hdr`)
+    EhFrame,
+    /// `SHT_NOTE`
+    Note,
+    /// the DWARF sections and the other debugging information
+    DebugInfo,
+    /// `.symtab` and `.dynsym`
+    SymbolTable,
+    /// `.strtab`, `.dynstr` and `.shstrtab`
+    StringTable,
+    /// `SHT_RELA` and `SHT_REL`
+    Relocation,
+    /// `.dynamic` and the tables read by the dynamic linker(`.hash`, `.gnu.version`, etc.)
+    Dynamic,
+    /// the other sections which aren't loaded, like `.comment`
+    Other,
+}
+
+impl fmt::Display for SectionKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Self::Null => "null",
+            Self::Text => "text",
+            Self::RoData => "rodata",
+            Self::Data => "data",
+            Self::Bss => "bss",
+            Self::Tls => "tls",
+            Self::TlsBss => "tbss",
+            Self::EhFrame => "eh_frame",
+            Self::Note => "note",
+            Self::DebugInfo => "debug",
+            Self::SymbolTable => "symtab",
+            Self::StringTable => "strtab",
+            Self::Relocation => "relocation",
+            Self::Dynamic => "dynamic",
+            Self::Other => "other",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// the output section which the default linker script of GNU ld puts the input section into.
+///
+/// `.zdebug_*` is the compressed `.debug_*`.
+/// The names without aliases in `SECTION_ALIASES` are returned as is.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::section;
+///
+/// assert_eq!(".text", section::canonical_name(".text.unlikely.foo"));
+/// assert_eq!(".data.rel.ro", section::canonical_name(".data.rel.ro.local"));
+/// assert_eq!(".debug_info", section::canonical_name(".zdebug_info"));
+/// assert_eq!(".comment", section::canonical_name(".comment"));
+/// ```
+pub fn canonical_name(name: &str) -> std::borrow::Cow<'_, str> {
+    if let Some(rest) = name.strip_prefix(".zdebug") {
+        return format!(".debug{}", rest).into();
+    }
+    SECTION_ALIASES
+        .iter()
+        .find(|(prefix, _)| name.starts_with(prefix))
+        .map_or(name, |(_, canonical)| canonical)
+        .into()
+}
+
+/// classify a section by its type and flags, using the name only where they can't tell.
+///
+/// The name decides the debugging information(`.debug_*`, `.zdebug_*`, `.stab*`, etc.)
+/// and the unwind tables, which are `SHT_PROGBITS` like the others.
+/// So `.text.hot` is `Text` and `.data.rel.ro` is `Data` by their flags.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::section;
+///
+/// let flags = |fs: &[section::Flag]| fs.iter().fold(0, |acc, f| acc | u64::from(*f));
+///
+/// let text = flags(&[section::Flag::Alloc, section::Flag::ExecInstr]);
+/// assert_eq!(
+///     section::SectionKind::Text,
+///     section::canonical_kind(".text.hot", section::Type::ProgBits, text)
+/// );
+/// let relro = flags(&[section::Flag::Alloc, section::Flag::Write]);
+/// assert_eq!(
+///     section::SectionKind::Data,
+///     section::canonical_kind(".data.rel.ro", section::Type::ProgBits, relro)
+/// );
+/// assert_eq!(
+///     section::SectionKind::DebugInfo,
+///     section::canonical_kind(".zdebug_line", section::Type::ProgBits, 0)
+/// );
+/// ```
+pub fn canonical_kind(name: &str, ty: section::Type, flags: Elf64Xword) -> SectionKind {
+    let has = |flag: section::Flag| flags & Elf64Xword::from(flag) != 0;
+    match ty {
+        section::Type::Null => return SectionKind::Null,
+        section::Type::SymTab | section::Type::DynSym => return SectionKind::SymbolTable,
+        section::Type::StrTab => return SectionKind::StringTable,
+        section::Type::Rela | section::Type::Rel => return SectionKind::Relocation,
+        section::Type::Dynamic | section::Type::Hash => return SectionKind::Dynamic,
+        section::Type::Note => return SectionKind::Note,
+        section::Type::InitArray | section::Type::FiniArray | section::Type::PreInitArray => {
+            return SectionKind::Data
+        }
+        section::Type::NoBits if has(section::Flag::TLS) => return SectionKind::TlsBss,
+        section::Type::NoBits if has(section::Flag::Alloc) => return SectionKind::Bss,
+        section::Type::Any(SHT_GNU_HASH)
+        | section::Type::Any(SHT_GNU_VERDEF)
+        | section::Type::Any(SHT_GNU_VERNEED)
+        | section::Type::Any(SHT_GNU_VERSYM) => return SectionKind::Dynamic,
+        _ => {}
+    }
+
+    let canonical = canonical_name(name);
+    if canonical.starts_with(".debug") || name.starts_with(".stab") || name == ".gdb_index" {
+        return SectionKind::DebugInfo;
+    }
+    if canonical == ".eh_frame" || canonical == ".eh_frame_hdr" {
+        return SectionKind::EhFrame;
+    }
+    if !has(section::Flag::Alloc) {
+        return SectionKind::Other;
+    }
+    if has(section::Flag::TLS) {
+        SectionKind::Tls
+    } else if has(section::Flag::ExecInstr) {
+        SectionKind::Text
+    } else if has(section::Flag::Write) {
+        SectionKind::Data
+    } else {
+        SectionKind::RoData
+    }
+}
+
+impl section::Section64 {
+    /// `canonical_kind()` of the section.
+    pub fn kind(&self) -> SectionKind {
+        canonical_kind(&self.name, self.header.get_type(), self.header.sh_flags)
+    }
+}
+
+#[cfg(test)]
+mod kind_tests {
+    use super::*;
+
+    #[test]
+    fn sample_kinds_test() {
+        let elf = parser::parse_elf64("src/parser/testdata/sample").unwrap();
+        let kind = |name: &str| elf.first_section_by(|sct| sct.name == name).unwrap().kind();
+        assert_eq!(SectionKind::Text, kind(".plt"));
+        assert_eq!(SectionKind::RoData, kind(".rodata"));
+        assert_eq!(SectionKind::Data, kind(".init_array"));
+        assert_eq!(SectionKind::Data, kind(".got"));
+        assert_eq!(SectionKind::Bss, kind(".bss"));
+        assert_eq!(SectionKind::EhFrame, kind(".eh_frame_hdr"));
+        assert_eq!(SectionKind::Dynamic, kind(".gnu.hash"));
+        assert_eq!(SectionKind::Dynamic, kind(".gnu.version_r"));
+        assert_eq!(SectionKind::StringTable, kind(".dynstr"));
+        assert_eq!(SectionKind::Note, kind(".note.ABI-tag"));
+        assert_eq!(SectionKind::Other, kind(".comment"));
+    }
+}
@@ -0,0 +1,288 @@
+//! The sections emitted by LLVM for `lld`.
+//!
+//! They are excluded from linked files(`SHF_EXCLUDE`), but must survive edits on relocatable files.
+
+use crate::*;
+use section::{PayloadReader, ReadError};
+
+/// `SHT_LLVM_ADDRSIG`, the symbols whose addresses are significant(for `--icf=safe`)
+pub const SHT_LLVM_ADDRSIG: Elf64Word = 0x6fff4c03;
+/// `SHT_LLVM_CALL_GRAPH_PROFILE`, the weights of the call edges(for `--call-graph-profile-sort`)
+pub const SHT_LLVM_CALL_GRAPH_PROFILE: Elf64Word = 0x6fff4c09;
+/// `SHT_LLVM_OFFLOADING`, the device images embedded by `-fembed-offload-object`
+pub const SHT_LLVM_OFFLOADING: Elf64Word = 0x6fff4c0b;
+
+/// the section embedding another object file, e.g. the bitcode of `-fembed-bitcode` or an offloading image
+pub const LLVM_EMBEDDED_OBJECT: &str = ".llvm.embedded.object";
+
+/// the relocations which give the symbols of `.llvm.call-graph-profile`
+const CALL_GRAPH_PROFILE_REL: &str = ".rel.llvm.call-graph-profile";
+
+/// The LLVM-specific sections
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LlvmSection {
+    Addrsig,
+    CallGraphProfile,
+    EmbeddedObject,
+}
+
+impl LlvmSection {
+    /// recognize the section by its type(or name for `.llvm.embedded.object`, which may be `SHT_PROGBITS`).
+    pub fn of(sct: &section::Section64) -> Option<Self> {
+        match sct.header.sh_type {
+            SHT_LLVM_ADDRSIG => Some(Self::Addrsig),
+            SHT_LLVM_CALL_GRAPH_PROFILE => Some(Self::CallGraphProfile),
+            SHT_LLVM_OFFLOADING => Some(Self::EmbeddedObject),
+            _ if sct.name == LLVM_EMBEDDED_OBJECT => Some(Self::EmbeddedObject),
+            _ => None,
+        }
+    }
+}
+
+/// The symbol indices in `.llvm_addrsig`
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::section;
+///
+/// let addrsig = section::LlvmAddrsig { symbols: vec![1, 200] };
+/// let bytes = addrsig.to_bytes();
+/// assert_eq!(vec![0x01, 0xc8, 0x01], bytes);
+/// assert_eq!(addrsig, section::LlvmAddrsig::parse(&bytes).unwrap());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct LlvmAddrsig {
+    pub symbols: Vec<u64>,
+}
+
+impl LlvmAddrsig {
+    pub fn parse(bytes: &[u8]) -> Result<Self, ReadError> {
+        // ULEB128の列なのでエンディアンは関係ない
+        let mut r = PayloadReader::new(bytes, header::Data::LSB2);
+        let mut symbols = Vec::new();
+        while !r.is_empty() {
+            symbols.push(r.uleb128()?);
+        }
+        Ok(Self { symbols })
+    }
+
+    /// Create the section's contents from this.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.symbols
+            .iter()
+            .flat_map(|idx| util::encode_uleb128(*idx))
+            .collect()
+    }
+}
+
+/// An edge in `.llvm.call-graph-profile`
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CallGraphEdge {
+    /// the symbol index of the caller
+    pub from: u64,
+    /// the symbol index of the callee
+    pub to: u64,
+    pub weight: u64,
+}
+
+impl section::Section64 {
+    /// rewrite the symbol indices in `.llvm_addrsig` and `.rel.llvm.call-graph-profile` by `new_index`.
+    ///
+    /// Removed symbols(`None`) are dropped from `.llvm_addrsig`,
+    /// and become the null symbol in the call graph profile so that the pairs of relocations are kept.
+    pub(crate) fn remap_llvm_symbols<F>(&mut self, data: header::Data, new_index: F)
+    where
+        F: Fn(u64) -> Option<u64>,
+    {
+        let ty = self.header.sh_type;
+        let bytes = match &mut self.contents {
+            section::Contents64::Raw(bytes) => bytes,
+            _ => return,
+        };
+        if ty == SHT_LLVM_ADDRSIG {
+            if let Ok(mut addrsig) = LlvmAddrsig::parse(bytes) {
+                addrsig.symbols = addrsig
+                    .symbols
+                    .iter()
+                    .filter_map(|idx| new_index(*idx))
+                    .collect();
+                *bytes = addrsig.to_bytes();
+            }
+        } else if self.header.get_type() == section::Type::Rel
+            && self.name == CALL_GRAPH_PROFILE_REL
+        {
+            // r_offset, r_info の順で各8バイト
+            for rel in bytes.chunks_exact_mut(16) {
+                let info = PayloadReader::new(&rel[8..], data).u64().unwrap();
+                let sym = new_index(info >> 32).unwrap_or(0);
+                let info = sym << 32 | (info & 0xffff_ffff);
+                rel[8..].copy_from_slice(&match data {
+                    header::Data::MSB2 => info.to_be_bytes(),
+                    _ => info.to_le_bytes(),
+                });
+            }
+        }
+        self.header.sh_size = self.contents.size() as Elf64Xword;
+    }
+}
+
+impl file::ELF64 {
+    /// decode `.llvm_addrsig` if exists.
+    pub fn llvm_addrsig(&self) -> Option<Result<LlvmAddrsig, ReadError>> {
+        let sct = self.first_section_by(|sct| sct.header.sh_type == SHT_LLVM_ADDRSIG)?;
+        Some(LlvmAddrsig::parse(&sct.to_le_bytes()))
+    }
+
+    /// decode `.llvm.call-graph-profile` with the symbols given by its relocations.
+    ///
+    /// Each weight has two relocations at its offset, the first for the caller and the second for the callee.
+    pub fn call_graph_profile(&self) -> Option<Result<Vec<CallGraphEdge>, ReadError>> {
+        let idx = self.first_shidx_by(|sct| sct.header.sh_type == SHT_LLVM_CALL_GRAPH_PROFILE)?;
+        Some(call_graph_edges(self, idx))
+    }
+
+    /// the contents of `.llvm.embedded.object` if exists.
+    pub fn llvm_embedded_object(&self) -> Option<Vec<u8>> {
+        let sct =
+            self.first_section_by(|sct| LlvmSection::of(sct) == Some(LlvmSection::EmbeddedObject))?;
+        Some(sct.to_le_bytes())
+    }
+}
+
+fn call_graph_edges(elf: &file::ELF64, idx: usize) -> Result<Vec<CallGraphEdge>, ReadError> {
+    let data = elf.ehdr.get_data();
+    let mut symbols: Vec<(Elf64Addr, u64)> = Vec::new();
+    for sct in elf.sections.iter() {
+        if sct.header.sh_info as usize != idx {
+            continue;
+        }
+        match &sct.contents {
+            section::Contents64::RelaSymbols(relas) => {
+                symbols.extend(relas.iter().map(|rela| (rela.get_offset(), rela.get_sym())))
+            }
+            section::Contents64::Raw(bytes) if sct.header.get_type() == section::Type::Rel => {
+                let mut r = PayloadReader::new(bytes, data);
+                while !r.is_empty() {
+                    let offset = r.u64()?;
+                    let info = r.u64()?;
+                    symbols.push((offset, info >> 32));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let bytes = elf.sections[idx].to_le_bytes();
+    let mut r = PayloadReader::new(&bytes, data);
+    let mut edges = Vec::new();
+    while !r.is_empty() {
+        let offset = r.position() as Elf64Addr;
+        let weight = r.u64()?;
+        let mut syms = symbols
+            .iter()
+            .filter(|(o, _)| *o == offset)
+            .map(|(_, sym)| *sym);
+        edges.push(CallGraphEdge {
+            from: syms.next().unwrap_or(0),
+            to: syms.next().unwrap_or(0),
+            weight,
+        });
+    }
+    Ok(edges)
+}
+
+#[cfg(test)]
+mod llvm_tests {
+    use super::*;
+
+    #[test]
+    fn llvm_sections_test() {
+        let mut elf = file::ELF64::default();
+        elf.ehdr.set_elf_type(header::Type::Rel);
+        elf.add_section(section::Section64::new(
+            ".text".to_string(),
+            section::ShdrPreparation64::default()
+                .ty(section::Type::ProgBits)
+                .flags([section::Flag::Alloc, section::Flag::ExecInstr].iter()),
+            section::Contents64::Raw(vec![0xc3, 0xc3]),
+        ));
+        let mut syms = vec![symbol::Symbol64::new_null_symbol()];
+        for (i, name) in ["f", "g"].iter().enumerate() {
+            let mut sym = symbol::Symbol64 {
+                symbol_name: (*name).into(),
+                st_shndx: 1,
+                st_value: i as u64,
+                st_size: 1,
+                ..Default::default()
+            };
+            sym.set_info(symbol::Type::Func, symbol::Bind::Global);
+            syms.push(sym);
+        }
+        elf.add_section(section::Section64::new(
+            ".symtab".to_string(),
+            section::ShdrPreparation64::default()
+                .ty(section::Type::SymTab)
+                .info(1),
+            section::Contents64::Symbols(syms),
+        ));
+        let symtab = elf.sections.len() - 2;
+
+        // llvm-mc の .addrsig_sym g, f と .cg_profile f, g, 32 の出力と同じ形
+        let addrsig = LlvmAddrsig {
+            symbols: vec![2, 1],
+        };
+        elf.add_section(section::Section64::new(
+            ".llvm_addrsig".to_string(),
+            section::ShdrPreparation64::default()
+                .ty(section::Type::Any(SHT_LLVM_ADDRSIG))
+                .link(symtab as Elf64Word),
+            section::Contents64::Raw(addrsig.to_bytes()),
+        ));
+        elf.add_section(section::Section64::new(
+            ".llvm.call-graph-profile".to_string(),
+            section::ShdrPreparation64::default()
+                .ty(section::Type::Any(SHT_LLVM_CALL_GRAPH_PROFILE))
+                .link(symtab as Elf64Word),
+            section::Contents64::Raw(32u64.to_le_bytes().to_vec()),
+        ));
+        let cgp = elf.sections.len() - 2;
+        let mut rel = Vec::new();
+        for sym in [1u64, 2].iter() {
+            rel.extend_from_slice(&0u64.to_le_bytes());
+            rel.extend_from_slice(&(sym << 32).to_le_bytes());
+        }
+        elf.add_section(section::Section64::new(
+            CALL_GRAPH_PROFILE_REL.to_string(),
+            section::ShdrPreparation64::default()
+                .ty(section::Type::Rel)
+                .link(symtab as Elf64Word)
+                .info(cgp as Elf64Word),
+            section::Contents64::Raw(rel),
+        ));
+        elf.add_section(section::Section64::new(
+            LLVM_EMBEDDED_OBJECT.to_string(),
+            section::ShdrPreparation64::default().ty(section::Type::ProgBits),
+            section::Contents64::Raw(b"BC\xc0\xde".to_vec()),
+        ));
+
+        assert_eq!(Some(Ok(addrsig)), elf.llvm_addrsig());
+        let edge = CallGraphEdge {
+            from: 1,
+            to: 2,
+            weight: 32,
+        };
+        assert_eq!(Some(Ok(vec![edge])), elf.call_graph_profile());
+        assert_eq!(Some(b"BC\xc0\xde".to_vec()), elf.llvm_embedded_object());
+        assert_eq!(
+            Some(LlvmSection::CallGraphProfile),
+            LlvmSection::of(&elf.sections[cgp])
+        );
+
+        // セクションシンボルが2つ追加され，f, g は 3, 4 になる
+        transform::split_functions(&mut elf).unwrap();
+        assert_eq!(vec![4, 3], elf.llvm_addrsig().unwrap().unwrap().symbols);
+        let edges = elf.call_graph_profile().unwrap().unwrap();
+        assert_eq!((3, 4, 32), (edges[0].from, edges[0].to, edges[0].weight));
+    }
+}
@@ -0,0 +1,299 @@
+//! Typed reading of section contents.
+
+use std::borrow::Cow;
+
+use crate::*;
+use thiserror::Error as TError;
+
+#[derive(TError, Debug, Clone, PartialEq, Eq)]
+pub enum ReadError {
+    #[error("{needed} bytes are needed at offset {offset:#x} but the payload ends")]
+    UnexpectedEnd { offset: usize, needed: usize },
+    #[error("LEB128 at offset {offset:#x} overflows 64 bits")]
+    LebOverflow { offset: usize },
+    #[error("initial length {value:#x} at offset {offset:#x} is reserved")]
+    ReservedInitialLength { offset: usize, value: u32 },
+    #[error("format version {version:#x} at offset {offset:#x} is not supported")]
+    UnsupportedVersion { offset: usize, version: u8 },
+    #[error("string at offset {offset:#x} is not terminated")]
+    UnterminatedString { offset: usize },
+    #[error("string at offset {offset:#x} is not valid UTF-8")]
+    InvalidString { offset: usize },
+}
+
+/// A cursor over a section's payload, which decodes integers in the given data encoding.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::{header, section};
+///
+/// let bytes = [0x12, 0x34, 0xe5, 0x8e, 0x26, 0x7f, b'h', b'i', 0];
+/// let mut r = section::PayloadReader::new(&bytes, header::Data::MSB2);
+/// assert_eq!(Ok(0x1234), r.u16());
+/// assert_eq!(Ok(624485), r.uleb128());
+/// assert_eq!(Ok(-1), r.sleb128());
+/// assert_eq!(Ok("hi".to_string()), r.cstr());
+/// assert!(r.is_empty());
+/// assert!(r.u8().is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PayloadReader<'a> {
+    bytes: Cow<'a, [u8]>,
+    pos: usize,
+    data: header::Data,
+}
+
+impl<'a> PayloadReader<'a> {
+    /// `data` other than `Data::MSB2` is read as little endian.
+    pub fn new(bytes: &'a [u8], data: header::Data) -> Self {
+        Self {
+            bytes: Cow::Borrowed(bytes),
+            pos: 0,
+            data,
+        }
+    }
+
+    fn from_cow(bytes: Cow<'a, [u8]>, data: header::Data) -> Self {
+        Self {
+            bytes,
+            pos: 0,
+            data,
+        }
+    }
+
+    /// the current offset in the payload.
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+    /// move to `offset`, which may be the end of the payload.
+    pub fn seek(&mut self, offset: usize) -> Result<(), ReadError> {
+        if offset > self.bytes.len() {
+            return Err(ReadError::UnexpectedEnd { offset, needed: 0 });
+        }
+        self.pos = offset;
+        Ok(())
+    }
+    pub fn skip(&mut self, len: usize) -> Result<(), ReadError> {
+        self.take(len).map(|_| ())
+    }
+    /// skip to the next multiple of `align`(e.g. after a note's name).
+    pub fn align(&mut self, align: usize) -> Result<(), ReadError> {
+        let rem = self.pos % align.max(1);
+        if rem == 0 {
+            return Ok(());
+        }
+        self.skip(align - rem)
+    }
+    pub fn remaining(&self) -> usize {
+        self.bytes.len() - self.pos
+    }
+    pub fn is_empty(&self) -> bool {
+        self.remaining() == 0
+    }
+
+    pub fn bytes(&mut self, len: usize) -> Result<Vec<u8>, ReadError> {
+        self.take(len).map(|b| b.to_vec())
+    }
+    pub fn u8(&mut self) -> Result<u8, ReadError> {
+        self.take(1).map(|b| b[0])
+    }
+    pub fn u16(&mut self) -> Result<u16, ReadError> {
+        let mut buf = [0; 2];
+        buf.copy_from_slice(self.take(2)?);
+        Ok(match self.data {
+            header::Data::MSB2 => u16::from_be_bytes(buf),
+            _ => u16::from_le_bytes(buf),
+        })
+    }
+    pub fn u32(&mut self) -> Result<u32, ReadError> {
+        let mut buf = [0; 4];
+        buf.copy_from_slice(self.take(4)?);
+        Ok(match self.data {
+            header::Data::MSB2 => u32::from_be_bytes(buf),
+            _ => u32::from_le_bytes(buf),
+        })
+    }
+    pub fn u64(&mut self) -> Result<u64, ReadError> {
+        let mut buf = [0; 8];
+        buf.copy_from_slice(self.take(8)?);
+        Ok(match self.data {
+            header::Data::MSB2 => u64::from_be_bytes(buf),
+            _ => u64::from_le_bytes(buf),
+        })
+    }
+
+    /// unsigned LEB128, used by DWARF and `.eh_frame`.
+    pub fn uleb128(&mut self) -> Result<u64, ReadError> {
+        let start = self.pos;
+        let (value, len) = self.at(start, util::decode_uleb128(&self.bytes[start..]))?;
+        self.pos += len;
+        Ok(value)
+    }
+
+    /// signed LEB128
+    pub fn sleb128(&mut self) -> Result<i64, ReadError> {
+        let start = self.pos;
+        let (value, len) = self.at(start, util::decode_sleb128(&self.bytes[start..]))?;
+        self.pos += len;
+        Ok(value)
+    }
+
+    /// the initial length of a DWARF unit or a CIE/FDE.
+    pub fn initial_length(&mut self) -> Result<util::InitialLength, ReadError> {
+        let start = self.pos;
+        let decoded = util::decode_initial_length(&self.bytes[start..], self.data);
+        let (length, len) = self.at(start, decoded)?;
+        self.pos += len;
+        Ok(length)
+    }
+
+    /// a NUL-terminated string. the NUL is consumed but not returned.
+    pub fn cstr(&mut self) -> Result<String, ReadError> {
+        let start = self.pos;
+        let len = self.bytes[start..]
+            .iter()
+            .position(|b| *b == 0)
+            .ok_or(ReadError::UnterminatedString { offset: start })?;
+        let s = std::str::from_utf8(&self.bytes[start..start + len])
+            .map_err(|_| ReadError::InvalidString { offset: start })?
+            .to_string();
+        self.pos += len + 1;
+        Ok(s)
+    }
+
+    /// make the offsets of an error from `util` relative to the payload.
+    fn at<T>(&self, start: usize, result: Result<T, ReadError>) -> Result<T, ReadError> {
+        result.map_err(|e| match e {
+            ReadError::UnexpectedEnd { offset, needed } => ReadError::UnexpectedEnd {
+                offset: start + offset,
+                needed,
+            },
+            ReadError::LebOverflow { offset } => ReadError::LebOverflow {
+                offset: start + offset,
+            },
+            ReadError::ReservedInitialLength { offset, value } => {
+                ReadError::ReservedInitialLength {
+                    offset: start + offset,
+                    value,
+                }
+            }
+            ReadError::UnsupportedVersion { offset, version } => ReadError::UnsupportedVersion {
+                offset: start + offset,
+                version,
+            },
+            ReadError::UnterminatedString { offset } => ReadError::UnterminatedString {
+                offset: start + offset,
+            },
+            ReadError::InvalidString { offset } => ReadError::InvalidString {
+                offset: start + offset,
+            },
+        })
+    }
+
+    fn take(&mut self, len: usize) -> Result<&[u8], ReadError> {
+        if self.remaining() < len {
+            return Err(ReadError::UnexpectedEnd {
+                offset: self.pos,
+                needed: len,
+            });
+        }
+        let start = self.pos;
+        self.pos += len;
+        Ok(&self.bytes[start..start + len])
+    }
+}
+
+impl section::Section64 {
+    /// a reader over the contents, which are decoded as little endian like the rest of `ELF64`.
+    ///
+    /// Use `ELF64::section_reader()` to honor the file's `EI_DATA`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use elf_utilities::section;
+    ///
+    /// let sct = section::Section64::new(
+    ///     ".custom".to_string(),
+    ///     section::ShdrPreparation64::default(),
+    ///     section::Contents64::Raw(vec![0x01, 0x00, 0x00, 0x00, 0x80, 0x01]),
+    /// );
+    /// let mut r = sct.reader();
+    /// assert_eq!(Ok(1), r.u32());
+    /// assert_eq!(Ok(128), r.uleb128());
+    /// ```
+    pub fn reader(&self) -> PayloadReader<'_> {
+        self.reader_with(header::Data::LSB2)
+    }
+
+    fn reader_with(&self, data: header::Data) -> PayloadReader<'_> {
+        match &self.contents {
+            section::Contents64::Raw(bytes) => PayloadReader::new(bytes, data),
+            _ => PayloadReader::from_cow(Cow::Owned(self.to_le_bytes()), data),
+        }
+    }
+}
+
+impl file::ELF64 {
+    /// a reader over the contents of the section at `shidx`, in the data encoding of the file.
+    pub fn section_reader(&self, shidx: usize) -> Option<PayloadReader<'_>> {
+        let data = self.ehdr.get_data();
+        self.sections.get(shidx).map(|sct| sct.reader_with(data))
+    }
+}
+
+#[cfg(test)]
+mod reader_tests {
+    use super::*;
+
+    #[test]
+    fn leb128_test() {
+        let read = |bytes: &[u8]| {
+            let mut r = PayloadReader::new(bytes, header::Data::LSB2);
+            (r.uleb128(), r.position())
+        };
+        assert_eq!((Ok(2), 1), read(&[0x02]));
+        assert_eq!((Ok(127), 1), read(&[0x7f, 0x01]));
+        assert_eq!(
+            (Ok(u64::MAX), 10),
+            read(&[0xff; 9].iter().chain(&[0x01]).copied().collect::<Vec<_>>())
+        );
+        assert_eq!(
+            Err(ReadError::LebOverflow { offset: 0 }),
+            read(&[0xff; 9].iter().chain(&[0x02]).copied().collect::<Vec<_>>()).0
+        );
+        assert_eq!(
+            Err(ReadError::UnexpectedEnd {
+                offset: 1,
+                needed: 1
+            }),
+            read(&[0x80]).0
+        );
+
+        let sread = |bytes: &[u8]| PayloadReader::new(bytes, header::Data::LSB2).sleb128();
+        assert_eq!(Ok(2), sread(&[0x02]));
+        assert_eq!(Ok(-2), sread(&[0x7e]));
+        assert_eq!(Ok(-128), sread(&[0x80, 0x7f]));
+        assert_eq!(
+            Ok(i64::MIN),
+            sread(&[0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x7f])
+        );
+    }
+
+    #[test]
+    fn endian_test() {
+        let bytes = [1, 2, 3, 4, 5, 6, 7, 8];
+        let mut le = PayloadReader::new(&bytes, header::Data::LSB2);
+        let mut be = PayloadReader::new(&bytes, header::Data::MSB2);
+        assert_eq!(Ok(0x0807060504030201), le.u64());
+        assert_eq!(Ok(0x0102030405060708), be.u64());
+
+        let mut elf = file::ELF64::default();
+        elf.ehdr.set_data(header::Data::MSB2);
+        let mut r = elf.section_reader(elf.ehdr.e_shstrndx as usize).unwrap();
+        assert_eq!(Ok(String::new()), r.cstr());
+        assert_eq!(Ok(".shstrtab".to_string()), r.cstr());
+        assert!(elf.section_reader(elf.sections.len()).is_none());
+    }
+}
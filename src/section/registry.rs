@@ -0,0 +1,171 @@
+//! Registration of the vendor-specific section types.
+//!
+//! The parser only knows the generic and GNU section types, so the contents of the others are kept as `Raw`.
+//! A `TypeRegistry` gives them names and typed decoders.
+
+use std::any::Any;
+use std::fmt;
+use std::ops::RangeInclusive;
+
+use crate::*;
+use section::{PayloadReader, ReadError};
+
+/// The contents decoded by a decoder registered in `TypeRegistry`
+pub trait CustomContents: fmt::Debug + fmt::Display + Send + Sync {
+    /// the problems in the contents, reported by `validation::check_registered_types()`.
+    fn validate(&self, _shdr: &section::Shdr64) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// for downcasting to the decoder's type.
+    fn as_any(&self) -> &dyn Any;
+}
+
+/// The result of a `Decoder`
+pub type Decoded = Result<Box<dyn CustomContents>, ReadError>;
+
+/// decode the contents of a section, which the reader reads in the data encoding of the file.
+pub type Decoder = fn(&section::Shdr64, PayloadReader<'_>) -> Decoded;
+
+/// A section type registered in `TypeRegistry`
+#[derive(Debug, Clone)]
+pub struct RegisteredType {
+    /// the values of `sh_type`
+    pub range: RangeInclusive<Elf64Word>,
+    pub name: String,
+    pub decoder: Decoder,
+}
+
+impl RegisteredType {
+    /// the name of `sh_type`, with the offset from the start of the range unless it is the start.
+    pub fn type_name(&self, sh_type: Elf64Word) -> String {
+        let offset = sh_type - self.range.start();
+        if offset == 0 {
+            self.name.clone()
+        } else {
+            format!("{}+{:#x}", self.name, offset)
+        }
+    }
+}
+
+/// The section types defined by vendors or users
+///
+/// # Examples
+///
+/// ```
+/// use std::any::Any;
+/// use std::fmt;
+///
+/// use elf_utilities::{file, section};
+///
+/// #[derive(Debug)]
+/// struct Checksums(Vec<u32>);
+///
+/// impl fmt::Display for Checksums {
+///     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+///         write!(f, "{} checksums", self.0.len())
+///     }
+/// }
+///
+/// impl section::CustomContents for Checksums {
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+/// }
+///
+/// fn decode(
+///     _shdr: &section::Shdr64,
+///     mut r: section::PayloadReader<'_>,
+/// ) -> section::Decoded {
+///     let mut sums = Vec::new();
+///     while !r.is_empty() {
+///         sums.push(r.u32()?);
+///     }
+///     Ok(Box::new(Checksums(sums)))
+/// }
+///
+/// let registry = section::TypeRegistry::new().register(0x8000_0100..=0x8000_01ff, "ACME_CHECKSUMS", decode);
+///
+/// let mut elf = file::ELF64::default();
+/// elf.add_section(section::Section64::new(
+///     ".acme.sums".to_string(),
+///     section::ShdrPreparation64::default().ty(section::Type::Any(0x8000_0101)),
+///     section::Contents64::Raw(vec![1, 0, 0, 0, 2, 0, 0, 0]),
+/// ));
+///
+/// assert_eq!("ACME_CHECKSUMS+0x1", registry.type_name(section::Type::Any(0x8000_0101)));
+/// assert_eq!("PROGBITS", registry.type_name(section::Type::ProgBits));
+///
+/// let decoded = elf.decode_registered(&registry);
+/// let (shidx, contents) = &decoded[0];
+/// assert_eq!(1, *shidx);
+/// let contents = contents.as_ref().unwrap();
+/// assert_eq!("2 checksums", contents.to_string());
+/// assert_eq!(vec![1, 2], contents.as_any().downcast_ref::<Checksums>().unwrap().0);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct TypeRegistry {
+    types: Vec<RegisteredType>,
+}
+
+impl TypeRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// register the range of `sh_type`.
+    /// When ranges overlap, the type registered later wins.
+    pub fn register(
+        mut self,
+        range: RangeInclusive<Elf64Word>,
+        name: &str,
+        decoder: Decoder,
+    ) -> Self {
+        self.types.push(RegisteredType {
+            range,
+            name: name.to_string(),
+            decoder,
+        });
+        self
+    }
+
+    /// the registered type containing `sh_type`.
+    pub fn lookup(&self, sh_type: Elf64Word) -> Option<&RegisteredType> {
+        self.types
+            .iter()
+            .rev()
+            .find(|registered| registered.range.contains(&sh_type))
+    }
+
+    /// the name of the type, falling back to the name readelf shows.
+    pub fn type_name(&self, ty: section::Type) -> String {
+        let sh_type = Elf64Word::from(ty);
+        match self.lookup(sh_type) {
+            Some(registered) => registered.type_name(sh_type),
+            None => ty.to_string(),
+        }
+    }
+
+    /// decode the contents of the section, `None` if its type isn't registered.
+    pub fn decode(&self, sct: &section::Section64, data: header::Data) -> Option<Decoded> {
+        let registered = self.lookup(sct.header.sh_type)?;
+        let bytes = sct.to_le_bytes();
+        Some((registered.decoder)(
+            &sct.header,
+            PayloadReader::new(&bytes, data),
+        ))
+    }
+}
+
+impl file::ELF64 {
+    /// decode the sections whose types are registered, with their indices.
+    pub fn decode_registered(&self, registry: &TypeRegistry) -> Vec<(usize, Decoded)> {
+        let data = self.ehdr.get_data();
+        self.sections
+            .iter()
+            .enumerate()
+            .filter(|(_, sct)| sct.header.get_type() != section::Type::NoBits)
+            .filter_map(|(shidx, sct)| Some((shidx, registry.decode(sct, data)?)))
+            .collect()
+    }
+}
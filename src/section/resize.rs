@@ -0,0 +1,189 @@
+//! Resizing the contents of sections.
+
+use crate::layout::{align_up, is_alloc, is_nobits, is_tls};
+use crate::*;
+use thiserror::Error as TError;
+
+#[derive(TError, Debug, Clone, PartialEq, Eq)]
+pub enum ResizeError {
+    #[error("the section has no raw contents")]
+    NotRaw,
+    #[error("section index {shidx} is out of range")]
+    InvalidSection { shidx: usize },
+    #[error("`{name}` can't grow to {size:#x} bytes since `{next}` follows it in memory")]
+    NoRoom {
+        name: String,
+        size: Elf64Xword,
+        next: String,
+    },
+}
+
+impl section::Section64 {
+    /// truncate the contents to `new_size` bytes, or extend them with `fill`, and update `sh_size`.
+    /// `SHT_NOBITS` sections only get the new `sh_size`.
+    ///
+    /// Nothing else is updated. Use `ELF64::resize_section()` to move the sections behind it and fit the segments.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use elf_utilities::section;
+    ///
+    /// let mut text = section::Section64::new(
+    ///     ".text".to_string(),
+    ///     section::ShdrPreparation64::default().ty(section::Type::ProgBits),
+    ///     section::Contents64::Raw(vec![0xc3]),
+    /// );
+    /// text.resize(4, 0xcc).unwrap();
+    /// assert_eq!(vec![0xc3, 0xcc, 0xcc, 0xcc], text.to_le_bytes());
+    /// assert_eq!(4, text.header.sh_size);
+    /// text.resize(0, 0xcc).unwrap();
+    /// assert_eq!(0, text.header.sh_size);
+    /// ```
+    pub fn resize(&mut self, new_size: Elf64Xword, fill: u8) -> Result<(), ResizeError> {
+        if !is_nobits(&self.header) {
+            match &mut self.contents {
+                section::Contents64::Raw(contents) => contents.resize(new_size as usize, fill),
+                _ => return Err(ResizeError::NotRaw),
+            }
+        }
+        self.header.sh_size = new_size;
+        Ok(())
+    }
+}
+
+impl file::ELF64 {
+    /// resize the section `shidx` like `Section64::resize()`, keeping the headers consistent.
+    ///
+    /// Relocatable files and the files without segments are laid out again by `ELF64::condition()`.
+    /// In linked files the virtual addresses never change:
+    ///
+    /// - when the section grows, what follows it in the file is moved back by the growth rounded up to
+    ///   the largest alignment of the moved sections and segments, so that they keep their alignments
+    /// - an `SHF_ALLOC` section can grow only up to the next allocated section in memory
+    /// - the segments ending at the end of the section grow or shrink with it,
+    ///   and the ones behind it get the new `p_offset`
+    /// - when the section shrinks, nothing is moved and the rest is left as a gap
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use elf_utilities::parser;
+    ///
+    /// let mut elf = parser::parse_elf64("tests/fixtures/exceptions").unwrap();
+    /// let comment = elf.first_shidx_by(|sct| sct.name == ".comment").unwrap();
+    /// let next_offset = elf.sections[comment + 1].header.sh_offset;
+    ///
+    /// elf.resize_section(comment, 0x100, 0).unwrap();
+    /// assert_eq!(0x100, elf.sections[comment].header.sh_size);
+    /// assert!(elf.sections[comment + 1].header.sh_offset >= next_offset + 0x80);
+    /// assert!(elf.validate().is_ok());
+    ///
+    /// // .textの後ろには他のセクションが続く
+    /// let text = elf.first_shidx_by(|sct| sct.name == ".text").unwrap();
+    /// assert!(elf.resize_section(text, 0x10000, 0xcc).is_err());
+    /// ```
+    pub fn resize_section(
+        &mut self,
+        shidx: usize,
+        new_size: Elf64Xword,
+        fill: u8,
+    ) -> Result<(), ResizeError> {
+        let old = self
+            .sections
+            .get(shidx)
+            .ok_or(ResizeError::InvalidSection { shidx })?
+            .header;
+        let linked = self.ehdr.get_type() != header::Type::Rel && !self.segments.is_empty();
+        if linked && is_alloc(&old) && new_size > old.sh_size {
+            self.check_room(shidx, new_size)?;
+        }
+        self.sections[shidx].resize(new_size, fill)?;
+        self.invalidate_address_index();
+        if !linked {
+            self.condition();
+            return Ok(());
+        }
+
+        let old_mem_end = old.sh_addr + old.sh_size;
+        let new_mem_end = old.sh_addr + new_size;
+        let old_file_end = old.sh_offset + if is_nobits(&old) { 0 } else { old.sh_size };
+        let new_file_end = old.sh_offset + if is_nobits(&old) { 0 } else { new_size };
+
+        // 後ろのセクションとセグメントは，アラインメントを保つように動かす
+        let shift = if new_file_end > old_file_end {
+            let moved_sections = self
+                .sections
+                .iter()
+                .enumerate()
+                .filter(|(i, sct)| *i != shidx && *i != 0 && sct.header.sh_offset >= old_file_end)
+                .map(|(_, sct)| sct.header.sh_addralign);
+            let moved_segments = self
+                .segments
+                .iter()
+                .filter(|sgt| sgt.header.p_offset >= old_file_end)
+                .map(|sgt| sgt.header.p_align);
+            let align = moved_sections
+                .chain(moved_segments)
+                .max()
+                .unwrap_or(1)
+                .max(8);
+            align_up(new_file_end - old_file_end, align)
+        } else {
+            0
+        };
+
+        for (i, sct) in self.sections.iter_mut().enumerate() {
+            if i != shidx && i != 0 && sct.header.sh_offset >= old_file_end {
+                sct.header.sh_offset += shift;
+            }
+        }
+        for sgt in self.segments.iter_mut() {
+            let phdr = &mut sgt.header;
+            // SHF_ALLOCでないセクションはどのセグメントにも含まれない
+            let covers = is_alloc(&old)
+                && phdr.p_offset <= old.sh_offset
+                && old.sh_addr >= phdr.p_vaddr
+                && old_mem_end <= phdr.p_vaddr + phdr.p_memsz;
+            if covers {
+                if !is_nobits(&old) && phdr.p_offset + phdr.p_filesz == old_file_end {
+                    phdr.p_filesz = new_file_end - phdr.p_offset;
+                }
+                if phdr.p_vaddr + phdr.p_memsz == old_mem_end {
+                    phdr.p_memsz = new_mem_end - phdr.p_vaddr;
+                }
+                phdr.p_memsz = phdr.p_memsz.max(phdr.p_filesz);
+            } else if phdr.p_offset >= old_file_end {
+                phdr.p_offset += shift;
+            }
+        }
+        if self.ehdr.e_shoff >= old_file_end {
+            self.ehdr.e_shoff += shift;
+        }
+        Ok(())
+    }
+
+    /// whether the allocated section can grow to `new_size` without overlapping the next one in memory.
+    fn check_room(&self, shidx: usize, new_size: Elf64Xword) -> Result<(), ResizeError> {
+        let sct = &self.sections[shidx];
+        let start = sct.header.sh_addr;
+        let end = start + new_size;
+        let next = self.sections.iter().enumerate().find(|(i, other)| {
+            *i != shidx
+                && is_alloc(&other.header)
+                // TLSのNOBITSはアドレス空間を占有しない
+                && !(is_nobits(&other.header) && is_tls(&other.header))
+                && other.header.sh_addr >= start
+                && other.header.sh_addr < end
+                && (other.header.sh_addr > start || other.header.sh_size != 0)
+        });
+        match next {
+            Some((_, next)) => Err(ResizeError::NoRoom {
+                name: sct.name.to_string(),
+                size: new_size,
+                next: next.name.to_string(),
+            }),
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,191 @@
+//! `.riscv.attributes` section utilities.
+
+use crate::*;
+use section::{build_attributes, BuildAttributeValue, ReadError};
+
+/// `SHT_RISCV_ATTRIBUTES`
+pub const SHT_RISCV_ATTRIBUTES: Elf64Word = 0x70000003;
+
+/// `EM_RISCV`
+pub const EM_RISCV: Elf64Half = 243;
+
+/// the vendor of the public attributes
+pub const RISCV_VENDOR: &str = "riscv";
+
+/// the stack alignment in bytes
+pub const TAG_RISCV_STACK_ALIGN: u64 = 4;
+/// the ISA string, e.g. "rv64i2p0_m2p0"
+pub const TAG_RISCV_ARCH: u64 = 5;
+/// 1 if the code may access memory unaligned
+pub const TAG_RISCV_UNALIGNED_ACCESS: u64 = 6;
+pub const TAG_RISCV_PRIV_SPEC: u64 = 8;
+pub const TAG_RISCV_PRIV_SPEC_MINOR: u64 = 10;
+pub const TAG_RISCV_PRIV_SPEC_REVISION: u64 = 12;
+pub const TAG_RISCV_ATOMIC_ABI: u64 = 14;
+pub const TAG_RISCV_X3_REG_USAGE: u64 = 16;
+
+const RISCV: build_attributes::Vendor = build_attributes::Vendor {
+    name: RISCV_VENDOR,
+    // 奇数のタグは文字列
+    is_string_tag: |tag| tag % 2 == 1,
+    compat_tag: None,
+};
+
+/// The build attributes in `.riscv.attributes`
+///
+/// The subsections of the other vendors are kept as is, so parsing and emitting loses nothing.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::section;
+///
+/// let mut attrs = section::RiscvAttributes::default();
+/// attrs.set_arch("rv64i2p0_m2p0_a2p0");
+/// attrs.set_stack_align(16);
+///
+/// let parsed = section::RiscvAttributes::parse_le(&attrs.to_le_bytes()).unwrap();
+/// assert_eq!(Some("rv64i2p0_m2p0_a2p0"), parsed.arch());
+/// assert_eq!(Some(16), parsed.stack_align());
+/// assert_eq!(None, parsed.unaligned_access());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct RiscvAttributes {
+    pub subsections: Vec<section::BuildAttributesSubsection>,
+}
+
+impl RiscvAttributes {
+    pub fn parse(bytes: &[u8], data: header::Data) -> Result<Self, ReadError> {
+        let subsections = build_attributes::parse_subsections(bytes, data, &RISCV)?;
+        Ok(Self { subsections })
+    }
+
+    /// RISC-V is little endian in practice.
+    pub fn parse_le(bytes: &[u8]) -> Result<Self, ReadError> {
+        Self::parse(bytes, header::Data::LSB2)
+    }
+
+    /// Create the section's contents from this.
+    pub fn to_bytes(&self, data: header::Data) -> Vec<u8> {
+        build_attributes::encode_subsections(&self.subsections, data)
+    }
+
+    pub fn to_le_bytes(&self) -> Vec<u8> {
+        self.to_bytes(header::Data::LSB2)
+    }
+
+    /// create `.riscv.attributes` from this.
+    pub fn to_section64(&self) -> section::Section64 {
+        let mut hdr =
+            section::ShdrPreparation64::default().ty(section::Type::Any(SHT_RISCV_ATTRIBUTES));
+        hdr.sh_addralign = 1;
+        section::Section64::new(
+            ".riscv.attributes".to_string(),
+            hdr,
+            section::Contents64::Raw(self.to_le_bytes()),
+        )
+    }
+
+    /// the file-scope attribute in the "riscv" subsection.
+    pub fn file_attribute(&self, tag: u64) -> Option<&BuildAttributeValue> {
+        build_attributes::file_attribute(&self.subsections, tag)
+    }
+
+    /// set the file-scope attribute in the "riscv" subsection, creating it if not found.
+    pub fn set_file_attribute(&mut self, tag: u64, value: BuildAttributeValue) {
+        build_attributes::set_file_attribute(&mut self.subsections, &RISCV, tag, value)
+    }
+
+    /// `Tag_RISCV_arch`
+    pub fn arch(&self) -> Option<&str> {
+        match self.file_attribute(TAG_RISCV_ARCH)? {
+            BuildAttributeValue::Str(arch) => Some(arch),
+            _ => None,
+        }
+    }
+    /// `Tag_RISCV_stack_align`
+    pub fn stack_align(&self) -> Option<u64> {
+        self.int_attribute(TAG_RISCV_STACK_ALIGN)
+    }
+    /// `Tag_RISCV_unaligned_access`
+    pub fn unaligned_access(&self) -> Option<bool> {
+        self.int_attribute(TAG_RISCV_UNALIGNED_ACCESS)
+            .map(|v| v != 0)
+    }
+
+    pub fn set_arch(&mut self, arch: &str) {
+        self.set_file_attribute(TAG_RISCV_ARCH, BuildAttributeValue::Str(arch.to_string()));
+    }
+    pub fn set_stack_align(&mut self, align: u64) {
+        self.set_file_attribute(TAG_RISCV_STACK_ALIGN, BuildAttributeValue::Int(align));
+    }
+    pub fn set_unaligned_access(&mut self, allowed: bool) {
+        self.set_file_attribute(
+            TAG_RISCV_UNALIGNED_ACCESS,
+            BuildAttributeValue::Int(allowed as u64),
+        );
+    }
+
+    fn int_attribute(&self, tag: u64) -> Option<u64> {
+        match self.file_attribute(tag)? {
+            BuildAttributeValue::Int(v) => Some(*v),
+            _ => None,
+        }
+    }
+}
+
+impl file::ELF64 {
+    /// decode `.riscv.attributes` if the file is for RISC-V and has it.
+    pub fn riscv_attributes(&self) -> Option<Result<RiscvAttributes, ReadError>> {
+        // SHT_RISCV_ATTRIBUTESはSHT_ARM_ATTRIBUTESと同じ値
+        if self.ehdr.get_machine() != header::Machine::Any(EM_RISCV) {
+            return None;
+        }
+        let sct = self.first_section_by(|sct| sct.header.sh_type == SHT_RISCV_ATTRIBUTES)?;
+        Some(RiscvAttributes::parse(
+            &sct.to_le_bytes(),
+            self.ehdr.get_data(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod riscv_attributes_tests {
+    use super::*;
+
+    // llvm-mc -triple=riscv64 で arch, stack_align, unaligned_access, priv_spec を指定
+    const ATTRIBUTES: [u8; 37] = [
+        0x41, 0x24, 0x00, 0x00, 0x00, 0x72, 0x69, 0x73, 0x63, 0x76, 0x00, 0x01, 0x1a, 0x00, 0x00,
+        0x00, 0x05, 0x72, 0x76, 0x36, 0x34, 0x69, 0x32, 0x70, 0x30, 0x5f, 0x6d, 0x32, 0x70, 0x30,
+        0x00, 0x04, 0x10, 0x06, 0x01, 0x08, 0x01,
+    ];
+
+    #[test]
+    fn riscv_attributes_test() {
+        let mut attrs = RiscvAttributes::parse_le(&ATTRIBUTES).unwrap();
+        assert_eq!(Some("rv64i2p0_m2p0"), attrs.arch());
+        assert_eq!(Some(16), attrs.stack_align());
+        assert_eq!(Some(true), attrs.unaligned_access());
+        assert_eq!(
+            Some(&BuildAttributeValue::Int(1)),
+            attrs.file_attribute(TAG_RISCV_PRIV_SPEC)
+        );
+        assert_eq!(ATTRIBUTES.to_vec(), attrs.to_le_bytes());
+
+        // 他のベンダーのサブセクションはそのまま残る
+        attrs.subsections.push(section::BuildAttributesSubsection {
+            vendor: "gnu".to_string(),
+            contents: section::VendorAttributes::Raw(vec![1, 2, 3]),
+        });
+        attrs.set_unaligned_access(false);
+        let reparsed = RiscvAttributes::parse_le(&attrs.to_le_bytes()).unwrap();
+        assert_eq!(attrs, reparsed);
+        assert_eq!(Some(false), reparsed.unaligned_access());
+
+        let mut elf = file::ELF64::default();
+        elf.add_section(attrs.to_section64());
+        assert_eq!(None, elf.riscv_attributes());
+        elf.ehdr.set_machine(header::Machine::Any(EM_RISCV));
+        assert_eq!(Some(Ok(attrs)), elf.riscv_attributes());
+    }
+}
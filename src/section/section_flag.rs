@@ -27,11 +27,30 @@ pub enum Flag {
     TLS,
     /// Section with compressed data
     COMPRESSED,
+    /// OS/processor specific or unknown flag
+    Any(Elf64Xword),
 }
 
-impl Into<Elf32Word> for Flag {
-    fn into(self) -> Elf32Word {
-        match self {
+impl Flag {
+    /// every flag in the order of its bit
+    pub const ALL: [Flag; 11] = [
+        Flag::Write,
+        Flag::Alloc,
+        Flag::ExecInstr,
+        Flag::Merge,
+        Flag::Strings,
+        Flag::InfoLink,
+        Flag::LinkOrder,
+        Flag::OSNonConforming,
+        Flag::Group,
+        Flag::TLS,
+        Flag::COMPRESSED,
+    ];
+}
+
+impl From<Flag> for Elf32Word {
+    fn from(flag: Flag) -> Self {
+        match flag {
             Flag::Write => 1 << 0,
             Flag::Alloc => 1 << 1,
             Flag::ExecInstr => 1 << 2,
@@ -43,13 +62,14 @@ impl Into<Elf32Word> for Flag {
             Flag::Group => 1 << 9,
             Flag::TLS => 1 << 10,
             Flag::COMPRESSED => 1 << 11,
+            Flag::Any(v) => v as Elf32Word,
         }
     }
 }
 
-impl Into<Elf64Xword> for Flag {
-    fn into(self) -> Elf64Xword {
-        match self {
+impl From<Flag> for Elf64Xword {
+    fn from(flag: Flag) -> Self {
+        match flag {
             Flag::Write => 1 << 0,
             Flag::Alloc => 1 << 1,
             Flag::ExecInstr => 1 << 2,
@@ -61,44 +81,32 @@ impl Into<Elf64Xword> for Flag {
             Flag::Group => 1 << 9,
             Flag::TLS => 1 << 10,
             Flag::COMPRESSED => 1 << 11,
+            Flag::Any(v) => v,
         }
     }
 }
 
 impl From<Elf32Word> for Flag {
     fn from(v: Elf32Word) -> Self {
-        match v {
-            0b1 => Flag::Write,
-            0b10 => Flag::Alloc,
-            0b100 => Flag::ExecInstr,
-            0b1000 => Flag::Merge,
-            0b10000 => Flag::Strings,
-            0b100000 => Flag::InfoLink,
-            0b1000000 => Flag::LinkOrder,
-            0b10000000 => Flag::OSNonConforming,
-            0b100000000 => Flag::Group,
-            0b1000000000 => Flag::TLS,
-            0b10000000000 => Flag::COMPRESSED,
-            _ => unimplemented!(),
-        }
+        Flag::from(v as Elf64Xword)
     }
 }
 
 impl From<Elf64Xword> for Flag {
     fn from(v: Elf64Xword) -> Self {
         match v {
-            0b1 => Flag::Write,
-            0b10 => Flag::Alloc,
-            0b100 => Flag::ExecInstr,
-            0b1000 => Flag::Merge,
-            0b10000 => Flag::Strings,
-            0b100000 => Flag::InfoLink,
-            0b1000000 => Flag::LinkOrder,
-            0b10000000 => Flag::OSNonConforming,
-            0b100000000 => Flag::Group,
-            0b1000000000 => Flag::TLS,
-            0b10000000000 => Flag::COMPRESSED,
-            _ => unimplemented!(),
+            0x1 => Flag::Write,
+            0x2 => Flag::Alloc,
+            0x4 => Flag::ExecInstr,
+            0x10 => Flag::Merge,
+            0x20 => Flag::Strings,
+            0x40 => Flag::InfoLink,
+            0x80 => Flag::LinkOrder,
+            0x100 => Flag::OSNonConforming,
+            0x200 => Flag::Group,
+            0x400 => Flag::TLS,
+            0x800 => Flag::COMPRESSED,
+            _ => Flag::Any(v),
         }
     }
 }
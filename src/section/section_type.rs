@@ -1,6 +1,7 @@
 //! Type definitions for section header types.
 
 use crate::*;
+use std::fmt;
 
 #[derive(Debug, Clone, Hash, Copy, Eq, Ord, PartialEq, PartialOrd)]
 pub enum Type {
@@ -43,28 +44,28 @@ pub enum Type {
     Any(Elf64Word),
 }
 
-impl Into<Elf64Word> for Type {
-    fn into(self) -> Elf64Word {
-        match self {
-            Self::Null => 0,
-            Self::ProgBits => 1,
-            Self::SymTab => 2,
-            Self::StrTab => 3,
-            Self::Rela => 4,
-            Self::Hash => 5,
-            Self::Dynamic => 6,
-            Self::Note => 7,
-            Self::NoBits => 8,
-            Self::Rel => 9,
-            Self::ShLib => 10,
-            Self::DynSym => 11,
-            Self::InitArray => 14,
-            Self::FiniArray => 15,
-            Self::PreInitArray => 16,
-            Self::Group => 17,
-            Self::SymTabShNdx => 18,
-            Self::Num => 19,
-            Self::Any(c) => c,
+impl From<Type> for Elf64Word {
+    fn from(ty: Type) -> Self {
+        match ty {
+            Type::Null => 0,
+            Type::ProgBits => 1,
+            Type::SymTab => 2,
+            Type::StrTab => 3,
+            Type::Rela => 4,
+            Type::Hash => 5,
+            Type::Dynamic => 6,
+            Type::Note => 7,
+            Type::NoBits => 8,
+            Type::Rel => 9,
+            Type::ShLib => 10,
+            Type::DynSym => 11,
+            Type::InitArray => 14,
+            Type::FiniArray => 15,
+            Type::PreInitArray => 16,
+            Type::Group => 17,
+            Type::SymTabShNdx => 18,
+            Type::Num => 19,
+            Type::Any(c) => c,
         }
     }
 }
@@ -94,3 +95,57 @@ impl From<Elf64Word> for Type {
         }
     }
 }
+
+/// Display as readelf does.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::section;
+///
+/// assert_eq!("PROGBITS", section::Type::ProgBits.to_string());
+/// assert_eq!("GNU_HASH", section::Type::Any(0x6ffffff6).to_string());
+/// assert_eq!("LOOS+0x10", section::Type::Any(0x60000010).to_string());
+/// ```
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let v = Elf64Word::from(*self);
+        let name = match v {
+            0 => "NULL",
+            1 => "PROGBITS",
+            2 => "SYMTAB",
+            3 => "STRTAB",
+            4 => "RELA",
+            5 => "HASH",
+            6 => "DYNAMIC",
+            7 => "NOTE",
+            8 => "NOBITS",
+            9 => "REL",
+            10 => "SHLIB",
+            11 => "DYNSYM",
+            14 => "INIT_ARRAY",
+            15 => "FINI_ARRAY",
+            16 => "PREINIT_ARRAY",
+            17 => "GROUP",
+            18 => "SYMTAB SECTION INDICES",
+            19 => "RELR",
+            0x6fff4c00 => "LLVM_ODRTAB",
+            0x6fff4c01 => "LLVM_LINKER_OPTIONS",
+            0x6fff4c02 => "LLVM_CALL_GRAPH_PROFILE",
+            0x6fff4c03 => "LLVM_ADDRSIG",
+            0x6fff4c04 => "LLVM_DEPENDENT_LIBRARIES",
+            0x6fff4c05 => "LLVM_SYMPART",
+            0x6ffffff5 => "GNU_ATTRIBUTES",
+            0x6ffffff6 => "GNU_HASH",
+            0x6ffffff7 => "GNU_LIBLIST",
+            0x6ffffffd => "VERDEF",
+            0x6ffffffe => "VERNEED",
+            0x6fffffff => "VERSYM",
+            0x60000000..=0x6fffffff => return write!(f, "LOOS+{:#x}", v - 0x60000000),
+            0x70000000..=0x7fffffff => return write!(f, "LOPROC+{:#x}", v - 0x70000000),
+            0x80000000..=0xffffffff => return write!(f, "LOUSER+{:#x}", v - 0x80000000),
+            _ => return write!(f, "<unknown>: {:#x}", v),
+        };
+        write!(f, "{}", name)
+    }
+}
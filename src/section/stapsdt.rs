@@ -0,0 +1,203 @@
+//! `.note.stapsdt` section utilities, which describe the USDT probes of SystemTap's `<sys/sdt.h>`.
+
+use crate::*;
+use section::{PayloadReader, ReadError};
+
+/// the note type of the probes
+pub const NT_STAPSDT: Elf64Word = 3;
+/// the owner of the probe notes
+pub const STAPSDT_OWNER: &str = "stapsdt";
+/// the section of the probe notes
+pub const STAPSDT_SECTION: &str = ".note.stapsdt";
+/// the section whose address the notes record, to detect prelinking
+pub const STAPSDT_BASE_SECTION: &str = ".stapsdt.base";
+/// the section of the semaphores
+pub const PROBES_SECTION: &str = ".probes";
+
+/// A USDT probe in `.note.stapsdt`
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Probe {
+    pub provider: String,
+    pub name: String,
+    /// the address of the probe's `nop`
+    pub pc: Elf64Addr,
+    /// the address of `.stapsdt.base` when the note was written
+    pub base: Elf64Addr,
+    /// the address of the semaphore counting the tracers, 0 if the probe has none
+    pub semaphore: Elf64Addr,
+    /// the arguments, e.g. `-4@%edi 8@%rsi`
+    pub arguments: String,
+}
+
+impl Probe {
+    /// parse the probes in the notes(in ELF64). the notes of the other types are skipped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use elf_utilities::{header, section};
+    ///
+    /// let mut note = vec![8, 0, 0, 0, 0x25, 0, 0, 0, 3, 0, 0, 0];
+    /// note.extend_from_slice(b"stapsdt\0");
+    /// note.extend_from_slice(&[0x29, 0x11, 0, 0, 0, 0, 0, 0]);
+    /// note.extend_from_slice(&[0x04, 0x20, 0, 0, 0, 0, 0, 0]);
+    /// note.extend_from_slice(&[0; 8]);
+    /// note.extend_from_slice(b"myapp\0start\0\0\0\0\0");
+    ///
+    /// let probes = section::Probe::parse_notes(&note, header::Data::LSB2).unwrap();
+    /// assert_eq!("myapp:start", probes[0].to_string());
+    /// assert_eq!(0x1129, probes[0].pc);
+    /// assert!(probes[0].args().is_empty());
+    /// ```
+    pub fn parse_notes(bytes: &[u8], data: header::Data) -> Result<Vec<Self>, ReadError> {
+        let mut r = PayloadReader::new(bytes, data);
+        let mut probes = Vec::new();
+        while !r.is_empty() {
+            let namesz = r.u32()? as usize;
+            let descsz = r.u32()? as usize;
+            let ty = r.u32()?;
+            let name = r.bytes(namesz)?;
+            r.align(4)?;
+            let desc_end = r.position() + descsz;
+            if ty != NT_STAPSDT || name.strip_suffix(b"\0") != Some(STAPSDT_OWNER.as_bytes()) {
+                r.skip(descsz)?;
+                r.align(4)?;
+                continue;
+            }
+
+            let pc = r.u64()?;
+            let base = r.u64()?;
+            let semaphore = r.u64()?;
+            let provider = r.cstr()?;
+            let name = r.cstr()?;
+            let arguments = r.cstr()?;
+            if r.position() > desc_end {
+                return Err(ReadError::UnexpectedEnd {
+                    offset: desc_end,
+                    needed: r.position() - desc_end,
+                });
+            }
+            r.seek(desc_end)?;
+            r.align(4)?;
+            probes.push(Self {
+                provider,
+                name,
+                pc,
+                base,
+                semaphore,
+                arguments,
+            });
+        }
+        Ok(probes)
+    }
+
+    /// the arguments separated, e.g. `["-4@%edi", "8@%rsi"]`.
+    pub fn args(&self) -> Vec<&str> {
+        self.arguments.split_whitespace().collect()
+    }
+
+    /// the probe moved by the difference between `base` and the actual address of `.stapsdt.base`,
+    /// as the tracers do for prelinked files.
+    pub fn relocated(&self, base_addr: Elf64Addr) -> Self {
+        let delta = base_addr.wrapping_sub(self.base);
+        Self {
+            pc: self.pc.wrapping_add(delta),
+            base: base_addr,
+            semaphore: if self.semaphore == 0 {
+                0
+            } else {
+                self.semaphore.wrapping_add(delta)
+            },
+            ..self.clone()
+        }
+    }
+}
+
+impl std::fmt::Display for Probe {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.provider, self.name)
+    }
+}
+
+impl file::ELF64 {
+    /// the probes in `.note.stapsdt` if exists, relocated by the address of `.stapsdt.base`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use elf_utilities::parser;
+    ///
+    /// let elf = parser::parse_elf64("tests/fixtures/usdt").unwrap();
+    /// let probes = elf.stapsdt_probes().unwrap().unwrap();
+    /// assert_eq!("myapp:request", probes[0].to_string());
+    /// assert_eq!(vec!["-4@%edi", "8@%rsi"], probes[0].args());
+    ///
+    /// // the semaphore is in .probes
+    /// let offset = elf.stapsdt_semaphore_offset(&probes[0]).unwrap();
+    /// assert_eq!(0x3010, offset);
+    /// ```
+    pub fn stapsdt_probes(&self) -> Option<Result<Vec<Probe>, ReadError>> {
+        let sct = self.first_section_by(|sct| {
+            sct.header.get_type() == section::Type::Note && sct.name == STAPSDT_SECTION
+        })?;
+        let probes = match Probe::parse_notes(&sct.to_le_bytes(), self.ehdr.get_data()) {
+            Ok(probes) => probes,
+            Err(e) => return Some(Err(e)),
+        };
+        // .stapsdt.base がなければ(e.g. 古いsdt.h)調整しない
+        let base = self.first_section_by(|sct| sct.name == STAPSDT_BASE_SECTION);
+        Some(Ok(match base {
+            Some(base) => probes
+                .iter()
+                .map(|probe| probe.relocated(base.header.sh_addr))
+                .collect(),
+            None => probes,
+        }))
+    }
+
+    /// the file offset of the probe's semaphore, which uprobes takes as the reference counter.
+    pub fn stapsdt_semaphore_offset(&self, probe: &Probe) -> Option<Elf64Off> {
+        if probe.semaphore == 0 {
+            return None;
+        }
+        let sct = self.section_containing_vaddr(probe.semaphore)?;
+        if sct.header.get_type() == section::Type::NoBits {
+            return None;
+        }
+        Some(probe.semaphore - sct.header.sh_addr + sct.header.sh_offset)
+    }
+}
+
+#[cfg(test)]
+mod stapsdt_tests {
+    use super::*;
+
+    #[test]
+    fn stapsdt_probes_test() {
+        let mut elf = parser::parse_elf64("tests/fixtures/usdt").unwrap();
+        let probes = elf.stapsdt_probes().unwrap().unwrap();
+        assert_eq!(2, probes.len());
+        assert_eq!(
+            ("myapp", "start"),
+            (probes[1].provider.as_str(), probes[1].name.as_str())
+        );
+        assert_eq!(None, elf.stapsdt_semaphore_offset(&probes[1]));
+
+        let semaphore = elf
+            .first_section_by(|sct| sct.name == PROBES_SECTION)
+            .unwrap()
+            .header
+            .sh_addr;
+        assert_eq!(semaphore, probes[0].semaphore);
+
+        // prelinkでずらされた場合
+        let shidx = elf
+            .first_shidx_by(|sct| sct.name == STAPSDT_BASE_SECTION)
+            .unwrap();
+        elf.sections[shidx].header.sh_addr += 0x1000;
+        let moved = elf.stapsdt_probes().unwrap().unwrap();
+        assert_eq!(probes[0].pc + 0x1000, moved[0].pc);
+        assert_eq!(probes[0].semaphore + 0x1000, moved[0].semaphore);
+        assert_eq!(0, moved[1].semaphore);
+    }
+}
@@ -0,0 +1,223 @@
+//! A builder for string table sections.
+
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+use super::{Contents32, Contents64, ReadError, StrTabEntry};
+
+/// StringTable builds the contents of a string table section(`.strtab`, `.dynstr`, etc.)
+/// and keeps track of each string's offset.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::section::StringTable;
+///
+/// let mut tab = StringTable::new();
+/// assert_eq!(1, tab.add("libc.so.6"));
+/// assert_eq!(11, tab.add("libm.so.6"));
+///
+/// // the same string is stored only once.
+/// assert_eq!(1, tab.add("libc.so.6"));
+/// assert_eq!(0, tab.add(""));
+/// assert_eq!(21, tab.size());
+/// ```
+#[derive(Debug, Clone)]
+pub struct StringTable {
+    entries: Vec<StrTabEntry>,
+    size: usize,
+    // 文字列からオフセットを引くための索引(entriesから導出できるので比較には使わない)
+    index: HashMap<String, usize>,
+}
+
+impl Default for StringTable {
+    fn default() -> Self {
+        // 文字列テーブルは必ずnull-byteから始まる
+        Self {
+            entries: Vec::new(),
+            size: 1,
+            index: HashMap::new(),
+        }
+    }
+}
+
+impl PartialEq for StringTable {
+    fn eq(&self, other: &Self) -> bool {
+        self.entries == other.entries && self.size == other.size
+    }
+}
+
+impl Eq for StringTable {}
+
+impl PartialOrd for StringTable {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for StringTable {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (&self.entries, self.size).cmp(&(&other.entries, other.size))
+    }
+}
+
+impl Hash for StringTable {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.entries.hash(state);
+        self.size.hash(state);
+    }
+}
+
+impl StringTable {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// create a table from the strings of parsed entries.
+    ///
+    /// The strings are laid out again, so duplicated and empty entries are dropped
+    /// and the offsets may change.
+    /// Use [`StringTable::from_parsed`] to keep the offsets which are referred by other sections.
+    pub fn from_entries(entries: Vec<StrTabEntry>) -> Self {
+        let mut tab = Self::new();
+        for ent in entries.iter() {
+            tab.add(&ent.v);
+        }
+        tab
+    }
+
+    /// create a table which keeps the offsets of parsed entries.
+    /// strings added later are appended after them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use elf_utilities::section::{StrTabEntry, StringTable};
+    ///
+    /// // "\0libc.so.6\0\0libc.so.6\0" のように重複とパディングを含むテーブル
+    /// let entries = vec![
+    ///     StrTabEntry { v: "libc.so.6".to_string(), idx: 1 },
+    ///     StrTabEntry { v: "libc.so.6".to_string(), idx: 12 },
+    /// ];
+    /// let mut tab = StringTable::from_parsed(entries);
+    /// assert_eq!(Some("libc.so.6"), tab.get(12));
+    /// assert_eq!(1, tab.add("libc.so.6"));
+    /// assert_eq!(22, tab.add("libm.so.6"));
+    /// assert_eq!(32, tab.size());
+    /// ```
+    pub fn from_parsed(mut entries: Vec<StrTabEntry>) -> Self {
+        entries.sort_by_key(|ent| ent.idx);
+
+        let mut tab = Self::new();
+        for ent in entries {
+            // 前の文字列と重なるエントリはバイト列として表現できない
+            if ent.idx < tab.size {
+                continue;
+            }
+            // 空のエントリはnull-byte1つになるので，それで隙間を埋める
+            while tab.size < ent.idx {
+                tab.entries.push(StrTabEntry {
+                    v: String::new(),
+                    idx: tab.size,
+                });
+                tab.size += 1;
+            }
+
+            if !ent.v.is_empty() {
+                tab.index.entry(ent.v.clone()).or_insert(ent.idx);
+            }
+            tab.size += ent.v.len() + 1;
+            tab.entries.push(ent);
+        }
+        tab
+    }
+
+    /// add a string and return its offset.
+    /// if the string already exists, the offset of existing one is returned.
+    pub fn add(&mut self, s: &str) -> usize {
+        if s.is_empty() {
+            return 0;
+        }
+        if let Some(idx) = self.offset_of(s) {
+            return idx;
+        }
+
+        let idx = self.size;
+        self.entries.push(StrTabEntry {
+            v: s.to_string(),
+            idx,
+        });
+        self.index.insert(s.to_string(), idx);
+        self.size += s.len() + 1;
+        idx
+    }
+
+    /// get the offset of the string if exists.
+    pub fn offset_of(&self, s: &str) -> Option<usize> {
+        if s.is_empty() {
+            return Some(0);
+        }
+        self.index.get(s).copied()
+    }
+
+    /// get the string which starts at `offset`.
+    pub fn get(&self, offset: usize) -> Option<&str> {
+        if offset == 0 {
+            return Some("");
+        }
+        self.entries
+            .iter()
+            .find(|ent| ent.idx <= offset && offset < ent.idx + ent.v.len())
+            .map(|ent| &ent.v[offset - ent.idx..])
+    }
+
+    /// the size of the table in bytes.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn entries(&self) -> &[StrTabEntry] {
+        &self.entries
+    }
+
+    pub fn to_contents64(&self) -> Contents64 {
+        Contents64::StrTab(self.entries.clone())
+    }
+    pub fn to_contents32(&self) -> Contents32 {
+        Contents32::StrTab(self.entries.clone())
+    }
+}
+
+/// the NUL-terminated string at `offset` in the raw bytes of a string table.
+///
+/// The offset and the terminating NUL must be in `table`,
+/// so a corrupt `sh_name` or `st_name` never reads past the section.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::section;
+///
+/// let table = b"\0.text\0.data\0";
+/// assert_eq!(Ok(".text"), section::read_name(table, 1));
+/// // 文字列の途中を指すこともできる
+/// assert_eq!(Ok("data"), section::read_name(table, 8));
+/// assert_eq!(Ok(""), section::read_name(table, 0));
+///
+/// assert!(section::read_name(table, 14).is_err());
+/// assert_eq!(
+///     Err(section::ReadError::UnterminatedString { offset: 1 }),
+///     section::read_name(b"\0.text", 1)
+/// );
+/// ```
+pub fn read_name(table: &[u8], offset: usize) -> Result<&str, ReadError> {
+    let rest = table
+        .get(offset..)
+        .filter(|rest| !rest.is_empty())
+        .ok_or(ReadError::UnexpectedEnd { offset, needed: 1 })?;
+    let len = rest
+        .iter()
+        .position(|b| *b == 0)
+        .ok_or(ReadError::UnterminatedString { offset })?;
+    std::str::from_utf8(&rest[..len]).map_err(|_| ReadError::InvalidString { offset })
+}
@@ -3,6 +3,8 @@
 mod base;
 mod elf32;
 mod elf64;
+mod note;
+mod relro;
 mod segment_flag;
 mod segment_type;
 
@@ -10,5 +12,7 @@ mod segment_type;
 pub use base::*;
 pub use elf32::*;
 pub use elf64::*;
+pub use note::*;
+pub use relro::*;
 pub use segment_flag::*;
 pub use segment_type::*;
@@ -1,6 +1,8 @@
 //! Type definitions for 32-bit ELF binaries.
 
+use crate::flags_debug::FlagsDebug;
 use crate::*;
+use std::fmt;
 
 use crate::segment::*;
 use serde::{Deserialize, Serialize};
@@ -18,7 +20,7 @@ impl Segment32 {
 }
 
 #[repr(C)]
-#[derive(Debug, Clone, Copy, Hash, PartialOrd, Ord, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Default, Clone, Copy, Hash, PartialOrd, Ord, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Phdr32 {
     /// Segment type
     pub p_type: Elf32Word,
@@ -45,18 +47,25 @@ pub struct Phdr32 {
     pub p_align: Elf32Word,
 }
 
-impl Default for Phdr32 {
-    fn default() -> Self {
-        Self {
-            p_type: 0,
-            p_flags: 0,
-            p_offset: 0,
-            p_vaddr: 0,
-            p_paddr: 0,
-            p_filesz: 0,
-            p_memsz: 0,
-            p_align: 0,
-        }
+impl fmt::Debug for Phdr32 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Phdr32")
+            .field("p_type", &self.get_type())
+            .field(
+                "p_flags",
+                &FlagsDebug {
+                    bits: self.p_flags as u64,
+                    all: &segment::Flag::ALL,
+                    to_bits: |f| Elf64Word::from(f) as u64,
+                },
+            )
+            .field("p_offset", &self.p_offset)
+            .field("p_vaddr", &self.p_vaddr)
+            .field("p_paddr", &self.p_paddr)
+            .field("p_filesz", &self.p_filesz)
+            .field("p_memsz", &self.p_memsz)
+            .field("p_align", &self.p_align)
+            .finish()
     }
 }
 
@@ -68,15 +77,25 @@ impl Phdr32 {
     }
 
     // setter
+    /// add the flags to `p_flags`.
     pub fn set_flags<'a, I>(&mut self, flags: I)
     where
         I: Iterator<Item = &'a segment::Flag>,
     {
         for flag in flags {
-            self.p_flags = self.p_flags | Into::<Elf32Word>::into(*flag);
+            self.p_flags |= Into::<Elf32Word>::into(*flag);
         }
     }
 
+    /// replace `p_flags` with the flags.
+    pub fn replace_flags<'a, I>(&mut self, flags: I)
+    where
+        I: Iterator<Item = &'a segment::Flag>,
+    {
+        self.p_flags = 0;
+        self.set_flags(flags);
+    }
+
     /// # Examples
     ///
     /// ```
@@ -2,7 +2,9 @@
 
 use std::collections::HashSet;
 
+use crate::flags_debug::FlagsDebug;
 use crate::*;
+use std::fmt;
 
 use crate::segment::*;
 use serde::{Deserialize, Serialize};
@@ -15,7 +17,7 @@ pub struct Segment64 {
 }
 
 #[repr(C)]
-#[derive(Debug, Clone, Copy, Hash, PartialOrd, Ord, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Default, Clone, Copy, Hash, PartialOrd, Ord, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Phdr64 {
     /// Segment type
     pub p_type: Elf64Word,
@@ -42,18 +44,25 @@ pub struct Phdr64 {
     pub p_align: Elf64Xword,
 }
 
-impl Default for Phdr64 {
-    fn default() -> Self {
-        Self {
-            p_type: 0,
-            p_flags: 0,
-            p_offset: 0,
-            p_vaddr: 0,
-            p_paddr: 0,
-            p_filesz: 0,
-            p_memsz: 0,
-            p_align: 0,
-        }
+impl fmt::Debug for Phdr64 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Phdr64")
+            .field("p_type", &self.get_type())
+            .field(
+                "p_flags",
+                &FlagsDebug {
+                    bits: self.p_flags as u64,
+                    all: &segment::Flag::ALL,
+                    to_bits: |f| Elf64Word::from(f) as u64,
+                },
+            )
+            .field("p_offset", &self.p_offset)
+            .field("p_vaddr", &self.p_vaddr)
+            .field("p_paddr", &self.p_paddr)
+            .field("p_filesz", &self.p_filesz)
+            .field("p_memsz", &self.p_memsz)
+            .field("p_align", &self.p_align)
+            .finish()
     }
 }
 
@@ -80,6 +89,36 @@ impl Phdr64 {
         flags
     }
 
+    /// the load memory address(LMA), where the file image is stored before the program runs.
+    /// it differs from `p_vaddr` if the startup code copies the segment, e.g. `.data` in flash.
+    pub fn lma(&self) -> Elf64Addr {
+        self.p_paddr
+    }
+
+    /// the LMA of the virtual address in the segment, `None` if the segment doesn't cover it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use elf_utilities::segment;
+    ///
+    /// let phdr = segment::Phdr64 {
+    ///     p_vaddr: 0x2000_0000,
+    ///     p_paddr: 0x0800_1000,
+    ///     p_memsz: 0x100,
+    ///     ..Default::default()
+    /// };
+    /// assert_eq!(Some(0x0800_1010), phdr.lma_of(0x2000_0010));
+    /// assert_eq!(None, phdr.lma_of(0x2000_0100));
+    /// ```
+    pub fn lma_of(&self, vaddr: Elf64Addr) -> Option<Elf64Addr> {
+        if self.p_vaddr <= vaddr && vaddr < self.p_vaddr + self.p_memsz {
+            Some(self.p_paddr + (vaddr - self.p_vaddr))
+        } else {
+            None
+        }
+    }
+
     // setter
     /// # Examples
     ///
@@ -95,15 +134,53 @@ impl Phdr64 {
         self.p_type = ptype.to_bytes();
     }
 
+    pub fn set_lma(&mut self, lma: Elf64Addr) {
+        self.p_paddr = lma;
+    }
+
+    /// add the flags to `p_flags`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use elf_utilities::segment;
+    ///
+    /// let mut phdr : segment::Phdr64 = Default::default();
+    /// phdr.set_flags([segment::Flag::R].iter());
+    /// phdr.set_flags([segment::Flag::X].iter());
+    ///
+    /// assert_eq!(phdr.p_flags, 0b101);
+    /// ```
     pub fn set_flags<'a, I>(&mut self, flags: I)
     where
         I: Iterator<Item = &'a segment::Flag>,
     {
         for flag in flags {
-            self.p_flags = self.p_flags | Into::<Elf64Word>::into(*flag);
+            self.p_flags |= Into::<Elf64Word>::into(*flag);
         }
     }
 
+    /// replace `p_flags` with the flags.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use elf_utilities::segment;
+    ///
+    /// let mut phdr : segment::Phdr64 = Default::default();
+    /// phdr.set_flags([segment::Flag::R, segment::Flag::W, segment::Flag::X].iter());
+    /// phdr.replace_flags([segment::Flag::R, segment::Flag::X].iter());
+    ///
+    /// assert_eq!(phdr.p_flags, 0b101);
+    /// ```
+    pub fn replace_flags<'a, I>(&mut self, flags: I)
+    where
+        I: Iterator<Item = &'a segment::Flag>,
+    {
+        self.p_flags = 0;
+        self.set_flags(flags);
+    }
+
     /// Create Vec<u8> from this.
     ///
     /// # Examples
@@ -0,0 +1,102 @@
+//! `PT_NOTE` utilities.
+
+use crate::*;
+
+use crate::segment::*;
+
+/// compute the `PT_NOTE` segments which cover the note sections.
+///
+/// Only `SHF_ALLOC` note sections are covered, because the loader reads notes from memory.
+/// Note sections which are adjacent in both the file and memory, and have the same alignment,
+/// are merged into one segment.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::{file, layout, section, segment};
+///
+/// let mut elf = file::ELF64::default();
+/// for name in [".note.gnu.build-id", ".note.ABI-tag"].iter() {
+///     let mut sct = section::Section64::new(
+///         name.to_string(),
+///         section::ShdrPreparation64::default()
+///             .ty(section::Type::Note)
+///             .flags([section::Flag::Alloc].iter()),
+///         section::Contents64::Raw(vec![0; 0x10]),
+///     );
+///     sct.header.sh_addralign = 4;
+///     elf.add_section(sct);
+/// }
+/// layout::Layout::new().apply(&mut elf).unwrap();
+///
+/// let notes = segment::compute_note_segments(&elf);
+/// assert_eq!(1, notes.len());
+/// assert_eq!(segment::Type::Note, notes[0].get_type());
+/// assert_eq!(elf.sections[1].header.sh_offset, notes[0].p_offset);
+/// assert_eq!(0x20, notes[0].p_filesz);
+/// ```
+pub fn compute_note_segments(elf: &file::ELF64) -> Vec<Phdr64> {
+    note_groups(elf, |prev, next| {
+        let prev = &elf.sections[prev].header;
+        let next = &elf.sections[next].header;
+        let align = next.sh_addralign.max(1);
+        next.sh_offset == layout::align_up(prev.sh_offset + prev.sh_size, align)
+            && next.sh_addr == layout::align_up(prev.sh_addr + prev.sh_size, align)
+    })
+    .iter()
+    .map(|shidxs| note_phdr(elf, shidxs))
+    .collect()
+}
+
+/// group the note sections which are next to each other in the section header table.
+/// `mergeable` decides whether two neighboring note sections can share a segment.
+pub(crate) fn note_groups<F>(elf: &file::ELF64, mergeable: F) -> Vec<Vec<usize>>
+where
+    F: Fn(usize, usize) -> bool,
+{
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    let mut prev: Option<usize> = None;
+
+    for (shidx, sct) in elf.sections.iter().enumerate().skip(1) {
+        if !is_alloc_note(&sct.header) {
+            prev = None;
+            continue;
+        }
+
+        // 4バイト境界のノートと8バイト境界のノートは同じセグメントに入れられない
+        match prev {
+            Some(p)
+                if elf.sections[p].header.sh_addralign == sct.header.sh_addralign
+                    && elf.sections[p].header.sh_flags == sct.header.sh_flags
+                    && mergeable(p, shidx) =>
+            {
+                groups.last_mut().unwrap().push(shidx);
+            }
+            _ => groups.push(vec![shidx]),
+        }
+        prev = Some(shidx);
+    }
+
+    groups
+}
+
+/// create a `PT_NOTE` segment which covers the sections.
+pub(crate) fn note_phdr(elf: &file::ELF64, shidxs: &[usize]) -> Phdr64 {
+    let first = &elf.sections[shidxs[0]].header;
+    let last = &elf.sections[shidxs[shidxs.len() - 1]].header;
+
+    let mut phdr = Phdr64::default();
+    phdr.set_type(Type::Note);
+    phdr.set_flags([Flag::R].iter());
+    phdr.p_offset = first.sh_offset;
+    phdr.p_vaddr = first.sh_addr;
+    phdr.p_paddr = first.sh_addr;
+    phdr.p_filesz = last.sh_offset + last.sh_size - first.sh_offset;
+    phdr.p_memsz = phdr.p_filesz;
+    phdr.p_align = first.sh_addralign.max(1);
+    phdr
+}
+
+fn is_alloc_note(shdr: &section::Shdr64) -> bool {
+    shdr.get_type() == section::Type::Note && layout::is_alloc(shdr)
+}
@@ -0,0 +1,193 @@
+//! `PT_GNU_RELRO` utilities.
+
+use crate::*;
+
+use crate::segment::*;
+use thiserror::Error as TError;
+
+/// sections which become read-only after relocation
+pub const RELRO_SECTIONS: [&str; 5] = [
+    ".init_array",
+    ".fini_array",
+    ".dynamic",
+    ".got",
+    ".data.rel.ro",
+];
+
+/// check the section becomes read-only after relocation.
+///
+/// Besides [`RELRO_SECTIONS`], the suffixed names which linkers merge into them
+/// (`.data.rel.ro.local`, `.init_array.00100`, etc.) are relro sections.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::segment;
+///
+/// assert!(segment::is_relro_section(".got"));
+/// assert!(segment::is_relro_section(".data.rel.ro.local"));
+/// assert!(segment::is_relro_section(".init_array.00100"));
+/// assert!(!segment::is_relro_section(".got.plt"));
+/// assert!(!segment::is_relro_section(".data"));
+/// ```
+pub fn is_relro_section(name: &str) -> bool {
+    RELRO_SECTIONS.contains(&name)
+        || [".data.rel.ro.", ".init_array.", ".fini_array."]
+            .iter()
+            .any(|prefix| name.starts_with(prefix))
+}
+
+#[derive(TError, Debug)]
+pub enum RelroError {
+    #[error("GNU_RELRO segment is not contained in a writable LOAD segment")]
+    NotInWritableLoad,
+    #[error("GNU_RELRO segment makes writable section `{name}` read-only")]
+    CoversWritableSection { name: String },
+}
+
+/// compute the `PT_GNU_RELRO` segment which covers the relro sections.
+///
+/// The end of the region is aligned to the page size(`p_align` of the containing `PT_LOAD`),
+/// because the dynamic linker protects only whole pages.
+/// If there are no relro sections, the segment's size is zero.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::{builder, segment};
+///
+/// let elf = builder::SharedObjectWriter::new()
+///     .function(builder::ExportedFunction::new("f", vec![0xc3]))
+///     .build()
+///     .unwrap();
+///
+/// let relro = segment::compute_relro(&elf);
+/// let dynamic = elf.first_section_by(|sct| sct.name == ".dynamic").unwrap();
+/// assert_eq!(segment::Type::GNURelRO, relro.get_type());
+/// assert_eq!(dynamic.header.sh_addr, relro.p_vaddr);
+/// assert_eq!(0, (relro.p_vaddr + relro.p_memsz) % 0x1000);
+/// assert!(segment::validate_relro(&elf, &relro).is_ok());
+/// ```
+pub fn compute_relro(elf: &file::ELF64) -> Phdr64 {
+    let mut phdr = Phdr64::default();
+    phdr.set_type(Type::GNURelRO);
+    phdr.set_flags([Flag::R].iter());
+    phdr.p_align = 1;
+
+    let headers: Vec<&section::Shdr64> = elf
+        .sections
+        .iter()
+        .filter(|sct| is_relro_section(&sct.name))
+        .filter(|sct| layout::is_alloc(&sct.header))
+        .map(|sct| &sct.header)
+        .collect();
+    let first = match headers.iter().min_by_key(|h| h.sh_addr) {
+        Some(h) => h,
+        None => return phdr,
+    };
+
+    let mem_end = headers.iter().map(|h| h.sh_addr + h.sh_size).max().unwrap();
+    let file_end = headers
+        .iter()
+        .filter(|h| !layout::is_nobits(h))
+        .map(|h| h.sh_offset + h.sh_size)
+        .max()
+        .unwrap_or(first.sh_offset);
+    let page_size = containing_load(elf, first.sh_addr)
+        .map_or(layout::DEFAULT_PAGE_SIZE, |load| load.p_align.max(1));
+
+    phdr.p_offset = first.sh_offset;
+    phdr.p_vaddr = first.sh_addr;
+    phdr.p_paddr = first.sh_addr;
+    phdr.p_filesz = file_end - first.sh_offset;
+    phdr.p_memsz = layout::align_up(mem_end, page_size) - first.sh_addr;
+    phdr
+}
+
+/// check the `PT_GNU_RELRO` segment doesn't protect any other writable sections.
+pub fn validate_relro(elf: &file::ELF64, relro: &Phdr64) -> Result<(), RelroError> {
+    if relro.p_memsz == 0 {
+        return Ok(());
+    }
+    let load = containing_load(elf, relro.p_vaddr).ok_or(RelroError::NotInWritableLoad)?;
+    if load.p_flags & Elf64Word::from(Flag::W) == 0 {
+        return Err(RelroError::NotInWritableLoad);
+    }
+
+    // 動的リンカはページ単位で保護するので，先頭のページ全体が対象になる
+    let start = relro.p_vaddr / load.p_align.max(1) * load.p_align.max(1);
+    let end = relro.p_vaddr + relro.p_memsz;
+    let writable: Elf64Xword = section::Flag::Write.into();
+    for sct in elf.sections.iter() {
+        if !layout::is_alloc(&sct.header)
+            || sct.header.sh_flags & writable == 0
+            || is_relro_section(&sct.name)
+        {
+            continue;
+        }
+        let sct_end = sct.header.sh_addr + sct.header.sh_size;
+        if sct.header.sh_addr < end && start < sct_end {
+            return Err(RelroError::CoversWritableSection {
+                name: sct.name.to_string(),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+fn containing_load(elf: &file::ELF64, addr: Elf64Addr) -> Option<&Phdr64> {
+    elf.segments
+        .iter()
+        .map(|sgt| &sgt.header)
+        .filter(|phdr| phdr.get_type() == Type::Load)
+        .find(|phdr| phdr.p_vaddr <= addr && addr < phdr.p_vaddr + phdr.p_memsz)
+}
+
+#[cfg(test)]
+mod relro_tests {
+    use super::*;
+
+    #[test]
+    fn validate_relro_test() {
+        let mut elf = crate::builder::SharedObjectWriter::new()
+            .function(crate::builder::ExportedFunction::new("f", vec![0xc3]))
+            .build()
+            .unwrap();
+        let relro = compute_relro(&elf);
+        assert!(validate_relro(&elf, &relro).is_ok());
+
+        // .dynamicと同じページに書き込み可能なセクションを置く
+        let dynamic = elf.first_shidx_by(|sct| sct.name == ".dynamic").unwrap();
+        let mut data = elf.sections[dynamic].clone();
+        data.name = ".data".into();
+        data.header.sh_addr += data.header.sh_size;
+        elf.sections.push(data);
+        assert!(matches!(
+            validate_relro(&elf, &relro),
+            Err(RelroError::CoversWritableSection { name }) if name == ".data"
+        ));
+    }
+
+    #[test]
+    fn data_rel_ro_test() {
+        let mut elf = crate::builder::SharedObjectWriter::new()
+            .function(crate::builder::ExportedFunction::new("f", vec![0xc3]))
+            .build()
+            .unwrap();
+
+        // .dynamicの直後に.data.rel.roを置くと，GNU_RELROがそこまで伸びる
+        let dynamic = elf.first_shidx_by(|sct| sct.name == ".dynamic").unwrap();
+        let mut data = elf.sections[dynamic].clone();
+        data.name = ".data.rel.ro".into();
+        data.header.sh_addr += data.header.sh_size;
+        data.header.sh_offset += data.header.sh_size;
+        elf.sections.push(data);
+
+        let relro = compute_relro(&elf);
+        let dynamic = &elf.sections[dynamic].header;
+        assert_eq!(dynamic.sh_addr, relro.p_vaddr);
+        assert_eq!(dynamic.sh_size * 2, relro.p_filesz);
+        assert!(validate_relro(&elf, &relro).is_ok());
+    }
+}
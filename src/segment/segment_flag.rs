@@ -11,14 +11,22 @@ pub enum Flag {
     W,
     /// segment is readable
     R,
+    /// OS/processor specific or unknown flag
+    Any(Elf64Word),
 }
 
-impl Into<Elf64Word> for Flag {
-    fn into(self) -> Elf64Word {
-        match self {
+impl Flag {
+    /// every flag in the order of its bit
+    pub const ALL: [Flag; 3] = [Flag::X, Flag::W, Flag::R];
+}
+
+impl From<Flag> for Elf64Word {
+    fn from(flag: Flag) -> Self {
+        match flag {
             Flag::X => 1 << 0,
             Flag::W => 1 << 1,
             Flag::R => 1 << 2,
+            Flag::Any(v) => v,
         }
     }
 }
@@ -29,7 +37,7 @@ impl From<Elf64Word> for Flag {
             0b1 => Flag::X,
             0b10 => Flag::W,
             0b100 => Flag::R,
-            _ => unimplemented!(),
+            _ => Flag::Any(v),
         }
     }
 }
@@ -1,8 +1,9 @@
 //! Type definitions for segment types.
 
 use crate::*;
+use std::fmt;
 
-#[derive(Debug, Eq, Ord, PartialEq, PartialOrd)]
+#[derive(Debug, Clone, Copy, Hash, PartialOrd, Ord, PartialEq, Eq)]
 pub enum Type {
     /// Program header table entry unused
     Null,
@@ -71,3 +72,38 @@ impl From<Elf64Word> for Type {
         }
     }
 }
+
+/// Display as readelf does.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::segment;
+///
+/// assert_eq!("LOAD", segment::Type::Load.to_string());
+/// assert_eq!("GNU_STACK", segment::Type::GNUStack.to_string());
+/// ```
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let v = self.to_bytes();
+        let name = match v {
+            0 => "NULL",
+            1 => "LOAD",
+            2 => "DYNAMIC",
+            3 => "INTERP",
+            4 => "NOTE",
+            5 => "SHLIB",
+            6 => "PHDR",
+            7 => "TLS",
+            0x6474e550 => "GNU_EH_FRAME",
+            0x6474e551 => "GNU_STACK",
+            0x6474e552 => "GNU_RELRO",
+            0x6474e553 => "GNU_PROPERTY",
+            0x6474e554 => "GNU_SFRAME",
+            0x60000000..=0x6fffffff => return write!(f, "LOOS+{:#x}", v - 0x60000000),
+            0x70000000..=0x7fffffff => return write!(f, "LOPROC+{:#x}", v - 0x70000000),
+            _ => return write!(f, "<unknown>: {:#x}", v),
+        };
+        write!(f, "{}", name)
+    }
+}
@@ -2,12 +2,16 @@
 
 pub use elf32::*;
 pub use elf64::*;
+pub use handle::*;
 pub use symbol_bind::*;
 pub use symbol_type::*;
 pub use symbol_visibility::*;
+pub use table::*;
 
 mod elf32;
 mod elf64;
+mod handle;
 mod symbol_bind;
 mod symbol_type;
 mod symbol_visibility;
+mod table;
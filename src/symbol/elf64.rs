@@ -62,7 +62,7 @@ pub struct Symbol64 {
     /// option member for utilities.
     #[serde(skip_serializing)]
     #[serde(skip_deserializing)]
-    pub symbol_name: String,
+    pub symbol_name: intern::Name,
 }
 
 #[allow(dead_code)]
@@ -93,6 +93,23 @@ impl Symbol64 {
         symbol::Visibility::from(self.st_other & 0x03)
     }
 
+    /// Set symbol's visibility to Symbol64
+    /// # Examples
+    ///
+    /// ```
+    /// use elf_utilities::symbol;
+    /// let mut sym = symbol::Symbol64::new_null_symbol();
+    /// sym.st_other = 0x80;
+    ///
+    /// sym.set_visibility(symbol::Visibility::Hidden);
+    ///
+    /// assert_eq!(symbol::Visibility::Hidden, sym.get_visibility());
+    /// assert_eq!(0x82, sym.st_other);
+    /// ```
+    pub fn set_visibility(&mut self, visibility: symbol::Visibility) {
+        self.st_other = self.st_other & !0x03 | visibility.to_byte() & 0x03;
+    }
+
     /// Set symbol's information to Symbol64
     /// # Examples
     ///
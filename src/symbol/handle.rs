@@ -0,0 +1,161 @@
+//! Typed handles to `.symtab` and `.dynsym` with their string tables.
+
+use std::ops::Deref;
+
+use crate::*;
+
+/// A symbol table section bound to the string table in its `sh_link`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SymbolSection<'a> {
+    shidx: usize,
+    section: &'a section::Section64,
+    symbols: &'a [symbol::Symbol64],
+    strtab: Option<&'a section::Section64>,
+}
+
+impl<'a> SymbolSection<'a> {
+    fn new(elf: &'a file::ELF64, ty: section::Type) -> Option<Self> {
+        let shidx = elf.first_shidx_by(|sct| sct.header.get_type() == ty)?;
+        let section = &elf.sections[shidx];
+        let symbols = match &section.contents {
+            section::Contents64::Symbols(syms) => syms,
+            _ => return None,
+        };
+        let strtab = elf
+            .sections
+            .get(section.header.sh_link as usize)
+            .filter(|sct| sct.header.get_type() == section::Type::StrTab);
+        Some(Self {
+            shidx,
+            section,
+            symbols,
+            strtab,
+        })
+    }
+
+    /// the index of the symbol table section.
+    pub fn shidx(&self) -> usize {
+        self.shidx
+    }
+
+    pub fn section(&self) -> &'a section::Section64 {
+        self.section
+    }
+
+    /// the string table linked by `sh_link`, if it's `SHT_STRTAB`.
+    pub fn strtab(&self) -> Option<&'a section::Section64> {
+        self.strtab
+    }
+
+    /// all the symbols including the null symbol.
+    pub fn symbols(&self) -> &'a [symbol::Symbol64] {
+        self.symbols
+    }
+
+    pub fn get(&self, idx: usize) -> Option<&'a symbol::Symbol64> {
+        self.symbols.get(idx)
+    }
+
+    /// the index of the first symbol with the name.
+    pub fn position(&self, name: &str) -> Option<usize> {
+        self.symbols
+            .iter()
+            .skip(1)
+            .position(|sym| sym.symbol_name == name)
+            .map(|idx| idx + 1)
+    }
+
+    /// the first defined symbol with the name.
+    pub fn find_defined(&self, name: &str) -> Option<&'a symbol::Symbol64> {
+        self.symbols
+            .iter()
+            .skip(1)
+            .find(|sym| sym.symbol_name == name && sym.st_shndx != section::SHN_UNDEF)
+    }
+
+    /// the string at `st_name` in the bound string table.
+    pub fn name_at(&self, st_name: Elf64Word) -> Option<&'a str> {
+        let offset = st_name as usize;
+        if offset == 0 {
+            return Some("");
+        }
+        match &self.strtab?.contents {
+            section::Contents64::StrTab(entries) => entries
+                .iter()
+                .find(|ent| ent.idx <= offset && offset < ent.idx + ent.v.len())
+                .map(|ent| &ent.v[offset - ent.idx..]),
+            _ => None,
+        }
+    }
+
+    /// the offset of the name in the bound string table, used as `st_name`.
+    pub fn offset_of(&self, name: &str) -> Option<Elf64Word> {
+        if name.is_empty() {
+            return Some(0);
+        }
+        match &self.strtab?.contents {
+            section::Contents64::StrTab(entries) => entries
+                .iter()
+                .find(|ent| ent.v.ends_with(name))
+                .map(|ent| (ent.idx + ent.v.len() - name.len()) as Elf64Word),
+            _ => None,
+        }
+    }
+}
+
+/// The static symbol table(`SHT_SYMTAB`) with `.strtab`, returned by `ELF64::symtab()`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SymTab<'a>(SymbolSection<'a>);
+
+/// The dynamic symbol table(`SHT_DYNSYM`) with `.dynstr`, returned by `ELF64::dynsym()`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DynSym<'a>(SymbolSection<'a>);
+
+impl<'a> Deref for SymTab<'a> {
+    type Target = SymbolSection<'a>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<'a> Deref for DynSym<'a> {
+    type Target = SymbolSection<'a>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl file::ELF64 {
+    /// the first `SHT_SYMTAB`, which stripped files don't have.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use elf_utilities::parser;
+    ///
+    /// let elf = parser::parse_elf64("src/parser/testdata/sample").unwrap();
+    /// let symtab = elf.symtab().unwrap();
+    /// let dynsym = elf.dynsym().unwrap();
+    /// assert_eq!(".strtab", symtab.strtab().unwrap().name.as_ref() as &str);
+    /// assert_eq!(".dynstr", dynsym.strtab().unwrap().name.as_ref() as &str);
+    ///
+    /// // mainは.symtabにしか存在しない
+    /// assert!(symtab.find_defined("main").is_some());
+    /// assert!(dynsym.position("main").is_none());
+    ///
+    /// // st_nameはそれぞれの文字列テーブルで解決される
+    /// let idx = dynsym.position("__libc_start_main").unwrap();
+    /// let st_name = dynsym.get(idx).unwrap().st_name;
+    /// assert_eq!(Some("__libc_start_main"), dynsym.name_at(st_name));
+    /// ```
+    pub fn symtab(&self) -> Option<SymTab<'_>> {
+        SymbolSection::new(self, section::Type::SymTab).map(SymTab)
+    }
+
+    /// the first `SHT_DYNSYM`, which statically linked files don't have.
+    pub fn dynsym(&self) -> Option<DynSym<'_>> {
+        SymbolSection::new(self, section::Type::DynSym).map(DynSym)
+    }
+}
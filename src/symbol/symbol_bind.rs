@@ -1,4 +1,6 @@
-#[derive(Debug, Eq, Ord, PartialEq, PartialOrd)]
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, Hash, PartialOrd, Ord, PartialEq, Eq)]
 pub enum Bind {
     /// Local Symbol
     Local,
@@ -45,7 +47,7 @@ impl From<u8> for Bind {
             1 => Self::Global,
             2 => Self::Weak,
             3 => Self::Num,
-            10 => Self::LoProc,
+            10 => Self::GNUUnique,
             12 => Self::HiOS,
             13 => Self::LoProc,
             15 => Self::HiProc,
@@ -53,3 +55,18 @@ impl From<u8> for Bind {
         }
     }
 }
+
+/// Display as readelf does.
+impl fmt::Display for Bind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.to_byte() {
+            0 => write!(f, "LOCAL"),
+            1 => write!(f, "GLOBAL"),
+            2 => write!(f, "WEAK"),
+            10 => write!(f, "UNIQUE"),
+            v @ 11..=12 => write!(f, "<OS specific>: {}", v),
+            v @ 13..=15 => write!(f, "<processor specific>: {}", v),
+            v => write!(f, "<unknown>: {}", v),
+        }
+    }
+}
@@ -1,5 +1,7 @@
+use std::fmt;
+
 /// Symbol type definitions
-#[derive(Debug, Eq, Ord, PartialEq, PartialOrd)]
+#[derive(Debug, Clone, Copy, Hash, PartialOrd, Ord, PartialEq, Eq)]
 pub enum Type {
     /// Unspecified
     NoType,
@@ -62,7 +64,7 @@ impl From<u8> for Type {
             5 => Self::Common,
             6 => Self::TLS,
             7 => Self::Num,
-            10 => Self::LoProc,
+            10 => Self::GNUIFunc,
             12 => Self::HiOS,
             13 => Self::LoProc,
             15 => Self::HiProc,
@@ -70,3 +72,32 @@ impl From<u8> for Type {
         }
     }
 }
+
+/// Display as readelf does.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::symbol;
+///
+/// assert_eq!("FUNC", symbol::Type::Func.to_string());
+/// assert_eq!("GLOBAL", symbol::Bind::Global.to_string());
+/// assert_eq!("DEFAULT", symbol::Visibility::Default.to_string());
+/// ```
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.to_byte() {
+            0 => write!(f, "NOTYPE"),
+            1 => write!(f, "OBJECT"),
+            2 => write!(f, "FUNC"),
+            3 => write!(f, "SECTION"),
+            4 => write!(f, "FILE"),
+            5 => write!(f, "COMMON"),
+            6 => write!(f, "TLS"),
+            10 => write!(f, "IFUNC"),
+            v @ 11..=12 => write!(f, "<OS specific>: {}", v),
+            v @ 13..=15 => write!(f, "<processor specific>: {}", v),
+            v => write!(f, "<unknown>: {}", v),
+        }
+    }
+}
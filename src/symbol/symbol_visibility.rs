@@ -1,6 +1,9 @@
 //! ELF symbol visibility.
 
+use std::fmt;
+
 /// Symbol Visibilities.
+#[derive(Debug, Clone, Copy, Hash, PartialOrd, Ord, PartialEq, Eq)]
 pub enum Visibility {
     /// Default symbol visibility rules.
     Default,
@@ -41,3 +44,16 @@ impl From<u8> for Visibility {
         }
     }
 }
+
+/// Display as readelf does.
+impl fmt::Display for Visibility {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.to_byte() {
+            0 => write!(f, "DEFAULT"),
+            1 => write!(f, "INTERNAL"),
+            2 => write!(f, "HIDDEN"),
+            3 => write!(f, "PROTECTED"),
+            v => write!(f, "<unknown>: {}", v),
+        }
+    }
+}
@@ -0,0 +1,411 @@
+//! A symbol table which keeps the local symbols first.
+
+use crate::*;
+
+/// SymbolTable holds the entries of a symbol table section(`.symtab`, `.dynsym`)
+/// and keeps the local symbols before the others, so that `sh_info` is always `first_non_local()`.
+///
+/// Adding, removing and rebinding symbols may move the other symbols.
+/// The table remembers where each original symbol went, and `new_index()` answers it
+/// for rewriting the references(e.g. relocations). `ELF64::update_symbol_table()` does it for relocations.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::symbol::{self, SymbolTable};
+///
+/// let mut f = symbol::Symbol64 {
+///     symbol_name: "f".into(),
+///     ..Default::default()
+/// };
+/// f.set_info(symbol::Type::Func, symbol::Bind::Global);
+///
+/// let mut tab = SymbolTable::new();
+/// assert_eq!(1, tab.push(f));
+///
+/// // the new local symbol is placed before `f`
+/// let mut local = symbol::Symbol64 {
+///     symbol_name: "local".into(),
+///     ..Default::default()
+/// };
+/// local.set_info(symbol::Type::Object, symbol::Bind::Local);
+/// assert_eq!(1, tab.push(local));
+/// assert_eq!(2, tab.first_non_local());
+/// assert_eq!(Some(2), tab.position("f"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SymbolTable {
+    symbols: Vec<symbol::Symbol64>,
+    /// the original index of each symbol, `None` for the added ones
+    origins: Vec<Option<usize>>,
+    /// the number of the original symbols
+    original_len: usize,
+}
+
+impl Default for SymbolTable {
+    fn default() -> Self {
+        // シンボルテーブルは必ずNULLシンボルから始まる
+        Self {
+            symbols: vec![symbol::Symbol64::new_null_symbol()],
+            origins: vec![None],
+            original_len: 0,
+        }
+    }
+}
+
+impl SymbolTable {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// create a table from parsed symbols.
+    ///
+    /// If a local symbol follows a non-local one, the locals are moved first keeping their order.
+    /// `new_index()` tells where the symbols went.
+    pub fn from_symbols(symbols: Vec<symbol::Symbol64>) -> Self {
+        if symbols.is_empty() {
+            return Self::new();
+        }
+        let original_len = symbols.len();
+        let mut entries: Vec<(Option<usize>, symbol::Symbol64)> = symbols
+            .into_iter()
+            .enumerate()
+            .map(|(i, sym)| (Some(i), sym))
+            .collect();
+        // NULLシンボルはローカル扱いで先頭に残る
+        entries[1..].sort_by_key(|(_, sym)| !is_local(sym));
+        let (origins, symbols) = entries.into_iter().unzip();
+        Self {
+            symbols,
+            origins,
+            original_len,
+        }
+    }
+
+    /// create a table from the contents of a symbol table section.
+    pub fn from_section(sct: &section::Section64) -> Option<Self> {
+        match &sct.contents {
+            section::Contents64::Symbols(syms) => Some(Self::from_symbols(syms.clone())),
+            _ => None,
+        }
+    }
+
+    pub fn symbols(&self) -> &[symbol::Symbol64] {
+        &self.symbols
+    }
+
+    pub fn into_symbols(self) -> Vec<symbol::Symbol64> {
+        self.symbols
+    }
+
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    /// the table has only the null symbol.
+    pub fn is_empty(&self) -> bool {
+        self.symbols.len() <= 1
+    }
+
+    pub fn get(&self, idx: usize) -> Option<&symbol::Symbol64> {
+        self.symbols.get(idx)
+    }
+
+    /// the index of the first symbol with the name.
+    pub fn position(&self, name: &str) -> Option<usize> {
+        self.symbols
+            .iter()
+            .skip(1)
+            .position(|sym| sym.symbol_name == name)
+            .map(|idx| idx + 1)
+    }
+
+    /// the index of the first non-local symbol, which is the `sh_info` of the section.
+    pub fn first_non_local(&self) -> usize {
+        self.symbols
+            .iter()
+            .skip(1)
+            .position(|sym| !is_local(sym))
+            .map_or(self.symbols.len(), |idx| idx + 1)
+    }
+
+    /// add a symbol and return its index.
+    /// a local symbol is placed after the other locals.
+    pub fn push(&mut self, sym: symbol::Symbol64) -> usize {
+        let idx = if is_local(&sym) {
+            self.first_non_local()
+        } else {
+            self.symbols.len()
+        };
+        self.symbols.insert(idx, sym);
+        self.origins.insert(idx, None);
+        idx
+    }
+
+    /// remove the symbols which `f` returns false for. the null symbol is always kept.
+    /// Returns the removed symbols.
+    pub fn retain<F>(&mut self, mut f: F) -> Vec<symbol::Symbol64>
+    where
+        F: FnMut(&symbol::Symbol64) -> bool,
+    {
+        let mut removed = Vec::new();
+        let mut kept = Vec::with_capacity(self.symbols.len());
+        let mut kept_origins = Vec::with_capacity(self.symbols.len());
+        for (i, (sym, origin)) in self
+            .symbols
+            .drain(..)
+            .zip(self.origins.drain(..))
+            .enumerate()
+        {
+            if i == 0 || f(&sym) {
+                kept.push(sym);
+                kept_origins.push(origin);
+            } else {
+                removed.push(sym);
+            }
+        }
+        self.symbols = kept;
+        self.origins = kept_origins;
+        removed
+    }
+
+    /// remove the symbols at the indices. the null symbol is always kept.
+    /// Returns the removed symbols.
+    pub fn remove(&mut self, indices: &[usize]) -> Vec<symbol::Symbol64> {
+        let mut removed = Vec::new();
+        for idx in (1..self.symbols.len()).rev() {
+            if indices.contains(&idx) {
+                self.origins.remove(idx);
+                removed.push(self.symbols.remove(idx));
+            }
+        }
+        removed.reverse();
+        removed
+    }
+
+    /// modify the symbol and return its new index, which differs if the binding changed between local and non-local.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `idx` is out of range.
+    pub fn modify<F>(&mut self, idx: usize, f: F) -> usize
+    where
+        F: FnOnce(&mut symbol::Symbol64),
+    {
+        let was_local = is_local(&self.symbols[idx]);
+        f(&mut self.symbols[idx]);
+        if idx == 0 || was_local == is_local(&self.symbols[idx]) {
+            return idx;
+        }
+
+        let sym = self.symbols.remove(idx);
+        let origin = self.origins.remove(idx);
+        // ローカルになったものはローカルの末尾，大域になったものは大域の先頭へ
+        let idx = self.first_non_local();
+        self.symbols.insert(idx, sym);
+        self.origins.insert(idx, origin);
+        idx
+    }
+
+    /// change the binding of the symbol and return its new index.
+    pub fn set_bind(&mut self, idx: usize, bind: symbol::Bind) -> usize {
+        self.modify(idx, |sym| sym.set_info(sym.get_type(), bind))
+    }
+
+    /// the current index of the symbol which was at `old` when the table was created.
+    /// `None` if it was removed.
+    pub fn new_index(&self, old: usize) -> Option<usize> {
+        if old == 0 {
+            return Some(0);
+        }
+        if old >= self.original_len {
+            return None;
+        }
+        self.origins.iter().position(|origin| *origin == Some(old))
+    }
+
+    /// `new_index()` of all the original symbols.
+    pub fn index_map(&self) -> Vec<Option<usize>> {
+        let mut map = vec![None; self.original_len.max(1)];
+        map[0] = Some(0);
+        for (idx, origin) in self.origins.iter().enumerate() {
+            if let Some(origin) = origin {
+                map[*origin] = Some(idx);
+            }
+        }
+        map
+    }
+}
+
+fn is_local(sym: &symbol::Symbol64) -> bool {
+    sym.get_bind() == symbol::Bind::Local
+}
+
+impl file::ELF64 {
+    /// replace the contents of the symbol table section `shidx` with the table,
+    /// and rewrite the references to the symbols.
+    ///
+    /// `sh_info` and `sh_size` are updated, and the relocations(and LLVM's `.llvm_addrsig` and call graph profile)
+    /// linked to the section follow the symbols by `SymbolTable::new_index()`.
+    /// `sh_info` of `SHT_GROUP` sections, which is the signature symbol, follows them too(0 if removed).
+    /// Relocations against removed symbols are taken out of their sections and returned with the section index,
+    /// keeping the old symbol index. The ones with a broken index get the null symbol.
+    /// The other sections indexing the symbols(e.g. `.gnu.version` and the hash tables for `.dynsym`) are left to the caller.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `shidx` is out of range.
+    pub fn update_symbol_table(
+        &mut self,
+        shidx: usize,
+        table: SymbolTable,
+    ) -> Vec<(usize, relocation::Rela64)> {
+        self.invalidate_address_index();
+        let map = table.index_map();
+        // 範囲外のインデックスは壊れているので，NULLシンボルを指すようにする
+        let new_index = |old: u64| match map.get(old as usize) {
+            Some(idx) => idx.map(|i| i as u64),
+            None => Some(0),
+        };
+
+        let sct = &mut self.sections[shidx];
+        sct.header.sh_info = table.first_non_local() as Elf64Word;
+        sct.contents = section::Contents64::Symbols(table.into_symbols());
+        sct.header.sh_size = sct.contents.size() as Elf64Xword;
+
+        let data = self.ehdr.get_data();
+        let mut dropped = Vec::new();
+        for (idx, sct) in self.sections.iter_mut().enumerate() {
+            if sct.header.sh_link as usize != shidx {
+                continue;
+            }
+            if sct.header.get_type() == section::Type::Group {
+                sct.header.sh_info = new_index(sct.header.sh_info as u64).unwrap_or(0) as Elf64Word;
+                continue;
+            }
+            sct.remap_llvm_symbols(data, new_index);
+            if let section::Contents64::RelaSymbols(relas) = &mut sct.contents {
+                let (kept, removed): (Vec<_>, Vec<_>) = relas
+                    .drain(..)
+                    .partition(|rela| new_index(rela.get_sym()).is_some());
+                dropped.extend(removed.into_iter().map(|rela| (idx, rela)));
+                *relas = kept;
+                for rela in relas.iter_mut() {
+                    let sym = new_index(rela.get_sym()).unwrap();
+                    rela.set_info(sym << 32 | rela.get_type());
+                }
+                sct.header.sh_size = sct.contents.size() as Elf64Xword;
+            }
+        }
+        dropped
+    }
+}
+
+#[cfg(test)]
+mod table_tests {
+    use super::*;
+
+    fn sym(name: &str, bind: symbol::Bind) -> symbol::Symbol64 {
+        let mut sym = symbol::Symbol64 {
+            symbol_name: name.into(),
+            ..Default::default()
+        };
+        sym.set_info(symbol::Type::Func, bind);
+        sym
+    }
+
+    #[test]
+    fn symbol_table_test() {
+        let null = symbol::Symbol64::new_null_symbol();
+        let mut tab = SymbolTable::from_symbols(vec![
+            null,
+            sym("a", symbol::Bind::Global),
+            sym("b", symbol::Bind::Local),
+            sym("c", symbol::Bind::Weak),
+        ]);
+        // ローカルシンボルが先頭に移動する
+        assert_eq!(Some(1), tab.position("b"));
+        assert_eq!(2, tab.first_non_local());
+        assert_eq!(vec![Some(0), Some(2), Some(1), Some(3)], tab.index_map());
+
+        // a をローカルにすると b の後ろへ
+        assert_eq!(2, tab.set_bind(2, symbol::Bind::Local));
+        assert_eq!(3, tab.first_non_local());
+        // b を大域にすると大域シンボルの先頭へ
+        assert_eq!(2, tab.set_bind(1, symbol::Bind::Global));
+        assert_eq!(Some(1), tab.position("a"));
+        assert_eq!(2, tab.first_non_local());
+
+        assert_eq!(2, tab.push(sym("d", symbol::Bind::Local)));
+        let removed = tab.retain(|sym| sym.symbol_name != "a");
+        assert_eq!("a", removed[0].symbol_name);
+        assert_eq!(None, tab.new_index(1));
+        assert_eq!(Some(2), tab.new_index(2));
+        assert_eq!(Some(3), tab.new_index(3));
+        assert_eq!(2, tab.first_non_local());
+        let names: Vec<&str> = tab
+            .symbols()
+            .iter()
+            .map(|s| s.symbol_name.as_str())
+            .collect();
+        assert_eq!(vec!["", "d", "b", "c"], names);
+    }
+
+    #[test]
+    fn update_symbol_table_test() {
+        let mut elf = file::ELF64::default();
+        let syms = vec![
+            symbol::Symbol64::new_null_symbol(),
+            sym("sig", symbol::Bind::Weak),
+            sym("dead", symbol::Bind::Global),
+        ];
+        elf.add_section(section::Section64::new(
+            ".symtab".to_string(),
+            section::ShdrPreparation64::default().ty(section::Type::SymTab),
+            section::Contents64::Symbols(syms),
+        ));
+        elf.add_section(section::Section64::new(
+            ".group".to_string(),
+            section::ShdrPreparation64::default()
+                .ty(section::Type::Group)
+                .link(1)
+                .info(1),
+            section::Contents64::Raw(vec![1, 0, 0, 0]),
+        ));
+        let mut relas = vec![relocation::Rela64::default(); 2];
+        relas[0].set_info(1 << 32 | relocation::R_X86_64_PLT32);
+        relas[1].set_info(2 << 32 | relocation::R_X86_64_PLT32);
+        relas[1].set_offset(8);
+        elf.add_section(section::Section64::new(
+            ".rela.text".to_string(),
+            section::ShdrPreparation64::default()
+                .ty(section::Type::Rela)
+                .link(1),
+            section::Contents64::RelaSymbols(relas),
+        ));
+
+        // ローカルシンボルを足すとsigの番号がずれる
+        let mut tab = SymbolTable::from_section(&elf.sections[1]).unwrap();
+        tab.push(sym("local", symbol::Bind::Local));
+        tab.retain(|sym| sym.symbol_name != "dead");
+        let dropped = elf.update_symbol_table(1, tab);
+
+        assert_eq!(2, elf.sections[2].header.sh_info);
+        assert_eq!(1, dropped.len());
+        assert_eq!(
+            (3, 2, 8),
+            (
+                dropped[0].0,
+                dropped[0].1.get_sym(),
+                dropped[0].1.get_offset()
+            )
+        );
+        match &elf.sections[3].contents {
+            section::Contents64::RelaSymbols(relas) => {
+                assert_eq!(1, relas.len());
+                assert_eq!(2, relas[0].get_sym());
+            }
+            _ => unreachable!(),
+        }
+    }
+}
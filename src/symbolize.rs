@@ -0,0 +1,386 @@
+//! Resolving addresses to the symbols of linked files.
+//!
+//! The addresses are link-time virtual addresses by default.
+//! Runtime addresses of ASLR'd processes, e.g. the samples of a profiler,
+//! are resolved by giving the load bias of the module,
+//! which `process::map_modules()` computes from `/proc/<pid>/maps`.
+//! `batch_resolve()` resolves many addresses at once, caching the parsed files across calls.
+
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+
+use crate::*;
+
+/// The symbol found at an address
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Resolved {
+    pub name: intern::Name,
+    /// the link-time virtual address of the symbol
+    pub vaddr: Elf64Addr,
+    /// the distance from the symbol to the resolved address
+    pub offset: Elf64Addr,
+}
+
+/// The defined symbols of a file, sorted by their addresses
+///
+/// `.symtab` and `.dynsym` are merged, so stripped files are resolved by the dynamic symbols.
+/// Section, file and TLS symbols and the symbols out of the sections(`SHN_ABS`, etc.) aren't indexed.
+/// The index owns the names, so it can be cached apart from the file.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SymbolIndex {
+    /// `(st_value, st_size, name)`
+    symbols: Vec<(Elf64Addr, Elf64Xword, intern::Name)>,
+    /// the largest `st_size`, which bounds the search
+    max_size: Elf64Xword,
+}
+
+impl SymbolIndex {
+    pub fn new(elf: &file::ELF64) -> Self {
+        let mut symbols: Vec<(Elf64Addr, Elf64Xword, intern::Name)> = elf
+            .sections
+            .iter()
+            .filter_map(|sct| match &sct.contents {
+                section::Contents64::Symbols(syms) => Some(syms),
+                _ => None,
+            })
+            .flat_map(|syms| syms.iter())
+            .filter(|sym| {
+                sym.st_shndx != 0
+                    && sym.st_shndx < 0xff00
+                    && !sym.symbol_name.is_empty()
+                    && !matches!(
+                        sym.get_type(),
+                        symbol::Type::Section | symbol::Type::File | symbol::Type::TLS
+                    )
+            })
+            .map(|sym| (sym.st_value, sym.st_size, sym.symbol_name.clone()))
+            .collect();
+        // .symtabと.dynsymの重複を除く
+        symbols.sort();
+        symbols.dedup();
+        let max_size = symbols.iter().map(|(_, size, _)| *size).max().unwrap_or(0);
+        Self { symbols, max_size }
+    }
+
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    /// the symbol containing the link-time virtual address.
+    ///
+    /// A sized symbol contains `[st_value, st_value + st_size)`,
+    /// and a symbol without size matches only its own address.
+    /// Of the overlapping symbols the one starting last is taken.
+    pub fn lookup(&self, vaddr: Elf64Addr) -> Option<Resolved> {
+        let end = self
+            .symbols
+            .partition_point(|(start, _, _)| *start <= vaddr);
+        // max_sizeより前から始まるシンボルはvaddrに届かない
+        self.symbols[..end]
+            .iter()
+            .rev()
+            .take_while(|(start, _, _)| vaddr - start <= self.max_size)
+            .find(|(start, size, _)| vaddr - start < (*size).max(1))
+            .map(|(start, _, name)| Resolved {
+                name: name.clone(),
+                vaddr: *start,
+                offset: vaddr - start,
+            })
+    }
+}
+
+/// A symbolizer for a file loaded at `load_bias`
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::{parser, section, symbolize};
+///
+/// let elf = parser::parse_elf64("src/parser/testdata/sample").unwrap();
+/// let main = elf
+///     .first_section_by(|sct| sct.name == ".symtab")
+///     .and_then(|sct| match &sct.contents {
+///         section::Contents64::Symbols(syms) => {
+///             syms.iter().find(|sym| sym.symbol_name == "main").cloned()
+///         }
+///         _ => None,
+///     })
+///     .unwrap();
+///
+/// // PIEがASLRで0x5555_5555_4000にロードされた
+/// let symbolizer = symbolize::Symbolizer::new(&elf).load_bias(0x5555_5555_4000);
+/// let resolved = symbolizer.symbolize(0x5555_5555_4000 + main.st_value + 1).unwrap();
+/// assert_eq!("main", resolved.name);
+/// assert_eq!(1, resolved.offset);
+///
+/// // リンク時のアドレスはそのまま引ける
+/// assert_eq!("main", symbolizer.symbolize_vaddr(main.st_value).unwrap().name);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbolizer {
+    index: SymbolIndex,
+    load_bias: Elf64Addr,
+}
+
+impl Symbolizer {
+    pub fn new(elf: &file::ELF64) -> Self {
+        Self::from_index(SymbolIndex::new(elf))
+    }
+
+    pub fn from_index(index: SymbolIndex) -> Self {
+        Self {
+            index,
+            load_bias: 0,
+        }
+    }
+
+    /// create a symbolizer of a module mapped in a process, with its load bias.
+    /// `None` for 32bit files.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use elf_utilities::{process, symbolize};
+    ///
+    /// #[no_mangle]
+    /// pub extern "C" fn elf_utilities_symbolize_marker() {}
+    ///
+    /// let addr = elf_utilities_symbolize_marker as usize as u64;
+    /// let modules = process::map_modules(std::process::id()).unwrap();
+    /// let module = process::module_containing(&modules, addr).unwrap();
+    /// let symbolizer = symbolize::Symbolizer::for_module(module).unwrap();
+    /// assert_eq!(
+    ///     "elf_utilities_symbolize_marker",
+    ///     symbolizer.symbolize(addr).unwrap().name
+    /// );
+    /// ```
+    pub fn for_module(module: &process::Module) -> Option<Self> {
+        match &module.elf {
+            file::ELF::ELF64(elf) => Some(Self::new(elf).load_bias(module.load_bias)),
+            file::ELF::ELF32(_) => None,
+        }
+    }
+
+    /// set the difference between the runtime addresses and the link-time addresses.
+    /// 0 by default, i.e. the addresses are link-time addresses.
+    pub fn load_bias(mut self, load_bias: Elf64Addr) -> Self {
+        self.load_bias = load_bias;
+        self
+    }
+
+    /// resolve the runtime address.
+    pub fn symbolize(&self, addr: Elf64Addr) -> Option<Resolved> {
+        self.symbolize_vaddr(addr.wrapping_sub(self.load_bias))
+    }
+
+    /// resolve the link-time virtual address, ignoring the load bias.
+    pub fn symbolize_vaddr(&self, vaddr: Elf64Addr) -> Option<Resolved> {
+        self.index.lookup(vaddr)
+    }
+}
+
+/// An address resolved by `batch_resolve()`
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ResolvedFrame {
+    /// the index of the module in the `modules` argument
+    pub module: usize,
+    /// the link-time virtual address in the module
+    pub vaddr: Elf64Addr,
+    /// `None` if no symbol covers the address, e.g. in a stripped file
+    pub symbol: Option<Resolved>,
+}
+
+/// the parsed parts of a file kept by `BatchSymbolizer`
+#[derive(Debug, Clone)]
+struct CachedFile {
+    index: SymbolIndex,
+    /// the virtual address ranges of the `PT_LOAD` segments
+    loads: Vec<(Elf64Addr, Elf64Addr)>,
+}
+
+/// A symbolizer which caches the symbol indices of the files across calls
+///
+/// Each file is parsed once, at the first call which refers it, and only its symbols and segments are kept.
+/// The files which can't be parsed, including 32bit files, are cached as well and resolve nothing.
+/// Call `clear()` when the files may have changed.
+#[derive(Debug, Clone, Default)]
+pub struct BatchSymbolizer {
+    files: HashMap<String, Option<CachedFile>>,
+}
+
+impl BatchSymbolizer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// resolve runtime addresses against the modules given as `(path, load bias)`.
+    ///
+    /// Each address is attributed to the module whose `PT_LOAD` segment contains it after the bias is removed.
+    /// The result has the same length as `addrs`; `None` for the addresses out of the modules.
+    pub fn resolve(
+        &mut self,
+        addrs: &[Elf64Addr],
+        modules: &[(&str, Elf64Addr)],
+    ) -> Vec<Option<ResolvedFrame>> {
+        for (path, _) in modules.iter() {
+            if !self.files.contains_key(*path) {
+                self.files.insert(path.to_string(), load_file(path));
+            }
+        }
+
+        // 実行時のアドレス範囲を並べて二分探索する
+        let mut ranges: Vec<(Elf64Addr, Elf64Addr, usize)> = Vec::new();
+        for (module, (path, bias)) in modules.iter().enumerate() {
+            if let Some(file) = &self.files[*path] {
+                for (start, end) in file.loads.iter() {
+                    ranges.push((start.wrapping_add(*bias), end.wrapping_add(*bias), module));
+                }
+            }
+        }
+        ranges.sort_unstable();
+
+        addrs
+            .iter()
+            .map(|addr| {
+                let i = ranges.partition_point(|(start, _, _)| start <= addr);
+                let (_, end, module) = ranges[..i].last()?;
+                if addr >= end {
+                    return None;
+                }
+                let (path, bias) = modules[*module];
+                let vaddr = addr.wrapping_sub(bias);
+                let file = self.files[path].as_ref()?;
+                Some(ResolvedFrame {
+                    module: *module,
+                    vaddr,
+                    symbol: file.index.lookup(vaddr),
+                })
+            })
+            .collect()
+    }
+
+    /// the number of the cached files.
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// forget the cached files.
+    pub fn clear(&mut self) {
+        self.files.clear();
+    }
+}
+
+fn load_file(path: &str) -> Option<CachedFile> {
+    let elf = parser::parse_elf64(path).ok()?;
+    let loads = elf
+        .segments
+        .iter()
+        .map(|sgt| &sgt.header)
+        .filter(|phdr| phdr.get_type() == segment::Type::Load)
+        .map(|phdr| (phdr.p_vaddr, phdr.p_vaddr + phdr.p_memsz))
+        .collect();
+    Some(CachedFile {
+        index: SymbolIndex::new(&elf),
+        loads,
+    })
+}
+
+/// resolve runtime addresses, e.g. the frame-pointer stacks of perf samples,
+/// against the modules given as `(path, load bias)`.
+///
+/// This uses a `BatchSymbolizer` shared by the whole process,
+/// so the files are parsed only at the first call.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::{parser, section, symbolize};
+///
+/// let path = "src/parser/testdata/sample";
+/// let elf = parser::parse_elf64(path).unwrap();
+/// let main = match &elf.first_section_by(|sct| sct.name == ".symtab").unwrap().contents {
+///     section::Contents64::Symbols(syms) => syms.iter().find(|sym| sym.symbol_name == "main").unwrap().st_value,
+///     _ => unreachable!(),
+/// };
+///
+/// let bias = 0x5555_5555_4000;
+/// let frames = symbolize::batch_resolve(&[bias + main + 4, 0x10], &[(path, bias)]);
+/// let frame = frames[0].as_ref().unwrap();
+/// assert_eq!(0, frame.module);
+/// assert_eq!(main + 4, frame.vaddr);
+/// assert_eq!("main", frame.symbol.as_ref().unwrap().name);
+/// // どのモジュールにも含まれない
+/// assert!(frames[1].is_none());
+/// ```
+pub fn batch_resolve(
+    addrs: &[Elf64Addr],
+    modules: &[(&str, Elf64Addr)],
+) -> Vec<Option<ResolvedFrame>> {
+    static GLOBAL: OnceLock<Mutex<BatchSymbolizer>> = OnceLock::new();
+    GLOBAL
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap()
+        .resolve(addrs, modules)
+}
+
+#[cfg(test)]
+mod symbolize_tests {
+    use super::*;
+
+    #[test]
+    fn lookup_test() {
+        let index = SymbolIndex {
+            symbols: vec![
+                (0x1000, 0x100, "outer".into()),
+                (0x1010, 0x10, "inner".into()),
+                (0x1200, 0, "label".into()),
+            ],
+            max_size: 0x100,
+        };
+        let name = |vaddr| index.lookup(vaddr).map(|r| r.name.to_string());
+
+        assert_eq!(None, name(0xfff));
+        assert_eq!(Some("outer".to_string()), name(0x1000));
+        assert_eq!(Some("inner".to_string()), name(0x101f));
+        // innerの後ろはouterに戻る
+        assert_eq!(Some("outer".to_string()), name(0x1020));
+        assert_eq!(0x20, index.lookup(0x1020).unwrap().offset);
+        assert_eq!(None, name(0x1100));
+        assert_eq!(Some("label".to_string()), name(0x1200));
+        assert_eq!(None, name(0x1201));
+    }
+
+    #[test]
+    fn batch_symbolizer_test() {
+        let sample = "src/parser/testdata/sample";
+        let mut batch = BatchSymbolizer::new();
+        let modules = [("Cargo.toml", 0x1000), (sample, 0x7f00_0000_0000)];
+        let elf = parser::parse_elf64(sample).unwrap();
+        let symbolizer = Symbolizer::new(&elf).load_bias(0x7f00_0000_0000);
+        let text = elf.first_section_by(|sct| sct.name == ".text").unwrap();
+        let addrs: Vec<Elf64Addr> = (0..text.header.sh_size)
+            .map(|off| 0x7f00_0000_0000 + text.header.sh_addr + off)
+            .collect();
+
+        for _ in 0..2 {
+            let frames = batch.resolve(&addrs, &modules);
+            for (addr, frame) in addrs.iter().zip(frames) {
+                let frame = frame.unwrap();
+                assert_eq!(1, frame.module);
+                assert_eq!(symbolizer.symbolize(*addr), frame.symbol);
+            }
+        }
+        // ELFでないファイルも失敗として覚えておく
+        assert_eq!(2, batch.len());
+        assert_eq!(vec![None], batch.resolve(&[0x1000], &modules));
+    }
+}
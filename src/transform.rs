@@ -0,0 +1,683 @@
+//! Transformations on ELF files.
+
+use std::collections::HashSet;
+
+use crate::*;
+use thiserror::Error as TError;
+
+mod branch_protection;
+mod bundle;
+mod constructor;
+mod convert;
+mod detour;
+mod exports;
+mod flat;
+mod import;
+mod segment;
+mod soname;
+mod version_script;
+#[cfg(feature = "yaml")]
+mod yaml;
+
+pub use branch_protection::*;
+pub use bundle::*;
+pub use constructor::*;
+pub use convert::*;
+pub use detour::*;
+pub use exports::*;
+pub use flat::*;
+pub use import::*;
+pub use segment::*;
+pub use version_script::*;
+#[cfg(feature = "yaml")]
+pub use yaml::*;
+
+#[derive(TError, Debug)]
+pub enum TransformError {
+    #[error("the transformation supports only relocatable files")]
+    NotRelocatable,
+    #[error("the transformation supports only executables and shared objects")]
+    NotLinked,
+    #[error("the file has no dynamic symbol table")]
+    NoDynamicSymbols,
+    #[error("machine {machine} is not supported")]
+    UnsupportedMachine { machine: Elf64Half },
+    #[error("`{name}` is not imported")]
+    ImportNotFound { name: String },
+    #[error("the file already defines symbol versions")]
+    AlreadyVersioned,
+    #[error("the file defines no symbol versions")]
+    Unversioned,
+    #[error("version `{name}` is already defined")]
+    VersionExists { name: String },
+    #[error("invalid version sections => {0}")]
+    InvalidVersions(section::ReadError),
+    #[error("invalid version script at line {line} => `{message}`")]
+    InvalidVersionScript { line: usize, message: String },
+    #[error("the file has no program property {pr_type:#x}")]
+    MissingProperty { pr_type: Elf64Word },
+    #[error("invalid note => {0}")]
+    InvalidNote(section::ReadError),
+    #[error("no section has the contents at {vaddr:#x}")]
+    Unmapped { vaddr: Elf64Addr },
+    #[error("can't move the instruction at {vaddr:#x}")]
+    UnsupportedInstruction { vaddr: Elf64Addr },
+    #[error("no symbol with its size covers {vaddr:#x}")]
+    NoFunction { vaddr: Elf64Addr },
+    #[error("the function at {vaddr:#x} is too small for the jump")]
+    FunctionTooSmall { vaddr: Elf64Addr },
+    #[error("the branch at {from:#x} to {to:#x} crosses functions without a relocation")]
+    UnrelocatedBranch { from: Elf64Addr, to: Elf64Addr },
+    #[error("the file has no loadable contents")]
+    NoLoadableContents,
+    #[error("the segment at {paddr:#x} overlaps another segment")]
+    OverlappingSegments { paddr: Elf64Addr },
+    #[error("address {addr:#x} can't be represented in the output")]
+    OutOfRange { addr: Elf64Addr },
+    #[error("post-processing failed => `{message}`")]
+    PostProcess { message: String },
+    #[error("invalid YAML description => `{message}`")]
+    InvalidYaml { message: String },
+    #[error("failed to parse `{path}` => `{message}`")]
+    Parse { path: String, message: String },
+    #[error("I/O on `{path}` failed => `{message}`")]
+    Io { path: String, message: String },
+    #[error("can't convert to {class:?}/{data:?}")]
+    UnsupportedTarget {
+        class: header::Class,
+        data: header::Data,
+    },
+}
+
+/// update the entry of `ty`, or insert it before `DT_NULL`.
+/// `None` only inserts the entry if absent.
+fn set_dynamic(
+    entries: &mut Vec<dynamic::Dyn64>,
+    ty: dynamic::EntryType,
+    value: Option<Elf64Xword>,
+) {
+    if let Some(ent) = entries.iter_mut().find(|ent| ent.get_type() == ty) {
+        if let Some(value) = value {
+            ent.d_un = value;
+        }
+        return;
+    }
+    let pos = entries
+        .iter()
+        .position(|ent| ent.get_type() == dynamic::EntryType::Null)
+        .unwrap_or(entries.len());
+    entries.insert(pos, dynamic::Dyn64::new(ty, value.unwrap_or(0)));
+}
+
+/// edit the entries of `.dynamic` and add strings to `.dynstr` with `f`.
+/// the strings are appended, so the offsets referred by `.dynsym` and the others are kept.
+/// if either grows, both are moved to a new segment by `move_to_new_segment()`,
+/// and `DT_STRTAB`/`DT_STRSZ` are updated.
+fn edit_dynamic<F>(elf: &mut file::ELF64, f: F) -> Result<(), TransformError>
+where
+    F: FnOnce(&mut Vec<dynamic::Dyn64>, &mut section::StringTable),
+{
+    let dynamic = elf
+        .first_shidx_by(|sct| sct.header.get_type() == section::Type::Dynamic)
+        .ok_or(TransformError::NotLinked)?;
+    let dynstr = elf.sections[dynamic].header.sh_link as usize;
+    let mut tab = match elf.sections.get(dynstr).map(|s| &s.contents) {
+        Some(section::Contents64::StrTab(strs)) => section::StringTable::from_parsed(strs.clone()),
+        _ => return Err(TransformError::NoDynamicSymbols),
+    };
+    let mut entries = match &elf.sections[dynamic].contents {
+        section::Contents64::Dynamics(entries) => entries.clone(),
+        _ => return Err(TransformError::NotLinked),
+    };
+    let (old_size, old_len) = (tab.size(), entries.len());
+    f(&mut entries, &mut tab);
+    let grown = tab.size() > old_size || entries.len() > old_len;
+    if grown {
+        set_dynamic(&mut entries, dynamic::EntryType::StrTab, None);
+        set_dynamic(&mut entries, dynamic::EntryType::StrSz, None);
+    }
+    elf.sections[dynstr].contents = tab.to_contents64();
+    elf.sections[dynamic].contents = section::Contents64::Dynamics(entries);
+    if !grown {
+        return Ok(());
+    }
+
+    move_to_new_segment(elf, &[dynstr, dynamic])?;
+    let addr = elf.sections[dynstr].header.sh_addr;
+    let size = elf.sections[dynstr].header.sh_size;
+    if let section::Contents64::Dynamics(entries) = &mut elf.sections[dynamic].contents {
+        set_dynamic(entries, dynamic::EntryType::StrTab, Some(addr));
+        set_dynamic(entries, dynamic::EntryType::StrSz, Some(size));
+    }
+    Ok(())
+}
+
+/// a part of `.text` which is moved to its own section
+struct Chunk {
+    start: Elf64Addr,
+    end: Elf64Addr,
+    shndx: usize,
+    /// index of the section symbol in the new symbol table
+    section_sym: usize,
+}
+
+/// split `.text` into `.text.<func>` sections like `-ffunction-sections`, so that `ELF64::gc_sections()` can remove each function.
+///
+/// Each function symbol(`STT_FUNC` with its size) starts a new section,
+/// which also contains the padding up to the next function.
+/// Bytes before the first function are kept in `.text`.
+/// Symbols and relocations in `.text`(and `.rela.text`) are moved to the new sections,
+/// and a section symbol is added for each section.
+/// Relocations against the section symbol of `.text` are retargeted to the section containing the addend
+/// (plus 4 for `R_X86_64_PC32`/`R_X86_64_PLT32`, which is the size of the field).
+/// This works only for x86_64, so the other machines with such relocations are refused by `UnsupportedMachine`.
+/// Returns the names of the created sections.
+///
+/// The assembler resolves branches to local functions in the same section without relocations,
+/// which would be broken by moving the functions.
+/// For x86_64 and AArch64, the functions are decoded and such a branch is refused by `UnrelocatedBranch`
+/// (`UnsupportedInstruction` if an instruction can't be decoded) before anything is changed.
+/// The other machines aren't checked.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::{file, header, section, symbol, transform};
+///
+/// let mut elf = file::ELF64::default();
+/// elf.ehdr.set_elf_type(header::Type::Rel);
+/// elf.add_section(section::Section64::new(
+///     ".text".to_string(),
+///     section::ShdrPreparation64::default()
+///         .ty(section::Type::ProgBits)
+///         .flags([section::Flag::Alloc, section::Flag::ExecInstr].iter()),
+///     section::Contents64::Raw(vec![0xc3, 0xc3]),
+/// ));
+/// let mut syms = vec![symbol::Symbol64::new_null_symbol()];
+/// for (i, name) in ["f", "g"].iter().enumerate() {
+///     let mut sym = symbol::Symbol64 {
+///         symbol_name: (*name).into(),
+///         st_shndx: 1,
+///         st_value: i as u64,
+///         st_size: 1,
+///         ..Default::default()
+///     };
+///     sym.set_info(symbol::Type::Func, symbol::Bind::Global);
+///     syms.push(sym);
+/// }
+/// elf.add_section(section::Section64::new(
+///     ".symtab".to_string(),
+///     section::ShdrPreparation64::default().ty(section::Type::SymTab).info(1),
+///     section::Contents64::Symbols(syms),
+/// ));
+///
+/// let created = transform::split_functions(&mut elf).unwrap();
+/// assert_eq!(vec![".text.f".to_string(), ".text.g".to_string()], created);
+/// ```
+pub fn split_functions(elf: &mut file::ELF64) -> Result<Vec<String>, TransformError> {
+    if elf.ehdr.get_type() != header::Type::Rel {
+        return Err(TransformError::NotRelocatable);
+    }
+    let text_idx = match elf.first_shidx_by(|sct| sct.name == ".text") {
+        Some(idx) => idx,
+        None => return Ok(Vec::new()),
+    };
+    let symtab_idx = match elf.first_shidx_by(|sct| sct.header.get_type() == section::Type::SymTab)
+    {
+        Some(idx) => idx,
+        None => return Ok(Vec::new()),
+    };
+    let text = match &elf.sections[text_idx].contents {
+        section::Contents64::Raw(bytes) => bytes.clone(),
+        _ => return Ok(Vec::new()),
+    };
+    let syms = match &elf.sections[symtab_idx].contents {
+        section::Contents64::Symbols(syms) => syms.clone(),
+        _ => return Ok(Vec::new()),
+    };
+
+    let mut funcs: Vec<(Elf64Addr, Elf64Xword, String)> = syms
+        .iter()
+        .filter(|sym| {
+            sym.st_shndx as usize == text_idx
+                && sym.get_type() == symbol::Type::Func
+                && sym.st_size != 0
+                && sym.st_value < text.len() as Elf64Addr
+        })
+        .map(|sym| (sym.st_value, sym.st_size, sym.symbol_name.to_string()))
+        .collect();
+    // 同じアドレスの別名は最初のシンボルの名前を使う
+    funcs.sort_by_key(|(start, _, _)| *start);
+    funcs.dedup_by_key(|(start, _, _)| *start);
+    if funcs.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut chunks = vec![Chunk {
+        start: 0,
+        end: funcs[0].0,
+        shndx: text_idx,
+        section_sym: 0,
+    }];
+    for (i, (start, _, _)) in funcs.iter().enumerate() {
+        let end = funcs
+            .get(i + 1)
+            .map_or(text.len() as Elf64Addr, |(next, _, _)| *next);
+        chunks.push(Chunk {
+            start: *start,
+            end,
+            shndx: elf.sections.len() + i,
+            section_sym: 0,
+        });
+    }
+    check_branches(elf, text_idx, &text, &funcs, &chunks)?;
+    let text_section_sym = syms.iter().position(|sym| {
+        sym.get_type() == symbol::Type::Section && sym.st_shndx as usize == text_idx
+    });
+    // セクションシンボルへの参照先の判定は，x86_64のPC相対の補正に依存する
+    if elf.ehdr.get_machine() != header::Machine::X8664
+        && text_section_sym.is_some_and(|text_sym| refers_symbol(elf, symtab_idx, text_sym))
+    {
+        return Err(TransformError::UnsupportedMachine {
+            machine: elf.ehdr.e_machine,
+        });
+    }
+
+    // シンボルテーブルの再構築
+    // 新しいセクションシンボルはローカルシンボルの末尾に追加される
+    let mut table = symbol::SymbolTable::from_symbols(syms.clone());
+    for c in chunks.iter_mut().skip(1) {
+        let mut sym = symbol::Symbol64 {
+            st_shndx: c.shndx as Elf64Section,
+            ..Default::default()
+        };
+        sym.set_info(symbol::Type::Section, symbol::Bind::Local);
+        c.section_sym = table.push(sym);
+    }
+    let text_section_sym = text_section_sym.and_then(|idx| table.new_index(idx));
+    chunks[0].section_sym = text_section_sym.unwrap_or(0);
+    let chunk_of = |offset: Elf64Addr| chunks.iter().rposition(|c| c.start <= offset).unwrap_or(0);
+
+    for idx in 0..table.len() {
+        table.modify(idx, |sym| {
+            if sym.st_shndx as usize != text_idx || sym.get_type() == symbol::Type::Section {
+                return;
+            }
+            let c = &chunks[chunk_of(sym.st_value)];
+            sym.st_shndx = c.shndx as Elf64Section;
+            sym.st_value -= c.start;
+        });
+    }
+    elf.update_symbol_table(symtab_idx, table);
+
+    // リロケーションの書き換え
+    let mut split_relas: Vec<Vec<relocation::Rela64>> = vec![Vec::new(); chunks.len()];
+    let mut text_rela_idx = None;
+    for (idx, sct) in elf.sections.iter_mut().enumerate() {
+        if sct.header.sh_link as usize != symtab_idx {
+            continue;
+        }
+        let relas = match &mut sct.contents {
+            section::Contents64::RelaSymbols(relas) => relas,
+            _ => continue,
+        };
+
+        // シンボルの番号は update_symbol_table() で書き換え済み
+        for rela in relas.iter_mut() {
+            let sym = rela.get_sym();
+            if sym != 0 && text_section_sym == Some(sym as usize) {
+                let field = match rela.get_type() {
+                    relocation::R_X86_64_PC32 | relocation::R_X86_64_PLT32 => 4,
+                    _ => 0,
+                };
+                let c = &chunks[chunk_of((rela.get_addend() + field).max(0) as Elf64Addr)];
+                rela.set_info((c.section_sym as Elf64Xword) << 32 | rela.get_type());
+                rela.set_addend(rela.get_addend() - c.start as Elf64Sxword);
+            }
+        }
+
+        if sct.header.sh_info as usize == text_idx {
+            text_rela_idx = Some(idx);
+            for rela in relas.drain(..) {
+                let c = chunk_of(rela.get_offset());
+                let mut rela = rela;
+                rela.set_offset(rela.get_offset() - chunks[c].start);
+                split_relas[c].push(rela);
+            }
+            *relas = std::mem::take(&mut split_relas[0]);
+        }
+    }
+
+    // 新しいセクションの追加
+    let text_header = elf.sections[text_idx].header;
+    let mut created = Vec::new();
+    for (c, (_, _, name)) in chunks.iter().skip(1).zip(funcs.iter()) {
+        let mut header = text_header;
+        let start_align = if c.start == 0 {
+            header.sh_addralign
+        } else {
+            c.start & c.start.wrapping_neg()
+        };
+        header.sh_addralign = header.sh_addralign.min(start_align).max(1);
+        let name = format!(".text.{}", name);
+        created.push(name.clone());
+        elf.sections.push(section::Section64 {
+            name: name.into(),
+            header,
+            contents: section::Contents64::Raw(text[c.start as usize..c.end as usize].to_vec()),
+        });
+    }
+    if let Some(rela_idx) = text_rela_idx {
+        let rela_header = elf.sections[rela_idx].header;
+        for (i, relas) in split_relas.into_iter().enumerate().skip(1) {
+            if relas.is_empty() {
+                continue;
+            }
+            let mut header = rela_header;
+            header.sh_info = chunks[i].shndx as Elf64Word;
+            elf.sections.push(section::Section64 {
+                name: format!(".rela{}", created[i - 1]).into(),
+                header,
+                contents: section::Contents64::RelaSymbols(relas),
+            });
+        }
+    }
+    elf.sections[text_idx].contents =
+        section::Contents64::Raw(text[..chunks[0].end as usize].to_vec());
+    elf.condition();
+
+    Ok(created)
+}
+
+/// refuse the relative branches from a function to another chunk which have no relocations.
+fn refers_symbol(elf: &file::ELF64, symtab_idx: usize, sym_idx: usize) -> bool {
+    elf.sections
+        .iter()
+        .filter(|sct| sct.header.sh_link as usize == symtab_idx)
+        .filter_map(|sct| match &sct.contents {
+            section::Contents64::RelaSymbols(relas) => Some(relas),
+            _ => None,
+        })
+        .flat_map(|relas| relas.iter())
+        .any(|rela| rela.get_sym() as usize == sym_idx)
+}
+
+fn check_branches(
+    elf: &file::ELF64,
+    text_idx: usize,
+    text: &[u8],
+    funcs: &[(Elf64Addr, Elf64Xword, String)],
+    chunks: &[Chunk],
+) -> Result<(), TransformError> {
+    let arch = match detour::Arch::of(elf) {
+        Ok(arch) => arch,
+        Err(_) => return Ok(()),
+    };
+    let relocated: HashSet<Elf64Addr> = elf
+        .sections
+        .iter()
+        .filter(|sct| sct.header.sh_info as usize == text_idx)
+        .filter_map(|sct| match &sct.contents {
+            section::Contents64::RelaSymbols(relas) => Some(relas),
+            _ => None,
+        })
+        .flat_map(|relas| relas.iter().map(|rela| rela.get_offset()))
+        .collect();
+    let base = elf.sections[text_idx].header.sh_addr;
+
+    for ((start, size, _), c) in funcs.iter().zip(chunks.iter().skip(1)) {
+        let code = &text[*start as usize..c.end as usize];
+        let branches = arch
+            .relative_branches(code, *size as usize)
+            .map_err(|offset| TransformError::UnsupportedInstruction {
+                vaddr: base + start + offset as Elf64Addr,
+            })?;
+        for (field, to) in branches {
+            let field = start + field as Elf64Addr;
+            let to = *start as i64 + to;
+            if relocated.contains(&field) || (c.start as i64 <= to && to < c.end as i64) {
+                continue;
+            }
+            return Err(TransformError::UnrelocatedBranch {
+                from: base + field,
+                to: base.wrapping_add(to as Elf64Addr),
+            });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod transform_tests {
+    use super::*;
+
+    #[test]
+    fn edit_dynamic_keeps_offsets_test() {
+        let mut elf = builder::SharedObjectWriter::new()
+            .function(builder::ExportedFunction::new("f", vec![0xc3]))
+            .build()
+            .unwrap();
+        let dynstr = elf.first_shidx_by(|sct| sct.name == ".dynstr").unwrap();
+        let dynsym = elf.first_shidx_by(|sct| sct.name == ".dynsym").unwrap();
+
+        // パディングの後に重複した文字列を置き，fのst_nameをそちらに向ける
+        let dup = match &mut elf.sections[dynstr].contents {
+            section::Contents64::StrTab(strs) => {
+                let end = strs.last().map_or(1, |ent| ent.idx + ent.v.len() + 1);
+                strs.push(section::StrTabEntry {
+                    v: String::new(),
+                    idx: end,
+                });
+                strs.push(section::StrTabEntry {
+                    v: "f".to_string(),
+                    idx: end + 1,
+                });
+                end + 1
+            }
+            _ => unreachable!(),
+        };
+        if let section::Contents64::Symbols(syms) = &mut elf.sections[dynsym].contents {
+            let f = syms.iter_mut().find(|sym| sym.symbol_name == "f").unwrap();
+            f.st_name = dup as Elf64Word;
+        }
+
+        let mut needed = 0;
+        edit_dynamic(&mut elf, |entries, tab| {
+            needed = tab.add("libnew.so");
+            set_dynamic(
+                entries,
+                dynamic::EntryType::Needed,
+                Some(needed as Elf64Xword),
+            );
+        })
+        .unwrap();
+
+        let bytes = elf.sections[dynstr].contents.to_le_bytes();
+        assert_eq!(Ok("libnew.so"), section::read_name(&bytes, needed));
+        let syms = match &elf.sections[dynsym].contents {
+            section::Contents64::Symbols(syms) => syms.clone(),
+            _ => unreachable!(),
+        };
+        assert!(syms.iter().any(|sym| sym.st_name as usize == dup));
+        for sym in syms.iter() {
+            assert_eq!(
+                Ok(sym.symbol_name.as_str()),
+                section::read_name(&bytes, sym.st_name as usize)
+            );
+        }
+    }
+
+    #[test]
+    fn split_functions_test() {
+        // main: call helper(.text+0x10); ret
+        let mut code = vec![0xe8, 0, 0, 0, 0, 0xc3];
+        code.resize(0x10, 0x90);
+        code.extend_from_slice(&[0xc3, 0xc3]);
+
+        let mut elf = file::ELF64::default();
+        elf.ehdr.set_elf_type(header::Type::Rel);
+        elf.ehdr.set_machine(header::Machine::X8664);
+        elf.add_section(section::Section64::new(
+            ".text".to_string(),
+            section::ShdrPreparation64::default()
+                .ty(section::Type::ProgBits)
+                .flags([section::Flag::Alloc, section::Flag::ExecInstr].iter()),
+            section::Contents64::Raw(code),
+        ));
+        let mut section_sym = symbol::Symbol64 {
+            st_shndx: 1,
+            ..Default::default()
+        };
+        section_sym.set_info(symbol::Type::Section, symbol::Bind::Local);
+        let mut syms = vec![symbol::Symbol64::new_null_symbol(), section_sym];
+        for (name, value) in [("main", 0), ("helper", 0x10), ("unused", 0x11)].iter() {
+            let mut sym = symbol::Symbol64 {
+                symbol_name: (*name).into(),
+                st_shndx: 1,
+                st_value: *value,
+                st_size: 1,
+                ..Default::default()
+            };
+            sym.set_info(symbol::Type::Func, symbol::Bind::Global);
+            syms.push(sym);
+        }
+        elf.add_section(section::Section64::new(
+            ".symtab".to_string(),
+            section::ShdrPreparation64::default()
+                .ty(section::Type::SymTab)
+                .info(2),
+            section::Contents64::Symbols(syms),
+        ));
+        let mut rela = relocation::Rela64::default();
+        rela.set_offset(1);
+        rela.set_info(1 << 32 | relocation::R_X86_64_PLT32);
+        rela.set_addend(0x10 - 4);
+        elf.add_section(section::Section64::new(
+            ".rela.text".to_string(),
+            section::ShdrPreparation64::default()
+                .ty(section::Type::Rela)
+                .flags([section::Flag::InfoLink].iter())
+                .link(2)
+                .info(1),
+            section::Contents64::RelaSymbols(vec![rela]),
+        ));
+
+        // x86_64以外ではリロケーションの参照先を判定できない
+        let mut other = elf.clone();
+        other.ehdr.set_machine(header::Machine::Intel386);
+        assert!(matches!(
+            split_functions(&mut other),
+            Err(TransformError::UnsupportedMachine { .. })
+        ));
+        assert_eq!(elf.sections, other.sections);
+
+        let created = split_functions(&mut elf).unwrap();
+        assert_eq!(vec![".text.main", ".text.helper", ".text.unused"], created);
+        assert_eq!(0, elf.sections[1].header.sh_size);
+        let helper = elf
+            .first_shidx_by(|sct| sct.name == ".text.helper")
+            .unwrap();
+
+        // .text+0xcへの参照は.text.helperのセクションシンボルへの参照になる
+        let rela_main = elf
+            .first_section_by(|sct| sct.name == ".rela.text.main")
+            .unwrap();
+        let syms = match &elf.sections[2].contents {
+            section::Contents64::Symbols(syms) => syms.clone(),
+            _ => unreachable!(),
+        };
+        assert_eq!(5, elf.sections[2].header.sh_info);
+        if let section::Contents64::RelaSymbols(relas) = &rela_main.contents {
+            let target = &syms[relas[0].get_sym() as usize];
+            assert_eq!(symbol::Type::Section, target.get_type());
+            assert_eq!(helper, target.st_shndx as usize);
+            assert_eq!(-4, relas[0].get_addend());
+            assert_eq!(1, relas[0].get_offset());
+        }
+        let main = elf.first_shidx_by(|sct| sct.name == ".text.main").unwrap();
+        assert_eq!(main, rela_main.header.sh_info as usize);
+
+        // 分割したセクションはgc_sections()で削除できる
+        let removed = elf.gc_sections(&["main"]).unwrap();
+        assert_eq!(vec![".text", ".rela.text", ".text.unused"], removed);
+    }
+
+    #[test]
+    fn split_functions_branch_test() {
+        // helper: ret; api: call helper; ret
+        let code = vec![0xc3, 0xe8, 0xfa, 0xff, 0xff, 0xff, 0xc3];
+        let mut elf = file::ELF64::default();
+        elf.ehdr.set_elf_type(header::Type::Rel);
+        elf.ehdr.set_machine(header::Machine::X8664);
+        elf.add_section(section::Section64::new(
+            ".text".to_string(),
+            section::ShdrPreparation64::default()
+                .ty(section::Type::ProgBits)
+                .flags([section::Flag::Alloc, section::Flag::ExecInstr].iter()),
+            section::Contents64::Raw(code),
+        ));
+        let mut syms = vec![symbol::Symbol64::new_null_symbol()];
+        for (name, value, size, bind) in [
+            ("helper", 0, 1, symbol::Bind::Local),
+            ("api", 1, 6, symbol::Bind::Global),
+        ]
+        .iter()
+        {
+            let mut sym = symbol::Symbol64 {
+                symbol_name: (*name).into(),
+                st_shndx: 1,
+                st_value: *value,
+                st_size: *size,
+                ..Default::default()
+            };
+            sym.set_info(symbol::Type::Func, *bind);
+            syms.push(sym);
+        }
+        elf.add_section(section::Section64::new(
+            ".symtab".to_string(),
+            section::ShdrPreparation64::default()
+                .ty(section::Type::SymTab)
+                .info(2),
+            section::Contents64::Symbols(syms),
+        ));
+        // apiをシグネチャに持つグループ
+        elf.add_section(section::Section64::new(
+            ".group".to_string(),
+            section::ShdrPreparation64::default()
+                .ty(section::Type::Group)
+                .link(2)
+                .info(2),
+            section::Contents64::Raw(vec![1, 0, 0, 0]),
+        ));
+
+        // アセンブラが解決したcallは分割すると壊れるので拒否する
+        let before = elf.clone();
+        assert!(matches!(
+            split_functions(&mut elf),
+            Err(TransformError::UnrelocatedBranch { from: 2, to: 0 })
+        ));
+        assert_eq!(before, elf);
+
+        let mut rela = relocation::Rela64::default();
+        rela.set_offset(2);
+        rela.set_info(1 << 32 | relocation::R_X86_64_PLT32);
+        rela.set_addend(-4);
+        elf.add_section(section::Section64::new(
+            ".rela.text".to_string(),
+            section::ShdrPreparation64::default()
+                .ty(section::Type::Rela)
+                .flags([section::Flag::InfoLink].iter())
+                .link(2)
+                .info(1),
+            section::Contents64::RelaSymbols(vec![rela]),
+        ));
+        split_functions(&mut elf).unwrap();
+
+        // セクションシンボルの分だけシグネチャの番号がずれる
+        let symtab = elf.symtab().unwrap();
+        assert_eq!(
+            symtab.position("api"),
+            Some(elf.sections[3].header.sh_info as usize)
+        );
+    }
+}
@@ -0,0 +1,120 @@
+//! Setting the AArch64 branch protection features of linked files.
+
+use super::TransformError;
+use crate::*;
+
+/// set or clear `GNU_PROPERTY_AARCH64_FEATURE_1_BTI` in `.note.gnu.property`.
+///
+/// The property is patched in place, so the file must already have
+/// `GNU_PROPERTY_AARCH64_FEATURE_1_AND`(linkers emit it when any feature is enabled).
+/// Clearing the bit is how a binary whose code isn't fully BTI-compatible opts out of the enforcement.
+/// Returns the previous state of the bit.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::{analysis, file, header, section, transform};
+///
+/// let mut elf = file::ELF64::default();
+/// elf.ehdr.set_machine(header::Machine::Any(analysis::EM_AARCH64));
+///
+/// let mut note = vec![4, 0, 0, 0, 0x10, 0, 0, 0, 5, 0, 0, 0];
+/// note.extend_from_slice(b"GNU\0");
+/// // GNU_PROPERTY_AARCH64_FEATURE_1_AND: BTI | PAC
+/// note.extend_from_slice(&[0x00, 0x00, 0x00, 0xc0, 4, 0, 0, 0, 3, 0, 0, 0, 0, 0, 0, 0]);
+/// elf.add_section(section::Section64::new(
+///     ".note.gnu.property".to_string(),
+///     section::ShdrPreparation64::default().ty(section::Type::Note),
+///     section::Contents64::Raw(note),
+/// ));
+///
+/// assert!(transform::set_aarch64_bti(&mut elf, false).unwrap());
+/// let report = analysis::branch_protection(&elf).unwrap();
+/// assert!(!report.bti && report.pac);
+/// ```
+pub fn set_aarch64_bti(elf: &mut file::ELF64, enabled: bool) -> Result<bool, TransformError> {
+    if elf.ehdr.get_machine() != header::Machine::Any(analysis::EM_AARCH64) {
+        return Err(TransformError::UnsupportedMachine {
+            machine: elf.ehdr.e_machine,
+        });
+    }
+    let data = elf.ehdr.get_data();
+    let props = elf
+        .gnu_properties()
+        .ok_or(TransformError::MissingProperty {
+            pr_type: section::GNU_PROPERTY_AARCH64_FEATURE_1_AND,
+        })?
+        .map_err(TransformError::InvalidNote)?;
+    let property = props
+        .get(section::GNU_PROPERTY_AARCH64_FEATURE_1_AND)
+        .filter(|p| p.pr_data.len() >= 4)
+        .ok_or(TransformError::MissingProperty {
+            pr_type: section::GNU_PROPERTY_AARCH64_FEATURE_1_AND,
+        })?;
+    let features = props
+        .feature_1_and(section::GNU_PROPERTY_AARCH64_FEATURE_1_AND)
+        .unwrap_or(0);
+    let previous = features & section::GNU_PROPERTY_AARCH64_FEATURE_1_BTI != 0;
+
+    let features = if enabled {
+        features | section::GNU_PROPERTY_AARCH64_FEATURE_1_BTI
+    } else {
+        features & !section::GNU_PROPERTY_AARCH64_FEATURE_1_BTI
+    };
+    let encoded = match data {
+        header::Data::MSB2 => features.to_be_bytes(),
+        _ => features.to_le_bytes(),
+    };
+
+    let sct = elf
+        .sections
+        .iter_mut()
+        .find(|sct| {
+            sct.header.get_type() == section::Type::Note && sct.name == ".note.gnu.property"
+        })
+        .unwrap();
+    // Rawでなければバイト列に直してから書き換える
+    let mut bytes = sct.to_le_bytes();
+    bytes[property.offset..property.offset + 4].copy_from_slice(&encoded);
+    sct.contents = section::Contents64::Raw(bytes);
+    Ok(previous)
+}
+
+#[cfg(test)]
+mod branch_protection_tests {
+    use super::*;
+
+    #[test]
+    fn set_aarch64_bti_test() {
+        let mut elf = file::ELF64::default();
+        assert!(matches!(
+            set_aarch64_bti(&mut elf, true),
+            Err(TransformError::UnsupportedMachine { .. })
+        ));
+        elf.ehdr
+            .set_machine(header::Machine::Any(analysis::EM_AARCH64));
+        elf.ehdr.set_data(header::Data::MSB2);
+        assert!(matches!(
+            set_aarch64_bti(&mut elf, true),
+            Err(TransformError::MissingProperty { .. })
+        ));
+
+        // ビッグエンディアンのノート(PACのみ)
+        let mut note = vec![0, 0, 0, 4, 0, 0, 0, 0x10, 0, 0, 0, 5];
+        note.extend_from_slice(b"GNU\0");
+        note.extend_from_slice(&[0xc0, 0x00, 0x00, 0x00, 0, 0, 0, 4, 0, 0, 0, 2, 0, 0, 0, 0]);
+        elf.add_section(section::Section64::new(
+            ".note.gnu.property".to_string(),
+            section::ShdrPreparation64::default().ty(section::Type::Note),
+            section::Contents64::Raw(note),
+        ));
+
+        assert!(!set_aarch64_bti(&mut elf, true).unwrap());
+        let report = analysis::branch_protection(&elf).unwrap();
+        assert!(report.bti && report.pac);
+        assert!(set_aarch64_bti(&mut elf, true).unwrap());
+        assert!(set_aarch64_bti(&mut elf, false).unwrap());
+        let report = analysis::branch_protection(&elf).unwrap();
+        assert!(!report.bti && report.pac);
+    }
+}
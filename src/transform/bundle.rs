@@ -0,0 +1,263 @@
+//! Bundling applications with their libraries, AppImage-style.
+
+use std::path::{Path, PathBuf};
+
+use super::TransformError;
+use crate::*;
+
+/// the `DT_RUNPATH` of the bundled files, which find the libraries in `lib/` next to `bin/`
+pub const BUNDLE_RUNPATH: &str = "$ORIGIN/../lib";
+
+/// The options of `make_relocatable_bundle()`
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct BundleOptions {
+    soname_tag: Option<String>,
+}
+
+impl BundleOptions {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// rename the bundled libraries by inserting `-<tag>` before `.so`(e.g. `libfoo-tag.so.1`),
+    /// so that they never collide with the libraries of the system already loaded in the process.
+    pub fn rename_sonames(mut self, tag: &str) -> Self {
+        self.soname_tag = Some(tag.to_string());
+        self
+    }
+
+    fn bundled_name(&self, name: &str) -> String {
+        let tag = match &self.soname_tag {
+            Some(tag) => tag,
+            None => return name.to_string(),
+        };
+        match name.find(".so") {
+            Some(pos) => format!("{}-{}{}", &name[..pos], tag, &name[pos..]),
+            None => format!("{}-{}", name, tag),
+        }
+    }
+}
+
+/// set `DT_RUNPATH`(replacing `DT_RPATH`) and rename the libraries in
+/// `DT_NEEDED`, `DT_SONAME` and `.gnu.version_r` by `renames`, the pairs of the old and new names.
+///
+/// `.dynstr` grows for the new strings, so it's moved with `.dynamic` to a new segment if needed.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::{builder, transform};
+///
+/// let f = builder::ExportedFunction::new("f", vec![0xc3]);
+/// let mut elf = builder::SharedObjectWriter::new()
+///     .function(f)
+///     .soname("libfoo.so.1")
+///     .needed("libbar.so.2")
+///     .build()
+///     .unwrap();
+///
+/// let renames = [("libbar.so.2".to_string(), "libbar-app.so.2".to_string())];
+/// transform::relink_dependencies(&mut elf, Some("$ORIGIN"), &renames).unwrap();
+/// assert_eq!(vec!["libbar-app.so.2".to_string()], elf.needed());
+/// assert_eq!(Some("$ORIGIN".to_string()), elf.rpath());
+/// ```
+pub fn relink_dependencies(
+    elf: &mut file::ELF64,
+    runpath: Option<&str>,
+    renames: &[(String, String)],
+) -> Result<(), TransformError> {
+    let renamed = |name: &str| {
+        renames
+            .iter()
+            .find(|(old, _)| old == name)
+            .map(|(_, new)| new.as_str())
+    };
+    // .gnu.version_rのvn_fileもDT_NEEDEDと同じ名前を指す
+    let verneed_files: Vec<&str> = elf
+        .version_needs()
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|need| renamed(&need.file))
+        .collect();
+    super::edit_dynamic(elf, |entries, tab| {
+        for ent in entries.iter_mut() {
+            match ent.get_type() {
+                dynamic::EntryType::Needed | dynamic::EntryType::SOName => {
+                    let new = tab.get(ent.d_un as usize).and_then(renamed);
+                    if let Some(new) = new {
+                        ent.d_un = tab.add(new) as Elf64Xword;
+                    }
+                }
+                _ => {}
+            }
+        }
+        if let Some(runpath) = runpath {
+            entries.retain(|ent| {
+                !matches!(
+                    ent.get_type(),
+                    dynamic::EntryType::RPath | dynamic::EntryType::RunPath
+                )
+            });
+            let offset = tab.add(runpath) as Elf64Xword;
+            super::set_dynamic(entries, dynamic::EntryType::RunPath, Some(offset));
+        }
+        for new in verneed_files.iter() {
+            tab.add(new);
+        }
+    })?;
+
+    let dynstr = match elf.first_section_by(|sct| sct.header.get_type() == section::Type::DynSym) {
+        Some(dynsym) => dynsym.header.sh_link as usize,
+        None => return Ok(()),
+    };
+    let tab = match elf.sections.get(dynstr).map(|s| &s.contents) {
+        Some(section::Contents64::StrTab(strs)) => section::StringTable::from_parsed(strs.clone()),
+        _ => return Ok(()),
+    };
+    for sct in elf.sections.iter_mut() {
+        if sct.header.get_type() != section::Type::Any(section::SHT_GNU_VERNEED)
+            || sct.header.sh_link as usize != dynstr
+        {
+            continue;
+        }
+        let bytes = match &mut sct.contents {
+            section::Contents64::Raw(bytes) => bytes,
+            _ => continue,
+        };
+        // Elf64_Verneed: vn_version, vn_cnt, vn_file, vn_aux, vn_next
+        let mut vn = 0;
+        for _ in 0..sct.header.sh_info {
+            let field = |bytes: &[u8], at: usize| {
+                bytes
+                    .get(at..at + 4)
+                    .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            };
+            let file = match field(bytes, vn + 4) {
+                Some(file) => file as usize,
+                None => break,
+            };
+            let new = tab.get(file).and_then(renamed);
+            if let Some(offset) = new.and_then(|new| tab.offset_of(new)) {
+                bytes[vn + 4..vn + 8].copy_from_slice(&(offset as u32).to_le_bytes());
+            }
+            match field(bytes, vn + 12) {
+                Some(0) | None => break,
+                Some(next) => vn += next as usize,
+            }
+        }
+    }
+    Ok(())
+}
+
+/// copy `app` to `out_dir/bin` and `libs` to `out_dir/lib`, and rewrite them so that
+/// the application loads the bundled libraries wherever the directory is moved.
+///
+/// Every file gets `DT_RUNPATH` of `$ORIGIN/../lib` by `relink_dependencies()`.
+/// Each library is written with the name of its `DT_SONAME`(or the file name), which `DT_NEEDED` refers to.
+/// With `BundleOptions::rename_sonames()` the libraries are renamed,
+/// and the references to them in the bundled files are updated.
+/// Returns the paths of the written files, the application first.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::{builder, parser, transform};
+///
+/// let f = builder::ExportedFunction::new("f", vec![0xc3]);
+/// let app = builder::SharedObjectWriter::new()
+///     .function(f.clone())
+///     .needed("libdep.so.1")
+///     .build()
+///     .unwrap();
+/// let lib = builder::SharedObjectWriter::new()
+///     .function(f)
+///     .soname("libdep.so.1")
+///     .build()
+///     .unwrap();
+///
+/// let dir = std::env::temp_dir().join(format!("elf_utilities_bundle_doctest_{}", std::process::id()));
+/// std::fs::create_dir_all(&dir).unwrap();
+/// let app_path = dir.join("app");
+/// let lib_path = dir.join("libdep.so");
+/// std::fs::write(&app_path, app.to_le_bytes()).unwrap();
+/// std::fs::write(&lib_path, lib.to_le_bytes()).unwrap();
+///
+/// let out = dir.join("bundle");
+/// let written = transform::make_relocatable_bundle(
+///     app_path.to_str().unwrap(),
+///     &[lib_path.to_str().unwrap()],
+///     out.to_str().unwrap(),
+///     &transform::BundleOptions::new().rename_sonames("app"),
+/// )
+/// .unwrap();
+///
+/// assert_eq!(out.join("lib/libdep-app.so.1"), written[1]);
+/// let bundled = parser::parse_elf64(written[0].to_str().unwrap()).unwrap();
+/// assert_eq!(vec!["libdep-app.so.1".to_string()], bundled.needed());
+/// assert_eq!(Some(transform::BUNDLE_RUNPATH.to_string()), bundled.rpath());
+/// # std::fs::remove_dir_all(&dir).unwrap();
+/// ```
+pub fn make_relocatable_bundle(
+    app: &str,
+    libs: &[&str],
+    out_dir: &str,
+    options: &BundleOptions,
+) -> Result<Vec<PathBuf>, TransformError> {
+    let mut elf_app = read_elf64(app)?;
+    let mut elf_libs = Vec::new();
+    let mut renames = Vec::new();
+    for lib in libs.iter() {
+        let elf = read_elf64(lib)?;
+        let name = elf.soname().unwrap_or_else(|| file_name(lib));
+        let new = options.bundled_name(&name);
+        if new != name {
+            renames.push((name, new.clone()));
+        }
+        elf_libs.push((lib, new, elf));
+    }
+
+    let out = Path::new(out_dir);
+    let mut written = Vec::new();
+    relink_dependencies(&mut elf_app, Some(BUNDLE_RUNPATH), &renames)?;
+    let app_out = out.join("bin").join(file_name(app));
+    write_elf64(&elf_app, app, &app_out)?;
+    written.push(app_out);
+    for (lib, name, elf) in elf_libs.iter_mut() {
+        relink_dependencies(elf, Some(BUNDLE_RUNPATH), &renames)?;
+        let lib_out = out.join("lib").join(name.as_str());
+        write_elf64(elf, lib, &lib_out)?;
+        written.push(lib_out);
+    }
+    Ok(written)
+}
+
+fn file_name(path: &str) -> String {
+    Path::new(path)
+        .file_name()
+        .map_or_else(|| path.to_string(), |n| n.to_string_lossy().to_string())
+}
+
+fn read_elf64(path: &str) -> Result<file::ELF64, TransformError> {
+    let parse_error = |message: String| TransformError::Parse {
+        path: path.to_string(),
+        message,
+    };
+    match parser::parse_elf(path).map_err(|e| parse_error(e.to_string()))? {
+        file::ELF::ELF64(elf) => Ok(elf),
+        _ => Err(parse_error("not a 64-bit ELF file".to_string())),
+    }
+}
+
+/// write the file with the permissions of `original`.
+fn write_elf64(elf: &file::ELF64, original: &str, path: &Path) -> Result<(), TransformError> {
+    let io_error = |e: std::io::Error| TransformError::Io {
+        path: path.to_string_lossy().to_string(),
+        message: e.to_string(),
+    };
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(io_error)?;
+    }
+    std::fs::write(path, elf.to_le_bytes()).map_err(io_error)?;
+    let permissions = std::fs::metadata(original).map_err(io_error)?.permissions();
+    std::fs::set_permissions(path, permissions).map_err(io_error)
+}
@@ -0,0 +1,226 @@
+//! Injecting constructors into linked files.
+
+use super::TransformError;
+use crate::*;
+
+/// `R_RISCV_RELATIVE`
+const R_RISCV_RELATIVE: Elf64Xword = 3;
+
+/// the name of the section which contains the injected code
+pub const INJECTED_TEXT: &str = ".text.injected";
+
+/// make `code` run before `main` as a constructor, loading `deps` beforehand.
+///
+/// `code` is placed in a new executable segment and its address is appended to `.init_array`
+/// (which is created if absent), so it's called like `void f(int argc, char **argv, char **envp)`
+/// after the existing constructors.
+/// It must be position independent if the file is a PIE or a shared object.
+/// Each library in `deps` is added as `DT_NEEDED` unless it's already needed.
+///
+/// `.init_array`, `.dynamic` and `.dynstr`(and `.rela.dyn` for `ET_DYN`) are moved to a new writable segment
+/// by `move_to_new_segment()`, and `DT_*` entries are updated.
+/// For `ET_DYN` a relative relocation is added for the new entry,
+/// which is supported on x86_64, aarch64 and riscv64.
+/// Returns the address of the injected code.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::{file, transform};
+///
+/// let mut elf = file::ELF64::default();
+/// assert!(transform::inject_constructor(&mut elf, &[0xc3], &["libm.so.6"]).is_err());
+/// ```
+pub fn inject_constructor(
+    elf: &mut file::ELF64,
+    code: &[u8],
+    deps: &[&str],
+) -> Result<Elf64Addr, TransformError> {
+    let dynamic = elf
+        .first_shidx_by(|sct| sct.header.get_type() == section::Type::Dynamic)
+        .ok_or(TransformError::NotLinked)?;
+    let relative = if elf.ehdr.get_type() == header::Type::Dyn {
+        Some(
+            relative_type(elf.ehdr.e_machine).ok_or(TransformError::UnsupportedMachine {
+                machine: elf.ehdr.e_machine,
+            })?,
+        )
+    } else {
+        None
+    };
+    let dynstr = elf.sections[dynamic].header.sh_link as usize;
+    if !matches!(
+        elf.sections.get(dynstr).map(|s| &s.contents),
+        Some(section::Contents64::StrTab(_))
+    ) {
+        return Err(TransformError::NoDynamicSymbols);
+    }
+
+    // コードは書き込み不可のセグメントに置く
+    elf.sections.push(new_section(
+        INJECTED_TEXT,
+        section::Type::ProgBits,
+        &[section::Flag::Alloc, section::Flag::ExecInstr],
+        16,
+        section::Contents64::Raw(code.to_vec()),
+    ));
+    let text = elf.sections.len() - 1;
+    super::move_to_new_segment(elf, &[text])?;
+    let entry = elf.sections[text].header.sh_addr;
+
+    add_needed(elf, dynamic, dynstr, deps);
+
+    let init_array = match elf.first_shidx_by(|s| s.header.get_type() == section::Type::InitArray) {
+        Some(idx) => idx,
+        None => {
+            elf.sections.push(new_section(
+                ".init_array",
+                section::Type::InitArray,
+                &[section::Flag::Alloc, section::Flag::Write],
+                8,
+                section::Contents64::Raw(Vec::new()),
+            ));
+            elf.sections.len() - 1
+        }
+    };
+    if let section::Contents64::Raw(bytes) = &mut elf.sections[init_array].contents {
+        bytes.extend_from_slice(&entry.to_le_bytes());
+    }
+
+    let rela_dyn = relative.map(|ty| {
+        let idx = rela_dyn(elf, dynamic);
+        let mut rela = relocation::Rela64::default();
+        rela.set_info(ty);
+        rela.set_addend(entry as Elf64Sxword);
+        if let section::Contents64::RelaSymbols(relas) = &mut elf.sections[idx].contents {
+            relas.push(rela);
+        }
+        idx
+    });
+
+    // 移動後に.dynamicが大きくならないよう，先にエントリを用意しておく
+    let mut types = vec![
+        dynamic::EntryType::InitArray,
+        dynamic::EntryType::InitArraySz,
+        dynamic::EntryType::StrTab,
+        dynamic::EntryType::StrSz,
+    ];
+    if rela_dyn.is_some() {
+        types.extend_from_slice(&[
+            dynamic::EntryType::Rela,
+            dynamic::EntryType::RelaSz,
+            dynamic::EntryType::RelaEnt,
+        ]);
+    }
+    if let section::Contents64::Dynamics(entries) = &mut elf.sections[dynamic].contents {
+        for ty in types.iter() {
+            super::set_dynamic(entries, *ty, None);
+        }
+    }
+
+    let mut moved = vec![init_array, dynamic, dynstr];
+    moved.extend(rela_dyn);
+    super::move_to_new_segment(elf, &moved)?;
+
+    let init_array_addr = elf.sections[init_array].header.sh_addr;
+    let init_array_size = elf.sections[init_array].header.sh_size;
+    let mut values = vec![
+        init_array_addr,
+        init_array_size,
+        elf.sections[dynstr].header.sh_addr,
+        elf.sections[dynstr].header.sh_size,
+    ];
+    if let Some(idx) = rela_dyn {
+        if let section::Contents64::RelaSymbols(relas) = &mut elf.sections[idx].contents {
+            let last = relas.len() - 1;
+            relas[last].set_offset(init_array_addr + init_array_size - 8);
+        }
+        values.push(elf.sections[idx].header.sh_addr);
+        values.push(elf.sections[idx].header.sh_size);
+        values.push(relocation::Rela64::SIZE);
+    }
+    if let section::Contents64::Dynamics(entries) = &mut elf.sections[dynamic].contents {
+        for (ty, value) in types.into_iter().zip(values) {
+            super::set_dynamic(entries, ty, Some(value));
+        }
+    }
+
+    Ok(entry)
+}
+
+fn relative_type(machine: Elf64Half) -> Option<Elf64Xword> {
+    match header::Machine::from(machine) {
+        header::Machine::X8664 => Some(relocation::R_X86_64_RELATIVE),
+        _ if machine == 183 => Some(relocation::R_AARCH64_RELATIVE),
+        _ if machine == 243 => Some(R_RISCV_RELATIVE),
+        _ => None,
+    }
+}
+
+fn new_section(
+    name: &str,
+    ty: section::Type,
+    flags: &[section::Flag],
+    align: Elf64Xword,
+    contents: section::Contents64,
+) -> section::Section64 {
+    let mut hdr = section::ShdrPreparation64::default()
+        .ty(ty)
+        .flags(flags.iter());
+    hdr.sh_addralign = align;
+    section::Section64::new(name.to_string(), hdr, contents)
+}
+
+/// add `DT_NEEDED` entries after the existing ones.
+fn add_needed(elf: &mut file::ELF64, dynamic: usize, dynstr: usize, deps: &[&str]) {
+    let mut offsets = Vec::new();
+    if let section::Contents64::StrTab(strs) = &mut elf.sections[dynstr].contents {
+        let mut tab = section::StringTable::from_parsed(strs.clone());
+        for dep in deps.iter() {
+            offsets.push(tab.add(dep) as Elf64Xword);
+        }
+        *strs = tab.entries().to_vec();
+    }
+
+    if let section::Contents64::Dynamics(entries) = &mut elf.sections[dynamic].contents {
+        let needed = |ent: &dynamic::Dyn64| ent.get_type() == dynamic::EntryType::Needed;
+        let mut pos = entries.iter().rposition(needed).map_or(0, |i| i + 1);
+        for offset in offsets {
+            if entries.iter().any(|ent| needed(ent) && ent.d_un == offset) {
+                continue;
+            }
+            entries.insert(pos, dynamic::Dyn64::new(dynamic::EntryType::Needed, offset));
+            pos += 1;
+        }
+    }
+}
+
+/// the section `DT_RELA` points to, or a new `.rela.dyn`.
+fn rela_dyn(elf: &mut file::ELF64, dynamic: usize) -> usize {
+    let addr = match &elf.sections[dynamic].contents {
+        section::Contents64::Dynamics(entries) => entries
+            .iter()
+            .find(|ent| ent.get_type() == dynamic::EntryType::Rela)
+            .map(|ent| ent.d_un),
+        _ => None,
+    };
+    let found = addr.and_then(|addr| {
+        elf.first_shidx_by(|s| {
+            s.header.sh_addr == addr && matches!(s.contents, section::Contents64::RelaSymbols(_))
+        })
+    });
+    if let Some(idx) = found {
+        return idx;
+    }
+
+    let mut sct = new_section(
+        ".rela.dyn",
+        section::Type::Rela,
+        &[section::Flag::Alloc],
+        8,
+        section::Contents64::RelaSymbols(Vec::new()),
+    );
+    sct.header.sh_entsize = relocation::Rela64::SIZE;
+    elf.sections.push(sct);
+    elf.sections.len() - 1
+}
@@ -0,0 +1,584 @@
+//! Re-encoding an ELF64 into another class or data encoding.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use super::TransformError;
+use crate::layout::{align_up, is_nobits};
+use crate::*;
+
+const SHT_GNU_VERSYM: Elf64Word = 0x6fffffff;
+
+/// the format which `convert()` produces
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Target {
+    pub class: header::Class,
+    pub data: header::Data,
+}
+
+impl Target {
+    pub fn new(class: header::Class, data: header::Data) -> Self {
+        Self { class, data }
+    }
+
+    fn is_64bit(&self) -> bool {
+        self.class == header::Class::Bit64
+    }
+    fn is_big_endian(&self) -> bool {
+        self.data == header::Data::MSB2
+    }
+}
+
+/// A value which can't be represented in the target format
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Loss {
+    /// the value doesn't fit in the field and is truncated
+    Truncated {
+        location: String,
+        field: String,
+        value: u64,
+    },
+    /// the contents are copied as is although their layout depends on the format
+    VerbatimContents { name: String },
+    /// `p_offset` is no longer congruent to `p_vaddr` modulo `p_align`
+    MisalignedSegment { index: usize },
+}
+
+impl fmt::Display for Loss {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Truncated {
+                location,
+                field,
+                value,
+            } => write!(f, "{}: {} {:#x} is truncated", location, field, value),
+            Self::VerbatimContents { name } => {
+                write!(f, "section {}: contents are copied as is", name)
+            }
+            Self::MisalignedSegment { index } => {
+                write!(f, "segment {}: p_offset is misaligned", index)
+            }
+        }
+    }
+}
+
+/// The result of `convert()`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conversion {
+    pub bytes: Vec<u8>,
+    pub losses: Vec<Loss>,
+}
+
+/// re-encode the headers and the tables of `elf` for `target`.
+///
+/// The ELF header, the program/section header tables, symbol tables, relocations(both `SHT_RELA` and `SHT_REL`),
+/// dynamic sections, `SHT_HASH`, `SHT_GNU_versym` and the array sections(`.init_array` etc.) are re-encoded.
+/// Other contents are copied as is, and the sections whose layout depends on the format(e.g. notes)
+/// are reported as `Loss::VerbatimContents`.
+/// When the class changes, the sections are packed again in the order of their offsets,
+/// so the addresses are kept but loadable segments may no longer be mapped.
+///
+/// The parser reads only little-endian files, so the big-endian output is meant for other tools.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::{file, header, transform};
+///
+/// let mut elf = file::ELF64::default();
+/// elf.ehdr.e_entry = 0x1_0000_0000;
+///
+/// let target = transform::Target::new(header::Class::Bit32, header::Data::MSB2);
+/// let conversion = transform::convert(&elf, target).unwrap();
+/// assert_eq!(&[0x7f, b'E', b'L', b'F', 1, 2], &conversion.bytes[..6]);
+/// assert_eq!(
+///     "header: e_entry 0x100000000 is truncated",
+///     conversion.losses[0].to_string()
+/// );
+/// ```
+pub fn convert(elf: &file::ELF64, target: Target) -> Result<Conversion, TransformError> {
+    let valid_class = matches!(target.class, header::Class::Bit32 | header::Class::Bit64);
+    let valid_data = matches!(target.data, header::Data::LSB2 | header::Data::MSB2);
+    if !valid_class || !valid_data {
+        return Err(TransformError::UnsupportedTarget {
+            class: target.class,
+            data: target.data,
+        });
+    }
+    let identical = target.is_64bit() && !target.is_big_endian();
+
+    let mut losses = Vec::new();
+    let mut contents = Vec::with_capacity(elf.sections.len());
+    let mut entsizes = Vec::with_capacity(elf.sections.len());
+    for sct in elf.sections.iter() {
+        let mut w = Writer::new(target, &mut losses);
+        let entsize = encode_contents(&mut w, sct);
+        let bytes = w.buf;
+        if entsize.is_none()
+            && !identical
+            && !matches!(
+                sct.header.get_type(),
+                section::Type::Null
+                    | section::Type::ProgBits
+                    | section::Type::NoBits
+                    | section::Type::StrTab
+            )
+        {
+            losses.push(Loss::VerbatimContents {
+                name: sct.name.to_string(),
+            });
+        }
+        contents.push(bytes);
+        entsizes.push(entsize);
+    }
+
+    let sizes = HeaderSizes::new(target);
+    let mut offsets: Vec<u64> = elf.sections.iter().map(|s| s.header.sh_offset).collect();
+    let mut phoff = elf.ehdr.e_phoff;
+    let mut shoff = elf.ehdr.e_shoff;
+    let mut phdrs: Vec<segment::Phdr64> = elf.segments.iter().map(|s| s.header).collect();
+
+    // 64bitのままならテーブルの大きさは変わらないので，オフセットはそのまま使える
+    if !target.is_64bit() {
+        if phoff != 0 || !elf.segments.is_empty() {
+            phoff = sizes.ehdr;
+        }
+        let pht_end = phoff.max(sizes.ehdr) + sizes.phdr * elf.segments.len() as u64;
+
+        let mut order: Vec<usize> = (1..elf.sections.len()).collect();
+        order.sort_by_key(|&i| elf.sections[i].header.sh_offset);
+        let mut cur = pht_end;
+        for i in order {
+            let shdr = &elf.sections[i].header;
+            cur = align_up(cur, shdr.sh_addralign.max(1));
+            offsets[i] = cur;
+            if !is_nobits(shdr) {
+                cur += contents[i].len() as u64;
+            }
+        }
+        if shoff != 0 {
+            shoff = align_up(cur, 4);
+        }
+
+        phdrs = relocate_segments(elf, &offsets, &contents, phoff, &mut losses);
+    }
+
+    let mut w = Writer::new(target, &mut losses);
+    encode_ehdr(&mut w, elf, &sizes, phoff, shoff);
+    let mut bytes = w.buf;
+
+    let mut w = Writer::new(target, &mut losses);
+    for (i, phdr) in phdrs.iter().enumerate() {
+        encode_phdr(&mut w, i, phdr);
+    }
+    if !phdrs.is_empty() {
+        write_at(&mut bytes, phoff, &w.buf);
+    }
+
+    for (i, sct) in elf.sections.iter().enumerate() {
+        if !is_nobits(&sct.header) {
+            write_at(&mut bytes, offsets[i], &contents[i]);
+        }
+    }
+
+    if shoff != 0 {
+        let mut w = Writer::new(target, &mut losses);
+        for (i, sct) in elf.sections.iter().enumerate() {
+            let mut shdr = sct.header;
+            shdr.sh_offset = offsets[i];
+            if !is_nobits(&shdr) {
+                shdr.sh_size = contents[i].len() as u64;
+            }
+            if let Some(entsize) = entsizes[i] {
+                shdr.sh_entsize = entsize;
+            }
+            encode_shdr(&mut w, &sct.name, &shdr);
+        }
+        write_at(&mut bytes, shoff, &w.buf);
+    }
+
+    Ok(Conversion { bytes, losses })
+}
+
+struct HeaderSizes {
+    ehdr: u64,
+    phdr: u64,
+    shdr: u64,
+}
+
+impl HeaderSizes {
+    fn new(target: Target) -> Self {
+        if target.is_64bit() {
+            Self {
+                ehdr: header::Ehdr64::SIZE as u64,
+                phdr: segment::Phdr64::SIZE as u64,
+                shdr: section::Shdr64::SIZE as u64,
+            }
+        } else {
+            Self {
+                ehdr: header::Ehdr32::SIZE as u64,
+                phdr: segment::Phdr32::SIZE as u64,
+                shdr: section::Shdr32::SIZE as u64,
+            }
+        }
+    }
+}
+
+/// encodes values in the target format, and records the values which are truncated.
+struct Writer<'a> {
+    target: Target,
+    buf: Vec<u8>,
+    losses: &'a mut Vec<Loss>,
+}
+
+impl<'a> Writer<'a> {
+    fn new(target: Target, losses: &'a mut Vec<Loss>) -> Self {
+        Self {
+            target,
+            buf: Vec::new(),
+            losses,
+        }
+    }
+
+    fn u8(&mut self, v: u8) {
+        self.buf.push(v);
+    }
+    fn u16(&mut self, v: u16) {
+        if self.target.is_big_endian() {
+            self.buf.extend_from_slice(&v.to_be_bytes());
+        } else {
+            self.buf.extend_from_slice(&v.to_le_bytes());
+        }
+    }
+    fn u32(&mut self, v: u32) {
+        if self.target.is_big_endian() {
+            self.buf.extend_from_slice(&v.to_be_bytes());
+        } else {
+            self.buf.extend_from_slice(&v.to_le_bytes());
+        }
+    }
+    fn u64(&mut self, v: u64) {
+        if self.target.is_big_endian() {
+            self.buf.extend_from_slice(&v.to_be_bytes());
+        } else {
+            self.buf.extend_from_slice(&v.to_le_bytes());
+        }
+    }
+
+    /// `Elf32_Word` or `Elf64_Xword`(and addresses/offsets)
+    fn word(&mut self, location: &str, field: &str, v: u64) {
+        if self.target.is_64bit() {
+            self.u64(v);
+        } else {
+            let v = self.fit_u32(location, field, v);
+            self.u32(v);
+        }
+    }
+
+    /// `Elf32_Sword` or `Elf64_Sxword`
+    fn sword(&mut self, location: &str, field: &str, v: i64) {
+        if self.target.is_64bit() {
+            self.u64(v as u64);
+        } else {
+            if v as i32 as i64 != v {
+                self.truncated(location, field, v as u64);
+            }
+            self.u32(v as i32 as u32);
+        }
+    }
+
+    /// `r_info` of `Elf32_Rel(a)` or `Elf64_Rel(a)`
+    fn r_info(&mut self, location: &str, sym: u64, ty: u64) {
+        if self.target.is_64bit() {
+            self.u64(sym << 32 | ty);
+        } else {
+            if sym > 0xffffff || ty > 0xff {
+                self.truncated(location, "r_info", sym << 32 | ty);
+            }
+            self.u32(((sym as u32) << 8) | (ty as u32 & 0xff));
+        }
+    }
+
+    fn fit_u32(&mut self, location: &str, field: &str, v: u64) -> u32 {
+        if v > u32::MAX as u64 {
+            self.truncated(location, field, v);
+        }
+        v as u32
+    }
+
+    fn truncated(&mut self, location: &str, field: &str, value: u64) {
+        self.losses.push(Loss::Truncated {
+            location: location.to_string(),
+            field: field.to_string(),
+            value,
+        });
+    }
+}
+
+fn encode_ehdr(w: &mut Writer, elf: &file::ELF64, sizes: &HeaderSizes, phoff: u64, shoff: u64) {
+    let ehdr = &elf.ehdr;
+    let mut ident = ehdr.e_ident;
+    ident.class = w.target.class;
+    ident.data = w.target.data;
+
+    w.buf.extend_from_slice(&ident.to_bytes());
+    w.u16(ehdr.e_type);
+    w.u16(ehdr.e_machine);
+    w.u32(ehdr.e_version);
+    w.word("header", "e_entry", ehdr.e_entry);
+    w.word("header", "e_phoff", phoff);
+    w.word("header", "e_shoff", shoff);
+    w.u32(ehdr.e_flags);
+    w.u16(sizes.ehdr as u16);
+    w.u16(sizes.phdr as u16);
+    w.u16(elf.segments.len() as u16);
+    w.u16(sizes.shdr as u16);
+    w.u16(elf.sections.len() as u16);
+    w.u16(ehdr.e_shstrndx);
+}
+
+fn encode_phdr(w: &mut Writer, index: usize, phdr: &segment::Phdr64) {
+    let location = format!("segment {}", index);
+    if w.target.is_64bit() {
+        w.u32(phdr.p_type);
+        w.u32(phdr.p_flags);
+        w.u64(phdr.p_offset);
+        w.u64(phdr.p_vaddr);
+        w.u64(phdr.p_paddr);
+        w.u64(phdr.p_filesz);
+        w.u64(phdr.p_memsz);
+        w.u64(phdr.p_align);
+    } else {
+        w.u32(phdr.p_type);
+        w.word(&location, "p_offset", phdr.p_offset);
+        w.word(&location, "p_vaddr", phdr.p_vaddr);
+        w.word(&location, "p_paddr", phdr.p_paddr);
+        w.word(&location, "p_filesz", phdr.p_filesz);
+        w.word(&location, "p_memsz", phdr.p_memsz);
+        w.u32(phdr.p_flags);
+        w.word(&location, "p_align", phdr.p_align);
+    }
+}
+
+fn encode_shdr(w: &mut Writer, name: &str, shdr: &section::Shdr64) {
+    let location = format!("section {}", name);
+    w.u32(shdr.sh_name);
+    w.u32(shdr.sh_type);
+    w.word(&location, "sh_flags", shdr.sh_flags);
+    w.word(&location, "sh_addr", shdr.sh_addr);
+    w.word(&location, "sh_offset", shdr.sh_offset);
+    w.word(&location, "sh_size", shdr.sh_size);
+    w.u32(shdr.sh_link);
+    w.u32(shdr.sh_info);
+    w.word(&location, "sh_addralign", shdr.sh_addralign);
+    w.word(&location, "sh_entsize", shdr.sh_entsize);
+}
+
+/// encode the contents of the section, and returns `sh_entsize` if they are re-encoded.
+fn encode_contents(w: &mut Writer, sct: &section::Section64) -> Option<u64> {
+    let is_64bit = w.target.is_64bit();
+    let word_size = if is_64bit { 8 } else { 4 };
+
+    match &sct.contents {
+        section::Contents64::Symbols(syms) => {
+            for (i, sym) in syms.iter().enumerate() {
+                let location = format!("{}[{}]", sct.name, i);
+                w.u32(sym.st_name);
+                if is_64bit {
+                    w.u8(sym.st_info);
+                    w.u8(sym.st_other);
+                    w.u16(sym.st_shndx);
+                    w.u64(sym.st_value);
+                    w.u64(sym.st_size);
+                } else {
+                    w.word(&location, "st_value", sym.st_value);
+                    w.word(&location, "st_size", sym.st_size);
+                    w.u8(sym.st_info);
+                    w.u8(sym.st_other);
+                    w.u16(sym.st_shndx);
+                }
+            }
+            Some(if is_64bit {
+                symbol::Symbol64::SIZE as u64
+            } else {
+                symbol::Symbol32::SIZE as u64
+            })
+        }
+        section::Contents64::RelaSymbols(relas) => {
+            for (i, rela) in relas.iter().enumerate() {
+                let location = format!("{}[{}]", sct.name, i);
+                w.word(&location, "r_offset", rela.get_offset());
+                w.r_info(&location, rela.get_sym(), rela.get_type());
+                w.sword(&location, "r_addend", rela.get_addend());
+            }
+            Some(if is_64bit {
+                relocation::Rela64::SIZE
+            } else {
+                relocation::Rela32::SIZE
+            })
+        }
+        section::Contents64::Dynamics(dyns) => {
+            for (i, d) in dyns.iter().enumerate() {
+                let location = format!("{}[{}]", sct.name, i);
+                w.sword(&location, "d_tag", d.d_tag);
+                w.word(&location, "d_un", d.d_un);
+            }
+            Some(if is_64bit {
+                dynamic::Dyn64::SIZE as u64
+            } else {
+                dynamic::Dyn32::SIZE as u64
+            })
+        }
+        // 外部のコーデックの形式は分からないのでそのまま書く
+        section::Contents64::StrTab(_) | section::Contents64::Custom(_) => {
+            w.buf.extend_from_slice(&sct.to_le_bytes());
+            None
+        }
+        section::Contents64::Raw(raw) => {
+            let entsize = match sct.header.get_type() {
+                section::Type::Rel => 16,
+                section::Type::InitArray
+                | section::Type::FiniArray
+                | section::Type::PreInitArray => 8,
+                section::Type::Hash => 4,
+                section::Type::Any(SHT_GNU_VERSYM) => 2,
+                _ => 0,
+            };
+            if entsize == 0 || raw.len() % entsize != 0 {
+                w.buf.extend_from_slice(raw);
+                return None;
+            }
+
+            for (i, entry) in raw.chunks(entsize).enumerate() {
+                let location = format!("{}[{}]", sct.name, i);
+                match entsize {
+                    16 => {
+                        let info = read_u64(&entry[8..]);
+                        w.word(&location, "r_offset", read_u64(entry));
+                        w.r_info(&location, info >> 32, info & 0xffffffff);
+                    }
+                    8 => w.word(&location, "address", read_u64(entry)),
+                    4 => w.u32(u32::from_le_bytes([entry[0], entry[1], entry[2], entry[3]])),
+                    _ => w.u16(u16::from_le_bytes([entry[0], entry[1]])),
+                }
+            }
+            Some(match entsize {
+                16 => 2 * word_size,
+                8 => word_size,
+                _ => entsize as u64,
+            })
+        }
+    }
+}
+
+/// move the segments along with the sections in them.
+fn relocate_segments(
+    elf: &file::ELF64,
+    offsets: &[u64],
+    contents: &[Vec<u8>],
+    phoff: u64,
+    losses: &mut Vec<Loss>,
+) -> Vec<segment::Phdr64> {
+    // 旧オフセットから新オフセットへの対応付け
+    let map = |old: u64| -> u64 {
+        let containing = elf
+            .sections
+            .iter()
+            .enumerate()
+            .skip(1)
+            .filter(|(_, s)| !is_nobits(&s.header) && s.header.sh_offset <= old)
+            .max_by_key(|(_, s)| s.header.sh_offset);
+        match containing {
+            Some((i, s)) => offsets[i] + (old - s.header.sh_offset).min(contents[i].len() as u64),
+            None if old == 0 => 0,
+            None if old == elf.ehdr.e_phoff => phoff,
+            None => old.min(phoff),
+        }
+    };
+
+    let mut phdrs = Vec::with_capacity(elf.segments.len());
+    for (i, sgt) in elf.segments.iter().enumerate() {
+        let mut phdr = sgt.header;
+        if sgt.header.get_type() == segment::Type::Phdr {
+            phdr.p_offset = phoff;
+        } else {
+            let start = map(phdr.p_offset);
+            let end = map(phdr.p_offset + phdr.p_filesz).max(start);
+            phdr.p_offset = start;
+            phdr.p_filesz = end - start;
+        }
+        let align = phdr.p_align.max(1);
+        if phdr.p_offset != sgt.header.p_offset
+            && sgt.header.get_type() == segment::Type::Load
+            && phdr.p_offset % align != phdr.p_vaddr % align
+        {
+            losses.push(Loss::MisalignedSegment { index: i });
+        }
+        phdrs.push(phdr);
+    }
+    phdrs
+}
+
+fn read_u64(bytes: &[u8]) -> u64 {
+    let mut buf = [0; 8];
+    buf.copy_from_slice(&bytes[..8]);
+    u64::from_le_bytes(buf)
+}
+
+fn write_at(buf: &mut Vec<u8>, offset: u64, bytes: &[u8]) {
+    let offset = offset as usize;
+    if buf.len() < offset + bytes.len() {
+        buf.resize(offset + bytes.len(), 0);
+    }
+    buf[offset..offset + bytes.len()].copy_from_slice(bytes);
+}
+
+#[cfg(test)]
+mod convert_tests {
+    use super::*;
+
+    #[test]
+    fn convert_test() {
+        let mut elf = file::ELF64::default();
+        elf.ehdr.set_elf_type(header::Type::Rel);
+        let mut sym = symbol::Symbol64 {
+            symbol_name: "f".into(),
+            st_name: 1,
+            st_value: 0x10,
+            st_size: 0x20,
+            ..Default::default()
+        };
+        sym.set_info(symbol::Type::Func, symbol::Bind::Global);
+        elf.add_section(section::Section64::new(
+            ".symtab".to_string(),
+            section::ShdrPreparation64::default().ty(section::Type::SymTab),
+            section::Contents64::Symbols(vec![symbol::Symbol64::new_null_symbol(), sym]),
+        ));
+        elf.ehdr.set_class(header::Class::Bit64);
+        elf.ehdr.set_data(header::Data::LSB2);
+        elf.sections[1].header.sh_entsize = symbol::Symbol64::SIZE as u64;
+        elf.condition();
+
+        // 同じ形式なら to_le_bytes() と一致する
+        let same = Target::new(header::Class::Bit64, header::Data::LSB2);
+        let conversion = convert(&elf, same).unwrap();
+        assert_eq!(elf.to_le_bytes(), conversion.bytes);
+        assert!(conversion.losses.is_empty());
+
+        let target = Target::new(header::Class::Bit32, header::Data::MSB2);
+        let conversion = convert(&elf, target).unwrap();
+        assert!(conversion.losses.is_empty());
+        let bytes = conversion.bytes;
+        assert_eq!(&[0x00, 0x01], &bytes[0x10..0x12]);
+
+        // st_name, st_value, st_size, st_info, st_other, st_shndx の順
+        let symtab = elf.sections[1].header.sh_offset as usize;
+        let symtab = header::Ehdr32::SIZE as usize + symtab - header::Ehdr64::SIZE as usize;
+        let expected = [0, 0, 0, 1, 0, 0, 0, 0x10, 0, 0, 0, 0x20, 0x12, 0, 0, 0];
+        assert_eq!(&expected, &bytes[symtab + 16..symtab + 32]);
+
+        let invalid = Target::new(header::Class::None, header::Data::LSB2);
+        assert!(convert(&elf, invalid).is_err());
+    }
+}
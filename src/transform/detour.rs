@@ -0,0 +1,796 @@
+//! Detouring functions of linked files to hooks.
+
+use std::convert::TryFrom;
+
+use super::TransformError;
+use crate::layout::{align_up, is_alloc, is_nobits};
+use crate::*;
+
+/// the name of the section created when no code cave is found
+pub const DETOUR_SECTION: &str = ".detour";
+
+/// the size of the created `.detour`, which the later detours also use
+const DETOUR_SECTION_SIZE: usize = 0x100;
+
+const X86_64_NOP: u8 = 0x90;
+const X86_64_INT3: u8 = 0xcc;
+const X86_64_UD2: [u8; 2] = [0x0f, 0x0b];
+
+const AARCH64_NOP: u32 = 0xd503201f;
+const AARCH64_BRK_0: u32 = 0xd4200000;
+/// `ldr x16, #8`
+const AARCH64_LDR_X16_8: u32 = 0x58000050;
+const AARCH64_BR_X16: u32 = 0xd61f0200;
+/// lldがAArch64のコードの隙間を埋めるトラップ
+const AARCH64_LLD_TRAP: u32 = 0xd4d4d4d4;
+
+/// A detour written by `insert_detour()`
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Detour {
+    pub target: Elf64Addr,
+    pub hook: Elf64Addr,
+    /// the address of the trampoline, which runs the displaced instructions and jumps back to the target.
+    /// the hook calls it to run the original function.
+    pub trampoline: Elf64Addr,
+    /// the original bytes overwritten by the jump to the hook
+    pub displaced: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Arch {
+    X86_64,
+    AArch64,
+}
+
+impl Arch {
+    pub(super) fn of(elf: &file::ELF64) -> Result<Self, TransformError> {
+        match elf.ehdr.get_machine() {
+            header::Machine::X8664 => Ok(Arch::X86_64),
+            header::Machine::Any(analysis::EM_AARCH64) => Ok(Arch::AArch64),
+            _ => Err(TransformError::UnsupportedMachine {
+                machine: elf.ehdr.e_machine,
+            }),
+        }
+    }
+
+    /// the size of the longest jump, which reaches any address.
+    fn long_jump_size(self) -> usize {
+        match self {
+            // jmp [rip]; .quad
+            Arch::X86_64 => 14,
+            // ldr x16, #8; br x16; .quad
+            Arch::AArch64 => 16,
+        }
+    }
+
+    /// the trap placed after the jump back of the trampoline.
+    /// the cave finder doesn't take it as padding, so the trampoline is never reused.
+    fn trap(self) -> Vec<u8> {
+        match self {
+            Arch::X86_64 => X86_64_UD2.to_vec(),
+            Arch::AArch64 => AARCH64_BRK_0.to_le_bytes().to_vec(),
+        }
+    }
+
+    fn padding(self) -> u8 {
+        match self {
+            Arch::X86_64 => X86_64_INT3,
+            Arch::AArch64 => 0,
+        }
+    }
+
+    fn align(self) -> usize {
+        match self {
+            Arch::X86_64 => 16,
+            Arch::AArch64 => 4,
+        }
+    }
+
+    fn is_padding(self, code: &[u8]) -> bool {
+        match self {
+            Arch::X86_64 => code[0] == X86_64_INT3,
+            Arch::AArch64 => {
+                let insn = u32::from_le_bytes([code[0], code[1], code[2], code[3]]);
+                insn == 0 || insn == AARCH64_LLD_TRAP
+            }
+        }
+    }
+
+    /// the width of the unit `is_padding()` checks.
+    fn unit(self) -> usize {
+        match self {
+            Arch::X86_64 => 1,
+            Arch::AArch64 => 4,
+        }
+    }
+
+    /// encode the shortest jump from `pc` to `dest`.
+    fn jump(self, pc: Elf64Addr, dest: Elf64Addr, data: header::Data) -> Vec<u8> {
+        match self {
+            Arch::X86_64 => {
+                let disp = dest.wrapping_sub(pc + 5) as i64;
+                match i32::try_from(disp) {
+                    Ok(disp) => {
+                        let mut code = vec![0xe9];
+                        code.extend_from_slice(&disp.to_le_bytes());
+                        code
+                    }
+                    Err(_) => {
+                        let mut code = vec![0xff, 0x25, 0, 0, 0, 0];
+                        code.extend_from_slice(&dest.to_le_bytes());
+                        code
+                    }
+                }
+            }
+            Arch::AArch64 => {
+                let disp = dest.wrapping_sub(pc) as i64;
+                // bは±128MiB
+                if (-(1 << 27)..(1 << 27)).contains(&disp) {
+                    let insn = 0x1400_0000 | ((disp >> 2) as u32 & 0x03ff_ffff);
+                    return insn.to_le_bytes().to_vec();
+                }
+                // 命令は常にリトルエンディアンだが，リテラルはデータのエンディアン
+                let mut code = AARCH64_LDR_X16_8.to_le_bytes().to_vec();
+                code.extend_from_slice(&AARCH64_BR_X16.to_le_bytes());
+                match data {
+                    header::Data::MSB2 => code.extend_from_slice(&dest.to_be_bytes()),
+                    _ => code.extend_from_slice(&dest.to_le_bytes()),
+                }
+                code
+            }
+        }
+    }
+
+    /// fill `len` bytes with nops.
+    fn nops(self, len: usize) -> Vec<u8> {
+        match self {
+            Arch::X86_64 => vec![X86_64_NOP; len],
+            Arch::AArch64 => AARCH64_NOP
+                .to_le_bytes()
+                .iter()
+                .copied()
+                .cycle()
+                .take(len)
+                .collect(),
+        }
+    }
+
+    /// decode the instruction at the head of `code`, which can be moved to the trampoline.
+    fn decode(self, code: &[u8]) -> Option<Instruction> {
+        match self {
+            Arch::X86_64 => decode_x86_64(code).filter(|insn| insn.branch.is_none()),
+            Arch::AArch64 => decode_aarch64(code),
+        }
+    }
+
+    /// the relative branches in the first `len` bytes of `code`.
+    ///
+    /// Returns the offset of the field which a relocation would patch, and the offset of the destination.
+    /// `Err` has the offset of the first instruction which can't be decoded.
+    pub(super) fn relative_branches(
+        self,
+        code: &[u8],
+        len: usize,
+    ) -> Result<Vec<(usize, i64)>, usize> {
+        let mut branches = Vec::new();
+        let mut pos = 0;
+        while pos < len.min(code.len()) {
+            let (insn_len, branch) = match self {
+                Arch::X86_64 => {
+                    let insn = decode_x86_64(&code[pos..]).ok_or(pos)?;
+                    (insn.len, insn.branch)
+                }
+                Arch::AArch64 => {
+                    let bytes = code.get(pos..pos + 4).ok_or(pos)?;
+                    let insn = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+                    (4, branch_aarch64(insn))
+                }
+            };
+            if let Some(branch) = branch {
+                branches.push((pos + branch.field, pos as i64 + branch.disp));
+            }
+            pos += insn_len;
+        }
+        Ok(branches)
+    }
+}
+
+/// A decoded instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Instruction {
+    len: usize,
+    /// the offset of the RIP-relative disp32 in the instruction
+    rip_disp: Option<usize>,
+    /// the relative branch, which can't be moved
+    branch: Option<Branch>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Branch {
+    /// the offset of the displacement in the instruction
+    field: usize,
+    /// the destination relative to the instruction
+    disp: i64,
+}
+
+/// find `len` bytes of padding in the executable sections, which no symbol covers.
+///
+/// The padding is `int3`(0xcc) for x86_64, and zeros or lld's `0xd4d4d4d4` for AArch64.
+/// The returned address is aligned to 16 bytes for x86_64 and 4 bytes for AArch64.
+/// Returns `None` if the machine is neither of them.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::{builder, transform};
+///
+/// // ret, and padding
+/// let mut code = vec![0xc3];
+/// code.resize(0x40, 0xcc);
+/// let f = builder::ExportedFunction::new("f", code);
+/// let elf = builder::SharedObjectWriter::new().function(f).build().unwrap();
+///
+/// // the symbol `f` covers the padding
+/// assert_eq!(None, transform::find_code_cave(&elf, 0x20));
+/// ```
+pub fn find_code_cave(elf: &file::ELF64, len: usize) -> Option<Elf64Addr> {
+    let arch = Arch::of(elf).ok()?;
+    let exec: Elf64Xword = section::Flag::ExecInstr.into();
+    let symbols = defined_ranges(elf);
+
+    for sct in elf.sections.iter() {
+        if !is_alloc(&sct.header) || is_nobits(&sct.header) || sct.header.sh_flags & exec == 0 {
+            continue;
+        }
+        let code = match &sct.contents {
+            section::Contents64::Raw(code) => code,
+            _ => continue,
+        };
+        let base = sct.header.sh_addr;
+        let unit = arch.unit();
+        let mut start = None;
+        let mut offset = 0;
+        while offset + unit <= code.len() {
+            if !arch.is_padding(&code[offset..]) {
+                start = None;
+                offset += unit;
+                continue;
+            }
+            let run = *start.get_or_insert(offset);
+            // 隙間の中で揃ったアドレスから使う
+            let cave = align_up(base + run as Elf64Addr, arch.align() as Elf64Addr);
+            let end = base + (offset + unit) as Elf64Addr;
+            if cave + len as Elf64Addr <= end
+                && !symbols
+                    .iter()
+                    .any(|(s, e)| *s < cave + len as Elf64Addr && cave < *e)
+            {
+                return Some(cave);
+            }
+            offset += unit;
+        }
+    }
+    None
+}
+
+/// the ranges of the symbols with sizes.
+fn defined_ranges(elf: &file::ELF64) -> Vec<(Elf64Addr, Elf64Addr)> {
+    let mut ranges = Vec::new();
+    for sct in elf.sections.iter() {
+        if let section::Contents64::Symbols(syms) = &sct.contents {
+            ranges.extend(
+                syms.iter()
+                    .filter(|sym| sym.st_shndx != 0 && sym.st_size != 0)
+                    .map(|sym| (sym.st_value, sym.st_value + sym.st_size)),
+            );
+        }
+    }
+    ranges
+}
+
+/// detour the function at `target_vaddr` to `hook_vaddr`.
+///
+/// The instructions at the target are replaced with a jump to the hook,
+/// and a trampoline running the displaced instructions and jumping back to the rest of the target
+/// is written to a code cave found by `find_code_cave()`.
+/// If no cave is found, `.detour` is created in a new segment by `move_to_new_segment()`,
+/// and its rest is used as caves by the later detours.
+///
+/// The displaced instructions must not be branch targets, and must be in the function:
+/// the target must be covered by a symbol with its size(`NoFunction` otherwise),
+/// and `FunctionTooSmall` is returned if the jump doesn't fit in it.
+/// RIP-relative operands of x86_64 are adjusted for the trampoline,
+/// but relative branches(and AArch64's PC-relative instructions like `adrp`) can't be displaced.
+/// Supported machines are x86_64 and AArch64.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::{builder, section, transform};
+///
+/// // push rbp; mov rbp, rsp; mov eax, 42; pop rbp; ret
+/// let main = vec![0x55, 0x48, 0x89, 0xe5, 0xb8, 0x2a, 0x00, 0x00, 0x00, 0x5d, 0xc3];
+/// let mut elf = builder::ExecutableWriter::new()
+///     .function(builder::ExportedFunction::new("main", main))
+///     .function(builder::ExportedFunction::new("hook", vec![0xc3]))
+///     .start_stub(builder::start_stub("main"))
+///     .build()
+///     .unwrap();
+/// let symtab = elf.first_section_by(|sct| sct.name == ".symtab").unwrap();
+/// let addr = |name: &str| match &symtab.contents {
+///     section::Contents64::Symbols(syms) => {
+///         syms.iter().find(|sym| sym.symbol_name == name).unwrap().st_value
+///     }
+///     _ => unreachable!(),
+/// };
+/// let (main, hook) = (addr("main"), addr("hook"));
+///
+/// let detour = transform::insert_detour(&mut elf, main, hook).unwrap();
+/// // 5バイトのjmpで上書きされる push rbp; mov rbp, rsp; mov eax, 42 が退避される
+/// assert_eq!(9, detour.displaced.len());
+/// assert!(elf.first_section_by(|sct| sct.name == ".detour").is_some());
+/// ```
+pub fn insert_detour(
+    elf: &mut file::ELF64,
+    target_vaddr: Elf64Addr,
+    hook_vaddr: Elf64Addr,
+) -> Result<Detour, TransformError> {
+    let arch = Arch::of(elf)?;
+    match elf.ehdr.get_type() {
+        header::Type::Exec | header::Type::Dyn => {}
+        _ => return Err(TransformError::NotLinked),
+    }
+    let data = elf.ehdr.get_data();
+
+    // フックへのジャンプが上書きする命令を求める
+    let patch = arch.jump(target_vaddr, hook_vaddr, data);
+    let function_end = defined_ranges(elf)
+        .into_iter()
+        .filter(|(start, end)| *start <= target_vaddr && target_vaddr < *end)
+        .map(|(_, end)| end)
+        .min()
+        .ok_or(TransformError::NoFunction {
+            vaddr: target_vaddr,
+        })?;
+    let code = read_code(elf, target_vaddr)?;
+    let too_small = TransformError::FunctionTooSmall {
+        vaddr: target_vaddr,
+    };
+    if function_end - target_vaddr < patch.len() as Elf64Addr {
+        return Err(too_small);
+    }
+    let mut insns = Vec::new();
+    let mut displaced_len = 0;
+    while displaced_len < patch.len() {
+        let insn =
+            arch.decode(&code[displaced_len..])
+                .ok_or(TransformError::UnsupportedInstruction {
+                    vaddr: target_vaddr + displaced_len as Elf64Addr,
+                })?;
+        insns.push((displaced_len, insn));
+        displaced_len += insn.len;
+        // 関数の外の命令は退避しない
+        if target_vaddr + displaced_len as Elf64Addr > function_end {
+            return Err(too_small);
+        }
+    }
+    let displaced = code[..displaced_len].to_vec();
+
+    let trap = arch.trap();
+    let cave_len = displaced_len + arch.long_jump_size() + trap.len();
+    // 失敗しても元のファイルを変更しないよう，新しいセグメントは複製に作る
+    let mut moved = None;
+    let trampoline = match find_code_cave(elf, cave_len) {
+        Some(cave) => cave,
+        None => {
+            let mut copy = elf.clone();
+            let cave = create_cave(&mut copy, arch, cave_len)?;
+            moved = Some(copy);
+            cave
+        }
+    };
+
+    let mut code = displaced.clone();
+    for (offset, insn) in insns.iter() {
+        let disp_offset = match insn.rip_disp {
+            Some(disp_offset) => offset + disp_offset,
+            None => continue,
+        };
+        let field = &mut code[disp_offset..disp_offset + 4];
+        let disp = i32::from_le_bytes([field[0], field[1], field[2], field[3]]) as i64;
+        // 命令の位置がずれた分だけ変位を補正する
+        let moved = trampoline.wrapping_sub(target_vaddr) as i64;
+        let disp =
+            i32::try_from(disp - moved).map_err(|_| TransformError::UnsupportedInstruction {
+                vaddr: target_vaddr + *offset as Elf64Addr,
+            })?;
+        field.copy_from_slice(&disp.to_le_bytes());
+    }
+    let back = trampoline + code.len() as Elf64Addr;
+    code.extend(arch.jump(back, target_vaddr + displaced_len as Elf64Addr, data));
+    code.extend(trap);
+    if let Some(moved) = moved {
+        *elf = moved;
+    }
+    write_code(elf, trampoline, &code)?;
+
+    let mut patch = patch;
+    patch.extend(arch.nops(displaced_len - patch.len()));
+    write_code(elf, target_vaddr, &patch)?;
+
+    Ok(Detour {
+        target: target_vaddr,
+        hook: hook_vaddr,
+        trampoline,
+        displaced,
+    })
+}
+
+/// create `.detour` filled with the padding in a new segment, and return the cave at its head.
+fn create_cave(elf: &mut file::ELF64, arch: Arch, len: usize) -> Result<Elf64Addr, TransformError> {
+    let size = align_up(len.max(DETOUR_SECTION_SIZE) as u64, arch.align() as u64) as usize;
+    let mut hdr = section::ShdrPreparation64::default()
+        .ty(section::Type::ProgBits)
+        .flags([section::Flag::Alloc, section::Flag::ExecInstr].iter());
+    hdr.sh_addralign = arch.align() as Elf64Xword;
+    elf.sections.push(section::Section64::new(
+        DETOUR_SECTION.to_string(),
+        hdr,
+        section::Contents64::Raw(vec![arch.padding(); size]),
+    ));
+    let shidx = elf.sections.len() - 1;
+    super::move_to_new_segment(elf, &[shidx])?;
+    Ok(elf.sections[shidx].header.sh_addr)
+}
+
+/// the bytes from `vaddr` to the end of the section.
+fn read_code(elf: &file::ELF64, vaddr: Elf64Addr) -> Result<Vec<u8>, TransformError> {
+    let sct = elf
+        .address_index()
+        .section_containing_vaddr(vaddr)
+        .filter(|sct| !is_nobits(&sct.header))
+        .ok_or(TransformError::Unmapped { vaddr })?;
+    match &sct.contents {
+        section::Contents64::Raw(code) => {
+            Ok(code[(vaddr - sct.header.sh_addr) as usize..].to_vec())
+        }
+        _ => Err(TransformError::Unmapped { vaddr }),
+    }
+}
+
+fn write_code(elf: &mut file::ELF64, vaddr: Elf64Addr, bytes: &[u8]) -> Result<(), TransformError> {
+    let shidx = elf
+        .address_index()
+        .shidx_containing_vaddr(vaddr)
+        .ok_or(TransformError::Unmapped { vaddr })?;
+    let sct = &mut elf.sections[shidx];
+    let start = (vaddr - sct.header.sh_addr) as usize;
+    match &mut sct.contents {
+        section::Contents64::Raw(code) if start + bytes.len() <= code.len() => {
+            code[start..start + bytes.len()].copy_from_slice(bytes);
+            Ok(())
+        }
+        _ => Err(TransformError::Unmapped { vaddr }),
+    }
+}
+
+/// decode an AArch64 instruction, rejecting the PC-relative ones.
+fn decode_aarch64(code: &[u8]) -> Option<Instruction> {
+    if code.len() < 4 {
+        return None;
+    }
+    let insn = u32::from_le_bytes([code[0], code[1], code[2], code[3]]);
+    let pc_relative = [
+        // adr, adrp
+        (0x1f00_0000, 0x1000_0000),
+        // b, bl
+        (0x7c00_0000, 0x1400_0000),
+        // b.cond
+        (0xff00_0010, 0x5400_0000),
+        // cbz, cbnz
+        (0x7e00_0000, 0x3400_0000),
+        // tbz, tbnz
+        (0x7e00_0000, 0x3600_0000),
+        // ldr (literal), ldrsw (literal), prfm (literal)
+        (0x3b00_0000, 0x1800_0000),
+    ];
+    if pc_relative
+        .iter()
+        .any(|(mask, value)| insn & mask == *value)
+    {
+        return None;
+    }
+    Some(Instruction {
+        len: 4,
+        rip_disp: None,
+        branch: None,
+    })
+}
+
+/// the branch of an AArch64 instruction(`b`, `bl`, `b.cond`, `cbz`, `cbnz`, `tbz` and `tbnz`).
+fn branch_aarch64(insn: u32) -> Option<Branch> {
+    // (マスク, 値, 即値の位置, 即値のビット数)
+    let branches = [
+        (0x7c00_0000, 0x1400_0000, 0, 26),
+        (0xff00_0010, 0x5400_0000, 5, 19),
+        (0x7e00_0000, 0x3400_0000, 5, 19),
+        (0x7e00_0000, 0x3600_0000, 5, 14),
+    ];
+    let (_, _, shift, bits) = branches
+        .iter()
+        .find(|(mask, value, _, _)| insn & mask == *value)?;
+    let imm = (insn >> shift) & ((1 << bits) - 1);
+    // 符号拡張して4倍する
+    let disp = ((imm << (32 - bits)) as i32 >> (32 - bits)) as i64 * 4;
+    Some(Branch { field: 0, disp })
+}
+
+/// decode the length of an x86_64 instruction.
+///
+/// Only the common general-purpose and SSE instructions are known(no VEX/EVEX).
+fn decode_x86_64(code: &[u8]) -> Option<Instruction> {
+    let mut pos = 0;
+    let mut operand16 = false;
+    let mut rex_w = false;
+    loop {
+        match *code.get(pos)? {
+            0x66 => operand16 = true,
+            0x26 | 0x2e | 0x36 | 0x3e | 0x64 | 0x65 | 0x67 | 0xf0 | 0xf2 | 0xf3 => {}
+            _ => break,
+        }
+        pos += 1;
+    }
+    if let 0x40..=0x4f = *code.get(pos)? {
+        rex_w = code[pos] & 0x08 != 0;
+        pos += 1;
+    }
+    let imm_z = if operand16 { 2 } else { 4 };
+    let mut relative = false;
+
+    let op = *code.get(pos)?;
+    pos += 1;
+    // (ModR/Mの有無, 即値の長さ)
+    let (modrm, imm) = if op == 0x0f {
+        let op2 = *code.get(pos)?;
+        pos += 1;
+        match op2 {
+            0x05 | 0x0b | 0xa2 => (false, 0),
+            0x80..=0x8f => {
+                relative = true;
+                (false, 4)
+            }
+            0x10..=0x17 | 0x1e | 0x1f | 0x28..=0x2f | 0x40..=0x6f | 0x74..=0x7f => (true, 0),
+            0x70..=0x73 | 0xba | 0xc2 | 0xc4..=0xc6 => (true, 1),
+            0x90..=0x9f | 0xa3 | 0xab | 0xaf | 0xb0 | 0xb1 | 0xb3 | 0xb6 | 0xb7 | 0xbb..=0xbf => {
+                (true, 0)
+            }
+            0xc0 | 0xc1 | 0xc7 | 0xd0..=0xfe => (true, 0),
+            _ => return None,
+        }
+    } else {
+        match op {
+            0x00..=0x3f => match op & 7 {
+                0..=3 => (true, 0),
+                4 => (false, 1),
+                5 => (false, imm_z),
+                _ => return None,
+            },
+            0x50..=0x5f | 0x90..=0x99 | 0xc3 | 0xc9 | 0xcc | 0xf4 | 0xf5 | 0xf8..=0xfd => {
+                (false, 0)
+            }
+            0x63 | 0x84..=0x8b | 0x8d | 0x8f | 0xd0..=0xd3 | 0xfe | 0xff => (true, 0),
+            0x68 => (false, 4),
+            0x69 | 0x81 | 0xc7 => (true, imm_z),
+            0x6a | 0xa8 | 0xb0..=0xb7 => (false, 1),
+            0x6b | 0x80 | 0x83 | 0xc0 | 0xc1 | 0xc6 => (true, 1),
+            0xa9 => (false, imm_z),
+            // 相対分岐
+            0x70..=0x7f | 0xe0..=0xe3 | 0xeb => {
+                relative = true;
+                (false, 1)
+            }
+            0xe8 | 0xe9 => {
+                relative = true;
+                (false, imm_z)
+            }
+            0xb8..=0xbf if rex_w => (false, 8),
+            0xb8..=0xbf => (false, imm_z),
+            0xf6 | 0xf7 => {
+                // test だけが即値を持つ
+                let reg = (*code.get(pos)? >> 3) & 7;
+                let imm = match (reg, op) {
+                    (0 | 1, 0xf6) => 1,
+                    (0 | 1, _) => imm_z,
+                    _ => 0,
+                };
+                (true, imm)
+            }
+            _ => return None,
+        }
+    };
+
+    let mut rip_disp = None;
+    if modrm {
+        let modrm = *code.get(pos)?;
+        pos += 1;
+        let (md, rm) = (modrm >> 6, modrm & 7);
+        if md != 3 && rm == 4 {
+            let sib = *code.get(pos)?;
+            pos += 1;
+            if md == 0 && sib & 7 == 5 {
+                pos += 4;
+            }
+        }
+        match (md, rm) {
+            (0, 5) => {
+                rip_disp = Some(pos);
+                pos += 4;
+            }
+            (1, _) => pos += 1,
+            (2, _) => pos += 4,
+            _ => {}
+        }
+    }
+    let field = pos;
+    pos += imm;
+    if pos > code.len() {
+        return None;
+    }
+    let branch = if relative {
+        let rel = match &code[field..pos] {
+            [rel] => *rel as i8 as i64,
+            [a, b] => i16::from_le_bytes([*a, *b]) as i64,
+            [a, b, c, d] => i32::from_le_bytes([*a, *b, *c, *d]) as i64,
+            _ => return None,
+        };
+        Some(Branch {
+            field,
+            disp: pos as i64 + rel,
+        })
+    } else {
+        None
+    };
+    Some(Instruction {
+        len: pos,
+        rip_disp,
+        branch,
+    })
+}
+
+#[cfg(test)]
+mod detour_tests {
+    use super::*;
+
+    #[test]
+    fn decode_x86_64_test() {
+        let len = |code: &[u8]| decode_x86_64(code).map(|insn| (insn.len, insn.rip_disp));
+        // endbr64
+        assert_eq!(Some((4, None)), len(&[0xf3, 0x0f, 0x1e, 0xfa]));
+        // sub rsp, 0x18
+        assert_eq!(Some((4, None)), len(&[0x48, 0x83, 0xec, 0x18]));
+        // mov rax, qword ptr [rip + 0x2fe1]
+        assert_eq!(
+            Some((7, Some(3))),
+            len(&[0x48, 0x8b, 0x05, 0xe1, 0x2f, 0x00, 0x00])
+        );
+        // mov dword ptr [rsp + 8], 1
+        assert_eq!(
+            Some((8, None)),
+            len(&[0xc7, 0x44, 0x24, 0x08, 0x01, 0x00, 0x00, 0x00])
+        );
+        // movabs rax, imm64
+        assert_eq!(Some((10, None)), len(&[0x48, 0xb8, 1, 2, 3, 4, 5, 6, 7, 8]));
+        // call rel32, je rel8 は移動できない
+        assert_eq!(None, Arch::X86_64.decode(&[0xe8, 0, 0, 0, 0]));
+        assert_eq!(None, Arch::X86_64.decode(&[0x74, 0x02]));
+        let branch = |code: &[u8]| decode_x86_64(code).and_then(|insn| insn.branch);
+        assert_eq!(
+            Some(Branch { field: 1, disp: 0 }),
+            branch(&[0xe8, 0xfb, 0xff, 0xff, 0xff])
+        );
+        assert_eq!(
+            Some(Branch { field: 2, disp: 6 }),
+            branch(&[0x0f, 0x84, 0, 0, 0, 0])
+        );
+        assert_eq!(Some(Branch { field: 1, disp: 4 }), branch(&[0x74, 0x02]));
+    }
+
+    #[test]
+    fn relative_branches_test() {
+        // xor eax, eax; jmp -4; call +0x10; ret
+        let code = [0x31, 0xc0, 0xeb, 0xfc, 0xe8, 0x10, 0, 0, 0, 0xc3];
+        assert_eq!(
+            Ok(vec![(3, 0), (5, 0x19)]),
+            Arch::X86_64.relative_branches(&code, code.len())
+        );
+        // VEXは解釈できない
+        assert_eq!(
+            Err(2),
+            Arch::X86_64.relative_branches(&[0x31, 0xc0, 0xc5, 0xf8, 0x77], 5)
+        );
+
+        // bl -4; cbz x0, +8; ret
+        let code: Vec<u8> = [0x97ff_ffff_u32, 0xb400_0040, 0xd65f_03c0]
+            .iter()
+            .flat_map(|insn| insn.to_le_bytes().to_vec())
+            .collect();
+        assert_eq!(
+            Ok(vec![(0, -4), (4, 12)]),
+            Arch::AArch64.relative_branches(&code, code.len())
+        );
+    }
+
+    #[test]
+    fn insert_detour_aarch64_test() {
+        let mut elf = file::ELF64::default();
+        elf.ehdr
+            .set_machine(header::Machine::Any(analysis::EM_AARCH64));
+        elf.ehdr.set_elf_type(header::Type::Dyn);
+
+        // stp x29, x30, [sp, #-16]!; mov x29, sp; adrp x0, 0; ret; 以降はパディング
+        let mut code: Vec<u8> = [0xa9bf_7bfd_u32, 0x9100_03fd, 0x9000_0000, 0xd65f_03c0]
+            .iter()
+            .flat_map(|insn| insn.to_le_bytes().to_vec())
+            .collect();
+        code.resize(0x40, 0);
+        let mut text = section::Section64::new(
+            ".text".to_string(),
+            section::ShdrPreparation64::default()
+                .ty(section::Type::ProgBits)
+                .flags([section::Flag::Alloc, section::Flag::ExecInstr].iter()),
+            section::Contents64::Raw(code),
+        );
+        text.header.sh_addr = 0x1000;
+        elf.add_section(text);
+        let mut f = symbol::Symbol64 {
+            symbol_name: "f".into(),
+            st_shndx: 1,
+            st_value: 0x1000,
+            st_size: 0x10,
+            ..Default::default()
+        };
+        f.set_info(symbol::Type::Func, symbol::Bind::Global);
+        elf.add_section(section::Section64::new(
+            ".symtab".to_string(),
+            section::ShdrPreparation64::default().ty(section::Type::SymTab),
+            section::Contents64::Symbols(vec![symbol::Symbol64::new_null_symbol(), f]),
+        ));
+
+        let detour = insert_detour(&mut elf, 0x1000, 0x1008).unwrap();
+        assert_eq!(0x1010, detour.trampoline);
+        assert_eq!(vec![0xfd, 0x7b, 0xbf, 0xa9], detour.displaced);
+
+        let text = elf.first_section_by(|sct| sct.name == ".text").unwrap();
+        let insns: Vec<u32> = text
+            .to_le_bytes()
+            .chunks_exact(4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .take(7)
+            .collect();
+        assert_eq!(
+            vec![
+                // b 0x1008
+                0x1400_0002,
+                0x9100_03fd,
+                0x9000_0000,
+                0xd65f_03c0,
+                // stp; b 0x1004; brk #0
+                0xa9bf_7bfd,
+                0x17ff_fffc,
+                AARCH64_BRK_0,
+            ],
+            insns
+        );
+
+        // adrp は移動できない
+        assert!(matches!(
+            insert_detour(&mut elf, 0x1008, 0x1000),
+            Err(TransformError::UnsupportedInstruction { vaddr: 0x1008 })
+        ));
+        // 関数の外は書き換えない
+        assert!(matches!(
+            insert_detour(&mut elf, 0x1020, 0x1000),
+            Err(TransformError::NoFunction { vaddr: 0x1020 })
+        ));
+    }
+}
@@ -0,0 +1,48 @@
+//! Trimming the dynamic symbols of linked files.
+
+use super::TransformError;
+use crate::file::glob_match;
+use crate::*;
+
+/// hide every exported dynamic symbol except the ones matching `allowlist`,
+/// and remove them from `.dynsym` with `ELF64::hide_symbols()`.
+///
+/// Each pattern is a glob where `*` matches any string and `?` matches any character.
+/// `.gnu.version`, `.gnu.hash` and `.hash` are rewritten for the remaining symbols.
+/// The symbols referenced by dynamic relocations stay in `.dynsym` as `STV_HIDDEN`,
+/// which the dynamic linker doesn't bind the other files to.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::{abi, builder, transform};
+///
+/// let mut elf = builder::SharedObjectWriter::new()
+///     .function(builder::ExportedFunction::new("api_open", vec![0xc3]))
+///     .function(builder::ExportedFunction::new("api_close", vec![0xc3]))
+///     .function(builder::ExportedFunction::new("vendored_inflate", vec![0xc3]))
+///     .build()
+///     .unwrap();
+///
+/// let changed = transform::retain_exports(&mut elf, &["api_*"]).unwrap();
+/// assert!(changed.removed.contains(&"vendored_inflate".to_string()));
+///
+/// let names: Vec<String> = abi::export_snapshot(&elf).symbols.into_iter().map(|s| s.name).collect();
+/// assert_eq!(vec!["api_close", "api_open"], names);
+/// ```
+pub fn retain_exports(
+    elf: &mut file::ELF64,
+    allowlist: &[&str],
+) -> Result<file::HiddenSymbols, TransformError> {
+    match elf.ehdr.get_type() {
+        header::Type::Exec | header::Type::Dyn => {}
+        _ => return Err(TransformError::NotLinked),
+    }
+    if elf.dynsym().is_none() {
+        return Err(TransformError::NoDynamicSymbols);
+    }
+    Ok(elf.hide_symbols_by(
+        |name| !allowlist.iter().any(|pattern| glob_match(pattern, name)),
+        true,
+    ))
+}
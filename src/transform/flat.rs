@@ -0,0 +1,394 @@
+//! Flat binary images for flash programmers and boot loaders, like `objcopy -O binary`/`-O ihex`.
+//!
+//! The post-processing is pluggable by `PostProcess`, so checksums and headers
+//! which a particular microcontroller requires can be added to the image.
+
+use super::TransformError;
+use crate::layout::align_up;
+use crate::*;
+
+/// A contiguous part of a flat image
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FlatRegion {
+    /// the load address(`p_paddr`) of the first byte
+    pub addr: Elf64Addr,
+    pub bytes: Vec<u8>,
+}
+
+impl FlatRegion {
+    pub fn end(&self) -> Elf64Addr {
+        self.addr + self.bytes.len() as Elf64Addr
+    }
+}
+
+/// The result of `FlatBinary::build()`
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FlatImage {
+    /// the regions ordered by the load address
+    pub regions: Vec<FlatRegion>,
+    /// the byte filling the gaps
+    pub fill: u8,
+}
+
+impl FlatImage {
+    /// the address of the first byte.
+    pub fn base(&self) -> Option<Elf64Addr> {
+        self.regions.first().map(|region| region.addr)
+    }
+
+    /// the whole image from `base()`, the gaps between the regions filled.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let base = match self.base() {
+            Some(base) => base,
+            None => return Vec::new(),
+        };
+        let mut bytes = Vec::new();
+        for region in self.regions.iter() {
+            let start = (region.addr - base) as usize;
+            bytes.resize(start, self.fill);
+            bytes.extend_from_slice(&region.bytes);
+        }
+        bytes
+    }
+
+    /// encode the regions in the Intel HEX format, with the start address record of `entry` if given.
+    ///
+    /// The gaps aren't filled, and the addresses must be below 4GiB.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use elf_utilities::transform::{FlatImage, FlatRegion};
+    ///
+    /// let image = FlatImage {
+    ///     regions: vec![FlatRegion { addr: 0x0800_0000, bytes: vec![0x00, 0x10, 0x00, 0x20] }],
+    ///     fill: 0xff,
+    /// };
+    /// assert_eq!(
+    ///     ":020000040800F2\n:0400000000100020CC\n:00000001FF\n",
+    ///     image.to_ihex(None).unwrap()
+    /// );
+    /// ```
+    pub fn to_ihex(&self, entry: Option<Elf64Addr>) -> Result<String, TransformError> {
+        let mut hex = String::new();
+        let mut upper = 0;
+        for region in self.regions.iter() {
+            if region.end() > 0x1_0000_0000 {
+                return Err(TransformError::OutOfRange { addr: region.addr });
+            }
+            let mut addr = region.addr;
+            let mut rest = &region.bytes[..];
+            while !rest.is_empty() {
+                if addr >> 16 != upper {
+                    upper = addr >> 16;
+                    ihex_record(&mut hex, 0, 0x04, &(upper as u16).to_be_bytes());
+                }
+                // レコードは64KiBの境界を跨げない
+                let len = rest
+                    .len()
+                    .min(16)
+                    .min((0x1_0000 - (addr & 0xffff)) as usize);
+                ihex_record(&mut hex, addr as u16, 0x00, &rest[..len]);
+                addr += len as Elf64Addr;
+                rest = &rest[len..];
+            }
+        }
+        if let Some(entry) = entry {
+            if entry > 0xffff_ffff {
+                return Err(TransformError::OutOfRange { addr: entry });
+            }
+            ihex_record(&mut hex, 0, 0x05, &(entry as u32).to_be_bytes());
+        }
+        ihex_record(&mut hex, 0, 0x01, &[]);
+        Ok(hex)
+    }
+}
+
+fn ihex_record(hex: &mut String, addr: u16, ty: u8, data: &[u8]) {
+    let mut record = vec![data.len() as u8];
+    record.extend_from_slice(&addr.to_be_bytes());
+    record.push(ty);
+    record.extend_from_slice(data);
+    let sum = record.iter().fold(0u8, |sum, b| sum.wrapping_add(*b));
+    record.push(sum.wrapping_neg());
+
+    hex.push(':');
+    for b in record {
+        hex.push_str(&format!("{:02X}", b));
+    }
+    hex.push('\n');
+}
+
+/// A step which modifies the image after the regions are laid out
+///
+/// Closures taking `&mut FlatImage` are also hooks.
+pub trait PostProcess {
+    /// modify the image. the error is a message for `TransformError::PostProcess`.
+    fn process(&self, image: &mut FlatImage) -> Result<(), String>;
+}
+
+impl<F> PostProcess for F
+where
+    F: Fn(&mut FlatImage) -> Result<(), String>,
+{
+    fn process(&self, image: &mut FlatImage) -> Result<(), String> {
+        self(image)
+    }
+}
+
+/// append the CRC-32(IEEE 802.3, as zlib computes) of each region to its end, in little-endian.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct AppendCrc32;
+
+impl PostProcess for AppendCrc32 {
+    fn process(&self, image: &mut FlatImage) -> Result<(), String> {
+        for region in image.regions.iter_mut() {
+            let crc = crc32(&region.bytes);
+            region.bytes.extend_from_slice(&crc.to_le_bytes());
+        }
+        Ok(())
+    }
+}
+
+/// store the checksum of the Cortex-M vector table at the start of the image,
+/// which some boot ROMs(e.g. NXP LPC) verify.
+///
+/// The 8th word is set to make the sum of the first 8 little-endian words zero.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct VectorTableChecksum;
+
+impl PostProcess for VectorTableChecksum {
+    fn process(&self, image: &mut FlatImage) -> Result<(), String> {
+        let table = match image.regions.first_mut() {
+            Some(region) if region.bytes.len() >= 32 => &mut region.bytes[..32],
+            _ => return Err("the image is shorter than the vector table".to_string()),
+        };
+        let sum = table[..28].chunks(4).fold(0u32, |sum, word| {
+            sum.wrapping_add(u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+        });
+        table[28..].copy_from_slice(&sum.wrapping_neg().to_le_bytes());
+        Ok(())
+    }
+}
+
+/// The builder of flat images from the `PT_LOAD` segments
+///
+/// By default all segments are put in a single region and the gaps are filled with zeros,
+/// as `objcopy -O binary` does.
+/// Only the file images(`p_filesz`) are copied, so `.bss` isn't in the image.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::{builder, transform};
+///
+/// let main = builder::ExportedFunction::new("main", vec![0xb8, 0x2a, 0x00, 0x00, 0x00, 0xc3]);
+/// let elf = builder::ExecutableWriter::new()
+///     .function(main)
+///     .start_stub(builder::start_stub("main"))
+///     .build()
+///     .unwrap();
+///
+/// let image = transform::FlatBinary::new()
+///     .fill(0xff)
+///     .align(4)
+///     .hook(transform::AppendCrc32)
+///     .build(&elf)
+///     .unwrap();
+/// assert_eq!(1, image.regions.len());
+/// let bytes = image.to_bytes();
+/// let (body, crc) = bytes.split_at(bytes.len() - 4);
+/// assert_eq!(0, body.len() % 4);
+/// assert_eq!(transform::crc32(body).to_le_bytes(), crc);
+/// ```
+#[derive(Default)]
+pub struct FlatBinary {
+    fill: u8,
+    max_gap: Option<Elf64Xword>,
+    align: Elf64Xword,
+    hooks: Vec<Box<dyn PostProcess>>,
+}
+
+impl FlatBinary {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// the byte filling the gaps between the segments and the alignment padding.
+    pub fn fill(mut self, fill: u8) -> Self {
+        self.fill = fill;
+        self
+    }
+
+    /// start a new region where the gap between segments is larger than `max_gap` bytes,
+    /// e.g. to separate the flash and the RAM images. `0` splits at every gap.
+    pub fn split_gaps(mut self, max_gap: Elf64Xword) -> Self {
+        self.max_gap = Some(max_gap);
+        self
+    }
+
+    /// pad each region to a multiple of `align` bytes(e.g. the flash page size).
+    pub fn align(mut self, align: Elf64Xword) -> Self {
+        self.align = align;
+        self
+    }
+
+    /// add a post-processing step. the steps run in the order they are added.
+    pub fn hook<P: PostProcess + 'static>(mut self, hook: P) -> Self {
+        self.hooks.push(Box::new(hook));
+        self
+    }
+
+    pub fn build(&self, elf: &file::ELF64) -> Result<FlatImage, TransformError> {
+        let mut loads: Vec<&segment::Phdr64> = elf
+            .segments
+            .iter()
+            .map(|sgt| &sgt.header)
+            .filter(|phdr| phdr.get_type() == segment::Type::Load && phdr.p_filesz != 0)
+            .collect();
+        if loads.is_empty() {
+            return Err(TransformError::NoLoadableContents);
+        }
+        loads.sort_by_key(|phdr| phdr.p_paddr);
+
+        let file = elf.to_le_bytes();
+        let mut regions: Vec<FlatRegion> = Vec::new();
+        for phdr in loads {
+            let contents = file
+                .get(phdr.p_offset as usize..(phdr.p_offset + phdr.p_filesz) as usize)
+                .ok_or(TransformError::Unmapped {
+                    vaddr: phdr.p_vaddr,
+                })?;
+            match regions.last_mut() {
+                Some(last) if phdr.p_paddr < last.end() => {
+                    return Err(TransformError::OverlappingSegments {
+                        paddr: phdr.p_paddr,
+                    });
+                }
+                Some(last)
+                    if self
+                        .max_gap
+                        .is_none_or(|max_gap| phdr.p_paddr - last.end() <= max_gap) =>
+                {
+                    let gap = (phdr.p_paddr - last.end()) as usize;
+                    last.bytes.extend(std::iter::repeat_n(self.fill, gap));
+                    last.bytes.extend_from_slice(contents);
+                }
+                _ => regions.push(FlatRegion {
+                    addr: phdr.p_paddr,
+                    bytes: contents.to_vec(),
+                }),
+            }
+        }
+
+        if self.align > 1 {
+            for region in regions.iter_mut() {
+                let len = align_up(region.bytes.len() as Elf64Xword, self.align);
+                region.bytes.resize(len as usize, self.fill);
+            }
+        }
+
+        let mut image = FlatImage {
+            regions,
+            fill: self.fill,
+        };
+        for hook in self.hooks.iter() {
+            hook.process(&mut image)
+                .map_err(|message| TransformError::PostProcess { message })?;
+        }
+        Ok(image)
+    }
+}
+
+/// the CRC-32 used by zlib, PNG and Ethernet(reflected, polynomial `0x04c11db7`).
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes.iter() {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod flat_tests {
+    use super::*;
+
+    fn firmware() -> file::ELF64 {
+        let mut elf = file::ELF64::default();
+        // ベクタテーブルと，隙間を挟んだ2つの領域
+        for (paddr, offset, contents) in [
+            (0x0800_0000, 0x1000, vec![0u8; 32]),
+            (0x0800_0022, 0x1022, vec![0xaa, 0xbb]),
+            (0x0800_1000, 0x2000, vec![1, 2, 3]),
+        ] {
+            let name = format!(".s{:x}", paddr);
+            elf.add_section(section::Section64::new(
+                name.clone(),
+                section::ShdrPreparation64::default()
+                    .ty(section::Type::ProgBits)
+                    .flags([section::Flag::Alloc].iter()),
+                section::Contents64::Raw(contents.clone()),
+            ));
+            // add_section()はオフセットを詰めて置くので，後から配置する
+            let sct = elf.sections.iter_mut().find(|s| s.name == name).unwrap();
+            sct.header.sh_addr = paddr;
+            sct.header.sh_offset = offset;
+            elf.segments.push(segment::Segment64 {
+                header: segment::Phdr64 {
+                    p_type: segment::Type::Load.to_bytes(),
+                    p_offset: offset,
+                    p_vaddr: paddr,
+                    p_paddr: paddr,
+                    p_filesz: contents.len() as Elf64Xword,
+                    p_memsz: contents.len() as Elf64Xword,
+                    ..Default::default()
+                },
+            });
+        }
+        elf
+    }
+
+    #[test]
+    fn split_gaps_test() {
+        let elf = firmware();
+        let image = FlatBinary::new()
+            .fill(0xff)
+            .split_gaps(0x100)
+            .hook(VectorTableChecksum)
+            .build(&elf)
+            .unwrap();
+
+        assert_eq!(2, image.regions.len());
+        let flash = &image.regions[0];
+        assert_eq!(0x0800_0000, flash.addr);
+        assert_eq!(&[0xff, 0xff, 0xaa, 0xbb], &flash.bytes[32..]);
+        assert_eq!(&[1, 2, 3], &image.regions[1].bytes[..]);
+        assert_eq!(
+            0,
+            u32::from_le_bytes([
+                flash.bytes[28],
+                flash.bytes[29],
+                flash.bytes[30],
+                flash.bytes[31]
+            ])
+        );
+
+        // 単一のイメージではRAM側までが埋められる
+        let image = FlatBinary::new().build(&elf).unwrap();
+        let bytes = image.to_bytes();
+        assert_eq!(0x1003, bytes.len());
+        assert_eq!(0, bytes[0x24]);
+    }
+
+    #[test]
+    fn crc32_test() {
+        assert_eq!(0xcbf4_3926, crc32(b"123456789"));
+    }
+}
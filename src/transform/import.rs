@@ -0,0 +1,142 @@
+//! Redirecting imported symbols of linked files.
+
+use super::TransformError;
+use crate::*;
+
+const SHT_GNU_VERSYM: Elf64Word = 0x6fffffff;
+/// `VER_NDX_GLOBAL`: the symbol is unversioned
+const VER_NDX_GLOBAL: u16 = 1;
+
+/// make the dynamic relocations against `from` resolve to `to` at load time.
+///
+/// The relocations(`.rela.plt`, `.rela.dyn`, ...) referring `from` in `.dynsym` are retargeted to `to`.
+/// If `to` isn't in `.dynsym`, it's added as an unversioned import with the same type and binding as `from`.
+/// In that case `.dynsym`, `.dynstr` and `.gnu.version` are moved to a new segment by `move_to_new_segment()`
+/// and `DT_SYMTAB`/`DT_STRTAB`/`DT_STRSZ`/`DT_VERSYM` are updated.
+/// The hash tables are kept, since the new symbol is undefined and never looked up in them.
+/// Returns the number of the retargeted relocations.
+///
+/// Only `SHT_RELA` sections are rewritten.
+/// The prelink information is dropped by `ELF64::invalidate_prelink()`,
+/// since the dynamic linker would skip the relocations of a prelinked file.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::{file, transform};
+///
+/// let mut elf = file::ELF64::default();
+/// assert!(transform::redirect_import(&mut elf, "malloc", "my_malloc").is_err());
+/// ```
+pub fn redirect_import(
+    elf: &mut file::ELF64,
+    from: &str,
+    to: &str,
+) -> Result<usize, TransformError> {
+    let dynsym = elf
+        .first_shidx_by(|sct| sct.header.get_type() == section::Type::DynSym)
+        .ok_or(TransformError::NoDynamicSymbols)?;
+    let syms = match &elf.sections[dynsym].contents {
+        section::Contents64::Symbols(syms) => syms,
+        _ => return Err(TransformError::NoDynamicSymbols),
+    };
+    let from_idx = syms
+        .iter()
+        .position(|sym| sym.symbol_name == from && sym.st_shndx == section::SHN_UNDEF)
+        .ok_or_else(|| TransformError::ImportNotFound {
+            name: from.to_string(),
+        })?;
+
+    let (from_idx, to_idx) = match syms.iter().position(|sym| sym.symbol_name == to) {
+        Some(idx) => (from_idx, idx),
+        None => add_import(elf, dynsym, from_idx, to)?,
+    };
+
+    elf.invalidate_prelink();
+    let mut count = 0;
+    for sct in elf.sections.iter_mut() {
+        if sct.header.sh_link as usize != dynsym {
+            continue;
+        }
+        if let section::Contents64::RelaSymbols(relas) = &mut sct.contents {
+            for rela in relas
+                .iter_mut()
+                .filter(|rela| rela.get_sym() == from_idx as u64)
+            {
+                rela.set_info((to_idx as u64) << 32 | rela.get_type());
+                count += 1;
+            }
+        }
+    }
+    Ok(count)
+}
+
+/// add `name` to `.dynsym` as a copy of the symbol `from_idx`.
+/// returns the indices of `from_idx` and the new symbol, since `.dynsym` may be reordered to keep the locals first.
+fn add_import(
+    elf: &mut file::ELF64,
+    dynsym: usize,
+    from_idx: usize,
+    name: &str,
+) -> Result<(usize, usize), TransformError> {
+    let dynstr = elf.sections[dynsym].header.sh_link as usize;
+    let st_name = match elf.sections.get_mut(dynstr).map(|sct| &mut sct.contents) {
+        Some(section::Contents64::StrTab(strs)) => {
+            let mut tab = section::StringTable::from_parsed(strs.clone());
+            let idx = tab.add(name);
+            *strs = tab.entries().to_vec();
+            idx
+        }
+        _ => return Err(TransformError::NoDynamicSymbols),
+    };
+
+    let mut table = symbol::SymbolTable::from_section(&elf.sections[dynsym])
+        .ok_or(TransformError::NoDynamicSymbols)?;
+    let from_idx = table
+        .new_index(from_idx)
+        .ok_or(TransformError::NoDynamicSymbols)?;
+    let mut sym = table.get(from_idx).unwrap().clone();
+    sym.st_name = st_name as Elf64Word;
+    sym.symbol_name = name.into();
+    let to_idx = table.push(sym);
+    elf.update_symbol_table(dynsym, table);
+
+    // .gnu.versionは.dynsymと同じ数のエントリを持つ
+    let versym = elf.first_shidx_by(|sct| {
+        sct.header.get_type() == section::Type::Any(SHT_GNU_VERSYM)
+            && sct.header.sh_link as usize == dynsym
+    });
+    if let Some(versym) = versym {
+        if let section::Contents64::Raw(bytes) = &mut elf.sections[versym].contents {
+            bytes.extend_from_slice(&VER_NDX_GLOBAL.to_le_bytes());
+        }
+    }
+
+    let mut moved = vec![dynsym, dynstr];
+    moved.extend(versym);
+    super::move_to_new_segment(elf, &moved)?;
+
+    let dynsym_addr = elf.sections[dynsym].header.sh_addr;
+    let dynstr_addr = elf.sections[dynstr].header.sh_addr;
+    let dynstr_size = elf.sections[dynstr].header.sh_size;
+    let versym_addr = versym.map(|i| elf.sections[i].header.sh_addr);
+    for sct in elf.sections.iter_mut() {
+        if let section::Contents64::Dynamics(entries) = &mut sct.contents {
+            for ent in entries.iter_mut() {
+                match ent.get_type() {
+                    dynamic::EntryType::SymTab => ent.d_un = dynsym_addr,
+                    dynamic::EntryType::StrTab => ent.d_un = dynstr_addr,
+                    dynamic::EntryType::StrSz => ent.d_un = dynstr_size,
+                    dynamic::EntryType::VerSym => {
+                        if let Some(addr) = versym_addr {
+                            ent.d_un = addr;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    Ok((from_idx, to_idx))
+}
@@ -0,0 +1,236 @@
+//! Growing linked files by moving sections into a new segment.
+
+use super::TransformError;
+use crate::layout::{align_up, is_alloc, is_nobits};
+use crate::*;
+
+/// move the sections to a new `PT_LOAD` segment placed after the whole file and address space.
+///
+/// This is the way to grow alloc sections(e.g. `.dynstr`) of executables and shared objects,
+/// since the existing segments can't be extended without moving the code.
+/// The sections get new `sh_offset`/`sh_addr`, and the other segments exactly covering a moved section
+/// (e.g. `PT_DYNAMIC` for `.dynamic`) follow it.
+/// The program header table is also moved to the new segment to get a room for the new entry,
+/// and `.shstrtab` and the section header table are placed after it.
+/// Relocations applied to the moved sections and symbols defined in them follow the sections,
+/// but the other references(e.g. `DT_STRTAB`) must be updated by the caller.
+/// The old contents are left as zeros.
+///
+/// New sections can be moved as well after pushing them to `elf.sections`.
+/// Returns the index of the new segment.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::{builder, section, segment, transform};
+///
+/// let main = builder::ExportedFunction::new("main", vec![0xc3]);
+/// let mut elf = builder::ExecutableWriter::new()
+///     .function(main)
+///     .start_stub(builder::start_stub("main"))
+///     .build()
+///     .unwrap();
+///
+/// elf.sections.push(section::Section64::new(
+///     ".added".to_string(),
+///     section::ShdrPreparation64::default()
+///         .ty(section::Type::ProgBits)
+///         .flags([section::Flag::Alloc].iter()),
+///     section::Contents64::Raw(vec![0; 16]),
+/// ));
+/// let added = elf.sections.len() - 1;
+/// let idx = transform::move_to_new_segment(&mut elf, &[added]).unwrap();
+///
+/// let phdr = elf.segments[idx].header;
+/// assert_eq!(segment::Type::Load, phdr.get_type());
+/// assert_eq!(elf.ehdr.e_phoff, phdr.p_offset);
+/// assert!(phdr.p_vaddr < elf.sections[added].header.sh_addr);
+/// ```
+pub fn move_to_new_segment(
+    elf: &mut file::ELF64,
+    shidxs: &[usize],
+) -> Result<usize, TransformError> {
+    elf.invalidate_address_index();
+    let first_load = elf
+        .segments
+        .iter()
+        .filter(|sgt| sgt.header.get_type() == segment::Type::Load)
+        .min_by_key(|sgt| sgt.header.p_vaddr)
+        .ok_or(TransformError::NotLinked)?
+        .header;
+    // 先頭のPT_LOADと同じ差分で配置すれば，古いカーネルが e_phoff から求める AT_PHDR も正しくなる
+    let bias = first_load.p_vaddr.wrapping_sub(first_load.p_offset);
+    let page = elf.max_page_size().unwrap_or(1).max(1);
+
+    let mut file_end = elf.ehdr.e_phoff + (elf.segments.len() * segment::Phdr64::SIZE) as u64;
+    if elf.ehdr.e_shoff != 0 {
+        file_end =
+            file_end.max(elf.ehdr.e_shoff + (elf.sections.len() * section::Shdr64::SIZE) as u64);
+    }
+    let mut mem_end = 0;
+    for sgt in elf.segments.iter() {
+        let phdr = &sgt.header;
+        file_end = file_end.max(phdr.p_offset + phdr.p_filesz);
+        if phdr.get_type() == segment::Type::Load {
+            mem_end = mem_end.max(phdr.p_vaddr + phdr.p_memsz);
+        }
+    }
+    for sct in elf.sections.iter().filter(|s| !is_nobits(&s.header)) {
+        file_end = file_end.max(sct.header.sh_offset + sct.header.sh_size);
+    }
+
+    let start = align_up(file_end.max(mem_end.wrapping_sub(bias)), page);
+    let vaddr = bias.wrapping_add(start);
+    let phnum = elf.segments.len() + 1;
+
+    // NOBITSは末尾にまとめる
+    let mut order: Vec<usize> = shidxs
+        .iter()
+        .copied()
+        .filter(|&i| !is_nobits(&elf.sections[i].header))
+        .collect();
+    let file_sections = order.len();
+    order.extend(
+        shidxs
+            .iter()
+            .copied()
+            .filter(|&i| is_nobits(&elf.sections[i].header)),
+    );
+
+    // (index, old sh_addr, old sh_size) of the sections which were already loaded
+    let mut moved = Vec::new();
+    let mut cur = start + (phnum * segment::Phdr64::SIZE) as u64;
+    let mut file_size = cur - start;
+    let write: Elf64Xword = section::Flag::Write.into();
+    let exec: Elf64Xword = section::Flag::ExecInstr.into();
+    let mut flags: Elf64Word = segment::Flag::R.into();
+    for (n, &i) in order.iter().enumerate() {
+        if n == file_sections {
+            file_size = cur - start;
+        }
+        let (old_offset, old_size) = {
+            let sct = &mut elf.sections[i];
+            let old = (sct.header.sh_offset, sct.header.sh_size);
+            if is_alloc(&sct.header) {
+                moved.push((i, sct.header.sh_addr, sct.header.sh_size));
+            }
+            if !is_nobits(&sct.header) {
+                sct.header.sh_size = sct.contents.size() as u64;
+            }
+            cur = align_up(cur, sct.header.sh_addralign.max(1));
+            sct.header.sh_offset = cur;
+            sct.header.sh_addr = vaddr + (cur - start);
+            sct.header.sh_flags |= Elf64Xword::from(section::Flag::Alloc);
+            if sct.header.sh_flags & write != 0 {
+                flags |= Elf64Word::from(segment::Flag::W);
+            }
+            if sct.header.sh_flags & exec != 0 {
+                flags |= Elf64Word::from(segment::Flag::X);
+            }
+            cur += sct.header.sh_size;
+            old
+        };
+
+        let new_offset = elf.sections[i].header.sh_offset;
+        let new_addr = elf.sections[i].header.sh_addr;
+        let new_size = elf.sections[i].header.sh_size;
+        for sgt in elf.segments.iter_mut() {
+            let phdr = &mut sgt.header;
+            let ty = phdr.get_type();
+            if ty == segment::Type::Load || ty == segment::Type::Phdr {
+                continue;
+            }
+            if old_size != 0 && phdr.p_offset == old_offset && phdr.p_filesz == old_size {
+                phdr.p_offset = new_offset;
+                phdr.p_vaddr = new_addr;
+                phdr.p_paddr = new_addr;
+                phdr.p_filesz = new_size;
+                phdr.p_memsz = new_size;
+            }
+        }
+    }
+    if file_sections == order.len() {
+        file_size = cur - start;
+    }
+    let mem_size = cur - start;
+
+    let phdr = segment::Phdr64 {
+        p_type: segment::Type::Load.to_bytes(),
+        p_flags: flags,
+        p_offset: start,
+        p_vaddr: vaddr,
+        p_paddr: vaddr,
+        p_filesz: file_size,
+        p_memsz: mem_size,
+        p_align: page,
+    };
+    // PT_LOADは仮想アドレスの昇順に並べる
+    let idx = elf
+        .segments
+        .iter()
+        .rposition(|sgt| sgt.header.get_type() == segment::Type::Load)
+        .map_or(elf.segments.len(), |i| i + 1);
+    elf.segments
+        .insert(idx, segment::Segment64 { header: phdr });
+
+    let pht_size = (phnum * segment::Phdr64::SIZE) as u64;
+    for sgt in elf.segments.iter_mut() {
+        if sgt.header.get_type() == segment::Type::Phdr {
+            sgt.header.p_offset = start;
+            sgt.header.p_vaddr = vaddr;
+            sgt.header.p_paddr = vaddr;
+            sgt.header.p_filesz = pht_size;
+            sgt.header.p_memsz = pht_size;
+        }
+    }
+    elf.ehdr.e_phoff = start;
+    elf.ehdr.e_phnum = phnum as Elf64Half;
+
+    for (i, old_addr, old_size) in moved {
+        let new_addr = elf.sections[i].header.sh_addr;
+        shift_references(elf, i, old_addr, old_size, new_addr);
+    }
+
+    // .shstrtabとSHTは新しいセグメントの後ろに置く
+    let mut end = start + file_size;
+    let shstrndx = elf.ehdr.e_shstrndx as usize;
+    if shstrndx != 0 && shstrndx < elf.sections.len() {
+        elf.rebuild_shstrtab();
+        let shstrtab = &mut elf.sections[shstrndx];
+        shstrtab.header.sh_offset = end;
+        shstrtab.header.sh_size = shstrtab.contents.size() as u64;
+        end += shstrtab.header.sh_size;
+    }
+    if elf.ehdr.e_shoff != 0 {
+        elf.ehdr.e_shoff = align_up(end, 8);
+        elf.ehdr.e_shnum = elf.sections.len() as Elf64Half;
+    }
+
+    Ok(idx)
+}
+
+/// move the relocations and the symbols in the section from `old_addr` to `new_addr`.
+fn shift_references(
+    elf: &mut file::ELF64,
+    shidx: usize,
+    old_addr: Elf64Addr,
+    old_size: Elf64Xword,
+    new_addr: Elf64Addr,
+) {
+    let in_section = |addr: Elf64Addr| old_addr <= addr && addr < old_addr + old_size;
+    for sct in elf.sections.iter_mut() {
+        match &mut sct.contents {
+            section::Contents64::RelaSymbols(relas) => {
+                for rela in relas.iter_mut().filter(|r| in_section(r.get_offset())) {
+                    rela.set_offset(rela.get_offset() - old_addr + new_addr);
+                }
+            }
+            section::Contents64::Symbols(syms) => {
+                for sym in syms.iter_mut().filter(|s| s.st_shndx as usize == shidx) {
+                    sym.st_value = sym.st_value.wrapping_sub(old_addr).wrapping_add(new_addr);
+                }
+            }
+            _ => {}
+        }
+    }
+}
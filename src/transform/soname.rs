@@ -0,0 +1,228 @@
+//! Editing `DT_SONAME` and the defined versions of shared objects.
+
+use super::TransformError;
+use crate::file::sysv_hash;
+use crate::section::{SHT_GNU_VERDEF, SHT_GNU_VERSYM, VER_FLG_BASE};
+use crate::*;
+
+const VERDEF_SIZE: usize = 20;
+const VERDAUX_SIZE: usize = 8;
+
+impl file::ELF64 {
+    /// set `DT_SONAME`, adding the entry if absent.
+    ///
+    /// The base version in `.gnu.version_d`, which is named after the SONAME, is renamed too.
+    /// `.dynstr` grows for the new name, so it's moved with `.dynamic` to a new segment if needed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use elf_utilities::builder;
+    ///
+    /// let mut elf = builder::SharedObjectWriter::new()
+    ///     .function(builder::ExportedFunction::new("f", vec![0xc3]))
+    ///     .build()
+    ///     .unwrap();
+    /// assert_eq!(None, elf.soname());
+    ///
+    /// elf.set_soname("libfoo.so.2").unwrap();
+    /// assert_eq!(Some("libfoo.so.2".to_string()), elf.soname());
+    /// ```
+    pub fn set_soname(&mut self, name: &str) -> Result<(), TransformError> {
+        super::edit_dynamic(self, |entries, tab| {
+            let offset = tab.add(name) as Elf64Xword;
+            super::set_dynamic(entries, dynamic::EntryType::SOName, Some(offset));
+        })?;
+
+        let offset = match self
+            .dynamic_string_table()
+            .and_then(|tab| tab.offset_of(name))
+        {
+            Some(offset) => offset as u32,
+            None => return Ok(()),
+        };
+        let verdef = match self
+            .first_shidx_by(|sct| sct.header.get_type() == section::Type::Any(SHT_GNU_VERDEF))
+        {
+            Some(idx) => idx,
+            None => return Ok(()),
+        };
+        let count = self.sections[verdef].header.sh_info;
+        if let section::Contents64::Raw(bytes) = &mut self.sections[verdef].contents {
+            for vd in verdef_offsets(bytes, count) {
+                if read_u16(bytes, vd + 2) & VER_FLG_BASE == 0 {
+                    continue;
+                }
+                // Elf64_Verdef: vd_version, vd_flags, vd_ndx, vd_cnt, vd_hash, vd_aux, vd_next
+                let vda = vd + read_u32(bytes, vd + 12) as usize;
+                if vda + 4 > bytes.len() {
+                    break;
+                }
+                bytes[vd + 8..vd + 12].copy_from_slice(&sysv_hash(name).to_le_bytes());
+                bytes[vda..vda + 4].copy_from_slice(&offset.to_le_bytes());
+            }
+        }
+        Ok(())
+    }
+
+    /// define the version `version` inheriting the latest defined version,
+    /// and move the symbols of the latest version to it as their default(`sym@@version`).
+    ///
+    /// The symbols kept in the old version as non-default(`sym@OLD`) aren't moved.
+    /// Files linked against the old version of the moved symbols must be relinked,
+    /// while files referring them without versions keep working.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use elf_utilities::{builder, transform};
+    ///
+    /// let mut elf = builder::SharedObjectWriter::new()
+    ///     .function(builder::ExportedFunction::new("f", vec![0xc3]))
+    ///     .build()
+    ///     .unwrap();
+    /// let script = transform::VersionScript::parse("LIBFOO_1 { global: f; local: *; };").unwrap();
+    /// transform::apply_version_script(&mut elf, &script).unwrap();
+    ///
+    /// elf.bump_default_version("LIBFOO_2").unwrap();
+    /// let defs = elf.version_definitions().unwrap();
+    /// let latest = defs.last().unwrap();
+    /// assert_eq!("LIBFOO_2", latest.name);
+    /// assert_eq!(vec!["LIBFOO_1".to_string()], latest.parents);
+    /// ```
+    pub fn bump_default_version(&mut self, version: &str) -> Result<(), TransformError> {
+        let defs = self
+            .version_definitions()
+            .map_err(TransformError::InvalidVersions)?;
+        if defs.iter().any(|def| def.name == version) {
+            return Err(TransformError::VersionExists {
+                name: version.to_string(),
+            });
+        }
+        let latest = defs
+            .iter()
+            .filter(|def| !def.is_base())
+            .max_by_key(|def| def.index)
+            .cloned()
+            .ok_or(TransformError::Unversioned)?;
+        let verdef = self
+            .first_shidx_by(|sct| sct.header.get_type() == section::Type::Any(SHT_GNU_VERDEF))
+            .ok_or(TransformError::Unversioned)?;
+
+        // 定義と要求は同じ番号の空間を使うので，どちらとも重ならない番号にする
+        let needed = self
+            .version_needs()
+            .map_err(TransformError::InvalidVersions)?;
+        let index = defs
+            .iter()
+            .map(|def| def.index)
+            .chain(
+                needed
+                    .iter()
+                    .flat_map(|need| need.versions.iter().map(|v| v.other)),
+            )
+            .max()
+            .unwrap_or(0)
+            + 1;
+
+        let count = self.sections[verdef].header.sh_info + 1;
+        super::edit_dynamic(self, |entries, tab| {
+            tab.add(version);
+            tab.add(&latest.name);
+            super::set_dynamic(
+                entries,
+                dynamic::EntryType::VerDefNum,
+                Some(count as Elf64Xword),
+            );
+        })?;
+        let tab = self
+            .dynamic_string_table()
+            .ok_or(TransformError::NoDynamicSymbols)?;
+        let name = tab.offset_of(version).unwrap_or(0) as u32;
+        let parent = tab.offset_of(&latest.name).unwrap_or(0) as u32;
+
+        let bytes = match &mut self.sections[verdef].contents {
+            section::Contents64::Raw(bytes) => bytes,
+            _ => return Err(TransformError::Unversioned),
+        };
+        let last = verdef_offsets(bytes, count - 1)
+            .last()
+            .copied()
+            .ok_or(TransformError::Unversioned)?;
+        let added = bytes.len();
+        bytes[last + 16..last + 20].copy_from_slice(&((added - last) as u32).to_le_bytes());
+        bytes.extend_from_slice(&1u16.to_le_bytes());
+        bytes.extend_from_slice(&0u16.to_le_bytes());
+        bytes.extend_from_slice(&index.to_le_bytes());
+        bytes.extend_from_slice(&2u16.to_le_bytes());
+        bytes.extend_from_slice(&sysv_hash(version).to_le_bytes());
+        bytes.extend_from_slice(&(VERDEF_SIZE as u32).to_le_bytes());
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+        // Elf64_Verdaux: vda_name, vda_next．二つ目は親のバージョン
+        bytes.extend_from_slice(&name.to_le_bytes());
+        bytes.extend_from_slice(&(VERDAUX_SIZE as u32).to_le_bytes());
+        bytes.extend_from_slice(&parent.to_le_bytes());
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+        self.sections[verdef].header.sh_info = count;
+
+        if let Some(versym) =
+            self.first_shidx_by(|sct| sct.header.get_type() == section::Type::Any(SHT_GNU_VERSYM))
+        {
+            if let section::Contents64::Raw(bytes) = &mut self.sections[versym].contents {
+                for entry in bytes.chunks_exact_mut(2) {
+                    if u16::from_le_bytes([entry[0], entry[1]]) == latest.index {
+                        entry.copy_from_slice(&index.to_le_bytes());
+                    }
+                }
+            }
+        }
+
+        // .gnu.version_dが大きくなったので，新しいセグメントに移す
+        super::move_to_new_segment(self, &[verdef])?;
+        let addr = self.sections[verdef].header.sh_addr;
+        let dynamic = self
+            .first_shidx_by(|sct| sct.header.get_type() == section::Type::Dynamic)
+            .ok_or(TransformError::NotLinked)?;
+        if let section::Contents64::Dynamics(entries) = &mut self.sections[dynamic].contents {
+            super::set_dynamic(entries, dynamic::EntryType::VerDef, Some(addr));
+        }
+        Ok(())
+    }
+
+    /// `.dynstr`, the string table linked from `.dynamic`.
+    fn dynamic_string_table(&self) -> Option<section::StringTable> {
+        let dynamic =
+            self.first_section_by(|sct| sct.header.get_type() == section::Type::Dynamic)?;
+        match &self.sections.get(dynamic.header.sh_link as usize)?.contents {
+            section::Contents64::StrTab(strs) => {
+                Some(section::StringTable::from_parsed(strs.clone()))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// the offsets of the first `count` entries in `.gnu.version_d`.
+fn verdef_offsets(bytes: &[u8], count: Elf64Word) -> Vec<usize> {
+    let mut offsets = Vec::new();
+    let mut vd = 0;
+    for _ in 0..count {
+        if vd + VERDEF_SIZE > bytes.len() {
+            break;
+        }
+        offsets.push(vd);
+        match read_u32(bytes, vd + 16) {
+            0 => break,
+            next => vd += next as usize,
+        }
+    }
+    offsets
+}
+
+fn read_u16(bytes: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([bytes[at], bytes[at + 1]])
+}
+
+fn read_u32(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+}
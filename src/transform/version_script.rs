@@ -0,0 +1,633 @@
+//! Applying GNU version scripts to linked files.
+
+use super::TransformError;
+use crate::file::{glob_match, sysv_hash};
+use crate::*;
+
+const SHT_GNU_VERDEF: Elf64Word = 0x6ffffffd;
+const SHT_GNU_VERNEED: Elf64Word = 0x6ffffffe;
+const SHT_GNU_VERSYM: Elf64Word = 0x6fffffff;
+/// `VER_NDX_LOCAL`: the symbol is local
+const VER_NDX_LOCAL: u16 = 0;
+/// `VER_NDX_GLOBAL`: the symbol is unversioned
+const VER_NDX_GLOBAL: u16 = 1;
+/// `VER_FLG_BASE`: the version of the file itself
+const VER_FLG_BASE: u16 = 1;
+const VERDEF_SIZE: u32 = 20;
+const VERDAUX_SIZE: u32 = 8;
+
+/// A version node in a version script
+///
+/// `name` is `None` for the anonymous node(`{ global: ...; local: *; };`),
+/// which controls only the visibility.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct VersionNode {
+    pub name: Option<String>,
+    /// the patterns of the exported symbols
+    pub global: Vec<String>,
+    /// the patterns of the hidden symbols
+    pub local: Vec<String>,
+    /// the versions which this version inherits
+    pub parents: Vec<String>,
+}
+
+/// A GNU version script
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::transform;
+///
+/// let script = transform::VersionScript::parse(
+///     "LIBFOO_1.0 { global: foo; foo_*; local: *; };
+///      LIBFOO_2.0 { global: bar; } LIBFOO_1.0;",
+/// )
+/// .unwrap();
+/// assert_eq!(2, script.nodes.len());
+/// assert_eq!(vec!["foo", "foo_*"], script.nodes[0].global);
+/// assert_eq!(vec!["LIBFOO_1.0"], script.nodes[1].parents);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct VersionScript {
+    pub nodes: Vec<VersionNode>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Word(String),
+    Str(String),
+    Punct(char),
+}
+
+impl VersionScript {
+    /// parse a version script.
+    ///
+    /// `extern "C++"` blocks aren't supported.
+    pub fn parse(src: &str) -> Result<Self, TransformError> {
+        let tokens = tokenize(src)?;
+        let mut parser = ScriptParser { tokens, pos: 0 };
+        let mut nodes: Vec<VersionNode> = Vec::new();
+        while parser.peek().is_some() {
+            let line = parser.line();
+            let node = parser.node()?;
+            let anonymous = node.name.is_none() || nodes.iter().any(|n| n.name.is_none());
+            if anonymous && !nodes.is_empty() {
+                return Err(invalid(
+                    line,
+                    "an anonymous version must be the only version",
+                ));
+            }
+            if nodes
+                .iter()
+                .any(|n| n.name.is_some() && n.name == node.name)
+            {
+                return Err(invalid(line, "duplicate version"));
+            }
+            for parent in node.parents.iter() {
+                if !nodes.iter().any(|n| n.name.as_ref() == Some(parent)) {
+                    return Err(invalid(line, &format!("unknown version `{}`", parent)));
+                }
+            }
+            nodes.push(node);
+        }
+        Ok(Self { nodes })
+    }
+
+    /// the version node index and whether the symbol is local.
+    ///
+    /// Like `ld`, exact names are preferred to wildcards, and `*` is the last resort.
+    fn lookup(&self, name: &str) -> Option<(usize, bool)> {
+        let priority = |pattern: &str| {
+            if pattern == "*" {
+                2
+            } else if pattern.contains(['*', '?']) {
+                1
+            } else {
+                0
+            }
+        };
+        (0..3).find_map(|p| {
+            self.nodes.iter().enumerate().find_map(|(i, node)| {
+                let matches = |patterns: &[String]| {
+                    patterns
+                        .iter()
+                        .any(|pat| priority(pat) == p && glob_match(pat, name))
+                };
+                if matches(&node.global) {
+                    Some((i, false))
+                } else if matches(&node.local) {
+                    Some((i, true))
+                } else {
+                    None
+                }
+            })
+        })
+    }
+}
+
+fn invalid(line: usize, message: &str) -> TransformError {
+    TransformError::InvalidVersionScript {
+        line,
+        message: message.to_string(),
+    }
+}
+
+fn tokenize(src: &str) -> Result<Vec<(Token, usize)>, TransformError> {
+    let mut tokens = Vec::new();
+    let mut chars = src.chars().peekable();
+    let mut line = 1;
+    while let Some(c) = chars.next() {
+        match c {
+            '\n' => line += 1,
+            c if c.is_whitespace() => {}
+            '#' => {
+                while chars.peek().is_some_and(|c| *c != '\n') {
+                    chars.next();
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut prev = ' ';
+                loop {
+                    match chars.next() {
+                        Some('/') if prev == '*' => break,
+                        Some(c) => {
+                            if c == '\n' {
+                                line += 1;
+                            }
+                            prev = c;
+                        }
+                        None => return Err(invalid(line, "unterminated comment")),
+                    }
+                }
+            }
+            '{' | '}' | ';' | ':' => tokens.push((Token::Punct(c), line)),
+            '"' => {
+                let mut s = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\n') | None => return Err(invalid(line, "unterminated string")),
+                        Some(c) => s.push(c),
+                    }
+                }
+                tokens.push((Token::Str(s), line));
+            }
+            _ => {
+                let mut s = c.to_string();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || "{};:\"#".contains(c) {
+                        break;
+                    }
+                    s.push(c);
+                    chars.next();
+                }
+                tokens.push((Token::Word(s), line));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+struct ScriptParser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+}
+
+impl ScriptParser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(t, _)| t)
+    }
+
+    fn line(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .or_else(|| self.tokens.last())
+            .map_or(1, |(_, line)| *line)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let t = self.peek().cloned();
+        self.pos += 1;
+        t
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), TransformError> {
+        let line = self.line();
+        match self.next() {
+            Some(Token::Punct(p)) if p == c => Ok(()),
+            _ => Err(invalid(line, &format!("expected `{}`", c))),
+        }
+    }
+
+    fn node(&mut self) -> Result<VersionNode, TransformError> {
+        let mut node = VersionNode::default();
+        if let Some(Token::Word(name)) = self.peek().cloned() {
+            node.name = Some(name);
+            self.pos += 1;
+        }
+        self.expect('{')?;
+
+        let mut local = false;
+        loop {
+            let line = self.line();
+            let pattern = match self.next() {
+                Some(Token::Punct('}')) => break,
+                Some(Token::Word(w)) if w == "extern" => {
+                    return Err(invalid(line, "`extern` is not supported"));
+                }
+                Some(Token::Word(w))
+                    if (w == "global" || w == "local")
+                        && self.peek() == Some(&Token::Punct(':')) =>
+                {
+                    self.pos += 1;
+                    local = w == "local";
+                    continue;
+                }
+                Some(Token::Word(w)) | Some(Token::Str(w)) => w,
+                _ => return Err(invalid(line, "expected a symbol pattern")),
+            };
+            self.expect(';')?;
+            if local {
+                node.local.push(pattern);
+            } else {
+                node.global.push(pattern);
+            }
+        }
+
+        while let Some(Token::Word(parent)) = self.peek().cloned() {
+            node.parents.push(parent);
+            self.pos += 1;
+        }
+        self.expect(';')?;
+        Ok(node)
+    }
+}
+
+/// apply the version script to a shared object, like linking it with `--version-script`.
+///
+/// The dynamic symbols matching `local` patterns become `STV_HIDDEN`(in `.symtab` too),
+/// and the ones matching `global` patterns of a named version get the version as the default(`foo@@VER`).
+/// `.gnu.version_d` is created with the base version named by `DT_SONAME`,
+/// and `.gnu.version` is created or updated.
+/// Indices of the needed versions in `.gnu.version_r` are shifted after the new versions.
+/// `.gnu.version_d`, `.dynstr`, `.dynamic`(and the new `.gnu.version`)
+/// are moved to a new segment by `move_to_new_segment()`, and `DT_*` entries are updated.
+///
+/// The file must not define versions already.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::{builder, section, transform};
+///
+/// let mut elf = builder::SharedObjectWriter::new()
+///     .soname("libfoo.so.1")
+///     .function(builder::ExportedFunction::new("foo", vec![0xc3]))
+///     .function(builder::ExportedFunction::new("foo_internal", vec![0xc3]))
+///     .build()
+///     .unwrap();
+/// let script = transform::VersionScript::parse(
+///     "LIBFOO_1.0 { global: foo; local: *; };",
+/// )
+/// .unwrap();
+///
+/// transform::apply_version_script(&mut elf, &script).unwrap();
+/// let verdef = elf.first_section_by(|sct| sct.name == ".gnu.version_d").unwrap();
+/// // the base version and LIBFOO_1.0
+/// assert_eq!(2, verdef.header.sh_info);
+/// ```
+pub fn apply_version_script(
+    elf: &mut file::ELF64,
+    script: &VersionScript,
+) -> Result<(), TransformError> {
+    let dynamic = elf
+        .first_shidx_by(|sct| sct.header.get_type() == section::Type::Dynamic)
+        .ok_or(TransformError::NotLinked)?;
+    let dynsym = elf
+        .first_shidx_by(|sct| sct.header.get_type() == section::Type::DynSym)
+        .ok_or(TransformError::NoDynamicSymbols)?;
+    let dynstr = elf.sections[dynsym].header.sh_link as usize;
+    if !matches!(
+        elf.sections.get(dynstr).map(|s| &s.contents),
+        Some(section::Contents64::StrTab(_))
+    ) {
+        return Err(TransformError::NoDynamicSymbols);
+    }
+    if elf
+        .first_shidx_by(|sct| sct.header.get_type() == section::Type::Any(SHT_GNU_VERDEF))
+        .is_some()
+    {
+        return Err(TransformError::AlreadyVersioned);
+    }
+
+    // バージョン1は基底バージョンなので，ノードは2から番号を振る
+    let named: Vec<&VersionNode> = script.nodes.iter().filter(|n| n.name.is_some()).collect();
+    let version_index = |node: usize| {
+        script.nodes[node]
+            .name
+            .as_ref()
+            .and_then(|name| named.iter().position(|n| n.name.as_ref() == Some(name)))
+            .map_or(VER_NDX_GLOBAL, |i| i as u16 + 2)
+    };
+    let shift = named.len() as u16;
+
+    let (versions, hidden) = match &mut elf.sections[dynsym].contents {
+        section::Contents64::Symbols(syms) => {
+            let mut versions = Vec::with_capacity(syms.len());
+            let mut hidden = Vec::new();
+            for (i, sym) in syms.iter_mut().enumerate() {
+                let exported = i != 0
+                    && sym.st_shndx != section::SHN_UNDEF
+                    && matches!(sym.get_bind(), symbol::Bind::Global | symbol::Bind::Weak);
+                let found = if exported {
+                    script.lookup(&sym.symbol_name)
+                } else {
+                    None
+                };
+                versions.push(match found {
+                    Some((_, true)) => {
+                        sym.set_visibility(symbol::Visibility::Hidden);
+                        hidden.push(sym.symbol_name.clone());
+                        Some(VER_NDX_LOCAL)
+                    }
+                    Some((node, false)) => Some(version_index(node)),
+                    None if exported => Some(VER_NDX_GLOBAL),
+                    None => None,
+                });
+            }
+            (versions, hidden)
+        }
+        _ => return Err(TransformError::NoDynamicSymbols),
+    };
+    for sct in elf.sections.iter_mut() {
+        if sct.header.get_type() != section::Type::SymTab {
+            continue;
+        }
+        if let section::Contents64::Symbols(syms) = &mut sct.contents {
+            for sym in syms
+                .iter_mut()
+                .filter(|sym| sym.st_shndx != section::SHN_UNDEF)
+                .filter(|sym| hidden.contains(&sym.symbol_name))
+            {
+                sym.set_visibility(symbol::Visibility::Hidden);
+            }
+        }
+    }
+
+    shift_needed_versions(elf, shift);
+
+    // 定義されていないシンボルは既存の.gnu.versionのまま
+    let versym = elf.first_shidx_by(|sct| {
+        sct.header.get_type() == section::Type::Any(SHT_GNU_VERSYM)
+            && sct.header.sh_link as usize == dynsym
+    });
+    let mut entries: Vec<u16> = match versym.map(|i| &elf.sections[i].contents) {
+        Some(section::Contents64::Raw(bytes)) => bytes
+            .chunks_exact(2)
+            .map(|b| u16::from_le_bytes([b[0], b[1]]))
+            .map(|v| match v & 0x7fff {
+                VER_NDX_LOCAL | VER_NDX_GLOBAL => v,
+                _ => v + shift,
+            })
+            .collect(),
+        _ => (0..versions.len())
+            .map(|i| {
+                if i == 0 {
+                    VER_NDX_LOCAL
+                } else {
+                    VER_NDX_GLOBAL
+                }
+            })
+            .collect(),
+    };
+    entries.resize(versions.len(), VER_NDX_GLOBAL);
+    for (entry, version) in entries.iter_mut().zip(versions.iter()) {
+        if let Some(version) = version {
+            *entry = *version;
+        }
+    }
+    let versym_bytes: Vec<u8> = entries.iter().flat_map(|v| v.to_le_bytes()).collect();
+
+    let verdef_bytes = build_verdef(elf, dynamic, dynstr, &named);
+    let verdef_num = named.len() as Elf64Word + 1;
+    let mut hdr = section::ShdrPreparation64::default()
+        .ty(section::Type::Any(SHT_GNU_VERDEF))
+        .flags([section::Flag::Alloc].iter())
+        .link(dynstr as Elf64Word)
+        .info(verdef_num);
+    hdr.sh_addralign = 8;
+    elf.sections.push(section::Section64::new(
+        ".gnu.version_d".to_string(),
+        hdr,
+        section::Contents64::Raw(verdef_bytes),
+    ));
+    let verdef = elf.sections.len() - 1;
+
+    let mut moved = vec![verdef, dynstr, dynamic];
+    let versym = match versym {
+        Some(idx) => {
+            elf.sections[idx].contents = section::Contents64::Raw(versym_bytes);
+            idx
+        }
+        None => {
+            let mut hdr = section::ShdrPreparation64::default()
+                .ty(section::Type::Any(SHT_GNU_VERSYM))
+                .flags([section::Flag::Alloc].iter())
+                .link(dynsym as Elf64Word);
+            hdr.sh_addralign = 2;
+            let mut sct = section::Section64::new(
+                ".gnu.version".to_string(),
+                hdr,
+                section::Contents64::Raw(versym_bytes),
+            );
+            sct.header.sh_entsize = 2;
+            elf.sections.push(sct);
+            moved.push(elf.sections.len() - 1);
+            elf.sections.len() - 1
+        }
+    };
+
+    // 移動後に.dynamicが大きくならないよう，先にエントリを用意しておく
+    let types = [
+        dynamic::EntryType::VerDef,
+        dynamic::EntryType::VerDefNum,
+        dynamic::EntryType::VerSym,
+        dynamic::EntryType::StrTab,
+        dynamic::EntryType::StrSz,
+    ];
+    if let section::Contents64::Dynamics(entries) = &mut elf.sections[dynamic].contents {
+        for ty in types.iter() {
+            super::set_dynamic(entries, *ty, None);
+        }
+    }
+    super::move_to_new_segment(elf, &moved)?;
+
+    let values = [
+        elf.sections[verdef].header.sh_addr,
+        verdef_num as Elf64Xword,
+        elf.sections[versym].header.sh_addr,
+        elf.sections[dynstr].header.sh_addr,
+        elf.sections[dynstr].header.sh_size,
+    ];
+    if let section::Contents64::Dynamics(entries) = &mut elf.sections[dynamic].contents {
+        for (ty, value) in types.iter().zip(values.iter()) {
+            super::set_dynamic(entries, *ty, Some(*value));
+        }
+    }
+    Ok(())
+}
+
+/// build `.gnu.version_d`, adding the version names to `.dynstr`.
+fn build_verdef(
+    elf: &mut file::ELF64,
+    dynamic: usize,
+    dynstr: usize,
+    named: &[&VersionNode],
+) -> Vec<u8> {
+    let soname = match &elf.sections[dynamic].contents {
+        section::Contents64::Dynamics(entries) => entries
+            .iter()
+            .find(|ent| ent.get_type() == dynamic::EntryType::SOName)
+            .map(|ent| ent.d_un as usize),
+        _ => None,
+    };
+    let strs = match &mut elf.sections[dynstr].contents {
+        section::Contents64::StrTab(strs) => strs,
+        _ => unreachable!(),
+    };
+    let mut tab = section::StringTable::from_parsed(strs.clone());
+    let base_name = soname
+        .and_then(|offset| tab.get(offset))
+        .map_or_else(String::new, str::to_string);
+
+    // (フラグ, 自身と親の名前) をバージョンの順に並べる
+    let mut defs = vec![(VER_FLG_BASE, vec![base_name])];
+    for node in named.iter() {
+        let mut names = vec![node.name.clone().unwrap_or_default()];
+        names.extend(node.parents.iter().cloned());
+        defs.push((0, names));
+    }
+
+    let mut bytes = Vec::new();
+    for (i, (flags, names)) in defs.iter().enumerate() {
+        let cnt = names.len() as u32;
+        let next = if i + 1 == defs.len() {
+            0
+        } else {
+            VERDEF_SIZE + VERDAUX_SIZE * cnt
+        };
+        bytes.extend_from_slice(&1u16.to_le_bytes());
+        bytes.extend_from_slice(&flags.to_le_bytes());
+        bytes.extend_from_slice(&(i as u16 + 1).to_le_bytes());
+        bytes.extend_from_slice(&(cnt as u16).to_le_bytes());
+        bytes.extend_from_slice(&sysv_hash(&names[0]).to_le_bytes());
+        bytes.extend_from_slice(&VERDEF_SIZE.to_le_bytes());
+        bytes.extend_from_slice(&next.to_le_bytes());
+        for (j, name) in names.iter().enumerate() {
+            let offset = if name.is_empty() { 0 } else { tab.add(name) };
+            let next = if j + 1 == names.len() {
+                0
+            } else {
+                VERDAUX_SIZE
+            };
+            bytes.extend_from_slice(&(offset as u32).to_le_bytes());
+            bytes.extend_from_slice(&next.to_le_bytes());
+        }
+    }
+    *strs = tab.entries().to_vec();
+    bytes
+}
+
+/// shift `vna_other` of the needed versions to make room for the defined versions.
+fn shift_needed_versions(elf: &mut file::ELF64, shift: u16) {
+    let read_u32 = |b: &[u8], at: usize| -> Option<u32> {
+        b.get(at..at + 4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    };
+    for sct in elf.sections.iter_mut() {
+        if sct.header.get_type() != section::Type::Any(SHT_GNU_VERNEED) {
+            continue;
+        }
+        let bytes = match &mut sct.contents {
+            section::Contents64::Raw(bytes) => bytes,
+            _ => continue,
+        };
+        // Elf64_Verneed: vn_version, vn_cnt, vn_file, vn_aux, vn_next
+        let mut vn = 0;
+        for _ in 0..sct.header.sh_info.max(1) {
+            let cnt = match bytes.get(vn + 2..vn + 4) {
+                Some(b) => u16::from_le_bytes([b[0], b[1]]),
+                None => break,
+            };
+            // Elf64_Vernaux: vna_hash, vna_flags, vna_other, vna_name, vna_next
+            let mut vna = vn + read_u32(bytes, vn + 8).unwrap_or(0) as usize;
+            for _ in 0..cnt {
+                if let Some(b) = bytes.get_mut(vna + 6..vna + 8) {
+                    let other = u16::from_le_bytes([b[0], b[1]]) + shift;
+                    b.copy_from_slice(&other.to_le_bytes());
+                }
+                match read_u32(bytes, vna + 12) {
+                    Some(0) | None => break,
+                    Some(next) => vna += next as usize,
+                }
+            }
+            match read_u32(bytes, vn + 12) {
+                Some(0) | None => break,
+                Some(next) => vn += next as usize,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod version_script_tests {
+    use super::*;
+
+    #[test]
+    fn parse_test() {
+        let script = VersionScript::parse(
+            "# comment
+             VERS_1 {
+                 global:
+                     foo; \"bar\";
+                 local: /* hidden */ *;
+             };
+             VERS_2 { baz*; } VERS_1;",
+        )
+        .unwrap();
+        assert_eq!(
+            vec![
+                VersionNode {
+                    name: Some("VERS_1".to_string()),
+                    global: vec!["foo".to_string(), "bar".to_string()],
+                    local: vec!["*".to_string()],
+                    parents: vec![],
+                },
+                VersionNode {
+                    name: Some("VERS_2".to_string()),
+                    global: vec!["baz*".to_string()],
+                    local: vec![],
+                    parents: vec!["VERS_1".to_string()],
+                },
+            ],
+            script.nodes
+        );
+        assert_eq!(Some((1, false)), script.lookup("baz_1"));
+        assert_eq!(Some((0, true)), script.lookup("qux"));
+
+        for (src, line) in [
+            ("V { foo }", 1),
+            ("V { foo; };\n{ bar; };", 2),
+            ("V { foo; } W;", 1),
+            ("V {\n extern \"C++\" { ns::*; }; };", 2),
+        ]
+        .iter()
+        {
+            match VersionScript::parse(src) {
+                Err(TransformError::InvalidVersionScript { line: l, .. }) => assert_eq!(*line, l),
+                r => panic!("{:?}", r),
+            }
+        }
+    }
+}
@@ -0,0 +1,1032 @@
+//! Describing files in YAML, in the spirit of LLVM's `obj2yaml`/`yaml2obj`(requires the `yaml` feature).
+//!
+//! ```yaml
+//! FileHeader:
+//!   Class: ELFCLASS64
+//!   Data: ELFDATA2LSB
+//!   Type: ET_REL
+//!   Machine: EM_X86_64
+//! Sections:
+//!   - Name: .text
+//!     Type: SHT_PROGBITS
+//!     Flags: [ SHF_ALLOC, SHF_EXECINSTR ]
+//!     AddressAlign: 16
+//!     Content: "31c0c3"
+//! Symbols:
+//!   - Name: main
+//!     Type: STT_FUNC
+//!     Section: .text
+//!     Binding: STB_GLOBAL
+//!     Size: 3
+//! ```
+//!
+//! The names of the constants are the ones in `elf.h`, and numbers are accepted for the others.
+//! Sections are referred by their names, or by their indices if the names are ambiguous.
+//!
+//! `from_yaml()` fills what isn't written as `yaml2obj` does:
+//! - `.symtab`, `.strtab` and `.shstrtab` are appended if they aren't listed
+//! - the string tables without `Content` which the symbol tables link to are built from the symbol names
+//! - each section is placed after the contents placed so far, unless its `Offset` is given
+//! - `Offset`, `FileSize` and `MemSize` of program headers are computed from the sections `FirstSec` to `LastSec`
+//!
+//! Only 64bit little-endian files are supported.
+
+use serde::{Deserialize, Serialize};
+
+use super::TransformError;
+use crate::layout::{align_up, is_nobits};
+use crate::*;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase", deny_unknown_fields)]
+struct Document {
+    file_header: FileHeader,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    program_headers: Vec<ProgramHeader>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    sections: Vec<Section>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    symbols: Vec<Symbol>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    dynamic_symbols: Vec<Symbol>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase", deny_unknown_fields)]
+struct FileHeader {
+    class: String,
+    data: String,
+    #[serde(rename = "OSABI", default, skip_serializing_if = "Option::is_none")]
+    osabi: Option<String>,
+    r#type: String,
+    machine: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    flags: Option<Elf64Word>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    entry: Option<Elf64Addr>,
+    /// `e_shoff`, if the section header table isn't placed after the contents
+    #[serde(rename = "EShOff", default, skip_serializing_if = "Option::is_none")]
+    e_sh_off: Option<Elf64Off>,
+    /// the section header string table, if it isn't `.shstrtab`
+    #[serde(rename = "EShStrNdx", default, skip_serializing_if = "Option::is_none")]
+    e_sh_str_ndx: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase", deny_unknown_fields)]
+struct ProgramHeader {
+    r#type: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    flags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    first_sec: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    last_sec: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    v_addr: Option<Elf64Addr>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    p_addr: Option<Elf64Addr>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    align: Option<Elf64Xword>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    offset: Option<Elf64Off>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    file_size: Option<Elf64Xword>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    mem_size: Option<Elf64Xword>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase", deny_unknown_fields)]
+struct Section {
+    name: String,
+    r#type: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    flags: Vec<String>,
+    /// `sh_name`, if it isn't the one found in `.shstrtab`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sh_name: Option<Elf64Word>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    address: Option<Elf64Addr>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    link: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    info: Option<Elf64Word>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    address_align: Option<Elf64Xword>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ent_size: Option<Elf64Xword>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    offset: Option<Elf64Off>,
+    /// the size of `SHT_NOBITS`, or the size to which `Content` is zero-extended
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    size: Option<Elf64Xword>,
+    /// the contents in hex
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    content: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase", deny_unknown_fields)]
+struct Symbol {
+    name: String,
+    /// `st_name`, if it isn't the one found in the string table
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    st_name: Option<Elf64Word>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    r#type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    section: Option<String>,
+    /// a special section index like `SHN_ABS`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    index: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    binding: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    value: Option<Elf64Addr>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    size: Option<Elf64Xword>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    other: Option<u8>,
+}
+
+type Names = &'static [(&'static str, u64)];
+
+const CLASSES: Names = &[("ELFCLASSNONE", 0), ("ELFCLASS32", 1), ("ELFCLASS64", 2)];
+const DATA: Names = &[("ELFDATANONE", 0), ("ELFDATA2LSB", 1), ("ELFDATA2MSB", 2)];
+const OSABIS: Names = &[
+    ("ELFOSABI_NONE", 0),
+    ("ELFOSABI_HPUX", 1),
+    ("ELFOSABI_NETBSD", 2),
+    ("ELFOSABI_GNU", 3),
+    ("ELFOSABI_SOLARIS", 6),
+    ("ELFOSABI_FREEBSD", 9),
+    ("ELFOSABI_OPENBSD", 12),
+    ("ELFOSABI_ARM", 97),
+    ("ELFOSABI_STANDALONE", 255),
+];
+const TYPES: Names = &[
+    ("ET_NONE", 0),
+    ("ET_REL", 1),
+    ("ET_EXEC", 2),
+    ("ET_DYN", 3),
+    ("ET_CORE", 4),
+];
+const MACHINES: Names = &[
+    ("EM_NONE", 0),
+    ("EM_386", 3),
+    ("EM_MIPS", 8),
+    ("EM_PPC", 20),
+    ("EM_PPC64", 21),
+    ("EM_ARM", 40),
+    ("EM_X86_64", 62),
+    ("EM_AARCH64", 183),
+    ("EM_RISCV", 243),
+    ("EM_BPF", 247),
+    ("EM_LOONGARCH", 258),
+];
+const SECTION_TYPES: Names = &[
+    ("SHT_NULL", 0),
+    ("SHT_PROGBITS", 1),
+    ("SHT_SYMTAB", 2),
+    ("SHT_STRTAB", 3),
+    ("SHT_RELA", 4),
+    ("SHT_HASH", 5),
+    ("SHT_DYNAMIC", 6),
+    ("SHT_NOTE", 7),
+    ("SHT_NOBITS", 8),
+    ("SHT_REL", 9),
+    ("SHT_SHLIB", 10),
+    ("SHT_DYNSYM", 11),
+    ("SHT_INIT_ARRAY", 14),
+    ("SHT_FINI_ARRAY", 15),
+    ("SHT_PREINIT_ARRAY", 16),
+    ("SHT_GROUP", 17),
+    ("SHT_SYMTAB_SHNDX", 18),
+    ("SHT_RELR", 19),
+    ("SHT_LLVM_ADDRSIG", 0x6fff4c03),
+    ("SHT_GNU_ATTRIBUTES", 0x6ffffff5),
+    ("SHT_GNU_HASH", 0x6ffffff6),
+    ("SHT_GNU_verdef", 0x6ffffffd),
+    ("SHT_GNU_verneed", 0x6ffffffe),
+    ("SHT_GNU_versym", 0x6fffffff),
+];
+const SECTION_FLAGS: Names = &[
+    ("SHF_WRITE", 0x1),
+    ("SHF_ALLOC", 0x2),
+    ("SHF_EXECINSTR", 0x4),
+    ("SHF_MERGE", 0x10),
+    ("SHF_STRINGS", 0x20),
+    ("SHF_INFO_LINK", 0x40),
+    ("SHF_LINK_ORDER", 0x80),
+    ("SHF_OS_NONCONFORMING", 0x100),
+    ("SHF_GROUP", 0x200),
+    ("SHF_TLS", 0x400),
+    ("SHF_COMPRESSED", 0x800),
+    ("SHF_GNU_RETAIN", 0x20_0000),
+    ("SHF_EXCLUDE", 0x8000_0000),
+];
+const SEGMENT_TYPES: Names = &[
+    ("PT_NULL", 0),
+    ("PT_LOAD", 1),
+    ("PT_DYNAMIC", 2),
+    ("PT_INTERP", 3),
+    ("PT_NOTE", 4),
+    ("PT_SHLIB", 5),
+    ("PT_PHDR", 6),
+    ("PT_TLS", 7),
+    ("PT_GNU_EH_FRAME", 0x6474e550),
+    ("PT_GNU_STACK", 0x6474e551),
+    ("PT_GNU_RELRO", 0x6474e552),
+    ("PT_GNU_PROPERTY", 0x6474e553),
+];
+const SEGMENT_FLAGS: Names = &[("PF_X", 0x1), ("PF_W", 0x2), ("PF_R", 0x4)];
+const SYMBOL_TYPES: Names = &[
+    ("STT_NOTYPE", 0),
+    ("STT_OBJECT", 1),
+    ("STT_FUNC", 2),
+    ("STT_SECTION", 3),
+    ("STT_FILE", 4),
+    ("STT_COMMON", 5),
+    ("STT_TLS", 6),
+    ("STT_GNU_IFUNC", 10),
+];
+const BINDINGS: Names = &[
+    ("STB_LOCAL", 0),
+    ("STB_GLOBAL", 1),
+    ("STB_WEAK", 2),
+    ("STB_GNU_UNIQUE", 10),
+];
+const SPECIAL_INDICES: Names = &[
+    ("SHN_UNDEF", 0),
+    ("SHN_ABS", 0xfff1),
+    ("SHN_COMMON", 0xfff2),
+    ("SHN_XINDEX", 0xffff),
+];
+
+/// describe the file in YAML.
+///
+/// The contents of the symbol tables are written as `Symbols` and `DynamicSymbols`,
+/// and the contents of `.shstrtab` and the string tables only for symbols are omitted.
+/// The other contents are written in hex.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::{parser, transform};
+///
+/// let elf = parser::parse_elf64("src/parser/testdata/sample").unwrap();
+/// let yaml = transform::to_yaml(&elf);
+/// assert!(yaml.contains("Machine: EM_X86_64"));
+/// assert!(yaml.contains("- Name: main"));
+///
+/// // YAMLから元のファイルを作り直せる
+/// let rebuilt = transform::from_yaml(&yaml).unwrap();
+/// assert_eq!(elf.segments, rebuilt.segments);
+/// assert_eq!(elf.sections.len(), rebuilt.sections.len());
+/// ```
+pub fn to_yaml(elf: &file::ELF64) -> String {
+    let ehdr = &elf.ehdr;
+    let ident: [u8; 16] = ehdr.e_ident.into();
+    let file_header = FileHeader {
+        class: name_of(CLASSES, ident[4] as u64),
+        data: name_of(DATA, ident[5] as u64),
+        osabi: non_zero(ident[7] as u64).map(|v| name_of(OSABIS, v)),
+        r#type: name_of(TYPES, ehdr.e_type as u64),
+        machine: name_of(MACHINES, ehdr.e_machine as u64),
+        flags: non_zero(ehdr.e_flags as u64).map(|_| ehdr.e_flags),
+        entry: non_zero(ehdr.e_entry),
+        e_sh_off: None,
+        e_sh_str_ndx: match elf.sections.get(ehdr.e_shstrndx as usize) {
+            Some(sct) if sct.name == ".shstrtab" => None,
+            _ => Some(section_ref(elf, ehdr.e_shstrndx as usize)),
+        },
+    };
+
+    // 作り直すと同じになる文字列テーブルは中身を書かない
+    let generated = |shidx: usize| {
+        shidx != 0
+            && shidx < elf.sections.len()
+            && rebuilt_string_table(elf, shidx).is_some_and(|table| {
+                table.to_contents64().to_le_bytes() == elf.sections[shidx].to_le_bytes()
+            })
+    };
+
+    // 明示されないセクションはそれまでの中身の後ろに置かれる
+    let mut end =
+        (header::Ehdr64::SIZE as usize + segment::Phdr64::SIZE * elf.segments.len()) as u64;
+    let mut sections = Vec::new();
+    let shstrndx = ehdr.e_shstrndx as usize;
+    let sh_names = name_offsets(
+        elf,
+        shstrndx,
+        generated(shstrndx),
+        elf.sections.iter().skip(1).map(|sct| sct.name.to_string()),
+    );
+    for (i, sct) in elf.sections.iter().enumerate().skip(1) {
+        let shdr = &sct.header;
+        let nobits = is_nobits(shdr);
+        let derived = if nobits {
+            end
+        } else {
+            align_up(end, shdr.sh_addralign)
+        };
+        let content = if nobits || is_symbol_table(shdr) || generated(i) {
+            None
+        } else {
+            Some(
+                sct.to_le_bytes()
+                    .iter()
+                    .map(|b| format!("{:02x}", b))
+                    .collect(),
+            )
+        };
+        sections.push(Section {
+            name: sct.name.to_string(),
+            sh_name: match sh_names.get(i - 1) {
+                Some(&expected) if expected == shdr.sh_name as usize => None,
+                _ => Some(shdr.sh_name),
+            },
+            r#type: name_of(SECTION_TYPES, shdr.sh_type as u64),
+            flags: flag_names(SECTION_FLAGS, shdr.sh_flags),
+            address: non_zero(shdr.sh_addr),
+            link: non_zero(shdr.sh_link as u64).map(|link| section_ref(elf, link as usize)),
+            info: non_zero(shdr.sh_info as u64).map(|_| shdr.sh_info),
+            address_align: non_zero(shdr.sh_addralign),
+            ent_size: non_zero(shdr.sh_entsize),
+            offset: differs(shdr.sh_offset, derived),
+            size: if nobits { Some(shdr.sh_size) } else { None },
+            content,
+        });
+        if !nobits {
+            end = end.max(shdr.sh_offset + shdr.sh_size);
+        }
+    }
+
+    let symbols_of = |ty: section::Type| -> Vec<Symbol> {
+        let symtab = elf.sections.iter().find(|sct| sct.header.get_type() == ty);
+        let syms = symtab
+            .and_then(|sct| match &sct.contents {
+                section::Contents64::Symbols(syms) => Some(syms.as_slice()),
+                _ => None,
+            })
+            .unwrap_or(&[]);
+        let strtab = symtab.map_or(0, |sct| sct.header.sh_link as usize);
+        let st_names = name_offsets(
+            elf,
+            strtab,
+            generated(strtab),
+            syms.iter().skip(1).map(|sym| sym.symbol_name.to_string()),
+        );
+        syms.iter()
+            .skip(1)
+            .zip(st_names)
+            .map(|(sym, expected)| {
+                let shndx = sym.st_shndx as usize;
+                let in_section = shndx != 0 && shndx < 0xff00 && shndx < elf.sections.len();
+                Symbol {
+                    name: sym.symbol_name.to_string(),
+                    st_name: if expected == sym.st_name as usize {
+                        None
+                    } else {
+                        Some(sym.st_name)
+                    },
+                    r#type: non_zero((sym.st_info & 0xf) as u64).map(|v| name_of(SYMBOL_TYPES, v)),
+                    section: if in_section {
+                        Some(section_ref(elf, shndx))
+                    } else {
+                        None
+                    },
+                    index: if in_section || shndx == 0 {
+                        None
+                    } else {
+                        Some(name_of(SPECIAL_INDICES, shndx as u64))
+                    },
+                    binding: non_zero((sym.st_info >> 4) as u64).map(|v| name_of(BINDINGS, v)),
+                    value: non_zero(sym.st_value),
+                    size: non_zero(sym.st_size),
+                    other: non_zero(sym.st_other as u64).map(|_| sym.st_other),
+                }
+            })
+            .collect()
+    };
+
+    let program_headers = elf
+        .segments
+        .iter()
+        .map(|sgt| {
+            let phdr = &sgt.header;
+            let covered: Vec<usize> = (1..elf.sections.len())
+                .filter(|&i| {
+                    let shdr = &elf.sections[i].header;
+                    shdr.sh_flags & Elf64Xword::from(section::Flag::Alloc) != 0
+                        && phdr.p_vaddr <= shdr.sh_addr
+                        && shdr.sh_addr + shdr.sh_size <= phdr.p_vaddr + phdr.p_memsz
+                        && !(shdr.sh_size == 0 && shdr.sh_addr == phdr.p_vaddr + phdr.p_memsz)
+                })
+                .collect();
+            let range = match (covered.first(), covered.last()) {
+                (Some(&first), Some(&last)) => Some((first, last)),
+                _ => None,
+            };
+            let (offset, filesz, _, memsz) = match range {
+                Some((first, last)) => covered_range(&elf.sections, first, last),
+                None => (0, 0, 0, 0),
+            };
+            ProgramHeader {
+                r#type: name_of(SEGMENT_TYPES, phdr.p_type as u64),
+                flags: flag_names(SEGMENT_FLAGS, phdr.p_flags as u64),
+                first_sec: range.map(|(first, _)| section_ref(elf, first)),
+                last_sec: range.map(|(_, last)| section_ref(elf, last)),
+                v_addr: Some(phdr.p_vaddr),
+                p_addr: if phdr.p_paddr == phdr.p_vaddr {
+                    None
+                } else {
+                    Some(phdr.p_paddr)
+                },
+                align: Some(phdr.p_align),
+                offset: differs(phdr.p_offset, offset),
+                file_size: differs(phdr.p_filesz, filesz),
+                mem_size: differs(phdr.p_memsz, memsz),
+            }
+        })
+        .collect();
+
+    let mut file_header = file_header;
+    file_header.e_sh_off = differs(ehdr.e_shoff, align_up(end, 8));
+    let doc = Document {
+        file_header,
+        program_headers,
+        sections,
+        symbols: symbols_of(section::Type::SymTab),
+        dynamic_symbols: symbols_of(section::Type::DynSym),
+    };
+    serde_yaml::to_string(&doc).unwrap()
+}
+
+/// generate a file from the YAML description written by `to_yaml()` or by hand.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::{header, section, transform};
+///
+/// let elf = transform::from_yaml(
+///     "
+/// FileHeader:
+///   Class: ELFCLASS64
+///   Data: ELFDATA2LSB
+///   Type: ET_REL
+///   Machine: EM_X86_64
+/// Sections:
+///   - Name: .text
+///     Type: SHT_PROGBITS
+///     Flags: [ SHF_ALLOC, SHF_EXECINSTR ]
+///     AddressAlign: 0x10
+///     Content: '31c0c3'
+/// Symbols:
+///   - Name: main
+///     Type: STT_FUNC
+///     Section: .text
+///     Binding: STB_GLOBAL
+///     Size: 3
+/// ",
+/// )
+/// .unwrap();
+///
+/// assert_eq!(header::Type::Rel, elf.ehdr.get_type());
+/// let text = elf.first_section_by(|sct| sct.name == ".text").unwrap();
+/// assert_eq!(vec![0x31, 0xc0, 0xc3], text.to_le_bytes());
+///
+/// // .symtab/.strtab/.shstrtabは補われる
+/// let symtab = elf.first_section_by(|sct| sct.name == ".symtab").unwrap();
+/// match &symtab.contents {
+///     section::Contents64::Symbols(syms) => assert_eq!("main", syms[1].symbol_name),
+///     _ => unreachable!(),
+/// }
+/// assert_eq!(".shstrtab", elf.sections[elf.ehdr.e_shstrndx as usize].name);
+/// ```
+pub fn from_yaml(yaml: &str) -> Result<file::ELF64, TransformError> {
+    let mut doc: Document = serde_yaml::from_str(yaml).map_err(|e| invalid(e.to_string()))?;
+    let fh = &doc.file_header;
+    if value_of(CLASSES, "class", &fh.class)? != 2 {
+        return Err(invalid("only ELFCLASS64 is supported".to_string()));
+    }
+    if value_of(DATA, "data encoding", &fh.data)? != 1 {
+        return Err(invalid("only ELFDATA2LSB is supported".to_string()));
+    }
+
+    let has = |doc: &Document, name: &str| doc.sections.iter().any(|s| s.name == name);
+    if !doc.symbols.is_empty() && !has(&doc, ".symtab") {
+        doc.sections.push(Section {
+            name: ".symtab".to_string(),
+            r#type: "SHT_SYMTAB".to_string(),
+            link: Some(".strtab".to_string()),
+            address_align: Some(8),
+            ..Default::default()
+        });
+        if !has(&doc, ".strtab") {
+            doc.sections.push(implicit_strtab(".strtab"));
+        }
+    }
+    if doc.file_header.e_sh_str_ndx.is_none() && !has(&doc, ".shstrtab") {
+        doc.sections.push(implicit_strtab(".shstrtab"));
+    }
+
+    let mut names = vec![String::new()];
+    names.extend(doc.sections.iter().map(|s| s.name.clone()));
+
+    // 各セクションのヘッダと生の中身
+    let mut sections = vec![section::Section64::new_null_section()];
+    let mut raws: Vec<Option<Vec<u8>>> = vec![None];
+    for s in doc.sections.iter() {
+        let sh_type = value_of(SECTION_TYPES, "section type", &s.r#type)? as Elf64Word;
+        let default_entsize = match section::Type::from(sh_type) {
+            section::Type::SymTab | section::Type::DynSym => symbol::Symbol64::SIZE as u64,
+            section::Type::Rela => relocation::Rela64::SIZE,
+            section::Type::Dynamic => dynamic::Dyn64::SIZE as u64,
+            _ => 0,
+        };
+        let header = section::Shdr64 {
+            sh_type,
+            sh_flags: flag_bits(SECTION_FLAGS, "section flag", &s.flags)?,
+            sh_addr: s.address.unwrap_or(0),
+            sh_link: match &s.link {
+                Some(link) => resolve_section(&names, link)? as Elf64Word,
+                None => 0,
+            },
+            sh_info: s.info.unwrap_or(0),
+            sh_addralign: s.address_align.unwrap_or(0),
+            sh_entsize: s.ent_size.unwrap_or(default_entsize),
+            sh_size: s.size.unwrap_or(0),
+            ..Default::default()
+        };
+        let raw = match &s.content {
+            Some(content) => {
+                let mut bytes = from_hex(content)
+                    .ok_or_else(|| invalid(format!("section `{}`: Content isn't hex", s.name)))?;
+                bytes.resize(bytes.len().max(header.sh_size as usize), 0);
+                Some(bytes)
+            }
+            None => None,
+        };
+        sections.push(section::Section64 {
+            name: s.name.clone().into(),
+            header,
+            contents: section::Contents64::Raw(Vec::new()),
+        });
+        raws.push(raw);
+    }
+
+    // シンボルテーブルと，中身の無い文字列テーブルを作る
+    let mut tables: Vec<(usize, Option<section::StringTable>)> = Vec::new();
+    for (syms, ty) in [
+        (&doc.symbols, section::Type::SymTab),
+        (&doc.dynamic_symbols, section::Type::DynSym),
+    ] {
+        let shidx = match sections.iter().position(|sct| sct.header.get_type() == ty) {
+            Some(shidx) => shidx,
+            None if syms.is_empty() => continue,
+            None => {
+                return Err(invalid(format!(
+                    "no {} section for the symbols",
+                    name_of(SECTION_TYPES, Elf64Word::from(ty) as u64)
+                )))
+            }
+        };
+        let strtab = sections[shidx].header.sh_link as usize;
+        if strtab == 0 || strtab >= sections.len() {
+            return Err(invalid(format!("`{}` has no string table", names[shidx])));
+        }
+
+        let mut symbols = vec![symbol::Symbol64::new_null_symbol()];
+        for sym in syms.iter() {
+            let st_name = match &mut raws[strtab] {
+                _ if sym.st_name.is_some() => sym.st_name.unwrap() as usize,
+                Some(raw) => string_offset(raw, &sym.name),
+                None => {
+                    let table = match tables.iter_mut().find(|(i, _)| *i == strtab) {
+                        Some((_, table)) => table,
+                        None => {
+                            tables.push((strtab, Some(section::StringTable::new())));
+                            &mut tables.last_mut().unwrap().1
+                        }
+                    };
+                    table.as_mut().unwrap().add(&sym.name)
+                }
+            };
+            let st_shndx = match (&sym.section, &sym.index) {
+                (Some(sct), _) => resolve_section(&names, sct)?,
+                (None, Some(index)) => value_of(SPECIAL_INDICES, "section index", index)? as usize,
+                (None, None) => 0,
+            };
+            let ty = match &sym.r#type {
+                Some(ty) => value_of(SYMBOL_TYPES, "symbol type", ty)?,
+                None => 0,
+            };
+            let bind = match &sym.binding {
+                Some(bind) => value_of(BINDINGS, "symbol binding", bind)?,
+                None => 0,
+            };
+            symbols.push(symbol::Symbol64 {
+                st_name: st_name as Elf64Word,
+                st_info: (bind << 4 | (ty & 0xf)) as u8,
+                st_other: sym.other.unwrap_or(0),
+                st_shndx: st_shndx as Elf64Section,
+                st_value: sym.value.unwrap_or(0),
+                st_size: sym.size.unwrap_or(0),
+                symbol_name: sym.name.clone().into(),
+            });
+        }
+        // sh_infoは最初の非ローカルシンボル
+        if doc.sections[shidx - 1].info.is_none() {
+            sections[shidx].header.sh_info = symbols
+                .iter()
+                .position(|sym| sym.st_info >> 4 != 0)
+                .unwrap_or(symbols.len()) as Elf64Word;
+        }
+        sections[shidx].contents = section::Contents64::Symbols(symbols);
+    }
+
+    let codecs = section::Codecs::new();
+    for (shidx, sct) in sections.iter_mut().enumerate().skip(1) {
+        if let Some((_, table)) = tables.iter_mut().find(|(i, _)| *i == shidx) {
+            sct.contents = table.take().unwrap().to_contents64();
+        } else if let Some(raw) = &raws[shidx] {
+            sct.contents = codecs.decode(&sct.header, &sct.name, raw);
+        } else if !is_symbol_table(&sct.header) && !is_nobits(&sct.header) {
+            sct.contents = section::Contents64::Raw(vec![0; sct.header.sh_size as usize]);
+        }
+    }
+
+    let mut ehdr = header::Ehdr64::default();
+    ehdr.set_class(header::Class::Bit64);
+    ehdr.set_data(header::Data::LSB2);
+    ehdr.set_file_version(header::Version::Current);
+    ehdr.set_object_version(header::Version::Current);
+    if let Some(osabi) = &fh.osabi {
+        ehdr.set_osabi(header::OSABI::from(value_of(OSABIS, "OS ABI", osabi)? as u8));
+    }
+    ehdr.e_type = value_of(TYPES, "file type", &fh.r#type)? as Elf64Half;
+    ehdr.e_machine = value_of(MACHINES, "machine", &fh.machine)? as Elf64Half;
+    ehdr.e_flags = fh.flags.unwrap_or(0);
+    ehdr.e_entry = fh.entry.unwrap_or(0);
+    ehdr.e_ehsize = header::Ehdr64::SIZE as Elf64Half;
+    ehdr.e_phnum = doc.program_headers.len() as Elf64Half;
+    ehdr.e_shentsize = section::Shdr64::SIZE as Elf64Half;
+    ehdr.e_shnum = sections.len() as Elf64Half;
+    ehdr.e_shstrndx =
+        resolve_section(&names, fh.e_sh_str_ndx.as_deref().unwrap_or(".shstrtab"))? as Elf64Half;
+    // プログラムヘッダが無ければ0のまま
+    if ehdr.e_phnum == 0 {
+        ehdr.e_phoff = 0;
+        ehdr.e_phentsize = 0;
+    } else {
+        ehdr.e_phoff = header::Ehdr64::SIZE as Elf64Off;
+        ehdr.e_phentsize = segment::Phdr64::SIZE as Elf64Half;
+    }
+
+    let mut elf = file::ELF64 {
+        ehdr,
+        sections,
+        segments: Vec::new(),
+        address_cache: Default::default(),
+    };
+    let shstrndx = elf.ehdr.e_shstrndx as usize;
+    match raws[shstrndx].take() {
+        // 書かれた.shstrtabの中から名前を探す
+        Some(mut raw) => {
+            for (sct, s) in elf.sections.iter_mut().skip(1).zip(doc.sections.iter()) {
+                sct.header.sh_name = match s.sh_name {
+                    Some(sh_name) => sh_name,
+                    None => string_offset(&mut raw, &sct.name) as Elf64Word,
+                };
+            }
+            let header = elf.sections[shstrndx].header;
+            elf.sections[shstrndx].contents = codecs.decode(&header, ".shstrtab", &raw);
+        }
+        None => {
+            elf.rebuild_shstrtab();
+            for (sct, s) in elf.sections.iter_mut().skip(1).zip(doc.sections.iter()) {
+                if let Some(sh_name) = s.sh_name {
+                    sct.header.sh_name = sh_name;
+                }
+            }
+        }
+    }
+
+    let mut end =
+        (header::Ehdr64::SIZE as usize + segment::Phdr64::SIZE * doc.program_headers.len()) as u64;
+    for (sct, s) in elf.sections.iter_mut().skip(1).zip(doc.sections.iter()) {
+        let nobits = is_nobits(&sct.header);
+        sct.header.sh_offset = match s.offset {
+            Some(offset) => offset,
+            None if nobits => end,
+            None => align_up(end, sct.header.sh_addralign),
+        };
+        if !nobits {
+            sct.header.sh_size = sct.contents.size() as u64;
+            end = end.max(sct.header.sh_offset + sct.header.sh_size);
+        }
+    }
+    elf.ehdr.e_shoff = match fh.e_sh_off {
+        Some(shoff) => shoff,
+        None if elf.sections.len() > 1 => align_up(end, 8),
+        None => 0,
+    };
+
+    for ph in doc.program_headers.iter() {
+        let first = ph.first_sec.as_ref().or(ph.last_sec.as_ref());
+        let last = ph.last_sec.as_ref().or(ph.first_sec.as_ref());
+        let (offset, filesz, vaddr, memsz) = match (first, last) {
+            (Some(first), Some(last)) => covered_range(
+                &elf.sections,
+                resolve_section(&names, first)?,
+                resolve_section(&names, last)?,
+            ),
+            _ => (0, 0, 0, 0),
+        };
+        let vaddr = ph.v_addr.unwrap_or(vaddr);
+        elf.segments.push(segment::Segment64 {
+            header: segment::Phdr64 {
+                p_type: value_of(SEGMENT_TYPES, "segment type", &ph.r#type)? as Elf64Word,
+                p_flags: flag_bits(SEGMENT_FLAGS, "segment flag", &ph.flags)? as Elf64Word,
+                p_offset: ph.offset.unwrap_or(offset),
+                p_vaddr: vaddr,
+                p_paddr: ph.p_addr.unwrap_or(vaddr),
+                p_filesz: ph.file_size.unwrap_or(filesz),
+                p_memsz: ph.mem_size.unwrap_or(memsz),
+                p_align: ph.align.unwrap_or(1),
+            },
+        });
+    }
+
+    Ok(elf)
+}
+
+/// the string table which `from_yaml()` builds for the section without `Content`.
+fn rebuilt_string_table(elf: &file::ELF64, shidx: usize) -> Option<section::StringTable> {
+    let mut table = section::StringTable::new();
+    if shidx == elf.ehdr.e_shstrndx as usize {
+        for sct in elf.sections.iter().skip(1) {
+            table.add(&sct.name);
+        }
+        return Some(table);
+    }
+
+    let linked = |sct: &&section::Section64| sct.header.sh_link as usize == shidx;
+    if !elf
+        .sections
+        .iter()
+        .filter(linked)
+        .all(|sct| is_symbol_table(&sct.header))
+    {
+        return None;
+    }
+    let mut found = false;
+    for sct in elf.sections.iter().filter(linked) {
+        if let section::Contents64::Symbols(syms) = &sct.contents {
+            found = true;
+            for sym in syms.iter().skip(1) {
+                table.add(&sym.symbol_name);
+            }
+        }
+    }
+    if found {
+        Some(table)
+    } else {
+        None
+    }
+}
+
+/// the offsets which `from_yaml()` assigns to the names in the string table `shidx`,
+/// empty if `shidx` isn't a section.
+fn name_offsets(
+    elf: &file::ELF64,
+    shidx: usize,
+    generated: bool,
+    names: impl Iterator<Item = String>,
+) -> Vec<usize> {
+    if shidx == 0 || shidx >= elf.sections.len() {
+        return Vec::new();
+    }
+    if generated {
+        let mut table = section::StringTable::new();
+        names.map(|name| table.add(&name)).collect()
+    } else {
+        let mut raw = elf.sections[shidx].to_le_bytes();
+        names.map(|name| string_offset(&mut raw, &name)).collect()
+    }
+}
+
+fn invalid(message: String) -> TransformError {
+    TransformError::InvalidYaml { message }
+}
+
+fn implicit_strtab(name: &str) -> Section {
+    Section {
+        name: name.to_string(),
+        r#type: "SHT_STRTAB".to_string(),
+        address_align: Some(1),
+        ..Default::default()
+    }
+}
+
+fn is_symbol_table(shdr: &section::Shdr64) -> bool {
+    matches!(
+        shdr.get_type(),
+        section::Type::SymTab | section::Type::DynSym
+    )
+}
+
+fn non_zero(v: u64) -> Option<u64> {
+    if v == 0 {
+        None
+    } else {
+        Some(v)
+    }
+}
+
+fn differs(v: u64, derived: u64) -> Option<u64> {
+    if v == derived {
+        None
+    } else {
+        Some(v)
+    }
+}
+
+fn name_of(names: Names, v: u64) -> String {
+    match names.iter().find(|(_, value)| *value == v) {
+        Some((name, _)) => name.to_string(),
+        None => format!("{:#x}", v),
+    }
+}
+
+fn value_of(names: Names, what: &str, s: &str) -> Result<u64, TransformError> {
+    if let Some((_, v)) = names.iter().find(|(name, _)| *name == s) {
+        return Ok(*v);
+    }
+    parse_number(s).ok_or_else(|| invalid(format!("unknown {} `{}`", what, s)))
+}
+
+fn parse_number(s: &str) -> Option<u64> {
+    match s.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+/// the names of the bits, and the remaining bits in hex.
+fn flag_names(names: Names, bits: u64) -> Vec<String> {
+    let mut rest = bits;
+    let mut flags: Vec<String> = names
+        .iter()
+        .filter(|(_, v)| bits & v != 0)
+        .map(|(name, v)| {
+            rest &= !v;
+            name.to_string()
+        })
+        .collect();
+    if rest != 0 {
+        flags.push(format!("{:#x}", rest));
+    }
+    flags
+}
+
+fn flag_bits(names: Names, what: &str, flags: &[String]) -> Result<u64, TransformError> {
+    flags
+        .iter()
+        .try_fold(0, |bits, flag| Ok(bits | value_of(names, what, flag)?))
+}
+
+/// the name of the section, or its index if another section has the same name.
+fn section_ref(elf: &file::ELF64, shidx: usize) -> String {
+    match elf.sections.get(shidx) {
+        Some(sct) if elf.sections.iter().filter(|s| s.name == sct.name).count() == 1 => {
+            sct.name.to_string()
+        }
+        _ => shidx.to_string(),
+    }
+}
+
+fn resolve_section(names: &[String], s: &str) -> Result<usize, TransformError> {
+    let mut found = names.iter().enumerate().filter(|(_, name)| *name == s);
+    match (found.next(), found.next()) {
+        (Some((shidx, _)), None) => return Ok(shidx),
+        (Some(_), Some(_)) => {
+            return Err(invalid(format!(
+                "section `{}` is ambiguous, refer it by the index",
+                s
+            )))
+        }
+        _ => {}
+    }
+    match s.parse::<usize>() {
+        Ok(shidx) if shidx < names.len() => Ok(shidx),
+        _ => Err(invalid(format!("no section `{}`", s))),
+    }
+}
+
+/// `(p_offset, p_filesz, p_vaddr, p_memsz)` covering the sections `first..=last`.
+fn covered_range(
+    sections: &[section::Section64],
+    first: usize,
+    last: usize,
+) -> (Elf64Off, Elf64Xword, Elf64Addr, Elf64Xword) {
+    let start = &sections[first].header;
+    let mut file_end = start.sh_offset;
+    let mut mem_end = start.sh_addr;
+    for sct in sections[first..=last.max(first)].iter() {
+        let shdr = &sct.header;
+        if !is_nobits(shdr) {
+            file_end = file_end.max(shdr.sh_offset + shdr.sh_size);
+        }
+        mem_end = mem_end.max(shdr.sh_addr + shdr.sh_size);
+    }
+    (
+        start.sh_offset,
+        file_end - start.sh_offset,
+        start.sh_addr,
+        mem_end - start.sh_addr,
+    )
+}
+
+/// the offset of `s` in the raw string table, appending it if absent.
+fn string_offset(raw: &mut Vec<u8>, s: &str) -> usize {
+    if s.is_empty() {
+        return 0;
+    }
+    let needle: Vec<u8> = s.bytes().chain(std::iter::once(0)).collect();
+    // 末尾が一致する文字列を共有してよい
+    if let Some(offset) = raw
+        .windows(needle.len())
+        .position(|w| w == needle.as_slice())
+    {
+        return offset;
+    }
+    if raw.is_empty() {
+        raw.push(0);
+    }
+    let offset = raw.len();
+    raw.extend_from_slice(&needle);
+    offset
+}
+
+fn from_hex(s: &str) -> Option<Vec<u8>> {
+    let digits: Vec<u8> = s.bytes().filter(|b| !b.is_ascii_whitespace()).collect();
+    if !digits.len().is_multiple_of(2) {
+        return None;
+    }
+    digits
+        .chunks(2)
+        .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod yaml_tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip_test() {
+        let elf = parser::parse_elf64("src/parser/testdata/sample").unwrap();
+        let rebuilt = from_yaml(&to_yaml(&elf)).unwrap();
+
+        assert_eq!(elf.ehdr, rebuilt.ehdr);
+        assert_eq!(elf.to_le_bytes(), rebuilt.to_le_bytes());
+
+        // 生成したファイルは.strtabも作り直せる
+        let main = builder::ExportedFunction::new("main", vec![0xc3]);
+        let exe = builder::ExecutableWriter::new()
+            .function(main)
+            .start_stub(builder::start_stub("main"))
+            .build()
+            .unwrap();
+        let yaml = to_yaml(&exe);
+        let doc: Document = serde_yaml::from_str(&yaml).unwrap();
+        let strtab = doc.sections.iter().find(|s| s.name == ".strtab").unwrap();
+        assert_eq!(None, strtab.content);
+        assert_eq!(exe.to_le_bytes(), from_yaml(&yaml).unwrap().to_le_bytes());
+    }
+
+    #[test]
+    fn from_yaml_error_test() {
+        let header = "FileHeader: { Class: ELFCLASS64, Data: ELFDATA2LSB, Type: ET_REL, Machine: EM_X86_64 }\n";
+        let err = |body: &str| {
+            from_yaml(&format!("{}{}", header, body))
+                .unwrap_err()
+                .to_string()
+        };
+
+        assert!(err("Sections: [ { Name: .a, Type: SHT_FOO } ]")
+            .contains("unknown section type `SHT_FOO`"));
+        assert!(
+            err("Symbols: [ { Name: a, Section: .missing } ]").contains("no section `.missing`")
+        );
+        assert!(err("DynamicSymbols: [ { Name: a } ]").contains("no SHT_DYNSYM section"));
+        assert!(from_yaml(
+            "FileHeader: { Class: ELFCLASS32, Data: ELFDATA2LSB, Type: ET_REL, Machine: EM_386 }"
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,582 @@
+//! Unwind information in `.eh_frame` and the exception tables in `.gcc_except_table`.
+//!
+//! The addresses are resolved by the addresses of the sections,
+//! so they are meaningful in linked files only.
+
+use crate::*;
+use section::{PayloadReader, ReadError};
+use thiserror::Error as TError;
+
+pub const EH_FRAME_SECTION: &str = ".eh_frame";
+pub const GCC_EXCEPT_TABLE_SECTION: &str = ".gcc_except_table";
+
+/// the pointer encodings(`DW_EH_PE_*`), a format in the low 4 bits and an application in the high 4 bits
+pub const DW_EH_PE_ABSPTR: u8 = 0x00;
+pub const DW_EH_PE_ULEB128: u8 = 0x01;
+pub const DW_EH_PE_UDATA2: u8 = 0x02;
+pub const DW_EH_PE_UDATA4: u8 = 0x03;
+pub const DW_EH_PE_UDATA8: u8 = 0x04;
+pub const DW_EH_PE_SLEB128: u8 = 0x09;
+pub const DW_EH_PE_SDATA2: u8 = 0x0a;
+pub const DW_EH_PE_SDATA4: u8 = 0x0b;
+pub const DW_EH_PE_SDATA8: u8 = 0x0c;
+/// relative to the address of the pointer
+pub const DW_EH_PE_PCREL: u8 = 0x10;
+pub const DW_EH_PE_TEXTREL: u8 = 0x20;
+pub const DW_EH_PE_DATAREL: u8 = 0x30;
+pub const DW_EH_PE_FUNCREL: u8 = 0x40;
+pub const DW_EH_PE_ALIGNED: u8 = 0x50;
+/// the pointer points to the actual value, e.g. a GOT entry of the personality routine
+pub const DW_EH_PE_INDIRECT: u8 = 0x80;
+/// no pointer
+pub const DW_EH_PE_OMIT: u8 = 0xff;
+
+#[derive(TError, Debug, Clone, PartialEq, Eq)]
+pub enum UnwindError {
+    #[error("unsupported pointer encoding {encoding:#x} at offset {offset:#x}")]
+    UnsupportedPointerEncoding { offset: usize, encoding: u8 },
+    #[error("the FDE at offset {offset:#x} refers to no CIE at offset {cie:#x}")]
+    UnknownCie { offset: usize, cie: usize },
+    #[error("{0}")]
+    Read(#[from] ReadError),
+}
+
+/// A Common Information Entry
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Cie {
+    /// the offset in `.eh_frame`
+    pub offset: usize,
+    pub version: u8,
+    /// e.g. `zPLR`
+    pub augmentation: String,
+    pub code_alignment_factor: u64,
+    pub data_alignment_factor: i64,
+    pub return_address_register: u64,
+    /// the encoding of the addresses in the FDEs
+    pub fde_encoding: u8,
+    /// the encoding of the LSDA pointers in the FDEs(`L`)
+    pub lsda_encoding: Option<u8>,
+    /// the personality routine(`P`), or the address of its pointer if the encoding is `DW_EH_PE_indirect`
+    pub personality: Option<Elf64Addr>,
+    pub personality_encoding: Option<u8>,
+    /// the frames are signal handlers(`S`)
+    pub is_signal_frame: bool,
+    pub instructions: Vec<u8>,
+}
+
+/// A Frame Description Entry
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Fde {
+    /// the offset in `.eh_frame`
+    pub offset: usize,
+    /// the offset of the CIE in `.eh_frame`
+    pub cie: usize,
+    pub pc_begin: Elf64Addr,
+    pub pc_range: Elf64Xword,
+    /// the Language Specific Data Area, e.g. in `.gcc_except_table`
+    pub lsda: Option<Elf64Addr>,
+    pub instructions: Vec<u8>,
+}
+
+impl Fde {
+    pub fn pc_end(&self) -> Elf64Addr {
+        self.pc_begin + self.pc_range
+    }
+
+    pub fn contains(&self, pc: Elf64Addr) -> bool {
+        self.pc_begin <= pc && pc < self.pc_end()
+    }
+}
+
+/// The records in `.eh_frame`(in ELF64)
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct EhFrame {
+    pub cies: Vec<Cie>,
+    pub fdes: Vec<Fde>,
+}
+
+impl EhFrame {
+    /// parse `.eh_frame` placed at `addr`.
+    pub fn parse(bytes: &[u8], addr: Elf64Addr, data: header::Data) -> Result<Self, UnwindError> {
+        let mut r = PayloadReader::new(bytes, data);
+        let mut frame = Self::default();
+        while !r.is_empty() {
+            let offset = r.position();
+            let length = r.initial_length()?;
+            // 長さ0は終端
+            if length.length == 0 {
+                break;
+            }
+            let id_pos = r.position();
+            let end = id_pos + length.length as usize;
+            let id = if length.format == util::DwarfFormat::Dwarf64 {
+                r.u64()?
+            } else {
+                r.u32()? as u64
+            };
+            if id == 0 {
+                frame.cies.push(parse_cie(&mut r, offset, end, addr)?);
+            } else {
+                let cie_offset =
+                    id_pos
+                        .checked_sub(id as usize)
+                        .ok_or(UnwindError::UnknownCie {
+                            offset,
+                            cie: usize::MAX,
+                        })?;
+                let cie = frame
+                    .cies
+                    .iter()
+                    .find(|cie| cie.offset == cie_offset)
+                    .ok_or(UnwindError::UnknownCie {
+                        offset,
+                        cie: cie_offset,
+                    })?;
+                let fde = parse_fde(&mut r, offset, end, addr, cie)?;
+                frame.fdes.push(fde);
+            }
+            r.seek(end)?;
+        }
+        Ok(frame)
+    }
+
+    pub fn cie_of(&self, fde: &Fde) -> Option<&Cie> {
+        self.cies.iter().find(|cie| cie.offset == fde.cie)
+    }
+
+    /// the FDE describing `pc`.
+    pub fn fde_containing(&self, pc: Elf64Addr) -> Option<&Fde> {
+        self.fdes.iter().find(|fde| fde.contains(pc))
+    }
+}
+
+fn parse_cie(
+    r: &mut PayloadReader<'_>,
+    offset: usize,
+    end: usize,
+    addr: Elf64Addr,
+) -> Result<Cie, UnwindError> {
+    let version = r.u8()?;
+    let augmentation = r.cstr()?;
+    // 古いGCCの"eh"はポインタを持つ
+    if augmentation.contains("eh") {
+        r.skip(8)?;
+    }
+    let code_alignment_factor = r.uleb128()?;
+    let data_alignment_factor = r.sleb128()?;
+    let return_address_register = if version == 1 {
+        r.u8()? as u64
+    } else {
+        r.uleb128()?
+    };
+
+    let mut cie = Cie {
+        offset,
+        version,
+        augmentation: augmentation.clone(),
+        code_alignment_factor,
+        data_alignment_factor,
+        return_address_register,
+        fde_encoding: DW_EH_PE_ABSPTR,
+        lsda_encoding: None,
+        personality: None,
+        personality_encoding: None,
+        is_signal_frame: false,
+        instructions: Vec::new(),
+    };
+    if let Some(letters) = augmentation.strip_prefix('z') {
+        let data_len = r.uleb128()? as usize;
+        let data_end = r.position() + data_len;
+        for letter in letters.chars() {
+            match letter {
+                'L' => cie.lsda_encoding = Some(r.u8()?),
+                'P' => {
+                    let encoding = r.u8()?;
+                    cie.personality_encoding = Some(encoding);
+                    cie.personality = read_encoded(r, encoding, addr)?;
+                }
+                'R' => cie.fde_encoding = r.u8()?,
+                'S' => cie.is_signal_frame = true,
+                // 知らない文字以降はデータの長さで読み飛ばす
+                _ => break,
+            }
+        }
+        r.seek(data_end)?;
+    }
+    cie.instructions = r.bytes(end.saturating_sub(r.position()))?;
+    Ok(cie)
+}
+
+fn parse_fde(
+    r: &mut PayloadReader<'_>,
+    offset: usize,
+    end: usize,
+    addr: Elf64Addr,
+    cie: &Cie,
+) -> Result<Fde, UnwindError> {
+    let pc_begin = read_encoded(r, cie.fde_encoding, addr)?.unwrap_or_default();
+    // 範囲は形式だけに従う
+    let pc_range = read_encoded(r, cie.fde_encoding & 0x0f, 0)?.unwrap_or_default();
+    let mut lsda = None;
+    if cie.augmentation.starts_with('z') {
+        let data_len = r.uleb128()? as usize;
+        let data_end = r.position() + data_len;
+        if let Some(encoding) = cie.lsda_encoding {
+            lsda = read_encoded(r, encoding, addr)?.filter(|lsda| *lsda != 0);
+        }
+        r.seek(data_end)?;
+    }
+    Ok(Fde {
+        offset,
+        cie: cie.offset,
+        pc_begin,
+        pc_range,
+        lsda,
+        instructions: r.bytes(end.saturating_sub(r.position()))?,
+    })
+}
+
+/// read a pointer encoded by `DW_EH_PE_*` in a section placed at `addr`.
+/// `DW_EH_PE_omit` gives `None`, and a zero stays zero as the unwinder does.
+fn read_encoded(
+    r: &mut PayloadReader<'_>,
+    encoding: u8,
+    addr: Elf64Addr,
+) -> Result<Option<Elf64Addr>, UnwindError> {
+    if encoding == DW_EH_PE_OMIT {
+        return Ok(None);
+    }
+    let offset = r.position();
+    let unsupported = UnwindError::UnsupportedPointerEncoding { offset, encoding };
+    let value = match encoding & 0x0f {
+        DW_EH_PE_ABSPTR | DW_EH_PE_UDATA8 | DW_EH_PE_SDATA8 => r.u64()?,
+        DW_EH_PE_ULEB128 => r.uleb128()?,
+        DW_EH_PE_UDATA2 => r.u16()? as u64,
+        DW_EH_PE_UDATA4 => r.u32()? as u64,
+        DW_EH_PE_SLEB128 => r.sleb128()? as u64,
+        DW_EH_PE_SDATA2 => r.u16()? as i16 as u64,
+        DW_EH_PE_SDATA4 => r.u32()? as i32 as u64,
+        _ => return Err(unsupported),
+    };
+    if value == 0 {
+        return Ok(Some(0));
+    }
+    match encoding & 0x70 {
+        DW_EH_PE_ABSPTR => Ok(Some(value)),
+        DW_EH_PE_PCREL => Ok(Some(value.wrapping_add(addr + offset as u64))),
+        _ => Err(unsupported),
+    }
+}
+
+/// the size of the values in the format, `None` for LEB128.
+fn encoded_size(encoding: u8) -> Option<usize> {
+    match encoding & 0x0f {
+        DW_EH_PE_ABSPTR | DW_EH_PE_UDATA8 | DW_EH_PE_SDATA8 => Some(8),
+        DW_EH_PE_UDATA4 | DW_EH_PE_SDATA4 => Some(4),
+        DW_EH_PE_UDATA2 | DW_EH_PE_SDATA2 => Some(2),
+        _ => None,
+    }
+}
+
+/// A range of a function in the call-site table
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CallSite {
+    pub start: Elf64Addr,
+    pub length: Elf64Xword,
+    /// the landing pad, `None` if the exceptions are propagated to the caller
+    pub landing_pad: Option<Elf64Addr>,
+    /// 1 + the offset of the first action, 0 for cleanups only
+    pub action: u64,
+}
+
+impl CallSite {
+    pub fn end(&self) -> Elf64Addr {
+        self.start + self.length
+    }
+}
+
+/// The Language Specific Data Area of the C++ personality routine(`__gxx_personality_v0`)
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::{header, unwind};
+///
+/// // no type table, a call site at +4..+9 with a cleanup landing pad at +0xb
+/// let bytes = [0xff, 0xff, 0x01, 0x04, 0x04, 0x05, 0x0b, 0x00];
+/// let lsda = unwind::Lsda::parse(&bytes, 0x2190, 0x1199, header::Data::LSB2).unwrap();
+/// let site = lsda.call_sites[0];
+/// assert_eq!((0x119d, 0x11a2), (site.start, site.end()));
+/// assert_eq!(Some(0x11a4), site.landing_pad);
+/// assert_eq!(Vec::<i64>::new(), lsda.actions(&site).unwrap());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Lsda {
+    /// the address of the LSDA
+    pub address: Elf64Addr,
+    /// the base of the landing pads, the start of the function by default
+    pub lpstart: Elf64Addr,
+    pub ttype_encoding: u8,
+    /// the offset of the end of the type table from the LSDA, if the table exists
+    pub ttype_base: Option<usize>,
+    pub call_site_encoding: u8,
+    pub call_sites: Vec<CallSite>,
+    /// the offset of the action table from the LSDA
+    pub action_table: usize,
+    /// the bytes from the LSDA to the end of the section
+    pub bytes: Vec<u8>,
+    pub data: header::Data,
+}
+
+impl Lsda {
+    /// parse the LSDA at the start of `bytes`, placed at `addr` and describing the function at `func_start`.
+    pub fn parse(
+        bytes: &[u8],
+        addr: Elf64Addr,
+        func_start: Elf64Addr,
+        data: header::Data,
+    ) -> Result<Self, UnwindError> {
+        let mut r = PayloadReader::new(bytes, data);
+        let lpstart_encoding = r.u8()?;
+        let lpstart = read_encoded(&mut r, lpstart_encoding, addr)?.unwrap_or(func_start);
+
+        let ttype_encoding = r.u8()?;
+        let mut ttype_base = None;
+        if ttype_encoding != DW_EH_PE_OMIT {
+            let offset = r.uleb128()? as usize;
+            ttype_base = Some(r.position() + offset);
+        }
+
+        let call_site_encoding = r.u8()?;
+        let table_len = r.uleb128()? as usize;
+        let table_end = r.position() + table_len;
+        let mut call_sites = Vec::new();
+        while r.position() < table_end {
+            // 関数の先頭とLPStartからのオフセット
+            let start = read_encoded(&mut r, call_site_encoding & 0x0f, 0)?.unwrap_or_default();
+            let length = read_encoded(&mut r, call_site_encoding & 0x0f, 0)?.unwrap_or_default();
+            let landing_pad =
+                read_encoded(&mut r, call_site_encoding & 0x0f, 0)?.unwrap_or_default();
+            let action = r.uleb128()?;
+            call_sites.push(CallSite {
+                start: func_start + start,
+                length,
+                landing_pad: if landing_pad == 0 {
+                    None
+                } else {
+                    Some(lpstart + landing_pad)
+                },
+                action,
+            });
+        }
+
+        Ok(Self {
+            address: addr,
+            lpstart,
+            ttype_encoding,
+            ttype_base,
+            call_site_encoding,
+            call_sites,
+            action_table: table_end,
+            bytes: bytes.to_vec(),
+            data,
+        })
+    }
+
+    /// the type filters of the call site's actions in order.
+    /// A positive filter is an index into the type table, and a negative one is an exception specification.
+    pub fn actions(&self, site: &CallSite) -> Result<Vec<i64>, UnwindError> {
+        let mut filters = Vec::new();
+        if site.action == 0 {
+            return Ok(filters);
+        }
+        let mut r = PayloadReader::new(&self.bytes, self.data);
+        let mut offset = self.action_table + site.action as usize - 1;
+        loop {
+            r.seek(offset)?;
+            filters.push(r.sleb128()?);
+            let next_pos = r.position();
+            let next = r.sleb128()?;
+            if next == 0 {
+                return Ok(filters);
+            }
+            // 次のレコードは next フィールドからの相対位置
+            offset = (next_pos as i64 + next) as usize;
+            if filters.len() > self.bytes.len() {
+                return Err(ReadError::UnexpectedEnd { offset, needed: 0 }.into());
+            }
+        }
+    }
+
+    /// the `std::type_info` caught by a positive type filter, or the address of its pointer if the encoding is
+    /// `DW_EH_PE_indirect`. `None` is `catch (...)`.
+    pub fn type_info(&self, filter: i64) -> Result<Option<Elf64Addr>, UnwindError> {
+        let base = match self.ttype_base {
+            Some(base) if filter > 0 => base,
+            _ => return Ok(None),
+        };
+        let unsupported = UnwindError::UnsupportedPointerEncoding {
+            offset: base,
+            encoding: self.ttype_encoding,
+        };
+        let size = encoded_size(self.ttype_encoding).ok_or(unsupported)?;
+        let entry = base
+            .checked_sub(filter as usize * size)
+            .ok_or(ReadError::UnexpectedEnd {
+                offset: base,
+                needed: 0,
+            })?;
+        let mut r = PayloadReader::new(&self.bytes, self.data);
+        r.seek(entry)?;
+        let value = read_encoded(&mut r, self.ttype_encoding, self.address)?;
+        Ok(value.filter(|addr| *addr != 0))
+    }
+}
+
+/// A function with its unwind information and exception table
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ExceptionTable {
+    pub fde: Fde,
+    pub lsda: Lsda,
+}
+
+impl ExceptionTable {
+    /// the landing pads of the function, in the order of the call sites.
+    pub fn landing_pads(&self) -> Vec<Elf64Addr> {
+        let mut pads: Vec<Elf64Addr> = self
+            .lsda
+            .call_sites
+            .iter()
+            .filter_map(|site| site.landing_pad)
+            .collect();
+        pads.dedup();
+        pads
+    }
+}
+
+impl file::ELF64 {
+    /// parse `.eh_frame` if exists.
+    pub fn eh_frame(&self) -> Option<Result<EhFrame, UnwindError>> {
+        let sct = self.first_section_by(|sct| sct.name == EH_FRAME_SECTION)?;
+        Some(EhFrame::parse(
+            &sct.to_le_bytes(),
+            sct.header.sh_addr,
+            self.ehdr.get_data(),
+        ))
+    }
+
+    /// parse the LSDA of the FDE, which is usually in `.gcc_except_table`.
+    pub fn lsda(&self, fde: &unwind::Fde) -> Option<Result<Lsda, UnwindError>> {
+        let addr = fde.lsda?;
+        let sct = self.section_containing_vaddr(addr)?;
+        if sct.header.get_type() == section::Type::NoBits {
+            return None;
+        }
+        let bytes = sct.to_le_bytes();
+        let start = (addr - sct.header.sh_addr) as usize;
+        Some(Lsda::parse(
+            &bytes[start..],
+            addr,
+            fde.pc_begin,
+            self.ehdr.get_data(),
+        ))
+    }
+
+    /// the functions which have exception tables, i.e. the FDEs with LSDAs.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use elf_utilities::parser;
+    ///
+    /// let elf = parser::parse_elf64("tests/fixtures/exceptions").unwrap();
+    /// let tables = elf.exception_tables().unwrap();
+    /// // risky(), cleanup() and recover()
+    /// assert_eq!(3, tables.len());
+    /// let recover = &tables[2];
+    /// assert_eq!(2, recover.lsda.call_sites.len());
+    /// assert_eq!(1, recover.landing_pads().len());
+    /// ```
+    pub fn exception_tables(&self) -> Result<Vec<ExceptionTable>, UnwindError> {
+        let frame = match self.eh_frame() {
+            Some(frame) => frame?,
+            None => return Ok(Vec::new()),
+        };
+        let mut tables = Vec::new();
+        for fde in frame.fdes {
+            if let Some(lsda) = self.lsda(&fde) {
+                tables.push(ExceptionTable { lsda: lsda?, fde });
+            }
+        }
+        Ok(tables)
+    }
+}
+
+#[cfg(test)]
+mod unwind_tests {
+    use super::*;
+
+    #[test]
+    fn exception_tables_test() {
+        let elf = parser::parse_elf64("tests/fixtures/exceptions").unwrap();
+        let frame = elf.eh_frame().unwrap().unwrap();
+        let cie = frame
+            .cies
+            .iter()
+            .find(|cie| cie.augmentation == "zPLR")
+            .unwrap();
+        assert_eq!(Some(DW_EH_PE_PCREL | DW_EH_PE_SDATA4), cie.lsda_encoding);
+        assert_eq!(-8, cie.data_alignment_factor);
+        let personality = elf
+            .section_containing_vaddr(cie.personality.unwrap())
+            .unwrap();
+        // DW.ref.__gxx_personality_v0 を介する
+        assert_eq!(".data", personality.name);
+        assert_ne!(0, cie.personality_encoding.unwrap() & DW_EH_PE_INDIRECT);
+
+        let tables = elf.exception_tables().unwrap();
+        let address_of = |name: &str| {
+            let symtab = elf
+                .first_section_by(|sct| sct.header.get_type() == section::Type::SymTab)
+                .unwrap();
+            match &symtab.contents {
+                section::Contents64::Symbols(syms) => {
+                    syms.iter()
+                        .find(|sym| sym.symbol_name == name)
+                        .unwrap()
+                        .st_value
+                }
+                _ => unreachable!(),
+            }
+        };
+        let recover = tables
+            .iter()
+            .find(|table| table.fde.pc_begin == address_of("_Z7recoveri"))
+            .unwrap();
+        assert_eq!(recover.fde.lsda.unwrap(), recover.lsda.address,);
+        let site = recover.lsda.call_sites[0];
+        assert!(recover.fde.contains(site.start));
+        assert!(recover.fde.contains(site.landing_pad.unwrap()));
+        // catch (const std::invalid_argument &) の次に catch (...)
+        let filters = recover.lsda.actions(&site).unwrap();
+        assert_eq!(vec![1, 2], filters);
+        assert!(recover.lsda.type_info(1).unwrap().is_some());
+        assert_eq!(None, recover.lsda.type_info(2).unwrap());
+        // 2つ目の呼び出しは捕捉されない
+        assert_eq!(None, recover.lsda.call_sites[1].landing_pad);
+
+        // デストラクタを呼ぶだけのクリーンアップ
+        let cleanup = tables
+            .iter()
+            .find(|table| table.fde.pc_begin == address_of("_Z7cleanupiPi"))
+            .unwrap();
+        let site = cleanup
+            .lsda
+            .call_sites
+            .iter()
+            .find(|site| site.landing_pad.is_some())
+            .unwrap();
+        assert_eq!(0, site.action);
+        assert_eq!(None, cleanup.lsda.ttype_base);
+    }
+}
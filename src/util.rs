@@ -0,0 +1,270 @@
+//! Encoding primitives shared by DWARF-style sections.
+//!
+//! `.eh_frame`, `.debug_*` and the GNU notes encode their numbers by LEB128
+//! and prefix their units by an initial length which selects 32/64-bit DWARF.
+//! The decoders report errors with offsets relative to the given bytes.
+
+use crate::*;
+use section::ReadError;
+
+/// encode `value` as unsigned LEB128.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::util;
+///
+/// assert_eq!(vec![0xe5, 0x8e, 0x26], util::encode_uleb128(624485));
+/// assert_eq!(Ok((624485, 3)), util::decode_uleb128(&[0xe5, 0x8e, 0x26, 0xff]));
+/// ```
+pub fn encode_uleb128(mut value: u64) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            bytes.push(byte);
+            return bytes;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+/// encode `value` as signed LEB128.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::util;
+///
+/// assert_eq!(vec![0x80, 0x7f], util::encode_sleb128(-128));
+/// assert_eq!(Ok((-128, 2)), util::decode_sleb128(&[0x80, 0x7f]));
+/// ```
+pub fn encode_sleb128(mut value: i64) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        // 残りが符号ビットだけになったら終わり
+        let done = (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0);
+        if done {
+            bytes.push(byte);
+            return bytes;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+/// decode an unsigned LEB128 at the start of `bytes`, returning the value and its length.
+pub fn decode_uleb128(bytes: &[u8]) -> Result<(u64, usize), ReadError> {
+    let mut value = 0u64;
+    let mut shift = 0;
+    for (i, byte) in bytes.iter().enumerate() {
+        let bits = (byte & 0x7f) as u64;
+        // 64ビットからはみ出すビットが立っていたらエラー
+        if shift >= 64 || (shift == 63 && bits > 1) {
+            return Err(ReadError::LebOverflow { offset: 0 });
+        }
+        value |= bits << shift;
+        shift += 7;
+        if byte & 0x80 == 0 {
+            return Ok((value, i + 1));
+        }
+    }
+    Err(ReadError::UnexpectedEnd {
+        offset: bytes.len(),
+        needed: 1,
+    })
+}
+
+/// decode a signed LEB128 at the start of `bytes`, returning the value and its length.
+pub fn decode_sleb128(bytes: &[u8]) -> Result<(i64, usize), ReadError> {
+    let mut value = 0i64;
+    let mut shift = 0;
+    for (i, byte) in bytes.iter().enumerate() {
+        if shift >= 64 {
+            return Err(ReadError::LebOverflow { offset: 0 });
+        }
+        value |= ((byte & 0x7f) as i64) << shift;
+        shift += 7;
+        if byte & 0x80 == 0 {
+            // 最後のバイトの符号ビットで拡張する
+            if shift < 64 && byte & 0x40 != 0 {
+                value |= -1 << shift;
+            }
+            return Ok((value, i + 1));
+        }
+    }
+    Err(ReadError::UnexpectedEnd {
+        offset: bytes.len(),
+        needed: 1,
+    })
+}
+
+/// 32-bit or 64-bit DWARF, selected by the initial length of each unit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DwarfFormat {
+    Dwarf32,
+    Dwarf64,
+}
+
+impl DwarfFormat {
+    /// the size of offsets(and lengths) in the unit.
+    pub fn offset_size(&self) -> usize {
+        match self {
+            Self::Dwarf32 => 4,
+            Self::Dwarf64 => 8,
+        }
+    }
+}
+
+/// The length which starts a DWARF unit or a CIE/FDE
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct InitialLength {
+    /// the length of the unit, excluding the initial length itself
+    pub length: u64,
+    pub format: DwarfFormat,
+}
+
+impl InitialLength {
+    /// the escape which introduces a 64-bit length
+    pub const DWARF64_ESCAPE: u32 = 0xffff_ffff;
+    /// the start of the values reserved by DWARF
+    pub const RESERVED_START: u32 = 0xffff_fff0;
+
+    /// the size of the encoded initial length.
+    pub fn size(&self) -> usize {
+        match self.format {
+            DwarfFormat::Dwarf32 => 4,
+            DwarfFormat::Dwarf64 => 12,
+        }
+    }
+}
+
+/// decode an initial length at the start of `bytes`, returning it and its size.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::{header, util};
+///
+/// let bytes = util::encode_initial_length(0x20, util::DwarfFormat::Dwarf64, header::Data::LSB2);
+/// let (len, size) = util::decode_initial_length(&bytes, header::Data::LSB2).unwrap();
+/// assert_eq!(util::DwarfFormat::Dwarf64, len.format);
+/// assert_eq!((0x20, 12), (len.length, size));
+///
+/// // reserved values are rejected
+/// assert!(util::decode_initial_length(&[0xf0, 0xff, 0xff, 0xff], header::Data::LSB2).is_err());
+/// ```
+pub fn decode_initial_length(
+    bytes: &[u8],
+    data: header::Data,
+) -> Result<(InitialLength, usize), ReadError> {
+    let mut r = section::PayloadReader::new(bytes, data);
+    let word = r.u32()?;
+    let length = match word {
+        InitialLength::DWARF64_ESCAPE => InitialLength {
+            length: r.u64()?,
+            format: DwarfFormat::Dwarf64,
+        },
+        w if w >= InitialLength::RESERVED_START => {
+            return Err(ReadError::ReservedInitialLength {
+                offset: 0,
+                value: w,
+            })
+        }
+        w => InitialLength {
+            length: w as u64,
+            format: DwarfFormat::Dwarf32,
+        },
+    };
+    Ok((length, r.position()))
+}
+
+/// encode an initial length in `format`.
+/// `length` is truncated to 32 bits in `DwarfFormat::Dwarf32`.
+pub fn encode_initial_length(length: u64, format: DwarfFormat, data: header::Data) -> Vec<u8> {
+    let be = data == header::Data::MSB2;
+    let u32_bytes = |v: u32| {
+        if be {
+            v.to_be_bytes()
+        } else {
+            v.to_le_bytes()
+        }
+    };
+    match format {
+        DwarfFormat::Dwarf32 => u32_bytes(length as u32).to_vec(),
+        DwarfFormat::Dwarf64 => {
+            let mut bytes = u32_bytes(InitialLength::DWARF64_ESCAPE).to_vec();
+            if be {
+                bytes.extend_from_slice(&length.to_be_bytes());
+            } else {
+                bytes.extend_from_slice(&length.to_le_bytes());
+            }
+            bytes
+        }
+    }
+}
+
+#[cfg(test)]
+mod util_tests {
+    use super::*;
+
+    #[test]
+    fn leb128_round_trip_test() {
+        let unsigned = [
+            0,
+            1,
+            127,
+            128,
+            255,
+            0x3fff,
+            0x4000,
+            u32::MAX as u64,
+            u64::MAX,
+        ];
+        for v in unsigned.iter() {
+            let bytes = encode_uleb128(*v);
+            assert_eq!(Ok((*v, bytes.len())), decode_uleb128(&bytes));
+        }
+        let signed = [
+            0,
+            1,
+            -1,
+            63,
+            64,
+            -64,
+            -65,
+            i32::MIN as i64,
+            i64::MAX,
+            i64::MIN,
+        ];
+        for v in signed.iter() {
+            let bytes = encode_sleb128(*v);
+            assert_eq!(Ok((*v, bytes.len())), decode_sleb128(&bytes));
+        }
+        assert_eq!(vec![0x3f], encode_sleb128(63));
+        assert_eq!(vec![0xc0, 0x00], encode_sleb128(64));
+        assert_eq!(10, encode_uleb128(u64::MAX).len());
+    }
+
+    #[test]
+    fn initial_length_test() {
+        for data in [header::Data::LSB2, header::Data::MSB2].iter() {
+            for format in [DwarfFormat::Dwarf32, DwarfFormat::Dwarf64].iter() {
+                let bytes = encode_initial_length(0x1234, *format, *data);
+                let (len, size) = decode_initial_length(&bytes, *data).unwrap();
+                assert_eq!((0x1234, *format), (len.length, len.format));
+                assert_eq!(bytes.len(), size);
+                assert_eq!(len.size(), size);
+            }
+        }
+        assert_eq!(
+            Err(ReadError::UnexpectedEnd {
+                offset: 4,
+                needed: 8
+            }),
+            decode_initial_length(&[0xff; 6], header::Data::LSB2)
+        );
+    }
+}
@@ -0,0 +1,577 @@
+//! Checks which loaders and tools rely on, and repairs for them.
+//!
+//! `file::ELF64::validate()` checks that the file is consistent as a container,
+//! and this module checks the constraints on top of it, such as alignments
+//! and the rules which depend on `e_type`.
+
+use std::fmt;
+
+use crate::layout::{self, is_alloc, is_nobits, LayoutError};
+use crate::*;
+
+/// An alignment which a section or a segment violates
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum AlignmentIssue {
+    /// `sh_addralign` is neither 0 nor a power of two
+    InvalidSectionAlign { name: String, align: Elf64Xword },
+    /// `sh_addr` of the `SHF_ALLOC` section isn't a multiple of `sh_addralign`
+    SectionAddress {
+        name: String,
+        addr: Elf64Addr,
+        align: Elf64Xword,
+    },
+    /// `sh_offset` isn't a multiple of `sh_addralign`
+    SectionOffset {
+        name: String,
+        offset: Elf64Off,
+        align: Elf64Xword,
+    },
+    /// `p_align` is neither 0 nor a power of two
+    InvalidSegmentAlign { index: usize, align: Elf64Xword },
+    /// `p_offset` and `p_vaddr` aren't congruent modulo `p_align`, so the segment can't be mapped
+    SegmentCongruence {
+        index: usize,
+        offset: Elf64Off,
+        vaddr: Elf64Addr,
+        align: Elf64Xword,
+    },
+}
+
+impl fmt::Display for AlignmentIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidSectionAlign { name, align } => write!(
+                f,
+                "section `{}`: sh_addralign {:#x} is not a power of two",
+                name, align
+            ),
+            Self::SectionAddress { name, addr, align } => write!(
+                f,
+                "section `{}`: sh_addr {:#x} is not aligned to {:#x}",
+                name, addr, align
+            ),
+            Self::SectionOffset {
+                name,
+                offset,
+                align,
+            } => write!(
+                f,
+                "section `{}`: sh_offset {:#x} is not aligned to {:#x}",
+                name, offset, align
+            ),
+            Self::InvalidSegmentAlign { index, align } => write!(
+                f,
+                "segment {}: p_align {:#x} is not a power of two",
+                index, align
+            ),
+            Self::SegmentCongruence {
+                index,
+                offset,
+                vaddr,
+                align,
+            } => write!(
+                f,
+                "segment {}: p_offset {:#x} and p_vaddr {:#x} differ modulo {:#x}",
+                index, offset, vaddr, align
+            ),
+        }
+    }
+}
+
+/// find the sections and segments violating their alignments.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::{file, section, validation};
+///
+/// let mut elf = file::ELF64::default();
+/// elf.add_section(section::Section64::new(
+///     ".text".to_string(),
+///     section::ShdrPreparation64::default()
+///         .ty(section::Type::ProgBits)
+///         .flags([section::Flag::Alloc, section::Flag::ExecInstr].iter()),
+///     section::Contents64::Raw(vec![0xc3]),
+/// ));
+/// let mut data = section::Section64::new(
+///     ".data".to_string(),
+///     section::ShdrPreparation64::default()
+///         .ty(section::Type::ProgBits)
+///         .flags([section::Flag::Alloc, section::Flag::Write].iter()),
+///     section::Contents64::Raw(vec![0; 8]),
+/// );
+/// data.header.sh_addralign = 8;
+/// elf.add_section(data);
+///
+/// // add_section() packs the sections without padding
+/// let issues = validation::check_alignment(&elf);
+/// assert!(matches!(&issues[0], validation::AlignmentIssue::SectionOffset { name, .. } if name == ".data"));
+///
+/// validation::fix_alignment(&mut elf).unwrap();
+/// assert!(validation::check_alignment(&elf).is_empty());
+/// ```
+pub fn check_alignment(elf: &file::ELF64) -> Vec<AlignmentIssue> {
+    let mut issues = Vec::new();
+    for sct in elf.sections.iter().skip(1) {
+        let shdr = &sct.header;
+        let align = shdr.sh_addralign;
+        if align <= 1 {
+            continue;
+        }
+        if !align.is_power_of_two() {
+            issues.push(AlignmentIssue::InvalidSectionAlign {
+                name: sct.name.to_string(),
+                align,
+            });
+            continue;
+        }
+        if is_alloc(shdr) && shdr.sh_addr % align != 0 {
+            issues.push(AlignmentIssue::SectionAddress {
+                name: sct.name.to_string(),
+                addr: shdr.sh_addr,
+                align,
+            });
+        }
+        if !is_nobits(shdr) && shdr.sh_offset % align != 0 {
+            issues.push(AlignmentIssue::SectionOffset {
+                name: sct.name.to_string(),
+                offset: shdr.sh_offset,
+                align,
+            });
+        }
+    }
+
+    for (index, sgt) in elf.segments.iter().enumerate() {
+        let phdr = &sgt.header;
+        let align = phdr.p_align;
+        if align <= 1 {
+            continue;
+        }
+        if !align.is_power_of_two() {
+            issues.push(AlignmentIssue::InvalidSegmentAlign { index, align });
+        } else if phdr.p_offset % align != phdr.p_vaddr % align {
+            issues.push(AlignmentIssue::SegmentCongruence {
+                index,
+                offset: phdr.p_offset,
+                vaddr: phdr.p_vaddr,
+                align,
+            });
+        }
+    }
+    issues
+}
+
+/// repair the issues of `check_alignment()` by laying the file out again with `Layout::from_elf()`,
+/// and return the issues found before the repair.
+///
+/// The file is left as is if it has no issue.
+/// Otherwise invalid `sh_addralign` is rounded up to a power of two,
+/// `PT_LOAD` segments are regenerated, and the other segments are fitted to the sections they covered.
+/// The addresses of the sections may change, so repair the file before the code refers to them.
+pub fn fix_alignment(elf: &mut file::ELF64) -> Result<Vec<AlignmentIssue>, LayoutError> {
+    let issues = check_alignment(elf);
+    if issues.is_empty() {
+        return Ok(issues);
+    }
+
+    for sct in elf.sections.iter_mut() {
+        let align = sct.header.sh_addralign;
+        if align > 1 && !align.is_power_of_two() {
+            sct.header.sh_addralign = align.next_power_of_two();
+        }
+    }
+
+    // 再配置の前に，各セグメントが覆うセクションを覚えておく
+    let covered: Vec<Vec<usize>> = elf
+        .segments
+        .iter()
+        .map(|sgt| {
+            let phdr = &sgt.header;
+            (1..elf.sections.len())
+                .filter(|&i| {
+                    let shdr = &elf.sections[i].header;
+                    is_alloc(shdr)
+                        && shdr.sh_size != 0
+                        && phdr.p_vaddr <= shdr.sh_addr
+                        && shdr.sh_addr + shdr.sh_size <= phdr.p_vaddr + phdr.p_memsz
+                })
+                .collect()
+        })
+        .collect();
+    let refitted: Vec<(segment::Phdr64, Vec<usize>)> = elf
+        .segments
+        .iter()
+        .zip(covered)
+        .filter(|(sgt, shidxs)| {
+            !shidxs.is_empty()
+                && !matches!(
+                    sgt.header.get_type(),
+                    segment::Type::Load | segment::Type::Phdr | segment::Type::Note
+                )
+        })
+        .map(|(sgt, shidxs)| (sgt.header, shidxs))
+        .collect();
+
+    layout::Layout::from_elf(elf).apply(elf)?;
+
+    for (phdr, shidxs) in refitted {
+        if let Some(idx) = elf.segments.iter().position(|sgt| sgt.header == phdr) {
+            layout::fit_segment(elf, idx, &shidxs);
+        }
+    }
+    Ok(issues)
+}
+
+/// The rules a file is checked against, which usually follow `e_type`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Profile {
+    /// `ET_REL`: sections without segments or addresses
+    Relocatable,
+    /// `ET_EXEC`: segments loaded at fixed addresses and an entry point
+    Executable,
+    /// `ET_DYN`: shared objects and PIEs, which need `PT_DYNAMIC`
+    SharedObject,
+    /// `ET_CORE`: a memory image described by segments and notes
+    Core,
+    /// only the rules common to all types
+    Generic,
+}
+
+impl Profile {
+    /// the profile of `e_type`, `Generic` for the unknown types.
+    pub fn of(ty: header::Type) -> Self {
+        match ty {
+            header::Type::Rel => Self::Relocatable,
+            header::Type::Exec => Self::Executable,
+            header::Type::Dyn => Self::SharedObject,
+            header::Type::Core => Self::Core,
+            _ => Self::Generic,
+        }
+    }
+}
+
+/// A violation of the rules of a `Profile`
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ProfileIssue {
+    /// a relocatable file has a program header table
+    UnexpectedSegments,
+    /// a core file has sections other than the null section
+    UnexpectedSections,
+    /// a relocatable file has no section header table
+    NoSectionHeaders,
+    /// `e_entry` of a relocatable file isn't 0
+    UnexpectedEntry {
+        entry: Elf64Addr,
+    },
+    /// a `SHF_ALLOC` section of a relocatable file has an address
+    SectionAddress {
+        name: String,
+        addr: Elf64Addr,
+    },
+    NoLoadSegment,
+    /// `e_entry` isn't in an executable `PT_LOAD` segment
+    EntryNotExecutable {
+        entry: Elf64Addr,
+    },
+    /// a shared object has no `PT_DYNAMIC`
+    NoDynamic,
+    /// a core file has no `PT_NOTE`, where the registers are
+    NoNote,
+    /// `PT_PHDR` or `PT_INTERP` follows a `PT_LOAD`
+    SegmentAfterLoad {
+        index: usize,
+        ty: segment::Type,
+    },
+    /// the segment type must be unique
+    DuplicateSegment {
+        index: usize,
+        ty: segment::Type,
+    },
+    /// `PT_LOAD` segments aren't sorted by `p_vaddr`
+    UnsortedLoad {
+        index: usize,
+    },
+}
+
+impl fmt::Display for ProfileIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnexpectedSegments => write!(f, "a relocatable file has segments"),
+            Self::UnexpectedSections => write!(f, "a core file has sections"),
+            Self::NoSectionHeaders => write!(f, "the file has no section header table"),
+            Self::UnexpectedEntry { entry } => {
+                write!(f, "a relocatable file has entry point {:#x}", entry)
+            }
+            Self::SectionAddress { name, addr } => write!(
+                f,
+                "section `{}` of a relocatable file has address {:#x}",
+                name, addr
+            ),
+            Self::NoLoadSegment => write!(f, "the file has no PT_LOAD segment"),
+            Self::EntryNotExecutable { entry } => write!(
+                f,
+                "the entry point {:#x} isn't in an executable PT_LOAD segment",
+                entry
+            ),
+            Self::NoDynamic => write!(f, "the file has no PT_DYNAMIC segment"),
+            Self::NoNote => write!(f, "the file has no PT_NOTE segment"),
+            Self::SegmentAfterLoad { index, ty } => {
+                write!(f, "segment {}: {} follows a PT_LOAD segment", index, ty)
+            }
+            Self::DuplicateSegment { index, ty } => {
+                write!(f, "segment {}: the file has another {} segment", index, ty)
+            }
+            Self::UnsortedLoad { index } => write!(
+                f,
+                "segment {}: PT_LOAD segments are not sorted by address",
+                index
+            ),
+        }
+    }
+}
+
+/// check the rules of the profile selected by `e_type`.
+///
+/// # Examples
+///
+/// ```
+/// use elf_utilities::{builder, header, validation};
+///
+/// let main = builder::ExportedFunction::new("main", vec![0xc3]);
+/// let mut elf = builder::ExecutableWriter::new()
+///     .function(main)
+///     .start_stub(builder::start_stub("main"))
+///     .build()
+///     .unwrap();
+/// assert!(validation::check_profile(&elf).is_empty());
+///
+/// // an executable relabeled as a relocatable file
+/// elf.ehdr.set_elf_type(header::Type::Rel);
+/// let issues = validation::check_profile(&elf);
+/// assert!(issues.contains(&validation::ProfileIssue::UnexpectedSegments));
+///
+/// // unless the file is meant to be odd
+/// let issues = validation::check_profile_as(&elf, validation::Profile::Executable);
+/// assert!(issues.is_empty());
+/// ```
+pub fn check_profile(elf: &file::ELF64) -> Vec<ProfileIssue> {
+    check_profile_as(elf, Profile::of(elf.ehdr.get_type()))
+}
+
+/// check the rules of the profile regardless of `e_type`, for the files which are intentionally odd.
+pub fn check_profile_as(elf: &file::ELF64, profile: Profile) -> Vec<ProfileIssue> {
+    let mut issues = common_issues(elf);
+    match profile {
+        Profile::Relocatable => {
+            if !elf.segments.is_empty() {
+                issues.push(ProfileIssue::UnexpectedSegments);
+            }
+            if elf.ehdr.e_shoff == 0 {
+                issues.push(ProfileIssue::NoSectionHeaders);
+            }
+            if elf.ehdr.e_entry != 0 {
+                issues.push(ProfileIssue::UnexpectedEntry {
+                    entry: elf.ehdr.e_entry,
+                });
+            }
+            for sct in elf.sections.iter() {
+                if is_alloc(&sct.header) && sct.header.sh_addr != 0 {
+                    issues.push(ProfileIssue::SectionAddress {
+                        name: sct.name.to_string(),
+                        addr: sct.header.sh_addr,
+                    });
+                }
+            }
+        }
+        Profile::Executable | Profile::SharedObject => {
+            let loads: Vec<&segment::Phdr64> = elf
+                .segments
+                .iter()
+                .map(|sgt| &sgt.header)
+                .filter(|phdr| phdr.get_type() == segment::Type::Load)
+                .collect();
+            if loads.is_empty() {
+                issues.push(ProfileIssue::NoLoadSegment);
+            }
+            // 共有オブジェクトはエントリポイントを持たなくてよい
+            let entry = elf.ehdr.e_entry;
+            let entry_mapped = loads.iter().any(|phdr| {
+                phdr.p_flags & Elf64Word::from(segment::Flag::X) != 0
+                    && phdr.p_vaddr <= entry
+                    && entry < phdr.p_vaddr + phdr.p_memsz
+            });
+            if (profile == Profile::Executable || entry != 0) && !entry_mapped {
+                issues.push(ProfileIssue::EntryNotExecutable { entry });
+            }
+            let has_dynamic = elf
+                .segments
+                .iter()
+                .any(|sgt| sgt.header.get_type() == segment::Type::Dynamic);
+            if profile == Profile::SharedObject && !has_dynamic {
+                issues.push(ProfileIssue::NoDynamic);
+            }
+        }
+        Profile::Core => {
+            if elf.sections.len() > 1 {
+                issues.push(ProfileIssue::UnexpectedSections);
+            }
+            let has_note = elf
+                .segments
+                .iter()
+                .any(|sgt| sgt.header.get_type() == segment::Type::Note);
+            if !has_note {
+                issues.push(ProfileIssue::NoNote);
+            }
+        }
+        Profile::Generic => {}
+    }
+    issues
+}
+
+/// the rules on the program header table which every type follows.
+fn common_issues(elf: &file::ELF64) -> Vec<ProfileIssue> {
+    let mut issues = Vec::new();
+    let mut seen_load = false;
+    let mut last_vaddr = 0;
+    for (index, sgt) in elf.segments.iter().enumerate() {
+        let ty = sgt.header.get_type();
+        match ty {
+            segment::Type::Load => {
+                if seen_load && sgt.header.p_vaddr < last_vaddr {
+                    issues.push(ProfileIssue::UnsortedLoad { index });
+                }
+                seen_load = true;
+                last_vaddr = sgt.header.p_vaddr;
+            }
+            segment::Type::Phdr | segment::Type::Interp if seen_load => {
+                issues.push(ProfileIssue::SegmentAfterLoad { index, ty });
+            }
+            _ => {}
+        }
+        let unique = matches!(
+            ty,
+            segment::Type::Phdr
+                | segment::Type::Interp
+                | segment::Type::Dynamic
+                | segment::Type::GNUStack
+                | segment::Type::GNURelRO
+                | segment::Type::GNUEHFrame
+        );
+        if unique
+            && elf.segments[..index]
+                .iter()
+                .any(|prev| prev.header.get_type() == ty)
+        {
+            issues.push(ProfileIssue::DuplicateSegment { index, ty });
+        }
+    }
+    issues
+}
+
+/// A problem in a section whose type is registered in `section::TypeRegistry`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RegisteredTypeIssue {
+    /// the decoder failed
+    Undecodable {
+        shidx: usize,
+        ty: String,
+        error: section::ReadError,
+    },
+    /// the decoded contents reported a problem
+    Invalid {
+        shidx: usize,
+        ty: String,
+        message: String,
+    },
+}
+
+impl fmt::Display for RegisteredTypeIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Undecodable { shidx, ty, error } => {
+                write!(
+                    f,
+                    "section [{}] ({}) can't be decoded: {}",
+                    shidx, ty, error
+                )
+            }
+            Self::Invalid { shidx, ty, message } => {
+                write!(f, "section [{}] ({}): {}", shidx, ty, message)
+            }
+        }
+    }
+}
+
+/// decode the sections whose types are registered and collect the problems their decoders find.
+///
+/// # Examples
+///
+/// ```
+/// use std::any::Any;
+/// use std::fmt;
+///
+/// use elf_utilities::{file, section, validation};
+///
+/// #[derive(Debug)]
+/// struct Version(u32);
+///
+/// impl fmt::Display for Version {
+///     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+///         write!(f, "version {}", self.0)
+///     }
+/// }
+///
+/// impl section::CustomContents for Version {
+///     fn validate(&self, _shdr: &section::Shdr64) -> Vec<String> {
+///         if self.0 > 2 {
+///             vec![format!("unknown version {}", self.0)]
+///         } else {
+///             Vec::new()
+///         }
+///     }
+///     fn as_any(&self) -> &dyn Any {
+///         self
+///     }
+/// }
+///
+/// fn decode(
+///     _shdr: &section::Shdr64,
+///     mut r: section::PayloadReader<'_>,
+/// ) -> section::Decoded {
+///     Ok(Box::new(Version(r.u32()?)))
+/// }
+///
+/// let registry = section::TypeRegistry::new().register(0x8000_0000..=0x8000_0000, "ACME_VERSION", decode);
+/// let mut elf = file::ELF64::default();
+/// elf.add_section(section::Section64::new(
+///     ".acme.version".to_string(),
+///     section::ShdrPreparation64::default().ty(section::Type::Any(0x8000_0000)),
+///     section::Contents64::Raw(vec![3, 0, 0, 0]),
+/// ));
+///
+/// let issues = validation::check_registered_types(&elf, &registry);
+/// assert_eq!("section [1] (ACME_VERSION): unknown version 3", issues[0].to_string());
+/// ```
+pub fn check_registered_types(
+    elf: &file::ELF64,
+    registry: &section::TypeRegistry,
+) -> Vec<RegisteredTypeIssue> {
+    let mut issues = Vec::new();
+    for (shidx, decoded) in elf.decode_registered(registry) {
+        let shdr = &elf.sections[shidx].header;
+        let ty = registry.type_name(shdr.get_type());
+        match decoded {
+            Ok(contents) => {
+                for message in contents.validate(shdr) {
+                    issues.push(RegisteredTypeIssue::Invalid {
+                        shidx,
+                        ty: ty.clone(),
+                        message,
+                    });
+                }
+            }
+            Err(error) => issues.push(RegisteredTypeIssue::Undecodable { shidx, ty, error }),
+        }
+    }
+    issues
+}
@@ -0,0 +1,126 @@
+#![cfg(all(feature = "cli", unix))]
+
+mod tests {
+    use std::process::Command;
+
+    use elf_utilities::builder;
+
+    fn elfutil() -> Command {
+        Command::new(env!("CARGO_BIN_EXE_elfutil"))
+    }
+
+    fn write_answer_executable(name: &str) -> std::path::PathBuf {
+        use std::os::unix::fs::PermissionsExt;
+
+        let main = builder::ExportedFunction::new("main", vec![0xb8, 0x2a, 0x00, 0x00, 0x00, 0xc3]);
+        let f = builder::ExecutableWriter::new()
+            .function(main)
+            .start_stub(builder::start_stub("main"))
+            .build()
+            .unwrap();
+
+        let path = std::env::temp_dir().join(name);
+        std::fs::write(&path, f.to_le_bytes()).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    #[test]
+    fn json_output_test() {
+        let path = write_answer_executable("elf_utilities_cli_json");
+
+        let output = elfutil()
+            .arg("header")
+            .arg(&path)
+            .arg("--json")
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let header: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!("Exec", header["type"]);
+
+        let output = elfutil()
+            .arg("symbols")
+            .arg(&path)
+            .arg("--json")
+            .output()
+            .unwrap();
+        let symbols: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert!(symbols
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|sym| sym["name"] == "main" && sym["table"] == ".symtab"));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    fn strip_and_diff_test() {
+        let path = write_answer_executable("elf_utilities_cli_strip");
+        let stripped = std::env::temp_dir().join("elf_utilities_cli_stripped");
+
+        let status = elfutil()
+            .arg("strip")
+            .arg(&path)
+            .arg("-o")
+            .arg(&stripped)
+            .status()
+            .unwrap();
+        assert!(status.success());
+        assert_eq!(Some(42), Command::new(&stripped).status().unwrap().code());
+
+        // 差分があれば終了ステータスは1
+        let output = elfutil()
+            .arg("diff")
+            .arg(&path)
+            .arg(&stripped)
+            .arg("--json")
+            .output()
+            .unwrap();
+        assert_eq!(Some(1), output.status.code());
+        let diffs: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert!(diffs
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|d| d["SectionRemoved"]["name"] == ".symtab"));
+
+        let status = elfutil()
+            .arg("diff")
+            .arg(&path)
+            .arg(&path)
+            .status()
+            .unwrap();
+        assert_eq!(Some(0), status.code());
+
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&stripped).unwrap();
+    }
+
+    #[test]
+    fn functions_json_test() {
+        let output = elfutil()
+            .arg("functions")
+            .arg("tests/fixtures/exceptions.o")
+            .arg("--json")
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let facts: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        let recover = facts
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|f| f["name"] == "_Z7recoveri")
+            .unwrap();
+        assert_eq!(true, recover["exception_table"]);
+        assert_eq!(1, recover["landing_pads"].as_array().unwrap().len());
+        assert!(recover["calls"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|call| call["target"] == "_Z5riskyi"));
+    }
+}
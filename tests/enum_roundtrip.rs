@@ -0,0 +1,87 @@
+mod tests {
+    use elf_utilities::{dynamic, header, section, segment, symbol, Elf64Word, Elf64Xword};
+
+    /// 既知の値の周辺と，OS/プロセッサ固有の範囲の境界
+    fn word_samples() -> Vec<Elf64Word> {
+        let mut samples: Vec<Elf64Word> = (0..64).collect();
+        for base in [
+            0x60000000, 0x6474e550, 0x6fff4c00, 0x6ffffff0, 0x70000000, 0x80000000,
+        ] {
+            samples.extend(base..base + 16);
+        }
+        samples.extend([0x6fffffff, 0x7fffffff, Elf64Word::MAX]);
+        samples
+    }
+
+    #[test]
+    fn header_roundtrip_test() {
+        for v in 0..=u8::MAX {
+            assert_eq!(v, header::Class::from(v).to_identifier());
+            assert_eq!(v, header::Data::from(v).to_identifier());
+            assert_eq!(v, header::Version::from(v).to_identifier());
+            assert_eq!(v, header::OSABI::from(v).to_identifier());
+        }
+        for v in 0..=u16::MAX {
+            assert_eq!(v, header::Type::from(v).to_bytes());
+            assert_eq!(v, header::Machine::from(v).to_bytes());
+        }
+        for v in word_samples() {
+            assert_eq!(v, header::Version::from(v).to_object_version());
+        }
+
+        let bytes = [
+            0x7f, 0x45, 0x4c, 0x46, 0x09, 0x08, 0x07, 0x06, 0x05, 0x04, 0x03, 0x02, 0x01, 0xff,
+            0xfe, 0xfd,
+        ];
+        assert_eq!(bytes, header::Ident::from(bytes).to_bytes());
+    }
+
+    #[test]
+    fn section_roundtrip_test() {
+        for v in word_samples() {
+            assert_eq!(v, Elf64Word::from(section::Type::from(v)));
+        }
+        for i in 0..64 {
+            let bit: Elf64Xword = 1 << i;
+            assert_eq!(bit, Elf64Xword::from(section::Flag::from(bit)));
+        }
+    }
+
+    #[test]
+    fn segment_roundtrip_test() {
+        for v in word_samples() {
+            assert_eq!(v, segment::Type::from(v).to_bytes());
+        }
+        for i in 0..32 {
+            let bit: Elf64Word = 1 << i;
+            assert_eq!(bit, Elf64Word::from(segment::Flag::from(bit)));
+        }
+    }
+
+    #[test]
+    fn symbol_roundtrip_test() {
+        for v in 0..=u8::MAX {
+            assert_eq!(v, symbol::Bind::from(v).to_byte());
+            assert_eq!(v, symbol::Type::from(v).to_byte());
+            assert_eq!(v, symbol::Visibility::from(v).to_byte());
+        }
+    }
+
+    #[test]
+    fn dynamic_roundtrip_test() {
+        let mut samples: Vec<i64> = word_samples().into_iter().map(|v| v as i64).collect();
+        for base in [0x6000000d, 0x6ffff000, 0x6ffffdf5, 0x6ffffef5] {
+            samples.extend(base..base + 16);
+        }
+        samples.extend([-1, i64::MIN, i64::MAX]);
+        for v in samples {
+            assert_eq!(v, dynamic::EntryType::from(v).to_bytes());
+        }
+
+        for i in 0..64 {
+            let bit = 1 << i;
+            assert_eq!(bit, dynamic::Flag::from_def(bit).to_bytes());
+            assert_eq!(bit, dynamic::Flag::from_1(bit).to_bytes());
+        }
+    }
+}
@@ -0,0 +1,386 @@
+//! Table-driven parse tests over tests/fixtures.
+//! 新しいアーキテクチャを追加するときは tests/fixtures/README.md を参照
+
+mod tests {
+    use elf_utilities::{file, header, parser, section, transform, validation};
+
+    /// expected properties of a fixture.
+    struct Fixture {
+        path: &'static str,
+        class: header::Class,
+        machine: u16,
+        ty: header::Type,
+        /// sections which must exist with the type.
+        sections: &'static [(&'static str, section::Type)],
+        /// defined symbols and their section names.
+        symbols: &'static [(&'static str, &'static str)],
+        undefined: &'static [&'static str],
+        /// relocation sections and their number of entries.
+        relocations: &'static [(&'static str, usize)],
+    }
+
+    const RELA_OBJECT_SECTIONS: &[(&str, section::Type)] = &[
+        (".text", section::Type::ProgBits),
+        (".rela.text", section::Type::Rela),
+        (".data", section::Type::ProgBits),
+        (".rela.data", section::Type::Rela),
+        (".bss", section::Type::NoBits),
+        (".symtab", section::Type::SymTab),
+        (".strtab", section::Type::StrTab),
+    ];
+    const REL_OBJECT_SECTIONS: &[(&str, section::Type)] = &[
+        (".text", section::Type::ProgBits),
+        (".rel.text", section::Type::Rel),
+        (".data", section::Type::ProgBits),
+        (".rel.data", section::Type::Rel),
+        (".bss", section::Type::NoBits),
+        (".symtab", section::Type::SymTab),
+        (".strtab", section::Type::StrTab),
+    ];
+    const OBJECT_SYMBOLS: &[(&str, &str)] =
+        &[("answer", ".text"), ("value", ".data"), ("buffer", ".bss")];
+    const OBJECT_UNDEFINED: &[&str] = &["external"];
+    const RELA_OBJECT_RELOCATIONS: &[(&str, usize)] = &[(".rela.text", 1), (".rela.data", 1)];
+    const REL_OBJECT_RELOCATIONS: &[(&str, usize)] = &[(".rel.text", 1), (".rel.data", 1)];
+
+    const fn rela_object(path: &'static str, class: header::Class, machine: u16) -> Fixture {
+        Fixture {
+            path,
+            class,
+            machine,
+            ty: header::Type::Rel,
+            sections: RELA_OBJECT_SECTIONS,
+            symbols: OBJECT_SYMBOLS,
+            undefined: OBJECT_UNDEFINED,
+            relocations: RELA_OBJECT_RELOCATIONS,
+        }
+    }
+
+    const fn rel_object(path: &'static str, class: header::Class, machine: u16) -> Fixture {
+        Fixture {
+            path,
+            class,
+            machine,
+            ty: header::Type::Rel,
+            sections: REL_OBJECT_SECTIONS,
+            symbols: OBJECT_SYMBOLS,
+            undefined: OBJECT_UNDEFINED,
+            relocations: REL_OBJECT_RELOCATIONS,
+        }
+    }
+
+    const FIXTURES: &[Fixture] = &[
+        rela_object("tests/fixtures/x86_64.o", header::Class::Bit64, 62),
+        rel_object("tests/fixtures/i386.o", header::Class::Bit32, 3),
+        rela_object("tests/fixtures/aarch64.o", header::Class::Bit64, 183),
+        rel_object("tests/fixtures/arm.o", header::Class::Bit32, 40),
+        rela_object("tests/fixtures/riscv64.o", header::Class::Bit64, 243),
+        rela_object("tests/fixtures/riscv32.o", header::Class::Bit32, 243),
+        rela_object("tests/fixtures/mips64el.o", header::Class::Bit64, 8),
+        rel_object("tests/fixtures/mipsel.o", header::Class::Bit32, 8),
+        rela_object("tests/fixtures/ppc64le.o", header::Class::Bit64, 21),
+        Fixture {
+            path: "src/parser/testdata/sample",
+            class: header::Class::Bit64,
+            machine: 62,
+            ty: header::Type::Dyn,
+            sections: &[
+                (".text", section::Type::ProgBits),
+                (".rela.dyn", section::Type::Rela),
+                (".dynamic", section::Type::Dynamic),
+                (".symtab", section::Type::SymTab),
+            ],
+            symbols: &[("main", ".text"), ("_start", ".text")],
+            undefined: &[],
+            relocations: &[(".rela.dyn", 8)],
+        },
+        Fixture {
+            path: "src/parser/testdata/32bit",
+            class: header::Class::Bit32,
+            machine: 3,
+            ty: header::Type::Dyn,
+            sections: &[
+                (".text", section::Type::ProgBits),
+                (".rel.dyn", section::Type::Rel),
+                (".rel.plt", section::Type::Rel),
+                (".dynamic", section::Type::Dynamic),
+                (".symtab", section::Type::SymTab),
+            ],
+            symbols: &[("main", ".text"), ("_start", ".text")],
+            undefined: &[],
+            relocations: &[(".rel.dyn", 8), (".rel.plt", 2)],
+        },
+    ];
+
+    /// class-independent view of a parsed file.
+    struct Summary {
+        class: header::Class,
+        machine: u16,
+        ty: header::Type,
+        /// (name, type, sh_size, sh_entsize)
+        sections: Vec<(String, section::Type, u64, u64)>,
+        /// (name, st_shndx) in .symtab
+        symbols: Vec<(String, u16)>,
+    }
+
+    impl Summary {
+        fn section_index(&self, name: &str) -> Option<usize> {
+            self.sections.iter().position(|s| s.0 == name)
+        }
+    }
+
+    fn summarize(f: &file::ELF) -> Summary {
+        match f {
+            file::ELF::ELF64(f) => Summary {
+                class: f.ehdr.get_class(),
+                machine: f.ehdr.e_machine,
+                ty: f.ehdr.get_type(),
+                sections: f
+                    .sections
+                    .iter()
+                    .map(|s| {
+                        (
+                            s.name.to_string(),
+                            s.header.get_type(),
+                            s.header.sh_size,
+                            s.header.sh_entsize,
+                        )
+                    })
+                    .collect(),
+                symbols: match f.first_section_by(|s| s.name == ".symtab") {
+                    Some(section::Section64 {
+                        contents: section::Contents64::Symbols(syms),
+                        ..
+                    }) => syms
+                        .iter()
+                        .map(|sym| (sym.symbol_name.to_string(), sym.st_shndx))
+                        .collect(),
+                    _ => Vec::new(),
+                },
+            },
+            file::ELF::ELF32(f) => Summary {
+                class: f.ehdr.get_class(),
+                machine: f.ehdr.e_machine,
+                ty: f.ehdr.get_type(),
+                sections: f
+                    .sections
+                    .iter()
+                    .map(|s| {
+                        (
+                            s.name.clone(),
+                            s.header.get_type(),
+                            s.header.sh_size as u64,
+                            s.header.sh_entsize as u64,
+                        )
+                    })
+                    .collect(),
+                symbols: match f.sections.iter().find(|s| s.name == ".symtab") {
+                    Some(section::Section32 {
+                        contents: section::Contents32::Symbols(syms),
+                        ..
+                    }) => syms
+                        .iter()
+                        .map(|sym| (sym.symbol_name.clone(), sym.st_shndx))
+                        .collect(),
+                    _ => Vec::new(),
+                },
+            },
+        }
+    }
+
+    fn fixture_path(path: &str) -> String {
+        format!("{}/{}", env!("CARGO_MANIFEST_DIR"), path)
+    }
+
+    fn check(fixture: &Fixture, summary: &Summary) {
+        let path = fixture.path;
+        assert_eq!(fixture.class, summary.class, "{}", path);
+        assert_eq!(fixture.machine, summary.machine, "{}", path);
+        assert_eq!(fixture.ty, summary.ty, "{}", path);
+
+        for (name, ty) in fixture.sections {
+            let idx = summary
+                .section_index(name)
+                .unwrap_or_else(|| panic!("{}: {} not found", path, name));
+            assert_eq!(*ty, summary.sections[idx].1, "{}: {}", path, name);
+        }
+
+        for (name, section_name) in fixture.symbols {
+            let (_, shndx) = summary
+                .symbols
+                .iter()
+                .find(|sym| sym.0 == *name)
+                .unwrap_or_else(|| panic!("{}: symbol {} not found", path, name));
+            assert_eq!(
+                summary.section_index(section_name),
+                Some(*shndx as usize),
+                "{}: {}",
+                path,
+                name
+            );
+        }
+
+        for name in fixture.undefined {
+            assert!(
+                summary
+                    .symbols
+                    .iter()
+                    .any(|sym| sym.0 == *name && sym.1 == 0),
+                "{}: undefined symbol {} not found",
+                path,
+                name
+            );
+        }
+
+        for (name, count) in fixture.relocations {
+            let idx = summary
+                .section_index(name)
+                .unwrap_or_else(|| panic!("{}: {} not found", path, name));
+            let (_, _, size, entsize) = summary.sections[idx];
+            assert_ne!(0, entsize, "{}: {}", path, name);
+            assert_eq!(*count as u64, size / entsize, "{}: {}", path, name);
+        }
+    }
+
+    #[test]
+    fn parse_fixtures_test() {
+        for fixture in FIXTURES {
+            let f = parser::parse_elf(&fixture_path(fixture.path))
+                .unwrap_or_else(|e| panic!("{}: {}", fixture.path, e));
+            check(fixture, &summarize(&f));
+        }
+    }
+
+    #[test]
+    fn reparse_fixtures_test() {
+        for fixture in FIXTURES.iter().filter(|f| f.ty == header::Type::Rel) {
+            let bytes = match parser::parse_elf(&fixture_path(fixture.path)).unwrap() {
+                file::ELF::ELF64(f) => f.to_le_bytes(),
+                file::ELF::ELF32(f) => f.to_le_bytes(),
+            };
+            let reparsed = reparse(fixture, &bytes);
+            check(fixture, &summarize(&reparsed));
+        }
+    }
+
+    #[test]
+    fn condition_elf32_fixtures_test() {
+        for fixture in FIXTURES.iter().filter(|f| f.class == header::Class::Bit32) {
+            let path = fixture_path(fixture.path);
+            let mut f = parser::parse_elf32(&path).unwrap();
+            // 元のファイルと同じ位置に書き出される
+            assert_eq!(
+                std::fs::read(&path).unwrap(),
+                f.to_le_bytes(),
+                "{}",
+                fixture.path
+            );
+
+            if fixture.ty == header::Type::Rel {
+                f.condition();
+                check(fixture, &summarize(&reparse(fixture, &f.to_le_bytes())));
+            }
+        }
+    }
+
+    #[test]
+    fn condition_elf64_fixtures_test() {
+        for fixture in FIXTURES
+            .iter()
+            .filter(|f| f.class == header::Class::Bit64 && f.ty == header::Type::Rel)
+        {
+            let mut f = parser::parse_elf64(&fixture_path(fixture.path)).unwrap();
+            // LLVMのオブジェクトは.strtabをセクション名とシンボル名で共有する
+            f.condition();
+            assert_eq!(0, f.ehdr.e_phoff, "{}", fixture.path);
+            check(fixture, &summarize(&reparse(fixture, &f.to_le_bytes())));
+        }
+    }
+
+    #[test]
+    fn validate_fixtures_test() {
+        for fixture in FIXTURES.iter().filter(|f| f.class == header::Class::Bit64) {
+            let path = fixture_path(fixture.path);
+            let f = parser::parse_elf64(&path).unwrap();
+            assert_eq!(Ok(()), f.validate(), "{}", fixture.path);
+            assert_eq!(
+                Vec::<validation::AlignmentIssue>::new(),
+                validation::check_alignment(&f),
+                "{}",
+                fixture.path
+            );
+            assert_eq!(
+                Vec::<validation::ProfileIssue>::new(),
+                validation::check_profile(&f),
+                "{}",
+                fixture.path
+            );
+
+            let stats = f.stats();
+            let relocations: usize = fixture.relocations.iter().map(|(_, n)| n).sum();
+            assert_eq!(relocations, stats.relocations, "{}", fixture.path);
+            let size = std::fs::metadata(&path).unwrap().len();
+            assert_eq!(size, stats.file_size, "{}", fixture.path);
+        }
+    }
+
+    #[test]
+    fn convert_fixtures_test() {
+        let target = transform::Target::new(header::Class::Bit32, header::Data::LSB2);
+        for fixture in FIXTURES.iter().filter(|f| f.class == header::Class::Bit64) {
+            let f = parser::parse_elf64(&fixture_path(fixture.path)).unwrap();
+            let conversion = transform::convert(&f, target).unwrap();
+            // x86_64以外の再配置タイプは8bitに収まらない
+            assert!(
+                fixture.machine != 62
+                    || conversion
+                        .losses
+                        .iter()
+                        .all(|l| !matches!(l, transform::Loss::Truncated { .. })),
+                "{}: {:?}",
+                fixture.path,
+                conversion.losses
+            );
+
+            // クラス以外は変換前と同じ
+            let expected = Fixture {
+                class: header::Class::Bit32,
+                ..*fixture
+            };
+            let converted = reparse(fixture, &conversion.bytes);
+            check(&expected, &summarize(&converted));
+        }
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn yaml_fixtures_test() {
+        for fixture in FIXTURES.iter().filter(|f| f.class == header::Class::Bit64) {
+            let path = fixture_path(fixture.path);
+            let f = parser::parse_elf64(&path).unwrap();
+            let yaml = transform::to_yaml(&f);
+            let generated =
+                transform::from_yaml(&yaml).unwrap_or_else(|e| panic!("{}: {}", fixture.path, e));
+            // YAMLを経由しても同じファイルになる
+            assert_eq!(
+                std::fs::read(&path).unwrap(),
+                generated.to_le_bytes(),
+                "{}",
+                fixture.path
+            );
+        }
+    }
+
+    fn reparse(fixture: &Fixture, bytes: &[u8]) -> file::ELF {
+        let path = std::env::temp_dir().join(format!(
+            "elf_utilities_fixture_{}_{}_{}",
+            std::process::id(),
+            bytes.len(),
+            fixture.path.rsplit('/').next().unwrap()
+        ));
+        std::fs::write(&path, bytes).unwrap();
+        let reparsed = parser::parse_elf(path.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        reparsed
+    }
+}
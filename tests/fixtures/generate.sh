@@ -0,0 +1,36 @@
+#!/bin/sh
+# Regenerate the fixtures from src/*.
+# Requires llvm-mc and llc(LLVM 14 or later) and gcc/g++ for x86_64.
+set -eu
+cd "$(dirname "$0")"
+
+assemble() {
+    llvm-mc -filetype=obj -triple "$2" -o "$1.o" "src/$1.s"
+}
+
+assemble x86_64 x86_64-linux-gnu
+assemble i386 i386-linux-gnu
+assemble aarch64 aarch64-linux-gnu
+assemble arm armv7-linux-gnueabihf
+assemble riscv64 riscv64-linux-gnu
+assemble riscv32 riscv32-linux-gnu
+assemble mips64el mips64el-linux-gnuabi64
+assemble mipsel mipsel-linux-gnu
+assemble ppc64le powerpc64le-linux-gnu
+
+# a BPF object with BTF, compiled from IR since clang isn't required.
+llc -march=bpfel -filetype=obj -o bpf.o src/bpf.ll
+
+# a dynamically linked executable for the transformations of linked files.
+gcc -O0 -fno-builtin -fPIE -pie -o imports src/imports.c
+
+# an executable with USDT probes, written without <sys/sdt.h>.
+gcc -O1 -fPIE -pie -o usdt src/usdt.c
+
+# a C++ executable with exception tables in .gcc_except_table.
+g++ -O1 -fPIE -pie -o exceptions src/exceptions.cc
+g++ -O1 -c -o exceptions.o src/exceptions.cc
+
+# a shared object with internal symbols and the executable using it.
+gcc -O0 -fPIC -shared -o libexports.so src/exports.c
+gcc -O0 -o exports_main src/exports_main.c -L. -lexports -ldl
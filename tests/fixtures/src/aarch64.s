@@ -0,0 +1,19 @@
+    .text
+    .globl answer
+    .type answer, %function
+answer:
+    bl external
+    mov w0, #42
+    ret
+    .size answer, .-answer
+
+    .data
+    .globl value
+    .type value, %object
+value:
+    .xword answer
+    .size value, 8
+
+    .bss
+buffer:
+    .zero 64
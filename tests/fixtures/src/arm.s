@@ -0,0 +1,19 @@
+    .text
+    .globl answer
+    .type answer, %function
+answer:
+    bl external
+    mov r0, #42
+    bx lr
+    .size answer, .-answer
+
+    .data
+    .globl value
+    .type value, %object
+value:
+    .long answer
+    .size value, 4
+
+    .bss
+buffer:
+    .zero 64
@@ -0,0 +1,104 @@
+target datalayout = "e-m:e-p:64:64-i64:64-i128:128-n32:64-S128"
+target triple = "bpf"
+
+%struct.anon = type { [2 x i32]*, [16 x i32]*, i32*, i64* }
+%struct.bpf_map_def = type { i32, i32, i32, i32, i32 }
+
+@counts = dso_local global %struct.anon zeroinitializer, section ".maps", align 8, !dbg !0
+@_license = dso_local global [4 x i8] c"GPL\00", section "license", align 1, !dbg !29
+@legacy = dso_local global %struct.bpf_map_def { i32 1, i32 4, i32 8, i32 128, i32 0 }, section "maps", align 4, !dbg !79
+@llvm.compiler.used = appending global [5 x i8*] [i8* bitcast (%struct.anon* @counts to i8*), i8* getelementptr inbounds ([4 x i8], [4 x i8]* @_license, i32 0, i32 0), i8* bitcast (%struct.bpf_map_def* @legacy to i8*), i8* bitcast (i32 (i8*)* @prog to i8*), i8* bitcast (i32 (i8*)* @trace_open to i8*)], section "llvm.metadata"
+
+define dso_local i32 @prog(i8* %ctx) #0 section "xdp" !dbg !40 {
+entry:
+  call void @llvm.dbg.value(metadata i8* %ctx, metadata !45, metadata !DIExpression()), !dbg !46
+  ret i32 2, !dbg !47
+}
+
+define dso_local i32 @trace_open(i8* %ctx) #0 section "kprobe/do_sys_open" !dbg !70 {
+entry:
+  %key = alloca i32, align 4
+  %0 = bitcast i32* %key to i8*
+  store i32 0, i32* %key, align 4, !dbg !74
+  %call = call i8* inttoptr (i64 1 to i8* (i8*, i8*)*)(i8* bitcast (%struct.anon* @counts to i8*), i8* %0), !dbg !75
+  %found = icmp ne i8* %call, null, !dbg !75
+  br i1 %found, label %inc, label %legacy, !dbg !75
+
+inc:
+  %p = bitcast i8* %call to i64*
+  %v = load i64, i64* %p, align 8, !dbg !76
+  %add = add i64 %v, 1, !dbg !76
+  store i64 %add, i64* %p, align 8, !dbg !76
+  br label %legacy, !dbg !76
+
+legacy:
+  %old = call i8* inttoptr (i64 1 to i8* (i8*, i8*)*)(i8* bitcast (%struct.bpf_map_def* @legacy to i8*), i8* %0), !dbg !77
+  ret i32 0, !dbg !78
+}
+
+declare void @llvm.dbg.value(metadata, metadata, metadata)
+
+!llvm.dbg.cu = !{!2}
+!llvm.module.flags = !{!50, !51, !52}
+
+!0 = !DIGlobalVariableExpression(var: !1, expr: !DIExpression())
+!1 = distinct !DIGlobalVariable(name: "counts", scope: !2, file: !3, line: 3, type: !10, isLocal: false, isDefinition: true)
+!2 = distinct !DICompileUnit(language: DW_LANG_C99, file: !3, producer: "clang", isOptimized: true, runtimeVersion: 0, emissionKind: FullDebug, globals: !4)
+!3 = !DIFile(filename: "bpf.c", directory: "/tmp")
+!4 = !{!0, !29, !79}
+!10 = distinct !DICompositeType(tag: DW_TAG_structure_type, file: !3, line: 3, size: 256, elements: !11)
+!11 = !{!12, !17, !20, !24}
+!12 = !DIDerivedType(tag: DW_TAG_member, name: "type", scope: !10, file: !3, line: 4, baseType: !13, size: 64)
+!13 = !DIDerivedType(tag: DW_TAG_pointer_type, baseType: !14, size: 64)
+!14 = !DICompositeType(tag: DW_TAG_array_type, baseType: !15, size: 64, elements: !16)
+!15 = !DIBasicType(name: "int", size: 32, encoding: DW_ATE_signed)
+!16 = !{!60}
+!60 = !DISubrange(count: 2)
+!17 = !DIDerivedType(tag: DW_TAG_member, name: "max_entries", scope: !10, file: !3, line: 5, baseType: !18, size: 64, offset: 64)
+!18 = !DIDerivedType(tag: DW_TAG_pointer_type, baseType: !19, size: 64)
+!19 = !DICompositeType(tag: DW_TAG_array_type, baseType: !15, size: 512, elements: !61)
+!61 = !{!62}
+!62 = !DISubrange(count: 16)
+!20 = !DIDerivedType(tag: DW_TAG_member, name: "key", scope: !10, file: !3, line: 6, baseType: !21, size: 64, offset: 128)
+!21 = !DIDerivedType(tag: DW_TAG_pointer_type, baseType: !22, size: 64)
+!22 = !DIDerivedType(tag: DW_TAG_typedef, name: "__u32", file: !3, line: 1, baseType: !23)
+!23 = !DIBasicType(name: "unsigned int", size: 32, encoding: DW_ATE_unsigned)
+!24 = !DIDerivedType(tag: DW_TAG_member, name: "value", scope: !10, file: !3, line: 7, baseType: !25, size: 64, offset: 192)
+!25 = !DIDerivedType(tag: DW_TAG_pointer_type, baseType: !26, size: 64)
+!26 = !DIDerivedType(tag: DW_TAG_typedef, name: "__u64", file: !3, line: 2, baseType: !27)
+!27 = !DIBasicType(name: "unsigned long long", size: 64, encoding: DW_ATE_unsigned)
+!29 = !DIGlobalVariableExpression(var: !30, expr: !DIExpression())
+!30 = distinct !DIGlobalVariable(name: "_license", scope: !2, file: !3, line: 9, type: !31, isLocal: false, isDefinition: true)
+!31 = !DICompositeType(tag: DW_TAG_array_type, baseType: !32, size: 32, elements: !33)
+!32 = !DIBasicType(name: "char", size: 8, encoding: DW_ATE_signed_char)
+!33 = !{!34}
+!34 = !DISubrange(count: 4)
+!40 = distinct !DISubprogram(name: "prog", scope: !3, file: !3, line: 11, type: !41, scopeLine: 11, flags: DIFlagPrototyped, spFlags: DISPFlagDefinition | DISPFlagOptimized, unit: !2, retainedNodes: !44)
+!41 = !DISubroutineType(types: !42)
+!42 = !{!15, !43}
+!43 = !DIDerivedType(tag: DW_TAG_pointer_type, baseType: null, size: 64)
+!44 = !{!45}
+!45 = !DILocalVariable(name: "ctx", arg: 1, scope: !40, file: !3, line: 11, type: !43)
+!46 = !DILocation(line: 0, scope: !40)
+!47 = !DILocation(line: 12, column: 5, scope: !40)
+!70 = distinct !DISubprogram(name: "trace_open", scope: !3, file: !3, line: 15, type: !41, scopeLine: 15, flags: DIFlagPrototyped, spFlags: DISPFlagDefinition | DISPFlagOptimized, unit: !2, retainedNodes: !71)
+!71 = !{!72}
+!72 = !DILocalVariable(name: "ctx", arg: 1, scope: !70, file: !3, line: 15, type: !43)
+!74 = !DILocation(line: 16, column: 9, scope: !70)
+!75 = !DILocation(line: 17, column: 14, scope: !70)
+!76 = !DILocation(line: 19, column: 12, scope: !70)
+!77 = !DILocation(line: 20, column: 5, scope: !70)
+!78 = !DILocation(line: 21, column: 5, scope: !70)
+!79 = !DIGlobalVariableExpression(var: !80, expr: !DIExpression())
+!80 = distinct !DIGlobalVariable(name: "legacy", scope: !2, file: !3, line: 13, type: !81, isLocal: false, isDefinition: true)
+!81 = distinct !DICompositeType(tag: DW_TAG_structure_type, name: "bpf_map_def", file: !3, line: 13, size: 160, elements: !82)
+!82 = !{!83, !84, !85, !86, !87}
+!83 = !DIDerivedType(tag: DW_TAG_member, name: "type", scope: !81, file: !3, line: 13, baseType: !23, size: 32)
+!84 = !DIDerivedType(tag: DW_TAG_member, name: "key_size", scope: !81, file: !3, line: 13, baseType: !23, size: 32, offset: 32)
+!85 = !DIDerivedType(tag: DW_TAG_member, name: "value_size", scope: !81, file: !3, line: 13, baseType: !23, size: 32, offset: 64)
+!86 = !DIDerivedType(tag: DW_TAG_member, name: "max_entries", scope: !81, file: !3, line: 13, baseType: !23, size: 32, offset: 96)
+!87 = !DIDerivedType(tag: DW_TAG_member, name: "map_flags", scope: !81, file: !3, line: 13, baseType: !23, size: 32, offset: 128)
+!50 = !{i32 7, !"Dwarf Version", i32 5}
+!51 = !{i32 2, !"Debug Info Version", i32 3}
+!52 = !{i32 1, !"wchar_size", i32 4}
+attributes #0 = { nounwind }
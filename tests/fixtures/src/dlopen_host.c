@@ -0,0 +1,19 @@
+#include <dlfcn.h>
+#include <stdio.h>
+
+/* dlopen argv[1] and return the result of argv[2]. */
+int main(int argc, char **argv) {
+    if (argc < 3)
+        return 100;
+    void *handle = dlopen(argv[1], RTLD_NOW);
+    if (!handle) {
+        fprintf(stderr, "%s\n", dlerror());
+        return 101;
+    }
+    int (*f)(void) = (int (*)(void))dlsym(handle, argv[2]);
+    if (!f) {
+        fprintf(stderr, "%s\n", dlerror());
+        return 102;
+    }
+    return f();
+}
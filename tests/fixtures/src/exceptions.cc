@@ -0,0 +1,33 @@
+// Functions with landing pads, for the .gcc_except_table tests.
+#include <stdexcept>
+
+struct Guard {
+    int *counter;
+    ~Guard() { ++*counter; }
+};
+
+__attribute__((noinline)) int risky(int x) {
+    if (x < 0)
+        throw std::invalid_argument("negative");
+    return x * 2;
+}
+
+__attribute__((noinline)) int cleanup(int x, int *counter) {
+    Guard guard{counter};
+    return risky(x);
+}
+
+__attribute__((noinline)) int recover(int x) {
+    try {
+        return risky(x);
+    } catch (const std::invalid_argument &) {
+        return -1;
+    } catch (...) {
+        return -2;
+    }
+}
+
+int main(int argc, char **) {
+    int counter = 0;
+    return recover(-argc) + cleanup(argc, &counter) + counter == 2 ? 0 : 1;
+}
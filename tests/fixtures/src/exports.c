@@ -0,0 +1,7 @@
+int internal_counter = 40;
+
+int internal_helper(int x) { return x + internal_counter; }
+
+int internal_unused(void) { return 0; }
+
+int api(int x) { return internal_helper(x); }
@@ -0,0 +1,10 @@
+#define _GNU_SOURCE
+#include <dlfcn.h>
+
+int api(int x);
+
+int main(void) {
+    if (dlsym(RTLD_DEFAULT, "internal_helper") || dlsym(RTLD_DEFAULT, "internal_counter") || dlsym(RTLD_DEFAULT, "internal_unused"))
+        return 1;
+    return api(2);
+}
@@ -0,0 +1,19 @@
+    .text
+    .globl answer
+    .type answer, @function
+answer:
+    call external
+    movl $42, %eax
+    ret
+    .size answer, .-answer
+
+    .data
+    .globl value
+    .type value, @object
+value:
+    .long answer
+    .size value, 4
+
+    .bss
+buffer:
+    .zero 64
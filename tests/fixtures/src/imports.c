@@ -0,0 +1,9 @@
+#include <stdlib.h>
+#include <string.h>
+
+int main(int argc, char **argv) {
+    if (argc > 1) {
+        return (int)strlen(argv[1]);
+    }
+    return atoi("42");
+}
@@ -0,0 +1,22 @@
+    .text
+    .set noreorder
+    .globl answer
+    .type answer, @function
+answer:
+    jal external
+    nop
+    li $2, 42
+    jr $31
+    nop
+    .size answer, .-answer
+
+    .data
+    .globl value
+    .type value, @object
+value:
+    .8byte answer
+    .size value, 8
+
+    .bss
+buffer:
+    .zero 64
@@ -0,0 +1,22 @@
+    .text
+    .set noreorder
+    .globl answer
+    .type answer, @function
+answer:
+    jal external
+    nop
+    li $2, 42
+    jr $31
+    nop
+    .size answer, .-answer
+
+    .data
+    .globl value
+    .type value, @object
+value:
+    .4byte answer
+    .size value, 4
+
+    .bss
+buffer:
+    .zero 64
@@ -0,0 +1,20 @@
+    .text
+    .globl answer
+    .type answer, @function
+answer:
+    bl external
+    nop
+    li 3, 42
+    blr
+    .size answer, .-answer
+
+    .data
+    .globl value
+    .type value, @object
+value:
+    .quad answer
+    .size value, 8
+
+    .bss
+buffer:
+    .zero 64
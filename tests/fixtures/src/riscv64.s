@@ -0,0 +1,19 @@
+    .text
+    .globl answer
+    .type answer, @function
+answer:
+    call external
+    li a0, 42
+    ret
+    .size answer, .-answer
+
+    .data
+    .globl value
+    .type value, @object
+value:
+    .quad answer
+    .size value, 8
+
+    .bss
+buffer:
+    .zero 64
@@ -0,0 +1,41 @@
+/* USDT probes as <sys/sdt.h> emits them, written out since systemtap-sdt-dev isn't required. */
+
+#define PROBE_NOTE(provider, name, semaphore, args)                           \
+    "990: nop\n"                                                              \
+    ".pushsection .note.stapsdt,\"\",\"note\"\n"                              \
+    ".balign 4\n"                                                             \
+    ".4byte 992f-991f, 994f-993f, 3\n"                                        \
+    "991: .asciz \"stapsdt\"\n"                                               \
+    "992: .balign 4\n"                                                        \
+    "993: .8byte 990b\n"                                                      \
+    ".8byte _.stapsdt.base\n"                                                 \
+    ".8byte " semaphore "\n"                                                  \
+    ".asciz \"" provider "\"\n"                                               \
+    ".asciz \"" name "\"\n"                                                   \
+    ".asciz \"" args "\"\n"                                                   \
+    "994: .balign 4\n"                                                        \
+    ".popsection\n"                                                           \
+    ".ifndef _.stapsdt.base\n"                                                \
+    ".pushsection .stapsdt.base,\"aG\",\"progbits\",.stapsdt.base,comdat\n"   \
+    ".weak _.stapsdt.base\n"                                                  \
+    ".hidden _.stapsdt.base\n"                                                \
+    "_.stapsdt.base: .space 1\n"                                              \
+    ".size _.stapsdt.base, 1\n"                                               \
+    ".popsection\n"                                                           \
+    ".endif\n"
+
+__extension__ unsigned short myapp_request_semaphore
+    __attribute__((unused)) __attribute__((section(".probes")));
+
+int handle(int id, long size) {
+    __asm__ __volatile__(PROBE_NOTE("myapp", "request", "myapp_request_semaphore",
+                                    "-4@%0 8@%1")
+                         :
+                         : "nor"(id), "nor"(size));
+    return id + (int)size;
+}
+
+int main(void) {
+    __asm__ __volatile__(PROBE_NOTE("myapp", "start", "0", ""));
+    return handle(1, 2) == 3 ? 0 : 1;
+}
@@ -1,8 +1,8 @@
 mod tests {
     use elf_utilities::{
-        file, header,
+        builder, dynamic, file, header, layout, parser, relocation,
         section::{self, Contents64},
-        segment, Elf64Half, Elf64Off,
+        segment, symbol, Elf64Half, Elf64Off,
     };
 
     #[test]
@@ -13,10 +13,7 @@ mod tests {
         assert_eq!(".shstrtab", f.sections[1].name);
         assert_eq!(
             header::Ehdr64 {
-                e_ident: [
-                    0x7f, 0x45, 0x4c, 0x46, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-                    0x00, 0x00, 0x00,
-                ],
+                e_ident: header::Ident::default(),
                 e_type: 0,
                 e_machine: 0,
                 e_version: 0,
@@ -57,10 +54,7 @@ mod tests {
 
         assert_eq!(
             header::Ehdr64 {
-                e_ident: [
-                    0x7f, 0x45, 0x4c, 0x46, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-                    0x00, 0x00, 0x00,
-                ],
+                e_ident: header::Ident::default(),
                 e_type: 0,
                 e_machine: 0,
                 e_version: 0,
@@ -95,4 +89,441 @@ mod tests {
         );
         assert!(matches!(f.sections[2].contents, Contents64::StrTab(_)));
     }
+
+    #[test]
+    fn condition_shstrtab_test() {
+        let mut f = file::ELF64::default();
+        f.ehdr.set_class(header::Class::Bit64);
+        f.ehdr.set_data(header::Data::LSB2);
+        for name in [".text", ".rodata", ".text", ".comment"].iter() {
+            f.add_section(section::Section64::new(
+                name.to_string(),
+                section::ShdrPreparation64::default().ty(section::Type::ProgBits),
+                Contents64::Raw(vec![0x90; 4]),
+            ));
+        }
+        // 名前の順序を入れ替えても，sh_nameは書き込み時に再計算される
+        f.sections.swap(1, 4);
+        f.sections[2].name = ".data".into();
+        f.condition();
+
+        let path = std::env::temp_dir().join("elf_utilities_condition_shstrtab");
+        std::fs::write(&path, f.to_le_bytes()).unwrap();
+        let parsed = parser::parse_elf64(path.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let names: Vec<&str> = parsed
+            .sections
+            .iter()
+            .map(|sct| sct.name.as_str())
+            .collect();
+        assert_eq!(
+            vec!["", ".comment", ".data", ".text", ".text", ".shstrtab"],
+            names
+        );
+        assert_eq!(
+            parsed.sections[3].header.sh_name,
+            parsed.sections[4].header.sh_name
+        );
+    }
+
+    #[test]
+    fn move_section_test() {
+        let mut f = builder::SharedObjectWriter::new()
+            .function(builder::ExportedFunction::new("f", vec![0xc3]))
+            .build()
+            .unwrap();
+        let link_name = |f: &file::ELF64, name: &str| {
+            let sct = f.first_section_by(|sct| sct.name == name).unwrap();
+            f.sections[sct.header.sh_link as usize].name.clone()
+        };
+        let text_name = |f: &file::ELF64| {
+            let dynsym = f.first_section_by(|sct| sct.name == ".dynsym").unwrap();
+            match &dynsym.contents {
+                Contents64::Symbols(syms) => f.sections[syms[1].st_shndx as usize].name.clone(),
+                _ => panic!("unexpected contents"),
+            }
+        };
+        assert_eq!(".text", text_name(&f));
+
+        // .textを先頭に，.dynstrを末尾に移動する
+        let text = f.first_shidx_by(|sct| sct.name == ".text").unwrap();
+        f.move_section(text, 1);
+        let dynstr = f.first_shidx_by(|sct| sct.name == ".dynstr").unwrap();
+        f.move_section(dynstr, f.sections.len() - 1);
+
+        assert_eq!(".text", f.sections[1].name);
+        assert_eq!(".dynstr", f.sections.last().unwrap().name);
+        assert_eq!(".shstrtab", f.sections[f.ehdr.e_shstrndx as usize].name);
+        assert_eq!(".dynstr", link_name(&f, ".dynsym"));
+        assert_eq!(".dynstr", link_name(&f, ".dynamic"));
+        assert_eq!(".dynsym", link_name(&f, ".gnu.hash"));
+        assert_eq!(".text", text_name(&f));
+    }
+
+    #[test]
+    fn generate_shared_object_test() {
+        let answer =
+            builder::ExportedFunction::new("answer", vec![0xb8, 0x2a, 0x00, 0x00, 0x00, 0xc3]);
+        let call_answer = builder::ExportedFunction::new(
+            "call_answer",
+            vec![0xe8, 0, 0, 0, 0, 0xc3],
+        )
+        .relocation(1, "answer", relocation::R_X86_64_PLT32, -4);
+        let f = builder::SharedObjectWriter::new()
+            .soname("libanswer.so")
+            .function(answer)
+            .function(call_answer)
+            .build()
+            .unwrap();
+
+        let path = std::env::temp_dir().join("elf_utilities_generate_shared_object.so");
+        std::fs::write(&path, f.to_le_bytes()).unwrap();
+        let parsed = parser::parse_elf64(path.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(header::Type::Dyn, parsed.ehdr.get_type());
+        assert_eq!(f.sections.len(), parsed.sections.len());
+        let dynamic = parsed
+            .first_section_by(|sct| sct.name == ".dynamic")
+            .unwrap();
+        assert!(matches!(
+            &dynamic.contents,
+            Contents64::Dynamics(x) if x.iter().any(|ent| ent.get_type() == dynamic::EntryType::SOName)
+        ));
+
+        // call answerの相対オフセットが解決されているか
+        let text = parsed.first_section_by(|sct| sct.name == ".text").unwrap();
+        if let Contents64::Raw(code) = &text.contents {
+            let rel = i32::from_le_bytes([code[17], code[18], code[19], code[20]]);
+            assert_eq!(-21, rel);
+        } else {
+            panic!("unexpected contents");
+        }
+
+        let loads = parsed
+            .segments
+            .iter()
+            .filter(|sgt| sgt.header.get_type() == segment::Type::Load)
+            .count();
+        assert_eq!(3, loads);
+    }
+
+    #[test]
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    fn dlopen_shared_object_test() {
+        let answer =
+            builder::ExportedFunction::new("answer", vec![0xb8, 0x2a, 0x00, 0x00, 0x00, 0xc3]);
+        // call answer; inc eax; ret
+        let call_answer =
+            builder::ExportedFunction::new("call_answer", vec![0xe8, 0, 0, 0, 0, 0xff, 0xc0, 0xc3])
+                .relocation(1, "answer", relocation::R_X86_64_PLT32, -4);
+        // call *answer@GOTPCREL(%rip); add eax, 2; ret
+        let call_via_got = builder::ExportedFunction::new(
+            "call_via_got",
+            vec![0xff, 0x15, 0, 0, 0, 0, 0x83, 0xc0, 0x02, 0xc3],
+        )
+        .relocation(2, "answer", relocation::R_X86_64_GOTPCREL, -4);
+        let f = builder::SharedObjectWriter::new()
+            .soname("libanswer.so")
+            .function(answer)
+            .function(call_answer)
+            .function(call_via_got)
+            .build()
+            .unwrap();
+
+        // .dynsymのsh_infoは最初の非ローカルシンボルを指す
+        let dynsym = f.dynsym().unwrap();
+        let first_global = dynsym
+            .symbols()
+            .iter()
+            .position(|sym| sym.get_bind() != symbol::Bind::Local)
+            .unwrap();
+        assert_eq!(first_global as u32, dynsym.section().header.sh_info);
+
+        // ホストはCコンパイラがあればソースからビルドする
+        let host =
+            std::env::temp_dir().join(format!("elf_utilities_dlopen_host_{}", std::process::id()));
+        let built = std::process::Command::new("cc")
+            .arg("-o")
+            .arg(&host)
+            .arg(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/tests/fixtures/src/dlopen_host.c"
+            ))
+            .arg("-ldl")
+            .status()
+            .is_ok_and(|status| status.success());
+        if !built {
+            eprintln!("skipped: no C compiler to build the dlopen host");
+            return;
+        }
+
+        let path =
+            std::env::temp_dir().join(format!("elf_utilities_dlopen_{}.so", std::process::id()));
+        std::fs::write(&path, f.to_le_bytes()).unwrap();
+        let call = |name: &str| {
+            std::process::Command::new(&host)
+                .arg(&path)
+                .arg(name)
+                .status()
+                .unwrap()
+                .code()
+        };
+        let codes = (call("answer"), call("call_answer"), call("call_via_got"));
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&host).unwrap();
+
+        assert_eq!((Some(42), Some(43), Some(44)), codes);
+    }
+
+    #[test]
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    fn generate_freestanding_executable_test() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // mov eax, 42; ret
+        let main = builder::ExportedFunction::new("main", vec![0xb8, 0x2a, 0x00, 0x00, 0x00, 0xc3]);
+        let f = builder::ExecutableWriter::new()
+            .function(main)
+            .start_stub(builder::start_stub("main"))
+            .build()
+            .unwrap();
+        assert!(f
+            .segments
+            .iter()
+            .all(|sgt| sgt.header.get_type() != segment::Type::Interp));
+
+        let path = std::env::temp_dir().join("elf_utilities_generate_freestanding");
+        std::fs::write(&path, f.to_le_bytes()).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let status = std::process::Command::new(&path).status().unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(Some(42), status.code());
+    }
+
+    #[test]
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    fn generate_bss_heavy_executable_test() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let main = builder::ExportedFunction::new("main", vec![0xb8, 0x2a, 0x00, 0x00, 0x00, 0xc3]);
+        let mut f = builder::ExecutableWriter::new()
+            .function(main)
+            .start_stub(builder::start_stub("main"))
+            .build()
+            .unwrap();
+
+        // 16MiBの.bssを末尾に追加して再配置する
+        let mut bss = section::Section64::new(
+            ".bss".to_string(),
+            section::ShdrPreparation64::default()
+                .ty(section::Type::NoBits)
+                .flags([section::Flag::Alloc, section::Flag::Write].iter()),
+            Contents64::Raw(Vec::new()),
+        );
+        bss.header.sh_size = 0x1000000;
+        bss.header.sh_addralign = 0x20;
+        f.sections.push(bss);
+        f.ehdr.e_shnum += 1;
+        let text_addr = |f: &file::ELF64| {
+            f.first_section_by(|sct| sct.name == ".text")
+                .unwrap()
+                .header
+                .sh_addr
+        };
+        let old_text_addr = text_addr(&f);
+        layout::Layout::from_elf(&f).apply(&mut f).unwrap();
+        // PHTが伸びるので.textも移動する
+        f.ehdr.e_entry = f.ehdr.e_entry - old_text_addr + text_addr(&f);
+
+        // .bssはファイル上の領域を持たない
+        let bss = f.sections.last().unwrap().header;
+        assert_eq!(0x1000000, bss.sh_size);
+        assert!(bss.sh_offset <= f.ehdr.e_shoff);
+        let bytes = f.to_le_bytes();
+        assert!(bytes.len() < 0x10000);
+
+        let path = std::env::temp_dir().join("elf_utilities_generate_bss_heavy");
+        std::fs::write(&path, bytes).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let status = std::process::Command::new(&path).status().unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(Some(42), status.code());
+    }
+
+    #[test]
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    fn convert_to_pie_test() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let main = builder::ExportedFunction::new("main", vec![0xb8, 0x2a, 0x00, 0x00, 0x00, 0xc3]);
+        let mut f = builder::ExecutableWriter::new()
+            .function(main)
+            .start_stub(builder::start_stub("main"))
+            .build()
+            .unwrap();
+        f.convert_to_pie().unwrap();
+        assert_eq!(header::Type::Dyn, f.ehdr.get_type());
+
+        // カーネルがランダムなアドレスに読み込んでも動作する
+        let path = std::env::temp_dir().join("elf_utilities_convert_to_pie");
+        std::fs::write(&path, f.to_le_bytes()).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let status = std::process::Command::new(&path).status().unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(Some(42), status.code());
+    }
+
+    #[test]
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    fn generate_tiny_executable_test() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let main = builder::ExportedFunction::new("main", vec![0xb8, 0x2a, 0x00, 0x00, 0x00, 0xc3]);
+        let f = builder::ExecutableWriter::new()
+            .function(main)
+            .start_stub(builder::start_stub("main"))
+            .tiny(true)
+            .build()
+            .unwrap();
+        // SHTを持たないので，e_shnumはモデル上0
+        assert_eq!((0, 0), (f.ehdr.e_shoff, f.ehdr.e_shnum));
+        let bytes = f.to_le_bytes();
+        assert!(bytes.len() < 200);
+
+        // ファイル上のe_shnumはp_flagsと共有されるが，e_shoffが0なので無視される
+        let parsed =
+            parser::parse_elf64_buf_with_options("tiny", &bytes, &Default::default()).unwrap();
+        assert_eq!(5, parsed.ehdr.e_shnum);
+        assert!(parsed.sections.is_empty());
+        assert_eq!(f.segments, parsed.segments);
+
+        let path = std::env::temp_dir().join("elf_utilities_generate_tiny");
+        std::fs::write(&path, bytes).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let status = std::process::Command::new(&path).status().unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(Some(42), status.code());
+    }
+
+    #[test]
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    fn generate_interposer_test() {
+        let f = builder::generate_interposer(&[("atoi", None), ("strlen", Some("GLIBC_2.2.5"))])
+            .unwrap();
+        let path = std::env::temp_dir().join(format!(
+            "elf_utilities_interposer_{}.so",
+            std::process::id()
+        ));
+        std::fs::write(&path, f.to_le_bytes()).unwrap();
+
+        let imports = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/imports");
+        let run = |args: &[&str]| {
+            std::process::Command::new(imports)
+                .args(args)
+                .env("LD_PRELOAD", &path)
+                .env("LD_DEBUG", "bindings")
+                .output()
+                .unwrap()
+        };
+        let no_args = run(&[]);
+        let with_arg = run(&["abcd"]);
+        std::fs::remove_file(&path).unwrap();
+
+        // スタブを経由して本来の関数が呼ばれる
+        assert_eq!(Some(42), no_args.status.code());
+        assert_eq!(Some(4), with_arg.status.code());
+        let bindings = String::from_utf8_lossy(&with_arg.stderr);
+        let shim = path.to_str().unwrap();
+        assert!(bindings
+            .lines()
+            .any(|l| l.contains(shim) && l.contains("`strlen'")));
+    }
+
+    #[test]
+    fn generate_object32_test() {
+        // 各アーキテクチャで外部関数を呼び出して返る
+        let presets = [
+            (
+                builder::Arch32::I386,
+                vec![0xe8, 0, 0, 0, 0, 0xc3],
+                1,
+                relocation::R_386_PC32,
+                -4,
+            ),
+            (
+                builder::Arch32::Arm,
+                vec![0xfe, 0xff, 0xff, 0xeb, 0x1e, 0xff, 0x2f, 0xe1],
+                0,
+                relocation::R_ARM_CALL,
+                0,
+            ),
+            (
+                builder::Arch32::RiscV32,
+                vec![0x97, 0, 0, 0, 0xe7, 0x80, 0, 0, 0x67, 0x80, 0, 0],
+                0,
+                relocation::R_RISCV_CALL_PLT,
+                0,
+            ),
+        ];
+
+        for (arch, code, offset, ty, addend) in presets.iter() {
+            let main = builder::ExportedFunction::new("main", code.clone())
+                .relocation(*offset, "answer", *ty, *addend);
+            let f = builder::ObjectWriter32::new(*arch)
+                .function(main)
+                .data("value", vec![0x2a, 0, 0, 0])
+                .build()
+                .unwrap();
+
+            let path = std::env::temp_dir().join(format!("elf_utilities_object32_{:?}.o", arch));
+            std::fs::write(&path, f.to_le_bytes()).unwrap();
+            let parsed = parser::parse_elf32(path.to_str().unwrap()).unwrap();
+            std::fs::remove_file(&path).unwrap();
+
+            assert_eq!(header::Class::Bit32, parsed.ehdr.get_class(), "{:?}", arch);
+            assert_eq!(arch.machine(), parsed.ehdr.get_machine(), "{:?}", arch);
+            assert_eq!(arch.flags(), parsed.ehdr.e_flags, "{:?}", arch);
+            assert_eq!(f.sections, parsed.sections, "{:?}", arch);
+
+            let (rel_name, rel_ty) = if arch.uses_rela() {
+                (".rela.text", section::Type::Rela)
+            } else {
+                (".rel.text", section::Type::Rel)
+            };
+            let rel = parsed
+                .sections
+                .iter()
+                .find(|sct| sct.name == rel_name)
+                .unwrap();
+            assert_eq!(rel_ty, rel.header.get_type(), "{:?}", arch);
+            assert_eq!(rel.header.sh_entsize, rel.header.sh_size, "{:?}", arch);
+
+            let symtab = parsed
+                .sections
+                .iter()
+                .find(|sct| sct.name == ".symtab")
+                .unwrap();
+            match &symtab.contents {
+                section::Contents32::Symbols(syms) => {
+                    let names: Vec<(&str, u16)> = syms
+                        .iter()
+                        .map(|sym| (sym.symbol_name.as_str(), sym.st_shndx))
+                        .collect();
+                    assert_eq!(
+                        vec![("", 0), ("main", 1), ("value", 2), ("answer", 0)],
+                        names,
+                        "{:?}",
+                        arch
+                    );
+                }
+                _ => panic!("unexpected contents"),
+            }
+        }
+    }
 }
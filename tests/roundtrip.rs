@@ -0,0 +1,102 @@
+#![cfg(feature = "arbitrary")]
+
+mod tests {
+    use elf_utilities::{dynamic, file, header, parser, relocation, section, segment, symbol};
+    use proptest::prelude::*;
+
+    /// write the file to a unique temporary path.
+    fn write_temp(bytes: &[u8]) -> std::path::PathBuf {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        let path = std::env::temp_dir().join(format!(
+            "elf_utilities_roundtrip_{}_{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::SeqCst)
+        ));
+        std::fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    proptest! {
+        #[test]
+        fn ehdr_roundtrip(ehdr in any::<header::Ehdr64>()) {
+            let bytes = ehdr.to_le_bytes();
+            prop_assert_eq!(header::Ehdr64::SIZE as usize, bytes.len());
+            prop_assert_eq!(ehdr, header::Ehdr64::deserialize(&bytes, 0).unwrap());
+        }
+
+        #[test]
+        fn ehdr32_roundtrip(ehdr in any::<header::Ehdr32>()) {
+            let bytes = ehdr.to_le_bytes();
+            prop_assert_eq!(header::Ehdr32::SIZE as usize, bytes.len());
+            prop_assert_eq!(ehdr, header::Ehdr32::deserialize(&bytes, 0).unwrap());
+        }
+
+        #[test]
+        fn shdr_roundtrip(shdr64 in any::<section::Shdr64>(), shdr32 in any::<section::Shdr32>()) {
+            let bytes = shdr64.to_le_bytes();
+            prop_assert_eq!(section::Shdr64::SIZE, bytes.len());
+            prop_assert_eq!(shdr64, bincode::deserialize::<section::Shdr64>(&bytes).unwrap());
+            let bytes = shdr32.to_le_bytes();
+            prop_assert_eq!(section::Shdr32::SIZE, bytes.len());
+            prop_assert_eq!(shdr32, bincode::deserialize::<section::Shdr32>(&bytes).unwrap());
+        }
+
+        #[test]
+        fn phdr_roundtrip(phdr64 in any::<segment::Phdr64>(), phdr32 in any::<segment::Phdr32>()) {
+            let bytes = phdr64.to_le_bytes();
+            prop_assert_eq!(segment::Phdr64::SIZE, bytes.len());
+            prop_assert_eq!(phdr64, segment::Phdr64::deserialize(&bytes, 0).unwrap());
+            let bytes = phdr32.to_le_bytes();
+            prop_assert_eq!(segment::Phdr32::SIZE, bytes.len());
+            prop_assert_eq!(phdr32, segment::Phdr32::deserialize(&bytes, 0).unwrap());
+        }
+
+        #[test]
+        fn symbol_roundtrip(sym64 in any::<symbol::Symbol64>(), sym32 in any::<symbol::Symbol32>()) {
+            let bytes = sym64.to_le_bytes();
+            prop_assert_eq!(symbol::Symbol64::SIZE, bytes.len());
+            prop_assert_eq!(sym64, symbol::Symbol64::deserialize(&bytes, 0).unwrap());
+            let bytes = sym32.to_le_bytes();
+            prop_assert_eq!(symbol::Symbol32::SIZE, bytes.len());
+            prop_assert_eq!(sym32, symbol::Symbol32::deserialize(&bytes, 0).unwrap());
+        }
+
+        #[test]
+        fn rela_roundtrip(rela64 in any::<relocation::Rela64>(), rela32 in any::<relocation::Rela32>()) {
+            let bytes = rela64.to_le_bytes();
+            prop_assert_eq!(relocation::Rela64::SIZE as usize, bytes.len());
+            prop_assert_eq!(rela64, relocation::Rela64::deserialize(&bytes, 0).unwrap());
+            let bytes = rela32.to_le_bytes();
+            prop_assert_eq!(relocation::Rela32::SIZE as usize, bytes.len());
+            prop_assert_eq!(rela32, relocation::Rela32::deserialize(&bytes, 0).unwrap());
+        }
+
+        #[test]
+        fn dyn_roundtrip(dyn64 in any::<dynamic::Dyn64>(), dyn32 in any::<dynamic::Dyn32>()) {
+            let bytes = dyn64.to_le_bytes();
+            prop_assert_eq!(dynamic::Dyn64::SIZE, bytes.len());
+            prop_assert_eq!(dyn64, dynamic::Dyn64::deserialize(&bytes, 0).unwrap());
+            let bytes = dyn32.to_le_bytes();
+            prop_assert_eq!(dynamic::Dyn32::SIZE, bytes.len());
+            prop_assert_eq!(dyn32, dynamic::Dyn32::deserialize(&bytes, 0).unwrap());
+        }
+
+        #[test]
+        fn elf64_roundtrip(elf in any::<file::ELF64>()) {
+            let path = write_temp(&elf.to_le_bytes());
+            let parsed = parser::parse_elf64(path.to_str().unwrap()).unwrap();
+            std::fs::remove_file(&path).unwrap();
+            prop_assert_eq!(elf, parsed);
+        }
+
+        #[test]
+        fn elf32_roundtrip(elf in any::<file::ELF32>()) {
+            let path = write_temp(&elf.to_le_bytes());
+            let parsed = parser::parse_elf32(path.to_str().unwrap()).unwrap();
+            std::fs::remove_file(&path).unwrap();
+            prop_assert_eq!(elf, parsed);
+        }
+    }
+}
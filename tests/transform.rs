@@ -0,0 +1,433 @@
+#![cfg(all(target_os = "linux", target_arch = "x86_64"))]
+
+mod tests {
+    use std::process::{Command, Output};
+
+    use elf_utilities::{abi, analysis, builder, dynamic, file, parser, section, transform};
+
+    fn imports() -> file::ELF64 {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/imports");
+        parser::parse_elf64(path).unwrap()
+    }
+
+    /// write the file as an executable and run it.
+    fn execute(elf: &file::ELF64, name: &str, args: &[&str]) -> Output {
+        use std::os::unix::fs::PermissionsExt;
+
+        assert_eq!(Ok(()), elf.validate());
+        let path = std::env::temp_dir().join(format!("{}_{}", name, std::process::id()));
+        std::fs::write(&path, elf.to_le_bytes()).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let output = Command::new(&path).args(args).output().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        output
+    }
+
+    fn run(elf: &file::ELF64, name: &str, args: &[&str]) -> Option<i32> {
+        execute(elf, name, args).status.code()
+    }
+
+    #[test]
+    fn redirect_existing_import_test() {
+        let mut elf = imports();
+        assert_eq!(Some(42), run(&elf, "elf_utilities_imports", &[]));
+
+        // atoi("42") -> strlen("42")
+        assert_eq!(
+            1,
+            transform::redirect_import(&mut elf, "atoi", "strlen").unwrap()
+        );
+        assert_eq!(Some(2), run(&elf, "elf_utilities_redirect_existing", &[]));
+    }
+
+    #[test]
+    fn redirect_new_import_test() {
+        let mut elf = imports();
+        let segments = elf.segments.len();
+
+        // strlen("7") -> atol("7")
+        assert_eq!(
+            1,
+            transform::redirect_import(&mut elf, "strlen", "atol").unwrap()
+        );
+        assert_eq!(segments + 1, elf.segments.len());
+        assert_eq!(Some(7), run(&elf, "elf_utilities_redirect_new", &["7"]));
+        assert_eq!(Some(42), run(&elf, "elf_utilities_redirect_new", &[]));
+
+        assert!(transform::redirect_import(&mut elf, "printf", "puts").is_err());
+    }
+
+    #[test]
+    fn inject_constructor_test() {
+        let mut elf = imports();
+        transform::redirect_import(&mut elf, "strlen", "atol").unwrap();
+
+        // write(1, "hi\n", 3); ret
+        let mut code = vec![
+            0xb8, 0x01, 0x00, 0x00, 0x00, 0xbf, 0x01, 0x00, 0x00, 0x00, 0x48, 0x8d, 0x35, 0x08,
+            0x00, 0x00, 0x00, 0xba, 0x03, 0x00, 0x00, 0x00, 0x0f, 0x05, 0xc3,
+        ];
+        code.extend_from_slice(b"hi\n");
+        let addr = transform::inject_constructor(&mut elf, &code, &["libm.so.6"]).unwrap();
+        let text = elf
+            .first_section_by(|sct| sct.name == transform::INJECTED_TEXT)
+            .unwrap();
+        assert_eq!(addr, text.header.sh_addr);
+
+        let dynamic = elf
+            .first_section_by(|sct| sct.header.get_type() == section::Type::Dynamic)
+            .unwrap();
+        let needed = match &dynamic.contents {
+            section::Contents64::Dynamics(entries) => entries
+                .iter()
+                .filter(|ent| ent.get_type() == dynamic::EntryType::Needed)
+                .count(),
+            _ => unreachable!(),
+        };
+        assert_eq!(2, needed);
+
+        let output = execute(&elf, "elf_utilities_constructor", &["7"]);
+        assert_eq!(b"hi\n", &output.stdout[..]);
+        assert_eq!(Some(7), output.status.code());
+    }
+
+    #[test]
+    fn hide_symbols_test() {
+        let fixtures = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+        let main = format!("{}/exports_main", fixtures);
+        let run_with = |lib: &str| {
+            Command::new(&main)
+                .env("LD_LIBRARY_PATH", lib)
+                .status()
+                .unwrap()
+                .code()
+        };
+        assert_eq!(Some(1), run_with(fixtures));
+
+        let mut elf = parser::parse_elf64(&format!("{}/libexports.so", fixtures)).unwrap();
+        let changed = elf.hide_symbols("internal_*", true);
+        assert_eq!(
+            vec!["internal_counter", "internal_helper", "internal_unused"],
+            changed.hidden
+        );
+        // 他の二つはリロケーションから参照されている
+        assert_eq!(vec!["internal_unused"], changed.removed);
+        assert_eq!(Ok(()), elf.validate());
+
+        let dir = std::env::temp_dir().join(format!("elf_utilities_hide_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("libexports.so"), elf.to_le_bytes()).unwrap();
+        let code = run_with(dir.to_str().unwrap());
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(Some(42), code);
+    }
+
+    #[test]
+    fn retain_exports_test() {
+        let fixtures = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+        let mut elf = parser::parse_elf64(&format!("{}/libexports.so", fixtures)).unwrap();
+        let changed = transform::retain_exports(&mut elf, &["api"]).unwrap();
+        assert_eq!(vec!["internal_unused"], changed.removed);
+        assert_eq!(Ok(()), elf.validate());
+
+        let dir = std::env::temp_dir().join(format!("elf_utilities_retain_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("libexports.so"), elf.to_le_bytes()).unwrap();
+        let code = Command::new(format!("{}/exports_main", fixtures))
+            .env("LD_LIBRARY_PATH", &dir)
+            .status()
+            .unwrap()
+            .code();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(Some(42), code);
+
+        let mut rel = file::ELF64::default();
+        assert!(matches!(
+            transform::retain_exports(&mut rel, &["*"]),
+            Err(transform::TransformError::NotLinked)
+        ));
+    }
+
+    #[test]
+    fn apply_version_script_test() {
+        let fixtures = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+        let mut elf = parser::parse_elf64(&format!("{}/libexports.so", fixtures)).unwrap();
+        let script =
+            transform::VersionScript::parse("EXPORTS_1.0 { global: api; local: *; };").unwrap();
+        transform::apply_version_script(&mut elf, &script).unwrap();
+        assert_eq!(Ok(()), elf.validate());
+        assert!(matches!(
+            transform::apply_version_script(&mut elf, &script),
+            Err(transform::TransformError::AlreadyVersioned)
+        ));
+
+        let dir =
+            std::env::temp_dir().join(format!("elf_utilities_version_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("libexports.so"), elf.to_le_bytes()).unwrap();
+        let code = Command::new(format!("{}/exports_main", fixtures))
+            .env("LD_LIBRARY_PATH", &dir)
+            .status()
+            .unwrap()
+            .code();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(Some(42), code);
+
+        // 既存のGLIBC_*への参照はバージョン番号がずれる
+        let mut elf = imports();
+        let script = transform::VersionScript::parse("IMPORTS_1 { global: *; };").unwrap();
+        transform::apply_version_script(&mut elf, &script).unwrap();
+        assert_eq!(Some(42), run(&elf, "elf_utilities_version_script", &[]));
+
+        let raw = |name: &str| match &elf.first_section_by(|s| s.name == name).unwrap().contents {
+            section::Contents64::Raw(bytes) => bytes.clone(),
+            _ => unreachable!(),
+        };
+        let half = |b: &[u8], at: usize| u16::from_le_bytes([b[at], b[at + 1]]);
+        let word =
+            |b: &[u8], at: usize| u32::from_le_bytes([b[at], b[at + 1], b[at + 2], b[at + 3]]);
+        let verneed = raw(".gnu.version_r");
+        let mut needed = Vec::new();
+        let mut vn = 0;
+        loop {
+            let mut vna = vn + word(&verneed, vn + 8) as usize;
+            for _ in 0..half(&verneed, vn + 2) {
+                needed.push(half(&verneed, vna + 6));
+                vna += word(&verneed, vna + 12) as usize;
+            }
+            match word(&verneed, vn + 12) {
+                0 => break,
+                next => vn += next as usize,
+            }
+        }
+        // 0と1は予約済み，2はIMPORTS_1
+        assert!(needed.iter().all(|v| *v > 2));
+        let versym = raw(".gnu.version");
+        let dynsym = elf.first_section_by(|s| s.name == ".dynsym").unwrap();
+        if let section::Contents64::Symbols(syms) = &dynsym.contents {
+            let atoi = syms
+                .iter()
+                .position(|sym| sym.symbol_name == "atoi")
+                .unwrap();
+            assert!(needed.contains(&(half(&versym, atoi * 2) & 0x7fff)));
+        }
+    }
+
+    #[test]
+    fn make_relocatable_bundle_test() {
+        let fixtures = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+        let dir = std::env::temp_dir().join(format!("elf_utilities_bundle_{}", std::process::id()));
+        let written = transform::make_relocatable_bundle(
+            &format!("{}/exports_main", fixtures),
+            &[&format!("{}/libexports.so", fixtures)],
+            dir.to_str().unwrap(),
+            &transform::BundleOptions::new().rename_sonames("bundled"),
+        )
+        .unwrap();
+        assert_eq!(dir.join("lib/libexports-bundled.so"), written[1]);
+
+        // LD_LIBRARY_PATH無しで同梱したライブラリが読み込まれる
+        // (internal_*を公開したままなので1を返し，読み込めなければld.soが127で終わる)
+        let code = Command::new(&written[0]).status().unwrap().code();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(Some(1), code);
+
+        // バージョン要求の参照先も改名される
+        let mut elf = imports();
+        let renames = [("libc.so.6".to_string(), "libc-bundled.so.6".to_string())];
+        transform::relink_dependencies(&mut elf, None, &renames).unwrap();
+        assert_eq!(Ok(()), elf.validate());
+        assert_eq!(vec!["libc-bundled.so.6".to_string()], elf.needed());
+        assert_eq!("libc-bundled.so.6", elf.version_needs().unwrap()[0].file);
+    }
+
+    #[test]
+    fn soname_and_version_test() {
+        let fixtures = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+        let mut elf = parser::parse_elf64(&format!("{}/libexports.so", fixtures)).unwrap();
+        let script =
+            transform::VersionScript::parse("EXPORTS_1.0 { global: api; local: *; };").unwrap();
+        transform::apply_version_script(&mut elf, &script).unwrap();
+        assert!(matches!(
+            elf.bump_default_version("EXPORTS_1.0"),
+            Err(transform::TransformError::VersionExists { .. })
+        ));
+
+        elf.set_soname("libexports.so").unwrap();
+        elf.bump_default_version("EXPORTS_2.0").unwrap();
+        assert_eq!(Ok(()), elf.validate());
+        assert_eq!(Some("libexports.so".to_string()), elf.soname());
+        let defs = elf.version_definitions().unwrap();
+        assert_eq!("libexports.so", defs[0].name);
+        assert!(defs[0].is_base());
+        let snapshot = abi::export_snapshot(&elf);
+        let api = snapshot.symbols.iter().find(|s| s.name == "api").unwrap();
+        assert_eq!(Some("EXPORTS_2.0".to_string()), api.version);
+
+        // バージョン無しの参照は新しい既定のバージョンに結び付く
+        let dir = std::env::temp_dir().join(format!("elf_utilities_soname_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("libexports.so"), elf.to_le_bytes()).unwrap();
+        let code = Command::new(format!("{}/exports_main", fixtures))
+            .env("LD_LIBRARY_PATH", &dir)
+            .status()
+            .unwrap()
+            .code();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(Some(42), code);
+    }
+
+    #[test]
+    fn set_got_entry_test() {
+        let mut elf = imports();
+        let entries = analysis::got_entries(&elf);
+        let dynamic = elf
+            .first_section_by(|sct| sct.header.get_type() == section::Type::Dynamic)
+            .unwrap()
+            .header
+            .sh_addr;
+        // .got.plt[0] は_DYNAMIC
+        let first = entries
+            .iter()
+            .find(|ent| ent.section == ".got.plt")
+            .unwrap();
+        assert!(first.reserved);
+        assert_eq!(analysis::GotValue::Constant(dynamic), first.value);
+        let strlen = entries
+            .iter()
+            .find(|ent| {
+                matches!(&ent.value, analysis::GotValue::Symbol { name, plt: true, .. } if name == "strlen")
+            })
+            .unwrap();
+        assert!(!strlen.reserved);
+
+        assert_eq!(
+            Err(analysis::GotError::PltSlot {
+                symbol: "atoi".to_string()
+            }),
+            elf.set_got_entry("atoi", 0x1000)
+        );
+        assert_eq!(Ok(1), elf.set_got_entry("__gmon_start__", 0));
+        let gmon = analysis::got_entries(&elf)
+            .into_iter()
+            .find(|ent| ent.addr == 0x3fd0)
+            .unwrap();
+        assert_eq!(analysis::GotValue::Constant(0), gmon.value);
+        assert_eq!(Some(42), run(&elf, "elf_utilities_set_got_entry", &[]));
+    }
+
+    #[test]
+    fn resize_section_test() {
+        let mut elf = imports();
+        let comment = elf.first_shidx_by(|sct| sct.name == ".comment").unwrap();
+        elf.resize_section(comment, 0x1800, 0).unwrap();
+        let bss = elf.first_shidx_by(|sct| sct.name == ".bss").unwrap();
+        let memsz = |elf: &file::ELF64| {
+            elf.segments
+                .iter()
+                .map(|sgt| sgt.header.p_memsz)
+                .max()
+                .unwrap()
+        };
+        let before = memsz(&elf);
+        elf.resize_section(bss, 0x10000, 0).unwrap();
+        assert!(memsz(&elf) >= 0x10000 && memsz(&elf) > before);
+        assert_eq!(Some(42), run(&elf, "elf_utilities_resize_section", &[]));
+
+        // .dataの後ろには.bssが続く
+        let data = elf.first_shidx_by(|sct| sct.name == ".data").unwrap();
+        let size = elf.sections[data].header.sh_size;
+        assert!(matches!(
+            elf.resize_section(data, size + 0x100, 0),
+            Err(section::ResizeError::NoRoom { .. })
+        ));
+    }
+
+    #[test]
+    fn gc_sections_exceptions_test() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/exceptions.o");
+        let mut elf = parser::parse_elf64(path).unwrap();
+        let eh_relocations = |elf: &file::ELF64| match &elf
+            .first_section_by(|sct| sct.name == ".rela.eh_frame")
+            .unwrap()
+            .contents
+        {
+            section::Contents64::RelaSymbols(relas) => relas.len(),
+            _ => 0,
+        };
+        let before = eh_relocations(&elf);
+        elf.gc_sections(&["main"]).unwrap();
+        // 関数はすべて.textにあるので，FDEは一つも消えない
+        assert_eq!(before, eh_relocations(&elf));
+        // 例外表とパーソナリティはFDEから参照されるので残る
+        for name in [".gcc_except_table", ".eh_frame"].iter() {
+            assert!(
+                elf.first_section_by(|sct| sct.name == *name).is_some(),
+                "{}",
+                name
+            );
+        }
+
+        let dir = std::env::temp_dir();
+        let obj = dir.join(format!("elf_utilities_gc_{}.o", std::process::id()));
+        let exe = dir.join(format!("elf_utilities_gc_{}", std::process::id()));
+        std::fs::write(&obj, elf.to_le_bytes()).unwrap();
+        let linked = Command::new("c++")
+            .arg(&obj)
+            .arg("-o")
+            .arg(&exe)
+            .output()
+            .unwrap();
+        std::fs::remove_file(&obj).unwrap();
+        assert!(
+            linked.status.success(),
+            "{}",
+            String::from_utf8_lossy(&linked.stderr)
+        );
+        let status = Command::new(&exe).status().unwrap();
+        std::fs::remove_file(&exe).unwrap();
+        assert_eq!(Some(0), status.code());
+    }
+
+    #[test]
+    fn insert_detour_test() {
+        // main: push rbp; mov rbp, rsp; mov eax, 42; pop rbp; ret
+        let main = vec![
+            0x55, 0x48, 0x89, 0xe5, 0xb8, 0x2a, 0x00, 0x00, 0x00, 0x5d, 0xc3,
+        ];
+        // hook: call trampoline; add eax, 1; ret
+        let hook = vec![0xe8, 0x00, 0x00, 0x00, 0x00, 0x83, 0xc0, 0x01, 0xc3];
+        let mut elf = builder::ExecutableWriter::new()
+            .function(builder::ExportedFunction::new("main", main))
+            .function(builder::ExportedFunction::new("hook", hook))
+            .function(builder::ExportedFunction::new("tiny", vec![0xc3]))
+            .start_stub(builder::start_stub("main"))
+            .build()
+            .unwrap();
+        let symtab = elf.symtab().unwrap();
+        let addr = |name: &str| symtab.find_defined(name).unwrap().st_value;
+        let (main, hook, tiny) = (addr("main"), addr("hook"), addr("tiny"));
+        assert_eq!(Some(42), run(&elf, "elf_utilities_detour_orig", &[]));
+
+        // jmpが収まらない関数は書き換えない
+        let before = elf.clone();
+        assert!(matches!(
+            transform::insert_detour(&mut elf, tiny, main),
+            Err(transform::TransformError::FunctionTooSmall { .. })
+        ));
+        assert_eq!(before, elf);
+
+        let detour = transform::insert_detour(&mut elf, main, hook).unwrap();
+        // フックからトランポリンを呼び，元のmainの結果に1を足す
+        let text = elf.first_mut_section_by(|sct| sct.name == ".text").unwrap();
+        let call = (hook - text.header.sh_addr) as usize;
+        let rel = detour.trampoline.wrapping_sub(hook + 5) as i32;
+        match &mut text.contents {
+            section::Contents64::Raw(bytes) => {
+                bytes[call + 1..call + 5].copy_from_slice(&rel.to_le_bytes())
+            }
+            _ => unreachable!(),
+        }
+        assert_eq!(Some(43), run(&elf, "elf_utilities_detour", &[]));
+    }
+}